@@ -0,0 +1,12 @@
+/*!
+Parsing must never panic, whatever the input, since the highlighter parses every line as it is
+being typed. Run with cargo fuzz run parse_str.
+*/
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(s) = std::str::from_utf8(data) {
+        let _ = crush::lang::parser::parse_str(s);
+    }
+});
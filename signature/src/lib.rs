@@ -13,6 +13,8 @@ struct TypeData {
     unnamed_mutate: Option<TokenStream>,
     assign: TokenStream,
     signature: String,
+    /** The type of the argument, as described in the signature, e.g. file or integer. */
+    value_type: String,
 }
 
 type SignatureResult<T> = Result<T, TokenStream>;
@@ -65,6 +67,7 @@ fn extract_type(ty: &Type) -> SignatureResult<(&'static str, Vec<&'static str>)>
                         "OrderedStringMap" => "OrderedStringMap",
                         "Command" => "Command",
                         "Duration" => "Duration",
+                        "Time" => "Time",
                         "Field" => "Field",
                         "Value" => "Value",
                         "Stream" => "Stream",
@@ -155,6 +158,8 @@ fn simple_type_to_value(simple_type: &str) -> TokenStream {
         "char" => quote!{crate::lang::value::Value::String(value)},
         "Command" => quote!{crate::lang::value::Value::Command(value)},
         "Duration" => quote!{crate::lang::value::Value::Duration(value)},
+        "Time" => quote!{crate::lang::value::Value::Time(value)},
+        "PathBuf" => quote!{crate::lang::value::Value::File(value)},
         "Field" => quote!{crate::lang::value::Value::Field(value)},
         "Stream" => quote!{value},
         "Value" => quote!{value},
@@ -175,6 +180,8 @@ fn simple_type_to_value_description(simple_type: &str) -> &str {
         "char" => "string",
         "Command" => "command",
         "Duration" => "duration",
+        "Time" => "time",
+        "PathBuf" => "file",
         "Field" => "field",
         "Value" => "any value",
         "Stream" => "stream",
@@ -230,6 +237,18 @@ fn simple_type_to_mutator(
     }
 }
 
+/**
+Duration and time arguments can also be passed as strings, which are parsed using the syntax
+of the corresponding literals.
+*/
+fn simple_type_string_parser(simple_type: &str) -> Option<TokenStream> {
+    match simple_type {
+        "Duration" => Some(quote! {crate::util::time::parse_duration}),
+        "Time" => Some(quote! {crate::util::time::parse_time}),
+        _ => None,
+    }
+}
+
 fn simple_type_dump_list(simple_type: &str) -> &str {
     match simple_type {
         "String" => "dump_string",
@@ -242,6 +261,11 @@ fn simple_type_dump_list(simple_type: &str) -> &str {
     }
 }
 
+/** The name of the argument a field maps to, so that e.g. a field named r#type is given as type. */
+fn argument_name(name: &Ident) -> String {
+    let name = name.to_string();
+    name.strip_prefix("r#").unwrap_or(&name).to_string()
+}
 
 fn type_to_value(
     ty: &Type,
@@ -250,28 +274,44 @@ fn type_to_value(
     is_unnamed_target: bool,
     allowed_values: Option<Vec<Literal>>,
 ) -> SignatureResult<TypeData> {
-    let name_literal = proc_macro2::Literal::string(&name.to_string());
+    let name_literal = proc_macro2::Literal::string(&argument_name(name));
 
     let allowed_values_name =
-        allowed_values.as_ref().map(|_| Ident::new(&format!("{}_allowed_values", name.to_string()), ty.span()));
+        allowed_values.as_ref().map(|_| Ident::new(&format!("{}_allowed_values", argument_name(name)), ty.span()));
 
     let (type_name, args) = extract_type(ty)?;
     match type_name {
-        "i128" | "bool" | "String" | "char" | "ValueType" | "f64" | "Command" | "Duration" | "Field" | "Value" | "usize" | "i64" | "u64" | "Stream" => {
+        "i128" | "bool" | "String" | "char" | "ValueType" | "f64" | "Command" | "Duration" | "Time" | "PathBuf" | "Field" | "Value" | "usize" | "i64" | "u64" | "Stream" => {
             if !args.is_empty() {
                 fail!(ty.span(), "This type can't be paramterizised")
             } else {
                 let native_type = Ident::new(type_name, ty.span());
                 let mutator = simple_type_to_mutator(type_name, &allowed_values_name);
                 let value_type = simple_type_to_value(type_name);
+                let parser = simple_type_string_parser(type_name);
+                let string_mapping = parser.as_ref().map(|parser| quote! {
+                    (Some(#name_literal), crate::lang::value::Value::String(value)) => #name = Some(#parser(&value)?),
+                });
+                let string_unnamed = parser.as_ref().map(|parser| quote! {
+                    Some(crate::lang::value::Value::String(value)) => #name = Some(#parser(&value)?),
+                });
+                let default_value = match (&parser, &default) {
+                    (Some(parser), Some(TokenTree::Group(g))) if g.stream().to_string().starts_with('"') => {
+                        let def = g.stream();
+                        Some(quote! {#parser(#def)?})
+                    }
+                    (_, Some(def)) => Some(quote! {#native_type::from(#def)}),
+                    (_, None) => None,
+                };
                 Ok(TypeData {
                     signature:
                     if default.is_none() {
-                        format!("{}={}", name.to_string(), simple_type_to_value_description(type_name).to_string().to_lowercase())
+                        format!("{}={}", argument_name(name), simple_type_to_value_description(type_name).to_string().to_lowercase())
                     } else {
-                        format!("[{}={}]", name.to_string(), simple_type_to_value_description(type_name).to_string().to_lowercase())
+                        format!("[{}={}]", argument_name(name), simple_type_to_value_description(type_name).to_string().to_lowercase())
                     }
                     ,
+                    value_type: simple_type_to_value_description(type_name).to_string(),
                     initialize: match allowed_values {
                         None => quote! { let mut #name = None; },
                         Some(literals) => {
@@ -285,14 +325,18 @@ fn type_to_value(
                             }
                         }
                     },
-                    mappings: quote! {(Some(#name_literal), #value_type) => #name = Some(#mutator),},
+                    mappings: quote! {
+                        (Some(#name_literal), #value_type) => #name = Some(#mutator),
+                        #string_mapping
+                    },
                     unnamed_mutate:
-                    match default {
+                    match default_value {
                         None => {
                             Some(quote! {
 if #name.is_none() {
     match _unnamed.pop_front() {
         Some(#value_type) => #name = Some(#mutator),
+        #string_unnamed
         Some(value) =>
             return crate::lang::errors::argument_error(format!(
                 "Expected argument \"{}\" to be of type {}, was of type {}",
@@ -312,7 +356,8 @@ if #name.is_none() {
 if #name.is_none() {
     match _unnamed.pop_front() {
         Some(#value_type) => #name = Some(#mutator),
-        None => #name = Some(#native_type::from(#def)),
+        #string_unnamed
+        None => #name = Some(#def),
         _ => return crate::lang::errors::argument_error(format!("Expected argument {} to be of type {}", #name_literal, #type_name).as_str()),
         }
 }
@@ -333,7 +378,8 @@ if #name.is_none() {
                 fail!(ty.span(), "This type can't be paramterizised")
             } else {
                 Ok(TypeData {
-                    signature: format!("[{}=(file|glob|regex|list|table|table_stream)...]", name.to_string()),
+                    signature: format!("[{}=(file|glob|regex|list|table|table_stream)...]", argument_name(name)),
+                    value_type: "(file|glob|regex|list|table|table_stream)".to_string(),
                     initialize: quote! { let mut #name = crate::lang::files::Files::new(); },
                     mappings: quote! { (Some(#name_literal), value) => #name.expand(value, printer)?, },
                     unnamed_mutate: if is_unnamed_target {
@@ -360,7 +406,8 @@ if #name.is_none() {
                 let value_type = simple_type_to_value(args[0]);
 
                 Ok(TypeData {
-                    signature: format!("[{}={}...]", name.to_string(), simple_type_to_value_description(args[0]).to_string().to_lowercase()),
+                    signature: format!("[{}={}...]", argument_name(name), simple_type_to_value_description(args[0]).to_string().to_lowercase()),
+                    value_type: simple_type_to_value_description(args[0]).to_string(),
                     initialize: quote! { let mut #name = Vec::new(); },
                     mappings: quote! {
                         (Some(#name_literal), #value_type) => #name.push(#mutator),
@@ -394,6 +441,7 @@ if #name.is_none() {
 
                 Ok(TypeData {
                     signature: format!("[<any>={}...]", simple_type_to_value_description(args[0]).to_string().to_lowercase()),
+                    value_type: simple_type_to_value_description(args[0]).to_string(),
                     initialize: quote! { let mut #name = crate::lang::ordered_string_map::OrderedStringMap::new(); },
                     mappings: quote! { (Some(name), #value_type) => #name.insert(name.to_string(), #mutator), },
                     unnamed_mutate: None,
@@ -409,17 +457,35 @@ if #name.is_none() {
                 let sub_type = Literal::string(args[0]);
                 let mutator = simple_type_to_mutator(args[0], &None);
                 let value_type = simple_type_to_value(args[0]);
+                let parser = simple_type_string_parser(args[0]);
+                let string_mapping = parser.as_ref().map(|parser| quote! {
+                    (Some(#name_literal), crate::lang::value::Value::String(value)) => #name = Some(#parser(&value)?),
+                });
+                let string_unnamed = parser.as_ref().map(|parser| quote! {
+                    Some(crate::lang::value::Value::String(value)) => #name = Some(#parser(&value)?),
+                });
+                let type_mismatch = match args[0] {
+                    "Value" | "Stream" => quote! {},
+                    _ => quote! {
+                        Some(_) => return crate::lang::errors::argument_error(format!("Expected argument {} to be of type {}", #name_literal, #sub_type).as_str()),
+                    },
+                };
 
                 Ok(TypeData {
-                    signature: format!("[{}={}]", name.to_string(), simple_type_to_value_description(args[0]).to_string().to_lowercase()),
+                    signature: format!("[{}={}]", argument_name(name), simple_type_to_value_description(args[0]).to_string().to_lowercase()),
+                    value_type: simple_type_to_value_description(args[0]).to_string(),
                     initialize: quote! { let mut #name = None; },
-                    mappings: quote! { (Some(#name_literal), #value_type) => #name = Some(#mutator), },
+                    mappings: quote! {
+                        (Some(#name_literal), #value_type) => #name = Some(#mutator),
+                        #string_mapping
+                    },
                     unnamed_mutate: Some(quote_spanned! { ty.span() =>
                             if #name.is_none() {
                                 match _unnamed.pop_front() {
                                     None => {}
                                     Some(#value_type) => #name = Some(#mutator),
-                                    Some(_) => return crate::lang::errors::argument_error(format!("Expected argument {} to be of type {}", #name_literal, #sub_type).as_str()),
+                                    #string_unnamed
+                                    #type_mismatch
                                 }
                             }
                             }
@@ -586,6 +652,8 @@ fn signature_real(metadata: TokenStream, input: TokenStream) -> SignatureResult<
             let mut unnamed_mutations = proc_macro2::TokenStream::new();
             let mut assignments = proc_macro2::TokenStream::new();
             let mut named_fallback = proc_macro2::TokenStream::new();
+            let mut argument_names = Vec::new();
+            let mut arguments = proc_macro2::TokenStream::new();
             let mut had_unnamed_target = false;
             let struct_name = s.ident.clone();
             let mut had_field_description = false;
@@ -615,6 +683,15 @@ fn signature_real(metadata: TokenStream, input: TokenStream) -> SignatureResult<
                 let type_data = type_to_value(&field.ty, name, default_value.clone(), is_unnamed_target, allowed_values)?;
 
                 signature.push(type_data.signature);
+                let argument_literal = Literal::string(&argument_name(name));
+                let value_type_literal = Literal::string(&type_data.value_type);
+                arguments.extend(quote! {
+                    crate::lang::command::ArgumentDescription {
+                        name: #argument_literal,
+                        value_type: #value_type_literal,
+                        named: #is_named_target,
+                    },
+                });
 
                 let initialize = type_data.initialize;
                 let mappings = type_data.mappings;
@@ -624,6 +701,7 @@ fn signature_real(metadata: TokenStream, input: TokenStream) -> SignatureResult<
                     named_fallback.extend(mappings)
                 } else {
                     named_matchers.extend(mappings);
+                    argument_names.push(Literal::string(&argument_name(name)));
                 }
 
                 let default_help = if let Some(d) = &default_value {
@@ -636,7 +714,7 @@ fn signature_real(metadata: TokenStream, input: TokenStream) -> SignatureResult<
                         long_description.push("This command accepts the following arguments:".to_string());
                         had_field_description = true;
                     }
-                    long_description.push(format!("* {}{}, {}", name.to_string(), default_help, description));
+                    long_description.push(format!("* {}{}, {}", argument_name(name), default_help, description));
                 }
 
                 if !had_unnamed_target || default_value.is_some() {
@@ -658,6 +736,14 @@ fn signature_real(metadata: TokenStream, input: TokenStream) -> SignatureResult<
 
             let signature_literal = Literal::string(&signature.join(" "));
 
+            if named_fallback.is_empty() {
+                named_fallback = quote! {
+                    (Some(name), _) if ![#(#argument_names),*].contains(&name) =>
+                        return crate::lang::errors::argument_error(
+                            &crate::lang::errors::not_found("Unknown argument", name, vec![#(#argument_names),*])),
+                };
+            }
+
             let long_description = if !long_description.is_empty() {
                 let mut s = "    ".to_string();
                 s.push_str(&long_description.join("\n\n    "));
@@ -671,9 +757,10 @@ fn signature_real(metadata: TokenStream, input: TokenStream) -> SignatureResult<
 
 impl crate::lang::argument::ArgumentHandler for #struct_name {
     fn declare(env: &mut crate::lang::scope::ScopeLoader) -> crate::lang::errors::CrushResult <()> {
-        env.declare_command(
+        env.declare_command_with_arguments(
             #command_name, #command_invocation, #can_block,
             #signature_literal,
+            &[#arguments],
             #description,
             #long_description,
             #output)
@@ -685,7 +772,7 @@ impl crate::lang::argument::ArgumentHandler for #struct_name {
         env.insert(#command_name.to_string(),
                     crate::lang::command::CrushCommand::command(
                         #command_invocation, #can_block, full.iter().map(|e| e.to_string()).collect(),
-                        #signature_literal, #description, #long_description, #output));
+                        #signature_literal, &[#arguments], #description, #long_description, #output));
         Ok(())
     }
 
@@ -0,0 +1,10 @@
+/*!
+The shell as a library, so that other crates, like the fuzz targets, can use the parser and the
+rest of the language. The crush binary is a thin layer of line editing on top of it.
+*/
+#[macro_use]
+extern crate lalrpop_util;
+
+pub mod lang;
+pub mod lib;
+pub mod util;
@@ -1,14 +1,16 @@
-use crate::lang::job::Job;
-use crate::lang::errors::{CrushResult, error, to_crush_error};
+use crate::lang::job::{Job, JobCondition};
+use crate::lang::errors::{CrushResult, error, mandate, to_crush_error, Location};
 use crate::lang::command_invocation::CommandInvocation;
 use crate::lang::argument::ArgumentDefinition;
 use crate::lang::value::{ValueDefinition, Value, ValueType};
 use std::ops::Deref;
 use crate::lang::command::{Parameter, Command};
 use crate::util::glob::Glob;
+use crate::util::time::{parse_duration, parse_time};
 use regex::Regex;
 use std::path::PathBuf;
 use crate::lang::scope::Scope;
+use crate::lang::parser::parse_interpolated;
 
 pub struct JobListNode {
     pub jobs: Vec<JobNode>,
@@ -18,36 +20,60 @@ impl JobListNode {
     pub fn generate(&self, env: &Scope) -> CrushResult<Vec<Job>> {
         self.jobs.iter().map(|j| j.generate(env)).collect()
     }
+
+    /** Fails if the tree is nested more than the given number of levels. */
+    pub fn check_depth(&self, depth: usize) -> CrushResult<()> {
+        self.jobs.iter().try_for_each(|j| j.check_depth(depth))
+    }
 }
 
 pub struct JobNode {
     pub commands: Vec<CommandNode>,
+    pub condition: JobCondition,
 }
 
 impl JobNode {
     pub fn generate(&self, env: &Scope) -> CrushResult<Job> {
-        Ok(Job::new(self.commands.iter().map(|c| c.generate(env)).collect::<CrushResult<Vec<CommandInvocation>>>()?))
+        Ok(Job::with_condition(
+            self.commands.iter().map(|c| c.generate(env)).collect::<CrushResult<Vec<CommandInvocation>>>()?,
+            self.condition))
+    }
+
+    pub fn check_depth(&self, depth: usize) -> CrushResult<()> {
+        self.commands.iter()
+            .flat_map(|c| c.expressions.iter())
+            .try_for_each(|e| e.check_depth(depth))
     }
 }
 
 pub struct CommandNode {
     pub expressions: Vec<Node>,
+    /** Where in the source the command is, so that errors can point to it. */
+    pub location: Location,
 }
 
 impl CommandNode {
     pub fn generate(&self, env: &Scope) -> CrushResult<CommandInvocation> {
         if let Some(c) = self.expressions[0].generate_standalone(env)? {
             if self.expressions.len() == 1 {
-                Ok(c)
+                Ok(c.at(&self.location))
             } else {
                 error("Stray arguments")
             }
         } else {
             let cmd = self.expressions[0].generate_argument(env)?;
-            let arguments = self.expressions[1..].iter()
-                .map(|e| e.generate_argument(env))
-                .collect::<CrushResult<Vec<ArgumentDefinition>>>()?;
-            Ok(CommandInvocation::new(cmd.unnamed_value()?, arguments))
+            let arguments = match &self.expressions[..] {
+                /* for name in iterable body is shorthand for for name=iterable body. */
+                [Node::Label(f), Node::Label(name), Node::Label(i), iterable, body] if f == "for" && i == "in" =>
+                    vec![
+                        ArgumentDefinition::named(name, iterable.generate_argument(env)?.unnamed_value()?),
+                        body.generate_argument(env)?,
+                    ],
+                _ => self.expressions[1..].iter()
+                    .map(|e| e.generate_argument(env))
+                    .collect::<CrushResult<Vec<ArgumentDefinition>>>()?,
+            };
+            Ok(CommandInvocation::new(cmd.unnamed_value()?, arguments).at(&self.location))
         }
     }
 }
@@ -64,10 +90,12 @@ pub enum Node {
     Label(String),
     Regex(String),
     Field(String),
-    String(String),
+    String(String, Location),
     File(PathBuf),
     Integer(i128),
     Float(f64),
+    Duration(String),
+    Time(String),
     GetItem(Box<Node>, Box<Node>),
     GetAttr(Box<Node>, String),
     Path(Box<Node>, String),
@@ -75,6 +103,20 @@ pub enum Node {
     Closure(Option<Vec<ParameterNode>>, JobListNode),
 }
 
+/**
+Long expressions like 1+1+...+1 turn into very deep trees, so nodes are taken apart one at a
+time instead of recursively, which could run out of stack.
+*/
+impl Drop for Node {
+    fn drop(&mut self) {
+        let mut stack = Vec::new();
+        self.take_children(&mut stack);
+        while let Some(mut node) = stack.pop() {
+            node.take_children(&mut stack);
+        }
+    }
+}
+
 fn propose_name(name: &str, v: ValueDefinition) -> ValueDefinition {
     match v {
         ValueDefinition::ClosureDefinition(_, p, j) =>
@@ -111,13 +153,13 @@ impl Node {
                 Node::LogicalOperation(_, _, _) | Node::Comparison(_, _, _) | Node::Replace(_, _, _, _) |
                 Node::GetItem(_, _) | Node::Term(_, _, _) | Node::Factor(_, _, _) =>
                     ValueDefinition::JobDefinition(
-                        Job::new(vec![self.generate_standalone(env)?.unwrap()])
+                        Job::new(vec![mandate(self.generate_standalone(env)?, "Invalid expression")?])
                     ),
                 Node::Unary(op, r) =>
                     match op.deref() {
                         "neg" | "not" | "typeof" =>
                             ValueDefinition::JobDefinition(
-                                Job::new(vec![self.generate_standalone(env)?.unwrap()])
+                                Job::new(vec![mandate(self.generate_standalone(env)?, "Invalid expression")?])
                             ),
                         "@" =>
                             return Ok(ArgumentDefinition::list(r.generate_argument(env)?.unnamed_value()?)),
@@ -127,9 +169,22 @@ impl Node {
                     },
                 Node::Label(l) => ValueDefinition::Label(l.clone()),
                 Node::Regex(l) => ValueDefinition::Value(Value::Regex(l.clone(), to_crush_error(Regex::new(l.clone().as_ref()))?)),
-                Node::String(t) => ValueDefinition::Value(Value::string(unescape(t).as_str())),
+                Node::String(t, location) => match interpolation(t)? {
+                    None => ValueDefinition::Value(Value::string(unescape(t).as_str())),
+                    Some((pattern, expressions)) => {
+                        let arguments = expressions.iter()
+                            .map(|(e, offsets)| Ok(ArgumentDefinition::unnamed(ValueDefinition::JobDefinition(
+                                interpolated_job(e, offsets, location, env)?))))
+                            .collect::<CrushResult<Vec<_>>>()?;
+                        ValueDefinition::JobDefinition(Job::new(vec![CommandInvocation::new(
+                            ValueDefinition::GetAttr(Box::from(ValueDefinition::Value(Value::string(&pattern))), "format".to_string()),
+                            arguments)]))
+                    }
+                },
                 Node::Integer(i) => ValueDefinition::Value(Value::Integer(*i)),
                 Node::Float(f) => ValueDefinition::Value(Value::Float(*f)),
+                Node::Duration(d) => ValueDefinition::Value(Value::Duration(parse_duration(d)?)),
+                Node::Time(t) => ValueDefinition::Value(Value::Time(parse_time(t)?)),
                 Node::GetAttr(node, label) => {
                     let parent = node.generate_argument(env)?;
                     match parent.unnamed_value()? {
@@ -280,8 +335,8 @@ impl Node {
                     _ => error("Unknown operator"),
                 },
 
-            Node::Glob(_) | Node::Label(_) | Node::Regex(_) | Node::Field(_) | Node::String(_) |
-            Node::Integer(_) | Node::Float(_) | Node::GetAttr(_, _) | Node::Path(_, _) | Node::Substitution(_) |
+            Node::Glob(_) | Node::Label(_) | Node::Regex(_) | Node::Field(_) | Node::String(_, _) |
+            Node::Integer(_) | Node::Float(_) | Node::Duration(_) | Node::Time(_) | Node::GetAttr(_, _) | Node::Path(_, _) | Node::Substitution(_) |
             Node::Closure(_, _) | Node::File(_) => Ok(None),
         }
     }
@@ -301,6 +356,87 @@ impl Node {
         ))
     }
 
+    /** Fails if the tree is nested more than the given number of levels. */
+    pub fn check_depth(&self, depth: usize) -> CrushResult<()> {
+        if depth == 0 {
+            return error("Expression is nested too deeply");
+        }
+        let depth = depth - 1;
+        match self {
+            Node::Assignment(l, _, r) | Node::LogicalOperation(l, _, r) | Node::Comparison(l, _, r) |
+            Node::Term(l, _, r) | Node::Factor(l, _, r) | Node::GetItem(l, r) => {
+                l.check_depth(depth)?;
+                r.check_depth(depth)
+            }
+            Node::Replace(v1, _, v2, v3) => {
+                v1.check_depth(depth)?;
+                v2.check_depth(depth)?;
+                v3.check_depth(depth)
+            }
+            Node::Unary(_, n) | Node::GetAttr(n, _) | Node::Path(n, _) => n.check_depth(depth),
+            Node::Substitution(j) => j.check_depth(depth),
+            Node::Closure(s, l) => {
+                for p in s.iter().flatten() {
+                    if let ParameterNode::Parameter(_, value_type, default) = p {
+                        if let Some(t) = value_type {
+                            t.check_depth(depth)?;
+                        }
+                        if let Some(d) = default {
+                            d.check_depth(depth)?;
+                        }
+                    }
+                }
+                l.check_depth(depth)
+            }
+            Node::Glob(_) | Node::Label(_) | Node::Regex(_) | Node::Field(_) | Node::String(_, _) |
+            Node::File(_) | Node::Integer(_) | Node::Float(_) | Node::Duration(_) | Node::Time(_) => Ok(()),
+        }
+    }
+
+    /** Moves the children of this node to the stack, leaving placeholders behind. */
+    fn take_children(&mut self, stack: &mut Vec<Node>) {
+        fn take(n: &mut Box<Node>, stack: &mut Vec<Node>) {
+            stack.push(std::mem::replace(n.as_mut(), Node::Integer(0)));
+        }
+        match self {
+            Node::Assignment(l, _, r) | Node::LogicalOperation(l, _, r) | Node::Comparison(l, _, r) |
+            Node::Term(l, _, r) | Node::Factor(l, _, r) | Node::GetItem(l, r) => {
+                take(l, stack);
+                take(r, stack);
+            }
+            Node::Replace(v1, _, v2, v3) => {
+                take(v1, stack);
+                take(v2, stack);
+                take(v3, stack);
+            }
+            Node::Unary(_, n) | Node::GetAttr(n, _) | Node::Path(n, _) => take(n, stack),
+            Node::Substitution(j) => {
+                for c in &mut j.commands {
+                    stack.append(&mut c.expressions);
+                }
+            }
+            Node::Closure(s, l) => {
+                for p in s.iter_mut().flatten() {
+                    if let ParameterNode::Parameter(_, value_type, default) = p {
+                        if let Some(t) = value_type {
+                            take(t, stack);
+                        }
+                        if let Some(d) = default.take() {
+                            stack.push(d);
+                        }
+                    }
+                }
+                for j in &mut l.jobs {
+                    for c in &mut j.commands {
+                        stack.append(&mut c.expressions);
+                    }
+                }
+            }
+            Node::Glob(_) | Node::Label(_) | Node::Regex(_) | Node::Field(_) | Node::String(_, _) |
+            Node::File(_) | Node::Integer(_) | Node::Float(_) | Node::Duration(_) | Node::Time(_) => {}
+        }
+    }
+
     pub fn parse_label(s: &str) -> Box<Node> {
         if s.contains('%') || s.contains('?') {
             Box::from(Node::Glob(s.to_string()))
@@ -321,6 +457,88 @@ impl Node {
     }
 }
 
+/** The source code of an interpolated expression, and the offset in the literal of each of its bytes and of its end. */
+type Interpolated = (String, Vec<usize>);
+
+/**
+Splits a string literal with interpolated expressions, like "pid is ${p:pid}", into a pattern
+for string:format and the expressions. Returns None if the string has no interpolated
+expressions. A backslash before the dollar sign prevents interpolation, and inside of an
+expression, a backslash escapes the next character, e.g. a double quote.
+*/
+fn interpolation(s: &str) -> CrushResult<Option<(String, Vec<Interpolated>)>> {
+    if !s.contains("${") {
+        return Ok(None);
+    }
+    let mut pattern = String::new();
+    let mut expressions = Vec::new();
+    let mut chars = s[..s.len() - 1].char_indices().skip(1).peekable();
+    while let Some((_, c)) = chars.next() {
+        match c {
+            '\\' => match chars.next().map(|(_, c)| c) {
+                Some('n') => pattern.push('\n'),
+                Some('r') => pattern.push('\r'),
+                Some('t') => pattern.push('\t'),
+                Some(c) if c == '{' || c == '}' => {
+                    pattern.push(c);
+                    pattern.push(c);
+                }
+                Some(c) => pattern.push(c),
+                None => {}
+            },
+            '$' if chars.peek().map(|(_, c)| *c) == Some('{') => {
+                chars.next();
+                let mut expression = String::new();
+                let mut offsets = Vec::new();
+                let mut push = |idx: usize, c: char| {
+                    expression.push(c);
+                    offsets.extend(idx..idx + c.len_utf8());
+                };
+                let mut depth = 0;
+                loop {
+                    match chars.next() {
+                        None => return error("Unterminated interpolation in string"),
+                        Some((idx, '}')) if depth == 0 => {
+                            offsets.push(idx);
+                            break;
+                        }
+                        Some((_, '\\')) => if let Some((idx, c)) = chars.next() {
+                            push(idx, c)
+                        },
+                        Some((idx, c)) => {
+                            match c {
+                                '{' => depth += 1,
+                                '}' => depth -= 1,
+                                _ => {}
+                            }
+                            push(idx, c);
+                        }
+                    }
+                }
+                pattern.push_str("{}");
+                expressions.push((expression, offsets));
+            }
+            '{' => pattern.push_str("{{"),
+            '}' => pattern.push_str("}}"),
+            c => pattern.push(c),
+        }
+    }
+    Ok(Some((pattern, expressions)))
+}
+
+/**
+Generate the job of an expression interpolated into the string literal at the given location,
+so that its commands, and any syntax error in it, point to where it is in the string.
+*/
+fn interpolated_job(source: &str, offsets: &[usize], literal: &Location, env: &Scope) -> CrushResult<Job> {
+    let offsets = offsets.iter().map(|offset| literal.start + offset).collect::<Vec<_>>();
+    let mut jobs = parse_interpolated(&literal.source, source, &offsets)?.jobs;
+    if jobs.len() != 1 {
+        return error(format!("Expected a single pipeline in ${{{}}}", source).as_str());
+    }
+    jobs.remove(0).generate(env)
+}
+
 pub fn unescape(s: &str) -> String {
     let mut res = "".to_string();
     let mut was_backslash = false;
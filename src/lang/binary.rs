@@ -1,11 +1,13 @@
 use crate::lang::errors::{CrushResult, to_crush_error};
 use std::cmp::{min};
 use std::collections::{VecDeque};
-use std::io::{Error, Read, Write};
+use std::io::{BufRead, BufReader, Error, Read, Write};
 use crossbeam::{Receiver, bounded, Sender};
 use std::fmt::{Debug, Formatter};
 use std::fs::File;
 use std::path::PathBuf;
+use std::sync::Arc;
+use crate::util::mmap::MappedReader;
 
 struct ChannelReader {
     receiver: Receiver<Box<[u8]>>,
@@ -50,9 +52,10 @@ impl std::io::Read for ChannelReader {
                     self.buff = None;
                     Ok(res)
                 } else {
-                    dst.write_all(src)?;
-                    self.buff = Some(Box::from(&src[dst.len()..]));
-                    Ok(dst.len())
+                    let res = dst.len();
+                    dst.write_all(&src[..res])?;
+                    self.buff = Some(Box::from(&src[res..]));
+                    Ok(res)
                 }
             }
         }
@@ -77,6 +80,14 @@ impl std::io::Write for ChannelWriter {
 
 pub trait BinaryReader: Read + Debug + Send + Sync {
     fn clone(&self) -> Box<dyn BinaryReader + Send + Sync>;
+
+    /**
+    Turn this reader into a buffered reader. Readers that already have their data in memory
+    override this to avoid copying it into a separate buffer.
+    */
+    fn buffered(self: Box<Self>) -> Box<dyn BufRead + Send + Sync> where Self: 'static {
+        Box::new(BufReader::new(self))
+    }
 }
 
 struct FileReader {
@@ -110,25 +121,110 @@ impl BinaryReader for FileReader {
 impl dyn BinaryReader {
     pub fn paths(mut files: Vec<PathBuf>) -> CrushResult<Box<dyn BinaryReader + Send + Sync>> {
         if files.len() == 1 {
-            Ok(Box::from(FileReader::new(to_crush_error(File::open(files.remove(0)))?)))
+            <dyn BinaryReader>::path(files.remove(0))
         } else {
             let mut readers: Vec<Box<dyn BinaryReader + Send + Sync>> = Vec::new();
 
             for p in files.drain(..) {
-                let f = to_crush_error(File::open(p).map(|f| Box::from(FileReader::new(f))))?;
-                readers.push(f)
+                readers.push(<dyn BinaryReader>::path(p)?)
             }
             Ok(Box::from(MultiReader { inner: VecDeque::from(readers) }))
         }
     }
 
+    /**
+    Open a file for reading. Large regular files are memory mapped, which is a lot faster
+    than reading them through the file descriptor.
+    */
+    fn path(path: PathBuf) -> CrushResult<Box<dyn BinaryReader + Send + Sync>> {
+        let file = to_crush_error(File::open(path))?;
+        match to_crush_error(MappedReader::open(&file))? {
+            Some(mapped) => Ok(Box::from(mapped)),
+            None => Ok(Box::from(FileReader::new(file))),
+        }
+    }
+
     pub fn vec(vec: &Vec<u8>) -> Box<dyn BinaryReader + Send + Sync> {
         Box::from(VecReader { vec: vec.clone(), offset: 0 })
     }
+
+    pub fn stdin() -> Box<dyn BinaryReader + Send + Sync> {
+        Box::from(StdinReader {})
+    }
+
+    /**
+    Wrap a reader in a reader that transforms the data, e.g. by decompressing it. Since the
+    result is a BinaryReader itself, adapters can be stacked on top of each other.
+    */
+    pub fn adapt(source: Box<dyn BinaryReader + Send + Sync>, name: &str, adapter: Adapter) -> Box<dyn BinaryReader + Send + Sync> {
+        Box::from(AdaptedReader { source, reader: None, adapter, name: name.to_string() })
+    }
+}
+
+/**
+A function that wraps a reader in another reader, see BinaryReader::adapt.
+*/
+pub type Adapter = Arc<dyn Fn(Box<dyn BinaryReader + Send + Sync>) -> Box<dyn Read + Send + Sync> + Send + Sync>;
+
+/**
+A reader transformed by an adapter. The adapter is only applied to (a copy of) the source once
+the reader is first read from, so that creating and cloning adapted readers is cheap even when
+the adapter does something expensive, like starting a process that consumes the source.
+*/
+struct AdaptedReader {
+    source: Box<dyn BinaryReader + Send + Sync>,
+    reader: Option<Box<dyn Read + Send + Sync>>,
+    adapter: Adapter,
+    name: String,
+}
+
+impl BinaryReader for AdaptedReader {
+    fn clone(&self) -> Box<dyn BinaryReader + Send + Sync> {
+        <dyn BinaryReader>::adapt(self.source.as_ref().clone(), &self.name, self.adapter.clone())
+    }
+}
+
+impl Read for AdaptedReader {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        if self.reader.is_none() {
+            self.reader = Some((self.adapter)(self.source.as_ref().clone()));
+        }
+        self.reader.as_mut().unwrap().read(buf)
+    }
+}
+
+impl Debug for AdaptedReader {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+        write!(f, "<{} reader>", self.name)
+    }
+}
+
+/**
+Reads the standard input of the shell process. All copies read from the same underlying
+stream, so data read through one copy will not be seen by the others.
+*/
+struct StdinReader {}
+
+impl BinaryReader for StdinReader {
+    fn clone(&self) -> Box<dyn BinaryReader + Send + Sync> {
+        Box::from(StdinReader {})
+    }
+}
+
+impl Read for StdinReader {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, Error> {
+        std::io::stdin().read(buf)
+    }
+}
+
+impl Debug for StdinReader {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+        f.write_str("<stdin>")
+    }
 }
 
 
-pub fn binary_channel() -> (Box<dyn Write>, Box<dyn BinaryReader + Send + Sync>) {
+pub fn binary_channel() -> (Box<dyn Write + Send>, Box<dyn BinaryReader + Send + Sync>) {
     let (s, r) = bounded(32);
     (
         Box::from(ChannelWriter { sender: s }),
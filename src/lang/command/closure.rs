@@ -1,12 +1,12 @@
-use crate::lang::errors::{CrushResult, argument_error, error, mandate};
+use crate::lang::errors::{CrushResult, argument_error, error, mandate, not_found};
 use crate::lang::argument::{Argument, ArgumentDefinition, ArgumentType};
-use crate::lang::command::{Parameter, Command, BoundCommand, CrushCommand, OutputType};
+use crate::lang::command::{Parameter, Command, BoundCommand, CrushCommand, OutputType, ArgumentDescription};
 use crate::lang::scope::Scope;
 use std::collections::HashMap;
 use crate::lang::value::{Value, ValueType, ValueDefinition};
 use crate::lang::list::List;
 use crate::lang::dict::Dict;
-use crate::lang::job::Job;
+use crate::lang::job::{Job, JobCondition, JobStatus};
 use crate::lang::stream::{empty_channel, black_hole};
 use crate::lang::execution_context::{ExecutionContext, CompileContext, JobContext};
 use crate::lang::help::Help;
@@ -15,6 +15,7 @@ use crate::lang::serialization::model::{Element, element};
 use crate::lang::serialization::model;
 use crate::lang::command_invocation::CommandInvocation;
 use crate::lang::serialization::model::closure::Name;
+use crate::util::time::{parse_duration, parse_time};
 
 pub struct Closure {
     name: Option<String>,
@@ -25,6 +26,18 @@ pub struct Closure {
     long_help: String,
 }
 
+/**
+Strings passed to duration and time parameters are parsed using the syntax of the
+corresponding literals, the same way as for builtin commands.
+*/
+fn coerce(value_type: &ValueType, value: Value) -> CrushResult<Value> {
+    match (value_type, value) {
+        (ValueType::Duration, Value::String(s)) => Ok(Value::Duration(parse_duration(&s)?)),
+        (ValueType::Time, Value::String(s)) => Ok(Value::Time(parse_time(&s)?)),
+        (_, value) => Ok(value),
+    }
+}
+
 impl CrushCommand for Closure {
     fn invoke(&self, context: ExecutionContext) -> CrushResult<()> {
         let job_definitions = self.job_definitions.clone();
@@ -43,18 +56,21 @@ impl CrushCommand for Closure {
         if env.is_stopped() {
             return Ok(());
         }
+        let mut status = JobStatus::new();
         for (idx, job_definition) in job_definitions.iter().enumerate() {
+            if !job_definition.condition().should_run(status.is_success()) {
+                continue;
+            }
             let first = idx == 0;
             let last = idx == job_definitions.len() - 1;
             let input = if first { context.input.clone() } else { empty_channel() };
             let output = if last { context.output.clone() } else { black_hole() };
-            let job = job_definition.invoke(JobContext::new(input, output, env.clone(), context.printer.clone()))?;
-            job.join(&context.printer);
+            status = job_definition.run(JobContext::new(input, output, env.clone(), context.printer.clone()));
             if env.is_stopped() {
                 return Ok(());
             }
         }
-        Ok(())
+        status.result()
     }
 
     fn can_block(&self, _arg: &[ArgumentDefinition], _context: &mut CompileContext) -> bool {
@@ -92,6 +108,14 @@ impl CrushCommand for Closure {
     fn output(&self, input: &OutputType) -> Option<&ValueType> {
         None
     }
+
+    fn parameters(&self) -> Option<&[Parameter]> {
+        self.signature.as_deref()
+    }
+
+    fn arguments(&self) -> &[ArgumentDescription] {
+        &[]
+    }
 }
 
 struct ClosureSerializer<'a> {
@@ -184,6 +208,11 @@ impl<'a> ClosureSerializer<'a> {
         for c in job.commands() {
             s.commands.push(self.command(c)?);
         }
+        s.condition = match job.condition() {
+            JobCondition::Always => model::job::Condition::Always,
+            JobCondition::OnSuccess => model::job::Condition::OnSuccess,
+            JobCondition::OnFailure => model::job::Condition::OnFailure,
+        } as i32;
         Ok(s)
     }
 
@@ -325,10 +354,16 @@ impl<'a> ClosureDeserializer<'a> {
         &mut self,
         s: &model::Job,
     ) -> CrushResult<Job> {
-        Ok(Job::new(
+        Ok(Job::with_condition(
             s.commands.iter()
                 .map(|c| self.command(c))
-                .collect::<CrushResult<Vec<_>>>()?))
+                .collect::<CrushResult<Vec<_>>>()?,
+            match s.condition {
+                0 => JobCondition::Always,
+                1 => JobCondition::OnSuccess,
+                2 => JobCondition::OnFailure,
+                _ => return error("Unrecognised job condition"),
+            }))
     }
 
 
@@ -429,7 +464,26 @@ impl Help for Closure {
     }
 
     fn long_help(&self) -> Option<String> {
-        Some(self.long_help.clone())
+        if self.long_help.is_empty() {
+            None
+        } else {
+            Some(self.long_help.clone())
+        }
+    }
+}
+
+/**
+Split a doc string at the first empty line, so that the first paragraph becomes the short help
+and the rest becomes the long help.
+*/
+fn split_help(help: &str) -> (String, String) {
+    let lines: Vec<&str> = help.lines().collect();
+    match lines.iter().position(|line| line.trim().is_empty()) {
+        Some(idx) => (
+            lines[..idx].iter().map(|line| line.trim()).collect::<Vec<_>>().join(" "),
+            lines[idx + 1..].join("\n"),
+        ),
+        None => (help.to_string(), String::new()),
     }
 }
 
@@ -465,8 +519,13 @@ impl Closure {
         mut job_definitions: Vec<Job>,
         env: Scope,
     ) -> Closure {
-        let short_help = extract_help(&mut job_definitions);
-        let long_help = extract_help(&mut job_definitions);
+        let mut short_help = extract_help(&mut job_definitions);
+        let mut long_help = extract_help(&mut job_definitions);
+        if long_help.is_empty() {
+            let (short, long) = split_help(&short_help);
+            short_help = short;
+            long_help = long;
+        }
 
         Closure {
             name,
@@ -478,6 +537,37 @@ impl Closure {
         }
     }
 
+    /**
+    Fail if there are named arguments that no parameter takes, suggesting the parameters that
+    were probably meant. This is checked before anything else, because a misspelled argument
+    also causes the parameter it was meant for to seem to be missing.
+    */
+    fn check_named_arguments(signature: &[Parameter], named: &HashMap<String, Value>) -> CrushResult<()> {
+        if signature.iter().any(|param| matches!(param, Parameter::Named(_))) {
+            return Ok(());
+        }
+        let parameters = signature.iter()
+            .filter_map(|param| match param {
+                Parameter::Parameter(name, _, _) => Some(name.as_str()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        let mut unknown = named.keys()
+            .filter(|name| !parameters.contains(&name.as_str()))
+            .collect::<Vec<_>>();
+        unknown.sort();
+        if unknown.is_empty() {
+            Ok(())
+        } else {
+            argument_error(
+                unknown.iter()
+                    .map(|name| not_found("Unknown named argument", name, parameters.iter().cloned()))
+                    .collect::<Vec<_>>()
+                    .join("; ")
+                    .as_str())
+        }
+    }
+
     fn push_arguments_to_env(
         signature: &Option<Vec<Parameter>>,
         mut arguments: Vec<Argument>,
@@ -494,6 +584,7 @@ impl Closure {
                     None => unnamed.push(arg.value),
                 };
             }
+            Closure::check_named_arguments(signature, &named)?;
             let mut unnamed_name = None;
             let mut named_name = None;
 
@@ -501,22 +592,30 @@ impl Closure {
                 match param {
                     Parameter::Parameter(name, value_type, default) => {
                         if let Value::Type(value_type) = value_type.compile_bound(context)? {
-                            if named.contains_key(name) {
-                                let value = named.remove(name).unwrap();
-                                if !value_type.is(&value) {
-                                    return argument_error("Wrong parameter type");
-                                }
-                                context.env.redeclare(name, value)?;
+                            let value = if let Some(value) = named.remove(name) {
+                                value
                             } else if !unnamed.is_empty() {
-                                context.env.redeclare(name, unnamed.remove(0))?;
+                                unnamed.remove(0)
                             } else if let Some(default) = default {
-                                let env = context.env.clone();
-                                env.redeclare(name, default.compile_bound(context)?)?;
+                                let value = coerce(&value_type, default.compile_bound(context)?)?;
+                                if !value_type.is(&value) {
+                                    return argument_error(format!("Wrong type for the default value of parameter {}", name).as_str());
+                                }
+                                value
                             } else {
-                                return argument_error("Missing variable!!!");
+                                return argument_error(format!("Missing value for parameter {}", name).as_str());
+                            };
+                            let value = coerce(&value_type, value)?;
+                            if !value_type.is(&value) {
+                                return argument_error(format!(
+                                    "Expected parameter {} to be of type {}, got a value of type {}",
+                                    name,
+                                    value_type.to_string(),
+                                    value.value_type().to_string()).as_str());
                             }
+                            context.env.redeclare(name, value)?;
                         } else {
-                            return argument_error("Not a type");
+                            return argument_error(format!("The type of parameter {} is not a type", name).as_str());
                         }
                     }
                     Parameter::Named(name) => {
@@ -539,7 +638,7 @@ impl Closure {
                     unnamed_name.as_ref(),
                     Value::List(List::new(ValueType::Any, unnamed)))?;
             } else if !unnamed.is_empty() {
-                return argument_error("No target for unnamed arguments");
+                return argument_error(format!("Got {} more unnamed arguments than there are parameters", unnamed.len()).as_str());
             }
 
 
@@ -549,8 +648,6 @@ impl Closure {
                     d.insert(Value::string(&k), v)?;
                 }
                 context.env.redeclare(named_name.as_ref(), Value::Dict(d))?;
-            } else if !named.is_empty() {
-                return argument_error("No target for extra named arguments");
             }
         } else {
             for arg in arguments.drain(..) {
@@ -2,7 +2,7 @@ mod closure;
 
 use crate::lang::errors::{CrushResult, error};
 use std::fmt::Formatter;
-use crate::lang::{argument::ArgumentDefinition};
+use crate::lang::{argument::ArgumentDefinition, argument::Argument};
 use crate::lang::scope::Scope;
 use crate::lang::job::Job;
 use crate::lang::value::{ValueDefinition, Value, ValueType};
@@ -41,6 +41,20 @@ impl OutputType {
     }
 }
 
+/**
+An argument of a builtin command, as declared by its signature. Unlike the signature shown in
+the help of the command, this can be inspected, e.g. to find out which arguments a command
+takes.
+*/
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ArgumentDescription {
+    pub name: &'static str,
+    /** The type of the argument, as described in the signature, e.g. file or integer. */
+    pub value_type: &'static str,
+    /** True if the argument takes all named arguments that no other argument takes. */
+    pub named: bool,
+}
+
 pub trait CrushCommand: Help {
     fn invoke(&self, context: ExecutionContext) -> CrushResult<()>;
     fn can_block(&self, arguments: &[ArgumentDefinition], context: &mut CompileContext) -> bool;
@@ -50,6 +64,10 @@ pub trait CrushCommand: Help {
     fn serialize(&self, elements: &mut Vec<Element>, state: &mut SerializationState) -> CrushResult<usize>;
     fn bind(&self, this: Value) -> Command;
     fn output<'a>(&'a self, input: &'a OutputType) -> Option<&'a ValueType>;
+    /** The declared parameters of this command, if it has a signature that can be inspected. */
+    fn parameters(&self) -> Option<&[Parameter]>;
+    /** The declared arguments of this command, if it is a builtin with a signature. */
+    fn arguments(&self) -> &[ArgumentDescription];
 }
 
 pub trait TypeMap {
@@ -79,7 +97,7 @@ impl TypeMap for OrderedMap<String, Command> {
         self.insert(path[path.len() - 1].to_string(),
                     CrushCommand::command(
                         call, can_block, path.iter().map(|e| e.to_string()).collect(),
-                        signature, short_help, long_help, output),
+                        signature, &[], short_help, long_help, output),
         );
     }
 }
@@ -89,6 +107,7 @@ struct SimpleCommand {
     can_block: bool,
     full_name: Vec<String>,
     signature: &'static str,
+    arguments: &'static [ArgumentDescription],
     short_help: &'static str,
     long_help: Option<&'static str>,
     output: OutputType,
@@ -122,11 +141,12 @@ impl dyn CrushCommand {
         can_block: bool,
         full_name: Vec<String>,
         signature: &'static str,
+        arguments: &'static [ArgumentDescription],
         short_help: &'static str,
         long_help: Option<&'static str>,
         output: OutputType,
     ) -> Command {
-        Box::from(SimpleCommand { call, can_block, full_name, signature, short_help, long_help, output })
+        Box::from(SimpleCommand { call, can_block, full_name, signature, arguments, short_help, long_help, output })
     }
 
     pub fn condition(
@@ -139,6 +159,13 @@ impl dyn CrushCommand {
         Box::from(ConditionCommand { call, full_name, signature, short_help, long_help })
     }
 
+    pub fn partial(
+        command: Command,
+        arguments: Vec<Argument>,
+    ) -> Command {
+        Box::from(PartialCommand { command, arguments })
+    }
+
     pub fn deserialize(
         id: usize,
         elements: &[Element],
@@ -161,6 +188,19 @@ impl dyn CrushCommand {
                 let command = CrushCommand::deserialize(bound_command.command as usize, elements, state)?;
                 Ok(command.bind(this))
             }
+            element::Element::PartialCommand(partial_command) => {
+                let command = CrushCommand::deserialize(partial_command.command as usize, elements, state)?;
+                let arguments = partial_command.arguments.iter()
+                    .map(|a| Ok(Argument {
+                        argument_type: match &a.name {
+                            None | Some(model::partial_argument::Name::HasName(_)) => None,
+                            Some(model::partial_argument::Name::NameValue(name)) => Some(name.clone()),
+                        },
+                        value: Value::deserialize(a.value as usize, elements, state)?,
+                    }))
+                    .collect::<CrushResult<Vec<_>>>()?;
+                Ok(CrushCommand::partial(command, arguments))
+            }
             element::Element::Closure(_) => {
                 Closure::deserialize(id, elements, state)
             }
@@ -187,6 +227,7 @@ impl CrushCommand for SimpleCommand {
             can_block: self.can_block,
             full_name: self.full_name.clone(),
             signature: self.signature,
+            arguments: self.arguments,
             short_help: self.short_help,
             long_help: self.long_help,
             output: self.output.clone(),
@@ -217,6 +258,14 @@ impl CrushCommand for SimpleCommand {
     fn output<'a>(&'a self, input: &'a OutputType) -> Option<&'a ValueType> {
         self.output.calculate(input)
     }
+
+    fn parameters(&self) -> Option<&[Parameter]> {
+        None
+    }
+
+    fn arguments(&self) -> &[ArgumentDescription] {
+        self.arguments
+    }
 }
 
 impl Help for SimpleCommand {
@@ -300,6 +349,14 @@ impl CrushCommand for ConditionCommand {
     fn output(&self, input: &OutputType) -> Option<&ValueType> {
         None
     }
+
+    fn parameters(&self) -> Option<&[Parameter]> {
+        None
+    }
+
+    fn arguments(&self) -> &[ArgumentDescription] {
+        &[]
+    }
 }
 
 impl Help for ConditionCommand {
@@ -407,6 +464,14 @@ impl CrushCommand for BoundCommand {
     fn output<'a>(&'a self, input: &'a OutputType) -> Option<&'a ValueType> {
         self.command.output(input)
     }
+
+    fn parameters(&self) -> Option<&[Parameter]> {
+        self.command.parameters()
+    }
+
+    fn arguments(&self) -> &[ArgumentDescription] {
+        self.command.arguments()
+    }
 }
 
 impl Help for BoundCommand {
@@ -422,3 +487,98 @@ impl Help for BoundCommand {
         self.command.long_help()
     }
 }
+
+/**
+    A command with a set of arguments already supplied. The stored arguments
+    are passed in front of whatever arguments the command is invoked with.
+*/
+pub struct PartialCommand {
+    command: Command,
+    arguments: Vec<Argument>,
+}
+
+impl CrushCommand for PartialCommand {
+    fn invoke(&self, mut context: ExecutionContext) -> CrushResult<()> {
+        let mut arguments = self.arguments.clone();
+        arguments.append(&mut context.arguments);
+        context.arguments = arguments;
+        self.command.invoke(context)
+    }
+
+    fn can_block(&self, arguments: &[ArgumentDefinition], context: &mut CompileContext) -> bool {
+        self.command.can_block(arguments, context)
+    }
+
+    fn name(&self) -> &str {
+        self.command.name()
+    }
+
+    fn clone(&self) -> Command {
+        Box::from(
+            PartialCommand {
+                command: self.command.clone(),
+                arguments: self.arguments.clone(),
+            }
+        )
+    }
+
+    fn help(&self) -> &dyn Help {
+        self
+    }
+
+    fn serialize(&self, elements: &mut Vec<Element>, state: &mut SerializationState) -> CrushResult<usize> {
+        let command = self.command.serialize(elements, state)? as u64;
+        let mut arguments = Vec::new();
+        for a in &self.arguments {
+            arguments.push(model::PartialArgument {
+                name: Some(match &a.argument_type {
+                    None => model::partial_argument::Name::HasName(false),
+                    Some(name) => model::partial_argument::Name::NameValue(name.clone()),
+                }),
+                value: a.value.serialize(elements, state)? as u64,
+            });
+        }
+        let idx = elements.len();
+        elements.push(Element {
+            element: Some(element::Element::PartialCommand(
+                model::PartialCommand {
+                    command,
+                    arguments,
+                })),
+        });
+        Ok(idx)
+    }
+
+    fn bind(&self, this: Value) -> Command {
+        Box::from(BoundCommand {
+            command: self.clone(),
+            this,
+        })
+    }
+
+    fn output<'a>(&'a self, input: &'a OutputType) -> Option<&'a ValueType> {
+        self.command.output(input)
+    }
+
+    fn parameters(&self) -> Option<&[Parameter]> {
+        None
+    }
+
+    fn arguments(&self) -> &[ArgumentDescription] {
+        self.command.arguments()
+    }
+}
+
+impl Help for PartialCommand {
+    fn signature(&self) -> String {
+        self.command.signature()
+    }
+
+    fn short_help(&self) -> String {
+        self.command.short_help()
+    }
+
+    fn long_help(&self) -> Option<String> {
+        self.command.long_help()
+    }
+}
@@ -1,7 +1,10 @@
-use crate::lang::{execution_context::ExecutionContext, job::JobJoinHandle, command::Command, value::ValueDefinition};
-use crate::lang::{argument::ArgumentDefinition, argument::ArgumentVecCompiler, value::Value};
+use crate::lang::{execution_context::ExecutionContext, job::JobJoinHandle, command::Command, command::Parameter, value::ValueDefinition};
+use crate::lang::{argument::ArgumentDefinition, argument::ArgumentType, argument::ArgumentVecCompiler, value::Value};
+use crate::lang::help::{self, Help};
 use crate::lang::scope::Scope;
-use crate::lang::errors::{error, CrushResult, Kind};
+use crate::lang::errors::{error, not_found, CrushResult, Kind, Location};
+use crate::lang::value::ValueType;
+use ordered_map::OrderedMap;
 use crate::util::thread::{handle, build};
 use std::path::PathBuf;
 use crate::lang::execution_context::{JobContext, CompileContext};
@@ -11,6 +14,8 @@ use std::ops::Deref;
 pub struct CommandInvocation {
     command: ValueDefinition,
     arguments: Vec<ArgumentDefinition>,
+    /** Where in the source the command was written, if it was. */
+    location: Option<Location>,
 }
 
 fn resolve_external_command(name: &str, env: &Scope) -> CrushResult<Option<PathBuf>> {
@@ -42,7 +47,12 @@ fn arg_can_block(local_arguments: &Vec<ArgumentDefinition>, context: &mut Compil
 
 impl CommandInvocation {
     pub fn new(command: ValueDefinition, arguments: Vec<ArgumentDefinition>) -> CommandInvocation {
-        CommandInvocation { command, arguments }
+        CommandInvocation { command, arguments, location: None }
+    }
+
+    /** The same command, written at the given location. */
+    pub fn at(self, location: &Location) -> CommandInvocation {
+        CommandInvocation { location: Some(location.clone()), ..self }
     }
 
     pub fn as_string(&self) -> Option<String> {
@@ -116,7 +126,18 @@ impl CommandInvocation {
         }
     }
 
+    /**
+    Start running the command. Errors it reports point to where it was written, and errors
+    reported by commands it runs, e.g. the body of a loop, list it as the stage they ran in.
+    */
     pub fn invoke(&self, context: JobContext) -> CrushResult<JobJoinHandle> {
+        match &self.location {
+            Some(location) => self.invoke_here(context.at(location)).map_err(|e| e.at(location)),
+            None => self.invoke_here(context),
+        }
+    }
+
+    fn invoke_here(&self, context: JobContext) -> CrushResult<JobJoinHandle> {
         match self.command.compile_internal(&mut context.compile_context(), false) {
             Ok((this, value)) => {
                 invoke_value(this, value, self.arguments.clone(), context)
@@ -129,11 +150,11 @@ impl CommandInvocation {
                         move || {
                             match cmd.clone().compile_unbound(&mut context.compile_context()) {
                                 Ok((this, value)) =>
-                                    context.printer.handle_error(
+                                    context.handle_error(
                                         invoke_value(this, value, arguments, context.clone())),
 
                                 _ =>
-                                    context.printer.handle_error(
+                                    context.handle_error(
                                         try_external_command(cmd, arguments, context.clone())),
                             }
                         })))
@@ -145,6 +166,43 @@ impl CommandInvocation {
     }
 }
 
+/** Whether the arguments include --help, which is short for help=true. */
+fn asks_for_help(arguments: &[ArgumentDefinition]) -> bool {
+    let is_true = |value: &ValueDefinition| match value {
+        ValueDefinition::JobDefinition(job) => match job.commands() {
+            [call] => call.arguments().is_empty() && call.command().to_string() == "true",
+            _ => false,
+        },
+        ValueDefinition::Label(label) => label == "true",
+        _ => false,
+    };
+    arguments.iter().any(|a| match &a.argument_type {
+        ArgumentType::Some(name) => name == "help" && is_true(&a.value),
+        _ => false,
+    })
+}
+
+/**
+Whether a command handles --help itself, because it takes an argument named help, or any
+named argument at all, like cmd, which passes them on to an external program.
+*/
+fn handles_help(command: &Command) -> bool {
+    match command.parameters() {
+        Some(parameters) => parameters.iter().any(|parameter| match parameter {
+            Parameter::Parameter(name, _, _) => name == "help",
+            Parameter::Named(_) => true,
+            Parameter::Unnamed(_) => false,
+        }),
+        None => command.arguments().iter().any(|argument| argument.named || argument.name == "help"),
+    }
+}
+
+fn show_help(o: &dyn Help, context: JobContext) -> CrushResult<JobJoinHandle> {
+    context.printer.line(&help::render(o));
+    context.output.send(Value::Empty())?;
+    Ok(JobJoinHandle::Many(vec![]))
+}
+
 fn invoke_value(
     this: Option<Value>,
     value: Value,
@@ -152,7 +210,11 @@ fn invoke_value(
     context: JobContext) -> CrushResult<JobJoinHandle> {
     match value {
         Value::Command(command) =>
-            invoke_command(command, this, local_arguments, context),
+            if asks_for_help(&local_arguments) && !handles_help(&command) {
+                show_help(command.help(), context)
+            } else {
+                invoke_command(command, this, local_arguments, context)
+            },
         Value::File(f) =>
             if local_arguments.len() == 0 {
                 let meta = f.metadata();
@@ -195,6 +257,8 @@ fn invoke_value(
                     None,
                     vec![ArgumentDefinition::unnamed(ValueDefinition::Value(value))],
                     context)
+            } else if asks_for_help(&local_arguments) {
+                show_help(&value, context)
             } else {
                 error(format!("Not a command {}", value.to_string()).as_str())
             }
@@ -211,7 +275,7 @@ fn invoke_command(
             local_arguments,
             this,
             context.clone())?;
-        context.printer.handle_error(action.invoke(new_context));
+        context.handle_error(action.invoke(new_context));
         Ok(JobJoinHandle::Many(vec![]))
     } else {
         Ok(handle(build(action.name()).spawn(
@@ -221,15 +285,24 @@ fn invoke_command(
                     this,
                     context.clone());
                 if let Ok(ctx) = res {
-                    let p = ctx.printer.clone();
-                    p.handle_error(action.invoke(ctx));
+                    context.handle_error(action.invoke(ctx));
                 } else {
-                    context.printer.handle_error(res);
+                    context.handle_error(res);
                 }
             })))
     }
 }
 
+/** The names of the commands that can be called from a scope. */
+fn command_names(env: &Scope) -> Vec<String> {
+    let mut map = OrderedMap::new();
+    let _ = env.dump(&mut map);
+    map.iter()
+        .filter(|(_, value_type)| **value_type == ValueType::Command)
+        .map(|(name, _)| name.clone())
+        .collect()
+}
+
 fn try_external_command(
     def: ValueDefinition,
     mut arguments: Vec<ArgumentDefinition>,
@@ -245,7 +318,13 @@ fn try_external_command(
     };
 
     match resolve_external_command(&cmd, &context.env)? {
-        None => error(format!("Unknown command name {}", cmd).as_str()),
+        None => match (&sub, context.env.get(&cmd)?) {
+            (Some(sub), Some(value)) => {
+                let members = value.fields().iter().map(|member| format!("{}:{}", cmd, member)).collect::<Vec<_>>();
+                error(&not_found("Unknown command name", &format!("{}:{}", cmd, sub), members.iter().map(|n| n.as_str())))
+            }
+            _ => error(&not_found("Unknown command name", &cmd, command_names(&context.env).iter().map(|n| n.as_str()))),
+        },
         Some(path) => {
             arguments.insert(
                 0,
@@ -255,11 +334,10 @@ fn try_external_command(
                     1,
                     ArgumentDefinition::unnamed(ValueDefinition::Value(Value::string(subcmd.as_ref()))));
             }
-            let call = CommandInvocation {
-                command: ValueDefinition::Value(Value::Command(
+            let call = CommandInvocation::new(
+                ValueDefinition::Value(Value::Command(
                     context.env.global_static_cmd(vec!["global", "control", "cmd"])?)),
-                arguments,
-            };
+                arguments);
             call.invoke(context)
         }
     }
@@ -0,0 +1,277 @@
+use std::path::Path;
+
+use ordered_map::OrderedMap;
+
+use crate::lang::command::{Command, OutputType, Parameter};
+use crate::lang::scope::Scope;
+use crate::lang::value::{Value, ValueType};
+
+/**
+The pipelines that enclose the cursor, innermost last. Each pipeline is a list of the text of its
+commands, where the last command is the one containing the cursor.
+*/
+type Frames = Vec<Vec<String>>;
+
+/**
+Split the line up to the cursor into the pipelines enclosing the cursor. This is much simpler
+than parsing, so that it works on half written lines with unbalanced brackets.
+*/
+fn frames(line: &str) -> Option<Frames> {
+    let mut frames: Frames = vec![vec![String::new()]];
+    let mut in_quote = false;
+    let mut escaped = false;
+    for c in line.chars() {
+        let segment = frames.last_mut().unwrap();
+        if in_quote {
+            segment.last_mut().unwrap().push(c);
+            match (escaped, c) {
+                (false, '\\') => escaped = true,
+                (false, '"') => in_quote = false,
+                _ => escaped = false,
+            }
+            continue;
+        }
+        match c {
+            '"' => {
+                in_quote = true;
+                segment.last_mut().unwrap().push(c);
+            }
+            '(' | '{' | '[' => {
+                segment.last_mut().unwrap().push(c);
+                frames.push(vec![String::new()]);
+            }
+            ')' | '}' | ']' => {
+                if frames.len() > 1 {
+                    frames.pop();
+                }
+                frames.last_mut().unwrap().last_mut().unwrap().push(c);
+            }
+            '|' => segment.push(String::new()),
+            ';' | '\n' => *segment = vec![String::new()],
+            c => segment.last_mut().unwrap().push(c),
+        }
+    }
+    if in_quote {
+        None
+    } else {
+        Some(frames)
+    }
+}
+
+/** Look up a value by its path, e.g. git:branch. */
+fn lookup(scope: &Scope, path: &str) -> Option<Value> {
+    let mut parts = path.split(':');
+    let mut value = scope.get(parts.next()?).ok()??;
+    for part in parts {
+        value = value.field(part).ok()??;
+    }
+    Some(value)
+}
+
+fn command(scope: &Scope, segment: &str) -> Option<Command> {
+    match lookup(scope, segment.split_whitespace().next()?)? {
+        Value::Command(command) => Some(command),
+        _ => None,
+    }
+}
+
+/**
+The named parameters of a command, along with their types, as declared by the parameters of a
+closure or the signature of a builtin, e.g. `cwd` of type `file` for with.
+*/
+fn parameters(command: &Command) -> Vec<(String, String)> {
+    match command.parameters() {
+        Some(parameters) => parameters.iter()
+            .filter_map(|parameter| match parameter {
+                Parameter::Parameter(name, value_type, _) => Some((name.clone(), value_type.to_string())),
+                Parameter::Named(_) | Parameter::Unnamed(_) => None,
+            })
+            .collect(),
+        None => command.arguments().iter()
+            .filter(|argument| !argument.named)
+            .map(|argument| (argument.name.to_string(), argument.value_type.to_string()))
+            .collect(),
+    }
+}
+
+fn is_file_type(value_type: &str) -> bool {
+    value_type.trim_matches(['(', ')']).split('|').any(|t| t == "file")
+}
+
+/**
+The type of the output of a pipeline of commands, as far as it can be known without running it.
+*/
+fn output_type(scope: &Scope, segments: &[String]) -> Option<ValueType> {
+    let mut input = OutputType::Unknown;
+    for segment in segments {
+        input = command(scope, segment)
+            .and_then(|command| command.output(&input).cloned())
+            .map(OutputType::Known)
+            .unwrap_or(OutputType::Unknown);
+    }
+    match input {
+        OutputType::Known(t) => Some(t),
+        _ => None,
+    }
+}
+
+/**
+The columns of the input of the innermost command with a known input, e.g. the columns of the
+output of ls when completing `ls | where {^`.
+*/
+fn columns(scope: &Scope, frames: &Frames) -> Vec<String> {
+    for frame in frames.iter().rev() {
+        if frame.len() < 2 {
+            continue;
+        }
+        if let Some(ValueType::TableStream(columns)) | Some(ValueType::Table(columns)) =
+        output_type(scope, &frame[..frame.len() - 1]) {
+            return columns.into_iter().map(|c| c.name).collect();
+        }
+    }
+    Vec::new()
+}
+
+fn paths(prefix: &str) -> Vec<String> {
+    let (dir, name) = match prefix.rfind('/') {
+        Some(idx) => (&prefix[..=idx], &prefix[idx + 1..]),
+        None => ("", prefix),
+    };
+    let entries = match std::fs::read_dir(if dir.is_empty() { Path::new(".") } else { Path::new(dir) }) {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+    entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let file_name = entry.file_name().to_str()?.to_string();
+            if !file_name.starts_with(name) || (file_name.starts_with('.') && !name.starts_with('.')) {
+                return None;
+            }
+            let suffix = if entry.path().is_dir() { "/" } else { "" };
+            Some(format!("{}{}{}", dir, file_name, suffix))
+        })
+        .collect()
+}
+
+fn is_path(word: &str) -> bool {
+    word.starts_with('.') || word.starts_with('/') || word.starts_with('~')
+}
+
+fn names(scope: &Scope, word: &str) -> Vec<String> {
+    match word.rfind(':') {
+        Some(idx) => match lookup(scope, &word[..idx]) {
+            Some(value) => value.fields()
+                .into_iter()
+                .map(|name| format!("{}:{}", &word[..idx], name))
+                .collect(),
+            None => Vec::new(),
+        },
+        None => {
+            let mut map = OrderedMap::new();
+            let _ = scope.dump(&mut map);
+            map.keys().cloned().collect()
+        }
+    }
+}
+
+/**
+Complete the word before the cursor, which may be a command name, a named argument, a file or
+the name of a column of the input of the command. Returns the position in the line where the
+word starts, and the candidates to replace it with.
+*/
+pub fn complete(line: &str, cursor: usize, scope: &Scope) -> (usize, Vec<String>) {
+    let line = &line[..cursor];
+    let frames = match frames(line) {
+        Some(frames) => frames,
+        None => return (cursor, Vec::new()),
+    };
+    let start = line
+        .rfind(|c: char| c.is_whitespace() || "(){}[]|;".contains(c))
+        .map(|idx| idx + 1)
+        .unwrap_or(0);
+    let word = &line[start..];
+    let segment = frames.last().unwrap().last().unwrap();
+    let before_word = &segment[..segment.len() - word.len()];
+
+    let (position, mut candidates) = if let Some(field) = word.strip_prefix('^') {
+        (start + 1, columns(scope, &frames).into_iter().filter(|c| c.starts_with(field)).collect())
+    } else if before_word.trim().is_empty() {
+        (start, names(scope, word).into_iter().filter(|n| n.starts_with(word)).collect())
+    } else {
+        let parameters = command(scope, before_word).map(|c| parameters(&c)).unwrap_or_default();
+        match word.find('=') {
+            Some(idx) => {
+                let is_file = parameters.iter().any(|(name, t)| name == &word[..idx] && is_file_type(t));
+                (start + idx + 1, if is_file { paths(&word[idx + 1..]) } else { Vec::new() })
+            }
+            None => {
+                let mut candidates: Vec<String> = parameters.iter()
+                    .filter(|(name, _)| name.starts_with(word))
+                    .map(|(name, _)| format!("{}=", name))
+                    .collect();
+                if is_path(word) || parameters.iter().any(|(_, t)| is_file_type(t)) {
+                    candidates.append(&mut paths(word));
+                }
+                (start, candidates)
+            }
+        }
+    };
+    candidates.sort();
+    candidates.dedup();
+    (position, candidates)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lang::printer;
+    use crate::lang::stream::black_hole;
+
+    fn root() -> Scope {
+        let root = Scope::create_root();
+        let (printer, _capture) = printer::capture();
+        crate::lib::declare(&root, &printer).unwrap();
+        root
+    }
+
+    fn candidates(line: &str) -> Vec<String> {
+        complete(line, line.len(), &root()).1
+    }
+
+    #[test]
+    fn commands() {
+        assert!(candidates("git:br").contains(&"git:branch".to_string()));
+        assert!(candidates("echo 1; sou").contains(&"source".to_string()));
+        assert_eq!(complete("ls | sor", 8, &root()).0, 5);
+    }
+
+    #[test]
+    fn arguments() {
+        assert_eq!(candidates("with c"), vec!["command=", "cwd="]);
+        assert!(candidates("source ./example_data/mod").contains(&"./example_data/modules/".to_string()));
+        assert!(candidates("with cwd=./exa").is_empty());
+        assert!(candidates("echo \"a b").is_empty());
+        // The catch-all for environment variables is not an argument name
+        assert!(candidates("with v").is_empty());
+    }
+
+    #[test]
+    fn closure_arguments() {
+        let root = root();
+        let env = root.create_child(&root, false);
+        let (printer, _capture) = printer::capture();
+        crate::lang::execute::string(
+            env.clone(), "f := {|target: file count: integer @@rest| echo target}", &printer, &black_hole());
+        assert_eq!(complete("f c", 3, &env).1, vec!["count="]);
+        assert!(complete("f target=./example_data/mod", 27, &env).1.contains(&"./example_data/modules/".to_string()));
+    }
+
+    #[test]
+    fn fields() {
+        assert_eq!(candidates("find . | where {^si"), vec!["size"]);
+        assert_eq!(candidates("find . | sort ^mo"), vec!["modified"]);
+        assert!(candidates("find . | head 3 | where {^").contains(&"permissions".to_string()));
+        assert!(candidates("echo ^").is_empty());
+    }
+}
@@ -200,4 +200,8 @@ impl CrushStream for DictReader {
     fn types(&self) -> &[ColumnType] {
         &self.types
     }
+
+    fn row_count(&self) -> Option<usize> {
+        Some(self.list.len() - self.idx)
+    }
 }
@@ -1,4 +1,6 @@
 use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::sync::Arc;
 use crate::lang::errors::Kind::*;
 
 #[derive(Debug, PartialEq)]
@@ -9,49 +11,121 @@ pub enum Kind {
     GenericError,
     BlockError,
     SendError,
+    ExitStatus(i32),
+}
+
+/**
+Source code that is being run, e.g. a script or a line entered into the shell, so that errors
+can point out where in it they happened.
+*/
+#[derive(Debug)]
+pub struct Source {
+    /** The file the code was read from, if any. */
+    pub name: Option<String>,
+    pub text: String,
+}
+
+impl Source {
+    pub fn new(name: Option<&str>, text: &str) -> Arc<Source> {
+        Arc::new(Source { name: name.map(|n| n.to_string()), text: text.to_string() })
+    }
+
+    /** The line and column of a byte offset into the code, both counted from one. */
+    pub fn line_column(&self, offset: usize) -> (usize, usize) {
+        let before = &self.text[..offset.min(self.text.len())];
+        let line_start = before.rfind('\n').map(|idx| idx + 1).unwrap_or(0);
+        (before.matches('\n').count() + 1, before[line_start..].chars().count() + 1)
+    }
+}
+
+/** A span of source code, as byte offsets, e.g. the command of a pipeline that failed. */
+#[derive(Clone, Debug)]
+pub struct Location {
+    pub source: Arc<Source>,
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Location {
+    pub fn new(source: &Arc<Source>, start: usize, end: usize) -> Location {
+        Location { source: source.clone(), start, end }
+    }
+
+    /** The line and column where the span starts. */
+    pub fn line_column(&self) -> (usize, usize) {
+        self.source.line_column(self.start)
+    }
+
+    /** The code in the span. */
+    pub fn text(&self) -> &str {
+        &self.source.text[self.start..self.end]
+    }
+}
+
+impl PartialEq for Location {
+    fn eq(&self, other: &Location) -> bool {
+        Arc::ptr_eq(&self.source, &other.source) && self.start == other.start && self.end == other.end
+    }
+}
+
+impl Display for Location {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let (line, column) = self.line_column();
+        match &self.source.name {
+            Some(name) => write!(f, "{}:{}:{}", name, line, column),
+            None => write!(f, "line {}, column {}", line, column),
+        }
+    }
 }
 
 #[derive(Debug)]
 pub struct CrushError {
     pub kind: Kind,
     pub message: String,
+    /** The expression that failed, if known. */
+    pub location: Option<Location>,
+    /**
+    The commands that were running the one that failed, innermost first, e.g. the loop whose
+    body failed, and the command that called the closure containing the loop.
+    */
+    pub trace: Vec<Location>,
+}
+
+impl CrushError {
+    pub fn new(kind: Kind, message: &str) -> CrushError {
+        CrushError { kind, message: message.to_string(), location: None, trace: Vec::new() }
+    }
+
+    /** Make the error point to the given location, unless it already points somewhere more precise. */
+    pub fn at(self, location: &Location) -> CrushError {
+        CrushError { location: self.location.or_else(|| Some(location.clone())), ..self }
+    }
 }
 
 pub type CrushResult<T> = Result<T, CrushError>;
 
 pub fn block_error<T>() -> Result<T, CrushError> {
-    Err(CrushError {
-        message: String::from("Internal error: Tried to call blocking code in a thread that may not block"),
-        kind: BlockError,
-    })
+    Err(CrushError::new(BlockError, "Internal error: Tried to call blocking code in a thread that may not block"))
 }
 
 pub fn send_error<T>() -> Result<T, CrushError> {
-    Err(CrushError {
-        message: String::from("Tried to send data to a command that is no longer listening. This is almost normal behaviour and can be safely ignored."),
-        kind: SendError,
-    })
+    Err(CrushError::new(SendError, "Tried to send data to a command that is no longer listening. This is almost normal behaviour and can be safely ignored."))
+}
+
+pub fn exit_status_error<T>(code: i32) -> Result<T, CrushError> {
+    Err(CrushError::new(ExitStatus(code), &format!("Command exited with status {}", code)))
 }
 
 pub fn argument_error<T>(message: &str) -> Result<T, CrushError> {
-    Err(CrushError {
-        message: String::from(message),
-        kind: InvalidArgument,
-    })
+    Err(CrushError::new(InvalidArgument, message))
 }
 
 pub fn data_error<T>(message: &str) -> Result<T, CrushError> {
-    Err(CrushError {
-        message: String::from(message),
-        kind: InvalidData,
-    })
+    Err(CrushError::new(InvalidData, message))
 }
 
 pub fn error<T>(message: &str) -> Result<T, CrushError> {
-    Err(CrushError {
-        message: String::from(message),
-        kind: GenericError,
-    })
+    Err(CrushError::new(GenericError, message))
 }
 
 pub fn to_crush_error<T, E: Error>(result: Result<T, E>) -> Result<T, CrushError> {
@@ -67,3 +141,91 @@ pub fn mandate<T>(result: Option<T>, msg: &str) -> Result<T, CrushError> {
         None => error(msg),
     }
 }
+
+/**
+The number of characters that need to be inserted, removed, replaced or swapped with their
+neighbour to turn one string into the other.
+*/
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut d = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in d.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    d[0] = (0..=b.len()).collect();
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = if a[i - 1] == b[j - 1] { 0 } else { 1 };
+            d[i][j] = (d[i - 1][j] + 1).min(d[i][j - 1] + 1).min(d[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d[i][j] = d[i][j].min(d[i - 2][j - 2] + 1);
+            }
+        }
+    }
+    d[a.len()][b.len()]
+}
+
+/**
+The candidates that are most similar to a name that was not found, as long as they are similar
+enough that the name could be a typo of them.
+*/
+pub fn similar<'a>(name: &str, candidates: impl IntoIterator<Item=&'a str>) -> Vec<&'a str> {
+    let max_distance = (name.chars().count() / 3).max(1);
+    let mut res: Vec<(usize, &str)> = candidates.into_iter()
+        .map(|candidate| (edit_distance(name, candidate), candidate))
+        .filter(|(distance, candidate)| *distance <= max_distance && *candidate != name)
+        .collect();
+    res.sort();
+    res.dedup();
+    let closest = res.first().map(|(distance, _)| *distance);
+    res.into_iter()
+        .take_while(|(distance, _)| Some(*distance) == closest)
+        .take(3)
+        .map(|(_, candidate)| candidate)
+        .collect()
+}
+
+/**
+The message for something that was not found, e.g. `Unknown command name sotr, did you mean
+sort?`, with suggestions taken from the names that were available.
+*/
+pub fn not_found<'a>(message: &str, name: &str, candidates: impl IntoIterator<Item=&'a str>) -> String {
+    match similar(name, candidates).as_slice() {
+        [] => format!("{} {}", message, name),
+        [candidate] => format!("{} {}, did you mean {}?", message, name, candidate),
+        [first @ .., last] => format!("{} {}, did you mean {} or {}?", message, name, first.join(", "), last),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn suggestions() {
+        assert_eq!(edit_distance("sotr", "sort"), 1);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("", "ls"), 2);
+        assert_eq!(similar("sotr", vec!["sort", "sum", "select"]), vec!["sort"]);
+        assert_eq!(similar("cuont", vec!["count", "amount", "cont"]), vec!["cont", "count"]);
+        assert!(similar("x", vec!["find", "ls"]).is_empty());
+        assert_eq!(not_found("Unknown command name", "hed", vec!["head", "heed", "help"]),
+                   "Unknown command name hed, did you mean head or heed?");
+        assert_eq!(not_found("Unknown argument", "zzz", vec!["count"]), "Unknown argument zzz");
+    }
+
+    #[test]
+    fn locations() {
+        let source = Source::new(Some("a.crush"), "echo 1\nfor (seq 3) {\n  ä ls\n}");
+        assert_eq!(source.line_column(0), (1, 1));
+        assert_eq!(source.line_column(7), (2, 1));
+        let location = Location::new(&source, 26, 28);
+        assert_eq!(location.text(), "ls");
+        assert_eq!(location.to_string(), "a.crush:3:5");
+        assert_eq!(Location::new(&Source::new(None, "ls"), 0, 2).to_string(), "line 1, column 1");
+        assert!(location != Location::new(&Source::new(Some("a.crush"), &source.text), 26, 28));
+        let err = CrushError::new(GenericError, "failed").at(&location).at(&Location::new(&source, 0, 6));
+        assert!(err.location == Some(location));
+    }
+}
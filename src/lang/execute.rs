@@ -1,19 +1,71 @@
-use crate::lang::errors::{CrushResult, to_crush_error, argument_error, CrushError};
+use crate::lang::errors::{CrushResult, to_crush_error, argument_error, CrushError, Source};
 use crate::lang::printer::Printer;
 use crate::lang::scope::Scope;
 use std::{fs, thread};
-use crate::lang::parser::parse;
+use crate::lang::parser::{parse, parse_source};
+use crate::lang::ast::{CommandNode, JobNode, Node};
+use crate::lang::job::Job;
 use crate::lang::execution_context::{JobContext, ExecutionContext};
-use crate::lang::stream::{empty_channel, ValueSender, channels};
+use crate::lang::stream::{empty_channel, ValueSender, ValueReceiver, channels, streams};
 use std::path::Path;
 use crate::lang::serialization::{deserialize, serialize};
 use crate::lang::value::Value;
+use crate::lang::job::JobStatus;
+use crate::lang::table::Table;
 use std::io::Write;
+use std::sync::Arc;
 
-pub fn file(global_env: Scope, filename: &Path, printer: &Printer, output: &ValueSender) -> CrushResult<()> {
+/**
+The largest number of rows of a table stream that is kept around in order to be bound to
+the `_` variable. The output of pipelines producing more rows than this is not bound.
+*/
+const MAX_BOUND_ROWS: usize = 10_000;
+
+/** Execute a file. Errors point to where in the file they happened. */
+pub fn file(global_env: Scope, filename: &Path, printer: &Printer, output: &ValueSender) -> CrushResult<JobStatus> {
     let cmd = to_crush_error(fs::read_to_string(filename))?;
-    string(global_env, &cmd.as_str(), printer, output);
-    Ok(())
+    Ok(source(global_env, &Source::new(Some(&filename.to_string_lossy()), &cmd), printer, output))
+}
+
+fn is_args_parse(command: &CommandNode) -> bool {
+    match &command.expressions[0] {
+        Node::GetAttr(namespace, name) => name == "parse" && matches!(namespace.as_ref(), Node::Label(n) if n == "args"),
+        _ => false,
+    }
+}
+
+fn is_assignment(job: &JobNode) -> bool {
+    job.commands.len() == 1 && matches!(job.commands[0].expressions[0], Node::Assignment(..))
+}
+
+/**
+Run only the first args:parse command at the top level of a file, for generating the
+documentation of a script without running any other part of it. The assignments before it
+are run first, since they usually define the closure whose arguments are parsed. Returns
+None if the file has no such command, and otherwise the status of the last job that was
+run, whose errors have already been shown to the user.
+*/
+pub fn args_parse(global_env: Scope, filename: &Path, printer: &Printer, output: &ValueSender) -> CrushResult<Option<JobStatus>> {
+    let cmd = to_crush_error(fs::read_to_string(filename))?;
+    let tree = parse_source(&Source::new(Some(&filename.to_string_lossy()), &cmd))?;
+    let position = tree.jobs.iter()
+        .position(|job| job.commands.first().map(is_args_parse).unwrap_or(false));
+    let position = match position {
+        Some(position) => position,
+        None => return Ok(None),
+    };
+    let jobs = tree.jobs[..position].iter()
+        .filter(|job| is_assignment(job))
+        .chain(std::iter::once(&tree.jobs[position]));
+    let mut status = JobStatus::new();
+    for job in jobs {
+        status = Job::new(vec![job.commands[0].generate(&global_env)?]).run(JobContext::new(
+            empty_channel(), output.clone(), global_env.clone(), printer.clone()));
+        if !status.is_success() {
+            break;
+        }
+    }
+    Ok(Some(status))
 }
 
 pub fn pup(env: Scope, buf: &Vec<u8>, printer: &Printer) -> CrushResult<()> {
@@ -52,21 +104,129 @@ pub fn pup(env: Scope, buf: &Vec<u8>, printer: &Printer) -> CrushResult<()> {
     }
 }
 
-pub fn string(global_env: Scope, s: &str, printer: &Printer, output: &ValueSender) {
-    match parse(s, &global_env) {
+/**
+Execute a job list. The returned status is that of the last job that was run, which is what
+the exit status of a script reflects.
+*/
+pub fn string(global_env: Scope, s: &str, printer: &Printer, output: &ValueSender) -> JobStatus {
+    source(global_env, &Source::new(None, s), printer, output)
+}
+
+fn source(global_env: Scope, source: &Arc<Source>, printer: &Printer, output: &ValueSender) -> JobStatus {
+    let mut status = JobStatus::new();
+    match parse(source, &global_env) {
         Ok(jobs) => {
             for job_definition in jobs {
-                match job_definition.invoke(JobContext::new(
-                    empty_channel(), output.clone(), global_env.clone(), printer.clone())) {
-                    Ok(handle) => {
-                        handle.join(&printer);
-                    }
-                    Err(e) => printer.crush_error(e),
+                if job_definition.condition().should_run(status.is_success()) {
+                    status = job_definition.run(JobContext::new(
+                        empty_channel(), output.clone(), global_env.clone(), printer.clone()));
                 }
             }
         }
         Err(error) => {
+            status.fail(&error);
             printer.crush_error(error);
         }
     }
+    status
+}
+
+fn bind_result(env: &Scope, value: Value) -> CrushResult<()> {
+    if let Some(previous) = env.remove_str("_")? {
+        env.redeclare("__", previous)?;
+    }
+    env.redeclare("_", value)
+}
+
+/**
+Pass on the output of a pipeline to the pretty printer, keeping a copy of it that is bound
+to the `_` variable once the pipeline is done. Binary streams and empty values are not
+bound, so that e.g. running cd doesn't replace the last interesting result.
+*/
+fn forward_and_bind(env: Scope, input: ValueReceiver, output: ValueSender) -> CrushResult<()> {
+    while let Ok(value) = input.recv() {
+        match value {
+            Value::TableStream(stream) => {
+                let types = stream.types().to_vec();
+                let (forward, forwarded) = streams(types.clone());
+                output.send(Value::TableStream(forwarded))?;
+                let mut rows = Some(Vec::new());
+                while let Ok(row) = stream.recv() {
+                    rows = rows.filter(|r| r.len() < MAX_BOUND_ROWS);
+                    if let Some(rows) = &mut rows {
+                        rows.push(row.clone());
+                    }
+                    // The pretty printer has to be able to show rows as they arrive,
+                    // even when the pipeline pauses before sending the next one
+                    if forward.send(row).and_then(|_| forward.flush()).is_err() {
+                        break;
+                    }
+                }
+                if let Some(rows) = rows {
+                    bind_result(&env, Value::Table(Table::new(types, rows)))?;
+                }
+            }
+            Value::BinaryStream(_) | Value::Empty() => output.send(value)?,
+            value => {
+                bind_result(&env, value.clone())?;
+                output.send(value)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/**
+Execute a command entered interactively. The output of the command is bound to the `_`
+variable, and the previous output to `__`, so that it can be refined without running the
+whole pipeline again.
+*/
+pub fn interactive(global_env: Scope, s: &str, printer: &Printer, output: &ValueSender) -> JobStatus {
+    let (sender, receiver) = channels();
+    let env = global_env.clone();
+    let forward_output = output.clone();
+    let forward_printer = printer.clone();
+    let forwarder = thread::Builder::new().name("result-binder".to_string()).spawn(move || {
+        forward_printer.handle_error(forward_and_bind(env, receiver, forward_output));
+    });
+    let status = string(global_env, s, printer, &sender);
+    drop(sender);
+    match forwarder {
+        Ok(handle) => {
+            let _ = handle.join();
+        }
+        Err(e) => printer.handle_error::<()>(to_crush_error(Err(e))),
+    }
+    status
+}
+
+/**
+The prompt to show before reading a command. If the variable prompt is a command, it is run
+and its output is used as the prompt, so that the prompt can show e.g. the working directory,
+the status of the previous job or the current git branch.
+*/
+pub fn prompt(env: &Scope, printer: &Printer) -> String {
+    let default = "crush> ".to_string();
+    let command = match env.get("prompt") {
+        Ok(Some(Value::Command(command))) => command,
+        _ => return default,
+    };
+    let (sender, receiver) = channels();
+    let result = command.invoke(ExecutionContext {
+        input: empty_channel(),
+        output: sender,
+        arguments: vec![],
+        env: env.clone(),
+        this: None,
+        printer: printer.clone(),
+    });
+    if let Err(e) = result {
+        printer.crush_error(e);
+        return default;
+    }
+    match receiver.recv() {
+        Ok(Value::String(s)) => s,
+        Ok(Value::Empty()) | Err(_) => default,
+        Ok(value) => value.to_string(),
+    }
 }
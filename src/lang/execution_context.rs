@@ -1,4 +1,4 @@
-use crate::lang::errors::{CrushResult, argument_error, error, to_crush_error};
+use crate::lang::errors::{CrushResult, argument_error, error, to_crush_error, Location};
 use crate::lang::argument::Argument;
 use crate::lang::value::{Value, ValueType};
 use crate::util::replace::Replace;
@@ -14,7 +14,8 @@ use regex::Regex;
 use chrono::{DateTime, Local, Duration};
 use crate::lang::table::{Table, TableReader};
 use crate::lang::printer::Printer;
-use crate::lang::job::JobJoinHandle;
+use crate::lang::style::Styled;
+use crate::lang::job::{JobJoinHandle, JobStatus};
 use crate::lang::binary::{BinaryReader, binary_channel};
 use std::io::Write;
 use std::fs::File;
@@ -186,6 +187,7 @@ pub struct JobContext {
     pub output: ValueSender,
     pub env: Scope,
     pub printer: Printer,
+    pub status: JobStatus,
 }
 
 impl JobContext {
@@ -200,6 +202,7 @@ impl JobContext {
             output,
             env,
             printer,
+            status: JobStatus::new(),
         }
     }
 
@@ -212,6 +215,15 @@ impl JobContext {
             output,
             env: self.env.clone(),
             printer: self.printer.clone(),
+            status: self.status.clone(),
+        }
+    }
+
+    /** The context of a command of this job written at the given location. */
+    pub fn at(&self, location: &Location) -> JobContext {
+        JobContext {
+            printer: self.printer.at(location),
+            ..self.clone()
         }
     }
 
@@ -219,6 +231,16 @@ impl JobContext {
         CompileContext::new(self.env.clone(), self.printer.clone())
     }
 
+    /**
+    Report an error from one of the commands of this job, marking the job as failed.
+    */
+    pub fn handle_error<T>(&self, result: CrushResult<T>) {
+        if let Err(e) = &result {
+            self.status.fail(e);
+        }
+        self.printer.handle_error(result);
+    }
+
     pub fn execution_context(
         &self,
         arguments: Vec<Argument>,
@@ -311,6 +333,8 @@ pub trait This {
     fn table_stream(self) -> CrushResult<InputStream>;
     fn binary(self) -> CrushResult<Vec<u8>>;
     fn scope(self) -> CrushResult<Scope>;
+    fn command(self) -> CrushResult<Command>;
+    fn styled(self) -> CrushResult<Styled>;
 }
 
 macro_rules! this_method {
@@ -343,6 +367,8 @@ impl This for Option<Value> {
     this_method!(time, DateTime<Local>, Time, "time");
     this_method!(scope, Scope, Scope, "scope");
     this_method!(table_stream, InputStream, TableStream, "table_stream");
+    this_method!(command, Command, Command, "command");
+    this_method!(styled, Styled, Styled, "styled");
 
     fn re(mut self) -> CrushResult<(String, Regex)> {
         match self.take() {
@@ -27,17 +27,21 @@ impl Files {
         self.had_entries
     }
 
+    pub fn as_slice(&self) -> &[PathBuf] {
+        &self.files
+    }
+
     pub fn into_vec(self) -> Vec<PathBuf> {
         self.files
     }
 
     pub fn reader(self, input: ValueReceiver) -> CrushResult<Box<dyn BinaryReader + Send + Sync>> {
         if !self.had_entries {
-println!("TRAFDFKDSAÖLFADSK");
             match input.recv()? {
                 Value::BinaryStream(b) => Ok(b),
                 Value::Binary(b) => Ok(BinaryReader::vec(&b)),
-                _ => argument_error("Expected either a file to read or binary pipe io"),
+                Value::String(s) => Ok(<dyn BinaryReader>::vec(&s.into_bytes())),
+                _ => argument_error("Expected either a file to read or binary pipe io or a string"),
             }
         } else {
             BinaryReader::paths(self.files)
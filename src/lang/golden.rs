@@ -0,0 +1,172 @@
+/**
+Golden testing of output, where the output of a script or a value is compared against a file
+with the expected output that is checked in next to it. When the output changes on purpose,
+running in update mode rewrites the golden files, and the change shows up in the diff of the
+commit.
+
+Scripts are tested by running `crush test`, which runs every script named NAME.crush in the
+given files and directories, or in the tests directory, and compares what it writes to
+standard output with NAME.crush.output. Each script runs in a process of its own, so that
+scripts can change directory and set variables without affecting the others. They run in the
+UTC time zone, so that times without an explicit offset are shown the same way everywhere.
+With --update, the golden files are written instead.
+*/
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::lang::errors::{CrushResult, error, to_crush_error};
+use crate::lang::printer::Printer;
+
+/** The size values are formatted for in golden tests, regardless of the terminal. */
+#[cfg(test)]
+pub const WIDTH: usize = 80;
+#[cfg(test)]
+pub const HEIGHT: usize = 30;
+
+pub enum Outcome {
+    Passed,
+    Updated,
+    Failed(String),
+}
+
+/**
+A description of how the actual lines differ from the expected ones, with the line numbers of
+both, based on the longest common subsequence of lines.
+*/
+pub fn diff(expected: &str, actual: &str) -> String {
+    let a: Vec<&str> = expected.lines().collect();
+    let b: Vec<&str> = actual.lines().collect();
+    // common[i][j] is the length of the longest common subsequence of a[i..] and b[j..]
+    let mut common = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            common[i][j] = if a[i] == b[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+    let mut res = String::new();
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        if i < a.len() && j < b.len() && a[i] == b[j] {
+            i += 1;
+            j += 1;
+        } else if j == b.len() || (i < a.len() && common[i + 1][j] >= common[i][j + 1]) {
+            res.push_str(&format!("-{:4}: {}\n", i + 1, a[i]));
+            i += 1;
+        } else {
+            res.push_str(&format!("+{:4}: {}\n", j + 1, b[j]));
+            j += 1;
+        }
+    }
+    if res.is_empty() && expected != actual {
+        res.push_str("The outputs differ only in line endings\n");
+    }
+    res
+}
+
+/** Compares output with a golden file, or writes the golden file in update mode. */
+pub fn check(golden: &Path, actual: &str, update: bool) -> CrushResult<Outcome> {
+    let expected = fs::read_to_string(golden).ok();
+    if expected.as_deref() == Some(actual) {
+        return Ok(Outcome::Passed);
+    }
+    if update {
+        if let Some(dir) = golden.parent() {
+            to_crush_error(fs::create_dir_all(dir))?;
+        }
+        to_crush_error(fs::write(golden, actual))?;
+        return Ok(Outcome::Updated);
+    }
+    match expected {
+        Some(expected) => Ok(Outcome::Failed(diff(&expected, actual))),
+        None => Ok(Outcome::Failed(format!("Missing golden file {}\n", golden.display()))),
+    }
+}
+
+fn scripts(paths: &[String]) -> CrushResult<Vec<PathBuf>> {
+    let mut res = Vec::new();
+    for path in paths {
+        let path = PathBuf::from(path);
+        if path.is_dir() {
+            let mut found: Vec<PathBuf> = to_crush_error(fs::read_dir(&path))?
+                .filter_map(|entry| entry.ok().map(|e| e.path()))
+                .filter(|p| p.extension().map(|e| e == "crush").unwrap_or(false))
+                .collect();
+            found.sort();
+            res.append(&mut found);
+        } else {
+            res.push(path);
+        }
+    }
+    Ok(res)
+}
+
+/**
+Runs the golden tests of the scripts in the given files and directories, and returns true if
+all of them passed.
+*/
+pub fn test(paths: &[String], update: bool, printer: &Printer) -> CrushResult<bool> {
+    let paths = if paths.is_empty() { vec!["tests".to_string()] } else { paths.to_vec() };
+    let exe = to_crush_error(std::env::current_exe())?;
+    let mut failed = 0;
+    let scripts = scripts(&paths)?;
+    if scripts.is_empty() {
+        return error("No scripts to test");
+    }
+    for script in &scripts {
+        let output = to_crush_error(Command::new(&exe).arg(script).env("TZ", "UTC").output())?;
+        let actual = String::from_utf8_lossy(&output.stdout);
+        let mut golden = script.clone().into_os_string();
+        golden.push(".output");
+        match check(Path::new(&golden), &actual, update)? {
+            Outcome::Passed => {}
+            Outcome::Updated => printer.line(&format!("Updated {}", script.display())),
+            Outcome::Failed(diff) => {
+                failed += 1;
+                printer.line(&format!("Failed {}", script.display()));
+                printer.line(diff.trim_end());
+            }
+        }
+    }
+    printer.line(&format!("{} of {} tests passed", scripts.len() - failed, scripts.len()));
+    Ok(failed == 0)
+}
+
+/** The lines the pretty printer prints for a value, formatted for the golden size. */
+#[cfg(test)]
+pub fn render(value: crate::lang::value::Value) -> String {
+    let (printer, capture) = crate::lang::printer::capture_sized(WIDTH, HEIGHT);
+    crate::lang::pretty_printer::PrettyPrinter::new(printer).print_value(value);
+    let mut res = capture.lines().join("\n");
+    res.push('\n');
+    res
+}
+
+/**
+Compares output with the golden file tests/golden/NAME.txt. Set the environment variable
+CRUSH_UPDATE_GOLDEN to write the golden file instead.
+*/
+#[cfg(test)]
+pub fn assert_golden(name: &str, actual: &str) {
+    let golden = PathBuf::from("tests/golden").join(format!("{}.txt", name));
+    let update = std::env::var_os("CRUSH_UPDATE_GOLDEN").is_some();
+    if let Outcome::Failed(diff) = check(&golden, actual, update).unwrap() {
+        panic!("Output differs from {}:\n{}", golden.display(), diff);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diffs() {
+        assert_eq!(diff("a\nb\nc\n", "a\nb\nc\n"), "");
+        assert_eq!(diff("a\nb\nc\n", "a\nx\nc\nd\n"), "-   2: b\n+   2: x\n+   4: d\n");
+        assert_eq!(diff("a\n", "a"), "The outputs differ only in line endings\n");
+    }
+}
@@ -1,8 +1,16 @@
-
-
 pub trait Help {
     fn signature(&self) -> String;
     fn short_help(&self) -> String;
     fn long_help(&self) -> Option<String>;
 }
 
+/**
+The full help text about something, as shown by the help command and by --help: the signature,
+the short help and then the long help, if any.
+*/
+pub fn render(o: &dyn Help) -> String {
+    match o.long_help() {
+        None => format!("{}\n\n    {}", o.signature(), o.short_help()),
+        Some(long_help) => format!("{}\n\n    {}\n\n{}", o.signature(), o.short_help(), long_help),
+    }
+}
@@ -0,0 +1,160 @@
+use crate::lang::parser::{error_span, tokens, Token, TokenType};
+use crate::lang::style::{Color, Style};
+
+fn color(foreground: Color) -> Style {
+    Style { foreground: Some(foreground), ..Style::default() }
+}
+
+/** Whether a token is where a new command starts, e.g. at the start of the line or after a pipe. */
+fn starts_command(previous: Option<&str>) -> bool {
+    match previous {
+        None => true,
+        Some(text) => matches!(text, "|" | "||" | "&&" | "{" | "(" | "$(")
+            || text.starts_with(';')
+            || text.starts_with('\n'),
+    }
+}
+
+fn style(token_type: TokenType, text: &str, is_command: bool) -> Style {
+    match token_type {
+        TokenType::Label if is_command => Style { bold: true, ..color(Color::Green) },
+        TokenType::Label if text.contains(['%', '?']) => color(Color::Magenta),
+        TokenType::String | TokenType::Regex => color(Color::Yellow),
+        TokenType::Field | TokenType::Flag => color(Color::Cyan),
+        TokenType::Number => color(Color::Blue),
+        TokenType::Keyword => Style { bold: true, ..Style::default() },
+        TokenType::Comment => Style { dim: true, ..Style::default() },
+        TokenType::Error => Style { underline: true, ..color(Color::Red) },
+        _ => Style::default(),
+    }
+}
+
+/**
+The indices of the tokens that open and close each pair of brackets. Brackets inside of
+strings and comments are not tokens of their own, so they are never counted.
+*/
+fn brackets(tokens: &[Token]) -> Vec<(usize, usize)> {
+    let mut open = Vec::new();
+    let mut pairs = Vec::new();
+    for (idx, token) in tokens.iter().enumerate() {
+        match token.token_type {
+            TokenType::Open => open.push(idx),
+            TokenType::Close => if let Some(start) = open.pop() {
+                pairs.push((start, idx));
+            },
+            _ => {}
+        }
+    }
+    pairs
+}
+
+/**
+The bracket at the cursor, or just before it, along with the one it pairs up with.
+*/
+fn matching_brackets(tokens: &[Token], cursor: usize) -> Option<(usize, usize)> {
+    let pairs = brackets(tokens);
+    let find = |idx: usize| pairs.iter()
+        .find(|(open, close)| *open == idx || *close == idx)
+        .cloned();
+    tokens.iter().position(|t| t.start == cursor)
+        .and_then(find)
+        .or_else(|| tokens.iter().position(|t| t.end == cursor).and_then(find))
+}
+
+/**
+Render a line of code with escape sequences that color it the way it will be parsed. Commands,
+strings, fields, globs and comments each get a color of their own, the token where parsing
+fails is marked as an error, and if the cursor is next to a bracket, both it and the bracket
+it pairs up with are highlighted. The previous lines of a command that spans several lines are
+taken into account, but not rendered.
+*/
+pub fn highlight(previous: &str, line: &str, cursor: usize) -> String {
+    let offset = previous.len();
+    let text = format!("{}{}", previous, line);
+    let tokens = tokens(&text);
+    let error = error_span(&text);
+    let brackets = matching_brackets(&tokens, offset + cursor);
+    let mut res = String::with_capacity(line.len() * 2);
+    let mut position = offset;
+    let mut last = None;
+    for (idx, token) in tokens.iter().enumerate() {
+        if token.end > offset {
+            let start = token.start.max(offset);
+            let mut style = style(token.token_type, &text[token.start..token.end], starts_command(last));
+            if error.map(|(start, end)| start < token.end && token.start < end).unwrap_or(false) {
+                style = style.merge(&Style { underline: true, ..color(Color::Red) });
+            }
+            if brackets.map(|(open, close)| idx == open || idx == close).unwrap_or(false) {
+                style = style.merge(&Style { bold: true, ..color(Color::Blue) });
+            }
+            res.push_str(&text[position..start]);
+            res.push_str(&style.apply(&text[start..token.end]));
+            position = token.end;
+        }
+        if token.token_type != TokenType::Comment {
+            last = Some(&text[token.start..token.end]);
+        }
+    }
+    res.push_str(&text[position..]);
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn plain(line: &str) -> String {
+        let mut res = String::new();
+        let mut escape = false;
+        for c in line.chars() {
+            match c {
+                '\x1b' => escape = true,
+                'm' if escape => escape = false,
+                c if !escape => res.push(c),
+                _ => {}
+            }
+        }
+        res
+    }
+
+    #[test]
+    fn colors() {
+        let green = Style { bold: true, ..color(Color::Green) };
+        assert_eq!(
+            highlight("", "ls | where {^size > 3}", 0),
+            format!("{} | {} {{{} > {}}}",
+                    green.apply("ls"),
+                    green.apply("where"),
+                    color(Color::Cyan).apply("^size"),
+                    color(Color::Blue).apply("3")));
+        assert!(highlight("", "echo \"a {b\" %.txt", 0)
+            .contains(&format!("{} {}", color(Color::Yellow).apply("\"a {b\""), color(Color::Magenta).apply("%.txt"))));
+        for line in ["echo \"abc", "echo (1 + 2))", "a # comment\n\tb", "echo ä$ def", ""] {
+            assert_eq!(plain(&highlight("", line, line.len())), line);
+        }
+    }
+
+    #[test]
+    fn errors() {
+        let red = Style { underline: true, ..color(Color::Red) };
+        assert!(highlight("", "echo )", 0).ends_with(&red.apply(")")));
+        assert!(highlight("", "echo $", 0).ends_with(&red.apply("$")));
+        assert!(!highlight("", "echo (1 +", 0).contains(&red.escape()));
+        assert_eq!(highlight("for (seq 3) {\n  ", "echo \"a\"", 0), format!(
+            "{} {}", Style { bold: true, ..color(Color::Green) }.apply("echo"), color(Color::Yellow).apply("\"a\"")));
+        assert_eq!(highlight("echo \"a\n", "b\" }", 0), format!("{} {}", color(Color::Yellow).apply("b\""), red.apply("}")));
+    }
+
+    #[test]
+    fn matching() {
+        let line = "echo $(a \"(\" {b}) (c)";
+        let pairs = |cursor| matching_brackets(&tokens(line), cursor)
+            .map(|(open, close)| (tokens(line)[open].start, tokens(line)[close].start));
+        assert_eq!(pairs(5), Some((5, 16)));
+        assert_eq!(pairs(17), Some((5, 16)));
+        assert_eq!(pairs(15), Some((13, 15)));
+        assert_eq!(pairs(16), Some((5, 16)));
+        assert_eq!(pairs(10), None);
+        assert_eq!(pairs(8), None);
+    }
+}
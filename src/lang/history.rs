@@ -0,0 +1,198 @@
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Duration, Local, TimeZone};
+
+use crate::lang::errors::{CrushResult, to_crush_error};
+use crate::util::file::{config_dir, home};
+
+/** The history file is cut down to this many of the most recent entries when the shell starts. */
+pub const MAX_ENTRIES: usize = 10000;
+
+/**
+A command entered interactively. The history file has one entry per line, with the fields
+separated by tabs, and tabs, newlines and backslashes in the command escaped.
+*/
+pub struct Entry {
+    pub time: DateTime<Local>,
+    pub duration: Duration,
+    pub success: bool,
+    pub command: String,
+}
+
+pub fn file() -> CrushResult<PathBuf> {
+    Ok(config_dir()?.join("history"))
+}
+
+/** The plain history file of older versions of Crush, with one command per line. */
+pub fn legacy_file() -> CrushResult<PathBuf> {
+    Ok(home()?.join(".crush_history"))
+}
+
+fn escape(s: &str) -> String {
+    let mut res = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '\\' => res.push_str("\\\\"),
+            '\t' => res.push_str("\\t"),
+            '\n' => res.push_str("\\n"),
+            c => res.push(c),
+        }
+    }
+    res
+}
+
+fn unescape(s: &str) -> String {
+    let mut res = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            res.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('t') => res.push('\t'),
+            Some('n') => res.push('\n'),
+            Some(c) => res.push(c),
+            None => res.push('\\'),
+        }
+    }
+    res
+}
+
+impl Entry {
+    fn format(&self) -> String {
+        format!(
+            "{}\t{}\t{}\t{}\n",
+            self.time.timestamp(),
+            self.duration.num_milliseconds(),
+            if self.success { 1 } else { 0 },
+            escape(&self.command))
+    }
+
+    /** Parses a line of the history file, or returns None if it was e.g. cut short by a crash. */
+    fn parse(line: &str) -> Option<Entry> {
+        let mut fields = line.splitn(4, '\t');
+        let time = Local.timestamp_opt(fields.next()?.parse().ok()?, 0).single()?;
+        let duration = Duration::milliseconds(fields.next()?.parse().ok()?);
+        let success = fields.next()? == "1";
+        let command = unescape(fields.next()?);
+        Some(Entry { time, duration, success, command })
+    }
+}
+
+pub fn load(file: &Path) -> CrushResult<Vec<Entry>> {
+    if !file.exists() {
+        return Ok(Vec::new());
+    }
+    let content = to_crush_error(std::fs::read_to_string(file))?;
+    Ok(content.lines().filter_map(Entry::parse).collect())
+}
+
+/**
+Replace the history file with the specified entries. The entries are written to a new file
+that is then renamed, so that the history is never left half written.
+*/
+fn write(file: &Path, entries: &[Entry]) -> CrushResult<()> {
+    if let Some(dir) = file.parent() {
+        to_crush_error(std::fs::create_dir_all(dir))?;
+    }
+    let tmp = file.with_extension("tmp");
+    let content: String = entries.iter().map(Entry::format).collect();
+    to_crush_error(std::fs::write(&tmp, content))?;
+    to_crush_error(std::fs::rename(&tmp, file))
+}
+
+/**
+Copy the commands of a history file of an older version of Crush into a new history file. This
+only happens if there is no new history file yet, so it is done once. The time, duration and
+success of the old commands are unknown, so they all get the time the old file was last written.
+*/
+pub fn import(file: &Path, legacy: &Path) -> CrushResult<()> {
+    if file.exists() || !legacy.exists() {
+        return Ok(());
+    }
+    let time = to_crush_error(to_crush_error(std::fs::metadata(legacy))?.modified())?;
+    let content = to_crush_error(std::fs::read_to_string(legacy))?;
+    let entries: Vec<Entry> = content.lines()
+        // Newer versions of rustyline start the file with a version header
+        .filter(|line| !line.is_empty() && *line != "#V2")
+        .map(|line| Entry {
+            time: DateTime::<Local>::from(time),
+            duration: Duration::zero(),
+            success: true,
+            command: line.to_string(),
+        })
+        .collect();
+    write(file, &entries)
+}
+
+/**
+Load the history, and if it has grown past the specified number of entries, remove the oldest
+ones from the file.
+*/
+pub fn load_limited(file: &Path, max: usize) -> CrushResult<Vec<Entry>> {
+    let mut entries = load(file)?;
+    if entries.len() > max {
+        entries.drain(..entries.len() - max);
+        write(file, &entries)?;
+    }
+    Ok(entries)
+}
+
+/**
+Append an entry to the history file. Every entry is written using a single write, so that
+several shells can share the same history file.
+*/
+pub fn append(file: &Path, entry: &Entry) -> CrushResult<()> {
+    if let Some(dir) = file.parent() {
+        to_crush_error(std::fs::create_dir_all(dir))?;
+    }
+    let mut out = to_crush_error(OpenOptions::new().create(true).append(true).open(file))?;
+    to_crush_error(out.write_all(entry.format().as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entries() {
+        let entry = Entry {
+            time: Local.timestamp(1600000000, 0),
+            duration: Duration::milliseconds(1500),
+            success: false,
+            command: "echo \"a\\tb\"\n\tls".to_string(),
+        };
+        let parsed = Entry::parse(entry.format().trim_end_matches('\n')).unwrap();
+        assert_eq!(parsed.time, entry.time);
+        assert_eq!(parsed.duration, entry.duration);
+        assert!(!parsed.success);
+        assert_eq!(parsed.command, entry.command);
+        assert!(Entry::parse("ls").is_none());
+        assert!(Entry::parse("x\t1\t1\tls").is_none());
+    }
+
+    #[test]
+    fn import_and_limit() {
+        let dir = std::env::temp_dir().join(format!("crush-history-test-{}", std::process::id()));
+        let file = dir.join("config").join("history");
+        let legacy = dir.join(".crush_history");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(&legacy, "ls\n\necho 1\npwd\n").unwrap();
+
+        import(&file, &legacy).unwrap();
+        let commands = |entries: Vec<Entry>| entries.into_iter().map(|e| e.command).collect::<Vec<_>>();
+        assert_eq!(commands(load(&file).unwrap()), vec!["ls", "echo 1", "pwd"]);
+
+        // The old file is only imported if there is no history yet
+        std::fs::write(&legacy, "cd\n").unwrap();
+        import(&file, &legacy).unwrap();
+        assert_eq!(commands(load(&file).unwrap()), vec!["ls", "echo 1", "pwd"]);
+
+        assert_eq!(commands(load_limited(&file, 2).unwrap()), vec!["echo 1", "pwd"]);
+        assert_eq!(commands(load(&file).unwrap()), vec!["echo 1", "pwd"]);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -1,9 +1,12 @@
 use crate::lang::stream::{channels};
 use crate::lang::{command_invocation::CommandInvocation};
-use crate::lang::errors::{ CrushResult};
+use crate::lang::errors::{CrushResult, CrushError, Kind, error};
+use crate::lang::errors::Kind::ExitStatus;
 use std::thread::JoinHandle;
 use crate::lang::execution_context::{JobContext, CompileContext};
-use crate::lang::printer::Printer;
+use std::sync::{Arc, Mutex};
+use crate::lang::value::Value;
+use crate::lang::r#struct::Struct;
 
 pub enum JobJoinHandle {
     Many(Vec<JobJoinHandle>),
@@ -11,29 +14,136 @@ pub enum JobJoinHandle {
 }
 
 impl JobJoinHandle {
-    pub fn join(self, printer: &Printer) {
+    /**
+    Wait for every command to exit. A command that panicked has not reported any error,
+    so that is a failure of its own.
+    */
+    pub fn join(self) -> CrushResult<()> {
         match self {
             JobJoinHandle::Async(a) => match a.join() {
-                Ok(_) => {},
-                Err(_) => printer.error("Unknown error while waiting for command to exit"),
+                Ok(_) => Ok(()),
+                Err(_) => error("The command panicked"),
             },
             JobJoinHandle::Many(v) => {
+                let mut result = Ok(());
                 for j in v {
-                    j.join(printer);
+                    let res = j.join();
+                    if result.is_ok() {
+                        result = res;
+                    }
                 }
+                result
             }
         }
     }
 }
 
+/**
+The outcome of a job. Every command in the job shares the same status, and the job
+has failed if any of them reported an error.
+*/
+#[derive(Clone)]
+pub struct JobStatus {
+    failure: Arc<Mutex<Option<(i32, String)>>>,
+}
+
+impl JobStatus {
+    pub fn new() -> JobStatus {
+        JobStatus { failure: Arc::from(Mutex::new(None)) }
+    }
+
+    pub fn fail(&self, err: &CrushError) {
+        let code = match err.kind {
+            Kind::SendError => return,
+            Kind::ExitStatus(code) => code,
+            _ => 1,
+        };
+        if let Ok(mut failure) = self.failure.lock() {
+            *failure = Some((code, err.message.clone()));
+        }
+    }
+
+    pub fn is_success(&self) -> bool {
+        self.failure.lock().map(|f| f.is_none()).unwrap_or(false)
+    }
+
+    /**
+    The failure of the job, if any. The error has already been shown to the user, so it
+    will not be printed again.
+    */
+    pub fn result(&self) -> CrushResult<()> {
+        match self.failure.lock() {
+            Ok(failure) => match failure.as_ref() {
+                None => Ok(()),
+                Some((code, message)) => Err(CrushError::new(ExitStatus(*code), message)),
+            }
+            Err(_) => Err(CrushError::new(ExitStatus(1), "Unknown job status")),
+        }
+    }
+
+    /** The exit status of the job, as it would be reported to the parent process. */
+    pub fn code(&self) -> i32 {
+        match self.failure.lock() {
+            Ok(failure) => failure.as_ref().map(|(code, _)| *code).unwrap_or(0),
+            Err(_) => 1,
+        }
+    }
+
+    pub fn value(&self) -> Value {
+        let (code, message) = match self.result() {
+            Ok(()) => (0, String::new()),
+            Err(e) => match e.kind {
+                ExitStatus(code) => (code, e.message),
+                _ => (1, e.message),
+            }
+        };
+        Value::Struct(Struct::new(
+            vec![
+                ("success".to_string(), Value::Bool(code == 0)),
+                ("code".to_string(), Value::Integer(code as i128)),
+                ("message".to_string(), Value::string(message.as_str())),
+            ],
+            None))
+    }
+}
+
+/**
+Decides if a job in a job list is run, based on the success of the previous job.
+*/
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum JobCondition {
+    Always,
+    OnSuccess,
+    OnFailure,
+}
+
+impl JobCondition {
+    pub fn should_run(&self, previous_success: bool) -> bool {
+        match self {
+            JobCondition::Always => true,
+            JobCondition::OnSuccess => previous_success,
+            JobCondition::OnFailure => !previous_success,
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct Job {
     commands: Vec<CommandInvocation>,
+    condition: JobCondition,
 }
 
 impl Job {
     pub fn new(commands: Vec<CommandInvocation>) -> Job {
-        Job { commands }
+        Job { commands, condition: JobCondition::Always }
+    }
+
+    pub fn with_condition(commands: Vec<CommandInvocation>, condition: JobCondition) -> Job {
+        Job { commands, condition }
+    }
+
+    pub fn condition(&self) -> JobCondition {
+        self.condition
     }
 
     pub fn can_block(&self, context: &mut CompileContext) -> bool {
@@ -65,6 +175,20 @@ impl Job {
         Ok(JobJoinHandle::Many(calls))
     }
 
+    /**
+    Invoke the job and wait for it to finish. The result is also stored in the `status`
+    variable of the job's scope.
+    */
+    pub fn run(&self, context: JobContext) -> JobStatus {
+        match self.invoke(context.clone()) {
+            Ok(handle) => context.handle_error(handle.join()),
+            Err(e) => context.handle_error::<()>(Err(e)),
+        }
+        context.printer.handle_error(context.env.remove_temporary_files());
+        context.printer.handle_error(context.env.redeclare("status", context.status.value()));
+        context.status
+    }
+
     pub fn as_string(&self) -> Option<String> {
         if self.commands.len() != 1 {
             return None;
@@ -79,3 +203,21 @@ impl ToString for Job {
         self.commands.iter().map(|c| c.to_string()).collect::<Vec<String>>().join("|")
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn panicking_command_fails_the_job() {
+        let handle = JobJoinHandle::Many(vec![
+            JobJoinHandle::Async(thread::spawn(|| panic!("boom"))),
+            JobJoinHandle::Async(thread::spawn(|| {})),
+        ]);
+        let status = JobStatus::new();
+        status.fail(&handle.join().err().unwrap());
+        assert_eq!(status.code(), 1);
+        assert!(JobJoinHandle::Async(thread::spawn(|| {})).join().is_ok());
+    }
+}
@@ -253,4 +253,8 @@ impl CrushStream for ListReader {
     fn types(&self) -> &[ColumnType] {
         &self.types
     }
+
+    fn row_count(&self) -> Option<usize> {
+        Some(self.list.len().saturating_sub(self.idx))
+    }
 }
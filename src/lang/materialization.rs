@@ -0,0 +1,75 @@
+use crate::lang::errors::{CrushResult, argument_error};
+use crate::lang::value::Value;
+use crate::lang::binary::BinaryReader;
+
+/**
+What happens to a stream when it is assigned to a variable.
+*/
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Policy {
+    /** The stream is stored as is, and can only be read once. */
+    Lazy,
+    /** The stream is read right away and replaced by a table or binary value. */
+    Materialize,
+    /** The stream is spooled as it is read, and every use of the variable reads it from the start. */
+    Replay,
+}
+
+impl Policy {
+    pub fn from_name(name: &str) -> CrushResult<Policy> {
+        match name {
+            "lazy" => Ok(Policy::Lazy),
+            "materialize" => Ok(Policy::Materialize),
+            "replay" => Ok(Policy::Replay),
+            _ => argument_error(format!("Unknown materialization policy {}", name).as_str()),
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Policy::Lazy => "lazy",
+            Policy::Materialize => "materialize",
+            Policy::Replay => "replay",
+        }
+    }
+}
+
+/**
+The materialization policy for each kind of stream. Policies that are not set are inherited
+from the calling scope, and streams are lazy if no scope sets a policy.
+*/
+#[derive(Clone, Default, Debug)]
+pub struct MaterializationPolicy {
+    pub table_stream: Option<Policy>,
+    pub binary_stream: Option<Policy>,
+}
+
+impl MaterializationPolicy {
+    /** Fill in the policies not set in this one from the specified, outer, policy. */
+    pub fn merge(&self, outer: &MaterializationPolicy) -> MaterializationPolicy {
+        MaterializationPolicy {
+            table_stream: self.table_stream.or(outer.table_stream),
+            binary_stream: self.binary_stream.or(outer.binary_stream),
+        }
+    }
+
+    /** Convert a value that is about to be assigned to a variable according to this policy. */
+    pub fn apply(&self, value: Value) -> CrushResult<Value> {
+        Ok(match value {
+            Value::TableStream(s) => match self.table_stream.unwrap_or(Policy::Lazy) {
+                Policy::Lazy => Value::TableStream(s),
+                Policy::Materialize => Value::TableStream(s).materialize(),
+                Policy::Replay => Value::TableStream(s.replayable()?),
+            },
+            Value::BinaryStream(s) => match self.binary_stream.unwrap_or(Policy::Lazy) {
+                Policy::Lazy => Value::BinaryStream(s),
+                Policy::Materialize => Value::BinaryStream(s).materialize(),
+                Policy::Replay => match Value::BinaryStream(s).materialize() {
+                    Value::Binary(b) => Value::BinaryStream(<dyn BinaryReader>::vec(&b)),
+                    v => v,
+                },
+            },
+            v => v,
+        })
+    }
+}
@@ -19,5 +19,17 @@ pub mod help;
 pub mod execution_context;
 pub mod serialization;
 pub mod execute;
+pub mod module;
+pub mod history;
+pub mod completion;
+pub mod highlight;
 pub mod ordered_string_map;
 pub mod files;
+pub mod process_limits;
+pub mod process_environment;
+pub mod recording;
+pub mod materialization;
+pub mod spool;
+pub mod style;
+pub mod wire;
+pub mod golden;
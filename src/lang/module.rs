@@ -0,0 +1,141 @@
+use std::path::{Path, PathBuf};
+
+use crate::lang::errors::{CrushResult, error, mandate, to_crush_error};
+use crate::lang::execute;
+use crate::lang::printer::Printer;
+use crate::lang::scope::{Scope, ScopeLoader};
+use crate::lang::stream::{black_hole, ValueSender};
+
+type Loader = Box<dyn Send + FnOnce(&mut ScopeLoader) -> CrushResult<()>>;
+
+/**
+The files being loaded once the given file starts loading. Each load carries the chain of files
+that led to it, so that loads running at the same time don't see each other, and a file that
+is already in the chain is a cyclic dependency.
+*/
+fn start_loading(loading: &[PathBuf], file: &Path) -> CrushResult<Vec<PathBuf>> {
+    let file = to_crush_error(file.canonicalize())?;
+    let mut chain = loading.to_vec();
+    chain.push(file.clone());
+    if let Some(idx) = loading.iter().position(|f| *f == file) {
+        let cycle = chain[idx..].iter()
+            .map(|f| f.to_string_lossy().to_string())
+            .collect::<Vec<_>>();
+        return error(format!("Cyclic dependency between files: {}", cycle.join(" -> ")).as_str());
+    }
+    Ok(chain)
+}
+
+/** The name of the namespace for a file or directory, i.e. its name without the .crush suffix. */
+pub fn name(path: &Path) -> Option<String> {
+    let name = path.file_name()?.to_str()?;
+    Some(name.strip_suffix(".crush").unwrap_or(name).to_string())
+}
+
+fn is_module(path: &Path) -> bool {
+    path.is_dir() || path.extension().map(|e| e == "crush").unwrap_or(false)
+}
+
+/**
+Runs a file and copies the variables it declares into the namespace being loaded. The code of
+the file runs in a child of the home scope, i.e. the scope the module was declared in.
+*/
+fn load_file(env: &mut ScopeLoader, file: &Path, home: &Scope, loading: &[PathBuf], printer: &Printer) -> CrushResult<()> {
+    let tmp_env = home.create_child(home, false);
+    tmp_env.set_loading(Some(start_loading(loading, file)?));
+    execute::file(tmp_env.clone(), file, printer, &black_hole())?;
+    for (k, v) in tmp_env.export()?.mapping {
+        if k != "status" {
+            env.declare(&k, v)?;
+        }
+    }
+    Ok(())
+}
+
+fn load_directory(env: &mut ScopeLoader, dir: &Path, home: &Scope, loading: &[PathBuf], printer: &Printer) -> CrushResult<()> {
+    let mut entries = to_crush_error(std::fs::read_dir(dir))?
+        .map(|e| to_crush_error(e).map(|e| e.path()))
+        .collect::<CrushResult<Vec<_>>>()?;
+    entries.sort();
+    for path in entries {
+        if !is_module(&path) {
+            continue;
+        }
+        let name = mandate(name(&path), "Invalid module file name")?;
+        if name == "mod" && path.is_file() {
+            load_file(env, &path, home, loading, printer)?;
+        } else {
+            env.create_lazy_namespace(&name, loader(path, home.clone(), loading.to_vec(), printer.clone()))?;
+        }
+    }
+    Ok(())
+}
+
+/**
+The loader of a lazy namespace. The files that were being loaded when the namespace was
+declared are remembered, since the namespace is loaded later, when it is first used.
+*/
+fn loader(path: PathBuf, home: Scope, loading: Vec<PathBuf>, printer: Printer) -> Loader {
+    Box::new(move |env| {
+        let res = if path.is_dir() {
+            load_directory(env, &path, &home, &loading, &printer)
+        } else {
+            load_file(env, &path, &home, &loading, &printer)
+        };
+        /* A failed lookup is retried as an external command, which would hide the reason. */
+        if let Err(e) = &res {
+            printer.error(&e.message);
+        }
+        res
+    })
+}
+
+/**
+Declare a namespace with the given name in the scope, that is loaded from the given file or
+directory when first used.
+
+A file is loaded as a namespace containing the variables its code declares. A directory is
+loaded as a namespace with one member per .crush file and subdirectory in it, except for a file
+named mod.crush, whose variables are put directly into the namespace of the directory.
+*/
+pub fn declare(scope: &Scope, name: &str, path: &Path, printer: &Printer) -> CrushResult<Scope> {
+    if !is_module(path) {
+        return error(format!("{} is neither a .crush file nor a directory", path.to_string_lossy()).as_str());
+    }
+    scope.create_lazy_namespace(name, loader(path.to_path_buf(), scope.clone(), scope.loading(), printer.clone()))
+}
+
+/**
+Run a file in the given scope, so that the variables it declares end up in that scope. Errors
+point to where in the file they happened, and a file that sources itself, directly or through
+other files, is an error.
+*/
+pub fn source(scope: &Scope, file: &Path, printer: &Printer, output: &ValueSender) -> CrushResult<()> {
+    let previous = scope.set_loading(Some(start_loading(&scope.loading(), file)?));
+    let status = execute::file(scope.clone(), file, printer, output);
+    scope.set_loading(previous);
+    status?.result()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names() {
+        assert_eq!(name(Path::new("lib/util.crush")), Some("util".to_string()));
+        assert_eq!(name(Path::new("/home/me/lib")), Some("lib".to_string()));
+        assert_eq!(name(Path::new("/")), None);
+    }
+
+    #[test]
+    fn cycles() {
+        let a = std::env::current_dir().unwrap().join("example_data/modules/tools/mod.crush");
+        let b = std::env::current_dir().unwrap().join("example_data/modules/tools/strings.crush");
+        let chain = start_loading(&[], &a).unwrap();
+        let chain = start_loading(&chain, &b).unwrap();
+        assert!(start_loading(&chain, &a).is_err());
+        // Another load of the same file that does not go through the chain is not a cycle
+        assert!(start_loading(&[], &a).is_ok());
+    }
+}
@@ -1,9 +1,27 @@
-use crate::lang::errors::{CrushResult, to_crush_error};
+use std::sync::Arc;
+
+use lazy_static::lazy_static;
+use lalrpop_util::lexer::{self, MatcherBuilder};
+use lalrpop_util::ParseError;
+
+use crate::lang::ast::JobListNode;
+use crate::lang::errors::{CrushError, CrushResult, Kind, Location, Source};
 use crate::lang::job::Job;
 use crate::lang::scope::Scope;
 
 lalrpop_mod!(pub lalrparser, "/lang/lalrparser.rs");
 
+/**
+The deepest expressions are allowed to nest. Generating and running code recurses once per
+level, so without a limit, a long enough expression would run out of stack.
+*/
+const MAX_DEPTH: usize = 128;
+
+lazy_static! {
+    /** Creating a parser compiles the regular expressions of the lexer, so it is done once. */
+    static ref PARSER: lalrparser::JobListParser = lalrparser::JobListParser::new();
+}
+
 pub fn parse_name(s: &str) -> Option<Vec<String>> {
     let res = s.split('/').collect::<Vec<&str>>();
     for i in res.iter() {
@@ -14,6 +32,430 @@ pub fn parse_name(s: &str) -> Option<Vec<String>> {
     Some(res.iter().map(|e| e.to_string()).collect())
 }
 
-pub fn parse(s: &str, env: &Scope) -> CrushResult<Vec<Job>> {
-    to_crush_error(lalrparser::JobListParser::new().parse(s))?.generate(env)
+/**
+The span of the token where parsing fails, for an error of the parser. Running out of input is
+the empty span at the end of the input.
+*/
+fn span<T>(s: &str, error: &ParseError<usize, T, String>) -> Option<(usize, usize)> {
+    match error {
+        ParseError::InvalidToken { location } =>
+            Some((*location, location + s[*location..].chars().next().map(|c| c.len_utf8()).unwrap_or(0))),
+        ParseError::UnrecognizedToken { token: (start, _, end), .. } |
+        ParseError::ExtraToken { token: (start, _, end) } => Some((*start, *end)),
+        ParseError::UnrecognizedEOF { location, .. } => Some((*location, *location)),
+        ParseError::User { .. } => None,
+    }
+}
+
+/**
+Where the code being parsed comes from. Usually that is all of a source, but the expressions
+interpolated into a string literal are parsed on their own, with their escapes removed, so
+every offset into them has to be mapped back to the source to point to the right place.
+*/
+pub struct Origin<'a> {
+    source: &'a Arc<Source>,
+    /** The offset in the source of every byte of the code, and of its end. None is the identity. */
+    offsets: Option<&'a [usize]>,
+}
+
+impl Origin<'_> {
+    /** The offset in the source of an offset into the code being parsed. */
+    pub fn offset(&self, offset: usize) -> usize {
+        match self.offsets {
+            Some(offsets) => offsets[offset],
+            None => offset,
+        }
+    }
+
+    pub fn location(&self, start: usize, end: usize) -> Location {
+        Location::new(self.source, self.offset(start), self.offset(end))
+    }
+}
+
+fn parse_code(origin: &Origin, code: &str) -> CrushResult<JobListNode> {
+    let tree = PARSER.parse(origin, code).map_err(|e| {
+        let span = span(code, &e);
+        let err = CrushError::new(Kind::GenericError, &e.map_location(|offset| origin.offset(offset)).to_string());
+        match span {
+            Some((start, end)) => err.at(&origin.location(start, end)),
+            None => err,
+        }
+    })?;
+    tree.check_depth(MAX_DEPTH)?;
+    Ok(tree)
+}
+
+/**
+Parse source code into a syntax tree. This does not need a scope and never panics, whatever the
+input, so it can be used on half written code, e.g. for highlighting it as it is being typed.
+The commands in the tree know where in the source they are, and so do syntax errors.
+*/
+pub fn parse_source(source: &Arc<Source>) -> CrushResult<JobListNode> {
+    parse_code(&Origin { source, offsets: None }, &source.text)
+}
+
+/**
+Parse an expression interpolated into a string literal of a source. Since escapes have been
+removed from the code, the offset in the source of each of its bytes, and of its end, is needed
+for the locations in the tree to point into the string literal.
+*/
+pub fn parse_interpolated(source: &Arc<Source>, code: &str, offsets: &[usize]) -> CrushResult<JobListNode> {
+    parse_code(&Origin { source, offsets: Some(offsets) }, code)
+}
+
+/** Parse source code that does not come from a file. */
+pub fn parse_str(s: &str) -> CrushResult<JobListNode> {
+    parse_source(&Source::new(None, s))
+}
+
+pub fn parse(source: &Arc<Source>, env: &Scope) -> CrushResult<Vec<Job>> {
+    parse_source(source)?.generate(env)
+}
+
+/**
+The span of the token where parsing source code fails, if it does. Running out of input is not
+counted, because that is what a line that is still being typed looks like.
+*/
+pub fn error_span(s: &str) -> Option<(usize, usize)> {
+    match PARSER.parse(&Origin { source: &Source::new(None, s), offsets: None }, s) {
+        Err(ParseError::UnrecognizedEOF { .. }) | Ok(_) => None,
+        Err(e) => span(s, &e),
+    }
+}
+
+/**
+Whether source code is the start of a script that has not been finished yet, e.g. because a
+bracket or a string has not been closed, or because it ends with a pipe. More input could
+make it valid, whereas an error in complete code stays an error.
+*/
+pub fn is_incomplete(s: &str) -> bool {
+    match PARSER.parse(&Origin { source: &Source::new(None, s), offsets: None }, s) {
+        Err(ParseError::UnrecognizedEOF { .. }) => true,
+        Err(ParseError::InvalidToken { location }) => s[location..].starts_with(['"', '\'']),
+        _ => false,
+    }
+}
+
+/** What a token is, as far as highlighting it is concerned. */
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum TokenType {
+    Label,
+    Field,
+    Flag,
+    String,
+    Regex,
+    Number,
+    Keyword,
+    Operator,
+    Separator,
+    Open,
+    Close,
+    Comment,
+    Whitespace,
+    Error,
+}
+
+/**
+The terminals of the grammar in lalrparser.lalrpop, in the order lalrpop gives them to its lexer:
+the quoted literals that are only used in rules first, then the groups of the match block from
+the bottom up, each with its regular expressions before its quoted terminals. Later terminals
+win over earlier ones of the same length, so that keywords are not lexed as labels. lalrpop does not make its lexer public,
+so these have to be kept in sync with the grammar by hand. A test checks that they are the same
+terminals as those of the generated lexer, in the same order.
+*/
+const LITERALS: &[(&str, TokenType)] = &[
+    ("$(", TokenType::Open),
+    ("&&", TokenType::Separator),
+    ("(", TokenType::Open),
+    (")", TokenType::Close),
+    (":=", TokenType::Operator),
+    ("=", TokenType::Operator),
+    ("@", TokenType::Operator),
+    ("@@", TokenType::Operator),
+    ("[", TokenType::Open),
+    ("]", TokenType::Close),
+    ("{", TokenType::Open),
+    ("|", TokenType::Separator),
+    ("||", TokenType::Separator),
+    ("}", TokenType::Close),
+];
+
+const REGEXES: &[(&str, TokenType)] = &[
+    (r#""([^\\"]|\\.)*""#, TokenType::String),
+    (r"#[^\n]*", TokenType::Comment),
+    (r#"'([^\\']|\\.)*'"#, TokenType::String),
+    (r"( |\t|\\\n)+", TokenType::Whitespace),
+    (r"(;|\n)( |\t|;|\n|#[^\n]*)*", TokenType::Separator),
+    (r"(>=|<=|==|!=|=~|!~|>|<)", TokenType::Operator),
+    (r"([0-9][0-9_]*(\.[0-9_]+)?(ns|us|ms|s|m|h|d|w|y))+", TokenType::Number),
+    (r"([\._a-zA-Z%\?][\._0-9a-zA-Z%\?]*(/[\._0-9a-zA-Z%\?]+)*/?|/[\._0-9a-zA-Z%\?]+(/[\._0-9a-zA-Z%\?]+)*/?|/)", TokenType::Label),
+    (r"(\*|//)", TokenType::Operator),
+    (r"(\+|-)", TokenType::Operator),
+    (r"(~~|~)", TokenType::Operator),
+    (r"--[_0-9a-zA-Z]+", TokenType::Flag),
+    (r"[0-9][0-9_]*", TokenType::Number),
+    (r"[0-9][0-9_]*\.[0-9_]+", TokenType::Number),
+    (r"[0-9]{4}-[0-9]{2}-[0-9]{2}T[0-9]{2}:[0-9]{2}(:[0-9]{2}(\.[0-9]+)?)?", TokenType::Number),
+    (r"\^[\._a-zA-Z][\._a-zA-Z0-9]*", TokenType::Field),
+    (r#"re"([^"]|\\.)*""#, TokenType::Regex),
+    (":", TokenType::Operator),
+    (r"(and|or)", TokenType::Keyword),
+    (r"(typeof|neg|not)", TokenType::Keyword),
+    ("def", TokenType::Keyword),
+];
+
+lazy_static! {
+    static ref LEXER: MatcherBuilder = MatcherBuilder::new(
+        LITERALS.iter().map(|(literal, token_type)| (format!("^({})", regex::escape(literal)), *token_type))
+            .chain(REGEXES.iter().map(|(regex, token_type)| (format!("^({})", regex), *token_type)))
+            .map(|(regex, token_type)| (regex, token_type == TokenType::Whitespace))
+    ).unwrap();
+    static ref TOKEN_TYPES: Vec<TokenType> =
+        LITERALS.iter().chain(REGEXES.iter()).map(|(_, token_type)| *token_type).collect();
+}
+
+/**
+A token of source code. The positions are byte offsets. A character that does not start any
+token becomes an error token one character long, so that lexing carries on after it.
+*/
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Token {
+    pub token_type: TokenType,
+    pub start: usize,
+    pub end: usize,
+}
+
+/**
+Split source code into tokens the same way the parser does, except that comments are kept and
+invalid characters do not stop it. Whitespace is left out. This works on any prefix of a
+script, so it can be used on a line as it is being typed.
+*/
+pub fn tokens(s: &str) -> Vec<Token> {
+    let mut res = Vec::new();
+    let mut offset = 0;
+    while offset < s.len() {
+        let mut resume = None;
+        for token in LEXER.matcher::<()>(&s[offset..]) {
+            match token {
+                Ok((start, lexer::Token(index, _), end)) => res.push(Token {
+                    token_type: TOKEN_TYPES[index],
+                    start: offset + start,
+                    end: offset + end,
+                }),
+                Err(ParseError::InvalidToken { location }) => {
+                    let start = offset + location;
+                    let end = start + s[start..].chars().next().map(|c| c.len_utf8()).unwrap_or(1);
+                    res.push(Token { token_type: TokenType::Error, start, end });
+                    resume = Some(end);
+                    break;
+                }
+                Err(_) => break,
+            }
+        }
+        match resume {
+            Some(end) => offset = end,
+            None => break,
+        }
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{Rng, SeedableRng};
+    use rand::rngs::StdRng;
+
+    #[test]
+    fn malformed_input() {
+        assert!(parse_str("echo 99999999999999999999999999999999999999999999").is_err());
+        assert!(parse_str("echo (val 1").is_err());
+        assert!(parse_str("echo \"abc").is_err());
+        assert!(parse_str(&format!("echo {}1{}", "(".repeat(100_000), ")".repeat(100_000))).is_err());
+        assert!(parse_str(&format!("echo {}1", "1+".repeat(100_000))).is_err());
+        assert!(parse_str(&format!("echo {}1", "neg ".repeat(100_000))).is_err());
+        assert!(parse_str(&format!("echo {}1{}", "(".repeat(100), ")".repeat(100))).is_ok());
+        assert!(parse_str("").is_ok());
+    }
+
+    #[test]
+    fn definitions() {
+        assert!(parse_str("def f [a b:integer=1 @c @@d] {echo a}").is_ok());
+        assert!(parse_str("def f [\n  a\n  b\n] {\n  echo a\n}").is_ok());
+        assert!(parse_str("def f [] {}").is_ok());
+        assert!(parse_str("def f {}").is_err());
+        assert!(parse_str("echo def").is_err());
+    }
+
+    #[test]
+    fn shebang() {
+        assert_eq!(parse_str("#!/usr/bin/env crush\necho argv").unwrap().jobs.len(), 1);
+        assert!(parse_str("#!/usr/bin/env crush").is_ok());
+    }
+
+    #[test]
+    fn locations() {
+        let tree = parse_str("echo 1\nls | head 3\ndef f [] {\n  echo 2\n}").unwrap();
+        assert_eq!(tree.jobs[1].commands[1].location.text(), "head 3");
+        assert_eq!(tree.jobs[1].commands[1].location.line_column(), (2, 6));
+        assert_eq!(tree.jobs[2].commands[0].location.text(), "def f [] {\n  echo 2\n}");
+        let err = parse_source(&Source::new(Some("a.crush"), "echo 1\necho )")).err().unwrap();
+        assert_eq!(err.location.unwrap().to_string(), "a.crush:2:6");
+        assert_eq!(parse_str("echo (1 +").err().unwrap().location.unwrap().start, 9);
+    }
+
+    #[test]
+    fn interpolated_locations() {
+        let source = Source::new(Some("a.crush"), "echo 1\necho \"${head \\\"x\\\" | ls )}\"");
+        let start = source.text.find("head").unwrap();
+        let code = "head \"x\" | ls )";
+        let offsets = (start..start + 5).chain([start + 6, start + 7, start + 9]).chain(start + 10..start + 18)
+            .collect::<Vec<_>>();
+        assert_eq!(offsets.len(), code.len() + 1);
+        let tree = parse_interpolated(&source, "head \"x\" | ls", &offsets[..code.len() - 1]).unwrap();
+        assert_eq!(tree.jobs[0].commands[0].location.text(), "head \\\"x\\\"");
+        assert_eq!(tree.jobs[0].commands[1].location.to_string(), "a.crush:2:22");
+        let err = parse_interpolated(&source, code, &offsets).err().unwrap();
+        assert_eq!(err.location.unwrap().to_string(), "a.crush:2:25");
+    }
+
+    /**
+    The terminals of the match block of the grammar, with the precedence lalrpop gives them:
+    match groups count down to 1 from the top, doubled, plus one for quoted terminals. Also
+    returns whether the block has a catch all.
+    */
+    fn match_block(grammar: &str) -> (Vec<(String, bool, usize)>, bool) {
+        let block = grammar.split("\nmatch {\n").nth(1).unwrap().split("\n}\n").next().unwrap();
+        let groups = block.split("\n} else {\n").collect::<Vec<_>>();
+        let mut terminals = Vec::new();
+        let mut catch_all = false;
+        for (idx, group) in groups.iter().enumerate() {
+            for line in group.lines() {
+                let entry = line.trim().trim_end_matches(',');
+                let terminal = entry.split(" => ").next().unwrap();
+                let (terminal, quoted) = if terminal == "_" {
+                    catch_all = true;
+                    continue;
+                } else if let Some(regex) = terminal.strip_prefix("r#\"") {
+                    (regex.strip_suffix("\"#").unwrap(), false)
+                } else if let Some(regex) = terminal.strip_prefix("r\"") {
+                    (regex.strip_suffix('"').unwrap(), false)
+                } else {
+                    (terminal.strip_prefix('"').unwrap().strip_suffix('"').unwrap(), true)
+                };
+                terminals.push((terminal.to_string(), quoted, (groups.len() - idx) * 2 + quoted as usize));
+            }
+        }
+        (terminals, catch_all)
+    }
+
+    #[test]
+    fn terminals_match_grammar() {
+        let grammar = std::fs::read_to_string("src/lang/lalrparser.lalrpop").unwrap();
+        let terminals = LITERALS.iter().chain(REGEXES.iter()).map(|(t, _)| t.to_string()).collect::<Vec<_>>();
+        for terminal in &terminals {
+            assert!(
+                [format!("\"{}\"", terminal), format!("r\"{}\"", terminal), format!("r#\"{}\"#", terminal)]
+                    .iter().any(|quoted| grammar.contains(quoted.as_str())),
+                "{} is not a terminal of the grammar", terminal);
+        }
+
+        // The generated lexer has one regex per terminal, so with the above, having as many
+        // distinct terminals means that none are missing
+        let generated = std::fs::read_to_string(concat!(env!("OUT_DIR"), "/lang/lalrparser.rs")).unwrap();
+        let strs = generated.split("let __strs: &[(&str, bool)] = &[").nth(1).unwrap().split("];").next().unwrap();
+        let mut distinct = terminals.clone();
+        distinct.sort();
+        distinct.dedup();
+        assert_eq!(distinct.len(), terminals.len());
+        assert_eq!(terminals.len(), strs.lines().filter(|line| line.trim().starts_with("(\"^(")).count());
+
+        // lalrpop orders its terminals by precedence, then quoted before regex, then by text.
+        // Terminals that are not in the match block are added by its catch all.
+        let (matched, catch_all) = match_block(&grammar);
+        assert!(catch_all || matched.len() == terminals.len());
+        let key = |terminal: &String| match matched.iter().find(|(t, _, _)| t == terminal) {
+            Some((_, quoted, precedence)) => (*precedence, !*quoted, terminal.clone()),
+            None => {
+                let quoted = LITERALS.iter().any(|(t, _)| t == terminal);
+                (quoted as usize, !quoted, terminal.clone())
+            }
+        };
+        let mut ordered = terminals.clone();
+        ordered.sort_by_key(key);
+        assert_eq!(terminals, ordered);
+        for (terminal, _, _) in &matched {
+            assert!(terminals.contains(terminal), "The terminal {} of the grammar is missing", terminal);
+        }
+    }
+
+    #[test]
+    fn lexing() {
+        let types = |s| tokens(s).iter().map(|t| t.token_type).collect::<Vec<_>>();
+        assert_eq!(
+            types("ls %.txt | where {^size > 1000} # big"),
+            vec![TokenType::Label, TokenType::Label, TokenType::Separator, TokenType::Label,
+                 TokenType::Open, TokenType::Field, TokenType::Operator, TokenType::Number,
+                 TokenType::Close, TokenType::Comment]);
+        assert_eq!(types("not notes def"), vec![TokenType::Keyword, TokenType::Label, TokenType::Keyword]);
+        assert_eq!(types("echo \"abc"), vec![TokenType::Label, TokenType::Error, TokenType::Label]);
+        assert_eq!(
+            tokens("a ä b").iter().map(|t| (t.start, t.end)).collect::<Vec<_>>(),
+            vec![(0, 1), (2, 4), (5, 6)]);
+    }
+
+    #[test]
+    fn error_spans() {
+        assert_eq!(error_span("echo (1 +"), None);
+        assert_eq!(error_span("echo 1)"), Some((6, 7)));
+        assert_eq!(error_span("echo $x"), Some((5, 6)));
+        assert_eq!(error_span("echo {a}"), None);
+    }
+
+    #[test]
+    fn incomplete() {
+        for s in ["ls |", "for (seq 3) {", "echo (1 +", "echo 1 &&", "echo \"a\nb", "echo 're\\'", "def f [a"] {
+            assert!(is_incomplete(s), "{}", s);
+        }
+        for s in ["", "ls | sort\n", "for (seq 3) {\necho 1\n}", "echo )", "echo \"a\" $", "# {"] {
+            assert!(!is_incomplete(s), "{}", s);
+        }
+    }
+
+    /**
+    Feeds the parser mutations of the test scripts: fragments spliced together, bytes dropped,
+    duplicated and replaced with characters that mean something to the lexer. This is a cheap
+    smoke test, the real fuzzing is done by the parse_str target in the fuzz directory.
+    */
+    #[test]
+    fn fuzz() {
+        let mut corpus: Vec<Vec<u8>> = std::fs::read_dir("tests").unwrap()
+            .filter_map(|e| e.ok().map(|e| e.path()))
+            .filter(|p| p.extension().map(|e| e == "crush").unwrap_or(false))
+            .map(|p| std::fs::read(p).unwrap())
+            .collect();
+        corpus.sort();
+        let special = b"()[]{}|&;:=@^\"'\\#$%?/*+-<>~!.,_ \t\n0123456789aeznsmhdTre";
+        let mut rng = StdRng::seed_from_u64(0x5eed);
+        for _ in 0..5000 {
+            let mut input = corpus[rng.gen_range(0, corpus.len())].clone();
+            for _ in 0..rng.gen_range(1, 8) {
+                let pos = rng.gen_range(0, input.len() + 1);
+                match rng.gen_range(0, 4) {
+                    0 if pos < input.len() => {
+                        input.remove(pos);
+                    }
+                    1 => input.insert(pos, special[rng.gen_range(0, special.len())]),
+                    2 => {
+                        let other = &corpus[rng.gen_range(0, corpus.len())];
+                        let start = rng.gen_range(0, other.len());
+                        let end = rng.gen_range(start, other.len() + 1);
+                        input.splice(pos..pos, other[start..end].iter().cloned());
+                    }
+                    _ => input.truncate(pos),
+                }
+            }
+            let _ = parse_str(&String::from_utf8_lossy(&input));
+        }
+    }
 }
@@ -20,7 +20,7 @@ pub fn create_pretty_printer(printer: Printer) -> ValueSender {
     printer_clone.handle_error(to_crush_error(thread::Builder::new()
         .name("output-formater".to_string())
         .spawn(move || {
-            let pp = PrettyPrinter { printer };
+            let pp = PrettyPrinter::new(printer);
             while let Ok(val) = i.recv() {
                 pp.print_value(val);
             }
@@ -30,6 +30,8 @@ pub fn create_pretty_printer(printer: Printer) -> ValueSender {
 
 pub struct PrettyPrinter {
     printer: Printer,
+    /** Text attributes are only rendered as escape codes when the output is a terminal. */
+    is_tty: bool,
 }
 
 fn hex(v: u8) -> String {
@@ -89,7 +91,19 @@ fn is_text(buff: &[u8]) -> bool {
 impl PrettyPrinter {
     pub fn new(printer: Printer) -> PrettyPrinter {
         PrettyPrinter {
-            printer
+            is_tty: printer.is_terminal(),
+            printer,
+        }
+    }
+
+    /**
+    The text of a cell as it should be written to the output. Padding must be calculated
+    using the plain text, since escape codes take up no space on screen.
+    */
+    fn render(&self, cell: &Value, text: String) -> String {
+        match cell {
+            Value::Styled(s) if self.is_tty => s.style.apply(&text),
+            _ => text,
         }
     }
 
@@ -99,7 +113,7 @@ impl PrettyPrinter {
             Value::Table(rows) => self.print_readable(&mut TableReader::new(rows), 0),
             Value::BinaryStream(mut b) => self.print_binary(b.as_mut(), 0),
             Value::Empty() => {},
-            _ => self.printer.line(cell.to_string().as_str()),
+            _ => self.printer.line(self.render(&cell, cell.to_string()).as_str()),
         };
     }
 
@@ -181,8 +195,9 @@ impl PrettyPrinter {
         let mut row = " ".repeat(indent * 4);
         let last_idx = r.len() - 1;
         for (idx, c) in r.into_vec().drain(..).enumerate() {
-            let cell = c.to_string();
-            let spaces = if idx == cell_len - 1 { "".to_string() } else { " ".repeat(w[idx] - cell.len()) };
+            let text = c.to_string();
+            let spaces = if idx == cell_len - 1 { "".to_string() } else { " ".repeat(w[idx] - text.len()) };
+            let cell = self.render(&c, text);
             let is_last = idx == last_idx;
             match c.alignment() {
                 Alignment::Right => {
@@ -285,6 +300,7 @@ impl PrettyPrinter {
         let mut columns = 1;
         let mut widths = vec![];
         let mut items_per_column;
+        let rendered = data.iter().map(|s| self.render(&s.cells()[0], s.cells()[0].to_string())).collect::<Vec<_>>();
         let data = data.iter().map(|s| s.cells()[0].to_string()).collect::<Vec<_>>();
 
         for cols in (2..50).rev() {
@@ -307,7 +323,7 @@ impl PrettyPrinter {
         for start_idx in 0..lines {
             let mut line = "".to_string();
             for (off, idx) in (start_idx..data.len()).step_by(lines).enumerate() {
-                line += &data[idx];
+                line += &rendered[idx];
                 if off + 1 < widths.len() {
                     line += &" ".repeat(widths[off] - data[idx].len() + 1);
                 }
@@ -316,3 +332,53 @@ impl PrettyPrinter {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lang::golden::{assert_golden, render};
+
+    fn table(names: &[&str], rows: Vec<Vec<Value>>) -> Value {
+        Value::Table(Table::new(
+            names.iter().map(|n| ColumnType::new(n, ValueType::Any)).collect(),
+            rows.into_iter().map(Row::new).collect()))
+    }
+
+    #[test]
+    fn alignment() {
+        assert_golden("alignment", &render(table(
+            &["name", "size", "ratio", "ok"],
+            vec![
+                vec![Value::string("a"), Value::Integer(1), Value::Float(0.5), Value::Bool(true)],
+                vec![Value::string("a longer name"), Value::Integer(123456), Value::Float(12.25), Value::Bool(false)],
+                vec![Value::string(""), Value::Integer(-7), Value::Float(-1.0), Value::Empty()],
+            ])));
+    }
+
+    #[test]
+    fn nesting() {
+        let inner = |n: i128| table(
+            &["idx", "square"],
+            (0..n).map(|i| vec![Value::Integer(i), Value::Integer(i * i)]).collect());
+        assert_golden("nesting", &render(table(
+            &["name", "values"],
+            vec![
+                vec![Value::string("two"), inner(2)],
+                vec![Value::string("three"), inner(3)],
+            ])));
+    }
+
+    #[test]
+    fn single_column() {
+        assert_golden("single_column", &render(table(
+            &["word"],
+            (0..40).map(|i| vec![Value::string(&"w".repeat(i % 7 + 1))]).collect())));
+    }
+
+    #[test]
+    fn long_table() {
+        assert_golden("long_table", &render(table(
+            &["idx", "name"],
+            (0..40).map(|i| vec![Value::Integer(i), Value::string(&format!("row {}", i))]).collect())));
+    }
+}
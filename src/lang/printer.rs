@@ -1,42 +1,166 @@
 use crossbeam::Sender;
-use crossbeam::bounded;
+use crossbeam::{bounded, unbounded, Receiver};
 use std::thread;
-use crate::lang::errors::{CrushError, CrushResult, to_crush_error, Kind};
+use crate::lang::errors::{CrushError, CrushResult, to_crush_error, Kind, Location};
 
 enum PrinterMessage {
     CrushError(CrushError),
-    Error(String),
     Line(String),
 //    Lines(Vec<String>),
+    Command(String),
+    StartRecording(Recorder),
+    StopRecording(Sender<()>),
 }
 
 use crate::lang::printer::PrinterMessage::*;
 use std::thread::JoinHandle;
 use termion::terminal_size;
+use std::sync::{Arc, Mutex, MutexGuard};
+use crate::lang::recording::{Recorder, EventKind};
 
 #[derive(Clone)]
 pub struct Printer {
     sender: Sender<PrinterMessage>,
+    terminal: Arc<Mutex<()>>,
+    /** The width and height to format output for, instead of the size of the terminal. */
+    size: Option<(usize, usize)>,
+    /** Where errors go instead of to the terminal, for printers created by catching. */
+    errors: Option<Arc<Mutex<Vec<CrushError>>>>,
+    /**
+    The commands being run, outermost first, so that errors can point to the one that failed
+    and list the ones that were running it.
+    */
+    stages: Arc<Vec<Location>>,
+}
+
+/**
+Format an error the way it is shown to the user. If it is known where the error happened, the
+line of code is shown with the failing expression underlined, followed by the commands that
+were running it, innermost first.
+*/
+pub fn render(err: &CrushError) -> String {
+    let mut res = format!("Error: {}", err.message);
+    if let Some(location) = &err.location {
+        let (line, column) = location.line_column();
+        let code = location.source.text.lines().nth(line - 1).unwrap_or("");
+        let number = line.to_string();
+        let margin = " ".repeat(number.len());
+        let width = location.text().lines().next().unwrap_or("").chars().count().max(1);
+        /* Tabs are kept, so that the carets line up with the code whatever the tab width is. */
+        let indent: String = code.chars().take(column - 1).map(|c| if c == '\t' { c } else { ' ' }).collect();
+        res.push_str(&format!(
+            "\n{}--> {}\n{} |\n{} | {}\n{} | {}{}",
+            margin, location,
+            margin,
+            number, code,
+            margin, indent, "^".repeat(width)));
+    }
+    for stage in &err.trace {
+        res.push_str(&format!(
+            "\n  = called from {}: {}",
+            stage, stage.text().lines().next().unwrap_or("").trim()));
+    }
+    res
 }
 
 pub fn init() -> (Printer, JoinHandle<()>) {
     let (sender, receiver) = bounded(128);
+    let terminal = Arc::from(Mutex::new(()));
+    let printer_terminal = terminal.clone();
 
     (
-        Printer { sender: sender },
+        Printer { sender, terminal, size: None, errors: None, stages: Arc::new(Vec::new()) },
         thread::Builder::new().name("printer".to_string()).spawn(move || {
+            let mut recorder: Option<Recorder> = None;
             while let Ok(message) = receiver.recv() {
-                match message {
-                    Error(err) => eprintln!("Error: {}", err),
-                    CrushError(err) => eprintln!("Error: {}", err.message),
-                    Line(line) => println!("{}", line),
+                let _terminal = printer_terminal.lock().unwrap();
+                let recorded = match message {
+                    CrushError(err) => {
+                        eprintln!("{}", render(&err));
+                        Some((EventKind::Error, err.message))
+                    }
+                    Line(line) => {
+                        println!("{}", line);
+                        Some((EventKind::Output, line))
+                    }
 //                        Lines(lines) => for line in lines {println!("{}", line)},
+                    Command(cmd) => Some((EventKind::Command, cmd)),
+                    StartRecording(r) => {
+                        recorder = Some(r);
+                        None
+                    }
+                    StopRecording(done) => {
+                        recorder = None;
+                        let _ = done.send(());
+                        None
+                    }
+                };
+                if let (Some(r), Some((kind, text))) = (&mut recorder, recorded) {
+                    if let Err(e) = r.record(kind, &text) {
+                        eprintln!("Error: Failed to record session: {}", e.message);
+                        recorder = None;
+                    }
                 }
             }
         }).unwrap()
     )
 }
 
+/**
+Something that collects the lines printed to a printer created by capture.
+*/
+pub struct Capture {
+    receiver: Receiver<PrinterMessage>,
+}
+
+impl Capture {
+    /** All lines printed so far, with errors formatted the same way as on the terminal. */
+    pub fn lines(&self) -> Vec<String> {
+        self.receiver.try_iter()
+            .filter_map(|message| match message {
+                CrushError(err) => Some(render(&err)),
+                Line(line) => Some(line),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+/**
+Create a printer that collects everything printed to it instead of writing it to the terminal.
+*/
+pub fn capture() -> (Printer, Capture) {
+    let (sender, receiver) = unbounded();
+    (
+        Printer { sender, terminal: Arc::from(Mutex::new(())), size: None, errors: None, stages: Arc::new(Vec::new()) },
+        Capture { receiver },
+    )
+}
+
+/**
+The errors reported to a printer created by catching.
+*/
+pub struct Caught {
+    errors: Arc<Mutex<Vec<CrushError>>>,
+}
+
+impl Caught {
+    /** The errors reported so far, oldest first. */
+    pub fn take(&self) -> Vec<CrushError> {
+        self.errors.lock().unwrap().drain(..).collect()
+    }
+}
+
+/**
+Create a printer like capture does, that formats output for a fixed size instead of the size
+of the terminal, so that the output is the same wherever it runs.
+*/
+#[cfg(test)]
+pub fn capture_sized(width: usize, height: usize) -> (Printer, Capture) {
+    let (printer, capture) = capture();
+    (Printer { size: Some((width, height)), ..printer }, capture)
+}
+
 impl Printer {
     pub fn line(&self, line: &str) {
         self.handle_error(to_crush_error(self.sender.send(PrinterMessage::Line(line.to_string()))));
@@ -46,35 +170,137 @@ impl Printer {
             self.handle_error(to_crush_error(self.sender.send(PrinterMessage::Lines(lines))));
         }
     */
+    /** Tell the printer about a command entered by the user, so that it can be recorded. */
+    pub fn command(&self, cmd: &str) {
+        let _ = self.sender.send(PrinterMessage::Command(cmd.trim_end().to_string()));
+    }
+
+    /**
+    Record all commands and output passing through the printer from now on. Any
+    previous recording is stopped.
+    */
+    pub fn start_recording(&self, recorder: Recorder) -> CrushResult<()> {
+        to_crush_error(self.sender.send(PrinterMessage::StartRecording(recorder)))
+    }
+
+    /** Stop recording, and wait until everything printed so far has been recorded. */
+    pub fn stop_recording(&self) -> CrushResult<()> {
+        let (done, wait) = bounded(1);
+        to_crush_error(self.sender.send(PrinterMessage::StopRecording(done)))?;
+        to_crush_error(wait.recv())
+    }
+
     pub fn handle_error<T>(&self, result: CrushResult<T>) {
         if let Err(e) = result {
             match e.kind {
-                Kind::SendError => {}
+                Kind::SendError | Kind::ExitStatus(_) => {}
                 _ => self.crush_error(e),
             }
         }
     }
 
+    /**
+    Point an error that does not know where it happened to the innermost command being run, and
+    list the commands running it as its trace.
+    */
+    fn locate(&self, err: CrushError) -> CrushError {
+        let mut stages = self.stages.iter().rev();
+        match err.location {
+            None => CrushError { location: stages.next().cloned(), trace: stages.cloned().collect(), ..err },
+            Some(location) if err.trace.is_empty() => CrushError {
+                trace: stages.filter(|stage| **stage != location).cloned().collect(),
+                location: Some(location),
+                ..err
+            },
+            Some(_) => err,
+        }
+    }
+
     pub fn crush_error(&self, err: CrushError) {
-        let _ = self.sender.send(PrinterMessage::CrushError(err));
+        let err = self.locate(err);
+        match &self.errors {
+            Some(errors) => errors.lock().unwrap().push(err),
+            None => {
+                let _ = self.sender.send(PrinterMessage::CrushError(err));
+            }
+        }
     }
 
     pub fn error(&self, err: &str) {
-        let _ = self.sender.send(PrinterMessage::Error(err.to_string()));
+        self.crush_error(CrushError::new(Kind::GenericError, err))
+    }
+
+    /**
+    Create a printer that prints output the same way as this one, but that keeps the errors
+    reported to it instead of printing them, so that a script can handle them.
+    */
+    pub fn catching(&self) -> (Printer, Caught) {
+        let errors = Arc::from(Mutex::new(Vec::new()));
+        (
+            Printer { errors: Some(errors.clone()), ..self.clone() },
+            Caught { errors },
+        )
+    }
+
+    /**
+    Create a printer that prints the same way as this one, for a command written at the given
+    location, which errors reported to it are pointed to.
+    */
+    pub fn at(&self, location: &Location) -> Printer {
+        let mut stages = self.stages.as_ref().clone();
+        stages.push(location.clone());
+        Printer { stages: Arc::new(stages), ..self.clone() }
+    }
+
+    /**
+    Hold off all printing until the returned guard is dropped. Used while an external
+    command has taken over the terminal.
+    */
+    pub fn lock_terminal(&self) -> MutexGuard<'_, ()> {
+        self.terminal.lock().unwrap()
+    }
+
+    /** True if output is written to a terminal, so that text attributes can be used. */
+    pub fn is_terminal(&self) -> bool {
+        self.size.is_none() && termion::is_tty(&std::io::stdout())
     }
 
     pub fn width(&self) -> usize {
+        if let Some((width, _)) = self.size {
+            return width;
+        }
         match terminal_size() {
-            Ok(s) =>
+            Ok(s) if s.0 > 0 =>
                 s.0 as usize,
-            Err(_) => 80,
+            _ => 80,
         }
     }
 
     pub fn height(&self) -> usize {
+        if let Some((_, height)) = self.size {
+            return height;
+        }
         match terminal_size() {
-            Ok(s) => s.1 as usize,
-            Err(_) => 30,
+            Ok(s) if s.1 > 0 => s.1 as usize,
+            _ => 30,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lang::errors::Source;
+
+    #[test]
+    fn snippets() {
+        let source = Source::new(Some("a.crush"), "for (seq 3) {\n\tsotr 3\n}");
+        let (printer, capture) = capture();
+        printer.at(&Location::new(&source, 0, 23)).at(&Location::new(&source, 15, 21)).error("Unknown command");
+        printer.error("Plain");
+        assert_eq!(capture.lines(), vec![
+            "Error: Unknown command\n --> a.crush:2:2\n  |\n2 | \tsotr 3\n  | \t^^^^^^\n  = called from a.crush:1:1: for (seq 3) {",
+            "Error: Plain",
+        ]);
+    }
+}
@@ -0,0 +1,72 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::process::Command;
+
+/**
+The working directory and environment variables of the external commands started from a
+scope. Like process limits, they are only applied to the spawned process, and the working
+directory and environment of the shell itself are never changed, since changing them while
+other threads read them is a data race.
+*/
+#[derive(Clone, Default)]
+pub struct ProcessEnvironment {
+    pub cwd: Option<PathBuf>,
+    /** Changes to the environment of the shell, in order. None removes a variable. */
+    pub variables: Vec<(String, Option<String>)>,
+}
+
+impl ProcessEnvironment {
+    /**
+    Combine this environment with a more specific one. The working directory and any
+    variables set in the other environment take precedence.
+    */
+    pub fn merge(&self, other: &ProcessEnvironment) -> ProcessEnvironment {
+        let mut variables = self.variables.clone();
+        variables.extend(other.variables.iter().cloned());
+        ProcessEnvironment {
+            cwd: other.cwd.clone().or_else(|| self.cwd.clone()),
+            variables,
+        }
+    }
+
+    /** Set a variable, or remove it if the value is None. */
+    pub fn set(&mut self, name: &str, value: Option<String>) {
+        self.variables.retain(|(n, _)| n != name);
+        self.variables.push((name.to_string(), value));
+    }
+
+    /** The value of a variable, as external commands started with this environment see it. */
+    pub fn get(&self, name: &str) -> Option<String> {
+        match self.variables.iter().rev().find(|(n, _)| n == name) {
+            Some((_, value)) => value.clone(),
+            None => std::env::var_os(name).map(|v| v.to_string_lossy().to_string()),
+        }
+    }
+
+    /** All variables, as external commands started with this environment see them, sorted by name. */
+    pub fn list(&self) -> Vec<(String, String)> {
+        let mut res = std::env::vars_os()
+            .map(|(name, value)| (name.to_string_lossy().to_string(), value.to_string_lossy().to_string()))
+            .collect::<BTreeMap<_, _>>();
+        for (name, value) in &self.variables {
+            match value {
+                Some(value) => res.insert(name.clone(), value.clone()),
+                None => res.remove(name),
+            };
+        }
+        res.into_iter().collect()
+    }
+
+    /** Make the specified command run in this working directory and with these variables set. */
+    pub fn apply(&self, cmd: &mut Command) {
+        if let Some(cwd) = &self.cwd {
+            cmd.current_dir(cwd);
+        }
+        for (name, value) in &self.variables {
+            match value {
+                Some(value) => cmd.env(name, value),
+                None => cmd.env_remove(name),
+            };
+        }
+    }
+}
@@ -0,0 +1,162 @@
+use crate::lang::errors::{CrushResult, argument_error, error, to_crush_error};
+use std::ffi::CString;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::process::CommandExt;
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use nix::libc;
+
+static CGROUP_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/**
+Resource limits applied to the external commands started from a scope. Limits are only
+applied to the spawned process, never to the shell itself.
+*/
+#[derive(Clone, Default)]
+pub struct ProcessLimits {
+    pub nice: Option<i32>,
+    pub io_class: Option<i32>,
+    pub io_priority: Option<i32>,
+    pub memory: Option<u64>,
+    pub cgroup: bool,
+}
+
+impl ProcessLimits {
+    pub fn io_class_from_name(name: &str) -> CrushResult<i32> {
+        match name {
+            "realtime" => Ok(1),
+            "best-effort" => Ok(2),
+            "idle" => Ok(3),
+            _ => argument_error(
+                format!("Unknown IO scheduling class {}, expected one of realtime, best-effort and idle", name).as_str()),
+        }
+    }
+
+    /**
+    Combine these limits with a more specific set of limits. Limits set in the other
+    set take precedence.
+    */
+    pub fn merge(&self, other: &ProcessLimits) -> ProcessLimits {
+        ProcessLimits {
+            nice: other.nice.or(self.nice),
+            io_class: other.io_class.or(self.io_class),
+            io_priority: other.io_priority.or(self.io_priority),
+            memory: other.memory.or(self.memory),
+            cgroup: other.cgroup || self.cgroup,
+        }
+    }
+
+    /**
+    Make the specified command apply these limits to itself before it starts executing.
+    If a cgroup is used, it is removed when the returned value is dropped, which must
+    not happen until the command has exited.
+    */
+    pub fn apply(&self, cmd: &mut Command) -> CrushResult<Option<Cgroup>> {
+        let cgroup = if self.cgroup {
+            Some(Cgroup::create(self.memory)?)
+        } else {
+            None
+        };
+        let procs = match &cgroup {
+            Some(c) => Some(to_crush_error(CString::new(c.path.join("cgroup.procs").as_os_str().as_bytes()))?),
+            None => None,
+        };
+        let limits = self.clone();
+        // Only async-signal-safe calls may be made between fork and exec, so everything
+        // that needs allocation is prepared above.
+        unsafe {
+            cmd.pre_exec(move || limits.apply_to_current_process(&procs));
+        }
+        Ok(cgroup)
+    }
+
+    fn apply_to_current_process(&self, procs: &Option<CString>) -> std::io::Result<()> {
+        unsafe {
+            if let Some(procs) = procs {
+                let fd = libc::open(procs.as_ptr(), libc::O_WRONLY);
+                if fd < 0 {
+                    return Err(std::io::Error::last_os_error());
+                }
+                let written = libc::write(fd, b"0".as_ptr() as *const libc::c_void, 1);
+                libc::close(fd);
+                if written < 0 {
+                    return Err(std::io::Error::last_os_error());
+                }
+            }
+            if let Some(nice) = self.nice {
+                if libc::setpriority(libc::PRIO_PROCESS as _, 0, nice) != 0 {
+                    return Err(std::io::Error::last_os_error());
+                }
+            }
+            if self.io_class.is_some() || self.io_priority.is_some() {
+                set_io_priority(self.io_class.unwrap_or(2), self.io_priority.unwrap_or(4))?;
+            }
+            if let (Some(memory), None) = (self.memory, procs) {
+                let limit = libc::rlimit {
+                    rlim_cur: memory as libc::rlim_t,
+                    rlim_max: memory as libc::rlim_t,
+                };
+                if libc::setrlimit(libc::RLIMIT_AS, &limit) != 0 {
+                    return Err(std::io::Error::last_os_error());
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(target_os = "linux")]
+unsafe fn set_io_priority(class: i32, priority: i32) -> std::io::Result<()> {
+    const IOPRIO_WHO_PROCESS: libc::c_long = 1;
+    const IOPRIO_CLASS_SHIFT: i32 = 13;
+    if libc::syscall(libc::SYS_ioprio_set, IOPRIO_WHO_PROCESS, 0, (class << IOPRIO_CLASS_SHIFT) | priority) < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+unsafe fn set_io_priority(_class: i32, _priority: i32) -> std::io::Result<()> {
+    Err(std::io::Error::new(std::io::ErrorKind::Other, "IO priorities are only supported on Linux"))
+}
+
+/**
+A transient cgroup, created as a child of the cgroup the shell is running in. Only the
+unified (v2) cgroup hierarchy is supported, and the current cgroup must be delegated to
+the user running the shell. When a memory limit is used, the memory controller must be
+enabled for child cgroups.
+*/
+pub struct Cgroup {
+    path: PathBuf,
+}
+
+impl Cgroup {
+    fn create(memory: Option<u64>) -> CrushResult<Cgroup> {
+        let root = PathBuf::from("/sys/fs/cgroup");
+        if !root.join("cgroup.controllers").exists() {
+            return error("Transient cgroups require the unified cgroup hierarchy");
+        }
+        let current = to_crush_error(std::fs::read_to_string("/proc/self/cgroup"))?;
+        let relative = match current.lines().find(|l| l.starts_with("0::")) {
+            Some(l) => l.trim_start_matches("0::").trim_start_matches('/').to_string(),
+            None => return error("Could not find the current cgroup"),
+        };
+        let path = root.join(relative).join(format!(
+            "crush-{}-{}",
+            std::process::id(),
+            CGROUP_COUNTER.fetch_add(1, Ordering::Relaxed)));
+        to_crush_error(std::fs::create_dir(&path))?;
+        let cgroup = Cgroup { path };
+        if let Some(memory) = memory {
+            to_crush_error(std::fs::write(cgroup.path.join("memory.max"), memory.to_string()))?;
+        }
+        Ok(cgroup)
+    }
+}
+
+impl Drop for Cgroup {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir(&self.path);
+    }
+}
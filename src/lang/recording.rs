@@ -0,0 +1,109 @@
+use crate::lang::errors::{CrushResult, error, to_crush_error, mandate};
+use serde_json::json;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+use std::time::Instant;
+
+/**
+The kinds of events in a session recording.
+*/
+#[derive(Clone, Copy, PartialEq)]
+pub enum EventKind {
+    Command,
+    Output,
+    Error,
+}
+
+impl EventKind {
+    fn name(self) -> &'static str {
+        match self {
+            EventKind::Command => "command",
+            EventKind::Output => "output",
+            EventKind::Error => "error",
+        }
+    }
+
+    fn parse(name: &str) -> CrushResult<EventKind> {
+        match name {
+            "command" => Ok(EventKind::Command),
+            "output" => Ok(EventKind::Output),
+            "error" => Ok(EventKind::Error),
+            _ => error(format!("Unknown event kind {} in recording", name).as_str()),
+        }
+    }
+}
+
+pub struct Event {
+    /** Seconds since the recording started. */
+    pub time: f64,
+    pub kind: EventKind,
+    pub text: String,
+}
+
+/**
+Writes a session recording. Recordings are stored as JSON lines, starting with a header
+containing the terminal size and the start time, followed by one line per event.
+*/
+pub struct Recorder {
+    file: File,
+    start: Instant,
+}
+
+impl Recorder {
+    pub fn create(path: &Path, width: usize, height: usize) -> CrushResult<Recorder> {
+        let mut file = to_crush_error(File::create(path))?;
+        let header = json!({
+            "width": width,
+            "height": height,
+            "timestamp": chrono::Local::now().timestamp(),
+        });
+        to_crush_error(writeln!(file, "{}", header))?;
+        Ok(Recorder { file, start: Instant::now() })
+    }
+
+    pub fn record(&mut self, kind: EventKind, text: &str) -> CrushResult<()> {
+        let event = json!({
+            "time": self.start.elapsed().as_secs_f64(),
+            "kind": kind.name(),
+            "text": text,
+        });
+        to_crush_error(writeln!(self.file, "{}", event))
+    }
+}
+
+/**
+A session recording read back from disk.
+*/
+pub struct Recording {
+    pub width: u64,
+    pub height: u64,
+    pub timestamp: i64,
+    pub events: Vec<Event>,
+}
+
+impl Recording {
+    pub fn load(path: &Path) -> CrushResult<Recording> {
+        let reader = BufReader::new(to_crush_error(File::open(path))?);
+        let mut lines = reader.lines();
+        let header: serde_json::Value = match lines.next() {
+            Some(line) => to_crush_error(serde_json::from_str(&to_crush_error(line)?))?,
+            None => return error("Empty recording"),
+        };
+        let mut events = Vec::new();
+        for line in lines {
+            let event: serde_json::Value = to_crush_error(serde_json::from_str(&to_crush_error(line)?))?;
+            events.push(Event {
+                time: mandate(event["time"].as_f64(), "Missing time in recorded event")?,
+                kind: EventKind::parse(mandate(event["kind"].as_str(), "Missing kind in recorded event")?)?,
+                text: mandate(event["text"].as_str(), "Missing text in recorded event")?.to_string(),
+            });
+        }
+        Ok(Recording {
+            width: header["width"].as_u64().unwrap_or(80),
+            height: header["height"].as_u64().unwrap_or(30),
+            timestamp: header["timestamp"].as_i64().unwrap_or(0),
+            events,
+        })
+    }
+}
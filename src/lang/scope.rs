@@ -1,13 +1,17 @@
-use crate::lang::errors::{error, CrushResult, mandate};
+use crate::lang::errors::{error, CrushResult, mandate, to_crush_error};
 use std::sync::{Arc, Mutex, MutexGuard};
 use crate::lang::{value::Value, value::ValueType};
 use ordered_map::OrderedMap;
 use crate::lang::execution_context::ExecutionContext;
-use crate::lang::command::{CrushCommand, Command, OutputType};
+use crate::lang::command::{CrushCommand, Command, OutputType, ArgumentDescription};
 use crate::lang::r#struct::Struct;
 use crate::util::identity_arc::Identity;
 use crate::lang::help::Help;
 use std::cmp::max;
+use std::path::PathBuf;
+use crate::lang::process_limits::ProcessLimits;
+use crate::lang::process_environment::ProcessEnvironment;
+use crate::lang::materialization::MaterializationPolicy;
 
 /**
   This is where we store variables, including functions.
@@ -59,10 +63,25 @@ impl ScopeLoader {
         short_help: &'static str,
         long_help: Option<&'static str>,
         output: OutputType,
+    ) -> CrushResult<()> {
+        self.declare_command_with_arguments(name, call, can_block, signature, &[], short_help, long_help, output)
+    }
+
+    /** Declare a command whose arguments can be inspected, like the ones with a #[signature]. */
+    pub fn declare_command_with_arguments(
+        &mut self,
+        name: &str,
+        call: fn(ExecutionContext) -> CrushResult<()>,
+        can_block: bool,
+        signature: &'static str,
+        arguments: &'static [ArgumentDescription],
+        short_help: &'static str,
+        long_help: Option<&'static str>,
+        output: OutputType,
     ) -> CrushResult<()> {
         let mut full_name = self.path.clone();
         full_name.push(name.to_string());
-        let command = CrushCommand::command(call, can_block, full_name, signature, short_help, long_help, output);
+        let command = CrushCommand::command(call, can_block, full_name, signature, arguments, short_help, long_help, output);
         if self.mapping.contains_key(name) {
             return error(format!("Variable ${{{}}} already exists", name).as_str());
         }
@@ -136,6 +155,28 @@ pub struct ScopeData {
     lists can still be modified. */
     pub is_readonly: bool,
 
+    /** Temporary files created by jobs running in this scope. They are removed once the job that
+    created them has finished. */
+    pub temporary_files: Vec<PathBuf>,
+
+    /** Resource limits for external commands started from this scope or any scope it calls. */
+    pub process_limits: Option<ProcessLimits>,
+
+    /** Working directory and environment variables for external commands started from this
+    scope or any scope it calls. */
+    pub process_environment: Option<ProcessEnvironment>,
+
+    /** The files that code running in this scope or any scope it calls is being loaded from,
+    outermost first, for detecting files that load each other. */
+    pub loading: Option<Vec<PathBuf>>,
+
+    /** True if external commands started from this scope or any scope it calls should be given
+    the terminal through a pseudo terminal instead of having their output captured. */
+    pub use_pty: bool,
+
+    /** What happens to streams assigned to variables in this scope or any scope it calls. */
+    pub materialization: Option<MaterializationPolicy>,
+
     pub name: Option<String>,
     is_loaded: bool,
     loader: Option<Box<dyn Send + FnOnce(&mut ScopeLoader) -> CrushResult<()>>>,
@@ -151,6 +192,12 @@ impl ScopeData {
             mapping: OrderedMap::new(),
             is_stopped: false,
             is_readonly: false,
+            temporary_files: Vec::new(),
+            process_limits: None,
+            process_environment: None,
+            loading: None,
+            use_pty: false,
+            materialization: None,
             name,
             is_loaded: true,
             loader: None,
@@ -166,6 +213,12 @@ impl ScopeData {
             mapping: OrderedMap::new(),
             is_stopped: false,
             is_readonly: false,
+            temporary_files: Vec::new(),
+            process_limits: None,
+            process_environment: None,
+            loading: None,
+            use_pty: false,
+            materialization: None,
             name,
             is_loaded: false,
             loader: Some(loader),
@@ -183,6 +236,12 @@ impl Clone for ScopeData {
             mapping: self.mapping.clone(),
             is_stopped: self.is_stopped,
             is_readonly: self.is_readonly,
+            temporary_files: Vec::new(),
+            process_limits: self.process_limits.clone(),
+            process_environment: self.process_environment.clone(),
+            loading: self.loading.clone(),
+            use_pty: self.use_pty,
+            materialization: self.materialization.clone(),
             name: self.name.clone(),
             is_loaded: true,
             loader: None,
@@ -212,6 +271,12 @@ impl Scope {
                 is_loop,
                 is_stopped,
                 is_readonly,
+                temporary_files: Vec::new(),
+                process_limits: None,
+                process_environment: None,
+                loading: None,
+                use_pty: false,
+                materialization: None,
                 name,
                 is_loaded: true,
                 loader: None,
@@ -284,6 +349,99 @@ impl Scope {
         self.data.lock().unwrap().is_stopped
     }
 
+    pub fn add_temporary_file(&self, file: PathBuf) {
+        self.data.lock().unwrap().temporary_files.push(file);
+    }
+
+    pub fn remove_temporary_files(&self) -> CrushResult<()> {
+        let files = std::mem::take(&mut self.data.lock().unwrap().temporary_files);
+        for file in files {
+            if file.exists() {
+                to_crush_error(std::fs::remove_file(file))?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn set_process_limits(&self, limits: ProcessLimits) {
+        self.data.lock().unwrap().process_limits = Some(limits);
+    }
+
+    /** Returns the resource limits of the closest scope in the chain of calling scopes that has any. */
+    pub fn process_limits(&self) -> Option<ProcessLimits> {
+        let data = self.data.lock().unwrap();
+        if data.process_limits.is_some() {
+            return data.process_limits.clone();
+        }
+        let caller = data.calling_scope.clone();
+        drop(data);
+        caller.and_then(|c| c.process_limits())
+    }
+
+    pub fn set_process_environment(&self, environment: ProcessEnvironment) {
+        self.data.lock().unwrap().process_environment = Some(environment);
+    }
+
+    /** Returns the process environment of the closest scope in the chain of calling scopes that has one. */
+    pub fn process_environment(&self) -> Option<ProcessEnvironment> {
+        let data = self.data.lock().unwrap();
+        if data.process_environment.is_some() {
+            return data.process_environment.clone();
+        }
+        let caller = data.calling_scope.clone();
+        drop(data);
+        caller.and_then(|c| c.process_environment())
+    }
+
+    /** Set the files that are being loaded, and return the ones that were set before. */
+    pub fn set_loading(&self, files: Option<Vec<PathBuf>>) -> Option<Vec<PathBuf>> {
+        std::mem::replace(&mut self.data.lock().unwrap().loading, files)
+    }
+
+    /** Returns the files being loaded according to the closest scope in the chain of calling scopes that has any. */
+    pub fn loading(&self) -> Vec<PathBuf> {
+        let data = self.data.lock().unwrap();
+        if let Some(files) = &data.loading {
+            return files.clone();
+        }
+        let caller = data.calling_scope.clone();
+        drop(data);
+        caller.map(|c| c.loading()).unwrap_or_default()
+    }
+
+    pub fn set_use_pty(&self) {
+        self.data.lock().unwrap().use_pty = true;
+    }
+
+    pub fn use_pty(&self) -> bool {
+        let data = self.data.lock().unwrap();
+        if data.use_pty {
+            return true;
+        }
+        let caller = data.calling_scope.clone();
+        drop(data);
+        caller.map(|c| c.use_pty()).unwrap_or(false)
+    }
+
+    /** Set the materialization policies of this scope. Policies that are not set are kept as is. */
+    pub fn set_materialization_policy(&self, policy: MaterializationPolicy) {
+        let mut data = self.data.lock().unwrap();
+        data.materialization = Some(match &data.materialization {
+            Some(current) => policy.merge(current),
+            None => policy,
+        });
+    }
+
+    pub fn materialization_policy(&self) -> MaterializationPolicy {
+        let data = self.data.lock().unwrap();
+        let policy = data.materialization.clone().unwrap_or_default();
+        let caller = data.calling_scope.clone();
+        drop(data);
+        match caller {
+            Some(c) => policy.merge(&c.materialization_policy()),
+            None => policy,
+        }
+    }
 
     fn lock(&self) -> CrushResult<MutexGuard<ScopeData>> {
         let mut data = self.data.lock().unwrap();
@@ -327,7 +485,9 @@ impl Scope {
                 None => Ok(vec![name]),
                 Some(parent) => {
                     drop(data);
-                    let mut full_path = parent.full_path()?;
+                    let is_anonymous = parent.data.lock().unwrap().name.is_none();
+                    /* Namespaces declared in a local scope, e.g. by var:mod, have paths relative to that scope. */
+                    let mut full_path = if is_anonymous { Vec::new() } else { parent.full_path()? };
                     full_path.push(name);
                     Ok(full_path)
                 }
@@ -521,11 +681,17 @@ impl Scope {
     }
 
     pub fn dump(&self, map: &mut OrderedMap<String, ValueType>) -> CrushResult<()> {
-        if let Some(p) = self.lock()?.parent_scope.clone() {
+        // Used namespaces may be loaded by running code in a child of this scope, so this scope
+        // must not be locked while they are dumped
+        let (parent, uses) = {
+            let data = self.lock()?;
+            (data.parent_scope.clone(), data.uses.clone())
+        };
+        if let Some(p) = parent {
             p.dump(map)?;
         }
 
-        for u in self.data.lock().unwrap().uses.clone().iter().rev() {
+        for u in uses.iter().rev() {
             u.dump(map)?;
         }
 
@@ -544,6 +710,19 @@ impl Scope {
         Ok(self.lock()?.clone())
     }
 
+    /**
+    The names of the members of this scope, or None if it is a lazy namespace that has not
+    been loaded yet. Unlike export, this never runs the code that loads a namespace.
+    */
+    pub fn loaded_names(&self) -> Option<Vec<String>> {
+        let data = self.data.lock().unwrap();
+        if data.is_loaded {
+            Some(data.mapping.iter().map(|(k, _)| k.clone()).collect())
+        } else {
+            None
+        }
+    }
+
     pub fn set_parent(&self, parent: Option<Scope>) {
         self.data.lock().unwrap().parent_scope = parent;
     }
@@ -582,7 +761,7 @@ impl Help for Scope {
     fn long_help(&self) -> Option<String> {
         let mut lines = Vec::new();
 
-        let data = self.data.lock().unwrap();
+        let data = self.export().ok()?;
         let mut keys: Vec<_> = data.mapping.iter().collect();
         keys.sort_by(|x, y| x.0.cmp(&y.0));
 
@@ -17,6 +17,7 @@ use std::os::unix::ffi::OsStrExt;
 use chrono::offset::TimeZone;
 use crate::lang::dict::Dict;
 use crate::lang::scope::Scope;
+use crate::lang::style::{Color, Style, Styled};
 
 fn serialize_simple(value: &Value, elements: &mut Vec<Element>, state: &mut SerializationState) -> CrushResult<usize> {
     let idx = elements.len();
@@ -33,6 +34,15 @@ fn serialize_simple(value: &Value, elements: &mut Vec<Element>, state: &mut Seri
             Value::Empty() => element::Element::Empty(false),
             Value::Time(d) => element::Element::Time(d.timestamp_nanos()),
             Value::Field(f) => element::Element::Field(model::Strings { elements: f.clone() }),
+            Value::Styled(s) => element::Element::Styled(model::Styled {
+                text: s.text.clone(),
+                foreground: s.style.foreground.map(|c| c.to_string()).unwrap_or_default(),
+                background: s.style.background.map(|c| c.to_string()).unwrap_or_default(),
+                bold: s.style.bold,
+                dim: s.style.dim,
+                italic: s.style.italic,
+                underline: s.style.underline,
+            }),
             _ => return error("Expected simple value"),
         }),
     });
@@ -54,6 +64,17 @@ impl Serializable<Value> for Value {
             )),
             element::Element::Bool(v) => Ok(Value::Bool(*v)),
             element::Element::Empty(_) => Ok(Value::Empty()),
+            element::Element::Styled(s) => {
+                let color = |name: &str| if name.is_empty() { Ok(None) } else { Color::parse(name).map(Some) };
+                Ok(Value::Styled(Styled::new(&s.text, Style {
+                    foreground: color(&s.foreground)?,
+                    background: color(&s.background)?,
+                    bold: s.bold,
+                    dim: s.dim,
+                    italic: s.italic,
+                    underline: s.underline,
+                })))
+            }
 
             element::Element::SmallInteger(_) | element::Element::LargeInteger(_) =>
                 Ok(Value::Integer(i128::deserialize(id, elements, state)?)),
@@ -69,7 +90,7 @@ impl Serializable<Value> for Value {
             element::Element::Struct(_) => Ok(Value::Struct(Struct::deserialize(id, elements, state)?)),
 
             element::Element::Command(_) | element::Element::BoundCommand(_) |
-            element::Element::Closure(_) =>
+            element::Element::PartialCommand(_) | element::Element::Closure(_) =>
                 Ok(Value::Command(CrushCommand::deserialize(id, elements, state)?)),
 
             element::Element::Field(f) => Ok(Value::Field(f.elements.clone())),
@@ -91,7 +112,7 @@ impl Serializable<Value> for Value {
         match self {
             Value::String(_) | Value::Glob(_) | Value::Regex(_, _) | Value::File(_) |
             Value::Binary(_) | Value::Float(_) | Value::Bool(_) | Value::Empty() |
-            Value::Time(_) | Value::Field(_) => serialize_simple(self, elements, state),
+            Value::Time(_) | Value::Field(_) | Value::Styled(_) => serialize_simple(self, elements, state),
 
             Value::Integer(s) => s.serialize(elements, state),
 
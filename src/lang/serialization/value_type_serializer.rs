@@ -30,6 +30,7 @@ impl Serializable<ValueType> for ValueType {
                         14 => ValueType::Time,
                         15 => ValueType::Struct,
                         16 => ValueType::Any,
+                        18 => ValueType::Styled,
                         _ => return error("Unrecognised type")
                     })
                 }
@@ -79,6 +80,7 @@ impl Serializable<ValueType> for ValueType {
             ValueType::Any => SimpleTypeKind::Any,
             ValueType::Binary => SimpleTypeKind::Binary,
             ValueType::Type => SimpleTypeKind::Type,
+            ValueType::Styled => SimpleTypeKind::Styled,
             ValueType::List(t) => {
                 let l = model::ListType { element_type: t.serialize(elements, state)? as u64 };
                 let idx = elements.len();
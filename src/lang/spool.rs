@@ -0,0 +1,162 @@
+use crate::lang::table::Row;
+use crate::lang::value::Value;
+use crate::lang::list::List;
+use crate::lang::value::ValueType;
+use crate::lang::scope::Scope;
+use crate::lang::errors::{CrushResult, error, to_crush_error};
+use crate::lang::serialization::{serialize, deserialize};
+use crate::lang::stream::BatchReceiver;
+use std::fs::{File, OpenOptions};
+use std::os::unix::fs::FileExt;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/**
+The number of rows a spool keeps in memory before it starts writing rows to disk.
+*/
+const MAX_MEMORY_ROWS: usize = 10_000;
+
+static SPOOL_FILE_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+enum Entry {
+    Memory(Row),
+    Disk { offset: u64, len: usize },
+}
+
+struct Storage {
+    entries: Vec<Entry>,
+    rows_in_memory: usize,
+    file: Option<File>,
+    file_len: u64,
+    done: bool,
+}
+
+/**
+Only rows made up of plain data are written to disk. Commands and scopes can't be
+deserialized without the scope they came from, so rows containing them stay in memory.
+*/
+fn can_write_to_disk(row: &Row) -> bool {
+    row.cells().iter().all(|c| matches!(c,
+        Value::String(_) | Value::Integer(_) | Value::Float(_) | Value::Bool(_) |
+        Value::File(_) | Value::Time(_) | Value::Duration(_) | Value::Empty() |
+        Value::Glob(_) | Value::Regex(_, _) | Value::Field(_) | Value::Binary(_) |
+        Value::Styled(_)))
+}
+
+/**
+Create an anonymous temporary file. The file is removed right away, and its space is
+reclaimed as soon as it is closed.
+*/
+fn temporary_file() -> CrushResult<File> {
+    let path = std::env::temp_dir().join(format!(
+        "crush-spool-{}-{}", std::process::id(), SPOOL_FILE_COUNT.fetch_add(1, Ordering::SeqCst)));
+    let file = to_crush_error(OpenOptions::new().read(true).write(true).create_new(true).open(&path))?;
+    to_crush_error(std::fs::remove_file(&path))?;
+    Ok(file)
+}
+
+impl Storage {
+    fn push(&mut self, row: Row) -> CrushResult<()> {
+        if self.rows_in_memory < MAX_MEMORY_ROWS || !can_write_to_disk(&row) {
+            self.rows_in_memory += 1;
+            self.entries.push(Entry::Memory(row));
+            return Ok(());
+        }
+        if self.file.is_none() {
+            self.file = Some(temporary_file()?);
+        }
+        let mut buf = Vec::new();
+        serialize(&Value::List(List::new(ValueType::Any, row.into_vec())), &mut buf)?;
+        let file = self.file.as_ref().unwrap();
+        to_crush_error(file.write_all_at(&buf, self.file_len))?;
+        self.entries.push(Entry::Disk { offset: self.file_len, len: buf.len() });
+        self.file_len += buf.len() as u64;
+        Ok(())
+    }
+
+    fn get(&self, idx: usize) -> CrushResult<Row> {
+        match &self.entries[idx] {
+            Entry::Memory(row) => Ok(row.clone()),
+            Entry::Disk { offset, len } => {
+                let mut buf = vec![0u8; *len];
+                to_crush_error(self.file.as_ref().unwrap().read_exact_at(&mut buf, *offset))?;
+                match deserialize(&buf, &Scope::create_root())? {
+                    Value::List(l) => Ok(Row::new(l.dump())),
+                    _ => error("Corrupt spool file"),
+                }
+            }
+        }
+    }
+}
+
+/**
+The rows of a replayable stream that have been read so far. Any number of readers can read
+the rows, each at its own pace. Rows are read from the underlying stream as the reader that
+has gotten the furthest needs them, so the stream is never read further than needed. The
+first rows are kept in memory, and the rest are written to a temporary file.
+*/
+pub struct Spool {
+    receiver: BatchReceiver,
+    receiving: Mutex<()>,
+    storage: Mutex<Storage>,
+}
+
+impl Spool {
+    pub fn new(receiver: BatchReceiver, rows: Vec<Row>) -> CrushResult<Spool> {
+        let mut storage = Storage {
+            entries: Vec::new(),
+            rows_in_memory: 0,
+            file: None,
+            file_len: 0,
+            done: false,
+        };
+        for row in rows {
+            storage.push(row)?;
+        }
+        Ok(Spool {
+            receiver,
+            receiving: Mutex::new(()),
+            storage: Mutex::new(storage),
+        })
+    }
+
+    /**
+    Return the row at the specified index, or None if the underlying stream ended before it.
+    The receive function is used to read more rows from the underlying stream if needed, and
+    returns None once the stream has ended.
+    */
+    pub fn get<E>(
+        &self,
+        idx: usize,
+        receive: impl Fn(&BatchReceiver) -> Result<Option<Vec<Row>>, E>,
+    ) -> Result<Option<CrushResult<Row>>, E> {
+        loop {
+            {
+                let storage = self.storage.lock().unwrap();
+                if idx < storage.entries.len() {
+                    return Ok(Some(storage.get(idx)));
+                }
+                if storage.done {
+                    return Ok(None);
+                }
+            }
+            // Only one reader at a time reads from the underlying stream, while the others
+            // can keep reading the rows that are already spooled.
+            let _receiving = self.receiving.lock().unwrap();
+            if idx < self.storage.lock().unwrap().entries.len() {
+                continue;
+            }
+            match receive(&self.receiver)? {
+                Some(batch) => {
+                    let mut storage = self.storage.lock().unwrap();
+                    for row in batch {
+                        if let Err(e) = storage.push(row) {
+                            return Ok(Some(Err(e)));
+                        }
+                    }
+                }
+                None => self.storage.lock().unwrap().done = true,
+            }
+        }
+    }
+}
@@ -2,7 +2,11 @@ use crate::lang::table::ColumnType;
 use crate::lang::value::Value;
 use crate::lang::{table::Row};
 use crossbeam::{Receiver, bounded, unbounded, Sender};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use crate::lang::errors::{CrushError, error, CrushResult, to_crush_error, send_error};
+use crate::lang::spool::Spool;
 use lazy_static::lazy_static;
 use chrono::Duration;
 
@@ -54,28 +58,143 @@ impl ValueReceiver {
     }
 }
 
-pub enum OutputStream {
-    Sync(Sender<Row>),
-    Async(Sender<Row>),
+/**
+    The maximum number of rows that are sent across a stream channel as a
+    single message.
+*/
+const MAX_BATCH_SIZE: usize = 64;
+
+/**
+    The sending end of a table stream.
+
+    Rows are sent in batches in order to reduce the per row overhead of the
+    channel. A row is sent right away if the receiving end has nothing left to
+    read, so batching only happens while the receiving end is busy. Rows that
+    are held back for the next batch are shared with the receiving end, which
+    takes them itself once it has read everything else, so rows are never
+    held back while the receiving end is waiting for them, however long the
+    sending end pauses.
+*/
+pub struct OutputStream {
+    sender: Sender<Vec<Row>>,
+    pending: Arc<Mutex<Vec<Row>>>,
 }
 
 impl OutputStream {
     pub fn send(&self, row: Row) -> CrushResult<()> {
-        let native_output = match self {
-            OutputStream::Sync(s) => s.send(row),
-            OutputStream::Async(s) => s.send(row),
+        let batch = {
+            let mut pending = self.pending.lock().unwrap();
+            pending.push(row);
+            if pending.len() >= MAX_BATCH_SIZE || self.sender.is_empty() {
+                std::mem::replace(&mut *pending, Vec::with_capacity(MAX_BATCH_SIZE))
+            } else {
+                return Ok(());
+            }
         };
-        match native_output {
+        // The lock is not held while sending, since the channel may be full.
+        match self.sender.send(batch) {
+            Ok(_) => Ok(()),
+            Err(_) => error("Broken pipe"),
+        }
+    }
+
+    /** Send the rows held back for the next batch right away. */
+    pub fn flush(&self) -> CrushResult<()> {
+        let batch = std::mem::take(&mut *self.pending.lock().unwrap());
+        if batch.is_empty() {
+            return Ok(());
+        }
+        match self.sender.send(batch) {
             Ok(_) => Ok(()),
             Err(_) => error("Broken pipe"),
         }
     }
 }
 
-#[derive(Debug, Clone)]
+impl Drop for OutputStream {
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
+
+/**
+    The receiving end of the channel of a table stream, along with the rows
+    the sending end holds back for its next batch.
+*/
+#[derive(Clone)]
+pub struct BatchReceiver {
+    receiver: Receiver<Vec<Row>>,
+    pending: Arc<Mutex<Vec<Row>>>,
+}
+
+impl BatchReceiver {
+    /**
+        The next batch that can be read without waiting. The channel is read
+        before the held back rows, which were sent after anything in it. The
+        lock keeps the sending end from holding back another row in between.
+    */
+    fn try_recv(&self) -> Option<Vec<Row>> {
+        let mut pending = self.pending.lock().unwrap();
+        match self.receiver.try_recv() {
+            Ok(batch) => Some(batch),
+            Err(_) if !pending.is_empty() => Some(std::mem::take(&mut *pending)),
+            Err(_) => None,
+        }
+    }
+
+    pub fn recv(&self) -> Result<Vec<Row>, crossbeam::channel::RecvError> {
+        match self.try_recv() {
+            Some(batch) => Ok(batch),
+            None => self.receiver.recv(),
+        }
+    }
+
+    pub fn recv_timeout(&self, timeout: std::time::Duration) -> Result<Vec<Row>, RecvTimeoutError> {
+        match self.try_recv() {
+            Some(batch) => Ok(batch),
+            None => self.receiver.recv_timeout(timeout),
+        }
+    }
+}
+
+/**
+    The receiving end of a table stream.
+
+    Clones of an input stream share the same buffer, so every row is only
+    read once, no matter which clone reads it. The exception is replayable
+    streams, where every clone starts reading from the first row.
+*/
 pub struct InputStream {
-    receiver: Receiver<Row>,
+    receiver: BatchReceiver,
+    buffer: Arc<Mutex<VecDeque<Row>>>,
     types: Vec<ColumnType>,
+    spool: Option<SpoolReader>,
+}
+
+/**
+    A reader of a replayable stream, i.e. the spooled rows and the position
+    of one clone of the stream in them.
+*/
+struct SpoolReader {
+    spool: Arc<Spool>,
+    position: AtomicUsize,
+}
+
+impl Clone for InputStream {
+    fn clone(&self) -> Self {
+        InputStream {
+            receiver: self.receiver.clone(),
+            buffer: self.buffer.clone(),
+            types: self.types.clone(),
+            spool: self.spool.as_ref().map(|s| SpoolReader { spool: s.spool.clone(), position: AtomicUsize::new(0) }),
+        }
+    }
+}
+
+impl std::fmt::Debug for InputStream {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("InputStream").field("types", &self.types).finish()
+    }
 }
 
 impl InputStream {
@@ -95,12 +214,76 @@ impl InputStream {
         }
     }
 
+    fn next_buffered(&self) -> Option<Row> {
+        self.buffer.lock().unwrap().pop_front()
+    }
+
+    fn fill(&self, batch: Vec<Row>) {
+        self.buffer.lock().unwrap().extend(batch);
+    }
+
+    /**
+        Turn this stream into one that can be read any number of times, by
+        any number of readers at once. Every clone of the returned stream
+        reads all rows from the start. See Spool for how rows are stored.
+    */
+    pub fn replayable(self) -> CrushResult<InputStream> {
+        if self.spool.is_some() {
+            return Ok(self);
+        }
+        let rows = self.buffer.lock().unwrap().drain(..).collect();
+        Ok(InputStream {
+            receiver: self.receiver.clone(),
+            buffer: Arc::new(Mutex::new(VecDeque::new())),
+            spool: Some(SpoolReader {
+                spool: Arc::new(Spool::new(self.receiver, rows)?),
+                position: AtomicUsize::new(0),
+            }),
+            types: self.types,
+        })
+    }
+
     pub fn recv(&self) -> CrushResult<Row> {
-        self.validate(to_crush_error(self.receiver.recv()))
+        if let Some(reader) = &self.spool {
+            let position = reader.position.load(Ordering::SeqCst);
+            return match reader.spool.get(position, |r| Ok(r.recv().ok()))? {
+                Some(row) => {
+                    reader.position.store(position + 1, Ordering::SeqCst);
+                    self.validate(row)
+                }
+                None => error("EOF"),
+            };
+        }
+        loop {
+            if let Some(row) = self.next_buffered() {
+                return self.validate(Ok(row));
+            }
+            self.fill(to_crush_error(self.receiver.recv())?);
+        }
     }
 
     pub fn recv_timeout(&self, timeout: Duration) -> Result<Row, RecvTimeoutError> {
-        self.receiver.recv_timeout(timeout.to_std().unwrap())
+        if let Some(reader) = &self.spool {
+            let position = reader.position.load(Ordering::SeqCst);
+            let next = reader.spool.get(position, |r| match r.recv_timeout(timeout.to_std().unwrap()) {
+                Ok(batch) => Ok(Some(batch)),
+                Err(RecvTimeoutError::Disconnected) => Ok(None),
+                Err(e) => Err(e),
+            })?;
+            return match next {
+                Some(Ok(row)) => {
+                    reader.position.store(position + 1, Ordering::SeqCst);
+                    Ok(row)
+                }
+                _ => Err(RecvTimeoutError::Disconnected),
+            };
+        }
+        loop {
+            if let Some(row) = self.next_buffered() {
+                return Ok(row);
+            }
+            self.fill(self.receiver.recv_timeout(timeout.to_std().unwrap())?);
+        }
     }
 
     pub fn types(&self) -> &[ColumnType] {
@@ -134,14 +317,27 @@ pub fn channels() -> (ValueSender, ValueReceiver) {
     (ValueSender {sender: send}, ValueReceiver { receiver: recv })
 }
 
+fn stream_pair(sender: Sender<Vec<Row>>, receiver: Receiver<Vec<Row>>, signature: Vec<ColumnType>) -> (OutputStream, InputStream) {
+    let pending = Arc::new(Mutex::new(Vec::new()));
+    (
+        OutputStream { sender, pending: pending.clone() },
+        InputStream {
+            receiver: BatchReceiver { receiver, pending },
+            buffer: Arc::new(Mutex::new(VecDeque::new())),
+            types: signature,
+            spool: None,
+        },
+    )
+}
+
 pub fn streams(signature: Vec<ColumnType>) -> (OutputStream, InputStream) {
-    let (output, input) = bounded(128);
-    (OutputStream::Sync(output), InputStream { receiver: input, types: signature })
+    let (output, input) = bounded(16);
+    stream_pair(output, input, signature)
 }
 
 pub fn unlimited_streams(signature: Vec<ColumnType>) -> (OutputStream, InputStream) {
     let (output, input) = unbounded();
-    (OutputStream::Async(output), InputStream { receiver: input, types: signature })
+    stream_pair(output, input, signature)
 }
 
 pub fn empty_channel() -> ValueReceiver {
@@ -154,6 +350,14 @@ pub trait CrushStream {
     fn read(&mut self) -> CrushResult<Row>;
     fn read_timeout(&mut self, timeout: Duration) -> Result<Row, RecvTimeoutError>;
     fn types(&self) -> &[ColumnType];
+
+    /**
+        The number of rows left to read, if the stream knows it without
+        reading them.
+    */
+    fn row_count(&self) -> Option<usize> {
+        None
+    }
 }
 
 impl CrushStream for InputStream {
@@ -171,3 +375,24 @@ impl CrushStream for InputStream {
 }
 
 pub type Stream = Box<dyn CrushStream>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lang::value::ValueType;
+
+    #[test]
+    fn held_back_rows_are_received() {
+        let (output, input) = streams(vec![ColumnType::new("value", ValueType::Integer)]);
+        for i in 0..3 {
+            output.send(Row::new(vec![Value::Integer(i)])).unwrap();
+        }
+        // The sending end is still alive and never flushes.
+        for i in 0..3 {
+            let row = input.recv_timeout(Duration::seconds(1)).unwrap();
+            assert!(row.cells()[0] == Value::Integer(i));
+        }
+        assert!(input.recv_timeout(Duration::milliseconds(10)).is_err());
+        drop(output);
+    }
+}
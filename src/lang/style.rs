@@ -0,0 +1,221 @@
+use crate::lang::errors::{CrushResult, argument_error};
+use std::fmt::{Display, Formatter};
+
+/**
+A color that text can be rendered in. The basic colors and their bright variants are
+supported by practically all terminals, rgb colors need a terminal with true color support.
+*/
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Color {
+    Black,
+    Red,
+    Green,
+    Yellow,
+    Blue,
+    Magenta,
+    Cyan,
+    White,
+    Bright(u8),
+    Rgb(u8, u8, u8),
+}
+
+const NAMES: [&str; 8] = ["black", "red", "green", "yellow", "blue", "magenta", "cyan", "white"];
+
+impl Color {
+    fn basic(idx: u8) -> Color {
+        match idx {
+            0 => Color::Black,
+            1 => Color::Red,
+            2 => Color::Green,
+            3 => Color::Yellow,
+            4 => Color::Blue,
+            5 => Color::Magenta,
+            6 => Color::Cyan,
+            _ => Color::White,
+        }
+    }
+
+    fn index(&self) -> Option<u8> {
+        match self {
+            Color::Black => Some(0),
+            Color::Red => Some(1),
+            Color::Green => Some(2),
+            Color::Yellow => Some(3),
+            Color::Blue => Some(4),
+            Color::Magenta => Some(5),
+            Color::Cyan => Some(6),
+            Color::White => Some(7),
+            Color::Bright(_) | Color::Rgb(_, _, _) => None,
+        }
+    }
+
+    /**
+    Parse a color name like `red` or `bright_red`, or an rgb color on the form `#rrggbb`.
+    */
+    pub fn parse(name: &str) -> CrushResult<Color> {
+        if let Some(hex) = name.strip_prefix('#') {
+            if hex.len() == 6 && hex.chars().all(|c| c.is_ascii_hexdigit()) {
+                let component = |idx: usize| u8::from_str_radix(&hex[idx..idx + 2], 16).unwrap();
+                return Ok(Color::Rgb(component(0), component(2), component(4)));
+            }
+        } else if let Some(basic) = name.strip_prefix("bright_") {
+            if let Some(idx) = NAMES.iter().position(|n| *n == basic) {
+                return Ok(Color::Bright(idx as u8));
+            }
+        } else if let Some(idx) = NAMES.iter().position(|n| *n == name) {
+            return Ok(Color::basic(idx as u8));
+        }
+        argument_error(format!(
+            "Unknown color {}, expected one of {}, the same names prefixed with bright_, or #rrggbb",
+            name, NAMES.join(", ")).as_str())
+    }
+
+    /**
+    The red, green and blue components this color is drawn with outside of a terminal,
+    e.g. when rendering to an image. The basic colors use a common terminal palette.
+    */
+    pub fn rgb(&self) -> (u8, u8, u8) {
+        const PALETTE: [(u8, u8, u8); 16] = [
+            (0, 0, 0), (205, 49, 49), (13, 188, 121), (229, 229, 16),
+            (36, 114, 200), (188, 63, 188), (17, 168, 205), (229, 229, 229),
+            (102, 102, 102), (241, 76, 76), (35, 209, 139), (245, 245, 67),
+            (59, 142, 234), (214, 112, 214), (41, 184, 219), (255, 255, 255),
+        ];
+        match self {
+            Color::Bright(idx) => PALETTE[8 + *idx as usize],
+            Color::Rgb(r, g, b) => (*r, *g, *b),
+            basic => PALETTE[basic.index().unwrap() as usize],
+        }
+    }
+
+    /** The select graphic rendition parameters for using this color, offset by 30 for foreground or 40 for background. */
+    fn sgr(&self, base: u8) -> String {
+        match self {
+            Color::Bright(idx) => (base + 60 + idx).to_string(),
+            Color::Rgb(r, g, b) => format!("{};2;{};{};{}", base + 8, r, g, b),
+            basic => (base + basic.index().unwrap()).to_string(),
+        }
+    }
+}
+
+impl Display for Color {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Color::Bright(idx) => write!(f, "bright_{}", NAMES[*idx as usize]),
+            Color::Rgb(r, g, b) => write!(f, "#{:02x}{:02x}{:02x}", r, g, b),
+            basic => f.write_str(NAMES[basic.index().unwrap() as usize]),
+        }
+    }
+}
+
+/**
+The set of attributes that text is rendered with.
+*/
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default)]
+pub struct Style {
+    pub foreground: Option<Color>,
+    pub background: Option<Color>,
+    pub bold: bool,
+    pub dim: bool,
+    pub italic: bool,
+    pub underline: bool,
+}
+
+impl Style {
+    /**
+    Combine two styles, with the attributes of the other style taking precedence.
+    */
+    pub fn merge(&self, other: &Style) -> Style {
+        Style {
+            foreground: other.foreground.or(self.foreground),
+            background: other.background.or(self.background),
+            bold: self.bold || other.bold,
+            dim: self.dim || other.dim,
+            italic: self.italic || other.italic,
+            underline: self.underline || other.underline,
+        }
+    }
+
+    /**
+    The escape sequence that turns this style on, or the empty string for the plain style.
+    */
+    pub fn escape(&self) -> String {
+        let mut codes = Vec::new();
+        if self.bold {
+            codes.push("1".to_string());
+        }
+        if self.dim {
+            codes.push("2".to_string());
+        }
+        if self.italic {
+            codes.push("3".to_string());
+        }
+        if self.underline {
+            codes.push("4".to_string());
+        }
+        if let Some(color) = &self.foreground {
+            codes.push(color.sgr(30));
+        }
+        if let Some(color) = &self.background {
+            codes.push(color.sgr(40));
+        }
+        if codes.is_empty() {
+            String::new()
+        } else {
+            format!("\x1b[{}m", codes.join(";"))
+        }
+    }
+
+    /** Wrap the text in the escape sequences needed to render it using this style. */
+    pub fn apply(&self, text: &str) -> String {
+        let escape = self.escape();
+        if escape.is_empty() {
+            text.to_string()
+        } else {
+            format!("{}{}\x1b[0m", escape, text)
+        }
+    }
+}
+
+/**
+Text together with the attributes it should be rendered with. The attributes are only
+turned into escape codes when the text is printed to a terminal, everywhere else a
+styled value behaves like its text.
+*/
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub struct Styled {
+    pub text: String,
+    pub style: Style,
+}
+
+impl Styled {
+    pub fn new(text: &str, style: Style) -> Styled {
+        Styled { text: text.to_string(), style }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_color() {
+        assert_eq!(Color::parse("red").unwrap(), Color::Red);
+        assert_eq!(Color::parse("bright_blue").unwrap(), Color::Bright(4));
+        assert_eq!(Color::parse("#ff8000").unwrap(), Color::Rgb(255, 128, 0));
+        assert!(Color::parse("purple").is_err());
+        assert!(Color::parse("#ff80").is_err());
+        for name in &["white", "bright_black", "#0a0b0c"] {
+            assert_eq!(Color::parse(name).unwrap().to_string(), *name);
+        }
+    }
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(Style::default().apply("x"), "x");
+        let style = Style { foreground: Some(Color::Red), bold: true, ..Style::default() };
+        assert_eq!(style.apply("x"), "\x1b[1;31mx\x1b[0m");
+        let style = Style { background: Some(Color::Rgb(1, 2, 3)), foreground: Some(Color::Bright(2)), ..Style::default() };
+        assert_eq!(style.escape(), "\x1b[92;48;2;1;2;3m");
+    }
+}
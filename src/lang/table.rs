@@ -4,6 +4,7 @@ use crate::lang::stream::{CrushStream};
 use crate::util::replace::Replace;
 use crate::lang::value::ValueType;
 use time::Duration;
+use std::collections::HashSet;
 
 #[derive(PartialEq, PartialOrd, Clone)]
 pub struct Table {
@@ -67,6 +68,10 @@ impl CrushStream for TableReader {
     fn types(&self) -> &[ColumnType] {
         &self.row_type
     }
+
+    fn row_count(&self) -> Option<usize> {
+        Some(self.rows.rows().len() - self.idx)
+    }
 }
 
 #[derive(PartialEq, PartialOrd, Eq, Hash, Clone)]
@@ -129,12 +134,152 @@ impl ColumnType {
     }
 }
 
+/**
+    Maps rows from one set of columns onto another set of columns with the
+    same names, possibly in a different order. Integer columns can be widened
+    into float columns, all other columns must have identical types.
+*/
+pub struct ColumnMapping {
+    indices: Vec<usize>,
+    widen: Vec<bool>,
+}
+
+impl ColumnMapping {
+    pub fn new(from: &[ColumnType], to: &[ColumnType]) -> CrushResult<ColumnMapping> {
+        let mut indices = Vec::new();
+        let mut widen = Vec::new();
+        let mut mismatched = Vec::new();
+        let mut used = vec![false; from.len()];
+        for target in to {
+            /* Columns that share a name are matched in order. */
+            match (0..from.len()).find(|idx| !used[*idx] && from[*idx].name == target.name) {
+                Some(idx) => {
+                    used[idx] = true;
+                    let source = &from[idx].cell_type;
+                    if source == &target.cell_type {
+                        widen.push(false);
+                    } else if ColumnType::widen(source, &target.cell_type) == Some(target.cell_type.clone()) {
+                        widen.push(true);
+                    } else {
+                        mismatched.push(format!(
+                            "{} ({} vs {})",
+                            target.name, source.to_string(), target.cell_type.to_string()));
+                    }
+                    indices.push(idx);
+                }
+                None => mismatched.push(format!("{} (missing)", target.name)),
+            }
+        }
+        if from.len() != to.len() {
+            for c in from {
+                if !to.iter().any(|t| t.name == c.name) {
+                    mismatched.push(format!("{} (unexpected)", c.name));
+                }
+            }
+        }
+        if mismatched.is_empty() {
+            Ok(ColumnMapping { indices, widen })
+        } else {
+            argument_error(format!("Mismatched columns: {}", mismatched.join(", ")).as_str())
+        }
+    }
+
+    pub fn map(&self, row: Row) -> CrushResult<Row> {
+        let mut cells = row.into_vec();
+        let mut res = Vec::with_capacity(self.indices.len());
+        for (idx, widen) in self.indices.iter().zip(self.widen.iter()) {
+            let cell = cells.replace(*idx, Value::Empty());
+            res.push(if *widen { cell.convert(ValueType::Float)? } else { cell });
+        }
+        Ok(Row::new(res))
+    }
+}
+
+impl ColumnType {
+    /**
+        Rename columns so that no two columns share the same name. The first
+        column with a given name keeps it, later ones get a numeric suffix,
+        e.g. name, name_1, name_2.
+    */
+    pub fn deduplicate(types: &mut [ColumnType]) {
+        let original = types.iter().map(|t| t.name.clone()).collect::<HashSet<String>>();
+        let mut taken = HashSet::new();
+        for t in types.iter_mut() {
+            if taken.contains(&t.name) {
+                let mut suffix = 1;
+                let mut name = format!("{}_{}", t.name, suffix);
+                while taken.contains(&name) || original.contains(&name) {
+                    suffix += 1;
+                    name = format!("{}_{}", t.name, suffix);
+                }
+                t.name = name;
+            }
+            taken.insert(t.name.clone());
+        }
+    }
+
+    /**
+        Returns the narrowest type both of the specified types can be converted to
+        without loss, if any.
+    */
+    pub fn widen(a: &ValueType, b: &ValueType) -> Option<ValueType> {
+        match (a, b) {
+            (a, b) if a == b => Some(a.clone()),
+            (ValueType::Integer, ValueType::Float) | (ValueType::Float, ValueType::Integer) =>
+                Some(ValueType::Float),
+            _ => None,
+        }
+    }
+
+    /**
+        Calculate the columns of a stream that every one of the specified
+        column sets can be mapped onto. Columns are matched by name, the column
+        order of the first set is used.
+    */
+    pub fn merge(types: &[&[ColumnType]]) -> CrushResult<Vec<ColumnType>> {
+        let mut res = match types.first() {
+            Some(t) => t.to_vec(),
+            None => return Ok(vec![]),
+        };
+        for other in &types[1..] {
+            for col in res.iter_mut() {
+                if let Some(o) = other.iter().find(|c| c.name == col.name) {
+                    if let Some(t) = ColumnType::widen(&col.cell_type, &o.cell_type) {
+                        col.cell_type = t;
+                    }
+                }
+            }
+        }
+        for other in types {
+            ColumnMapping::new(other, &res)?;
+        }
+        Ok(res)
+    }
+}
+
 impl ToString for ColumnType {
     fn to_string(&self) -> String {
         format!("{}=({})", self.name, self.cell_type.to_string())
     }
 }
 
+fn find_unique(columns: &[ColumnType], needle: &str) -> CrushResult<Option<usize>> {
+    let matches = columns.iter()
+        .enumerate()
+        .filter(|(_, c)| c.name == needle)
+        .map(|(idx, _)| idx)
+        .collect::<Vec<_>>();
+    match matches.len() {
+        0 => Ok(None),
+        1 => Ok(Some(matches[0])),
+        _ => argument_error(format!(
+            "Ambiguous column name {}, it is used by the columns at positions {}",
+            needle,
+            matches.iter().map(|idx| idx.to_string()).collect::<Vec<String>>().join(", "),
+        ).as_str()),
+    }
+}
+
 pub trait ColumnVec {
     fn find_str(&self, needle: &str) -> CrushResult<usize>;
     fn find(&self, needle: &[String]) -> CrushResult<usize>;
@@ -142,10 +287,8 @@ pub trait ColumnVec {
 
 impl ColumnVec for &[ColumnType] {
     fn find_str(&self, needle: &str) -> CrushResult<usize> {
-        for (idx, field) in self.iter().enumerate() {
-            if field.name == needle {
-                return Ok(idx);
-            }
+        if let Some(idx) = find_unique(self, needle)? {
+            return Ok(idx);
         }
         argument_error(format!(
             "Unknown column {}, available columns are {}",
@@ -159,10 +302,8 @@ impl ColumnVec for &[ColumnType] {
             argument_error("Expected direct field")
         } else {
             let needle = &needle_vec[0];
-            for (idx, field) in self.iter().enumerate() {
-                if &field.name == needle {
-                    return Ok(idx);
-                }
+            if let Some(idx) = find_unique(self, needle)? {
+                return Ok(idx);
             }
 
             error(format!(
@@ -0,0 +1,135 @@
+use chrono::Duration;
+
+use crate::lang::errors::{CrushResult, argument_error, mandate};
+use crate::lang::value::Value;
+
+/**
+The binary operators of expressions like `1 + 2 * x`. The methods that implement the infix
+operators all dispatch through `Operator::apply`, so that every combination of operand types is
+described in one place.
+*/
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Operator {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Mod,
+}
+
+const NANOS_PER_SECOND: i128 = 1_000_000_000;
+
+fn overflow<T>(result: Option<T>) -> CrushResult<T> {
+    mandate(result, "Arithmetic overflow")
+}
+
+fn nanoseconds(d: Duration) -> i128 {
+    let seconds = d.num_seconds();
+    let rest = (d - Duration::seconds(seconds)).num_nanoseconds().unwrap_or(0);
+    seconds as i128 * NANOS_PER_SECOND + rest as i128
+}
+
+fn from_nanoseconds(n: i128) -> CrushResult<Duration> {
+    let seconds = n.div_euclid(NANOS_PER_SECOND);
+    if seconds.abs() > (i64::MAX / 1000) as i128 {
+        return argument_error("Arithmetic overflow");
+    }
+    overflow(Duration::seconds(seconds as i64)
+        .checked_add(&Duration::nanoseconds(n.rem_euclid(NANOS_PER_SECOND) as i64)))
+}
+
+fn divisor(b: i128) -> CrushResult<i128> {
+    if b == 0 {
+        argument_error("Division by zero")
+    } else {
+        Ok(b)
+    }
+}
+
+impl Operator {
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            Operator::Add => "+",
+            Operator::Sub => "-",
+            Operator::Mul => "*",
+            Operator::Div => "//",
+            Operator::Rem => "rem",
+            Operator::Mod => "mod",
+        }
+    }
+
+    pub fn apply(self, left: Value, right: Value) -> CrushResult<Value> {
+        use Operator::*;
+        match (self, left, right) {
+            (Add, Value::Integer(a), Value::Integer(b)) => Ok(Value::Integer(overflow(a.checked_add(b))?)),
+            (Sub, Value::Integer(a), Value::Integer(b)) => Ok(Value::Integer(overflow(a.checked_sub(b))?)),
+            (Mul, Value::Integer(a), Value::Integer(b)) => Ok(Value::Integer(overflow(a.checked_mul(b))?)),
+            (Div, Value::Integer(a), Value::Integer(b)) => Ok(Value::Integer(overflow(a.checked_div(divisor(b)?))?)),
+            (Rem, Value::Integer(a), Value::Integer(b)) => Ok(Value::Integer(overflow(a.checked_rem(divisor(b)?))?)),
+            (Mod, Value::Integer(a), Value::Integer(b)) => {
+                let r = overflow(a.checked_rem(divisor(b)?))?;
+                Ok(Value::Integer(if r != 0 && (r < 0) != (b < 0) { r + b } else { r }))
+            }
+
+            (op, Value::Integer(a), Value::Float(b)) => op.apply(Value::Float(a as f64), Value::Float(b)),
+            (op, Value::Float(a), Value::Integer(b)) => op.apply(Value::Float(a), Value::Float(b as f64)),
+            (Add, Value::Float(a), Value::Float(b)) => Ok(Value::Float(a + b)),
+            (Sub, Value::Float(a), Value::Float(b)) => Ok(Value::Float(a - b)),
+            (Mul, Value::Float(a), Value::Float(b)) => Ok(Value::Float(a * b)),
+            (Div, Value::Float(a), Value::Float(b)) => Ok(Value::Float(a / b)),
+
+            (Add, Value::Duration(a), Value::Duration(b)) => Ok(Value::Duration(overflow(a.checked_add(&b))?)),
+            (Sub, Value::Duration(a), Value::Duration(b)) => Ok(Value::Duration(overflow(a.checked_sub(&b))?)),
+            (Mul, Value::Duration(a), Value::Integer(b)) | (Mul, Value::Integer(b), Value::Duration(a)) =>
+                Ok(Value::Duration(from_nanoseconds(overflow(nanoseconds(a).checked_mul(b))?)?)),
+            (Div, Value::Duration(a), Value::Integer(b)) =>
+                Ok(Value::Duration(from_nanoseconds(nanoseconds(a) / divisor(b)?)?)),
+            (Div, Value::Duration(a), Value::Duration(b)) =>
+                Ok(Value::Float(nanoseconds(a) as f64 / divisor(nanoseconds(b))? as f64)),
+
+            (Add, Value::Time(a), Value::Duration(b)) | (Add, Value::Duration(b), Value::Time(a)) =>
+                Ok(Value::Time(overflow(a.checked_add_signed(b))?)),
+            (Sub, Value::Time(a), Value::Duration(b)) => Ok(Value::Time(overflow(a.checked_sub_signed(b))?)),
+            (Sub, Value::Time(a), Value::Time(b)) => Ok(Value::Duration(a.signed_duration_since(b))),
+
+            (Add, Value::String(a), Value::String(b)) => Ok(Value::String(a + &b)),
+            (Mul, Value::String(a), Value::Integer(b)) => {
+                if b < 0 || b > (1 << 30) / (a.len() as i128).max(1) {
+                    argument_error("A string can only be repeated a non-negative number of times, and up to a gigabyte in total")
+                } else {
+                    Ok(Value::String(a.repeat(b as usize)))
+                }
+            }
+
+            (op, l, r) => argument_error(format!(
+                "Can't apply {} to values of type {} and {}",
+                op.symbol(),
+                l.value_type().to_string(),
+                r.value_type().to_string(),
+            ).as_str()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::Operator::*;
+
+    #[test]
+    fn dispatch() {
+        assert!(Add.apply(Value::Integer(1), Value::Float(0.5)).unwrap() == Value::Float(1.5));
+        assert!(Mod.apply(Value::Integer(-7), Value::Integer(3)).unwrap() == Value::Integer(2));
+        assert!(Mod.apply(Value::Integer(7), Value::Integer(-3)).unwrap() == Value::Integer(-2));
+        assert!(Mul.apply(Value::Integer(3), Value::Duration(Duration::milliseconds(1500))).unwrap()
+            == Value::Duration(Duration::milliseconds(4500)));
+        assert!(Div.apply(Value::Duration(Duration::seconds(-3)), Value::Integer(2)).unwrap()
+            == Value::Duration(Duration::milliseconds(-1500)));
+        assert!(Mul.apply(Value::string("ab"), Value::Integer(3)).unwrap() == Value::string("ababab"));
+        assert!(Div.apply(Value::Integer(1), Value::Integer(0)).is_err());
+        assert!(Add.apply(Value::Integer(i128::MAX), Value::Integer(1)).is_err());
+        assert!(Mul.apply(Value::Duration(Duration::weeks(1000)), Value::Integer(1 << 100)).is_err());
+        assert!(Sub.apply(Value::string("a"), Value::string("b")).is_err());
+    }
+}
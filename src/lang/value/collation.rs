@@ -0,0 +1,204 @@
+use std::cmp::Ordering;
+use std::ffi::CString;
+use std::path::PathBuf;
+
+use crate::lang::errors::{CrushResult, argument_error};
+use crate::lang::style::{Style, Styled};
+use crate::lang::value::Value;
+
+/**
+Load the collation rules of the locale of the user. setlocale is not thread safe, so this must be
+called once at startup, before any other threads are started.
+*/
+pub fn init_locale() {
+    unsafe {
+        libc::setlocale(libc::LC_COLLATE, b"\0".as_ptr() as *const libc::c_char);
+    }
+}
+
+/**
+The ways text can be ordered. Collations only change how strings, files and styled text compare,
+all other values compare the same way under every collation.
+*/
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Collation {
+    /** Compare text character by character. */
+    Binary,
+    /** Compare runs of digits by their numeric value, so that file2 comes before file10. */
+    Natural,
+    /** Compare text using the collation rules of the locale given by LC_ALL, LC_COLLATE or LANG. */
+    Locale,
+    /** Compare text character by character, ignoring case. */
+    CaseInsensitive,
+}
+
+impl Collation {
+    pub fn parse(name: &str) -> CrushResult<Collation> {
+        match name {
+            "binary" => Ok(Collation::Binary),
+            "natural" => Ok(Collation::Natural),
+            "locale" => Ok(Collation::Locale),
+            "case_insensitive" => Ok(Collation::CaseInsensitive),
+            _ => argument_error(
+                format!("Unknown collation {}, expected one of binary, natural, locale and case_insensitive", name).as_str()),
+        }
+    }
+
+    pub fn compare(&self, a: &Value, b: &Value) -> Option<Ordering> {
+        if *self == Collation::Binary {
+            return a.partial_cmp(b);
+        }
+        match (a, b) {
+            (Value::String(a), Value::String(b)) => Some(self.compare_str(a, b)),
+            (Value::File(a), Value::File(b)) => Some(self.compare_str(&a.to_string_lossy(), &b.to_string_lossy())),
+            (Value::Styled(a), Value::Styled(b)) => Some(self.compare_str(&a.text, &b.text)),
+            _ => a.partial_cmp(b),
+        }
+    }
+
+    /**
+    A value that is equal for two values exactly when they collate as equal, ignoring the order of
+    characters that compare_str uses to break ties, e.g. the lowercase text for case_insensitive.
+    This lets uniq and group treat values that only differ in case or leading zeros as the same.
+    */
+    pub fn key(&self, value: &Value) -> Value {
+        match (self, value) {
+            (Collation::Binary, _) => value.clone(),
+            (_, Value::String(s)) => Value::String(self.key_str(s)),
+            (_, Value::File(f)) => Value::File(PathBuf::from(self.key_str(&f.to_string_lossy()))),
+            (_, Value::Styled(s)) => Value::Styled(Styled { text: self.key_str(&s.text), style: Style::default() }),
+            _ => value.clone(),
+        }
+    }
+
+    fn key_str(&self, s: &str) -> String {
+        match self {
+            Collation::Binary => s.to_string(),
+            Collation::Natural => natural_key(s),
+            Collation::Locale => locale_key(s),
+            Collation::CaseInsensitive => s.chars().flat_map(char::to_lowercase).collect(),
+        }
+    }
+
+    /** Text that collates as equal is ordered by its characters, so that sorting is deterministic. */
+    pub fn compare_str(&self, a: &str, b: &str) -> Ordering {
+        let res = match self {
+            Collation::Binary => Ordering::Equal,
+            Collation::Natural => natural(a, b),
+            Collation::Locale => locale(a, b),
+            Collation::CaseInsensitive => a.chars().flat_map(char::to_lowercase)
+                .cmp(b.chars().flat_map(char::to_lowercase)),
+        };
+        res.then_with(|| a.cmp(b))
+    }
+}
+
+/** Splits off the leading run of digits or of other characters. */
+fn chunk(s: &str) -> (&str, &str) {
+    let digits = s.starts_with(|c: char| c.is_ascii_digit());
+    let end = s.find(|c: char| c.is_ascii_digit() != digits).unwrap_or(s.len());
+    s.split_at(end)
+}
+
+fn natural(mut a: &str, mut b: &str) -> Ordering {
+    while !a.is_empty() && !b.is_empty() {
+        let (chunk_a, rest_a) = chunk(a);
+        let (chunk_b, rest_b) = chunk(b);
+        let both_digits = chunk_a.starts_with(|c: char| c.is_ascii_digit())
+            && chunk_b.starts_with(|c: char| c.is_ascii_digit());
+        let res = if both_digits {
+            // Numbers of any length are compared without parsing them, by ignoring leading
+            // zeros and then comparing first the number of digits and then the digits.
+            let number_a = chunk_a.trim_start_matches('0');
+            let number_b = chunk_b.trim_start_matches('0');
+            number_a.len().cmp(&number_b.len()).then_with(|| number_a.cmp(number_b))
+        } else {
+            chunk_a.cmp(chunk_b)
+        };
+        if res != Ordering::Equal {
+            return res;
+        }
+        a = rest_a;
+        b = rest_b;
+    }
+    a.len().cmp(&b.len())
+}
+
+/** The text with the leading zeros of every number removed. */
+fn natural_key(mut s: &str) -> String {
+    let mut res = String::new();
+    while !s.is_empty() {
+        let (chunk, rest) = chunk(s);
+        if chunk.starts_with(|c: char| c.is_ascii_digit()) {
+            let number = chunk.trim_start_matches('0');
+            res.push_str(if number.is_empty() { "0" } else { number });
+        } else {
+            res.push_str(chunk);
+        }
+        s = rest;
+    }
+    res
+}
+
+fn locale(a: &str, b: &str) -> Ordering {
+    match (CString::new(a), CString::new(b)) {
+        (Ok(a), Ok(b)) => unsafe { libc::strcoll(a.as_ptr(), b.as_ptr()) }.cmp(&0),
+        // Text with nul characters can't be passed to the C library
+        _ => a.cmp(b),
+    }
+}
+
+/**
+The text transformed by strxfrm, so that two keys are equal exactly when strcoll says the texts
+are. The bytes are hex encoded, since they need not be valid UTF-8.
+*/
+fn locale_key(s: &str) -> String {
+    let text = match CString::new(s) {
+        Ok(text) => text,
+        Err(_) => return s.to_string(),
+    };
+    let len = unsafe { libc::strxfrm(std::ptr::null_mut(), text.as_ptr(), 0) };
+    let mut buffer = vec![0 as libc::c_char; len + 1];
+    unsafe { libc::strxfrm(buffer.as_mut_ptr(), text.as_ptr(), len + 1) };
+    buffer[..len].iter().map(|b| format!("{:02x}", *b as u8)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sorted(collation: Collation, words: &[&str]) -> Vec<String> {
+        let mut res: Vec<String> = words.iter().map(|w| w.to_string()).collect();
+        res.sort_by(|a, b| collation.compare_str(a, b));
+        res
+    }
+
+    #[test]
+    fn collations() {
+        assert_eq!(
+            sorted(Collation::Natural, &["file10", "file2", "file02", "file1b", "file", "File3", "a100000000000000000000000000"]),
+            vec!["File3", "a100000000000000000000000000", "file", "file1b", "file02", "file2", "file10"]);
+        assert_eq!(
+            sorted(Collation::CaseInsensitive, &["b", "B", "a", "C"]),
+            vec!["a", "B", "b", "C"]);
+        assert_eq!(
+            sorted(Collation::Binary, &["b", "B", "a", "C"]),
+            vec!["B", "C", "a", "b"]);
+        assert_eq!(Collation::Locale.compare_str("a", "a"), Ordering::Equal);
+        assert!(Collation::parse("natural").is_ok());
+        assert!(Collation::parse("alphabetical").is_err());
+    }
+
+    #[test]
+    fn keys() {
+        let key = |collation: Collation, s: &str| collation.key(&Value::string(s));
+        assert!(key(Collation::Natural, "file002") == key(Collation::Natural, "file2"));
+        assert!(key(Collation::Natural, "a000b") == key(Collation::Natural, "a0b"));
+        assert!(key(Collation::Natural, "a0b") != key(Collation::Natural, "ab"));
+        assert!(key(Collation::CaseInsensitive, "File") == key(Collation::CaseInsensitive, "fILE"));
+        assert!(key(Collation::Binary, "File") != key(Collation::Binary, "file"));
+        assert!(key(Collation::Locale, "abc") == key(Collation::Locale, "abc"));
+        assert!(key(Collation::Locale, "abc") != key(Collation::Locale, "abd"));
+        assert!(Collation::Natural.key(&Value::Integer(7)) == Value::Integer(7));
+    }
+}
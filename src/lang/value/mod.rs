@@ -1,5 +1,7 @@
 mod value_definition;
 mod value_type;
+mod collation;
+mod arithmetic;
 
 use std::cmp::Ordering;
 use std::hash::Hasher;
@@ -25,14 +27,18 @@ use crate::lang::stream::{streams, InputStream, Stream};
 
 pub use value_type::ValueType;
 pub use value_definition::ValueDefinition;
+pub use collation::{Collation, init_locale};
+pub use arithmetic::Operator;
 use crate::lang::command::Command;
 use crate::lang::pretty_printer::format_buffer;
 use crate::util::regex::RegexFileMatcher;
 use crate::lang::printer::Printer;
 use crate::lang::help::Help;
+use crate::lang::style::Styled;
 use ordered_map::OrderedMap;
 
 pub type Field = Vec<String>;
+pub type Time = DateTime<Local>;
 
 pub enum Value {
     String(String),
@@ -56,6 +62,7 @@ pub enum Value {
     BinaryStream(Box<dyn BinaryReader + Send + Sync>),
     Binary(Vec<u8>),
     Type(ValueType),
+    Styled(Styled),
 }
 
 impl ToString for Value {
@@ -77,6 +84,7 @@ impl ToString for Value {
             Value::Binary(v) => format_buffer(v, true),
             Value::Type(t) => t.to_string(),
             Value::Struct(s) => s.to_string(),
+            Value::Styled(s) => s.text.clone(),
             _ => format!("<{}>", self.value_type().to_string()),
         }
     }
@@ -124,7 +132,12 @@ impl Value {
             Value::Struct(s) => {
                 res.append(&mut s.keys())
             }
-//            Value::Scope(subenv) => subenv.get(name),
+            Value::Scope(scope) => {
+                if let Ok(data) = scope.export() {
+                    res.extend(data.mapping.keys().cloned());
+                }
+                add_keys(self.value_type().fields(), &mut res)
+            }
             Value::Type(t) => {
                 add_keys(t.fields(), &mut res)
             }
@@ -201,6 +214,7 @@ impl Value {
             Value::BinaryStream(_) => ValueType::BinaryStream,
             Value::Binary(_) => ValueType::Binary,
             Value::Type(_) => ValueType::Type,
+            Value::Styled(_) => ValueType::Styled,
         }
     }
 
@@ -263,6 +277,8 @@ impl Value {
                 return Ok(Value::Bool(*i != 0)),
             (Value::Float(f), ValueType::Integer) =>
                 return Ok(Value::Integer(*f as i128)),
+            (Value::Integer(i), ValueType::Float) =>
+                return Ok(Value::Float(*i as f64)),
             _ => {}
         }
 
@@ -295,6 +311,7 @@ impl Value {
             ValueType::Any => error("Invalid convert"),
             ValueType::BinaryStream => error("invalid convert"),
             ValueType::Type => error("invalid convert"),
+            ValueType::Styled => Ok(Value::Styled(Styled::new(&str_val, Default::default()))),
         }
     }
 }
@@ -323,6 +340,7 @@ impl Clone for Value {
             Value::BinaryStream(v) => Value::BinaryStream(v.as_ref().clone()),
             Value::Binary(v) => Value::Binary(v.clone()),
             Value::Type(t) => Value::Type(t.clone()),
+            Value::Styled(s) => Value::Styled(s.clone()),
         }
     }
 }
@@ -370,6 +388,7 @@ impl std::hash::Hash for Value {
             }
             Value::Empty() => {}
             Value::Type(v) => v.to_string().hash(state),
+            Value::Styled(v) => v.hash(state),
         }
     }
 }
@@ -392,6 +411,7 @@ impl std::cmp::PartialEq for Value {
             (Value::Glob(val1), Value::Glob(val2)) => val1 == val2,
             (Value::Regex(val1, _), Value::Regex(val2, _)) => val1 == val2,
             (Value::File(val1), Value::String(val2)) => file_result_compare(&Path::new(&val2.to_string()), val1.as_ref()),
+            (Value::File(val1), Value::File(val2)) => val1 == val2,
             (Value::Table(val1), Value::Table(val2)) => match val1.partial_cmp(val2) {
                 None => false,
                 Some(o) => o == Ordering::Equal,
@@ -402,6 +422,7 @@ impl std::cmp::PartialEq for Value {
             (Value::Bool(val1), Value::Bool(val2)) => val1 == val2,
             (Value::Float(val1), Value::Float(val2)) => val1 == val2,
             (Value::Binary(val1), Value::Binary(val2)) => val1 == val2,
+            (Value::Styled(val1), Value::Styled(val2)) => val1 == val2,
             _ => false,
         }
     }
@@ -436,6 +457,7 @@ impl std::cmp::PartialOrd for Value {
             (Value::Bool(val1), Value::Bool(val2)) => Some(val1.cmp(val2)),
             (Value::Float(val1), Value::Float(val2)) => val1.partial_cmp(val2),
             (Value::Binary(val1), Value::Binary(val2)) => Some(val1.cmp(val2)),
+            (Value::Styled(val1), Value::Styled(val2)) => Some(val1.text.cmp(&val2.text)),
             _ => None,
         }
     }
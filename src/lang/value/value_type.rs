@@ -34,6 +34,7 @@ pub enum ValueType {
     BinaryStream,
     Binary,
     Type,
+    Styled,
 }
 
 lazy_static! {
@@ -73,6 +74,10 @@ impl ValueType {
                 &types::binary::METHODS,
             ValueType::Scope =>
                 &types::scope::METHODS,
+            ValueType::Command =>
+                &types::command::METHODS,
+            ValueType::Styled =>
+                &types::styled::METHODS,
             _ => &EMPTY_METHODS,
         }
     }
@@ -88,7 +93,7 @@ impl ValueType {
             ValueType::Regex | ValueType::Command | ValueType::File |
             ValueType::Scope | ValueType::Float | ValueType::Empty |
             ValueType::Any | ValueType::Binary | ValueType::Type |
-            ValueType::Struct | ValueType::Bool => self.clone(),
+            ValueType::Struct | ValueType::Bool | ValueType::Styled => self.clone(),
             ValueType::BinaryStream => ValueType::Binary,
             ValueType::TableStream(o) => ValueType::Table(ColumnType::materialize(o)),
             ValueType::Table(r) => ValueType::Table(ColumnType::materialize(r)),
@@ -162,6 +167,7 @@ impl Help for ValueType {
             ValueType::BinaryStream => "A stream of binary data",
             ValueType::Binary => "Binary data",
             ValueType::Type => "A type",
+            ValueType::Styled => "Text with colors and other attributes that are shown when printed to a terminal",
         }.to_string()
     }
 
@@ -211,6 +217,7 @@ impl ToString for ValueType {
             ValueType::BinaryStream => "binary_stream".to_string(),
             ValueType::Binary => "binary".to_string(),
             ValueType::Type => "type".to_string(),
+            ValueType::Styled => "styled".to_string(),
         }
     }
 }
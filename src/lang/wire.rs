@@ -0,0 +1,271 @@
+/**
+The protocol used to run a command in another crush instance and stream its output back.
+
+Everything is sent as frames, a kind byte followed by the length of the payload as a big
+endian u32 and the payload. Values are serialized with lang::serialization. The client sends
+an optional auth frame with a token, followed by a command frame with the closure to run, or
+a ping frame, to which the server replies with just a done frame. Otherwise the server replies
+with one of:
+
+* a value frame with the output of the command,
+* a header frame with an empty table that holds the column types of a table stream, followed
+  by one row frame per row, each a list of the cells of the row,
+* a binary frame followed by chunk frames with the raw data of a binary stream,
+
+and finishes with a done frame, or an error frame with the error message if the command failed.
+Since the column types are sent up front and every cell keeps its type, a table stream comes
+out at the client the same as it went in at the server.
+*/
+use std::io::{Read, Write};
+use std::thread;
+
+use openssl::memcmp;
+
+use crate::lang::binary::binary_channel;
+use crate::lang::command::Command;
+use crate::lang::errors::{CrushError, CrushResult, argument_error, data_error, error, to_crush_error};
+use crate::lang::execution_context::ExecutionContext;
+use crate::lang::list::List;
+use crate::lang::printer::Printer;
+use crate::lang::scope::Scope;
+use crate::lang::serialization::{deserialize, serialize};
+use crate::lang::stream::{channels, empty_channel, OutputStream};
+use crate::lang::stream::ValueSender;
+use crate::lang::table::{Row, Table};
+use crate::lang::value::{Value, ValueType};
+
+const AUTH: u8 = b'A';
+const COMMAND: u8 = b'C';
+const PING: u8 = b'P';
+const VALUE: u8 = b'V';
+const HEADER: u8 = b'H';
+const ROW: u8 = b'R';
+const BINARY: u8 = b'B';
+const CHUNK: u8 = b'b';
+const DONE: u8 = b'D';
+const ERROR: u8 = b'E';
+
+const CHUNK_SIZE: usize = 64 * 1024;
+const MAX_FRAME_SIZE: usize = 1 << 30;
+/**
+The largest auth frame. It is read before the client is authenticated, so it must not let
+anyone make the server allocate much memory.
+*/
+const MAX_AUTH_FRAME_SIZE: usize = 4096;
+
+fn write_frame(destination: &mut dyn Write, kind: u8, payload: &[u8]) -> CrushResult<()> {
+    if payload.len() > MAX_FRAME_SIZE {
+        return data_error("Value is too large to send");
+    }
+    let mut header = [kind, 0, 0, 0, 0];
+    header[1..].copy_from_slice(&(payload.len() as u32).to_be_bytes());
+    to_crush_error(destination.write_all(&header))?;
+    to_crush_error(destination.write_all(payload))
+}
+
+fn write_value(destination: &mut dyn Write, kind: u8, value: &Value) -> CrushResult<()> {
+    let mut buf = Vec::new();
+    serialize(value, &mut buf)?;
+    write_frame(destination, kind, &buf)
+}
+
+/** Reads a frame, failing if its payload is larger than the given limit. */
+fn read_frame(source: &mut dyn Read, limit: usize) -> CrushResult<(u8, Vec<u8>)> {
+    let mut header = [0u8; 5];
+    if source.read_exact(&mut header).is_err() {
+        return error("The connection to the remote crush instance was closed");
+    }
+    let len = u32::from_be_bytes([header[1], header[2], header[3], header[4]]) as usize;
+    if len > limit {
+        return data_error("Invalid frame size");
+    }
+    let mut payload = vec![0u8; len];
+    to_crush_error(source.read_exact(&mut payload))?;
+    Ok((header[0], payload))
+}
+
+/** Sends a command to run to a crush instance that is serving this protocol. */
+pub fn send_command(destination: &mut dyn Write, command: Command, token: Option<&str>) -> CrushResult<()> {
+    if let Some(token) = token {
+        write_frame(destination, AUTH, token.as_bytes())?;
+    }
+    write_value(destination, COMMAND, &Value::Command(command))?;
+    to_crush_error(destination.flush())
+}
+
+/** Checks that the instance is serving this protocol and accepts the token. */
+pub fn ping(stream: &mut (impl Read + Write), token: Option<&str>) -> CrushResult<()> {
+    if let Some(token) = token {
+        write_frame(stream, AUTH, token.as_bytes())?;
+    }
+    write_frame(stream, PING, &[])?;
+    to_crush_error(stream.flush())?;
+    match read_frame(stream, MAX_FRAME_SIZE)? {
+        (DONE, _) => Ok(()),
+        (ERROR, message) => error(String::from_utf8_lossy(&message).as_ref()),
+        _ => data_error("Unexpected reply from the remote crush instance"),
+    }
+}
+
+/** Reads the reply to a command and sends it to the output, with the same types it had remotely. */
+pub fn receive_output(source: &mut dyn Read, env: &Scope, output: ValueSender) -> CrushResult<()> {
+    let mut rows: Option<OutputStream> = None;
+    let mut binary: Option<Box<dyn Write>> = None;
+    loop {
+        let (kind, payload) = read_frame(source, MAX_FRAME_SIZE)?;
+        match kind {
+            VALUE => output.send(deserialize(&payload, env)?)?,
+            HEADER => match deserialize(&payload, env)? {
+                Value::Table(t) => rows = Some(output.initialize(t.types().to_vec())?),
+                _ => return data_error("Expected the header to be a table"),
+            },
+            ROW => match (&rows, deserialize(&payload, env)?) {
+                (Some(rows), Value::List(cells)) => rows.send(Row::new(cells.dump()))?,
+                _ => return data_error("Unexpected row"),
+            },
+            BINARY => {
+                let (writer, reader) = binary_channel();
+                output.send(Value::BinaryStream(reader))?;
+                binary = Some(writer);
+            }
+            CHUNK => match &mut binary {
+                Some(writer) => to_crush_error(writer.write_all(&payload))?,
+                None => return data_error("Unexpected binary data"),
+            },
+            DONE => return Ok(()),
+            ERROR => return error(String::from_utf8_lossy(&payload).as_ref()),
+            _ => return data_error("Unknown frame kind"),
+        }
+    }
+}
+
+fn send_output(value: Value, destination: &mut dyn Write) -> CrushResult<()> {
+    match value {
+        Value::TableStream(input) => {
+            write_value(destination, HEADER, &Value::Table(Table::new(input.types().to_vec(), vec![])))?;
+            while let Ok(row) = input.recv() {
+                write_value(destination, ROW, &Value::List(List::new(ValueType::Any, row.into_vec())))?;
+            }
+            Ok(())
+        }
+        Value::BinaryStream(mut input) => {
+            write_frame(destination, BINARY, &[])?;
+            let mut buf = vec![0u8; CHUNK_SIZE];
+            loop {
+                let count = to_crush_error(input.read(&mut buf))?;
+                if count == 0 {
+                    return Ok(());
+                }
+                write_frame(destination, CHUNK, &buf[..count])?;
+            }
+        }
+        value => write_value(destination, VALUE, &value),
+    }
+}
+
+fn check_token(frame: (u8, Vec<u8>), token: &str) -> CrushResult<()> {
+    match frame {
+        (AUTH, given) if given.len() == token.len() && memcmp::eq(&given, token.as_bytes()) => Ok(()),
+        _ => argument_error("Authentication failed"),
+    }
+}
+
+/** Returns the command to run, or None for a ping. */
+fn read_command(source: &mut dyn Read, env: &Scope, token: Option<&str>) -> CrushResult<Option<Command>> {
+    if let Some(token) = token {
+        check_token(read_frame(source, MAX_AUTH_FRAME_SIZE)?, token)?;
+    }
+    match read_frame(source, MAX_FRAME_SIZE)? {
+        (PING, _) => Ok(None),
+        (COMMAND, payload) => match deserialize(&payload, env)? {
+            Value::Command(command) => Ok(Some(command)),
+            _ => argument_error("Expected a command"),
+        },
+        _ => data_error("Expected a command"),
+    }
+}
+
+/**
+Reads a command from the source, runs it, and writes its output to the destination. If a
+token is given, the command is only run if the client sends the same token first.
+*/
+pub fn serve(
+    source: &mut dyn Read,
+    mut destination: Box<dyn Write + Send>,
+    env: Scope,
+    printer: &Printer,
+    token: Option<&str>,
+) -> CrushResult<()> {
+    let command = match read_command(source, &env, token) {
+        Ok(Some(command)) => command,
+        Ok(None) => {
+            write_frame(destination.as_mut(), DONE, &[])?;
+            return to_crush_error(destination.flush());
+        }
+        Err(e) => {
+            write_frame(destination.as_mut(), ERROR, e.message.as_bytes())?;
+            return Err(e);
+        }
+    };
+
+    let (sender, receiver) = channels();
+    let writer: thread::JoinHandle<(Box<dyn Write + Send>, CrushResult<()>)> = to_crush_error(
+        thread::Builder::new().name("remote:serve".to_string()).spawn(move || {
+            let res = match receiver.recv() {
+                Ok(value) => send_output(value, destination.as_mut()),
+                Err(_) => Ok(()),
+            };
+            (destination, res)
+        }))?;
+
+    let res = command.invoke(ExecutionContext {
+        input: empty_channel(),
+        output: sender,
+        arguments: vec![],
+        env,
+        this: None,
+        printer: printer.clone(),
+    });
+
+    let (mut destination, sent) = match writer.join() {
+        Ok(res) => res,
+        Err(_) => return error("Error while sending output"),
+    };
+    match res.and(sent) {
+        Ok(()) => write_frame(destination.as_mut(), DONE, &[])?,
+        Err(CrushError { message, .. }) => write_frame(destination.as_mut(), ERROR, message.as_bytes())?,
+    }
+    to_crush_error(destination.flush())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frames_round_trip() {
+        let mut buf = Vec::new();
+        write_frame(&mut buf, CHUNK, b"abc").unwrap();
+        write_frame(&mut buf, DONE, &[]).unwrap();
+        let mut source = buf.as_slice();
+        assert_eq!(read_frame(&mut source, MAX_FRAME_SIZE).unwrap(), (CHUNK, b"abc".to_vec()));
+        assert_eq!(read_frame(&mut source, MAX_FRAME_SIZE).unwrap(), (DONE, vec![]));
+        assert!(read_frame(&mut source, MAX_FRAME_SIZE).is_err());
+    }
+
+    #[test]
+    fn token_is_checked() {
+        assert!(check_token((AUTH, b"secret".to_vec()), "secret").is_ok());
+        assert!(check_token((AUTH, b"secreT".to_vec()), "secret").is_err());
+        assert!(check_token((AUTH, b"secret2".to_vec()), "secret").is_err());
+        assert!(check_token((COMMAND, b"secret".to_vec()), "secret").is_err());
+    }
+
+    #[test]
+    fn large_frames_are_refused_before_authentication() {
+        let mut buf = vec![AUTH];
+        buf.extend_from_slice(&(MAX_FRAME_SIZE as u32).to_be_bytes());
+        let env = Scope::create_root();
+        assert_eq!(read_command(&mut buf.as_slice(), &env, Some("secret")).err().unwrap().message, "Invalid frame size");
+    }
+}
@@ -0,0 +1,228 @@
+use std::path::Path;
+use std::sync::Mutex;
+
+use lazy_static::lazy_static;
+
+use crate::lang::command::Command;
+use crate::lang::errors::{CrushResult, argument_error};
+use crate::lang::value::ValueType;
+use super::Spec;
+
+/**
+The kind of documentation that `crush doc` generates.
+*/
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum DocFormat {
+    Man,
+    Bash,
+    Zsh,
+    Fish,
+}
+
+impl DocFormat {
+    pub fn parse(flag: &str) -> CrushResult<DocFormat> {
+        match flag {
+            "--man" => Ok(DocFormat::Man),
+            "--completions=bash" => Ok(DocFormat::Bash),
+            "--completions=zsh" => Ok(DocFormat::Zsh),
+            "--completions=fish" => Ok(DocFormat::Fish),
+            _ => argument_error(format!("Unknown documentation format {}, expected --man or --completions=bash|zsh|fish", flag).as_str()),
+        }
+    }
+}
+
+lazy_static! {
+    static ref FORMAT: Mutex<Option<DocFormat>> = Mutex::new(None);
+}
+
+/**
+Make args:parse output documentation in the specified format instead of parsing arguments.
+This is a property of the whole process, since `crush doc` does nothing else.
+*/
+pub fn set_format(format: DocFormat) {
+    *FORMAT.lock().unwrap() = Some(format);
+}
+
+pub fn format() -> Option<DocFormat> {
+    *FORMAT.lock().unwrap()
+}
+
+/**
+The name a script is installed as, i.e. the name of the file without the .crush extension.
+*/
+fn command_name(script: &str) -> String {
+    let name = Path::new(script)
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| script.to_string());
+    name.trim_end_matches(".crush").to_string()
+}
+
+/** Escape text for use in roff, where backslashes, dashes and leading dots are special. */
+fn roff(text: &str) -> String {
+    let escaped = text.replace('\\', "\\e").replace('-', "\\-");
+    if escaped.starts_with('.') || escaped.starts_with('\'') {
+        format!("\\&{}", escaped)
+    } else {
+        escaped
+    }
+}
+
+fn man(name: &str, spec: &Spec, command: &Command) -> String {
+    let help = command.help();
+    let mut res = vec![
+        format!(".TH {} 1", roff(&name.to_uppercase())),
+        ".SH NAME".to_string(),
+    ];
+    if help.short_help().is_empty() {
+        res.push(roff(name));
+    } else {
+        res.push(format!("{} \\- {}", roff(name), roff(&help.short_help())));
+    }
+    res.push(".SH SYNOPSIS".to_string());
+    let mut synopsis = format!(".B {}\n[\\fIOPTIONS\\fR]", roff(name));
+    for option in spec.options.iter().filter(|o| !o.is_flag() && o.default.is_none()) {
+        synopsis.push_str(&format!(" \\fI{}\\fR", roff(&option.name)));
+    }
+    if let Some(rest) = &spec.rest {
+        synopsis.push_str(&format!(" [\\fI{}\\fR...]", roff(rest)));
+    }
+    res.push(synopsis);
+    if let Some(long_help) = help.long_help().filter(|h| !h.is_empty()) {
+        res.push(".SH DESCRIPTION".to_string());
+        res.push(long_help.lines().map(roff).collect::<Vec<_>>().join("\n"));
+    }
+    res.push(".SH OPTIONS".to_string());
+    for option in &spec.options {
+        res.push(".TP".to_string());
+        if option.is_flag() {
+            res.push(format!(".B {}", roff(&option.flag())));
+        } else {
+            res.push(format!("\\fB{}\\fR=\\fI{}\\fR", roff(&option.flag()), roff(&option.value_type.to_string())));
+        }
+        let mut description = option.description.clone().unwrap_or_default();
+        if let (Some(default), false) = (&option.default, option.is_flag()) {
+            if !description.is_empty() {
+                description.push(' ');
+            }
+            description.push_str(&format!("(default: {})", default.to_string()));
+        }
+        res.push(roff(&description));
+    }
+    res.push(".TP\n.B \\-\\-help\nPrint a usage description and exit.".to_string());
+    res.join("\n")
+}
+
+/** Make a string safe for use inside single quotes in a shell script. */
+fn quote(text: &str) -> String {
+    text.replace('\'', "'\\''")
+}
+
+fn function_name(name: &str) -> String {
+    name.chars().map(|c| if c.is_ascii_alphanumeric() { c } else { '_' }).collect()
+}
+
+fn bash(name: &str, spec: &Spec) -> String {
+    let mut words = spec.options.iter()
+        .map(|o| if o.is_flag() { o.flag() } else { format!("{}=", o.flag()) })
+        .collect::<Vec<_>>();
+    words.push("--help".to_string());
+    let function = format!("_{}", function_name(name));
+    [
+        format!("{}() {{", function),
+        "    local cur=\"${COMP_WORDS[COMP_CWORD]}\"".to_string(),
+        "    if [[ \"$cur\" == -* ]]; then".to_string(),
+        format!("        COMPREPLY=($(compgen -W '{}' -- \"$cur\"))", quote(&words.join(" "))),
+        "        [[ \"${COMPREPLY[0]}\" == *= ]] && compopt -o nospace".to_string(),
+        "    else".to_string(),
+        "        COMPREPLY=($(compgen -f -- \"$cur\"))".to_string(),
+        "    fi".to_string(),
+        "}".to_string(),
+        format!("complete -F {} {}", function, name),
+    ].join("\n")
+}
+
+/** The zsh completion action for values of the specified type. */
+fn zsh_action(value_type: &ValueType) -> &'static str {
+    match value_type {
+        ValueType::File => "_files",
+        _ => " ",
+    }
+}
+
+fn zsh_escape(text: &str) -> String {
+    quote(&text.replace('[', "\\[").replace(']', "\\]").replace(':', "\\:"))
+}
+
+fn zsh(name: &str, spec: &Spec) -> String {
+    let mut res = vec![
+        format!("#compdef {}", name),
+        "_arguments \\".to_string(),
+    ];
+    for option in &spec.options {
+        let description = zsh_escape(option.description.as_deref().unwrap_or(""));
+        if option.is_flag() {
+            res.push(format!("    '{}[{}]' \\", option.flag(), description));
+        } else {
+            res.push(format!(
+                "    '{}=[{}]:{}:{}' \\",
+                option.flag(), description, option.value_type.to_string(), zsh_action(&option.value_type)));
+        }
+    }
+    res.push("    '--help[print a usage description and exit]' \\".to_string());
+    match &spec.rest {
+        Some(rest) => res.push(format!("    '*:{}:_files'", zsh_escape(rest))),
+        None => {
+            let last = res.pop().unwrap();
+            res.push(last.trim_end_matches(" \\").to_string());
+        }
+    }
+    res.join("\n")
+}
+
+fn fish(name: &str, spec: &Spec) -> String {
+    let mut res = Vec::new();
+    for option in &spec.options {
+        let mut line = format!("complete -c {} -l {}", name, &option.flag()[2..]);
+        if !option.is_flag() {
+            line.push_str(if option.value_type == ValueType::File { " -r -F" } else { " -r" });
+        }
+        if let Some(description) = &option.description {
+            line.push_str(&format!(" -d '{}'", quote(description)));
+        }
+        res.push(line);
+    }
+    res.push(format!("complete -c {} -l help -d 'print a usage description and exit'", name));
+    res.join("\n")
+}
+
+/**
+Generate documentation for the script with the specified arguments in the specified format.
+*/
+pub fn generate(format: DocFormat, script: &str, spec: &Spec, command: &Command) -> String {
+    let name = command_name(script);
+    match format {
+        DocFormat::Man => man(&name, spec, command),
+        DocFormat::Bash => bash(&name, spec),
+        DocFormat::Zsh => zsh(&name, spec),
+        DocFormat::Fish => fish(&name, spec),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_command_name() {
+        assert_eq!(command_name("/usr/local/bin/greet.crush"), "greet");
+        assert_eq!(command_name("greet"), "greet");
+    }
+
+    #[test]
+    fn test_roff() {
+        assert_eq!(roff("--count"), "\\-\\-count");
+        assert_eq!(roff(".hidden"), "\\&.hidden");
+        assert_eq!(roff("a\\b"), "a\\eb");
+    }
+}
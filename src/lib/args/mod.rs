@@ -0,0 +1,301 @@
+use std::path::PathBuf;
+
+use crate::lang::argument::{Argument, ArgumentHandler};
+use crate::lang::command::{Command, Parameter};
+use crate::lang::errors::{CrushResult, argument_error};
+use crate::lang::execution_context::ExecutionContext;
+use crate::lang::ordered_string_map::OrderedStringMap;
+use crate::lang::scope::Scope;
+use crate::lang::value::{Value, ValueType};
+use crate::util::time::{parse_duration, parse_time};
+use signature::signature;
+
+pub mod doc;
+
+/**
+A parameter of the closure passed to args:parse, with its type and default value resolved.
+*/
+pub struct ScriptOption {
+    pub name: String,
+    pub value_type: ValueType,
+    pub default: Option<Value>,
+    pub description: Option<String>,
+}
+
+impl ScriptOption {
+    /** Options are written with dashes instead of underscores, e.g. --follow-links. */
+    pub fn flag(&self) -> String {
+        format!("--{}", self.name.replace('_', "-"))
+    }
+
+    pub fn is_flag(&self) -> bool {
+        self.value_type == ValueType::Bool
+    }
+}
+
+/**
+The options and extra arguments accepted by a closure, as declared by its signature.
+*/
+pub struct Spec {
+    pub options: Vec<ScriptOption>,
+    pub rest: Option<String>,
+    pub named_rest: Option<String>,
+}
+
+impl Spec {
+    pub fn new(command: &Command, descriptions: &OrderedStringMap<String>, context: &ExecutionContext) -> CrushResult<Spec> {
+        let parameters = match command.parameters() {
+            Some(parameters) => parameters,
+            None => return argument_error("Expected a closure with a signature describing the arguments"),
+        };
+        let mut cc = context.compile_context();
+        let mut spec = Spec { options: Vec::new(), rest: None, named_rest: None };
+        for parameter in parameters {
+            match parameter {
+                Parameter::Parameter(name, value_type, default) => {
+                    let value_type = match value_type.compile_bound(&mut cc)? {
+                        Value::Type(t) => t,
+                        _ => return argument_error(format!("The type of parameter {} is not a type", name).as_str()),
+                    };
+                    spec.options.push(ScriptOption {
+                        name: name.clone(),
+                        value_type,
+                        default: match default {
+                            Some(default) => Some(default.compile_bound(&mut cc)?),
+                            None => None,
+                        },
+                        description: descriptions.get(name).cloned(),
+                    });
+                }
+                Parameter::Unnamed(name) => spec.rest = Some(name.clone()),
+                Parameter::Named(name) => spec.named_rest = Some(name.clone()),
+            }
+        }
+        Ok(spec)
+    }
+
+    /**
+    A usage description of the arguments, in the style of the --help output of most command line
+    tools.
+    */
+    pub fn usage(&self, name: &str, command: &Command) -> String {
+        let mut res = format!("Usage: {} [OPTIONS]", name);
+        for option in self.options.iter().filter(|o| !o.is_flag() && o.default.is_none()) {
+            res.push_str(&format!(" <{}>", option.name));
+        }
+        if let Some(rest) = &self.rest {
+            res.push_str(&format!(" [{}...]", rest));
+        }
+        res.push('\n');
+        let help = command.help();
+        if !help.short_help().is_empty() {
+            res.push_str(&format!("\n    {}\n", help.short_help()));
+        }
+        if let Some(long_help) = help.long_help().filter(|h| !h.is_empty()) {
+            res.push_str(&format!("\n{}\n", long_help));
+        }
+        res.push_str("\nOptions:\n");
+        let mut lines = self.options.iter()
+            .map(|o| {
+                let left = if o.is_flag() {
+                    o.flag()
+                } else {
+                    format!("{}=<{}>", o.flag(), o.value_type.to_string())
+                };
+                let mut right = o.description.clone().unwrap_or_default();
+                match &o.default {
+                    Some(default) if !o.is_flag() => {
+                        if !right.is_empty() {
+                            right.push(' ');
+                        }
+                        right.push_str(&format!("(default: {})", default.to_string()));
+                    }
+                    _ => {}
+                }
+                (left, right)
+            })
+            .collect::<Vec<_>>();
+        lines.push(("--help".to_string(), "print this help and exit".to_string()));
+        let width = lines.iter().map(|(left, _)| left.len()).max().unwrap_or(0);
+        for (left, right) in lines {
+            res.push_str(format!("    {:width$}    {}", left, right, width = width).trim_end());
+            res.push('\n');
+        }
+        res
+    }
+
+    /**
+    Parse command line style arguments into arguments for the closure. Returns None if help was
+    requested.
+    */
+    pub fn parse(&self, arguments: &[String]) -> CrushResult<Option<Vec<Argument>>> {
+        let mut values: Vec<Option<Value>> = self.options.iter().map(|_| None).collect();
+        let mut named_rest = Vec::new();
+        let mut positional = Vec::new();
+        let mut iter = arguments.iter();
+        while let Some(arg) = iter.next() {
+            if arg == "--" {
+                positional.extend(iter.by_ref().cloned());
+                break;
+            }
+            if arg == "--help" || arg == "-h" {
+                return Ok(None);
+            }
+            if !arg.starts_with("--") {
+                positional.push(arg.clone());
+                continue;
+            }
+            let (flag, inline) = match arg.find('=') {
+                Some(idx) => (&arg[..idx], Some(arg[idx + 1..].to_string())),
+                None => (arg.as_str(), None),
+            };
+            match self.options.iter().position(|o| o.flag() == flag) {
+                Some(idx) => {
+                    let option = &self.options[idx];
+                    let text = match inline {
+                        Some(text) => text,
+                        None if option.is_flag() => "true".to_string(),
+                        None => match iter.next() {
+                            Some(text) => text.clone(),
+                            None => return argument_error(format!("Missing value for {}", flag).as_str()),
+                        },
+                    };
+                    values[idx] = Some(convert(option, &text)?);
+                }
+                None => {
+                    if self.named_rest.is_some() {
+                        named_rest.push(Argument::named(
+                            &flag[2..].replace('-', "_"),
+                            Value::string(inline.as_deref().unwrap_or("true"))));
+                    } else {
+                        return argument_error(format!("Unknown option {}, see --help for a list of options", flag).as_str());
+                    }
+                }
+            }
+        }
+
+        let mut positional = positional.into_iter();
+        let mut res = Vec::new();
+        for (option, value) in self.options.iter().zip(values) {
+            let value = match value {
+                Some(value) => Some(value),
+                None if option.default.is_some() => option.default.clone(),
+                None if option.is_flag() => Some(Value::Bool(false)),
+                None => match positional.next() {
+                    Some(text) => Some(convert(option, &text)?),
+                    None => return argument_error(format!("Missing value for {}, see --help for usage", option.flag()).as_str()),
+                },
+            };
+            if let Some(value) = value {
+                res.push(Argument::named(&option.name, value));
+            }
+        }
+        let rest = positional.map(|s| Value::string(&s)).collect::<Vec<_>>();
+        if !rest.is_empty() && self.rest.is_none() {
+            return argument_error(format!("Unexpected argument {}, see --help for usage", rest[0].to_string()).as_str());
+        }
+        res.extend(rest.into_iter().map(Argument::unnamed));
+        res.extend(named_rest);
+        Ok(Some(res))
+    }
+}
+
+/**
+Convert the text of an argument to the type of the corresponding parameter.
+*/
+fn convert(option: &ScriptOption, text: &str) -> CrushResult<Value> {
+    let res = match &option.value_type {
+        ValueType::String | ValueType::Any => Ok(Value::string(text)),
+        ValueType::File => Ok(Value::File(PathBuf::from(text))),
+        ValueType::Duration => parse_duration(text).map(Value::Duration),
+        ValueType::Time => parse_time(text).map(Value::Time),
+        t => t.parse(text),
+    };
+    match res {
+        Ok(value) => Ok(value),
+        Err(_) => argument_error(
+            format!("Invalid value \"{}\" for {}, expected a value of type {}",
+                    text, option.flag(), option.value_type.to_string()).as_str()),
+    }
+}
+
+/**
+The arguments of the script, without the name of the script itself.
+*/
+fn script_arguments(env: &Scope) -> CrushResult<(String, Vec<String>)> {
+    match env.get("argv")? {
+        Some(Value::List(list)) => {
+            let mut arguments = list.dump().iter().map(|v| v.to_string()).collect::<Vec<_>>();
+            let name = if arguments.is_empty() { "script".to_string() } else { arguments.remove(0) };
+            Ok((name, arguments))
+        }
+        _ => Ok(("script".to_string(), Vec::new())),
+    }
+}
+
+#[signature(
+parse,
+can_block = true,
+short = "Parse the command line arguments of a script and invoke a closure with them",
+long = "The signature of the closure declares the arguments that the script accepts. Every",
+long = "parameter is an option that can be set using --name=value or --name value, and",
+long = "underscores in parameter names are written as dashes. Boolean parameters are flags, so",
+long = "--verbose sets verbose to true. Parameters without a default value are mandatory, and",
+long = "other arguments are assigned to the mandatory parameters that were not set by name, in",
+long = "order. Any remaining arguments go to the @ parameter of the closure.",
+long = "",
+long = "If the arguments contain --help, a usage description is printed instead. It is generated",
+long = "from the signature and help text of the closure, and from the descriptions of the",
+long = "parameters, which are passed as named arguments to args:parse.",
+long = "",
+long = "Running crush doc script.crush --man, or --completions=bash, zsh or fish, outputs a man",
+long = "page or a shell completion script for a script. Only the first args:parse at the top level",
+long = "of the script is run for this, and it outputs the documentation instead of parsing.",
+example = "args:parse {|lines: integer = 10 verbose: bool @files| \"Show the first lines of files\" head lines} lines=\"the number of lines to show\"")]
+struct Parse {
+    #[description("the closure to invoke with the parsed arguments.")]
+    command: Command,
+    #[description("the arguments to parse, preceded by the name of the script. The arguments of the current script by default.")]
+    argv: Option<Value>,
+    #[named()]
+    #[description("descriptions of the parameters, shown in the --help output.")]
+    descriptions: OrderedStringMap<String>,
+}
+
+fn parse(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Parse = Parse::parse(context.arguments.clone(), &context.printer)?;
+    let (name, arguments) = match cfg.argv {
+        None => script_arguments(&context.env)?,
+        Some(Value::List(list)) => {
+            let mut arguments = list.dump().iter().map(|v| v.to_string()).collect::<Vec<_>>();
+            if arguments.is_empty() {
+                return argument_error("Expected argv to start with the name of the script");
+            }
+            (arguments.remove(0), arguments)
+        }
+        Some(v) => return argument_error(
+            format!("Expected argv to be a list, got a value of type {}", v.value_type().to_string()).as_str()),
+    };
+    let spec = Spec::new(&cfg.command, &cfg.descriptions, &context)?;
+    if let Some(format) = doc::format() {
+        context.printer.line(&doc::generate(format, &name, &spec, &cfg.command));
+        return context.output.send(Value::Empty());
+    }
+    match spec.parse(&arguments)? {
+        Some(arguments) => cfg.command.invoke(context.with_args(arguments, None)),
+        None => {
+            context.printer.line(spec.usage(&name, &cfg.command).trim_end());
+            context.output.send(Value::Empty())
+        }
+    }
+}
+
+pub fn declare(root: &Scope) -> CrushResult<()> {
+    root.create_lazy_namespace(
+        "args",
+        Box::new(move |env| {
+            Parse::declare(env)?;
+            Ok(())
+        }))?;
+    Ok(())
+}
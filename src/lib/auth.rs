@@ -0,0 +1,431 @@
+use std::fs;
+use std::io::Write;
+use std::os::unix::fs::{DirBuilderExt, OpenOptionsExt};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use openssl::rand::rand_bytes;
+use openssl::sha::sha256;
+use openssl::symm::{decrypt_aead, encrypt_aead, Cipher};
+
+use crate::lang::argument::ArgumentHandler;
+use crate::lang::command::OutputType::Known;
+use crate::lang::errors::{CrushResult, argument_error, error, mandate, to_crush_error};
+use crate::lang::execution_context::ExecutionContext;
+use crate::lang::printer::Printer;
+use crate::lang::scope::Scope;
+use crate::lang::value::{Value, ValueType};
+use signature::signature;
+
+/** Tokens that expire within this many seconds are refreshed before being used. */
+const EXPIRY_MARGIN: u64 = 60;
+
+const DEVICE_CODE_GRANT: &str = "urn:ietf:params:oauth:grant-type:device_code";
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+struct Token {
+    access_token: String,
+    refresh_token: Option<String>,
+    /** Seconds since the epoch, or None if the token doesn't expire. */
+    expires_at: Option<u64>,
+}
+
+impl Token {
+    fn from_json(json: &serde_json::Value) -> CrushResult<Token> {
+        Ok(Token {
+            access_token: mandate(json["access_token"].as_str(), "The token endpoint did not return an access token")?.to_string(),
+            refresh_token: json["refresh_token"].as_str().map(|t| t.to_string()),
+            expires_at: json["expires_in"].as_u64().map(|e| now() + e),
+        })
+    }
+
+    fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "access_token": self.access_token,
+            "refresh_token": self.refresh_token,
+            "expires_at": self.expires_at,
+        })
+    }
+
+    fn is_valid(&self) -> bool {
+        self.expires_at.map(|e| e > now() + EXPIRY_MARGIN).unwrap_or(true)
+    }
+}
+
+/**
+Tokens are cached in the cache directory of the user, encrypted with a key that is created the
+first time it's needed and only readable by the user. This keeps tokens out of backups and
+accidental copies of the cache, but not away from the user the shell runs as.
+*/
+struct Cache {
+    dir: PathBuf,
+}
+
+impl Cache {
+    fn new() -> CrushResult<Cache> {
+        let dir = mandate(dirs::cache_dir(), "Could not find the cache directory")?.join("crush").join("oauth2");
+        to_crush_error(fs::DirBuilder::new().recursive(true).mode(0o700).create(&dir))?;
+        Ok(Cache { dir })
+    }
+
+    fn write_private(path: &Path, data: &[u8]) -> CrushResult<()> {
+        let mut file = to_crush_error(fs::OpenOptions::new().write(true).create(true).truncate(true).mode(0o600).open(path))?;
+        to_crush_error(file.write_all(data))
+    }
+
+    fn key(&self) -> CrushResult<Vec<u8>> {
+        let path = self.dir.join("key");
+        match fs::read(&path) {
+            Ok(key) if key.len() == 32 => Ok(key),
+            _ => {
+                let mut key = vec![0u8; 32];
+                to_crush_error(rand_bytes(&mut key))?;
+                Cache::write_private(&path, &key)?;
+                Ok(key)
+            }
+        }
+    }
+
+    fn read(&self, id: &str) -> Option<Token> {
+        let data = fs::read(self.dir.join(id)).ok()?;
+        if data.len() < 28 {
+            return None;
+        }
+        let (nonce, rest) = data.split_at(12);
+        let (tag, encrypted) = rest.split_at(16);
+        let plain = decrypt_aead(Cipher::aes_256_gcm(), &self.key().ok()?, Some(nonce), &[], encrypted, tag).ok()?;
+        let json: serde_json::Value = serde_json::from_slice(&plain).ok()?;
+        Some(Token {
+            access_token: json["access_token"].as_str()?.to_string(),
+            refresh_token: json["refresh_token"].as_str().map(|t| t.to_string()),
+            expires_at: json["expires_at"].as_u64(),
+        })
+    }
+
+    fn write(&self, id: &str, token: &Token) -> CrushResult<()> {
+        let mut nonce = [0u8; 12];
+        to_crush_error(rand_bytes(&mut nonce))?;
+        let mut tag = [0u8; 16];
+        let encrypted = to_crush_error(encrypt_aead(
+            Cipher::aes_256_gcm(), &self.key()?, Some(&nonce), &[], token.to_json().to_string().as_bytes(), &mut tag))?;
+        let mut data = nonce.to_vec();
+        data.extend_from_slice(&tag);
+        data.extend(encrypted);
+        Cache::write_private(&self.dir.join(id), &data)
+    }
+}
+
+/** Post a form to an OAuth2 endpoint, returning the JSON reply even if it describes an error. */
+fn post(url: &str, form: &[(&str, &str)]) -> CrushResult<serde_json::Value> {
+    let response = to_crush_error(reqwest::blocking::Client::new()
+        .post(url)
+        .header("Accept", "application/json")
+        .form(form)
+        .send())?;
+    let text = to_crush_error(response.text())?;
+    match serde_json::from_str(&text) {
+        Ok(json) => Ok(json),
+        Err(_) => error(format!("Expected a JSON reply from {}, got: {}", url, text.chars().take(200).collect::<String>()).as_str()),
+    }
+}
+
+fn check_error(json: &serde_json::Value) -> CrushResult<()> {
+    match json["error"].as_str() {
+        None => Ok(()),
+        Some(e) => error(format!(
+            "OAuth2 error {}{}",
+            e,
+            json["error_description"].as_str().map(|d| format!(": {}", d)).unwrap_or_default()).as_str()),
+    }
+}
+
+#[signature(
+oauth2,
+can_block = true,
+output = Known(ValueType::String),
+short = "Obtain an OAuth2 access token",
+long = "Two flows are supported. The client_credentials flow authenticates the client itself using",
+long = "its id and secret, and is meant for scripts and services. The device_code flow lets a user",
+long = "log in using a browser, possibly on another device, by visiting the printed address and",
+long = "entering the printed code.",
+long = "",
+long = "Tokens are cached on disk, encrypted, and reused until they expire. Expired tokens are",
+long = "refreshed using the refresh token if there is one, otherwise the flow is run again. The",
+long = "access token is returned as a string, ready to be passed to e.g. api:new.",
+example = "gh := (api:new \"https://api.github.com\" token=(auth:oauth2 flow=\"device_code\" client_id=\"Iv1.123\" device_url=\"https://github.com/login/device/code\" token_url=\"https://github.com/login/oauth/access_token\"))")]
+struct OAuth2 {
+    #[values("client_credentials", "device_code")]
+    #[description("the flow to use.")]
+    flow: String,
+    #[description("the token endpoint of the authorization server.")]
+    token_url: String,
+    #[description("the device authorization endpoint, needed by the device_code flow.")]
+    device_url: Option<String>,
+    #[description("the id of the client.")]
+    client_id: String,
+    #[description("the secret of the client, needed by the client_credentials flow.")]
+    client_secret: Option<String>,
+    #[description("the scopes to request.")]
+    scope: Vec<String>,
+    #[default(true)]
+    #[description("reuse and store tokens in the token cache.")]
+    cache: bool,
+}
+
+impl OAuth2 {
+    /** Tokens are cached per flow, endpoint, client and set of scopes. */
+    fn cache_id(&self) -> String {
+        let key = format!("{}\n{}\n{}\n{}", self.flow, self.token_url, self.client_id, self.scope.join(" "));
+        sha256(key.as_bytes()).iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    fn client_secret_form<'a>(&'a self, form: &mut Vec<(&'a str, &'a str)>) {
+        if let Some(secret) = &self.client_secret {
+            form.push(("client_secret", secret));
+        }
+    }
+
+    fn refresh(&self, refresh_token: &str) -> CrushResult<Token> {
+        let mut form = vec![
+            ("grant_type", "refresh_token"),
+            ("refresh_token", refresh_token),
+            ("client_id", self.client_id.as_str()),
+        ];
+        self.client_secret_form(&mut form);
+        let json = post(&self.token_url, &form)?;
+        check_error(&json)?;
+        let mut token = Token::from_json(&json)?;
+        // Servers may keep using the old refresh token instead of issuing a new one
+        if token.refresh_token.is_none() {
+            token.refresh_token = Some(refresh_token.to_string());
+        }
+        Ok(token)
+    }
+
+    fn client_credentials(&self) -> CrushResult<Token> {
+        if self.client_secret.is_none() {
+            return argument_error("The client_credentials flow needs a client_secret");
+        }
+        let scope = self.scope.join(" ");
+        let mut form = vec![
+            ("grant_type", "client_credentials"),
+            ("client_id", self.client_id.as_str()),
+        ];
+        self.client_secret_form(&mut form);
+        if !scope.is_empty() {
+            form.push(("scope", &scope));
+        }
+        let json = post(&self.token_url, &form)?;
+        check_error(&json)?;
+        Token::from_json(&json)
+    }
+
+    fn device_code(&self, printer: &Printer) -> CrushResult<Token> {
+        let device_url = mandate(self.device_url.as_ref(), "The device_code flow needs a device_url")?;
+        let scope = self.scope.join(" ");
+        let mut form = vec![("client_id", self.client_id.as_str())];
+        if !scope.is_empty() {
+            form.push(("scope", &scope));
+        }
+        let json = post(device_url, &form)?;
+        check_error(&json)?;
+        let device_code = mandate(json["device_code"].as_str(), "The device endpoint did not return a device code")?;
+        let user_code = mandate(json["user_code"].as_str(), "The device endpoint did not return a user code")?;
+        // Some servers use the name from a draft of the specification
+        let uri = mandate(
+            json["verification_uri"].as_str().or_else(|| json["verification_url"].as_str()),
+            "The device endpoint did not return a verification address")?;
+        let expires_at = now() + json["expires_in"].as_u64().unwrap_or(900);
+        let mut interval = json["interval"].as_u64().unwrap_or(5);
+
+        printer.line(format!("To log in, visit {} and enter the code {}", uri, user_code).as_str());
+
+        let mut form = vec![
+            ("grant_type", DEVICE_CODE_GRANT),
+            ("device_code", device_code),
+            ("client_id", self.client_id.as_str()),
+        ];
+        self.client_secret_form(&mut form);
+        while now() < expires_at {
+            std::thread::sleep(Duration::from_secs(interval));
+            let json = post(&self.token_url, &form)?;
+            match json["error"].as_str() {
+                Some("authorization_pending") => {}
+                Some("slow_down") => interval += 5,
+                _ => {
+                    check_error(&json)?;
+                    return Token::from_json(&json);
+                }
+            }
+        }
+        error("The device code expired before the login was completed")
+    }
+
+    fn token(&self, printer: &Printer) -> CrushResult<Token> {
+        match self.flow.as_str() {
+            "client_credentials" => self.client_credentials(),
+            _ => self.device_code(printer),
+        }
+    }
+}
+
+fn oauth2(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: OAuth2 = OAuth2::parse(context.arguments, &context.printer)?;
+    if !cfg.cache {
+        return context.output.send(Value::String(cfg.token(&context.printer)?.access_token));
+    }
+
+    let cache = Cache::new()?;
+    let id = cfg.cache_id();
+    let token = match cache.read(&id) {
+        Some(token) if token.is_valid() => return context.output.send(Value::String(token.access_token)),
+        Some(Token { refresh_token: Some(refresh_token), .. }) =>
+            match cfg.refresh(&refresh_token) {
+                Ok(token) => token,
+                // The refresh token may have expired or been revoked
+                Err(_) => cfg.token(&context.printer)?,
+            },
+        _ => cfg.token(&context.printer)?,
+    };
+    cache.write(&id, &token)?;
+    context.output.send(Value::String(token.access_token))
+}
+
+pub fn declare(root: &Scope) -> CrushResult<()> {
+    root.create_lazy_namespace(
+        "auth",
+        Box::new(move |env| {
+            OAuth2::declare(env)?;
+            Ok(())
+        }))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Read};
+    use std::net::TcpListener;
+    use std::thread::JoinHandle;
+    use crate::lang::printer;
+
+    /**
+    Serve one connection on localhost for each of the replies, in order, and return the address of
+    the server and a handle that gives the forms that were posted to it.
+    */
+    fn serve(replies: Vec<&'static str>) -> (String, JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/token", listener.local_addr().unwrap());
+        let handle = std::thread::spawn(move || {
+            replies.into_iter().map(|reply| {
+                let (stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if let Some(value) = line.to_lowercase().strip_prefix("content-length:") {
+                        length = value.trim().parse().unwrap();
+                    }
+                    if line == "\r\n" {
+                        break;
+                    }
+                }
+                let mut body = vec![0u8; length];
+                reader.read_exact(&mut body).unwrap();
+                write!(
+                    &stream,
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    reply.len(), reply).unwrap();
+                String::from_utf8(body).unwrap()
+            }).collect()
+        });
+        (url, handle)
+    }
+
+    fn config(flow: &str, token_url: &str) -> OAuth2 {
+        OAuth2 {
+            flow: flow.to_string(),
+            token_url: token_url.to_string(),
+            device_url: None,
+            client_id: "client".to_string(),
+            client_secret: Some("secret".to_string()),
+            scope: vec!["read".to_string(), "write".to_string()],
+            cache: false,
+        }
+    }
+
+    #[test]
+    fn test_client_credentials() {
+        let (url, server) = serve(vec![
+            r#"{"access_token": "access", "refresh_token": "refresh", "expires_in": 3600}"#,
+            r#"{"error": "invalid_client", "error_description": "Unknown client"}"#,
+        ]);
+        let cfg = config("client_credentials", &url);
+        let token = cfg.client_credentials().unwrap();
+        assert_eq!(token.access_token, "access");
+        assert_eq!(token.refresh_token.as_deref(), Some("refresh"));
+        assert!(token.is_valid());
+        let err = cfg.client_credentials().err().unwrap();
+        assert_eq!(err.message, "OAuth2 error invalid_client: Unknown client");
+        assert_eq!(
+            server.join().unwrap()[0],
+            "grant_type=client_credentials&client_id=client&client_secret=secret&scope=read+write");
+    }
+
+    #[test]
+    fn test_refresh() {
+        let (url, server) = serve(vec![r#"{"access_token": "new", "expires_in": 3600}"#]);
+        let token = config("client_credentials", &url).refresh("old").unwrap();
+        assert_eq!(token.access_token, "new");
+        // The old refresh token is kept when the server doesn't issue a new one
+        assert_eq!(token.refresh_token.as_deref(), Some("old"));
+        assert_eq!(
+            server.join().unwrap(),
+            vec!["grant_type=refresh_token&refresh_token=old&client_id=client&client_secret=secret"]);
+    }
+
+    #[test]
+    fn test_device_code() {
+        let (url, server) = serve(vec![
+            r#"{"device_code": "device", "user_code": "ABCD", "verification_uri": "https://example.com/device", "interval": 0}"#,
+            r#"{"error": "authorization_pending"}"#,
+            r#"{"access_token": "access"}"#,
+        ]);
+        let (printer, capture) = printer::capture();
+        let cfg = OAuth2 { device_url: Some(url.clone()), ..config("device_code", &url) };
+        let token = cfg.token(&printer).unwrap();
+        assert_eq!(token.access_token, "access");
+        assert_eq!(token.expires_at, None);
+        assert_eq!(capture.lines(), vec!["To log in, visit https://example.com/device and enter the code ABCD"]);
+        let forms = server.join().unwrap();
+        assert_eq!(forms[0], "client_id=client&scope=read+write");
+        assert_eq!(forms[1], forms[2]);
+        assert_eq!(
+            forms[2],
+            format!("grant_type={}&device_code=device&client_id=client&client_secret=secret", "urn%3Aietf%3Aparams%3Aoauth%3Agrant-type%3Adevice_code"));
+    }
+
+    #[test]
+    fn test_cache() {
+        let dir = std::env::temp_dir().join(format!("crush-oauth2-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let cache = Cache { dir: dir.clone() };
+        cache.write("id", &Token { access_token: "secret".to_string(), refresh_token: None, expires_at: Some(17) }).unwrap();
+        assert!(!String::from_utf8_lossy(&fs::read(dir.join("id")).unwrap()).contains("secret"));
+        let token = cache.read("id").unwrap();
+        assert_eq!(token.access_token, "secret");
+        assert_eq!(token.expires_at, Some(17));
+        assert!(!token.is_valid());
+
+        let mut data = fs::read(dir.join("id")).unwrap();
+        let last = data.len() - 1;
+        data[last] ^= 1;
+        fs::write(dir.join("id"), data).unwrap();
+        assert!(cache.read("id").is_none());
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
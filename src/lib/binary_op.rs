@@ -1,12 +1,10 @@
 macro_rules! binary_op {
-    ($name:ident, $this_type:ident, $($input_type:ident, $output_type:ident, $operation:expr), *) => {
+    ($name:ident, $operator:ident) => {
 fn $name(mut context: ExecutionContext) -> CrushResult<()> {
     context.arguments.check_len(1)?;
-    let this = context.this.$this_type()?;
-    match (context.arguments.value(0)?) {
-        $( Value::$input_type(v) => context.output.send(Value::$output_type($operation(this, v))), )*
-        _ => return argument_error("Expected only arguments of the same type"),
-    }
+    let this = mandate(context.this.take(), "Missing this value")?;
+    let that = context.arguments.value(0)?;
+    context.output.send(crate::lang::value::Operator::$operator.apply(this, that)?)
 }
     }
 }
@@ -0,0 +1,47 @@
+/**
+Commands that transform a single value, like math:sqrt and str:upper, can also transform each
+element of a list, or one column of each row of their input. This is the shared implementation
+of that.
+*/
+use crate::lang::errors::{CrushResult, mandate};
+use crate::lang::list::List;
+use crate::lang::stream::{ValueReceiver, ValueSender};
+use crate::lang::table::{ColumnVec, Row};
+use crate::lang::value::{Value, ValueType};
+
+/**
+Applies an operation to a value, to each element of a list, or, if given a field, to that
+column of each row of the input, passing on the other columns as they are. The result has the
+specified type, or the type of the original value if no type is specified.
+*/
+pub fn apply(
+    input: ValueReceiver,
+    output: ValueSender,
+    subject: Value,
+    result_type: Option<ValueType>,
+    op: impl Fn(&Value) -> CrushResult<Value>,
+) -> CrushResult<()> {
+    match subject {
+        Value::List(l) => {
+            let element_type = result_type.unwrap_or_else(|| l.element_type());
+            let cells = l.dump().iter().map(&op).collect::<CrushResult<Vec<_>>>()?;
+            output.send(Value::List(List::new(element_type, cells)))
+        }
+        Value::Field(field) => {
+            let mut stream = mandate(input.recv()?.stream(), "Expected a stream")?;
+            let idx = stream.types().find(&field)?;
+            let mut types = stream.types().to_vec();
+            if let Some(result_type) = result_type {
+                types[idx].cell_type = result_type;
+            }
+            let output = output.initialize(types)?;
+            while let Ok(row) = stream.read() {
+                let mut cells = row.into_vec();
+                cells[idx] = op(&cells[idx])?;
+                output.send(Row::new(cells))?;
+            }
+            Ok(())
+        }
+        v => output.send(op(&v)?),
+    }
+}
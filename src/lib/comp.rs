@@ -6,13 +6,29 @@ use std::cmp::Ordering;
 use crate::lang::value::ValueType;
 use crate::lang::command::OutputType::Known;
 
+/** Like partial_cmp, except that integers and floats can be compared with each other. */
+fn compare(l: &Value, r: &Value) -> Option<Ordering> {
+    match (l, r) {
+        (Value::Integer(a), Value::Float(b)) => (*a as f64).partial_cmp(b),
+        (Value::Float(a), Value::Integer(b)) => a.partial_cmp(&(*b as f64)),
+        _ => l.partial_cmp(r),
+    }
+}
+
+fn equal(l: &Value, r: &Value) -> bool {
+    match compare(l, r) {
+        Some(ordering) => ordering == Ordering::Equal,
+        None => l.eq(r),
+    }
+}
+
 macro_rules! cmp {
     ($name:ident, $op:expr) => {
 pub fn $name(mut context: ExecutionContext) -> CrushResult<()> {
     context.arguments.check_len(2)?;
     let l = context.arguments.value(0)?;
     let r = context.arguments.value(1)?;
-    match l.partial_cmp(&r) {
+    match compare(&l, &r) {
         Some(ordering) => context.output.send(Value::Bool($op(ordering))),
         None => return argument_error(
             format!(
@@ -34,14 +50,14 @@ pub fn eq(mut context: ExecutionContext) -> CrushResult<()> {
     context.arguments.check_len(2)?;
     let l = context.arguments.value(0)?;
     let r = context.arguments.value(1)?;
-    context.output.send(Value::Bool(l.eq(&r)))
+    context.output.send(Value::Bool(equal(&l, &r)))
 }
 
 pub fn neq(mut context: ExecutionContext) -> CrushResult<()> {
     context.arguments.check_len(2)?;
     let l = context.arguments.value(0)?;
     let r = context.arguments.value(1)?;
-    context.output.send(Value::Bool(!l.eq(&r)))
+    context.output.send(Value::Bool(!equal(&l, &r)))
 }
 
 pub fn not(mut context: ExecutionContext) -> CrushResult<()> {
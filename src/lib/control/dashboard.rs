@@ -0,0 +1,230 @@
+use crate::lang::errors::{CrushResult, argument_error, to_crush_error, mandate};
+use crate::lang::execution_context::ExecutionContext;
+use crate::lang::command::Command;
+use crate::lang::value::Value;
+use crate::lang::printer;
+use crate::lang::printer::Printer;
+use crate::lang::pretty_printer::PrettyPrinter;
+use crate::lang::stream::{channels, empty_channel};
+use crate::util::thread::build;
+use signature::signature;
+use crate::lang::argument::ArgumentHandler;
+use chrono::{Duration, Local};
+use nix::poll::{poll, PollFd, PollFlags};
+use std::io::{Read, Write};
+use std::time::Instant;
+use termion::raw::IntoRawMode;
+
+#[signature(
+dashboard,
+can_block = true,
+short = "Repeatedly run a set of commands and show their output side by side",
+long = "The panes argument is a dict from pane names to either a command, which is run every interval,",
+long = "or a struct with a command field and an interval field, for panes that should be refreshed at",
+long = "a different rate. The output of all panes is redrawn in place whenever one of them is rerun.",
+long = "",
+long = "Press q to quit. If the shell is not attached to a terminal, the output of every round is",
+long = "printed one after the other instead, which is mostly useful together with rounds.",
+long = "",
+long = "    panes := ((dict string any):new)",
+long = "    panes[\"processes\"] = {ps | top 10 by=^cpu}",
+long = "    panes[\"disk\"] = (data command={find /var/log | top 10 by=^size} interval=(duration:new seconds=30))",
+example = "dashboard panes")]
+pub struct Dashboard {
+    #[description("the panes to show.")]
+    panes: Value,
+    #[default("2s")]
+    #[description("how often to rerun the commands of panes without an interval of their own.")]
+    interval: Duration,
+    #[description("stop after redrawing this many times.")]
+    rounds: Option<i128>,
+}
+
+struct Pane {
+    name: String,
+    command: Command,
+    interval: std::time::Duration,
+    next: Instant,
+    lines: Vec<String>,
+}
+
+impl Pane {
+    fn run(&mut self, context: &ExecutionContext) {
+        let (printer, capture) = printer::capture();
+        let (sender, receiver) = channels();
+        let pane_context = ExecutionContext {
+            input: empty_channel(),
+            output: sender,
+            arguments: vec![],
+            env: context.env.clone(),
+            this: None,
+            printer: printer.clone(),
+        };
+        let command = self.command.as_ref().clone();
+        let job_printer = printer.clone();
+        let job = build("dashboard:pane").spawn(move || {
+            job_printer.handle_error(command.invoke(pane_context));
+        });
+        // Streams are read while the command is still running, so that commands producing
+        // more output than fits in a stream buffer don't block forever.
+        if let Ok(value) = receiver.recv() {
+            PrettyPrinter::new(printer.clone()).print_value(value);
+        }
+        if let Ok(job) = job {
+            let _ = job.join();
+        }
+        self.lines = capture.lines();
+        self.next = Instant::now() + self.interval;
+    }
+}
+
+fn to_std(duration: Duration) -> CrushResult<std::time::Duration> {
+    if duration <= Duration::zero() {
+        return argument_error("Expected the interval to be positive");
+    }
+    to_crush_error(duration.to_std())
+}
+
+fn panes(cfg: &Dashboard) -> CrushResult<Vec<Pane>> {
+    let dict = match &cfg.panes {
+        Value::Dict(d) => d,
+        v => return argument_error(
+            format!("Expected panes to be a dict, got a value of type {}", v.value_type().to_string()).as_str()),
+    };
+    let mut res = Vec::new();
+    for (name, value) in dict.elements() {
+        let (command, interval) = match value {
+            Value::Command(c) => (c, cfg.interval),
+            Value::Struct(s) => {
+                let command = match mandate(s.get("command"), "Missing command field in pane")? {
+                    Value::Command(c) => c,
+                    _ => return argument_error("Expected the command field of a pane to be a command"),
+                };
+                let interval = match s.get("interval") {
+                    Some(Value::Duration(d)) => d,
+                    None => cfg.interval,
+                    _ => return argument_error("Expected the interval field of a pane to be a duration"),
+                };
+                (command, interval)
+            }
+            v => return argument_error(
+                format!("Expected pane to be a command or a struct, got a value of type {}", v.value_type().to_string()).as_str()),
+        };
+        res.push(Pane {
+            name: name.to_string(),
+            command,
+            interval: to_std(interval)?,
+            next: Instant::now(),
+            lines: vec![],
+        });
+    }
+    res.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(res)
+}
+
+fn truncate(line: &str, width: usize) -> String {
+    line.chars().take(width).collect()
+}
+
+fn draw_terminal(panes: &[Pane], printer: &Printer, out: &mut impl Write) -> CrushResult<()> {
+    let width = printer.width();
+    let pane_height = (printer.height() / panes.len()).max(1);
+    to_crush_error(write!(out, "{}", termion::clear::All))?;
+    for (idx, pane) in panes.iter().enumerate() {
+        let top = idx * pane_height + 1;
+        let header = format!("── {} (updated {}) ", pane.name, Local::now().format("%H:%M:%S"));
+        let padding = width.saturating_sub(header.chars().count());
+        to_crush_error(write!(
+            out, "{}{}{}{}{}",
+            termion::cursor::Goto(1, top as u16),
+            termion::style::Bold,
+            truncate(&header, width),
+            "─".repeat(padding),
+            termion::style::Reset))?;
+        for (row, line) in pane.lines.iter().take(pane_height - 1).enumerate() {
+            to_crush_error(write!(out, "{}{}", termion::cursor::Goto(1, (top + row + 1) as u16), truncate(line, width)))?;
+        }
+    }
+    to_crush_error(out.flush())
+}
+
+fn draw_plain(panes: &[Pane], out: &mut impl Write) -> CrushResult<()> {
+    for pane in panes {
+        to_crush_error(writeln!(out, "── {}", pane.name))?;
+        for line in &pane.lines {
+            to_crush_error(writeln!(out, "{}", line))?;
+        }
+    }
+    to_crush_error(out.flush())
+}
+
+/** Wait until the specified time, returning true if the user asked to quit. */
+fn wait(until: Instant, interactive: bool) -> bool {
+    let now = Instant::now();
+    if until <= now {
+        return false;
+    }
+    if !interactive {
+        std::thread::sleep(until - now);
+        return false;
+    }
+    let mut fds = [PollFd::new(0, PollFlags::POLLIN)];
+    if let Ok(n) = poll(&mut fds, (until - now).as_millis() as i32) {
+        if n > 0 {
+            let mut buffer = [0u8; 64];
+            if let Ok(count) = std::io::stdin().read(&mut buffer) {
+                // q, or Ctrl-C, which doesn't send a signal in raw mode
+                return buffer[..count].iter().any(|b| *b == b'q' || *b == 3);
+            }
+        }
+    }
+    false
+}
+
+fn dashboard(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Dashboard = Dashboard::parse(context.arguments.clone(), &context.printer)?;
+    let mut panes = panes(&cfg)?;
+    if panes.is_empty() {
+        return argument_error("Expected at least one pane");
+    }
+
+    let interactive = termion::is_tty(&std::io::stdin()) && termion::is_tty(&std::io::stdout());
+    let _terminal = context.printer.lock_terminal();
+    let stdout = std::io::stdout();
+    let mut raw = if interactive {
+        let mut raw = to_crush_error(stdout.lock().into_raw_mode())?;
+        to_crush_error(write!(raw, "{}", termion::cursor::Hide))?;
+        Some(raw)
+    } else {
+        None
+    };
+
+    let mut round = 0;
+    let result = loop {
+        for pane in panes.iter_mut().filter(|p| p.next <= Instant::now()) {
+            pane.run(&context);
+        }
+        let drawn = match &mut raw {
+            Some(out) => draw_terminal(&panes, &context.printer, out),
+            None => draw_plain(&panes, &mut stdout.lock()),
+        };
+        if drawn.is_err() {
+            break drawn;
+        }
+        round += 1;
+        if cfg.rounds.map(|r| round >= r).unwrap_or(false) {
+            break Ok(());
+        }
+        let next = panes.iter().map(|p| p.next).min().unwrap();
+        if wait(next, interactive) {
+            break Ok(());
+        }
+    };
+
+    if let Some(mut out) = raw {
+        let _ = write!(out, "{}{}{}", termion::clear::All, termion::cursor::Goto(1, 1), termion::cursor::Show);
+        let _ = out.flush();
+    }
+    result?;
+    context.output.empty()
+}
@@ -0,0 +1,222 @@
+use std::io::{Read, Write};
+use std::os::unix::process::ExitStatusExt;
+use std::process::{Child, Stdio};
+use std::thread::JoinHandle;
+use std::time::Instant;
+
+use chrono::Duration;
+
+use crate::lang::argument::ArgumentHandler;
+use crate::lang::binary::{BinaryReader, binary_channel};
+use crate::lang::command::OutputType::Known;
+use crate::lang::errors::{CrushResult, argument_error, error, to_crush_error};
+use crate::lang::execution_context::ExecutionContext;
+use crate::lang::process_limits::Cgroup;
+use crate::lang::r#struct::Struct;
+use crate::lang::value::{Value, ValueType};
+use crate::lib::env::to_environment_value;
+use crate::util::thread::build;
+use signature::signature;
+
+/**
+Commands that write less than this to standard output have their struct returned once they
+have exited. The output of other commands is streamed, and their struct is returned right away.
+*/
+const STREAM_THRESHOLD: u64 = 1024 * 1024;
+
+/** A running command, and everything needed to report how it went once it exits. */
+struct Exited {
+    child: Child,
+    start: Instant,
+    writer: Option<JoinHandle<()>>,
+    errors: Option<JoinHandle<Vec<u8>>>,
+    _cgroup: Option<Cgroup>,
+}
+
+impl Exited {
+    /** Wait for the command to exit, returning the members describing how it went, and its standard error. */
+    fn wait(&mut self) -> (Vec<(String, Value)>, Vec<u8>) {
+        let status = self.child.wait();
+        let duration = self.start.elapsed();
+        if let Some(writer) = self.writer.take() {
+            let _ = writer.join();
+        }
+        let stderr = self.errors.take()
+            .and_then(|errors| errors.join().ok())
+            .unwrap_or_default();
+        let optional = |value: Option<i32>| value.map(|v| Value::Integer(i128::from(v))).unwrap_or(Value::Empty());
+        let (code, signal, success) = match status {
+            Ok(status) => (optional(status.code()), optional(status.signal()), Value::Bool(status.success())),
+            Err(_) => (Value::Empty(), Value::Empty(), Value::Bool(false)),
+        };
+        let members = vec![
+            ("status".to_string(), code),
+            ("signal".to_string(), signal),
+            ("success".to_string(), success),
+            ("duration".to_string(), Value::Duration(Duration::from_std(duration).unwrap_or_else(|_| Duration::zero()))),
+        ];
+        (members, stderr)
+    }
+}
+
+/** Lists are passed on as one argument per element, everything else as a single argument. */
+fn arguments(value: Value, res: &mut Vec<String>) {
+    match value {
+        Value::List(list) => {
+            for element in list.dump() {
+                arguments(element, res);
+            }
+        }
+        v => res.push(v.to_string()),
+    }
+}
+
+fn input(value: Value) -> CrushResult<Box<dyn BinaryReader + Send + Sync>> {
+    match value {
+        Value::BinaryStream(b) => Ok(b),
+        Value::Binary(b) => Ok(<dyn BinaryReader>::vec(&b)),
+        Value::String(s) => Ok(<dyn BinaryReader>::vec(&s.into_bytes())),
+        v => argument_error(format!("Expected stdin to be binary data or a string, got a {}", v.value_type().to_string()).as_str()),
+    }
+}
+
+#[signature(
+exec,
+can_block = true,
+output = Known(ValueType::Struct),
+short = "Run an external command and return how it went",
+long = "Unlike running an external command directly, a failing command is not an error. Instead, a",
+long = "struct is returned with the following members:",
+long = "",
+long = "    * status, the exit status, or empty if the command was killed by a signal",
+long = "    * signal, the signal that killed the command, or empty",
+long = "    * success, true if the exit status was zero",
+long = "    * duration, how long the command ran for",
+long = "    * stdout and stderr, everything the command wrote, as binary streams",
+long = "",
+long = "The struct is returned once the command has exited, unless it writes more than a megabyte to",
+long = "stdout. Then the struct is returned right away, so that the output can be processed while the",
+long = "command runs, and the status, signal, success and duration members are empty until the",
+long = "command has exited, which is always the case once stdout has been read to the end. Such a",
+long = "command blocks while its output is not being read. Standard error is available once the",
+long = "command has exited.",
+long = "",
+long = "All arguments are converted to strings, and lists are passed on as one argument per element.",
+long = "Since named arguments are used by exec itself, flags must be given as strings.",
+long = "",
+long = "The program inherits the environment of crush. Crush variables are only passed on as",
+long = "environment variables when listed in inherit, converted to strings the same way as by env:set.",
+example = "(exec \"git\" \"status\" \"--short\"):stdout | lines:from")]
+pub struct Exec {
+    #[description("the program to run, either a file or the name of a program in the PATH.")]
+    program: Value,
+    #[unnamed()]
+    #[description("the arguments to pass to the program.")]
+    args: Vec<Value>,
+    #[description("binary data or a string to pass to the command as its standard input.")]
+    stdin: Option<Value>,
+    #[description("names of crush variables to pass to the program as environment variables.")]
+    inherit: Vec<String>,
+}
+
+fn exec(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Exec = Exec::parse(context.arguments, &context.printer)?;
+    let program = match cfg.program {
+        Value::File(f) => f.into_os_string(),
+        Value::String(s) => s.into(),
+        v => return argument_error(format!("Expected the program to be a file or a string, got a {}", v.value_type().to_string()).as_str()),
+    };
+    let stdin = match cfg.stdin {
+        Some(value) => Some(input(value)?),
+        None => None,
+    };
+    let mut cmd = std::process::Command::new(&program);
+    if let Some(environment) = context.env.process_environment() {
+        environment.apply(&mut cmd);
+    }
+    let mut args = Vec::new();
+    for arg in cfg.args {
+        arguments(arg, &mut args);
+    }
+    for name in &cfg.inherit {
+        match context.env.get(name)? {
+            Some(value) => {
+                cmd.env(name, to_environment_value(&value)?);
+            }
+            None => return argument_error(format!("Unknown variable {}", name).as_str()),
+        }
+    }
+    cmd.args(args)
+        .stdin(if stdin.is_some() { Stdio::piped() } else { Stdio::null() })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    let cgroup = match context.env.process_limits() {
+        Some(limits) => limits.apply(&mut cmd)?,
+        None => None,
+    };
+
+    let start = Instant::now();
+    let mut child = match cmd.spawn() {
+        Ok(child) => child,
+        Err(e) => return error(format!("Could not run {}: {}", program.to_string_lossy(), e).as_str()),
+    };
+    // Standard input is written from a separate thread, so that a command that writes a lot of
+    // output before reading all of its input can't make both sides wait for each other
+    let writer = match (stdin, child.stdin.take()) {
+        (Some(mut reader), Some(mut child_stdin)) => {
+            Some(to_crush_error(build("exec:stdin").spawn(move || {
+                // The command may exit without reading all of its input, which is not an error
+                let _ = std::io::copy(&mut reader, &mut child_stdin);
+                let _ = child_stdin.flush();
+            }))?)
+        }
+        _ => None,
+    };
+    // Standard error is usually small, and is collected in full so that a command that writes
+    // a lot of it can't block while only standard output is being read
+    let mut child_stderr = child.stderr.take().unwrap();
+    let errors = to_crush_error(build("exec:stderr").spawn(move || {
+        let mut data = Vec::new();
+        let _ = child_stderr.read_to_end(&mut data);
+        data
+    }))?;
+
+    let mut child_stdout = child.stdout.take().unwrap();
+    let mut buffered = Vec::new();
+    to_crush_error((&mut child_stdout).take(STREAM_THRESHOLD).read_to_end(&mut buffered))?;
+    let mut exited = Exited { child, start, writer, errors: Some(errors), _cgroup: cgroup };
+
+    if (buffered.len() as u64) < STREAM_THRESHOLD {
+        let (mut members, stderr) = exited.wait();
+        members.push(("stdout".to_string(), Value::BinaryStream(<dyn BinaryReader>::vec(&buffered))));
+        members.push(("stderr".to_string(), Value::BinaryStream(<dyn BinaryReader>::vec(&stderr))));
+        return context.output.send(Value::Struct(Struct::new(members, None)));
+    }
+
+    let (mut stdout_writer, stdout) = binary_channel();
+    let (mut stderr_writer, stderr) = binary_channel();
+    let result = Struct::new(
+        vec![
+            ("status".to_string(), Value::Empty()),
+            ("signal".to_string(), Value::Empty()),
+            ("success".to_string(), Value::Empty()),
+            ("duration".to_string(), Value::Empty()),
+            ("stdout".to_string(), Value::BinaryStream(stdout)),
+            ("stderr".to_string(), Value::BinaryStream(stderr)),
+        ],
+        None);
+    let finished = result.clone();
+    to_crush_error(build("exec:stdout").spawn(move || {
+        let _ = stdout_writer.write_all(&buffered)
+            .and_then(|_| std::io::copy(&mut child_stdout, &mut stdout_writer));
+        drop(child_stdout);
+        let (members, errors) = exited.wait();
+        let _ = stderr_writer.write_all(&errors);
+        for (name, value) in members {
+            finished.set(&name, value);
+        }
+        // Standard output ends only once the members above are set
+        drop(stdout_writer);
+    }))?;
+    context.output.send(Value::Struct(result))
+}
@@ -1,17 +1,23 @@
 use crate::lang::argument::Argument;
-use crate::lang::value::Value;
+use crate::lang::value::{Value, ValueType};
 use crate::lang::{table::TableReader, list::ListReader, r#struct::Struct, dict::DictReader};
 use crate::lang::errors::{argument_error, CrushResult};
 use crate::lang::execution_context::{ExecutionContext, ArgumentVector};
-use crate::lang::stream::{empty_channel, CrushStream, black_hole};
+use crate::lang::stream::{empty_channel, CrushStream, channels};
 use crate::lang::command::Command;
+use crate::lang::table::{ColumnType, Row};
 
+/**
+Runs the body once per row of the input. The output of each iteration is a row of the output of
+the loop, except for empty values, so that a body like {echo i} outputs nothing.
+*/
 pub fn run(
     context: ExecutionContext,
     body: Command,
     name: Option<String>,
     mut input: impl CrushStream,
 ) -> CrushResult<()> {
+    let output = context.output.initialize(vec![ColumnType::new("value", ValueType::Any)])?;
     while let Ok(line) = input.read() {
         let env = context.env.create_child(&context.env, true);
         let arguments =
@@ -34,14 +40,19 @@ pub fn run(
                         )))]
                 }
             };
+        let (sender, receiver) = channels();
         body.invoke(ExecutionContext {
             input: empty_channel(),
-            output: black_hole(),
+            output: sender,
             arguments,
             env: env.clone(),
             this: None,
             printer: context.printer.clone(),
         })?;
+        match receiver.recv() {
+            Ok(Value::Empty()) | Err(_) => {}
+            Ok(value) => output.send(Row::new(vec![value]))?,
+        }
         if env.is_stopped() {
             break;
         }
@@ -50,7 +61,6 @@ pub fn run(
 }
 
 pub fn r#for(mut context: ExecutionContext) -> CrushResult<()> {
-    context.output.clone().initialize(vec![])?;
     context.arguments.check_len(2)?;
 
     let body = context.arguments.command(1)?;
@@ -63,8 +73,8 @@ pub fn r#for(mut context: ExecutionContext) -> CrushResult<()> {
             run(context, body, name, o),
         (_, Value::Table(r)) =>
             run(context, body, name, TableReader::new(r)),
-        (Some(name), Value::List(l)) =>
-            run(context, body, None, ListReader::new(l, name)),
+        (name, Value::List(l)) =>
+            run(context, body, None, ListReader::new(l, name.unwrap_or("value"))),
         (_, Value::Dict(l)) =>
             run(context, body, name, DictReader::new(l)),
         _ => argument_error(format!("Can not iterate over value of type {}", t.to_string()).as_str()),
@@ -0,0 +1,42 @@
+use crate::lang::command::OutputType::Known;
+use crate::lang::errors::CrushResult;
+use crate::lang::execution_context::ExecutionContext;
+use crate::lang::history;
+use crate::lang::table::{ColumnType, Row};
+use crate::lang::value::{Value, ValueType};
+use lazy_static::lazy_static;
+use signature::signature;
+
+lazy_static! {
+    static ref OUTPUT_TYPE: Vec<ColumnType> = vec![
+        ColumnType::new("index", ValueType::Integer),
+        ColumnType::new("time", ValueType::Time),
+        ColumnType::new("duration", ValueType::Duration),
+        ColumnType::new("command", ValueType::String),
+        ColumnType::new("success", ValueType::Bool),
+    ];
+}
+
+#[signature(
+history,
+can_block = true,
+output = Known(ValueType::TableStream(OUTPUT_TYPE.clone())),
+short = "Return a table stream of the commands entered interactively, oldest first",
+long = "The history is shared by all interactive crush sessions of the user, and is kept in the file",
+long = "~/.config/crush/history. Use Ctrl-R in the shell to search it.",
+example = "history | where {success == false} | tail 5")]
+pub struct History {}
+
+fn history(context: ExecutionContext) -> CrushResult<()> {
+    let output = context.output.initialize(OUTPUT_TYPE.clone())?;
+    for (idx, entry) in history::load(&history::file()?)?.into_iter().enumerate() {
+        output.send(Row::new(vec![
+            Value::Integer(idx as i128 + 1),
+            Value::Time(entry.time),
+            Value::Duration(entry.duration),
+            Value::String(entry.command),
+            Value::Bool(entry.success),
+        ]))?;
+    }
+    Ok(())
+}
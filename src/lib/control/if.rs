@@ -8,23 +8,25 @@ use crate::lang::command::Command;
 r#if,
 condition = true,
 short = "Conditionally execute a command once.",
-example = "if a > 10 {echo \"big\"} {echo \"small\"}")]
+long = "The clauses can also be given as named arguments, and the output of the clause that is",
+long = "executed is the output of if.",
+example = "if a > 10 then={echo \"big\"} else={echo \"small\"}")]
 pub struct If {
     #[description("the condition to filter on.")]
     condition: bool,
     #[description("the command to invoke if the condition is true.")]
-    true_clause: Command,
+    then: Command,
     #[description("the (optional) command to invoke if the condition is false.")]
-    false_clause: Option<Command>,
+    r#else: Option<Command>,
 }
 
 fn r#if(context: ExecutionContext) -> CrushResult<()> {
     let cfg: If = If::parse(context.arguments.clone(), &context.printer)?;
 
     if cfg.condition {
-        cfg.true_clause.invoke(context.with_args(vec![], None))
+        cfg.then.invoke(context.with_args(vec![], None))
     } else {
-        cfg.false_clause
+        cfg.r#else
             .map(|v| v.invoke(context.with_args(vec![], None)))
             .unwrap_or(Ok(()))
     }
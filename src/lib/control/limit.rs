@@ -0,0 +1,75 @@
+use crate::lang::errors::{CrushResult, argument_error};
+use crate::lang::execution_context::ExecutionContext;
+use crate::lang::command::Command;
+use crate::lang::process_limits::ProcessLimits;
+use signature::signature;
+use crate::lang::argument::ArgumentHandler;
+
+#[signature(
+limit,
+short = "Run a command with resource limits applied to the external commands it starts",
+long = "The limits only apply to external commands, and not to the shell itself, so a heavy",
+long = "background job can be kept from slowing down the interactive session. Limits of nested",
+long = "limit commands are combined, with the innermost one taking precedence.",
+long = "",
+long = "With cgroup=true, every external command is put in a new transient cgroup, which requires",
+long = "the unified cgroup hierarchy and a cgroup delegated to the current user. The memory limit",
+long = "then applies to the cgroup instead of to the address space of the process.",
+example = "limit nice=10 io_class=\"idle\" memory=(1024*1024*1024) {tar --c --z --f backup.tgz ./src}")]
+pub struct Limit {
+    #[description("the command to run.")]
+    command: Command,
+    #[description("the niceness of started processes, between -20 and 19.")]
+    nice: Option<i128>,
+    #[description("the IO scheduling class of started processes, one of realtime, best-effort and idle.")]
+    io_class: Option<String>,
+    #[description("the IO priority of started processes within their scheduling class, between 0 and 7.")]
+    io_priority: Option<i128>,
+    #[description("the maximum amount of memory in bytes that started processes may use.")]
+    memory: Option<i128>,
+    #[default(false)]
+    #[description("run started processes in a transient cgroup (Linux only).")]
+    cgroup: bool,
+}
+
+fn limit(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Limit = Limit::parse(context.arguments, &context.printer)?;
+
+    if let Some(nice) = cfg.nice {
+        if !(-20..=19).contains(&nice) {
+            return argument_error("The niceness must be between -20 and 19");
+        }
+    }
+    if let Some(priority) = cfg.io_priority {
+        if !(0..=7).contains(&priority) {
+            return argument_error("The IO priority must be between 0 and 7");
+        }
+    }
+    if let Some(memory) = cfg.memory {
+        if memory <= 0 {
+            return argument_error("The memory limit must be positive");
+        }
+    }
+
+    let limits = ProcessLimits {
+        nice: cfg.nice.map(|n| n as i32),
+        io_class: match &cfg.io_class {
+            Some(name) => Some(ProcessLimits::io_class_from_name(name)?),
+            None => None,
+        },
+        io_priority: cfg.io_priority.map(|p| p as i32),
+        memory: cfg.memory.map(|m| m as u64),
+        cgroup: cfg.cgroup,
+    };
+
+    let env = context.env.create_child(&context.env, false);
+    env.set_process_limits(context.env.process_limits().unwrap_or_default().merge(&limits));
+    cfg.command.invoke(ExecutionContext {
+        input: context.input,
+        output: context.output,
+        arguments: vec![],
+        env,
+        this: None,
+        printer: context.printer,
+    })
+}
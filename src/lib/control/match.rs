@@ -0,0 +1,128 @@
+use crate::lang::argument::{Argument, ArgumentHandler};
+use crate::lang::errors::{CrushResult, argument_error};
+use crate::lang::execution_context::ExecutionContext;
+use crate::lang::value::Value;
+use regex::Regex;
+use signature::signature;
+
+#[signature(
+r#match,
+condition = true,
+short = "Execute the command of the first pattern that matches a value.",
+long = "The arguments after the value are pairs of a pattern and a command, optionally followed by",
+long = "a single command that is executed if no pattern matches. The patterns are:",
+long = "* a type, which matches values of that type,",
+long = "* a glob or a regular expression, which matches strings and files,",
+long = "* a struct, which matches structs with all of its fields, where each field of the pattern is",
+long = "  in turn a pattern for the field of the value,",
+long = "* any other value, which matches values equal to it.",
+long = "",
+long = "The fields of a matched struct and the named groups of a matched regular expression are",
+long = "passed to the command as named arguments. If nothing matches and there is no fallback",
+long = "command, nothing is executed.",
+example = "match (data name=\"crush\" size=3) (data name=string size=integer) {echo name} {echo \"other\"}")]
+pub struct Match {
+    #[description("the value to match.")]
+    subject: Value,
+    #[unnamed()]
+    #[description("patterns, each followed by the command to execute if it matches.")]
+    cases: Vec<Value>,
+}
+
+fn captures(re: &Regex, s: &str, arguments: &mut Vec<Argument>) -> bool {
+    match re.captures(s) {
+        Some(captures) => {
+            for name in re.capture_names().flatten() {
+                if let Some(m) = captures.name(name) {
+                    arguments.push(Argument::named(name, Value::string(m.as_str())));
+                }
+            }
+            true
+        }
+        None => false,
+    }
+}
+
+/**
+Checks if the value matches the pattern, and if so, appends the values the pattern binds to the
+arguments.
+*/
+fn matches(pattern: &Value, value: &Value, arguments: &mut Vec<Argument>) -> bool {
+    match (pattern, value) {
+        (Value::Type(t), v) => t.is(v),
+        (Value::Glob(g), Value::String(s)) => g.matches(s),
+        (Value::Glob(g), Value::File(f)) => g.matches(&f.to_string_lossy()),
+        (Value::Regex(_, re), Value::String(s)) => captures(re, s, arguments),
+        (Value::Regex(_, re), Value::File(f)) => captures(re, &f.to_string_lossy(), arguments),
+        (Value::Struct(p), Value::Struct(s)) => {
+            let mut bound = Vec::new();
+            for column in p.local_signature() {
+                let field_matches = match (p.get(&column.name), s.get(&column.name)) {
+                    (Some(field_pattern), Some(field)) => {
+                        bound.push(Argument::named(&column.name, field.clone()));
+                        matches(&field_pattern, &field, &mut bound)
+                    }
+                    _ => false,
+                };
+                if !field_matches {
+                    return false;
+                }
+            }
+            arguments.append(&mut bound);
+            true
+        }
+        (p, v) => p == v,
+    }
+}
+
+fn r#match(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Match = Match::parse(context.arguments.clone(), &context.printer)?;
+    let mut cases = cfg.cases.into_iter();
+    loop {
+        match (cases.next(), cases.next()) {
+            (Some(pattern), Some(Value::Command(command))) => {
+                let mut arguments = Vec::new();
+                if matches(&pattern, &cfg.subject, &mut arguments) {
+                    return command.invoke(context.with_args(arguments, None));
+                }
+            }
+            (Some(Value::Command(fallback)), None) =>
+                return fallback.invoke(context.with_args(vec![], None)),
+            (None, _) => return Ok(()),
+            _ => return argument_error("Expected pairs of a pattern and a command"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lang::r#struct::Struct;
+    use crate::lang::value::ValueType;
+    use crate::util::glob::Glob;
+
+    #[test]
+    fn patterns() {
+        let mut arguments = Vec::new();
+        assert!(matches(&Value::Type(ValueType::Integer), &Value::Integer(1), &mut arguments));
+        assert!(!matches(&Value::Type(ValueType::String), &Value::Integer(1), &mut arguments));
+        assert!(matches(&Value::Glob(Glob::new("%.rs")), &Value::string("main.rs"), &mut arguments));
+        assert!(!matches(&Value::Integer(1), &Value::Float(1.0), &mut arguments));
+        assert!(arguments.is_empty());
+
+        let re = Regex::new("(?P<major>[0-9]+)\\.[0-9]+").unwrap();
+        assert!(matches(&Value::Regex(re.to_string(), re), &Value::string("v12.3"), &mut arguments));
+        assert_eq!(arguments.len(), 1);
+        assert!(arguments[0].value == Value::string("12"));
+
+        let pattern = Struct::new(vec![("size".to_string(), Value::Type(ValueType::Integer))], None);
+        let subject = Struct::new(vec![
+            ("name".to_string(), Value::string("crush")),
+            ("size".to_string(), Value::Integer(3)),
+        ], None);
+        let mut arguments = Vec::new();
+        assert!(matches(&Value::Struct(pattern.clone()), &Value::Struct(subject), &mut arguments));
+        assert_eq!(arguments.len(), 1);
+        assert!(!matches(&Value::Struct(pattern), &Value::Integer(3), &mut arguments));
+    }
+}
@@ -1,5 +1,5 @@
 use crate::lang::scope::Scope;
-use crate::lang::errors::{CrushResult, argument_error, to_crush_error};
+use crate::lang::errors::{CrushResult, argument_error, to_crush_error, exit_status_error, error};
 use crate::lang::{value::Value, list::List, value::ValueType, execution_context::ExecutionContext, binary::BinaryReader};
 use std::env;
 use signature::signature;
@@ -8,10 +8,21 @@ mod r#if;
 mod r#while;
 mod r#loop;
 mod r#for;
+mod r#match;
+mod r#try;
+mod with;
+mod limit;
+mod tty;
+mod dashboard;
+mod exec;
+mod source;
+mod history;
 
 use std::path::PathBuf;
+use std::process::ExitStatus;
 use chrono::Duration;
 use crate::lang::argument::ArgumentHandler;
+use crate::lang::command::ArgumentDescription;
 use crate::lang::command::OutputType::Known;
 
 pub fn r#break(context: ExecutionContext) -> CrushResult<()> {
@@ -51,6 +62,17 @@ pub fn cmd(mut context: ExecutionContext) -> CrushResult<()> {
                     }
                 }
             }
+            if let Some(environment) = context.env.process_environment() {
+                environment.apply(&mut cmd);
+            }
+            let _cgroup = match context.env.process_limits() {
+                Some(limits) => limits.apply(&mut cmd)?,
+                None => None,
+            };
+            if context.env.use_pty() {
+                let status = tty::run(&mut cmd, &context.printer)?;
+                return exit_status(status, context.output.send(Value::Empty()));
+            }
             let output = to_crush_error(cmd.output())?;
             let errors = String::from_utf8_lossy(&output.stderr);
             for e in errors.split('\n') {
@@ -59,14 +81,23 @@ pub fn cmd(mut context: ExecutionContext) -> CrushResult<()> {
                     context.printer.error(err);
                 }
             }
-            context.output.send(
+            let sent = context.output.send(
                 Value::BinaryStream(
-                    BinaryReader::vec(&output.stdout)))
+                    BinaryReader::vec(&output.stdout)));
+            exit_status(output.status, sent)
         }
         _ => argument_error("Not a valid command")
     }
 }
 
+fn exit_status(status: ExitStatus, sent: CrushResult<()>) -> CrushResult<()> {
+    match status.code() {
+        Some(0) => sent,
+        Some(code) => exit_status_error(code),
+        None => error("Command was terminated by a signal"),
+    }
+}
+
 #[signature(
 sleep,
 can_block = true,
@@ -100,17 +131,28 @@ pub fn declare(root: &Scope) -> CrushResult<()> {
             r#if::If::declare(env)?;
             r#while::While::declare(env)?;
             r#loop::Loop::declare(env)?;
+            r#match::Match::declare(env)?;
+            r#try::Try::declare(env)?;
 
             env.declare_condition_command(
                 "for",
                 r#for::r#for,
                 "for [name=]iterable:(table_stream|table|dict|list) body:command",
                 "Execute body once for every element in iterable.",
-                Some(r#"    Example:
+                Some(r#"    Without a name, the columns of each row are passed to the body as named
+    arguments. With a name, the whole row (or element of a list) is passed as a
+    named argument with that name, which can also be written as name in iterable.
+
+    The output of for is a stream with one row per iteration that had a
+    non-empty output, containing that output.
+
+    Example:
 
     for (seq 10) {
         echo ("Lap #{}":format value)
-    }"#))?;
+    }
+
+    for file in (ls) {file:size * 2}"#))?;
 
             env.declare_command(
                 "break", r#break, false,
@@ -120,12 +162,24 @@ pub fn declare(root: &Scope) -> CrushResult<()> {
                 "continue",
                 "Skip execution of the current iteration of a loop",
                 None, Known(ValueType::Empty))?;
-            env.declare_command(
+            env.declare_command_with_arguments(
                 "cmd", cmd, true,
                 "cmd external_command:(file|string) @arguments:any",
+                &[
+                    ArgumentDescription { name: "external_command", value_type: "(file|string)", named: false },
+                    ArgumentDescription { name: "arguments", value_type: "any", named: false },
+                    ArgumentDescription { name: "options", value_type: "any", named: true },
+                ],
                 "Execute external commands",
                 None, Known(ValueType::BinaryStream))?;
             Sleep::declare(env)?;
+            with::With::declare(env)?;
+            limit::Limit::declare(env)?;
+            tty::Tty::declare(env)?;
+            dashboard::Dashboard::declare(env)?;
+            exec::Exec::declare(env)?;
+            source::Source::declare(env)?;
+            history::History::declare(env)?;
             Ok(())
         }))?;
     root.r#use(&e);
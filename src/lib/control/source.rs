@@ -0,0 +1,27 @@
+use crate::lang::errors::CrushResult;
+use crate::lang::execution_context::ExecutionContext;
+use crate::lang::module;
+use crate::lang::stream::black_hole;
+use crate::lang::argument::ArgumentHandler;
+use signature::signature;
+use std::path::PathBuf;
+
+#[signature(
+source,
+can_block = true,
+short = "Run a file in the current scope",
+long = "Unlike a module loaded using var:mod, the variables the file declares are put directly into",
+long = "the scope source is called from, which makes it suitable for e.g. profile files. The output",
+long = "of the jobs in the file is discarded, errors are reported along with the name of the file,",
+long = "and a file that sources itself, directly or through other files, is an error.",
+example = "source ./profile.crush")]
+pub struct Source {
+    #[description("the file to run.")]
+    file: PathBuf,
+}
+
+fn source(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Source = Source::parse(context.arguments, &context.printer)?;
+    module::source(&context.env, &cfg.file, &context.printer, &black_hole())?;
+    context.output.empty()
+}
@@ -0,0 +1,116 @@
+use crate::lang::argument::{Argument, ArgumentHandler};
+use crate::lang::command::Command;
+use crate::lang::errors::{CrushError, CrushResult, Kind};
+use crate::lang::execution_context::ExecutionContext;
+use crate::lang::list::List;
+use crate::lang::r#struct::Struct;
+use crate::lang::value::{Value, ValueType};
+use signature::signature;
+
+#[signature(
+r#try,
+condition = true,
+short = "Execute a command, and handle its errors with another command.",
+long = "Errors reported by the commands run by body, including failures of commands in a",
+long = "pipeline and non-zero exit statuses, are not printed. Instead, once body has finished,",
+long = "catch is invoked with a named argument err, a struct with the following fields:",
+long = "* message:string, the message of the first error",
+long = "* kind:string, the kind of the first error, e.g. invalid_argument or exit_status",
+long = "* code:integer, the exit status of the failure",
+long = "* line:integer and column:integer, where in the code the first error happened, or 0 if",
+long = "  that is not known",
+long = "* trace:list, the messages of all the errors reported, in order",
+long = "",
+long = "Without catch, errors are silently ignored.",
+example = "try {rm ./some_file} catch={|err| echo (\"Could not remove: {}\":format err:message)}")]
+pub struct Try {
+    #[description("the command to invoke.")]
+    body: Command,
+    #[description("the command to invoke if body fails.")]
+    catch: Option<Command>,
+}
+
+fn kind(kind: &Kind) -> &'static str {
+    match kind {
+        Kind::InvalidArgument => "invalid_argument",
+        Kind::InvalidData => "invalid_data",
+        Kind::GenericError => "error",
+        Kind::BlockError => "block",
+        Kind::SendError => "send",
+        Kind::ExitStatus(_) => "exit_status",
+    }
+}
+
+/** The value passed to the catch command for the given errors, of which there is at least one. */
+fn error_value(errors: &[CrushError]) -> Value {
+    let first = &errors[0];
+    let code = match first.kind {
+        Kind::ExitStatus(code) => code,
+        _ => 1,
+    };
+    let (line, column) = first.location.as_ref().map(|l| l.line_column()).unwrap_or((0, 0));
+    Value::Struct(Struct::new(
+        vec![
+            ("message".to_string(), Value::string(&first.message)),
+            ("kind".to_string(), Value::string(kind(&first.kind))),
+            ("code".to_string(), Value::Integer(code as i128)),
+            ("line".to_string(), Value::Integer(line as i128)),
+            ("column".to_string(), Value::Integer(column as i128)),
+            ("trace".to_string(), Value::List(List::new(
+                ValueType::String,
+                errors.iter().map(|e| Value::string(&e.message)).collect()))),
+        ],
+        None))
+}
+
+fn r#try(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Try = Try::parse(context.arguments.clone(), &context.printer)?;
+    let (printer, caught) = context.printer.catching();
+    let result = cfg.body.invoke(ExecutionContext {
+        printer,
+        ..context.clone().with_args(vec![], None)
+    });
+
+    let mut errors = caught.take();
+    if let Err(e) = result {
+        /* The failure of a job repeats the message of an error that has already been reported. */
+        if !errors.iter().any(|reported| reported.message == e.message) {
+            errors.push(e);
+        }
+    }
+    errors.retain(|e| e.kind != Kind::SendError);
+    if errors.is_empty() {
+        return Ok(());
+    }
+    match cfg.catch {
+        Some(catch) => catch.invoke(context.with_args(vec![Argument::named("err", error_value(&errors))], None)),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lang::errors::{argument_error, exit_status_error};
+
+    #[test]
+    fn error_values() {
+        let errors = vec![
+            argument_error::<()>("Bad argument").unwrap_err(),
+            exit_status_error::<()>(3).unwrap_err(),
+        ];
+        match error_value(&errors) {
+            Value::Struct(s) => {
+                assert!(s.get("message").unwrap() == Value::string("Bad argument"));
+                assert!(s.get("kind").unwrap() == Value::string("invalid_argument"));
+                assert!(s.get("code").unwrap() == Value::Integer(1));
+                assert!(s.get("line").unwrap() == Value::Integer(0));
+                match s.get("trace") {
+                    Some(Value::List(l)) => assert_eq!(l.len(), 2),
+                    _ => panic!("Expected a list"),
+                }
+            }
+            _ => panic!("Expected a struct"),
+        }
+    }
+}
@@ -0,0 +1,151 @@
+use crate::lang::errors::{CrushResult, to_crush_error};
+use crate::lang::execution_context::ExecutionContext;
+use crate::lang::command::Command;
+use crate::lang::printer::Printer;
+use signature::signature;
+use crate::lang::argument::ArgumentHandler;
+use nix::libc;
+use nix::poll::{poll, PollFd, PollFlags};
+use nix::pty::{openpty, Winsize};
+use nix::sys::termios::{tcgetattr, tcsetattr, cfmakeraw, SetArg};
+use nix::unistd::{close, dup, read, write};
+use std::io::Write;
+use std::os::unix::io::{FromRawFd, RawFd};
+use std::os::unix::process::CommandExt;
+use std::process::{ExitStatus, Stdio};
+
+#[signature(
+tty,
+can_block = true,
+short = "Run a command with the external commands it starts attached to a pseudo terminal",
+long = "Normally, the output of external commands is captured so that it can be passed on to other",
+long = "commands. Full screen programs like editors, pagers and remote shells need a real terminal",
+long = "instead. Inside tty, every external command gets a pseudo terminal of its own, and the",
+long = "terminal of the shell is handed over to it until it exits. The output of such commands can",
+long = "not be piped into other commands.",
+example = "tty {vim README.md}")]
+pub struct Tty {
+    #[description("the command to run.")]
+    command: Command,
+}
+
+fn tty(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Tty = Tty::parse(context.arguments, &context.printer)?;
+    let env = context.env.create_child(&context.env, false);
+    env.set_use_pty();
+    cfg.command.invoke(ExecutionContext {
+        input: context.input,
+        output: context.output,
+        arguments: vec![],
+        env,
+        this: None,
+        printer: context.printer,
+    })
+}
+
+/**
+Run the specified command in a new pseudo terminal and wait for it to exit.
+
+All printing is held off while the command runs. If the shell itself is attached to a
+terminal, that terminal is put in raw mode and everything typed is passed on to the command.
+*/
+pub fn run(cmd: &mut std::process::Command, printer: &Printer) -> CrushResult<ExitStatus> {
+    let is_tty = termion::is_tty(&std::io::stdin());
+    let size = termion::terminal_size().ok().map(|(cols, rows)| Winsize {
+        ws_row: rows,
+        ws_col: cols,
+        ws_xpixel: 0,
+        ws_ypixel: 0,
+    });
+    let pty = to_crush_error(openpty(size.as_ref(), None))?;
+
+    unsafe {
+        cmd.stdin(Stdio::from_raw_fd(to_crush_error(dup(pty.slave))?));
+        cmd.stdout(Stdio::from_raw_fd(to_crush_error(dup(pty.slave))?));
+        cmd.stderr(Stdio::from_raw_fd(to_crush_error(dup(pty.slave))?));
+        cmd.pre_exec(|| {
+            if libc::setsid() < 0 || libc::ioctl(0, libc::TIOCSCTTY as _, 0) < 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+    let _ = close(pty.slave);
+
+    let _terminal = printer.lock_terminal();
+    let saved = if is_tty {
+        let saved = to_crush_error(tcgetattr(0))?;
+        let mut raw = saved.clone();
+        cfmakeraw(&mut raw);
+        to_crush_error(tcsetattr(0, SetArg::TCSANOW, &raw))?;
+        Some(saved)
+    } else {
+        None
+    };
+
+    let child = cmd.spawn();
+    // The command keeps its copies of the slave side open until they are replaced, and the
+    // master side only reports end of file once every copy is closed.
+    cmd.stdin(Stdio::null());
+    cmd.stdout(Stdio::null());
+    cmd.stderr(Stdio::null());
+
+    let result = child.and_then(|mut child| {
+        forward(pty.master, is_tty);
+        child.wait()
+    });
+
+    if let Some(saved) = saved {
+        let _ = tcsetattr(0, SetArg::TCSANOW, &saved);
+    }
+    let _ = close(pty.master);
+    to_crush_error(result)
+}
+
+fn write_all(fd: RawFd, mut data: &[u8]) {
+    while !data.is_empty() {
+        match write(fd, data) {
+            Ok(n) => data = &data[n..],
+            Err(_) => return,
+        }
+    }
+}
+
+/**
+Copy the output of the pseudo terminal to standard output, and optionally standard input to
+the pseudo terminal, until the pseudo terminal is closed.
+*/
+fn forward(master: RawFd, forward_input: bool) {
+    let mut buffer = [0u8; 4096];
+    let mut forward_input = forward_input;
+    let stdout = std::io::stdout();
+    loop {
+        let mut fds = vec![PollFd::new(master, PollFlags::POLLIN)];
+        if forward_input {
+            fds.push(PollFd::new(0, PollFlags::POLLIN));
+        }
+        match poll(&mut fds, -1) {
+            Ok(_) => {}
+            Err(nix::Error::Sys(nix::errno::Errno::EINTR)) => continue,
+            Err(_) => return,
+        }
+
+        if fds[0].revents().map(|r| !r.is_empty()).unwrap_or(false) {
+            match read(master, &mut buffer) {
+                Ok(0) | Err(_) => return,
+                Ok(n) => {
+                    let mut out = stdout.lock();
+                    let _ = out.write_all(&buffer[..n]);
+                    let _ = out.flush();
+                }
+            }
+        }
+
+        if forward_input && fds[1].revents().map(|r| !r.is_empty()).unwrap_or(false) {
+            match read(0, &mut buffer) {
+                Ok(0) | Err(_) => forward_input = false,
+                Ok(n) => write_all(master, &buffer[..n]),
+            }
+        }
+    }
+}
@@ -0,0 +1,72 @@
+use crate::lang::errors::{CrushResult, argument_error, to_crush_error};
+use crate::lang::execution_context::ExecutionContext;
+use crate::lang::value::Value;
+use crate::lang::command::Command;
+use crate::lang::ordered_string_map::OrderedStringMap;
+use crate::lang::process_environment::ProcessEnvironment;
+use signature::signature;
+use crate::lang::argument::ArgumentHandler;
+use std::path::PathBuf;
+
+#[signature(
+with,
+short = "Run a command with a different working directory and extra environment variables",
+long = "The working directory and environment variables apply to the external commands started",
+long = "by the command, e.g. through cmd or exec. The working directory and environment of the",
+long = "shell itself are left unchanged, so builtins and other jobs running at the same time are",
+long = "not affected. A relative working directory is resolved against the one of any enclosing",
+long = "with command. Settings of nested with commands are combined, with the innermost one",
+long = "taking precedence.",
+example = "with cwd=\"/tmp\" BUILD_MODE=\"release\" {make}")]
+pub struct With {
+    #[description("the command to run.")]
+    command: Command,
+    #[description("the working directory to run external commands in.")]
+    cwd: Option<Value>,
+    #[named()]
+    #[description("environment variables to set for external commands.")]
+    variables: OrderedStringMap<String>,
+}
+
+fn with(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: With = With::parse(context.arguments, &context.printer)?;
+    let current = context.env.process_environment().unwrap_or_default();
+
+    let cwd = match cfg.cwd {
+        None => None,
+        Some(Value::File(f)) => Some(f),
+        Some(Value::String(s)) => Some(PathBuf::from(s)),
+        Some(v) => return argument_error(
+            format!("Expected the working directory to be a file, got a value of type {}",
+                    v.value_type().to_string()).as_str()),
+    };
+    let cwd = match cwd {
+        Some(dir) if dir.is_relative() => {
+            let base = match &current.cwd {
+                Some(base) => base.clone(),
+                None => to_crush_error(std::env::current_dir())?,
+            };
+            Some(base.join(dir))
+        }
+        dir => dir,
+    };
+    if let Some(dir) = &cwd {
+        if !dir.is_dir() {
+            return argument_error(format!("{} is not a directory", dir.to_string_lossy()).as_str());
+        }
+    }
+
+    let env = context.env.create_child(&context.env, false);
+    env.set_process_environment(current.merge(&ProcessEnvironment {
+        cwd,
+        variables: cfg.variables.iter().map(|(k, v)| (k.clone(), Some(v.clone()))).collect(),
+    }));
+    cfg.command.invoke(ExecutionContext {
+        input: context.input,
+        output: context.output,
+        arguments: vec![],
+        env,
+        this: None,
+        printer: context.printer,
+    })
+}
@@ -0,0 +1,288 @@
+use chrono::{DateTime, Local, TimeZone};
+use lazy_static::lazy_static;
+use serde_json::json;
+
+use crate::lang::argument::ArgumentHandler;
+use crate::lang::command::OutputType::Known;
+use crate::lang::errors::{CrushResult, argument_error, data_error, error};
+use crate::lang::execution_context::ExecutionContext;
+use crate::lang::list::List;
+use crate::lang::scope::Scope;
+use crate::lang::table::{ColumnType, Row};
+use crate::lang::value::{Value, ValueType};
+use crate::util::docker::{escape, request, Lines};
+use signature::signature;
+
+lazy_static! {
+    static ref PS_OUTPUT_TYPE: Vec<ColumnType> = vec![
+        ColumnType::new("id", ValueType::String),
+        ColumnType::new("name", ValueType::String),
+        ColumnType::new("image", ValueType::String),
+        ColumnType::new("command", ValueType::String),
+        ColumnType::new("created", ValueType::Time),
+        ColumnType::new("state", ValueType::String),
+        ColumnType::new("status", ValueType::String),
+        ColumnType::new("ports", ValueType::List(Box::new(ValueType::String))),
+    ];
+    static ref IMAGES_OUTPUT_TYPE: Vec<ColumnType> = vec![
+        ColumnType::new("id", ValueType::String),
+        ColumnType::new("repository", ValueType::String),
+        ColumnType::new("tag", ValueType::String),
+        ColumnType::new("created", ValueType::Time),
+        ColumnType::new("size", ValueType::Integer),
+    ];
+    static ref LOGS_OUTPUT_TYPE: Vec<ColumnType> = vec![
+        ColumnType::new("time", ValueType::Time),
+        ColumnType::new("stream", ValueType::String),
+        ColumnType::new("line", ValueType::String),
+    ];
+    static ref EXEC_OUTPUT_TYPE: Vec<ColumnType> = vec![
+        ColumnType::new("stream", ValueType::String),
+        ColumnType::new("line", ValueType::String),
+    ];
+}
+
+/** Ids are shown shortened to twelve characters, like the docker command does. */
+fn short_id(id: &str) -> String {
+    id.trim_start_matches("sha256:").chars().take(12).collect()
+}
+
+fn string(json: &serde_json::Value) -> String {
+    json.as_str().unwrap_or("").to_string()
+}
+
+fn created(json: &serde_json::Value) -> Value {
+    json.as_i64()
+        .and_then(|seconds| Local.timestamp_opt(seconds, 0).single())
+        .map(Value::Time)
+        .unwrap_or(Value::Empty())
+}
+
+fn port(json: &serde_json::Value) -> String {
+    let private = format!("{}/{}", json["PrivatePort"].as_i64().unwrap_or(0), string(&json["Type"]));
+    match (json["IP"].as_str(), json["PublicPort"].as_i64()) {
+        (Some(ip), Some(public)) => format!("{}:{}->{}", ip, public, private),
+        _ => private,
+    }
+}
+
+#[signature(
+ps,
+can_block = true,
+output = Known(ValueType::TableStream(PS_OUTPUT_TYPE.clone())),
+short = "Return a table stream of the Docker containers",
+long = "The containers are listed using the Docker Engine API, on the socket in DOCKER_HOST or on",
+long = "/var/run/docker.sock. The state is e.g. running, exited or paused, and the status is the",
+long = "human readable description, e.g. \"Up 2 hours\". Ports are listed as they are published.",
+example = "docker:ps all=true | where {state == \"exited\"} | select ^name ^image")]
+pub struct Ps {
+    #[default(false)]
+    #[description("also list containers that aren't running.")]
+    all: bool,
+}
+
+fn ps(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Ps = Ps::parse(context.arguments, &context.printer)?;
+    let containers = request("GET", &format!("/containers/json?all={}", cfg.all), None)?.json()?;
+    let output = context.output.initialize(PS_OUTPUT_TYPE.clone())?;
+    for container in containers.as_array().unwrap_or(&vec![]) {
+        let ports = container["Ports"].as_array().unwrap_or(&vec![]).iter()
+            .map(|p| Value::String(port(p)))
+            .collect();
+        output.send(Row::new(vec![
+            Value::String(short_id(&string(&container["Id"]))),
+            Value::string(string(&container["Names"][0]).trim_start_matches('/')),
+            Value::String(string(&container["Image"])),
+            Value::String(string(&container["Command"])),
+            created(&container["Created"]),
+            Value::String(string(&container["State"])),
+            Value::String(string(&container["Status"])),
+            Value::List(List::new(ValueType::String, ports)),
+        ]))?;
+    }
+    Ok(())
+}
+
+#[signature(
+images,
+can_block = true,
+output = Known(ValueType::TableStream(IMAGES_OUTPUT_TYPE.clone())),
+short = "Return a table stream of the Docker images",
+long = "Images with several tags have one row per tag. Untagged images have <none> as their",
+long = "repository and tag. The size is in bytes.",
+example = "docker:images | where {size > 1000000000} | sort ^size")]
+pub struct Images {
+    #[default(false)]
+    #[description("also list intermediate images.")]
+    all: bool,
+}
+
+fn images(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Images = Images::parse(context.arguments, &context.printer)?;
+    let images = request("GET", &format!("/images/json?all={}", cfg.all), None)?.json()?;
+    let output = context.output.initialize(IMAGES_OUTPUT_TYPE.clone())?;
+    for image in images.as_array().unwrap_or(&vec![]) {
+        let mut tags = image["RepoTags"].as_array().unwrap_or(&vec![]).iter()
+            .map(string)
+            .collect::<Vec<_>>();
+        if tags.is_empty() {
+            tags.push("<none>:<none>".to_string());
+        }
+        for tag in tags {
+            // The repository can contain a colon too, if the registry has a port
+            let (repository, tag) = tag.rsplit_once(':').unwrap_or((&tag, "<none>"));
+            output.send(Row::new(vec![
+                Value::String(short_id(&string(&image["Id"]))),
+                Value::string(repository),
+                Value::string(tag),
+                created(&image["Created"]),
+                Value::Integer(image["Size"].as_i64().unwrap_or(0) as i128),
+            ]))?;
+        }
+    }
+    Ok(())
+}
+
+#[signature(
+logs,
+can_block = true,
+output = Known(ValueType::TableStream(LOGS_OUTPUT_TYPE.clone())),
+short = "Return a table stream of the log lines of a Docker container",
+long = "Each row contains the time the line was logged, whether it was written to stdout or stderr,",
+long = "and the line itself. Containers with a terminal only have stdout.",
+long = "",
+long = "If follow is true, the stream doesn't end until the container stops, so new lines can be",
+long = "processed as they are logged.",
+example = "docker:logs \"web\" tail=1000 | where {stream == \"stderr\"}")]
+pub struct Logs {
+    #[description("the name or id of the container.")]
+    container: String,
+    #[default(false)]
+    #[description("keep returning new lines as they are logged.")]
+    follow: bool,
+    #[description("only return this many of the last lines.")]
+    tail: Option<i128>,
+}
+
+fn tty(container: &str) -> CrushResult<bool> {
+    let info = request("GET", &format!("/containers/{}/json", escape(container)), None)?.json()?;
+    Ok(info["Config"]["Tty"].as_bool().unwrap_or(false))
+}
+
+fn logs(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Logs = Logs::parse(context.arguments, &context.printer)?;
+    let tail = match cfg.tail {
+        None => "all".to_string(),
+        Some(tail) if tail >= 0 => tail.to_string(),
+        Some(_) => return argument_error("Expected tail to be positive"),
+    };
+    let tty = tty(&cfg.container)?;
+    let response = request(
+        "GET",
+        &format!(
+            "/containers/{}/logs?stdout=true&stderr=true&timestamps=true&follow={}&tail={}",
+            escape(&cfg.container), cfg.follow, tail),
+        None)?.check()?;
+    let output = context.output.initialize(LOGS_OUTPUT_TYPE.clone())?;
+    let mut lines = Lines::new(response.body, tty);
+    while let Some((stream, line)) = lines.next()? {
+        let (time, line) = line.split_once(' ').unwrap_or((&line, ""));
+        let time = match DateTime::parse_from_rfc3339(time) {
+            Ok(time) => time.with_timezone(&Local),
+            Err(_) => return data_error("Expected log lines to start with a timestamp"),
+        };
+        output.send(Row::new(vec![
+            Value::Time(time),
+            Value::string(stream.name()),
+            Value::string(line),
+        ]))?;
+    }
+    Ok(())
+}
+
+#[signature(
+exec,
+can_block = true,
+output = Known(ValueType::TableStream(EXEC_OUTPUT_TYPE.clone())),
+short = "Run a command in a running Docker container",
+long = "Returns a table stream with the lines the command writes, and whether they were written to",
+long = "stdout or stderr. The command is run directly, not by a shell. It is an error if the",
+long = "command exits with a non-zero status, after all of its output has been returned.",
+example = "docker:exec \"db\" \"ls\" \"-l\" \"/var/lib/postgresql\"")]
+pub struct Exec {
+    #[description("the name or id of the container.")]
+    container: String,
+    #[unnamed()]
+    #[description("the command and its arguments.")]
+    command: Vec<String>,
+    #[description("the user to run the command as.")]
+    user: Option<String>,
+    #[description("the directory to run the command in.")]
+    workdir: Option<String>,
+}
+
+fn exec(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Exec = Exec::parse(context.arguments, &context.printer)?;
+    if cfg.command.is_empty() {
+        return argument_error("Expected a command to run");
+    }
+    let mut config = json!({
+        "AttachStdout": true,
+        "AttachStderr": true,
+        "Tty": false,
+        "Cmd": cfg.command,
+    });
+    if let Some(user) = cfg.user {
+        config["User"] = json!(user);
+    }
+    if let Some(workdir) = cfg.workdir {
+        config["WorkingDir"] = json!(workdir);
+    }
+    let created = request("POST", &format!("/containers/{}/exec", escape(&cfg.container)), Some(&config))?.json()?;
+    let id = string(&created["Id"]);
+    if id.is_empty() {
+        return data_error("The Docker daemon did not return an exec id");
+    }
+    let response = request("POST", &format!("/exec/{}/start", id), Some(&json!({"Detach": false, "Tty": false})))?.check()?;
+
+    let output = context.output.initialize(EXEC_OUTPUT_TYPE.clone())?;
+    let mut lines = Lines::new(response.body, false);
+    while let Some((stream, line)) = lines.next()? {
+        output.send(Row::new(vec![Value::string(stream.name()), Value::String(line)]))?;
+    }
+    match request("GET", &format!("/exec/{}/json", id), None)?.json()?["ExitCode"].as_i64() {
+        Some(0) | None => Ok(()),
+        Some(status) => error(format!("Command exited with status {}", status).as_str()),
+    }
+}
+
+pub fn declare(root: &Scope) -> CrushResult<()> {
+    root.create_lazy_namespace(
+        "docker",
+        Box::new(move |env| {
+            Ps::declare(env)?;
+            Images::declare(env)?;
+            Logs::declare(env)?;
+            Exec::declare(env)?;
+            Ok(())
+        }))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ports_are_formatted() {
+        assert_eq!(port(&json!({"IP": "0.0.0.0", "PrivatePort": 80, "PublicPort": 8080, "Type": "tcp"})), "0.0.0.0:8080->80/tcp");
+        assert_eq!(port(&json!({"PrivatePort": 53, "Type": "udp"})), "53/udp");
+    }
+
+    #[test]
+    fn invalid_creation_times_are_empty() {
+        assert!(created(&json!(1600000000)) == Value::Time(Local.timestamp(1600000000, 0)));
+        assert!(matches!(created(&json!(i64::MAX)), Value::Empty()));
+        assert!(matches!(created(&json!("yesterday")), Value::Empty()));
+    }
+}
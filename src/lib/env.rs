@@ -0,0 +1,159 @@
+use lazy_static::lazy_static;
+
+use crate::lang::argument::ArgumentHandler;
+use crate::lang::command::OutputType::{Known, Unknown};
+use crate::lang::errors::{CrushResult, argument_error};
+use crate::lang::execution_context::ExecutionContext;
+use crate::lang::ordered_string_map::OrderedStringMap;
+use crate::lang::process_environment::ProcessEnvironment;
+use crate::lang::scope::Scope;
+use crate::lang::{table::ColumnType, table::Row, value::Value, value::ValueType};
+use signature::signature;
+
+lazy_static! {
+    static ref LIST_OUTPUT_TYPE: Vec<ColumnType> = vec![
+        ColumnType::new("name", ValueType::String),
+        ColumnType::new("value", ValueType::String),
+    ];
+}
+
+/** Names that the operating system would reject or silently mangle. */
+fn check_name(name: &str) -> CrushResult<()> {
+    if name.is_empty() || name.contains('=') || name.contains('\0') {
+        argument_error(format!("Illegal environment variable name \"{}\"", name).as_str())
+    } else {
+        Ok(())
+    }
+}
+
+/** The string an environment variable is set to for a crush value. */
+pub fn to_environment_value(value: &Value) -> CrushResult<String> {
+    let res = match value {
+        Value::String(s) => s.clone(),
+        Value::File(f) => f.to_string_lossy().to_string(),
+        v => v.to_string(),
+    };
+    if res.contains('\0') {
+        argument_error("Environment variables can't contain null characters")
+    } else {
+        Ok(res)
+    }
+}
+
+/** The environment external commands started from the scope of the command get. */
+fn environment(env: &Scope) -> ProcessEnvironment {
+    env.process_environment().unwrap_or_default()
+}
+
+#[signature(
+get,
+can_block = false,
+output = Unknown,
+short = "Return the value of an environment variable",
+long = "If the variable isn't set, the default is returned, or nothing if there is no default.",
+long = "Variables set with env:set and with are included.",
+example = "env:get \"HOME\"")]
+struct Get {
+    #[description("the name of the environment variable.")]
+    name: String,
+    #[description("the value to return if the variable isn't set.")]
+    default: Option<String>,
+}
+
+fn get(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Get = Get::parse(context.arguments, &context.printer)?;
+    check_name(&cfg.name)?;
+    context.output.send(
+        match (environment(&context.env).get(&cfg.name), cfg.default) {
+            (Some(value), _) => Value::String(value),
+            (None, Some(default)) => Value::String(default),
+            (None, None) => Value::Empty(),
+        })
+}
+
+#[signature(
+set,
+can_block = false,
+output = Known(ValueType::Empty),
+short = "Set environment variables",
+long = "Values that aren't strings are converted to strings. The variables are set for the current",
+long = "scope and the commands it calls, and are inherited by the external commands started from",
+long = "them after this. The environment of the shell process itself is left unchanged, so jobs",
+long = "running at the same time are not affected.",
+example = "env:set EDITOR=\"vim\" JOBS=4")]
+struct Set {
+    #[named()]
+    #[description("the environment variables to set.")]
+    variables: OrderedStringMap<Value>,
+}
+
+fn set(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Set = Set::parse(context.arguments, &context.printer)?;
+    let mut variables = Vec::new();
+    for (name, value) in cfg.variables.iter() {
+        check_name(name)?;
+        variables.push((name, to_environment_value(value)?));
+    }
+    let mut environment = environment(&context.env);
+    for (name, value) in variables {
+        environment.set(name, Some(value));
+    }
+    context.env.set_process_environment(environment);
+    context.output.send(Value::Empty())
+}
+
+#[signature(
+unset,
+can_block = false,
+output = Known(ValueType::Empty),
+short = "Remove environment variables",
+long = "Like env:set, this applies to the current scope and the commands it calls.",
+example = "env:unset \"http_proxy\" \"https_proxy\"")]
+struct Unset {
+    #[unnamed()]
+    #[description("the names of the environment variables to remove.")]
+    names: Vec<String>,
+}
+
+fn unset(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Unset = Unset::parse(context.arguments, &context.printer)?;
+    for name in &cfg.names {
+        check_name(name)?;
+    }
+    let mut environment = environment(&context.env);
+    for name in &cfg.names {
+        environment.set(name, None);
+    }
+    context.env.set_process_environment(environment);
+    context.output.send(Value::Empty())
+}
+
+#[signature(
+list,
+can_block = true,
+output = Known(ValueType::TableStream(LIST_OUTPUT_TYPE.clone())),
+short = "List all environment variables",
+long = "The variables are sorted by name.",
+example = "env:list | where {name =~ re\"^LC_.*\"}")]
+struct List {}
+
+fn list(context: ExecutionContext) -> CrushResult<()> {
+    let output = context.output.initialize(LIST_OUTPUT_TYPE.clone())?;
+    for (name, value) in environment(&context.env).list() {
+        output.send(Row::new(vec![Value::String(name), Value::String(value)]))?;
+    }
+    Ok(())
+}
+
+pub fn declare(root: &Scope) -> CrushResult<()> {
+    root.create_lazy_namespace(
+        "env",
+        Box::new(move |env| {
+            Get::declare(env)?;
+            Set::declare(env)?;
+            Unset::declare(env)?;
+            List::declare(env)?;
+            Ok(())
+        }))?;
+    Ok(())
+}
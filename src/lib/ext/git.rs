@@ -0,0 +1,444 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Local, TimeZone};
+use git2::{Blame as GitBlame, BlameOptions, BranchType, Commit, DiffOptions, Repository, Sort, StatusOptions};
+use lazy_static::lazy_static;
+
+use crate::lang::argument::ArgumentHandler;
+use crate::lang::command::OutputType::{Known, Unknown};
+use crate::lang::errors::{CrushResult, argument_error, error, mandate, to_crush_error};
+use crate::lang::execution_context::ExecutionContext;
+use crate::lang::scope::Scope;
+use crate::lang::stream::OutputStream;
+use crate::lang::table::{ColumnType, Row};
+use crate::lang::value::{Value, ValueType};
+use signature::signature;
+
+lazy_static! {
+    static ref LOG_OUTPUT_TYPE: Vec<ColumnType> = vec![
+        ColumnType::new("hash", ValueType::String),
+        ColumnType::new("author", ValueType::String),
+        ColumnType::new("email", ValueType::String),
+        ColumnType::new("time", ValueType::Time),
+        ColumnType::new("message", ValueType::String),
+        ColumnType::new("files", ValueType::Integer),
+        ColumnType::new("insertions", ValueType::Integer),
+        ColumnType::new("deletions", ValueType::Integer),
+    ];
+    static ref STATUS_OUTPUT_TYPE: Vec<ColumnType> = vec![
+        ColumnType::new("file", ValueType::File),
+        ColumnType::new("staged", ValueType::String),
+        ColumnType::new("unstaged", ValueType::String),
+        ColumnType::new("from", ValueType::Any),
+    ];
+    static ref BRANCHES_OUTPUT_TYPE: Vec<ColumnType> = vec![
+        ColumnType::new("name", ValueType::String),
+        ColumnType::new("current", ValueType::Bool),
+        ColumnType::new("commit", ValueType::String),
+        ColumnType::new("time", ValueType::Time),
+        ColumnType::new("upstream", ValueType::Any),
+    ];
+    static ref BLAME_OUTPUT_TYPE: Vec<ColumnType> = vec![
+        ColumnType::new("line", ValueType::Integer),
+        ColumnType::new("hash", ValueType::String),
+        ColumnType::new("author", ValueType::String),
+        ColumnType::new("time", ValueType::Time),
+        ColumnType::new("text", ValueType::String),
+    ];
+}
+
+/** Opens the repository containing the given directory, or the current directory. */
+fn open(repository: &Option<PathBuf>) -> CrushResult<Repository> {
+    let directory = repository.clone().unwrap_or_else(|| PathBuf::from("."));
+    match Repository::discover(&directory) {
+        Ok(repository) => Ok(repository),
+        Err(e) => error(format!("{} is not in a git repository: {}", directory.to_string_lossy(), e.message()).as_str()),
+    }
+}
+
+/**
+The path of a file relative to the top of the working tree of a repository, which is how git
+names files. The file itself doesn't have to exist, e.g. when listing the history of a deleted file.
+*/
+fn in_workdir(repository: &Repository, file: &Path) -> CrushResult<PathBuf> {
+    let workdir = to_crush_error(mandate(repository.workdir(), "The repository has no working tree")?.canonicalize())?;
+    let file = if file.is_relative() { to_crush_error(std::env::current_dir())?.join(file) } else { file.to_path_buf() };
+    let file = match (file.canonicalize(), file.parent(), file.file_name()) {
+        (Ok(file), _, _) => file,
+        (Err(_), Some(parent), Some(name)) => to_crush_error(parent.canonicalize())?.join(name),
+        (Err(e), _, _) => return to_crush_error(Err(e)),
+    };
+    match file.strip_prefix(&workdir) {
+        Ok(relative) => Ok(relative.to_path_buf()),
+        Err(_) => argument_error(format!("{} is not in the repository", file.to_string_lossy()).as_str()),
+    }
+}
+
+fn time(seconds: i64) -> Value {
+    Value::Time(Local.timestamp(seconds, 0))
+}
+
+/**
+The number of files, inserted lines and deleted lines that a commit changed compared to its
+parent. Like git log --numstat, nothing is counted for merges.
+*/
+fn changes(repository: &Repository, commit: &Commit, file: &Option<PathBuf>) -> CrushResult<(usize, usize, usize)> {
+    if commit.parent_count() > 1 {
+        return Ok((0, 0, 0));
+    }
+    let parent = match commit.parent(0) {
+        Ok(parent) => Some(to_crush_error(parent.tree())?),
+        Err(_) => None,
+    };
+    let mut options = DiffOptions::new();
+    if let Some(file) = file {
+        options.pathspec(file);
+    }
+    let diff = to_crush_error(repository.diff_tree_to_tree(parent.as_ref(), Some(&to_crush_error(commit.tree())?), Some(&mut options)))?;
+    let stats = to_crush_error(diff.stats())?;
+    Ok((stats.files_changed(), stats.insertions(), stats.deletions()))
+}
+
+#[signature(
+log,
+can_block = true,
+output = Known(ValueType::TableStream(LOG_OUTPUT_TYPE.clone())),
+short = "Return a table stream of the commits of a git repository, newest first",
+long = "Each row contains the hash of a commit, the name and email address of its author, when it",
+long = "was authored, its full message, and how many files, inserted lines and deleted lines it",
+long = "changed.",
+example = "git:log | group ^author | select ^author commits={group | count} | sort ^commits")]
+pub struct Log {
+    #[description("the commit or range of commits to list, like main or v1.0..HEAD. The default is HEAD.")]
+    revision: Option<String>,
+    #[description("the maximum number of commits to list.")]
+    count: Option<i128>,
+    #[description("only list commits that changed this file or directory.")]
+    file: Option<PathBuf>,
+    #[description("the repository. The default is the current directory.")]
+    repository: Option<PathBuf>,
+}
+
+fn log(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Log = Log::parse(context.arguments, &context.printer)?;
+    let count = match cfg.count {
+        Some(count) if count < 0 => return argument_error("The count must not be negative"),
+        Some(count) => Some(count as usize),
+        None => None,
+    };
+    let repository = open(&cfg.repository)?;
+    // Files are relative to the repository if one is given, like with git -C
+    let file = match &cfg.file {
+        Some(file) => {
+            let file = match &cfg.repository {
+                Some(directory) => directory.join(file),
+                None => file.clone(),
+            };
+            Some(in_workdir(&repository, &file)?).filter(|f| !f.as_os_str().is_empty())
+        }
+        None => None,
+    };
+    let mut walk = to_crush_error(repository.revwalk())?;
+    to_crush_error(walk.set_sorting(Sort::TIME))?;
+    let revision = cfg.revision.unwrap_or_else(|| "HEAD".to_string());
+    if revision.contains("..") {
+        to_crush_error(walk.push_range(&revision))?;
+    } else {
+        let commit = to_crush_error(repository.revparse_single(&revision).and_then(|o| o.peel_to_commit()))?;
+        to_crush_error(walk.push(commit.id()))?;
+    }
+
+    let output = context.output.initialize(LOG_OUTPUT_TYPE.clone())?;
+    let mut listed = 0;
+    for id in walk {
+        if count.map(|c| listed >= c).unwrap_or(false) {
+            break;
+        }
+        let commit = to_crush_error(id.and_then(|id| repository.find_commit(id)))?;
+        let (files, insertions, deletions) = changes(&repository, &commit, &file)?;
+        // Like git log -- file, only list the commits that changed the file
+        if file.is_some() && files == 0 {
+            continue;
+        }
+        let author = commit.author();
+        output.send(Row::new(vec![
+            Value::string(&commit.id().to_string()),
+            Value::string(author.name().unwrap_or("")),
+            Value::string(author.email().unwrap_or("")),
+            time(author.when().seconds()),
+            Value::string(commit.message().unwrap_or("").trim()),
+            Value::Integer(files as i128),
+            Value::Integer(insertions as i128),
+            Value::Integer(deletions as i128),
+        ]))?;
+        listed += 1;
+    }
+    Ok(())
+}
+
+/** How a file was changed in the index and in the working tree, with the same names as git status uses. */
+fn file_changes(status: git2::Status) -> (&'static str, &'static str) {
+    if status.is_conflicted() {
+        return ("unmerged", "unmerged");
+    }
+    if status.is_wt_new() {
+        return ("untracked", "untracked");
+    }
+    if status.is_ignored() {
+        return ("ignored", "ignored");
+    }
+    let staged = if status.is_index_new() {
+        "added"
+    } else if status.is_index_modified() {
+        "modified"
+    } else if status.is_index_deleted() {
+        "deleted"
+    } else if status.is_index_renamed() {
+        "renamed"
+    } else if status.is_index_typechange() {
+        "type changed"
+    } else {
+        ""
+    };
+    let unstaged = if status.is_wt_modified() {
+        "modified"
+    } else if status.is_wt_deleted() {
+        "deleted"
+    } else if status.is_wt_renamed() {
+        "renamed"
+    } else if status.is_wt_typechange() {
+        "type changed"
+    } else {
+        ""
+    };
+    (staged, unstaged)
+}
+
+#[signature(
+status,
+can_block = true,
+output = Known(ValueType::TableStream(STATUS_OUTPUT_TYPE.clone())),
+short = "Return a table stream of the changed files in a git repository",
+long = "Each row contains a file relative to the top of the repository, how it was changed in the",
+long = "index and how it was changed in the working tree. A change is one of modified, type changed,",
+long = "added, deleted, renamed, unmerged, untracked or ignored, or empty if there is none.",
+long = "Renamed files also have the file they were made from.",
+example = "git:status | where {unstaged == \"modified\"}")]
+pub struct Status {
+    #[default(false)]
+    #[description("also list ignored files.")]
+    ignored: bool,
+    #[description("the repository. The default is the current directory.")]
+    repository: Option<PathBuf>,
+}
+
+fn status(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Status = Status::parse(context.arguments, &context.printer)?;
+    let repository = open(&cfg.repository)?;
+    let mut options = StatusOptions::new();
+    options.include_untracked(true)
+        .recurse_untracked_dirs(true)
+        .include_ignored(cfg.ignored)
+        .renames_head_to_index(true);
+    let statuses = to_crush_error(repository.statuses(Some(&mut options)))?;
+    let output = context.output.initialize(STATUS_OUTPUT_TYPE.clone())?;
+    for entry in statuses.iter() {
+        let (staged, unstaged) = file_changes(entry.status());
+        let renamed = entry.head_to_index().filter(|_| entry.status().is_index_renamed());
+        let file = match &renamed {
+            Some(delta) => delta.new_file().path().map(|p| p.to_path_buf()),
+            None => entry.path().map(PathBuf::from),
+        };
+        let from = renamed.and_then(|delta| delta.old_file().path().map(|p| Value::File(p.to_path_buf())));
+        output.send(Row::new(vec![
+            Value::File(file.unwrap_or_default()),
+            Value::string(staged),
+            Value::string(unstaged),
+            from.unwrap_or(Value::Empty()),
+        ]))?;
+    }
+    Ok(())
+}
+
+#[signature(
+branches,
+can_block = true,
+output = Known(ValueType::TableStream(BRANCHES_OUTPUT_TYPE.clone())),
+short = "Return a table stream of the branches of a git repository",
+long = "Each row contains the name of a branch, whether it is checked out, the hash and time of its",
+long = "latest commit and the branch it tracks, if any.",
+example = "git:branches | sort ^time")]
+pub struct Branches {
+    #[default(false)]
+    #[description("also list remote tracking branches, like origin/main.")]
+    remote: bool,
+    #[description("the repository. The default is the current directory.")]
+    repository: Option<PathBuf>,
+}
+
+fn branches(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Branches = Branches::parse(context.arguments, &context.printer)?;
+    let repository = open(&cfg.repository)?;
+    let mut types = vec![BranchType::Local];
+    if cfg.remote {
+        types.push(BranchType::Remote);
+    }
+    let output = context.output.initialize(BRANCHES_OUTPUT_TYPE.clone())?;
+    for branch_type in types {
+        let mut rows = Vec::new();
+        for branch in to_crush_error(repository.branches(Some(branch_type)))? {
+            let (branch, _) = to_crush_error(branch)?;
+            let name = match to_crush_error(branch.name())? {
+                Some(name) => name.to_string(),
+                None => continue,
+            };
+            // Remotes have a symbolic HEAD pointing to their default branch, which is not a branch
+            if name.ends_with("/HEAD") {
+                continue;
+            }
+            let commit = to_crush_error(branch.get().peel_to_commit())?;
+            let upstream = branch.upstream().ok()
+                .and_then(|upstream| upstream.name().ok().flatten().map(|n| n.to_string()));
+            rows.push((name.clone(), Row::new(vec![
+                Value::String(name),
+                Value::Bool(branch.is_head()),
+                Value::string(&commit.id().to_string()),
+                time(commit.time().seconds()),
+                upstream.map(Value::String).unwrap_or(Value::Empty()),
+            ])));
+        }
+        rows.sort_by(|a, b| a.0.cmp(&b.0));
+        for (_, row) in rows {
+            output.send(row)?;
+        }
+    }
+    Ok(())
+}
+
+#[signature(
+branch,
+can_block = true,
+output = Unknown,
+short = "Return the name of the checked out branch of a git repository",
+long = "If the repository is in detached HEAD state, HEAD is returned. If the directory is not in a",
+long = "git repository, nothing is returned, which makes this useful in prompts.",
+example = "git:branch")]
+pub struct Branch {
+    #[description("the repository. The default is the current directory.")]
+    repository: Option<PathBuf>,
+}
+
+fn branch(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Branch = Branch::parse(context.arguments, &context.printer)?;
+    let name = open(&cfg.repository).ok().and_then(|repository| {
+        if repository.head_detached().unwrap_or(false) {
+            return Some("HEAD".to_string());
+        }
+        repository.head().ok().and_then(|head| head.shorthand().map(|s| s.to_string()))
+    });
+    context.output.send(name.map(Value::String).unwrap_or(Value::Empty()))
+}
+
+/**
+Output a row for every line of a file. Lines without a commit have not been committed yet, and
+get the time the file was last modified, like in the output of git blame.
+*/
+fn send_blame(blame: &GitBlame, contents: &[u8], modified: i64, output: &OutputStream) -> CrushResult<()> {
+    for (idx, text) in String::from_utf8_lossy(contents).lines().enumerate() {
+        let (hash, author, time) = match blame.get_line(idx + 1) {
+            Some(hunk) if !hunk.final_commit_id().is_zero() => {
+                let signature = hunk.final_signature();
+                (hunk.final_commit_id().to_string(), signature.name().unwrap_or("").to_string(), signature.when().seconds())
+            }
+            _ => ("0".repeat(40), "Not Committed Yet".to_string(), modified),
+        };
+        output.send(Row::new(vec![
+            Value::Integer(idx as i128 + 1),
+            Value::String(hash),
+            Value::String(author),
+            Value::Time(Local.timestamp(time, 0)),
+            Value::string(text),
+        ]))?;
+    }
+    Ok(())
+}
+
+#[signature(
+blame,
+can_block = true,
+output = Known(ValueType::TableStream(BLAME_OUTPUT_TYPE.clone())),
+short = "Return a table stream of the lines of a file with the commit each was last changed in",
+long = "Each row contains the line number, the hash of the commit, its author and the time it was",
+long = "authored, and the text of the line.",
+example = "git:blame ./src/main.rs | group ^author | select ^author lines={group | count}")]
+pub struct Blame {
+    #[description("the file to blame.")]
+    file: PathBuf,
+    #[description("blame the file as of this commit. The default is the working tree.")]
+    revision: Option<String>,
+    #[description("the repository. The default is the directory of the file.")]
+    repository: Option<PathBuf>,
+}
+
+fn blame(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Blame = Blame::parse(context.arguments, &context.printer)?;
+    let directory = match &cfg.repository {
+        Some(repository) => Some(repository.clone()),
+        None => cfg.file.parent().filter(|p| !p.as_os_str().is_empty()).map(|p| p.to_path_buf()),
+    };
+    let repository = open(&directory)?;
+    let file = in_workdir(&repository, &cfg.file)?;
+    let output = context.output.initialize(BLAME_OUTPUT_TYPE.clone())?;
+    match &cfg.revision {
+        Some(revision) => {
+            let commit = to_crush_error(repository.revparse_single(revision).and_then(|o| o.peel_to_commit()))?;
+            let entry = to_crush_error(to_crush_error(commit.tree())?.get_path(&file))?;
+            let blob = to_crush_error(entry.to_object(&repository).and_then(|o| o.peel_to_blob()))?;
+            let mut options = BlameOptions::new();
+            options.newest_commit(commit.id());
+            let blame = to_crush_error(repository.blame_file(&file, Some(&mut options)))?;
+            send_blame(&blame, blob.content(), 0, &output)
+        }
+        None => {
+            let path = mandate(repository.workdir(), "The repository has no working tree")?.join(&file);
+            let contents = to_crush_error(fs::read(&path))?;
+            let modified: DateTime<Local> = DateTime::from(to_crush_error(to_crush_error(fs::metadata(&path))?.modified())?);
+            // Blame the committed file, and then the changes made to it in the working tree
+            let committed = to_crush_error(repository.blame_file(&file, None))?;
+            let blame = to_crush_error(committed.blame_buffer(&contents))?;
+            send_blame(&blame, &contents, modified.timestamp(), &output)
+        }
+    }
+}
+
+pub fn declare(root: &Scope) -> CrushResult<()> {
+    root.create_lazy_namespace(
+        "git",
+        Box::new(move |env| {
+            Log::declare(env)?;
+            Status::declare(env)?;
+            Branches::declare(env)?;
+            Branch::declare(env)?;
+            Blame::declare(env)?;
+            Ok(())
+        }))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use git2::Status as FileStatus;
+
+    #[test]
+    fn changes_of_files() {
+        assert_eq!(file_changes(FileStatus::INDEX_MODIFIED), ("modified", ""));
+        assert_eq!(file_changes(FileStatus::WT_DELETED), ("", "deleted"));
+        assert_eq!(file_changes(FileStatus::INDEX_NEW | FileStatus::WT_MODIFIED), ("added", "modified"));
+        assert_eq!(file_changes(FileStatus::INDEX_RENAMED), ("renamed", ""));
+        assert_eq!(file_changes(FileStatus::WT_NEW), ("untracked", "untracked"));
+        assert_eq!(file_changes(FileStatus::IGNORED), ("ignored", "ignored"));
+        assert_eq!(file_changes(FileStatus::CONFLICTED | FileStatus::INDEX_MODIFIED), ("unmerged", "unmerged"));
+    }
+}
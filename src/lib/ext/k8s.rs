@@ -0,0 +1,359 @@
+use std::io::{BufRead, BufReader};
+
+use chrono::{DateTime, Local};
+use lazy_static::lazy_static;
+
+use crate::lang::argument::ArgumentHandler;
+use crate::lang::command::OutputType::{Known, Unknown};
+use crate::lang::errors::{CrushResult, argument_error, to_crush_error};
+use crate::lang::execution_context::ExecutionContext;
+use crate::lang::list::List;
+use crate::lang::scope::Scope;
+use crate::lang::table::{ColumnType, Row};
+use crate::lang::value::{Value, ValueType};
+use crate::lib::io::json::from_json;
+use crate::util::kube::{collection_path, Config};
+use signature::signature;
+
+lazy_static! {
+    static ref PODS_OUTPUT_TYPE: Vec<ColumnType> = vec![
+        ColumnType::new("namespace", ValueType::String),
+        ColumnType::new("name", ValueType::String),
+        ColumnType::new("status", ValueType::String),
+        ColumnType::new("ready", ValueType::String),
+        ColumnType::new("restarts", ValueType::Integer),
+        ColumnType::new("node", ValueType::Any),
+        ColumnType::new("ip", ValueType::Any),
+        ColumnType::new("created", ValueType::Any),
+    ];
+    static ref NODES_OUTPUT_TYPE: Vec<ColumnType> = vec![
+        ColumnType::new("name", ValueType::String),
+        ColumnType::new("status", ValueType::String),
+        ColumnType::new("roles", ValueType::List(Box::new(ValueType::String))),
+        ColumnType::new("version", ValueType::String),
+        ColumnType::new("internal_ip", ValueType::Any),
+        ColumnType::new("cpu", ValueType::String),
+        ColumnType::new("memory", ValueType::String),
+        ColumnType::new("created", ValueType::Any),
+    ];
+    static ref LOGS_OUTPUT_TYPE: Vec<ColumnType> = vec![
+        ColumnType::new("time", ValueType::Any),
+        ColumnType::new("line", ValueType::String),
+    ];
+    static ref GET_OUTPUT_TYPE: Vec<ColumnType> = vec![
+        ColumnType::new("namespace", ValueType::Any),
+        ColumnType::new("name", ValueType::String),
+        ColumnType::new("created", ValueType::Any),
+        ColumnType::new("object", ValueType::Struct),
+    ];
+}
+
+/** Objects are listed this many at a time, so that large clusters don't need one huge response. */
+const PAGE_SIZE: usize = 500;
+
+fn string(json: &serde_json::Value) -> String {
+    json.as_str().unwrap_or("").to_string()
+}
+
+fn optional_string(json: &serde_json::Value) -> Value {
+    json.as_str().map(Value::string).unwrap_or(Value::Empty())
+}
+
+fn time(json: &serde_json::Value) -> Value {
+    json.as_str()
+        .and_then(|t| DateTime::parse_from_rfc3339(t).ok())
+        .map(|t| Value::Time(t.with_timezone(&Local)))
+        .unwrap_or(Value::Empty())
+}
+
+/** The namespace to list objects in, or None for all namespaces. */
+fn namespace(config: &Config, namespace: Option<String>, all_namespaces: bool) -> CrushResult<Option<String>> {
+    match (namespace, all_namespaces) {
+        (Some(_), true) => argument_error("Expected either a namespace or all_namespaces, not both"),
+        (None, true) => Ok(None),
+        (Some(namespace), false) => Ok(Some(namespace)),
+        (None, false) => Ok(Some(config.namespace.clone())),
+    }
+}
+
+/** Calls the callback with every object in a collection, following continue tokens between pages. */
+fn list(config: &Config, path: &str, selector: &Option<String>, mut callback: impl FnMut(&serde_json::Value) -> CrushResult<()>) -> CrushResult<()> {
+    let mut next: Option<String> = None;
+    loop {
+        let mut query = vec![("limit", PAGE_SIZE.to_string())];
+        if let Some(selector) = selector {
+            query.push(("labelSelector", selector.clone()));
+        }
+        if let Some(next) = next {
+            query.push(("continue", next));
+        }
+        let page = config.get(path, &query)?;
+        for item in page["items"].as_array().unwrap_or(&vec![]) {
+            callback(item)?;
+        }
+        match page["metadata"]["continue"].as_str() {
+            Some(token) if !token.is_empty() => next = Some(token.to_string()),
+            _ => return Ok(()),
+        }
+    }
+}
+
+/** The status kubectl shows, which is the reason a container is waiting or terminated, if any, or else the phase. */
+fn pod_status(pod: &serde_json::Value) -> String {
+    if pod["metadata"]["deletionTimestamp"].is_string() {
+        return "Terminating".to_string();
+    }
+    for container in pod["status"]["containerStatuses"].as_array().unwrap_or(&vec![]) {
+        for state in &["waiting", "terminated"] {
+            if let Some(reason) = container["state"][state]["reason"].as_str() {
+                if *state == "waiting" || reason != "Completed" {
+                    return reason.to_string();
+                }
+            }
+        }
+    }
+    string(&pod["status"]["phase"])
+}
+
+#[signature(
+pods,
+can_block = true,
+output = Known(ValueType::TableStream(PODS_OUTPUT_TYPE.clone())),
+short = "Return a table stream of the pods of a Kubernetes cluster",
+long = "Each row contains the namespace and name of a pod, its status as kubectl shows it, how many",
+long = "of its containers are ready, how many times they have been restarted, the node it runs on,",
+long = "its IP address and when it was created.",
+long = "",
+long = "The cluster is configured the same way as for kubectl, from the kubeconfig in KUBECONFIG or",
+long = "~/.kube/config, or from the service account when running in a cluster.",
+example = "k8s:pods all_namespaces=true | where {status != \"Running\"} | group ^namespace")]
+pub struct Pods {
+    #[description("the namespace of the pods. The default is the namespace of the context.")]
+    namespace: Option<String>,
+    #[default(false)]
+    #[description("list the pods of all namespaces.")]
+    all_namespaces: bool,
+    #[description("only list pods with matching labels, e.g. app=web.")]
+    selector: Option<String>,
+    #[description("the kubeconfig context to use. The default is the current one.")]
+    context: Option<String>,
+}
+
+fn pods(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Pods = Pods::parse(context.arguments, &context.printer)?;
+    let config = Config::load(cfg.context.as_deref())?;
+    let namespace = namespace(&config, cfg.namespace, cfg.all_namespaces)?;
+    let output = context.output.initialize(PODS_OUTPUT_TYPE.clone())?;
+    list(&config, &collection_path("pods", namespace.as_deref())?, &cfg.selector, |pod| {
+        let containers = pod["status"]["containerStatuses"].as_array().cloned().unwrap_or_default();
+        let ready = containers.iter().filter(|c| c["ready"].as_bool() == Some(true)).count();
+        let total = pod["spec"]["containers"].as_array().map(|c| c.len()).unwrap_or(containers.len());
+        let restarts = containers.iter().map(|c| c["restartCount"].as_i64().unwrap_or(0)).sum::<i64>();
+        output.send(Row::new(vec![
+            Value::String(string(&pod["metadata"]["namespace"])),
+            Value::String(string(&pod["metadata"]["name"])),
+            Value::String(pod_status(pod)),
+            Value::String(format!("{}/{}", ready, total)),
+            Value::Integer(restarts as i128),
+            optional_string(&pod["spec"]["nodeName"]),
+            optional_string(&pod["status"]["podIP"]),
+            time(&pod["metadata"]["creationTimestamp"]),
+        ]))
+    })
+}
+
+fn node_status(node: &serde_json::Value) -> &'static str {
+    let ready = node["status"]["conditions"].as_array()
+        .and_then(|conditions| conditions.iter().find(|c| c["type"].as_str() == Some("Ready")))
+        .and_then(|c| c["status"].as_str());
+    match ready {
+        Some("True") => "Ready",
+        Some("False") => "NotReady",
+        _ => "Unknown",
+    }
+}
+
+#[signature(
+nodes,
+can_block = true,
+output = Known(ValueType::TableStream(NODES_OUTPUT_TYPE.clone())),
+short = "Return a table stream of the nodes of a Kubernetes cluster",
+long = "Each row contains the name of a node, whether it is Ready, NotReady or Unknown, its roles, the",
+long = "version of its kubelet, its internal IP address, its CPU and memory capacity, and when it was",
+long = "created.",
+example = "k8s:nodes | where {status != \"Ready\"}")]
+pub struct Nodes {
+    #[description("only list nodes with matching labels.")]
+    selector: Option<String>,
+    #[description("the kubeconfig context to use. The default is the current one.")]
+    context: Option<String>,
+}
+
+fn nodes(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Nodes = Nodes::parse(context.arguments, &context.printer)?;
+    let config = Config::load(cfg.context.as_deref())?;
+    let output = context.output.initialize(NODES_OUTPUT_TYPE.clone())?;
+    list(&config, &collection_path("nodes", None)?, &cfg.selector, |node| {
+        let roles = node["metadata"]["labels"].as_object()
+            .map(|labels| labels.keys()
+                .filter_map(|label| label.strip_prefix("node-role.kubernetes.io/"))
+                .map(Value::string)
+                .collect())
+            .unwrap_or_default();
+        let internal_ip = node["status"]["addresses"].as_array()
+            .and_then(|addresses| addresses.iter().find(|a| a["type"].as_str() == Some("InternalIP")))
+            .map(|a| optional_string(&a["address"]))
+            .unwrap_or(Value::Empty());
+        output.send(Row::new(vec![
+            Value::String(string(&node["metadata"]["name"])),
+            Value::string(node_status(node)),
+            Value::List(List::new(ValueType::String, roles)),
+            Value::String(string(&node["status"]["nodeInfo"]["kubeletVersion"])),
+            internal_ip,
+            Value::String(string(&node["status"]["capacity"]["cpu"])),
+            Value::String(string(&node["status"]["capacity"]["memory"])),
+            time(&node["metadata"]["creationTimestamp"]),
+        ]))
+    })
+}
+
+#[signature(
+logs,
+can_block = true,
+output = Known(ValueType::TableStream(LOGS_OUTPUT_TYPE.clone())),
+short = "Return a table stream of the log lines of a Kubernetes pod",
+long = "Each row contains the time the line was logged and the line itself. Pods with more than one",
+long = "container need the container to be given.",
+long = "",
+long = "If follow is true, the stream doesn't end until the container stops, so new lines can be",
+long = "processed as they are logged.",
+example = "k8s:logs \"web-7d4b9c-x2x4z\" tail=1000 | where {line =~ re\"(?i)error\"}")]
+pub struct Logs {
+    #[description("the name of the pod.")]
+    pod: String,
+    #[description("the container in the pod.")]
+    container: Option<String>,
+    #[description("the namespace of the pod. The default is the namespace of the context.")]
+    namespace: Option<String>,
+    #[description("only return this many of the last lines.")]
+    tail: Option<i128>,
+    #[default(false)]
+    #[description("keep returning new lines as they are logged.")]
+    follow: bool,
+    #[default(false)]
+    #[description("return the logs of the previous instance of the container, e.g. after a crash.")]
+    previous: bool,
+    #[description("the kubeconfig context to use. The default is the current one.")]
+    context: Option<String>,
+}
+
+fn logs(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Logs = Logs::parse(context.arguments, &context.printer)?;
+    let config = Config::load(cfg.context.as_deref())?;
+    let namespace = cfg.namespace.unwrap_or_else(|| config.namespace.clone());
+    let mut query = vec![
+        ("timestamps", "true".to_string()),
+        ("follow", cfg.follow.to_string()),
+        ("previous", cfg.previous.to_string()),
+    ];
+    if let Some(container) = cfg.container {
+        query.push(("container", container));
+    }
+    match cfg.tail {
+        Some(tail) if tail < 0 => return argument_error("Expected tail to be positive"),
+        Some(tail) => query.push(("tailLines", tail.to_string())),
+        None => {}
+    }
+    let source = config.stream(&format!("{}/{}/log", collection_path("pods", Some(&namespace))?, cfg.pod), &query)?;
+    let output = context.output.initialize(LOGS_OUTPUT_TYPE.clone())?;
+    for line in BufReader::new(source).lines() {
+        let line = to_crush_error(line)?;
+        let (time, line) = match line.split_once(' ') {
+            Some((t, rest)) => match DateTime::parse_from_rfc3339(t) {
+                Ok(t) => (Value::Time(t.with_timezone(&Local)), rest.to_string()),
+                Err(_) => (Value::Empty(), line),
+            },
+            None => (Value::Empty(), line),
+        };
+        output.send(Row::new(vec![time, Value::String(line)]))?;
+    }
+    Ok(())
+}
+
+#[signature(
+get,
+can_block = true,
+output = Unknown,
+short = "Return Kubernetes objects of any kind",
+long = "If a name is given, the object is returned as a struct, with the same members as the object",
+long = "has in the API. Otherwise a table stream is returned, with the namespace, name and creation",
+long = "time of each object, and the whole object as a struct.",
+long = "",
+long = "The kind is the name of a built in resource, like deployments, its singular name or its short",
+long = "name, like deploy. Other resources, like custom resources, are given as group/version/resource,",
+long = "e.g. cert-manager.io/v1/certificates.",
+example = "k8s:get kind=\"deploy\" all_namespaces=true | where {object:status:availableReplicas != object:spec:replicas}")]
+pub struct Get {
+    #[description("the kind of objects to get.")]
+    kind: String,
+    #[description("the name of a single object to get.")]
+    name: Option<String>,
+    #[description("the namespace of the objects. The default is the namespace of the context.")]
+    namespace: Option<String>,
+    #[default(false)]
+    #[description("get the objects of all namespaces.")]
+    all_namespaces: bool,
+    #[description("only get objects with matching labels.")]
+    selector: Option<String>,
+    #[description("the kubeconfig context to use. The default is the current one.")]
+    context: Option<String>,
+}
+
+fn get(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Get = Get::parse(context.arguments, &context.printer)?;
+    let config = Config::load(cfg.context.as_deref())?;
+    let namespace = namespace(&config, cfg.namespace, cfg.all_namespaces)?;
+    let path = collection_path(&cfg.kind, namespace.as_deref())?;
+    if let Some(name) = cfg.name {
+        return context.output.send(from_json(&config.get(&format!("{}/{}", path, name), &[])?)?);
+    }
+    let output = context.output.initialize(GET_OUTPUT_TYPE.clone())?;
+    list(&config, &path, &cfg.selector, |object| {
+        output.send(Row::new(vec![
+            optional_string(&object["metadata"]["namespace"]),
+            Value::String(string(&object["metadata"]["name"])),
+            time(&object["metadata"]["creationTimestamp"]),
+            from_json(object)?,
+        ]))
+    })
+}
+
+pub fn declare(root: &Scope) -> CrushResult<()> {
+    root.create_lazy_namespace(
+        "k8s",
+        Box::new(move |env| {
+            Pods::declare(env)?;
+            Nodes::declare(env)?;
+            Logs::declare(env)?;
+            Get::declare(env)?;
+            Ok(())
+        }))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn pod_status_is_like_kubectl() {
+        assert_eq!(pod_status(&json!({"status": {"phase": "Running", "containerStatuses": [{"state": {"running": {}}}]}})), "Running");
+        assert_eq!(
+            pod_status(&json!({"status": {"phase": "Running", "containerStatuses": [{"state": {"waiting": {"reason": "CrashLoopBackOff"}}}]}})),
+            "CrashLoopBackOff");
+        assert_eq!(
+            pod_status(&json!({"status": {"phase": "Succeeded", "containerStatuses": [{"state": {"terminated": {"reason": "Completed"}}}]}})),
+            "Succeeded");
+    }
+}
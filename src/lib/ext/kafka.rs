@@ -0,0 +1,359 @@
+use std::collections::{BTreeMap, HashMap};
+use std::time::Duration;
+
+use chrono::{Local, TimeZone, Utc};
+use lazy_static::lazy_static;
+
+use crate::lang::argument::ArgumentHandler;
+use crate::lang::command::OutputType::Known;
+use crate::lang::errors::{CrushResult, argument_error, error};
+use crate::lang::execution_context::ExecutionContext;
+use crate::lang::scope::Scope;
+use crate::lang::stream::{CrushStream, RecvTimeoutError};
+use crate::lang::table::{ColumnType, Row};
+use crate::lang::value::{Value, ValueType};
+use crate::util::kafka::{self, Client, KeyValue, Partition, Record};
+use signature::signature;
+
+lazy_static! {
+    static ref CONSUME_OUTPUT_TYPE: Vec<ColumnType> = vec![
+        ColumnType::new("partition", ValueType::Integer),
+        ColumnType::new("offset", ValueType::Integer),
+        ColumnType::new("key", ValueType::Any),
+        ColumnType::new("value", ValueType::Any),
+        ColumnType::new("timestamp", ValueType::Time),
+    ];
+}
+
+/** The most records that are sent to a partition in one produce request. */
+const MAX_BATCH: usize = 1000;
+
+fn client(brokers: Option<String>) -> Client {
+    let brokers = brokers
+        .or_else(|| std::env::var("KAFKA_BROKERS").ok())
+        .unwrap_or_else(|| "localhost:9092".to_string());
+    Client::new(&brokers)
+}
+
+/** Keys and values are strings if they are valid UTF-8, and binary otherwise. */
+fn bytes_value(data: Option<Vec<u8>>) -> Value {
+    match data {
+        None => Value::Empty(),
+        Some(data) => match String::from_utf8(data) {
+            Ok(s) => Value::String(s),
+            Err(e) => Value::Binary(e.into_bytes()),
+        },
+    }
+}
+
+fn record_row(partition: i32, record: Record) -> Row {
+    Row::new(vec![
+        Value::Integer(partition as i128),
+        Value::Integer(record.offset as i128),
+        bytes_value(record.key),
+        bytes_value(record.value),
+        Local.timestamp_opt(record.timestamp.div_euclid(1000), (record.timestamp.rem_euclid(1000) * 1_000_000) as u32)
+            .single()
+            .map(Value::Time)
+            .unwrap_or(Value::Empty()),
+    ])
+}
+
+/** The partitions of the topic, grouped by the broker that leads them. */
+fn by_leader(partitions: &[Partition]) -> BTreeMap<i32, Vec<i32>> {
+    let mut res: BTreeMap<i32, Vec<i32>> = BTreeMap::new();
+    for partition in partitions {
+        res.entry(partition.leader).or_default().push(partition.id);
+    }
+    res
+}
+
+#[signature(
+consume,
+can_block = true,
+output = Known(ValueType::TableStream(CONSUME_OUTPUT_TYPE.clone())),
+short = "Return a table stream of the records of a Kafka topic",
+long = "Reads the records of all partitions of the topic, and keeps waiting for new ones until count",
+long = "records have been read, or forever if no count is given. Keys and values are strings if they",
+long = "are valid UTF-8, and binary otherwise.",
+long = "",
+long = "Without a group, reading starts at the end of the topic, or at the start if from_beginning is",
+long = "set. With a group, reading starts at the offsets committed for the group, and the offsets of",
+long = "the records read are committed as reading goes on. The group is only used to store offsets.",
+long = "This command does not join the group, so partitions are not shared with other members, and",
+long = "committing fails while the group has active members.",
+long = "",
+long = "The brokers default to the KAFKA_BROKERS environment variable, or localhost:9092.",
+example = "kafka:consume \"orders\" group=\"crush\" | where {key == \"42\"}")]
+pub struct Consume {
+    #[description("the topic to read.")]
+    topic: String,
+    #[description("the consumer group to fetch and commit offsets for.")]
+    group: Option<String>,
+    #[description("a comma separated list of brokers to connect to.")]
+    brokers: Option<String>,
+    #[default(false)]
+    #[description("start at the oldest records when there is no committed offset.")]
+    from_beginning: bool,
+    #[description("stop after reading this many records.")]
+    count: Option<i128>,
+}
+
+struct Consumer {
+    client: Client,
+    topic: String,
+    group: Option<(String, i32)>,
+    partitions: Vec<Partition>,
+    offsets: HashMap<i32, i64>,
+    /** The offsets that were last committed for the group. */
+    committed: HashMap<i32, i64>,
+}
+
+impl Consumer {
+    /** Moves the given partitions to their offsets at a time, or at the LATEST or EARLIEST special times. */
+    fn reset(&mut self, partitions: &[i32], timestamp: i64) -> CrushResult<()> {
+        for (leader, ids) in by_leader(&self.partitions) {
+            let ids: Vec<i32> = ids.into_iter().filter(|id| partitions.contains(id)).collect();
+            if !ids.is_empty() {
+                let offsets = self.client.list_offsets(leader, &self.topic, &ids, timestamp)?;
+                self.offsets.extend(offsets);
+            }
+        }
+        Ok(())
+    }
+
+    fn commit(&mut self) -> CrushResult<()> {
+        if let Some((group, coordinator)) = &self.group {
+            let changed: Vec<(i32, i64)> = self.offsets.iter()
+                .filter(|(partition, offset)| self.committed.get(partition) != Some(offset))
+                .map(|(partition, offset)| (*partition, *offset))
+                .collect();
+            if !changed.is_empty() {
+                self.client.commit(*coordinator, group, &self.topic, &changed)?;
+                self.committed.extend(changed);
+            }
+        }
+        Ok(())
+    }
+
+    /** Fetches one round of records from every leader, and sends them to the output. */
+    fn round(&mut self, remaining: &mut Option<i128>, output: &dyn Fn(Row) -> CrushResult<()>) -> CrushResult<()> {
+        let mut refresh = false;
+        let mut out_of_range = Vec::new();
+        for (leader, ids) in by_leader(&self.partitions) {
+            let offsets: Vec<(i32, i64)> = ids.iter().map(|id| (*id, self.offsets[id])).collect();
+            for fetched in self.client.fetch(leader, &self.topic, &offsets, Duration::from_millis(500))? {
+                match fetched.error {
+                    kafka::NONE => {}
+                    kafka::OFFSET_OUT_OF_RANGE => {
+                        out_of_range.push(fetched.partition);
+                        continue;
+                    }
+                    kafka::NOT_LEADER_OR_FOLLOWER | kafka::LEADER_NOT_AVAILABLE | kafka::UNKNOWN_TOPIC_OR_PARTITION => {
+                        refresh = true;
+                        continue;
+                    }
+                    code => return error(format!("Failed to read from Kafka: {}", kafka::error_name(code)).as_str()),
+                }
+                let start = self.offsets[&fetched.partition];
+                for record in fetched.records {
+                    // A batch is always returned whole, even if the fetch started in the middle of it
+                    if record.offset < start {
+                        continue;
+                    }
+                    if *remaining == Some(0) {
+                        return Ok(());
+                    }
+                    let offset = record.offset;
+                    output(record_row(fetched.partition, record))?;
+                    self.offsets.insert(fetched.partition, offset + 1);
+                    *remaining = remaining.map(|r| r - 1);
+                }
+                if let Some(next) = fetched.next_offset {
+                    self.offsets.insert(fetched.partition, next.max(start));
+                }
+            }
+        }
+        if !out_of_range.is_empty() {
+            self.reset(&out_of_range, kafka::EARLIEST)?;
+        }
+        if refresh {
+            // Give the cluster a moment to elect new leaders
+            std::thread::sleep(Duration::from_millis(500));
+            self.partitions = self.client.partitions(&self.topic)?;
+        }
+        Ok(())
+    }
+}
+
+fn consume(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Consume = Consume::parse(context.arguments, &context.printer)?;
+    if cfg.count.map(|c| c < 0).unwrap_or(false) {
+        return argument_error("The count must not be negative");
+    }
+    let mut client = client(cfg.brokers);
+    let partitions = client.partitions(&cfg.topic)?;
+    let ids: Vec<i32> = partitions.iter().map(|p| p.id).collect();
+    let (group, committed) = match cfg.group {
+        Some(group) => {
+            let coordinator = client.coordinator(&group)?;
+            let committed = client.committed(coordinator, &group, &cfg.topic, &ids)?;
+            (Some((group, coordinator)), committed)
+        }
+        None => (None, HashMap::new()),
+    };
+    let mut consumer = Consumer {
+        client,
+        topic: cfg.topic,
+        group,
+        partitions,
+        offsets: committed.clone(),
+        committed,
+    };
+    let missing: Vec<i32> = ids.into_iter().filter(|id| !consumer.offsets.contains_key(id)).collect();
+    consumer.reset(&missing, if cfg.from_beginning { kafka::EARLIEST } else { kafka::LATEST })?;
+
+    let output = context.output.initialize(CONSUME_OUTPUT_TYPE.clone())?;
+    let mut remaining = cfg.count;
+    while remaining != Some(0) {
+        let res = consumer.round(&mut remaining, &|row| output.send(row));
+        // Commit what was read even if the output was closed, so the next read continues there
+        consumer.commit()?;
+        res?;
+    }
+    Ok(())
+}
+
+#[signature(
+produce,
+can_block = true,
+output = Known(ValueType::Integer),
+short = "Write the rows of the input to a Kafka topic, and return the number of records written",
+long = "The input must have a value column, and may have a key column and a partition column. Strings",
+long = "and binaries are written as they are, the empty value is written as null, and other values are",
+long = "converted to strings. Records without a partition go to the partition picked by the hash of",
+long = "their key, the same one the Java client picks, or to each partition in turn if they have no key.",
+long = "",
+long = "Records are written in batches, when the input has been idle for a moment, so this works on",
+long = "both finite and endless streams.",
+long = "",
+long = "The brokers default to the KAFKA_BROKERS environment variable, or localhost:9092.",
+example = "csv:from orders.csv | select key={id} value={json:to} | kafka:produce \"orders\"")]
+pub struct Produce {
+    #[description("the topic to write to.")]
+    topic: String,
+    #[description("a comma separated list of brokers to connect to.")]
+    brokers: Option<String>,
+}
+
+fn bytes(value: &Value) -> Option<Vec<u8>> {
+    match value {
+        Value::Empty() => None,
+        Value::String(s) => Some(s.as_bytes().to_vec()),
+        Value::Binary(b) => Some(b.clone()),
+        v => Some(v.to_string().into_bytes()),
+    }
+}
+
+type Pending = HashMap<i32, Vec<KeyValue>>;
+
+fn flush(client: &mut Client, topic: &str, partitions: &[Partition], pending: &mut Pending) -> CrushResult<()> {
+    for (partition, records) in pending.drain() {
+        let leader = partitions[partition as usize].leader;
+        let batch = kafka::encode_batch(&records, Utc::now().timestamp_millis());
+        client.produce(leader, topic, partition, &batch)?;
+    }
+    Ok(())
+}
+
+fn run_produce(mut client: Client, topic: &str, input: &mut dyn CrushStream) -> CrushResult<i128> {
+    let types = input.types().to_vec();
+    let find = |name: &str| types.iter().position(|t| t.name == name);
+    let value = match find("value") {
+        Some(idx) => idx,
+        None => return error("Expected the input to have a value column"),
+    };
+    let key = find("key");
+    let partition = find("partition");
+
+    let partitions = client.partitions(topic)?;
+    let mut pending: Pending = HashMap::new();
+    let mut next_partition = 0;
+    let mut produced = 0;
+    loop {
+        let row = match input.read_timeout(chrono::Duration::milliseconds(100)) {
+            Ok(row) => row,
+            Err(RecvTimeoutError::Timeout) => {
+                flush(&mut client, topic, &partitions, &mut pending)?;
+                continue;
+            }
+            Err(RecvTimeoutError::Disconnected) => break,
+        };
+        let cells = row.cells();
+        let record_key = key.and_then(|idx| bytes(&cells[idx]));
+        let record_partition = match (partition.map(|idx| &cells[idx]), &record_key) {
+            (Some(Value::Integer(p)), _) => {
+                if *p < 0 || *p as usize >= partitions.len() {
+                    return error(format!("Topic {} has no partition {}", topic, p).as_str());
+                }
+                *p as i32
+            }
+            (Some(Value::Empty()), Some(k)) | (None, Some(k)) => kafka::partition_for_key(k, partitions.len()),
+            (Some(Value::Empty()), None) | (None, None) => {
+                next_partition = (next_partition + 1) % partitions.len();
+                next_partition as i32
+            }
+            (Some(v), _) => return error(format!("Expected the partition to be an integer, got a value of type {}", v.value_type().to_string()).as_str()),
+        };
+        let records = pending.entry(record_partition).or_default();
+        records.push((record_key, bytes(&cells[value])));
+        produced += 1;
+        if records.len() >= MAX_BATCH {
+            flush(&mut client, topic, &partitions, &mut pending)?;
+        }
+    }
+    flush(&mut client, topic, &partitions, &mut pending)?;
+    Ok(produced)
+}
+
+fn produce(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Produce = Produce::parse(context.arguments, &context.printer)?;
+    match context.input.recv()?.stream() {
+        Some(mut input) => {
+            let produced = run_produce(client(cfg.brokers), &cfg.topic, input.as_mut())?;
+            context.output.send(Value::Integer(produced))
+        }
+        None => error("Expected a stream"),
+    }
+}
+
+pub fn declare(root: &Scope) -> CrushResult<()> {
+    root.create_lazy_namespace(
+        "kafka",
+        Box::new(move |env| {
+            Consume::declare(env)?;
+            Produce::declare(env)?;
+            Ok(())
+        }))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn values() {
+        assert!(bytes_value(Some(b"hello".to_vec())) == Value::string("hello"));
+        assert!(bytes_value(Some(vec![0xff, 0])) == Value::Binary(vec![0xff, 0]));
+        assert!(matches!(bytes_value(None), Value::Empty()));
+        assert_eq!(bytes(&Value::Integer(42)), Some(b"42".to_vec()));
+        assert_eq!(bytes(&Value::Empty()), None);
+    }
+
+    #[test]
+    fn invalid_timestamps() {
+        let row = record_row(0, Record { offset: 1, timestamp: i64::MAX, key: None, value: None });
+        assert!(matches!(row.cells()[4], Value::Empty()));
+    }
+}
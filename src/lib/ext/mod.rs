@@ -0,0 +1,17 @@
+use crate::lang::errors::CrushResult;
+use crate::lang::scope::Scope;
+
+mod git;
+mod k8s;
+mod kafka;
+mod prom;
+mod s3;
+
+pub fn declare(root: &Scope) -> CrushResult<()> {
+    git::declare(root)?;
+    k8s::declare(root)?;
+    kafka::declare(root)?;
+    prom::declare(root)?;
+    s3::declare(root)?;
+    Ok(())
+}
@@ -0,0 +1,276 @@
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+
+use chrono::{Local, TimeZone};
+use lazy_static::lazy_static;
+
+use crate::lang::argument::ArgumentHandler;
+use crate::lang::command::Command;
+use crate::lang::command::OutputType::Known;
+use crate::lang::dict::Dict;
+use crate::lang::errors::{CrushResult, argument_error, data_error, error, mandate, to_crush_error};
+use crate::lang::execution_context::ExecutionContext;
+use crate::lang::scope::Scope;
+use crate::lang::stream::{channels, empty_channel};
+use crate::lang::table::{ColumnType, Row};
+use crate::lang::value::{Value, ValueType};
+use crate::util::prometheus::{self, Sample};
+use crate::util::thread::build;
+use signature::signature;
+
+lazy_static! {
+    static ref SCRAPE_OUTPUT_TYPE: Vec<ColumnType> = vec![
+        ColumnType::new("metric", ValueType::String),
+        ColumnType::new("labels", ValueType::Dict(Box::new(ValueType::String), Box::new(ValueType::String))),
+        ColumnType::new("value", ValueType::Float),
+        ColumnType::new("timestamp", ValueType::Any),
+    ];
+}
+
+#[signature(
+scrape,
+can_block = true,
+output = Known(ValueType::TableStream(SCRAPE_OUTPUT_TYPE.clone())),
+short = "Return a table stream of the metrics served by a Prometheus exporter",
+long = "Each row contains the name of a metric, its labels as a dict, its value and the time it was",
+long = "measured, if the exporter gave one. The HELP and TYPE comments are skipped.",
+example = "prom:scrape \"http://localhost:9100/metrics\" | where {metric == \"node_load1\"}")]
+pub struct Scrape {
+    #[description("the url of the metrics.")]
+    url: String,
+}
+
+fn sample_row(sample: Sample) -> CrushResult<Row> {
+    let labels = Dict::new(ValueType::String, ValueType::String);
+    for (key, value) in sample.labels {
+        labels.insert(Value::String(key), Value::String(value))?;
+    }
+    Ok(Row::new(vec![
+        Value::String(sample.metric),
+        Value::Dict(labels),
+        Value::Float(sample.value),
+        sample.timestamp
+            .and_then(|t| Local.timestamp_opt(t.div_euclid(1000), (t.rem_euclid(1000) * 1_000_000) as u32).single())
+            .map(Value::Time)
+            .unwrap_or(Value::Empty()),
+    ]))
+}
+
+fn scrape(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Scrape = Scrape::parse(context.arguments, &context.printer)?;
+    let response = to_crush_error(reqwest::blocking::Client::new()
+        .get(cfg.url.as_str())
+        .header("Accept", "text/plain;version=0.0.4")
+        .send())?;
+    if !response.status().is_success() {
+        return error(format!("Failed to scrape {}: {}", cfg.url, response.status()).as_str());
+    }
+    let text = to_crush_error(response.text())?;
+    let output = context.output.initialize(SCRAPE_OUTPUT_TYPE.clone())?;
+    for sample in prometheus::parse(&text)? {
+        output.send(sample_row(sample)?)?;
+    }
+    Ok(())
+}
+
+fn number(value: &Value) -> CrushResult<f64> {
+    match value {
+        Value::Integer(i) => Ok(*i as f64),
+        Value::Float(f) => Ok(*f),
+        Value::Bool(b) => Ok(if *b { 1.0 } else { 0.0 }),
+        Value::Duration(d) => Ok(d.num_milliseconds() as f64 / 1000.0),
+        v => data_error(format!("Expected the value of a metric to be a number, got a value of type {}", v.value_type().to_string()).as_str()),
+    }
+}
+
+/**
+Converts the output of the command of prom:expose to the exposition format. Strings, like the
+output of metric:export, are served as they are.
+*/
+fn exposition(value: Value) -> CrushResult<String> {
+    if let Value::String(s) = value {
+        return Ok(s);
+    }
+    let mut input = mandate(value.stream(), "Expected the command to output a table or a string")?;
+    let types = input.types().to_vec();
+    let find = |name: &str| types.iter().position(|t| t.name == name);
+    let metric = mandate(find("metric").or_else(|| find("name")), "Expected a metric column")?;
+    let value = mandate(find("value"), "Expected a value column")?;
+    let labels = find("labels");
+    let timestamp = find("timestamp");
+    let mut res = String::new();
+    while let Ok(row) = input.read() {
+        let cells = row.cells();
+        let mut sample = Sample {
+            metric: cells[metric].to_string(),
+            labels: vec![],
+            value: number(&cells[value])?,
+            timestamp: match timestamp.map(|idx| &cells[idx]) {
+                Some(Value::Time(t)) => Some(t.timestamp_millis()),
+                Some(Value::Integer(i)) => Some(*i as i64),
+                _ => None,
+            },
+        };
+        if let Some(Value::Dict(d)) = labels.map(|idx| &cells[idx]) {
+            for (k, v) in d.elements() {
+                sample.labels.push((k.to_string(), v.to_string()));
+            }
+        }
+        // All other columns are labels too
+        for (idx, column) in types.iter().enumerate() {
+            if idx != metric && idx != value && Some(idx) != labels && Some(idx) != timestamp {
+                sample.labels.push((column.name.clone(), cells[idx].to_string()));
+            }
+        }
+        res.push_str(&prometheus::format(&sample));
+        res.push('\n');
+    }
+    Ok(res)
+}
+
+/** Runs the command of prom:expose and returns its output in the exposition format. */
+fn collect(context: &ExecutionContext, command: &Command) -> CrushResult<String> {
+    let (sender, receiver) = channels();
+    let command_context = ExecutionContext {
+        input: empty_channel(),
+        output: sender,
+        arguments: vec![],
+        env: context.env.clone(),
+        this: None,
+        printer: context.printer.clone(),
+    };
+    let command = command.as_ref().clone();
+    let job = to_crush_error(build("prom:expose").spawn(move || command.invoke(command_context)))?;
+    // The output is read while the command is still running, so that commands producing more
+    // rows than fit in a stream buffer don't block forever.
+    let res = receiver.recv().map(exposition);
+    match job.join() {
+        Ok(Ok(())) => {}
+        Ok(Err(e)) => return Err(e),
+        Err(_) => return error("The command panicked"),
+    }
+    match res {
+        Ok(res) => res,
+        Err(_) => error("Expected the command to output a table or a string"),
+    }
+}
+
+fn respond(stream: &mut TcpStream, status: &str, body: &str) -> CrushResult<()> {
+    to_crush_error(write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status, body.len(), body))
+}
+
+fn handle(context: &ExecutionContext, command: &Command, mut stream: TcpStream) -> CrushResult<()> {
+    to_crush_error(stream.set_read_timeout(Some(std::time::Duration::from_secs(10))))?;
+    let mut reader = BufReader::new(to_crush_error(stream.try_clone())?);
+    let mut request = String::new();
+    to_crush_error(reader.read_line(&mut request))?;
+    loop {
+        let mut header = String::new();
+        if to_crush_error(reader.read_line(&mut header))? == 0 || header.trim().is_empty() {
+            break;
+        }
+    }
+    let mut parts = request.split_whitespace();
+    match (parts.next(), parts.next()) {
+        (Some("GET"), Some(path)) if path == "/metrics" || path.starts_with("/metrics?") => {
+            match collect(context, command) {
+                Ok(body) => respond(&mut stream, "200 OK", &body),
+                Err(e) => respond(&mut stream, "500 Internal Server Error", &format!("{}\n", e.message)),
+            }
+        }
+        (Some("GET"), Some(_)) => respond(&mut stream, "404 Not Found", "Metrics are served at /metrics\n"),
+        _ => respond(&mut stream, "405 Method Not Allowed", ""),
+    }
+}
+
+#[signature(
+expose,
+can_block = true,
+output = Known(ValueType::Empty),
+short = "Serve the output of a command as metrics for Prometheus to scrape",
+long = "Listens for http requests for /metrics, and answers each one by running the command. The",
+long = "command should output either a string in the exposition format, like the output of",
+long = "metric:export, or a table with a metric column and a value column. A labels column with a",
+long = "dict and a timestamp column are used if present, and any other columns become labels, so the",
+long = "output of prom:scrape can be served again.",
+long = "",
+long = "Requests are answered one at a time. This command does not return unless requests is given.",
+example = "prom:expose port=9200 {ps | group ^user | select metric={\"processes\"} ^user value={group | count}}")]
+pub struct Expose {
+    #[description("the port to listen on.")]
+    port: i128,
+    #[description("the command to run for every request.")]
+    command: Command,
+    #[default("0.0.0.0")]
+    #[description("the address to listen on.")]
+    host: String,
+    #[description("stop after answering this many requests.")]
+    requests: Option<i128>,
+}
+
+fn expose(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Expose = Expose::parse(context.arguments.clone(), &context.printer)?;
+    if cfg.port < 0 || cfg.port > 65535 {
+        return argument_error("Expected the port to be between 0 and 65535");
+    }
+    let listener = to_crush_error(TcpListener::bind((cfg.host.as_str(), cfg.port as u16)))?;
+    for (answered, stream) in listener.incoming().enumerate() {
+        match stream {
+            Ok(stream) => context.printer.handle_error(handle(&context, &cfg.command, stream)),
+            Err(e) => context.printer.handle_error::<()>(to_crush_error(Err(e))),
+        }
+        if cfg.requests.map(|r| answered as i128 + 1 >= r).unwrap_or(false) {
+            break;
+        }
+    }
+    context.output.send(Value::Empty())
+}
+
+pub fn declare(root: &Scope) -> CrushResult<()> {
+    root.create_lazy_namespace(
+        "prom",
+        Box::new(move |env| {
+            Scrape::declare(env)?;
+            Expose::declare(env)?;
+            Ok(())
+        }))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lang::table::Table;
+
+    #[test]
+    fn table_exposition() {
+        let table = Table::new(
+            vec![
+                ColumnType::new("metric", ValueType::String),
+                ColumnType::new("user", ValueType::String),
+                ColumnType::new("value", ValueType::Integer),
+            ],
+            vec![
+                Row::new(vec![Value::string("processes"), Value::string("root"), Value::Integer(12)]),
+                Row::new(vec![Value::string("processes"), Value::string("www \"data\""), Value::Integer(3)]),
+            ]);
+        assert_eq!(
+            exposition(Value::Table(table)).unwrap(),
+            "processes{user=\"root\"} 12\nprocesses{user=\"www \\\"data\\\"\"} 3\n");
+        assert!(exposition(Value::Integer(1)).is_err());
+    }
+
+    #[test]
+    fn scraped_rows() {
+        let sample = prometheus::parse("up{job=\"node\"} 1 1600000000000\n").unwrap().remove(0);
+        let row = sample_row(sample).unwrap();
+        let cells = row.cells();
+        assert!(cells[2] == Value::Float(1.0));
+        assert!(cells[3] == Value::Time(Local.timestamp(1600000000, 0)));
+        let sample = prometheus::parse("up 1 9223372036854775807\n").unwrap().remove(0);
+        assert!(matches!(sample_row(sample).unwrap().cells()[3], Value::Empty()));
+    }
+}
@@ -0,0 +1,339 @@
+use std::io::{BufRead, BufReader, Read};
+
+use chrono::{DateTime, Local, Utc};
+use lazy_static::lazy_static;
+use reqwest::blocking::{RequestBuilder, Response};
+
+use crate::lang::argument::ArgumentHandler;
+use crate::lang::binary::binary_channel;
+use crate::lang::command::OutputType::Known;
+use crate::lang::errors::{CrushResult, argument_error, data_error, error, to_crush_error};
+use crate::lang::execution_context::ExecutionContext;
+use crate::lang::files::Files;
+use crate::lang::scope::Scope;
+use crate::lang::table::{ColumnType, Row};
+use crate::lang::value::{Value, ValueType};
+use crate::util::aws::{self, Credentials};
+use crate::util::xml::{Event, Reader, local_name};
+use signature::signature;
+
+lazy_static! {
+    static ref LIST_OUTPUT_TYPE: Vec<ColumnType> = vec![
+        ColumnType::new("key", ValueType::String),
+        ColumnType::new("size", ValueType::Integer),
+        ColumnType::new("modified", ValueType::Time),
+        ColumnType::new("etag", ValueType::String),
+    ];
+}
+
+fn credentials(config: Option<Value>) -> CrushResult<Credentials> {
+    match config {
+        None => Credentials::new(None, "s3"),
+        Some(Value::Struct(s)) => Credentials::new(Some(s), "s3"),
+        Some(v) => argument_error(format!("Expected the credentials to be a struct, got a value of type {}", v.value_type().to_string()).as_str()),
+    }
+}
+
+/**
+Signs and sends a request for an object in a bucket, or for the bucket itself if there is no
+key. Buckets on AWS are addressed by their host name, while other endpoints get the bucket as
+the first part of the path, which is what services like MinIO expect.
+*/
+fn request(
+    credentials: &Credentials,
+    method: &str,
+    bucket: &str,
+    key: Option<&str>,
+    query: &[(&str, String)],
+    body: Option<Vec<u8>>,
+) -> CrushResult<Response> {
+    if bucket.is_empty() || bucket.contains('/') {
+        return argument_error(format!("Invalid bucket name {}", bucket).as_str());
+    }
+    let key = aws::uri_encode(key.unwrap_or(""), false);
+    let (scheme, host, path) = match &credentials.endpoint {
+        None => (
+            "https",
+            format!("{}.s3.{}.amazonaws.com", bucket, credentials.region),
+            format!("/{}", key)),
+        Some(endpoint) => {
+            let (scheme, host) = match endpoint.split_once("://") {
+                Some((scheme, host)) => (scheme, host.to_string()),
+                None => ("https", endpoint.clone()),
+            };
+            (scheme, host, format!("/{}/{}", aws::uri_encode(bucket, true), key))
+        }
+    };
+    let payload_hash = aws::sha256_hex(body.as_deref().unwrap_or(&[]))?;
+    let headers = aws::sign(credentials, "s3", &aws::Request {
+        method,
+        host: &host,
+        path: &path,
+        query,
+        headers: &[],
+        payload_hash: &payload_hash,
+    }, Utc::now())?;
+
+    let mut url = format!("{}://{}{}", scheme, host, path);
+    if !query.is_empty() {
+        url.push('?');
+        url.push_str(&aws::query_string(query));
+    }
+    let client = reqwest::blocking::Client::new();
+    let mut builder: RequestBuilder = match method {
+        "PUT" => client.put(url.as_str()),
+        _ => client.get(url.as_str()),
+    };
+    for (name, value) in headers {
+        builder = builder.header(name.as_str(), value);
+    }
+    if let Some(body) = body {
+        builder = builder.body(body);
+    }
+    let response = to_crush_error(builder.send())?;
+    if response.status().is_success() {
+        Ok(response)
+    } else {
+        let status = response.status();
+        let message = error_message(BufReader::new(response)).unwrap_or_default();
+        error(format!("S3 request failed with status {}{}", status, message).as_str())
+    }
+}
+
+/** The code and message of the XML error document S3 answers failed requests with. */
+fn error_message(body: impl BufRead) -> CrushResult<String> {
+    let mut reader = Reader::new(body);
+    let mut current = String::new();
+    let mut code = None;
+    let mut message = None;
+    while let Some(event) = reader.next()? {
+        match event {
+            Event::Start(name, _) => current = local_name(&name).to_string(),
+            Event::Text(text) => match current.as_str() {
+                "Code" => code = Some(text),
+                "Message" => message = Some(text),
+                _ => {}
+            },
+            Event::End(_) => current.clear(),
+        }
+    }
+    Ok(match (code, message) {
+        (Some(code), Some(message)) => format!(": {}: {}", code, message),
+        (Some(code), None) => format!(": {}", code),
+        _ => String::new(),
+    })
+}
+
+struct Page {
+    rows: Vec<Row>,
+    continuation: Option<String>,
+}
+
+fn parse_time(text: &str) -> CrushResult<Value> {
+    match DateTime::parse_from_rfc3339(text) {
+        Ok(time) => Ok(Value::Time(time.with_timezone(&Local))),
+        Err(_) => data_error(format!("Invalid modification time {}", text).as_str()),
+    }
+}
+
+/** Parses a page of the response to a ListObjectsV2 request. */
+fn parse_list(body: impl BufRead) -> CrushResult<Page> {
+    let mut reader = Reader::new(body);
+    let mut path: Vec<String> = Vec::new();
+    let mut object: Vec<Value> = Vec::new();
+    let mut page = Page { rows: Vec::new(), continuation: None };
+    let mut truncated = false;
+    while let Some(event) = reader.next()? {
+        match event {
+            Event::Start(name, _) => {
+                if local_name(&name) == "Contents" {
+                    object = vec![Value::string(""), Value::Integer(0), Value::Empty(), Value::string("")];
+                }
+                path.push(local_name(&name).to_string());
+            }
+            Event::Text(text) => match path.iter().map(|s| s.as_str()).collect::<Vec<_>>().as_slice() {
+                [_, "Contents", "Key"] => object[0] = Value::String(text),
+                [_, "Contents", "Size"] => object[1] = Value::Integer(to_crush_error(text.trim().parse::<i128>())?),
+                [_, "Contents", "LastModified"] => object[2] = parse_time(text.trim())?,
+                [_, "Contents", "ETag"] => object[3] = Value::String(text.trim_matches('"').to_string()),
+                [_, "IsTruncated"] => truncated = text.trim() == "true",
+                [_, "NextContinuationToken"] => page.continuation = Some(text),
+                _ => {}
+            },
+            Event::End(_) => {
+                if path.pop().as_deref() == Some("Contents") {
+                    page.rows.push(Row::new(std::mem::take(&mut object)));
+                }
+            }
+        }
+    }
+    if !truncated {
+        page.continuation = None;
+    }
+    Ok(page)
+}
+
+#[signature(
+list,
+can_block = true,
+output = Known(ValueType::TableStream(LIST_OUTPUT_TYPE.clone())),
+short = "Return a table stream of the objects in an S3 bucket",
+long = "The credentials are read from the AWS_ACCESS_KEY_ID, AWS_SECRET_ACCESS_KEY, AWS_SESSION_TOKEN",
+long = "and AWS_REGION environment variables, unless a credentials struct is given. The struct may have",
+long = "the fields access_key, secret_key, session_token, region and endpoint, and missing fields are",
+long = "read from the environment. Use the endpoint, or the AWS_ENDPOINT_URL environment variable, to",
+long = "talk to other services with an S3 compatible api, like MinIO.",
+example = "s3:list bucket=\"logs\" prefix=\"2020/\" | where {size > 1000000}")]
+pub struct List {
+    #[description("the bucket to list.")]
+    bucket: String,
+    #[description("only list objects with keys that start with this prefix.")]
+    prefix: Option<String>,
+    #[description("a struct with the credentials to use.")]
+    credentials: Option<Value>,
+}
+
+fn list(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: List = List::parse(context.arguments, &context.printer)?;
+    let credentials = credentials(cfg.credentials)?;
+    let output = context.output.initialize(LIST_OUTPUT_TYPE.clone())?;
+    let mut continuation: Option<String> = None;
+    loop {
+        let mut query = vec![("list-type", "2".to_string())];
+        if let Some(prefix) = &cfg.prefix {
+            query.push(("prefix", prefix.clone()));
+        }
+        if let Some(token) = continuation {
+            query.push(("continuation-token", token));
+        }
+        let response = request(&credentials, "GET", &cfg.bucket, None, &query, None)?;
+        let page = parse_list(BufReader::new(response))?;
+        for row in page.rows {
+            output.send(row)?;
+        }
+        match page.continuation {
+            Some(token) => continuation = Some(token),
+            None => return Ok(()),
+        }
+    }
+}
+
+#[signature(
+get,
+can_block = true,
+output = Known(ValueType::BinaryStream),
+short = "Return the content of an object in an S3 bucket as a binary stream",
+long = "The credentials are found the same way as for s3:list.",
+example = "s3:get bucket=\"logs\" key=\"2020/01/01.json\" | json:from")]
+pub struct Get {
+    #[description("the bucket of the object.")]
+    bucket: String,
+    #[description("the key of the object.")]
+    key: String,
+    #[description("a struct with the credentials to use.")]
+    credentials: Option<Value>,
+}
+
+fn get(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Get = Get::parse(context.arguments, &context.printer)?;
+    let credentials = credentials(cfg.credentials)?;
+    let mut response = request(&credentials, "GET", &cfg.bucket, Some(&cfg.key), &[], None)?;
+    let (mut output, input) = binary_channel();
+    context.output.send(Value::BinaryStream(input))?;
+    to_crush_error(response.copy_to(output.as_mut()))?;
+    Ok(())
+}
+
+#[signature(
+put,
+can_block = true,
+output = Known(ValueType::String),
+short = "Upload the specified file (or input) to an S3 bucket, and return the etag of the new object",
+long = "The whole content is read into memory before it is uploaded. The credentials are found the",
+long = "same way as for s3:list.",
+example = "csv:from ./report.csv | csv:to | s3:put bucket=\"reports\" key=\"2020/report.csv\"")]
+pub struct Put {
+    #[description("the bucket to upload to.")]
+    bucket: String,
+    #[description("the key of the new object.")]
+    key: String,
+    #[unnamed()]
+    #[description("the file to upload (upload the input if no file is specified).")]
+    files: Files,
+    #[description("a struct with the credentials to use.")]
+    credentials: Option<Value>,
+}
+
+fn put(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Put = Put::parse(context.arguments, &context.printer)?;
+    let credentials = credentials(cfg.credentials)?;
+    if cfg.files.as_slice().len() > 1 {
+        return argument_error("Expected at most one file to upload");
+    }
+    let mut body = Vec::new();
+    to_crush_error(cfg.files.reader(context.input)?.read_to_end(&mut body))?;
+    let response = request(&credentials, "PUT", &cfg.bucket, Some(&cfg.key), &[], Some(body))?;
+    let etag = response.headers().get("etag")
+        .and_then(|e| e.to_str().ok())
+        .unwrap_or("")
+        .trim_matches('"')
+        .to_string();
+    context.output.send(Value::String(etag))
+}
+
+pub fn declare(root: &Scope) -> CrushResult<()> {
+    root.create_lazy_namespace(
+        "s3",
+        Box::new(move |env| {
+            List::declare(env)?;
+            Get::declare(env)?;
+            Put::declare(env)?;
+            Ok(())
+        }))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn list_page() {
+        let body = r#"<?xml version="1.0" encoding="UTF-8"?>
+<ListBucketResult xmlns="http://s3.amazonaws.com/doc/2006-03-01/">
+  <Name>bucket</Name>
+  <Prefix/>
+  <KeyCount>2</KeyCount>
+  <MaxKeys>2</MaxKeys>
+  <IsTruncated>true</IsTruncated>
+  <Contents>
+    <Key>a &amp; b.txt</Key>
+    <LastModified>2009-10-12T17:50:30.000Z</LastModified>
+    <ETag>&quot;fba9dede5f27731c9771645a39863328&quot;</ETag>
+    <Size>434234</Size>
+    <StorageClass>STANDARD</StorageClass>
+  </Contents>
+  <Contents>
+    <Key>c.txt</Key>
+    <LastModified>2009-10-12T17:50:31.000Z</LastModified>
+    <ETag>"abc"</ETag>
+    <Size>0</Size>
+  </Contents>
+  <NextContinuationToken>1ueGcxLPRx1Tr/XYExHnhbYLgveDs2J/wm36Hy4vbOwM=</NextContinuationToken>
+</ListBucketResult>"#;
+        let page = parse_list(body.as_bytes()).unwrap();
+        assert_eq!(page.rows.len(), 2);
+        let cells = page.rows[0].cells();
+        assert!(cells[0] == Value::string("a & b.txt"));
+        assert!(cells[1] == Value::Integer(434234));
+        assert!(cells[2] == parse_time("2009-10-12T17:50:30Z").unwrap());
+        assert!(cells[3] == Value::string("fba9dede5f27731c9771645a39863328"));
+        assert_eq!(page.continuation.as_deref(), Some("1ueGcxLPRx1Tr/XYExHnhbYLgveDs2J/wm36Hy4vbOwM="));
+    }
+
+    #[test]
+    fn errors() {
+        let body = "<Error><Code>NoSuchKey</Code><Message>The resource you requested does not exist</Message></Error>";
+        assert_eq!(error_message(body.as_bytes()).unwrap(), ": NoSuchKey: The resource you requested does not exist");
+    }
+}
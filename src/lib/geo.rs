@@ -0,0 +1,180 @@
+use crate::lang::argument::ArgumentHandler;
+use crate::lang::command::OutputType::Known;
+use crate::lang::errors::{CrushResult, argument_error, mandate};
+use crate::lang::execution_context::ExecutionContext;
+use crate::lang::list::List;
+use crate::lang::scope::Scope;
+use crate::lang::value::{Value, ValueType};
+use signature::signature;
+
+/** The mean radius of the earth, in meters. */
+const EARTH_RADIUS: f64 = 6_371_008.8;
+
+/** The great circle distance in meters between two points, given as latitude and longitude in degrees. */
+pub fn haversine(from: (f64, f64), to: (f64, f64)) -> f64 {
+    let (lat1, lat2) = (from.0.to_radians(), to.0.to_radians());
+    let d_lat = lat2 - lat1;
+    let d_lon = (to.1 - from.1).to_radians();
+    let a = (d_lat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (d_lon / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS * a.sqrt().min(1.0).asin()
+}
+
+fn number(value: &Value) -> CrushResult<f64> {
+    match value {
+        Value::Float(f) => Ok(*f),
+        Value::Integer(i) => Ok(*i as f64),
+        v => argument_error(format!("Expected a number, got a {}", v.value_type().to_string()).as_str()),
+    }
+}
+
+/** A GeoJSON position, which is a list of longitude, latitude and optionally elevation. */
+fn position(list: &List) -> CrushResult<(f64, f64)> {
+    if list.len() < 2 {
+        return argument_error("Expected a position to contain a longitude and a latitude");
+    }
+    Ok((number(&list.get(1)?)?, number(&list.get(0)?)?))
+}
+
+/**
+Returns the latitude and longitude of a point, which is either a struct with lat and lon
+members, a GeoJSON position or a GeoJSON Point geometry.
+*/
+fn point(value: &Value) -> CrushResult<(f64, f64)> {
+    match value {
+        Value::List(list) => position(list),
+        Value::Struct(s) => match (s.get("lat"), s.get("lon"), s.get("type"), s.get("coordinates")) {
+            (Some(lat), Some(lon), _, _) => Ok((number(&lat)?, number(&lon)?)),
+            (_, _, Some(Value::String(t)), Some(Value::List(coordinates))) if t == "Point" => position(&coordinates),
+            _ => argument_error("Expected a struct with lat and lon members or a Point geometry"),
+        },
+        v => argument_error(format!("Expected a point, got a {}", v.value_type().to_string()).as_str()),
+    }
+}
+
+fn list(value: Value) -> CrushResult<List> {
+    match value {
+        Value::List(l) => Ok(l),
+        v => argument_error(format!("Expected a list of coordinates, got a {}", v.value_type().to_string()).as_str()),
+    }
+}
+
+/** Whether a point is inside of a ring of positions, by counting how many edges a ray from the point crosses. */
+fn in_ring(point: (f64, f64), ring: &List) -> CrushResult<bool> {
+    let positions = ring.dump().iter()
+        .map(|p| position(&list(p.clone())?))
+        .collect::<CrushResult<Vec<_>>>()?;
+    let (lat, lon) = point;
+    let mut inside = false;
+    for idx in 0..positions.len() {
+        let (lat1, lon1) = positions[idx];
+        let (lat2, lon2) = positions[(idx + positions.len() - 1) % positions.len()];
+        if (lat1 > lat) != (lat2 > lat) && lon < (lon2 - lon1) * (lat - lat1) / (lat2 - lat1) + lon1 {
+            inside = !inside;
+        }
+    }
+    Ok(inside)
+}
+
+/** The first ring of a polygon is its outline, any further rings are holes. */
+fn in_polygon(point: (f64, f64), polygon: List) -> CrushResult<bool> {
+    let rings = polygon.dump();
+    match rings.split_first() {
+        None => Ok(false),
+        Some((outline, holes)) => {
+            if !in_ring(point, &list(outline.clone())?)? {
+                return Ok(false);
+            }
+            for hole in holes {
+                if in_ring(point, &list(hole.clone())?)? {
+                    return Ok(false);
+                }
+            }
+            Ok(true)
+        }
+    }
+}
+
+#[signature(
+distance,
+can_block = false,
+output = Known(ValueType::Float),
+short = "The distance in meters between two points",
+long = "Points are either structs with lat and lon members, like the rows output by gpx:from,",
+long = "GeoJSON positions, which are lists of longitude and latitude, or GeoJSON Point geometries.",
+long = "The distance is measured along the surface of the earth, assuming it is a sphere.",
+example = "geo:distance (data lat=59.33 lon=18.07) (data lat=55.68 lon=12.57)")]
+struct Distance {
+    #[description("the first point.")]
+    from: Value,
+    #[description("the second point.")]
+    to: Value,
+}
+
+fn distance(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Distance = Distance::parse(context.arguments, &context.printer)?;
+    context.output.send(Value::Float(haversine(point(&cfg.from)?, point(&cfg.to)?)))
+}
+
+#[signature(
+contains,
+can_block = false,
+output = Known(ValueType::Bool),
+short = "True if the point is inside of the geometry",
+long = "The geometry must be a GeoJSON Polygon or MultiPolygon, like the geometries output by",
+long = "geojson:from. Points are the same as for geo:distance.",
+example = "geojson:from ./countries.geojson | where {geo:contains geometry (data lat=59.33 lon=18.07)}")]
+struct Contains {
+    #[description("the polygon to check.")]
+    geometry: Value,
+    #[description("the point to look for.")]
+    point: Value,
+}
+
+fn contains(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Contains = Contains::parse(context.arguments, &context.printer)?;
+    let point = point(&cfg.point)?;
+    let geometry = match cfg.geometry {
+        Value::Struct(s) => s,
+        _ => return argument_error("Expected the geometry to be a struct"),
+    };
+    let coordinates = list(mandate(geometry.get("coordinates"), "Expected the geometry to have coordinates")?)?;
+    let res = match geometry.get("type") {
+        Some(Value::String(t)) if t == "Polygon" => in_polygon(point, coordinates)?,
+        Some(Value::String(t)) if t == "MultiPolygon" => {
+            let mut res = false;
+            for polygon in coordinates.dump() {
+                if in_polygon(point, list(polygon)?)? {
+                    res = true;
+                    break;
+                }
+            }
+            res
+        }
+        _ => return argument_error("Expected a Polygon or MultiPolygon geometry"),
+    };
+    context.output.send(Value::Bool(res))
+}
+
+pub fn declare(root: &Scope) -> CrushResult<()> {
+    root.create_lazy_namespace(
+        "geo",
+        Box::new(move |env| {
+            Distance::declare(env)?;
+            Contains::declare(env)?;
+            Ok(())
+        }))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_distance() {
+        // Stockholm to Copenhagen is about 522 km
+        let d = haversine((59.3293, 18.0686), (55.6761, 12.5683));
+        assert!((d - 522_000.0).abs() < 2_000.0);
+        assert_eq!(haversine((10.0, 20.0), (10.0, 20.0)), 0.0);
+    }
+}
@@ -0,0 +1,136 @@
+use crate::lang::execution_context::ExecutionContext;
+use crate::lang::errors::{CrushResult, to_crush_error, argument_error};
+use crate::lang::scope::Scope;
+use crate::lang::value::{Value, ValueType};
+use crate::lang::table::{ColumnType, Row};
+use crate::lang::files::Files;
+use signature::signature;
+use crate::lang::argument::ArgumentHandler;
+use openssl::hash::{Hasher, MessageDigest};
+use std::fs::File;
+use std::io::Read;
+
+fn digest(algorithm: MessageDigest, reader: &mut dyn Read) -> CrushResult<String> {
+    let mut hasher = to_crush_error(Hasher::new(algorithm))?;
+    let mut buffer = vec![0u8; 64 * 1024];
+    loop {
+        let count = to_crush_error(reader.read(&mut buffer))?;
+        if count == 0 {
+            break;
+        }
+        to_crush_error(hasher.update(&buffer[..count]))?;
+    }
+    let bytes = to_crush_error(hasher.finish())?;
+    Ok(bytes.iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+/**
+Hash the specified files, outputting a row with the file and its digest for every file,
+or, if no files were specified, hash the input and output the digest.
+*/
+fn run(context: ExecutionContext, files: Files, algorithm: MessageDigest) -> CrushResult<()> {
+    if files.had_entries() {
+        let output = context.output.initialize(vec![
+            ColumnType::new("file", ValueType::File),
+            ColumnType::new("digest", ValueType::String),
+        ])?;
+        for file in files.into_vec() {
+            let hash = digest(algorithm, &mut to_crush_error(File::open(&file))?)?;
+            output.send(Row::new(vec![Value::File(file), Value::string(&hash)]))?;
+        }
+        Ok(())
+    } else {
+        let hash = match context.input.recv()? {
+            Value::String(s) => digest(algorithm, &mut s.as_bytes())?,
+            Value::Binary(b) => digest(algorithm, &mut &b[..])?,
+            Value::BinaryStream(mut b) => digest(algorithm, &mut b)?,
+            v => return argument_error(
+                format!("Expected a string or binary data to hash, got a value of type {}",
+                        v.value_type().to_string()).as_str()),
+        };
+        context.output.send(Value::string(&hash))
+    }
+}
+
+#[signature(
+md5,
+can_block = true,
+short = "Calculate the MD5 digest of files or of the input",
+long = "If files are specified, a table with the digest of every file is output. Otherwise, the",
+long = "digest of the input, which must be a string or binary data, is output as a hex string.",
+long = "Input is read in chunks, so even very large files are never read into memory at once.",
+example = "hash:md5 %.rs")]
+struct Md5 {
+    #[unnamed()]
+    #[description("the files to hash (hash the input if no file is specified).")]
+    files: Files,
+}
+
+fn md5(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Md5 = Md5::parse(context.arguments.clone(), &context.printer)?;
+    run(context, cfg.files, MessageDigest::md5())
+}
+
+#[signature(
+sha1,
+can_block = true,
+short = "Calculate the SHA-1 digest of files or of the input",
+long = "See hash:md5 for details.",
+example = "bin:from ./Cargo.lock | hash:sha1")]
+struct Sha1 {
+    #[unnamed()]
+    #[description("the files to hash (hash the input if no file is specified).")]
+    files: Files,
+}
+
+fn sha1(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Sha1 = Sha1::parse(context.arguments.clone(), &context.printer)?;
+    run(context, cfg.files, MessageDigest::sha1())
+}
+
+#[signature(
+sha256,
+can_block = true,
+short = "Calculate the SHA-256 digest of files or of the input",
+long = "See hash:md5 for details.",
+example = "hash:sha256 (find ./src | where {type == \"file\"} | select ^file) | group ^digest")]
+struct Sha256 {
+    #[unnamed()]
+    #[description("the files to hash (hash the input if no file is specified).")]
+    files: Files,
+}
+
+fn sha256(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Sha256 = Sha256::parse(context.arguments.clone(), &context.printer)?;
+    run(context, cfg.files, MessageDigest::sha256())
+}
+
+#[signature(
+sha512,
+can_block = true,
+short = "Calculate the SHA-512 digest of files or of the input",
+long = "See hash:md5 for details.",
+example = "val \"some text\" | hash:sha512")]
+struct Sha512 {
+    #[unnamed()]
+    #[description("the files to hash (hash the input if no file is specified).")]
+    files: Files,
+}
+
+fn sha512(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Sha512 = Sha512::parse(context.arguments.clone(), &context.printer)?;
+    run(context, cfg.files, MessageDigest::sha512())
+}
+
+pub fn declare(root: &Scope) -> CrushResult<()> {
+    root.create_lazy_namespace(
+        "hash",
+        Box::new(move |env| {
+            Md5::declare(env)?;
+            Sha1::declare(env)?;
+            Sha256::declare(env)?;
+            Sha512::declare(env)?;
+            Ok(())
+        }))?;
+    Ok(())
+}
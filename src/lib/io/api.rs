@@ -0,0 +1,459 @@
+use std::io::Read;
+
+use ordered_map::OrderedMap;
+use reqwest::header::HeaderMap;
+use reqwest::Method;
+
+use super::json::{from_json, to_json};
+use crate::lang::argument::ArgumentHandler;
+use crate::lang::command::Command;
+use crate::lang::command::OutputType::{Known, Unknown};
+use crate::lang::errors::{CrushResult, argument_error, data_error, error, to_crush_error};
+use crate::lang::execution_context::{ExecutionContext, This};
+use crate::lang::list::List;
+use crate::lang::ordered_string_map::OrderedStringMap;
+use crate::lang::r#struct::Struct;
+use crate::lang::scope::ScopeLoader;
+use crate::lang::value::{Value, ValueType};
+use signature::signature;
+
+/** The methods of the structs returned by api:new. */
+fn methods() -> CrushResult<OrderedMap<String, Command>> {
+    let mut res: OrderedMap<String, Command> = OrderedMap::new();
+    let path = vec!["global", "io", "api", "client"];
+    Get::declare_method(&mut res, &path)?;
+    Post::declare_method(&mut res, &path)?;
+    Put::declare_method(&mut res, &path)?;
+    Patch::declare_method(&mut res, &path)?;
+    Delete::declare_method(&mut res, &path)?;
+    Ok(res)
+}
+
+/** The settings of a client, stored as members of the struct returned by api:new. */
+struct Client {
+    base: String,
+    headers: Vec<String>,
+    token: Option<String>,
+    username: Option<String>,
+    password: Option<String>,
+}
+
+fn optional_string(value: Option<Value>) -> Option<String> {
+    match value {
+        Some(Value::String(s)) => Some(s),
+        _ => None,
+    }
+}
+
+fn optional_value(value: Option<String>) -> Value {
+    value.map(Value::String).unwrap_or(Value::Empty())
+}
+
+impl Client {
+    fn from_struct(handle: Struct) -> CrushResult<Client> {
+        let base = match handle.get("base") {
+            Some(Value::String(s)) => s,
+            _ => return argument_error("Expected an api client created by api:new"),
+        };
+        let headers = match handle.get("headers") {
+            Some(Value::List(l)) => l.dump().iter().map(|h| h.to_string()).collect(),
+            _ => Vec::new(),
+        };
+        Ok(Client {
+            base,
+            headers,
+            token: optional_string(handle.get("token")),
+            username: optional_string(handle.get("username")),
+            password: optional_string(handle.get("password")),
+        })
+    }
+
+    fn into_struct(self) -> CrushResult<Struct> {
+        let class = Struct::new(
+            methods()?.iter().map(|(name, cmd)| (name.clone(), Value::Command(cmd.as_ref().clone()))).collect(),
+            None);
+        Ok(Struct::new(
+            vec![
+                ("base".to_string(), Value::String(self.base)),
+                ("headers".to_string(), Value::List(List::new(ValueType::String, self.headers.into_iter().map(Value::String).collect()))),
+                ("token".to_string(), optional_value(self.token)),
+                ("username".to_string(), optional_value(self.username)),
+                ("password".to_string(), optional_value(self.password)),
+            ],
+            Some(class)))
+    }
+
+    /** Paths are relative to the base URL, unless they are full URLs themselves. */
+    fn url(&self, path: &str) -> String {
+        if path.starts_with("http://") || path.starts_with("https://") {
+            path.to_string()
+        } else if path.is_empty() {
+            self.base.clone()
+        } else {
+            format!("{}/{}", self.base.trim_end_matches('/'), path.trim_start_matches('/'))
+        }
+    }
+
+    fn send(
+        &self,
+        method: Method,
+        url: &str,
+        query: &[(String, String)],
+        body: &Option<serde_json::Value>,
+    ) -> CrushResult<(HeaderMap, serde_json::Value)> {
+        let mut request = reqwest::blocking::Client::new()
+            .request(method.clone(), url)
+            .query(query)
+            .header("Accept", "application/json");
+        for header in &self.headers {
+            match header.splitn(2, ':').collect::<Vec<_>>().as_slice() {
+                [name, value] => request = request.header(name.trim(), value.trim()),
+                _ => return argument_error(format!("Bad header format {}", header).as_str()),
+            }
+        }
+        if let Some(token) = &self.token {
+            request = request.bearer_auth(token);
+        } else if let Some(username) = &self.username {
+            request = request.basic_auth(username, self.password.as_ref());
+        }
+        if let Some(body) = body {
+            request = request.header("Content-Type", "application/json").body(body.to_string());
+        }
+
+        let mut response = to_crush_error(request.send())?;
+        let status = response.status();
+        let mut data = Vec::new();
+        to_crush_error(response.read_to_end(&mut data))?;
+        if !status.is_success() {
+            let text: String = String::from_utf8_lossy(&data).trim().chars().take(200).collect();
+            return error(format!("{} {} failed with status {}: {}", method, url, status, text).as_str());
+        }
+        let json = if data.iter().all(|b| b.is_ascii_whitespace()) {
+            serde_json::Value::Null
+        } else {
+            to_crush_error(serde_json::from_slice(&data))?
+        };
+        Ok((response.headers().clone(), json))
+    }
+}
+
+fn query_parameters(query: &OrderedStringMap<Value>) -> Vec<(String, String)> {
+    query.iter().map(|(name, value)| (name.clone(), value.to_string())).collect()
+}
+
+/** The URL of the page with rel="next" in a Link header, as used by e.g. GitHub and GitLab. */
+fn link_next(headers: &HeaderMap) -> Option<String> {
+    for header in headers.get_all("link") {
+        for link in header.to_str().ok()?.split(',') {
+            let mut parts = link.split(';');
+            let url = parts.next()?.trim().trim_start_matches('<').trim_end_matches('>');
+            if parts.any(|p| matches!(p.trim().replace('"', "").as_str(), "rel=next")) {
+                return Some(url.to_string());
+            }
+        }
+    }
+    None
+}
+
+/**
+The items of a page. Either the body is a list, or it's an object with the items in a member
+that is either specified or the only member that is a list.
+*/
+fn items(body: serde_json::Value, field: &Option<String>) -> CrushResult<Vec<serde_json::Value>> {
+    match (body, field) {
+        (serde_json::Value::Null, _) => Ok(Vec::new()),
+        (serde_json::Value::Array(items), None) => Ok(items),
+        (serde_json::Value::Object(mut object), Some(field)) => match object.remove(field) {
+            Some(serde_json::Value::Array(items)) => Ok(items),
+            None | Some(serde_json::Value::Null) => Ok(Vec::new()),
+            Some(_) => data_error(format!("Expected member {} of the response to be a list", field).as_str()),
+        },
+        (serde_json::Value::Object(object), None) => {
+            let mut lists = object.into_iter()
+                .filter_map(|(_, v)| match v {
+                    serde_json::Value::Array(items) => Some(items),
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+            match lists.len() {
+                1 => Ok(lists.remove(0)),
+                _ => data_error("Could not tell which member of the response contains the items, use the items parameter"),
+            }
+        }
+        _ => data_error("Expected the response to contain a list of items"),
+    }
+}
+
+fn request(
+    context: ExecutionContext,
+    method: Method,
+    path: &str,
+    query: &OrderedStringMap<Value>,
+    body: Option<Value>,
+) -> CrushResult<()> {
+    let client = Client::from_struct(context.this.r#struct()?)?;
+    let body = body.map(to_json).transpose()?;
+    let (_, json) = client.send(method, &client.url(path), &query_parameters(query), &body)?;
+    context.output.send(from_json(&json)?)
+}
+
+#[signature(
+get,
+can_block = true,
+output = Unknown,
+short = "Make a GET request and return the parsed JSON reply",
+long = "All named arguments that aren't listed below are used as query parameters.",
+long = "",
+long = "With pagination, the items of all pages are combined into a single list or table. The",
+long = "supported pagination schemes are:",
+long = "",
+long = "    * link, follow the URL with rel=\"next\" in the Link header of each reply.",
+long = "    * next, follow the URL in a member of the reply, named by next_field.",
+long = "    * page, increase the query parameter named by page_parameter, starting with 1, until a",
+long = "      page without items is returned.",
+long = "",
+long = "The items of a page are the reply if it is a list. Otherwise, they are the member named by",
+long = "the items parameter, or if not specified, the only member of the reply that is a list.",
+example = "gh:get \"/repos/rust-lang/rust/issues\" state=\"open\" paginate=\"link\" max_pages=3")]
+struct Get {
+    #[description("the path of the resource, relative to the base URL.")]
+    #[default("")]
+    path: String,
+    #[values("link", "next", "page")]
+    #[description("the pagination scheme to follow.")]
+    paginate: Option<String>,
+    #[description("the member of each page that contains the items.")]
+    items: Option<String>,
+    #[default("next")]
+    #[description("the member of each page that contains the URL of the next page.")]
+    next_field: String,
+    #[default("page")]
+    #[description("the query parameter containing the page number.")]
+    page_parameter: String,
+    #[description("the maximum number of pages to read.")]
+    max_pages: Option<i128>,
+    #[named()]
+    #[description("query parameters.")]
+    query: OrderedStringMap<Value>,
+}
+
+fn get(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Get = Get::parse(context.arguments.clone(), &context.printer)?;
+    let scheme = match cfg.paginate.clone() {
+        None => return request(context, Method::GET, &cfg.path, &cfg.query, None),
+        Some(scheme) => scheme,
+    };
+    let client = Client::from_struct(context.this.r#struct()?)?;
+    let mut url = client.url(&cfg.path);
+    let mut query = query_parameters(&cfg.query);
+    let mut page = 1;
+    if scheme == "page" {
+        query.retain(|(name, _)| *name != cfg.page_parameter);
+        query.push((cfg.page_parameter.clone(), page.to_string()));
+    }
+
+    let mut res = Vec::new();
+    let mut pages = 0;
+    loop {
+        let (headers, body) = client.send(Method::GET, &url, &query, &None)?;
+        pages += 1;
+        let next = match scheme.as_str() {
+            "link" => link_next(&headers),
+            "next" => body.get(&cfg.next_field).and_then(|n| n.as_str()).map(|n| client.url(n)),
+            _ => None,
+        };
+        let items = items(body, &cfg.items)?;
+        let empty = items.is_empty();
+        res.extend(items);
+        if cfg.max_pages.map(|max| pages >= max).unwrap_or(false) {
+            break;
+        }
+        if scheme == "page" {
+            if empty {
+                break;
+            }
+            page += 1;
+            if let Some(last) = query.last_mut() {
+                last.1 = page.to_string();
+            }
+        } else {
+            match next {
+                // The URL of the next page already contains all query parameters
+                Some(next) => {
+                    url = next;
+                    query.clear();
+                }
+                None => break,
+            }
+        }
+    }
+    context.output.send(from_json(&serde_json::Value::Array(res))?)
+}
+
+#[signature(
+post,
+can_block = true,
+output = Unknown,
+short = "Make a POST request with a JSON body and return the parsed JSON reply",
+long = "All named arguments except body are used as query parameters.",
+example = "gh:post \"/repos/me/project/issues\" body=(data title=\"Crash on startup\")")]
+struct Post {
+    #[description("the path of the resource, relative to the base URL.")]
+    #[default("")]
+    path: String,
+    #[description("the body of the request, converted to JSON.")]
+    body: Option<Value>,
+    #[named()]
+    #[description("query parameters.")]
+    query: OrderedStringMap<Value>,
+}
+
+fn post(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Post = Post::parse(context.arguments.clone(), &context.printer)?;
+    request(context, Method::POST, &cfg.path, &cfg.query, cfg.body)
+}
+
+#[signature(
+put,
+can_block = true,
+output = Unknown,
+short = "Make a PUT request with a JSON body and return the parsed JSON reply",
+long = "All named arguments except body are used as query parameters.",
+example = "gh:put \"/user/starred/rust-lang/rust\"")]
+struct Put {
+    #[description("the path of the resource, relative to the base URL.")]
+    #[default("")]
+    path: String,
+    #[description("the body of the request, converted to JSON.")]
+    body: Option<Value>,
+    #[named()]
+    #[description("query parameters.")]
+    query: OrderedStringMap<Value>,
+}
+
+fn put(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Put = Put::parse(context.arguments.clone(), &context.printer)?;
+    request(context, Method::PUT, &cfg.path, &cfg.query, cfg.body)
+}
+
+#[signature(
+patch,
+can_block = true,
+output = Unknown,
+short = "Make a PATCH request with a JSON body and return the parsed JSON reply",
+long = "All named arguments except body are used as query parameters.",
+example = "gh:patch \"/repos/me/project/issues/12\" body=(data state=\"closed\")")]
+struct Patch {
+    #[description("the path of the resource, relative to the base URL.")]
+    #[default("")]
+    path: String,
+    #[description("the body of the request, converted to JSON.")]
+    body: Option<Value>,
+    #[named()]
+    #[description("query parameters.")]
+    query: OrderedStringMap<Value>,
+}
+
+fn patch(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Patch = Patch::parse(context.arguments.clone(), &context.printer)?;
+    request(context, Method::PATCH, &cfg.path, &cfg.query, cfg.body)
+}
+
+#[signature(
+delete,
+can_block = true,
+output = Unknown,
+short = "Make a DELETE request and return the parsed JSON reply, if any",
+long = "All named arguments are used as query parameters.",
+example = "gh:delete \"/user/starred/rust-lang/rust\"")]
+struct Delete {
+    #[description("the path of the resource, relative to the base URL.")]
+    #[default("")]
+    path: String,
+    #[named()]
+    #[description("query parameters.")]
+    query: OrderedStringMap<Value>,
+}
+
+fn delete(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Delete = Delete::parse(context.arguments.clone(), &context.printer)?;
+    request(context, Method::DELETE, &cfg.path, &cfg.query, None)
+}
+
+#[signature(
+new,
+can_block = false,
+output = Known(ValueType::Struct),
+short = "Create a client for a JSON web API",
+long = "The client is a struct holding the base URL, the headers to send with every request and the",
+long = "credentials to use, with the methods get, post, put, patch and delete. The methods take a",
+long = "path relative to the base URL, send and receive JSON, and fail unless the reply has a",
+long = "successful status. A token is sent as a bearer token, otherwise a username and password",
+long = "are used for basic authentication.",
+example = "gh := (api:new \"https://api.github.com\" token=(env:get \"GITHUB_TOKEN\"))")]
+struct New {
+    #[description("the URL that all paths are relative to.")]
+    base: String,
+    #[description("HTTP headers to send with every request, on the form \"key:value\".")]
+    header: Vec<String>,
+    #[description("a bearer token.")]
+    token: Option<String>,
+    #[description("the username for basic authentication.")]
+    username: Option<String>,
+    #[description("the password for basic authentication.")]
+    password: Option<String>,
+}
+
+fn new(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: New = New::parse(context.arguments, &context.printer)?;
+    if !cfg.base.starts_with("http://") && !cfg.base.starts_with("https://") {
+        return argument_error("Expected the base URL to start with http:// or https://");
+    }
+    for header in &cfg.header {
+        if !header.contains(':') {
+            return argument_error(format!("Bad header format {}", header).as_str());
+        }
+    }
+    context.output.send(Value::Struct(Client {
+        base: cfg.base,
+        headers: cfg.header,
+        token: cfg.token,
+        username: cfg.username,
+        password: cfg.password,
+    }.into_struct()?))
+}
+
+pub fn declare(root: &mut ScopeLoader) -> CrushResult<()> {
+    root.create_lazy_namespace(
+        "api",
+        Box::new(move |env| {
+            New::declare(env)?;
+            Ok(())
+        }))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::header::HeaderValue;
+
+    #[test]
+    fn test_link_next() {
+        let mut headers = HeaderMap::new();
+        headers.insert("link", HeaderValue::from_static(
+            "<https://api.example.com/items?page=3>; rel=\"next\", <https://api.example.com/items?page=9>; rel=\"last\""));
+        assert_eq!(link_next(&headers), Some("https://api.example.com/items?page=3".to_string()));
+        headers.insert("link", HeaderValue::from_static("<https://api.example.com/items?page=1>; rel=\"first\""));
+        assert_eq!(link_next(&headers), None);
+    }
+
+    #[test]
+    fn test_items() {
+        let body: serde_json::Value = serde_json::from_str("{\"count\": 2, \"results\": [1, 2], \"next\": null}").unwrap();
+        assert_eq!(items(body.clone(), &None).unwrap().len(), 2);
+        assert_eq!(items(body, &Some("missing".to_string())).unwrap().len(), 0);
+        let body: serde_json::Value = serde_json::from_str("{\"a\": [1], \"b\": [2]}").unwrap();
+        assert!(items(body, &None).is_err());
+    }
+}
@@ -0,0 +1,292 @@
+use std::fs;
+use std::io::Read;
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
+use std::path::{Component, Path, PathBuf};
+
+use chrono::{DateTime, Local};
+use lazy_static::lazy_static;
+
+use crate::lang::scope::ScopeLoader;
+use crate::lang::errors::{CrushResult, argument_error, data_error, to_crush_error};
+use crate::lang::{value::Value, value::ValueType, table::ColumnType, table::Row};
+use crate::lang::binary::BinaryReader;
+use crate::lang::files::Files;
+use crate::lang::stream::ValueReceiver;
+
+mod tar;
+mod zip;
+
+lazy_static! {
+    static ref LIST_OUTPUT_TYPE: Vec<ColumnType> = vec![
+        ColumnType::new("name", ValueType::File),
+        ColumnType::new("type", ValueType::String),
+        ColumnType::new("size", ValueType::Integer),
+        ColumnType::new("mode", ValueType::Integer),
+        ColumnType::new("modified", ValueType::Time),
+    ];
+    static ref EXTRACT_OUTPUT_TYPE: Vec<ColumnType> = vec![
+        ColumnType::new("name", ValueType::File),
+        ColumnType::new("type", ValueType::String),
+        ColumnType::new("size", ValueType::Integer),
+        ColumnType::new("data", ValueType::BinaryStream),
+    ];
+    static ref EXTRACT_DESTINATION_OUTPUT_TYPE: Vec<ColumnType> = vec![
+        ColumnType::new("file", ValueType::File),
+        ColumnType::new("type", ValueType::String),
+        ColumnType::new("size", ValueType::Integer),
+    ];
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum EntryType {
+    File,
+    Directory,
+    Symlink(String),
+    Hardlink(String),
+    Other(&'static str),
+}
+
+impl EntryType {
+    fn name(&self) -> &'static str {
+        match self {
+            EntryType::File => "file",
+            EntryType::Directory => "directory",
+            EntryType::Symlink(_) => "symlink",
+            EntryType::Hardlink(_) => "hardlink",
+            EntryType::Other(name) => name,
+        }
+    }
+}
+
+/**
+The metadata of a single file in an archive.
+*/
+#[derive(Clone, Debug)]
+pub struct Entry {
+    pub name: String,
+    pub entry_type: EntryType,
+    pub size: u64,
+    pub mode: u32,
+    pub modified: DateTime<Local>,
+}
+
+impl Entry {
+    fn list_row(&self) -> Row {
+        Row::new(vec![
+            Value::File(PathBuf::from(&self.name)),
+            Value::string(self.entry_type.name()),
+            Value::Integer(i128::from(self.size)),
+            Value::Integer(i128::from(self.mode)),
+            Value::Time(self.modified),
+        ])
+    }
+
+    fn extract_row(&self, data: &[u8]) -> Row {
+        Row::new(vec![
+            Value::File(PathBuf::from(&self.name)),
+            Value::string(self.entry_type.name()),
+            Value::Integer(data.len() as i128),
+            Value::BinaryStream(<dyn BinaryReader>::vec(&data.to_vec())),
+        ])
+    }
+
+    fn destination_row(&self, file: PathBuf) -> Row {
+        Row::new(vec![
+            Value::File(file),
+            Value::string(self.entry_type.name()),
+            Value::Integer(i128::from(self.size)),
+        ])
+    }
+
+    /** Create this entry below the specified directory, returning the created file. */
+    fn create(&self, destination: &Path, data: &mut dyn Read) -> CrushResult<PathBuf> {
+        let file = inside(destination, &self.name)?;
+        if let Some(parent) = file.parent() {
+            to_crush_error(fs::create_dir_all(parent))?;
+        }
+        // Replace a symlink created by an earlier entry instead of writing through it
+        if is_symlink(&file) {
+            to_crush_error(fs::remove_file(&file))?;
+        }
+        match &self.entry_type {
+            EntryType::Directory => to_crush_error(fs::create_dir_all(&file))?,
+            EntryType::Symlink(target) => {
+                if fs::symlink_metadata(&file).is_ok() {
+                    to_crush_error(fs::remove_file(&file))?;
+                }
+                to_crush_error(std::os::unix::fs::symlink(target, &file))?;
+            }
+            EntryType::Hardlink(target) => to_crush_error(fs::hard_link(inside(destination, target)?, &file))?,
+            EntryType::File => {
+                let mut out = to_crush_error(fs::File::create(&file))?;
+                to_crush_error(std::io::copy(data, &mut out))?;
+            }
+            EntryType::Other(name) => return data_error(format!("Can't extract {} of type {}", self.name, name).as_str()),
+        }
+        if self.mode != 0 && matches!(self.entry_type, EntryType::File | EntryType::Directory) {
+            to_crush_error(fs::set_permissions(&file, fs::Permissions::from_mode(self.mode & 0o7777)))?;
+        }
+        Ok(file)
+    }
+}
+
+fn is_symlink(file: &Path) -> bool {
+    fs::symlink_metadata(file).map(|m| m.file_type().is_symlink()).unwrap_or(false)
+}
+
+/**
+The location of the specified archive entry inside of the destination directory. Entries that
+would end up outside of it, like ones with absolute names or names containing .., are rejected,
+and so are entries below a symlink inside the destination, which an earlier entry of the same
+archive could have pointed anywhere.
+*/
+fn inside(destination: &Path, name: &str) -> CrushResult<PathBuf> {
+    let relative = Path::new(name);
+    if !relative.components().all(|c| matches!(c, Component::Normal(_) | Component::CurDir)) {
+        return data_error(format!("Refusing to extract {} outside of the destination", name).as_str());
+    }
+    let mut file = destination.to_path_buf();
+    let mut components = relative.components().peekable();
+    while let Some(component) = components.next() {
+        file.push(component);
+        if components.peek().is_some() && is_symlink(&file) {
+            return data_error(
+                format!("Refusing to extract {} through the symlink {}", name, file.to_string_lossy()).as_str());
+        }
+    }
+    Ok(file)
+}
+
+/**
+The name to store a file from disk under. Archive names are always relative.
+*/
+fn archive_name(file: &Path) -> String {
+    file.components()
+        .filter_map(|c| match c {
+            Component::Normal(n) => Some(n.to_string_lossy().to_string()),
+            _ => None,
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/**
+An entry to add to a newly created archive, either a file on disk or a value from the input.
+*/
+pub enum Source {
+    Disk(PathBuf),
+    Data(Vec<u8>),
+}
+
+/**
+Find the entries to put in a new archive. Directories are added recursively, in alphabetical
+order. If no files were specified, the input must be a table with a name and a data column.
+*/
+fn sources(files: Files, input: ValueReceiver) -> CrushResult<Vec<(Entry, Source)>> {
+    let mut res = Vec::new();
+    if files.had_entries() {
+        for file in files.into_vec() {
+            add_disk_source(&file, &mut res)?;
+        }
+    } else {
+        let mut stream = match input.recv()?.stream() {
+            Some(stream) => stream,
+            None => return argument_error("Expected either files to archive or a table stream of entries"),
+        };
+        let types = stream.types().to_vec();
+        let name_idx = types.iter().position(|c| c.name == "name");
+        let data_idx = types.iter().position(|c| c.name == "data");
+        let (name_idx, data_idx) = match (name_idx, data_idx) {
+            (Some(n), Some(d)) => (n, d),
+            _ => return argument_error("Expected the input to have a name column and a data column"),
+        };
+        let now = Local::now();
+        while let Ok(row) = stream.read() {
+            let mut cells = row.into_vec();
+            let name = match cells[name_idx].clone() {
+                Value::File(f) => archive_name(&f),
+                Value::String(s) => archive_name(Path::new(s.as_str())),
+                v => return argument_error(format!("Expected the name of an entry to be a file or a string, got a value of type {}", v.value_type().to_string()).as_str()),
+            };
+            let data = match cells.remove(data_idx) {
+                Value::Binary(b) => b,
+                Value::String(s) => s.as_bytes().to_vec(),
+                Value::BinaryStream(mut b) => {
+                    let mut data = Vec::new();
+                    to_crush_error(b.read_to_end(&mut data))?;
+                    data
+                }
+                v => return argument_error(format!("Expected the data of an entry to be binary data or a string, got a value of type {}", v.value_type().to_string()).as_str()),
+            };
+            res.push((Entry {
+                name,
+                entry_type: EntryType::File,
+                size: data.len() as u64,
+                mode: 0o644,
+                modified: now,
+            }, Source::Data(data)));
+        }
+    }
+    Ok(res)
+}
+
+fn add_disk_source(file: &Path, res: &mut Vec<(Entry, Source)>) -> CrushResult<()> {
+    let meta = to_crush_error(fs::symlink_metadata(file))?;
+    let entry_type = if meta.is_dir() {
+        EntryType::Directory
+    } else if meta.file_type().is_symlink() {
+        EntryType::Symlink(to_crush_error(fs::read_link(file))?.to_string_lossy().to_string())
+    } else if meta.is_file() {
+        EntryType::File
+    } else {
+        // Devices, sockets and fifos are silently skipped
+        return Ok(());
+    };
+    let mut name = archive_name(file);
+    if name.is_empty() {
+        name = ".".to_string();
+    }
+    res.push((Entry {
+        name,
+        size: if entry_type == EntryType::File { meta.len() } else { 0 },
+        entry_type: entry_type.clone(),
+        mode: meta.mode() & 0o7777,
+        modified: DateTime::from(to_crush_error(meta.modified())?),
+    }, Source::Disk(file.to_path_buf())));
+    if entry_type == EntryType::Directory {
+        let mut children = to_crush_error(to_crush_error(fs::read_dir(file))?
+            .map(|e| e.map(|e| e.path()))
+            .collect::<std::io::Result<Vec<_>>>())?;
+        children.sort();
+        for child in children {
+            add_disk_source(&child, res)?;
+        }
+    }
+    Ok(())
+}
+
+/**
+Read the archive from the specified file or from the input.
+*/
+fn archive_reader(files: Files, input: ValueReceiver) -> CrushResult<Box<dyn BinaryReader + Send + Sync>> {
+    if files.had_entries() {
+        <dyn BinaryReader>::paths(vec![archive_file(files)?])
+    } else {
+        files.reader(input)
+    }
+}
+
+fn archive_file(files: Files) -> CrushResult<PathBuf> {
+    let mut files = files.into_vec();
+    if files.len() == 1 {
+        Ok(files.remove(0))
+    } else {
+        argument_error("Expected exactly one archive")
+    }
+}
+
+pub fn declare(root: &mut ScopeLoader) -> CrushResult<()> {
+    tar::declare(root)?;
+    zip::declare(root)?;
+    Ok(())
+}
@@ -0,0 +1,410 @@
+use std::cmp::min;
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::path::PathBuf;
+
+use chrono::{Local, TimeZone};
+
+use crate::lang::execution_context::ExecutionContext;
+use crate::lang::errors::{CrushResult, data_error, to_crush_error};
+use crate::lang::scope::ScopeLoader;
+use crate::lang::value::{Value, ValueType};
+use crate::lang::command::OutputType::Known;
+use crate::lang::binary::binary_channel;
+use crate::lang::files::Files;
+use signature::signature;
+use crate::lang::argument::ArgumentHandler;
+use super::{Entry, EntryType, Source, LIST_OUTPUT_TYPE, EXTRACT_OUTPUT_TYPE, EXTRACT_DESTINATION_OUTPUT_TYPE, archive_reader, sources};
+
+const BLOCK_SIZE: u64 = 512;
+
+fn padding(size: u64) -> u64 {
+    (BLOCK_SIZE - size % BLOCK_SIZE) % BLOCK_SIZE
+}
+
+fn parse_string(field: &[u8]) -> String {
+    let end = field.iter().position(|b| *b == 0).unwrap_or(field.len());
+    String::from_utf8_lossy(&field[..end]).to_string()
+}
+
+/**
+Numeric fields are octal text, except for values too large for that, which are stored in
+binary with the high bit of the first byte set.
+*/
+fn parse_number(field: &[u8]) -> CrushResult<u64> {
+    if field[0] & 0x80 != 0 {
+        return Ok(field[1..].iter().fold(u64::from(field[0] & 0x7f), |acc, b| (acc << 8) | u64::from(*b)));
+    }
+    let text = parse_string(field);
+    let text = text.trim();
+    if text.is_empty() {
+        Ok(0)
+    } else {
+        match u64::from_str_radix(text, 8) {
+            Ok(n) => Ok(n),
+            Err(_) => data_error(format!("Invalid number {} in tar header", text).as_str()),
+        }
+    }
+}
+
+fn put_string(field: &mut [u8], value: &str) {
+    let len = min(field.len(), value.len());
+    field[..len].copy_from_slice(&value.as_bytes()[..len]);
+}
+
+fn put_number(field: &mut [u8], value: u64) {
+    let digits = field.len() - 1;
+    let text = format!("{:0width$o}", value, width = digits);
+    if text.len() <= digits {
+        put_string(field, &text);
+    } else {
+        for (idx, b) in field.iter_mut().rev().enumerate() {
+            *b = if idx < 8 { (value >> (8 * idx)) as u8 } else { 0 };
+        }
+        field[0] |= 0x80;
+    }
+}
+
+fn checksum(header: &[u8; 512]) -> u64 {
+    header.iter()
+        .enumerate()
+        .map(|(idx, b)| if (148..156).contains(&idx) { 32 } else { u64::from(*b) })
+        .sum()
+}
+
+/** The records of a pax extended header, each of the form "<length> <key>=<value>\n". */
+fn parse_pax(data: &[u8]) -> HashMap<String, String> {
+    let mut res = HashMap::new();
+    let mut rest = data;
+    while let Some(space) = rest.iter().position(|b| *b == b' ') {
+        let len = match String::from_utf8_lossy(&rest[..space]).parse::<usize>() {
+            Ok(len) if len > space + 1 && len <= rest.len() => len,
+            _ => break,
+        };
+        let record = String::from_utf8_lossy(&rest[space + 1..len - 1]).to_string();
+        if let Some(eq) = record.find('=') {
+            res.insert(record[..eq].to_string(), record[eq + 1..].to_string());
+        }
+        rest = &rest[len..];
+    }
+    res
+}
+
+/**
+Reads the entries of a tar archive in order. After next has returned an entry, the data of
+that entry can be read from the reader itself.
+*/
+struct TarReader<R: Read> {
+    input: R,
+    remaining: u64,
+    padding: u64,
+}
+
+impl<R: Read> TarReader<R> {
+    fn new(input: R) -> TarReader<R> {
+        TarReader { input, remaining: 0, padding: 0 }
+    }
+
+    /** Read a block, returning false on a clean end of file. */
+    fn block(&mut self, block: &mut [u8; 512]) -> CrushResult<bool> {
+        let mut read = 0;
+        while read < block.len() {
+            match to_crush_error(self.input.read(&mut block[read..]))? {
+                0 if read == 0 => return Ok(false),
+                0 => return data_error("Unexpected end of tar archive"),
+                n => read += n,
+            }
+        }
+        Ok(true)
+    }
+
+    fn skip(&mut self) -> CrushResult<()> {
+        to_crush_error(std::io::copy(self, &mut std::io::sink()))?;
+        let padding = self.padding;
+        self.padding = 0;
+        to_crush_error(std::io::copy(&mut (&mut self.input).take(padding), &mut std::io::sink()))?;
+        Ok(())
+    }
+
+    fn data(&mut self) -> CrushResult<Vec<u8>> {
+        let mut res = Vec::new();
+        to_crush_error(self.read_to_end(&mut res))?;
+        Ok(res)
+    }
+
+    fn next(&mut self) -> CrushResult<Option<Entry>> {
+        let mut long_name = None;
+        let mut long_link = None;
+        let mut pax = HashMap::new();
+        loop {
+            self.skip()?;
+            let mut header = [0u8; 512];
+            if !self.block(&mut header)? || header.iter().all(|b| *b == 0) {
+                return Ok(None);
+            }
+            if parse_number(&header[148..156]).ok() != Some(checksum(&header)) {
+                return data_error("Invalid tar header checksum, the input is not a tar archive");
+            }
+            self.remaining = parse_number(&header[124..136])?;
+            self.padding = padding(self.remaining);
+            let flag = header[156];
+            match flag {
+                b'L' => long_name = Some(parse_string(&self.data()?)),
+                b'K' => long_link = Some(parse_string(&self.data()?)),
+                b'x' => pax = parse_pax(&self.data()?),
+                b'g' => {}
+                _ => {
+                    let mut name = parse_string(&header[0..100]);
+                    let prefix = parse_string(&header[345..500]);
+                    if &header[257..262] == b"ustar" && !prefix.is_empty() {
+                        name = format!("{}/{}", prefix, name);
+                    }
+                    let name = pax.remove("path").or_else(|| long_name.take()).unwrap_or(name);
+                    let link = pax.remove("linkpath").or_else(|| long_link.take()).unwrap_or_else(|| parse_string(&header[157..257]));
+                    if let Some(size) = pax.get("size").and_then(|s| s.parse::<u64>().ok()) {
+                        self.remaining = size;
+                        self.padding = padding(size);
+                    }
+                    let modified = match pax.get("mtime").and_then(|s| s.parse::<f64>().ok()) {
+                        Some(seconds) => seconds as i64,
+                        None => parse_number(&header[136..148])? as i64,
+                    };
+                    let entry_type = match flag {
+                        b'0' | b'\0' | b'7' if name.ends_with('/') => EntryType::Directory,
+                        b'0' | b'\0' | b'7' => EntryType::File,
+                        b'1' => EntryType::Hardlink(link),
+                        b'2' => EntryType::Symlink(link),
+                        b'3' => EntryType::Other("char_device"),
+                        b'4' => EntryType::Other("block_device"),
+                        b'5' => EntryType::Directory,
+                        b'6' => EntryType::Other("fifo"),
+                        _ => EntryType::Other("unknown"),
+                    };
+                    return Ok(Some(Entry {
+                        name: name.trim_end_matches('/').to_string(),
+                        entry_type,
+                        size: self.remaining,
+                        mode: parse_number(&header[100..108])? as u32 & 0o7777,
+                        modified: Local.timestamp(modified, 0),
+                    }));
+                }
+            }
+        }
+    }
+}
+
+impl<R: Read> Read for TarReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let max = min(buf.len() as u64, self.remaining) as usize;
+        if max == 0 {
+            return Ok(0);
+        }
+        let n = self.input.read(&mut buf[..max])?;
+        if n == 0 {
+            return Err(std::io::Error::new(std::io::ErrorKind::UnexpectedEof, "Unexpected end of tar archive"));
+        }
+        self.remaining -= n as u64;
+        Ok(n)
+    }
+}
+
+fn write_padded(out: &mut dyn Write, data: &mut dyn Read, size: u64) -> std::io::Result<()> {
+    let written = std::io::copy(&mut data.take(size), out)?;
+    // Files that shrunk while being archived are padded with zeros to the size in the header
+    out.write_all(&vec![0u8; (size - written + padding(size)) as usize])
+}
+
+struct Header<'a> {
+    prefix: &'a str,
+    name: &'a str,
+    flag: u8,
+    size: u64,
+    mode: u32,
+    modified: i64,
+    link: &'a str,
+}
+
+impl Header<'_> {
+    fn write(&self, out: &mut dyn Write) -> std::io::Result<()> {
+        let mut header = [0u8; 512];
+        put_string(&mut header[0..100], self.name);
+        put_number(&mut header[100..108], u64::from(self.mode));
+        put_number(&mut header[108..116], 0);
+        put_number(&mut header[116..124], 0);
+        put_number(&mut header[124..136], self.size);
+        put_number(&mut header[136..148], if self.modified < 0 { 0 } else { self.modified as u64 });
+        header[156] = self.flag;
+        put_string(&mut header[157..257], self.link);
+        put_string(&mut header[257..263], "ustar\0");
+        put_string(&mut header[263..265], "00");
+        put_string(&mut header[345..500], self.prefix);
+        let sum = format!("{:06o}\0 ", checksum(&header));
+        put_string(&mut header[148..156], &sum);
+        out.write_all(&header)
+    }
+}
+
+/** Write a GNU extension entry holding a name that is too long for the header. */
+fn write_long_name(out: &mut dyn Write, flag: u8, name: &str) -> std::io::Result<()> {
+    let data = format!("{}\0", name);
+    Header { prefix: "", name: "././@LongLink", flag, size: data.len() as u64, mode: 0, modified: 0, link: "" }.write(out)?;
+    write_padded(out, &mut data.as_bytes(), data.len() as u64)
+}
+
+/**
+Write the header of an entry. Names that don't fit in the header are split into a prefix and
+a name if possible, and written as a separate GNU long name entry otherwise.
+*/
+fn write_entry_header(out: &mut dyn Write, entry: &Entry) -> std::io::Result<()> {
+    let (flag, link, size) = match &entry.entry_type {
+        EntryType::Directory => (b'5', "", 0),
+        EntryType::Symlink(target) => (b'2', target.as_str(), 0),
+        EntryType::Hardlink(target) => (b'1', target.as_str(), 0),
+        _ => (b'0', "", entry.size),
+    };
+    let name = if entry.entry_type == EntryType::Directory {
+        format!("{}/", entry.name)
+    } else {
+        entry.name.clone()
+    };
+    if link.len() > 100 {
+        write_long_name(out, b'K', link)?;
+    }
+    let mut prefix = "";
+    let mut short_name = name.as_str();
+    if name.len() > 100 {
+        let split = name.char_indices()
+            .map(|(idx, _)| idx)
+            .find(|idx| name.as_bytes()[*idx] == b'/' && *idx <= 155 && name.len() - idx - 1 <= 100);
+        match split {
+            Some(idx) if idx + 1 < name.len() => {
+                prefix = &name[..idx];
+                short_name = &name[idx + 1..];
+            }
+            _ => write_long_name(out, b'L', &name)?,
+        }
+    }
+    Header { prefix, name: short_name, flag, size, mode: entry.mode, modified: entry.modified.timestamp(), link }.write(out)
+}
+
+fn write_archive(out: &mut dyn Write, sources: Vec<(Entry, Source)>) -> CrushResult<()> {
+    for (entry, source) in sources {
+        to_crush_error(write_entry_header(out, &entry))?;
+        if entry.entry_type == EntryType::File {
+            match source {
+                Source::Disk(file) => {
+                    let mut data = to_crush_error(std::fs::File::open(file))?;
+                    to_crush_error(write_padded(out, &mut data, entry.size))?;
+                }
+                Source::Data(data) => to_crush_error(write_padded(out, &mut data.as_slice(), entry.size))?,
+            }
+        }
+    }
+    to_crush_error(out.write_all(&[0u8; 1024]))?;
+    to_crush_error(out.flush())
+}
+
+#[signature(
+list,
+can_block = true,
+short = "List the entries of a tar archive",
+long = "A row is output for every entry, containing its name, its type, which is one of file,",
+long = "directory, symlink, hardlink, fifo, char_device and block_device, its size, its permission",
+long = "bits and the time it was last modified. The archive is read as a stream, so listing even",
+long = "a very large archive never reads more than one entry into memory.",
+example = "tar:list ./src.tar | where {type == \"file\"} | sort ^size",
+output = Known(ValueType::TableStream(LIST_OUTPUT_TYPE.clone())))]
+struct List {
+    #[unnamed()]
+    #[description("the archive to list (read the input if no archive is specified).")]
+    file: Files,
+}
+
+fn list(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: List = List::parse(context.arguments, &context.printer)?;
+    let mut reader = TarReader::new(archive_reader(cfg.file, context.input)?);
+    let output = context.output.initialize(LIST_OUTPUT_TYPE.clone())?;
+    while let Some(entry) = reader.next()? {
+        output.send(entry.list_row())?;
+    }
+    Ok(())
+}
+
+#[signature(
+extract,
+can_block = true,
+short = "Extract the entries of a tar archive",
+long = "If a destination is specified, all entries are extracted into that directory, and a row",
+long = "containing the created file, its type and its size is output for every entry. Entries",
+long = "with names that would end up outside of the destination are rejected.",
+long = "",
+long = "Otherwise, a row containing the name, type and size of every entry, and its data as a",
+long = "binary stream, is output, so that entries can be filtered and read without touching disk.",
+example = "tar:extract ./src.tar | where {name == src/main.rs} | first | member ^data | lines:from",
+output = Known(ValueType::TableStream(EXTRACT_OUTPUT_TYPE.clone())))]
+struct Extract {
+    #[unnamed()]
+    #[description("the archive to extract (read the input if no archive is specified).")]
+    file: Files,
+    #[description("the directory to extract the archive into.")]
+    destination: Option<PathBuf>,
+}
+
+fn extract(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Extract = Extract::parse(context.arguments, &context.printer)?;
+    let mut reader = TarReader::new(archive_reader(cfg.file, context.input)?);
+    match cfg.destination {
+        Some(destination) => {
+            let output = context.output.initialize(EXTRACT_DESTINATION_OUTPUT_TYPE.clone())?;
+            while let Some(entry) = reader.next()? {
+                let file = entry.create(&destination, &mut reader)?;
+                output.send(entry.destination_row(file))?;
+            }
+        }
+        None => {
+            let output = context.output.initialize(EXTRACT_OUTPUT_TYPE.clone())?;
+            while let Some(entry) = reader.next()? {
+                let data = reader.data()?;
+                output.send(entry.extract_row(&data))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[signature(
+create,
+can_block = true,
+short = "Create a tar archive",
+long = "The specified files are added to the archive, directories with all their contents. If no",
+long = "files are specified, the input must be a table with a name column, containing files or",
+long = "strings, and a data column, containing strings or binary data, with one row per entry.",
+long = "",
+long = "The archive is output as a binary stream.",
+example = "tar:create ./src | bin:to ./src.tar",
+output = Known(ValueType::BinaryStream))]
+struct Create {
+    #[unnamed()]
+    #[description("the files and directories to archive (read entries from the input if none are specified).")]
+    files: Files,
+}
+
+fn create(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Create = Create::parse(context.arguments, &context.printer)?;
+    let sources = sources(cfg.files, context.input)?;
+    let (mut out, reader) = binary_channel();
+    context.output.send(Value::BinaryStream(reader))?;
+    write_archive(out.as_mut(), sources)
+}
+
+pub fn declare(root: &mut ScopeLoader) -> CrushResult<()> {
+    root.create_lazy_namespace(
+        "tar",
+        Box::new(move |env| {
+            List::declare(env)?;
+            Extract::declare(env)?;
+            Create::declare(env)?;
+            Ok(())
+        }))?;
+    Ok(())
+}
@@ -0,0 +1,382 @@
+use std::fs;
+use std::io::{Cursor, Read, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+
+use chrono::{DateTime, Datelike, Local, TimeZone, Timelike};
+
+use crate::lang::execution_context::ExecutionContext;
+use crate::lang::errors::{CrushResult, data_error, to_crush_error};
+use crate::lang::scope::ScopeLoader;
+use crate::lang::value::{Value, ValueType};
+use crate::lang::command::OutputType::Known;
+use crate::lang::binary::binary_channel;
+use crate::lang::files::Files;
+use crate::lang::stream::ValueReceiver;
+use crate::util::crc32::crc32;
+use crate::util::deflate::{deflate, inflate};
+use signature::signature;
+use crate::lang::argument::ArgumentHandler;
+use super::{Entry, EntryType, Source, LIST_OUTPUT_TYPE, EXTRACT_OUTPUT_TYPE, EXTRACT_DESTINATION_OUTPUT_TYPE, archive_file, sources};
+
+const LOCAL_HEADER_SIGNATURE: u32 = 0x0403_4b50;
+const CENTRAL_HEADER_SIGNATURE: u32 = 0x0201_4b50;
+const END_SIGNATURE: u32 = 0x0605_4b50;
+/** The size of the end of central directory record, without the trailing comment. */
+const END_SIZE: usize = 22;
+
+const STORED: u16 = 0;
+const DEFLATED: u16 = 8;
+
+const S_IFMT: u32 = 0o170_000;
+const S_IFDIR: u32 = 0o040_000;
+const S_IFLNK: u32 = 0o120_000;
+const S_IFREG: u32 = 0o100_000;
+
+fn u16_at(data: &[u8], offset: usize) -> u16 {
+    u16::from(data[offset]) | (u16::from(data[offset + 1]) << 8)
+}
+
+fn u32_at(data: &[u8], offset: usize) -> u32 {
+    u32::from(u16_at(data, offset)) | (u32::from(u16_at(data, offset + 2)) << 16)
+}
+
+fn put_u16(out: &mut Vec<u8>, value: u16) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_u32(out: &mut Vec<u8>, value: u32) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+fn from_dos_time(date: u16, time: u16) -> DateTime<Local> {
+    Local.ymd_opt(
+        1980 + i32::from(date >> 9),
+        u32::from((date >> 5) & 0xf),
+        u32::from(date & 0x1f))
+        .and_hms_opt(u32::from(time >> 11), u32::from((time >> 5) & 0x3f), 2 * u32::from(time & 0x1f))
+        .single()
+        .unwrap_or_else(|| Local.timestamp(0, 0))
+}
+
+/** Returns the date and the time, both in the MS-DOS format zip uses. */
+fn to_dos_time(time: &DateTime<Local>) -> (u16, u16) {
+    if time.year() < 1980 {
+        return ((1 << 5) | 1, 0);
+    }
+    (
+        (((time.year() - 1980) as u16) << 9) | ((time.month() as u16) << 5) | time.day() as u16,
+        ((time.hour() as u16) << 11) | ((time.minute() as u16) << 5) | (time.second() as u16 / 2),
+    )
+}
+
+/** An entry of the central directory, which lists all entries at the end of the archive. */
+struct CentralEntry {
+    entry: Entry,
+    flags: u16,
+    method: u16,
+    crc: u32,
+    compressed_size: u64,
+    offset: u64,
+}
+
+trait ReadSeek: Read + Seek {}
+
+impl<T: Read + Seek> ReadSeek for T {}
+
+/**
+Zip archives can only be read by seeking to their central directory, so archives that are
+read from the input are read into memory first.
+*/
+fn open(files: Files, input: ValueReceiver) -> CrushResult<Box<dyn ReadSeek>> {
+    if files.had_entries() {
+        Ok(Box::from(to_crush_error(fs::File::open(archive_file(files)?))?))
+    } else {
+        let mut data = Vec::new();
+        to_crush_error(files.reader(input)?.read_to_end(&mut data))?;
+        Ok(Box::from(Cursor::new(data)))
+    }
+}
+
+fn read_directory(input: &mut dyn ReadSeek) -> CrushResult<Vec<CentralEntry>> {
+    let len = to_crush_error(input.seek(SeekFrom::End(0)))?;
+    // The end record is followed by a comment of at most 65535 bytes
+    let tail_len = std::cmp::min(len, (END_SIZE + 0xffff) as u64);
+    to_crush_error(input.seek(SeekFrom::Start(len - tail_len)))?;
+    let mut tail = Vec::new();
+    to_crush_error(input.read_to_end(&mut tail))?;
+    let end = match (0..(tail.len() + 1).saturating_sub(END_SIZE)).rev().find(|idx| u32_at(&tail, *idx) == END_SIGNATURE) {
+        Some(end) => end,
+        None => return data_error("Not a zip archive"),
+    };
+    let count = u16_at(&tail, end + 10);
+    let directory_size = u32_at(&tail, end + 12);
+    let directory_offset = u32_at(&tail, end + 16);
+    if count == 0xffff || directory_size == 0xffff_ffff || directory_offset == 0xffff_ffff {
+        return data_error("Zip64 archives are not supported");
+    }
+    to_crush_error(input.seek(SeekFrom::Start(u64::from(directory_offset))))?;
+    let mut directory = vec![0u8; directory_size as usize];
+    to_crush_error(input.read_exact(&mut directory))?;
+
+    let mut res = Vec::new();
+    let mut pos = 0;
+    for _ in 0..count {
+        if pos + 46 > directory.len() || u32_at(&directory, pos) != CENTRAL_HEADER_SIGNATURE {
+            return data_error("Corrupt zip central directory");
+        }
+        let header = &directory[pos..];
+        let name_len = usize::from(u16_at(header, 28));
+        let extra_len = usize::from(u16_at(header, 30));
+        let comment_len = usize::from(u16_at(header, 32));
+        if pos + 46 + name_len > directory.len() {
+            return data_error("Corrupt zip central directory");
+        }
+        let name = String::from_utf8_lossy(&header[46..46 + name_len]).to_string();
+        let unix_mode = if u16_at(header, 4) >> 8 == 3 { u32_at(header, 38) >> 16 } else { 0 };
+        let entry_type = if unix_mode & S_IFMT == S_IFLNK {
+            EntryType::Symlink(String::new())
+        } else if unix_mode & S_IFMT == S_IFDIR || name.ends_with('/') {
+            EntryType::Directory
+        } else {
+            EntryType::File
+        };
+        let mode = match (unix_mode & 0o7777, &entry_type) {
+            (0, EntryType::Directory) => 0o755,
+            (0, _) => 0o644,
+            (mode, _) => mode,
+        };
+        res.push(CentralEntry {
+            entry: Entry {
+                name: name.trim_end_matches('/').to_string(),
+                entry_type,
+                size: u64::from(u32_at(header, 24)),
+                mode,
+                modified: from_dos_time(u16_at(header, 14), u16_at(header, 12)),
+            },
+            flags: u16_at(header, 8),
+            method: u16_at(header, 10),
+            crc: u32_at(header, 16),
+            compressed_size: u64::from(u32_at(header, 20)),
+            offset: u64::from(u32_at(header, 42)),
+        });
+        pos += 46 + name_len + extra_len + comment_len;
+    }
+    Ok(res)
+}
+
+fn read_data(input: &mut dyn ReadSeek, entry: &CentralEntry) -> CrushResult<Vec<u8>> {
+    if entry.flags & 1 != 0 {
+        return data_error(format!("Can't extract {}, encrypted entries are not supported", entry.entry.name).as_str());
+    }
+    let mut header = [0u8; 30];
+    to_crush_error(input.seek(SeekFrom::Start(entry.offset)))?;
+    to_crush_error(input.read_exact(&mut header))?;
+    if u32_at(&header, 0) != LOCAL_HEADER_SIGNATURE {
+        return data_error("Corrupt zip local header");
+    }
+    let skip = i64::from(u16_at(&header, 26)) + i64::from(u16_at(&header, 28));
+    to_crush_error(input.seek(SeekFrom::Current(skip)))?;
+    let mut data = vec![0u8; entry.compressed_size as usize];
+    to_crush_error(input.read_exact(&mut data))?;
+    let data = match entry.method {
+        STORED => data,
+        DEFLATED => to_crush_error(inflate(&data))?,
+        method => return data_error(format!("Can't extract {}, unsupported compression method {}", entry.entry.name, method).as_str()),
+    };
+    if crc32(&data) != entry.crc {
+        return data_error(format!("Checksum mismatch in {}", entry.entry.name).as_str());
+    }
+    Ok(data)
+}
+
+fn write_archive(out: &mut dyn Write, sources: Vec<(Entry, Source)>) -> CrushResult<()> {
+    if sources.len() >= 0xffff {
+        return data_error("Zip64 archives are not supported, too many entries");
+    }
+    let mut directory = Vec::new();
+    let mut offset: u64 = 0;
+    for (entry, source) in &sources {
+        let (data, file_type) = match (&entry.entry_type, source) {
+            (EntryType::Directory, _) => (Vec::new(), S_IFDIR),
+            (EntryType::Symlink(target), _) => (target.as_bytes().to_vec(), S_IFLNK),
+            (_, Source::Disk(file)) => (to_crush_error(fs::read(file))?, S_IFREG),
+            (_, Source::Data(data)) => (data.clone(), S_IFREG),
+        };
+        let compressed = to_crush_error(deflate(&data))?;
+        let (method, stored) = if compressed.len() < data.len() { (DEFLATED, &compressed) } else { (STORED, &data) };
+        let name = if entry.entry_type == EntryType::Directory { format!("{}/", entry.name) } else { entry.name.clone() };
+        if data.len() >= 0xffff_ffff || offset >= 0xffff_ffff {
+            return data_error("Zip64 archives are not supported, archive too large");
+        }
+        let (date, time) = to_dos_time(&entry.modified);
+        let crc = crc32(&data);
+
+        let mut common = Vec::new();
+        put_u16(&mut common, 20);
+        // Bit 11 marks names as UTF-8
+        put_u16(&mut common, 1 << 11);
+        put_u16(&mut common, method);
+        put_u16(&mut common, time);
+        put_u16(&mut common, date);
+        put_u32(&mut common, crc);
+        put_u32(&mut common, stored.len() as u32);
+        put_u32(&mut common, data.len() as u32);
+        put_u16(&mut common, name.len() as u16);
+        put_u16(&mut common, 0);
+
+        let mut local = Vec::new();
+        put_u32(&mut local, LOCAL_HEADER_SIGNATURE);
+        local.extend_from_slice(&common);
+        local.extend_from_slice(name.as_bytes());
+        to_crush_error(out.write_all(&local))?;
+        to_crush_error(out.write_all(stored))?;
+
+        put_u32(&mut directory, CENTRAL_HEADER_SIGNATURE);
+        // Made by version 3.0 on unix, so that the permission bits are used
+        put_u16(&mut directory, (3 << 8) | 30);
+        directory.extend_from_slice(&common);
+        put_u16(&mut directory, 0);
+        put_u16(&mut directory, 0);
+        put_u16(&mut directory, 0);
+        let dos_attributes = if file_type == S_IFDIR { 0x10 } else { 0 };
+        put_u32(&mut directory, ((file_type | entry.mode) << 16) | dos_attributes);
+        put_u32(&mut directory, offset as u32);
+        directory.extend_from_slice(name.as_bytes());
+
+        offset += (local.len() + stored.len()) as u64;
+    }
+    if offset >= 0xffff_ffff {
+        return data_error("Zip64 archives are not supported, archive too large");
+    }
+    let mut end = Vec::new();
+    put_u32(&mut end, END_SIGNATURE);
+    put_u16(&mut end, 0);
+    put_u16(&mut end, 0);
+    put_u16(&mut end, sources.len() as u16);
+    put_u16(&mut end, sources.len() as u16);
+    put_u32(&mut end, directory.len() as u32);
+    put_u32(&mut end, offset as u32);
+    put_u16(&mut end, 0);
+    to_crush_error(out.write_all(&directory))?;
+    to_crush_error(out.write_all(&end))?;
+    to_crush_error(out.flush())
+}
+
+#[signature(
+list,
+can_block = true,
+short = "List the entries of a zip archive",
+long = "A row is output for every entry, containing its name, its type, which is one of file,",
+long = "directory and symlink, its uncompressed size, its permission bits and the time it was last",
+long = "modified. Archives read from the input are read into memory first, since the list of",
+long = "entries is stored at the end of a zip archive.",
+example = "zipfile:list ./src.zip | where {type == \"file\"} | sort ^size",
+output = Known(ValueType::TableStream(LIST_OUTPUT_TYPE.clone())))]
+struct List {
+    #[unnamed()]
+    #[description("the archive to list (read the input if no archive is specified).")]
+    file: Files,
+}
+
+fn list(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: List = List::parse(context.arguments, &context.printer)?;
+    let mut input = open(cfg.file, context.input)?;
+    let output = context.output.initialize(LIST_OUTPUT_TYPE.clone())?;
+    for entry in read_directory(input.as_mut())? {
+        output.send(entry.entry.list_row())?;
+    }
+    Ok(())
+}
+
+#[signature(
+extract,
+can_block = true,
+short = "Extract the entries of a zip archive",
+long = "If a destination is specified, all entries are extracted into that directory, and a row",
+long = "containing the created file, its type and its size is output for every entry. Entries",
+long = "with names that would end up outside of the destination are rejected.",
+long = "",
+long = "Otherwise, a row containing the name, type and size of every entry, and its data as a",
+long = "binary stream, is output, so that entries can be filtered and read without touching disk.",
+long = "",
+long = "Entries must be stored or compressed using deflate, which covers nearly all zip archives.",
+long = "Encrypted entries and zip64 archives are not supported.",
+example = "zipfile:extract ./src.zip destination=./src",
+output = Known(ValueType::TableStream(EXTRACT_OUTPUT_TYPE.clone())))]
+struct Extract {
+    #[unnamed()]
+    #[description("the archive to extract (read the input if no archive is specified).")]
+    file: Files,
+    #[description("the directory to extract the archive into.")]
+    destination: Option<PathBuf>,
+}
+
+fn extract(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Extract = Extract::parse(context.arguments, &context.printer)?;
+    let mut input = open(cfg.file, context.input)?;
+    let entries = read_directory(input.as_mut())?;
+    match cfg.destination {
+        Some(destination) => {
+            let output = context.output.initialize(EXTRACT_DESTINATION_OUTPUT_TYPE.clone())?;
+            for central in entries {
+                let data = read_data(input.as_mut(), &central)?;
+                let mut entry = central.entry;
+                if let EntryType::Symlink(_) = entry.entry_type {
+                    entry.entry_type = EntryType::Symlink(String::from_utf8_lossy(&data).to_string());
+                }
+                let file = entry.create(&destination, &mut data.as_slice())?;
+                output.send(entry.destination_row(file))?;
+            }
+        }
+        None => {
+            let output = context.output.initialize(EXTRACT_OUTPUT_TYPE.clone())?;
+            for central in entries {
+                let data = read_data(input.as_mut(), &central)?;
+                output.send(central.entry.extract_row(&data))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[signature(
+create,
+can_block = true,
+short = "Create a zip archive",
+long = "The specified files are added to the archive, directories with all their contents. If no",
+long = "files are specified, the input must be a table with a name column, containing files or",
+long = "strings, and a data column, containing strings or binary data, with one row per entry.",
+long = "",
+long = "Entries are compressed using deflate, unless that would make them larger. The archive is",
+long = "output as a binary stream.",
+example = "zipfile:create ./src | bin:to ./src.zip",
+output = Known(ValueType::BinaryStream))]
+struct Create {
+    #[unnamed()]
+    #[description("the files and directories to archive (read entries from the input if none are specified).")]
+    files: Files,
+}
+
+fn create(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Create = Create::parse(context.arguments, &context.printer)?;
+    let sources = sources(cfg.files, context.input)?;
+    let (mut out, reader) = binary_channel();
+    context.output.send(Value::BinaryStream(reader))?;
+    write_archive(out.as_mut(), sources)
+}
+
+/**
+The namespace is called zipfile rather than zip, since zip is already the name of the command
+that combines the rows of two streams.
+*/
+pub fn declare(root: &mut ScopeLoader) -> CrushResult<()> {
+    root.create_lazy_namespace(
+        "zipfile",
+        Box::new(move |env| {
+            List::declare(env)?;
+            Extract::declare(env)?;
+            Create::declare(env)?;
+            Ok(())
+        }))?;
+    Ok(())
+}
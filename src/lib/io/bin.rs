@@ -7,6 +7,7 @@ use crate::lang::files::Files;
 use signature::signature;
 use crate::lang::argument::ArgumentHandler;
 use crate::lang::scope::ScopeLoader;
+use std::io::Write;
 
 #[signature(
 from,
@@ -40,7 +41,15 @@ pub fn to(context: ExecutionContext) -> CrushResult<()> {
             to_crush_error(std::io::copy(input.as_mut(), out.as_mut()))?;
             Ok(())
         }
-        _ => argument_error("Expected a binary stream"),
+        Value::Binary(b) => {
+            let mut out = cfg.file.writer(context.output)?;
+            to_crush_error(out.write_all(&b))
+        }
+        Value::String(s) => {
+            let mut out = cfg.file.writer(context.output)?;
+            to_crush_error(out.write_all(s.as_bytes()))
+        }
+        _ => argument_error("Expected a binary stream, a binary or a string"),
     }
 }
 
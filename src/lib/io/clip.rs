@@ -0,0 +1,181 @@
+use std::io::{ErrorKind, Read, Write};
+use std::process::{Command, Stdio};
+
+use lazy_static::lazy_static;
+
+use crate::lang::argument::ArgumentHandler;
+use crate::lang::command::OutputType::{Known, Unknown};
+use crate::lang::errors::{CrushResult, error, to_crush_error};
+use crate::lang::execution_context::ExecutionContext;
+use crate::lang::scope::ScopeLoader;
+use crate::lang::table::{ColumnType, Row};
+use crate::lang::value::{Value, ValueType};
+use signature::signature;
+
+lazy_static! {
+    static ref LINES_OUTPUT_TYPE: Vec<ColumnType> = vec![ColumnType::new("line", ValueType::String)];
+}
+
+/** The programs that can copy to and paste from the clipboard, in order of preference. */
+fn programs(paste: bool) -> Vec<(&'static str, &'static [&'static str])> {
+    let mut res: Vec<(&'static str, &'static [&'static str])> = Vec::new();
+    if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+        res.push(if paste { ("wl-paste", &["--no-newline"]) } else { ("wl-copy", &[]) });
+    }
+    if std::env::var_os("DISPLAY").is_some() {
+        res.push(if paste { ("xclip", &["-selection", "clipboard", "-o"]) } else { ("xclip", &["-selection", "clipboard"]) });
+        res.push(if paste { ("xsel", &["--clipboard", "--output"]) } else { ("xsel", &["--clipboard", "--input"]) });
+    }
+    res.push(if paste { ("pbpaste", &[]) } else { ("pbcopy", &[]) });
+    res
+}
+
+/** Runs the first clipboard program that is installed, and returns what it wrote. */
+fn run(paste: bool, input: &[u8]) -> CrushResult<Vec<u8>> {
+    for (program, args) in programs(paste) {
+        let mut child = match Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(if paste { Stdio::piped() } else { Stdio::null() })
+            .stderr(Stdio::piped())
+            .spawn() {
+            Ok(child) => child,
+            Err(e) if e.kind() == ErrorKind::NotFound => continue,
+            Err(e) => return to_crush_error(Err(e)),
+        };
+        if let Some(mut stdin) = child.stdin.take() {
+            to_crush_error(stdin.write_all(input))?;
+        }
+        // wl-copy and xclip keep running in the background to serve the clipboard, so the output
+        // of copying is not read
+        let mut output = Vec::new();
+        if paste {
+            if let Some(mut stdout) = child.stdout.take() {
+                to_crush_error(stdout.read_to_end(&mut output))?;
+            }
+        }
+        let status = to_crush_error(child.wait())?;
+        if !status.success() {
+            let mut message = String::new();
+            if let Some(mut stderr) = child.stderr.take() {
+                let _ = stderr.read_to_string(&mut message);
+            }
+            return error(format!("{} failed: {}", program, message.trim()).as_str());
+        }
+        return Ok(output);
+    }
+    error("No clipboard program found, install wl-clipboard, xclip or xsel")
+}
+
+fn cell(value: &Value) -> String {
+    value.to_string().replace(['\t', '\n'], " ")
+}
+
+/** Tables become tab separated values with a header line, so they paste into spreadsheets as cells. */
+fn to_text(value: Value) -> CrushResult<Vec<u8>> {
+    match value {
+        Value::String(s) => Ok(s.into_bytes()),
+        Value::Binary(b) => Ok(b),
+        Value::BinaryStream(mut b) => {
+            let mut res = Vec::new();
+            to_crush_error(b.read_to_end(&mut res))?;
+            Ok(res)
+        }
+        value => match value.stream() {
+            Some(mut stream) => {
+                let mut res = stream.types().iter().map(|t| t.name.clone()).collect::<Vec<_>>().join("\t");
+                res.push('\n');
+                while let Ok(row) = stream.read() {
+                    res.push_str(&row.cells().iter().map(cell).collect::<Vec<_>>().join("\t"));
+                    res.push('\n');
+                }
+                Ok(res.into_bytes())
+            }
+            None => Ok(value.to_string().into_bytes()),
+        },
+    }
+}
+
+#[signature(
+copy,
+can_block = true,
+output = Known(ValueType::Empty),
+short = "Copy a value to the clipboard",
+long = "Strings and binary data are copied as is. Tables, table streams, lists and dicts are copied as",
+long = "tab separated values with a header line, which most spreadsheets paste as cells. Other values",
+long = "are copied as they are printed.",
+long = "",
+long = "The clipboard is accessed using wl-copy, xclip, xsel or pbcopy, whichever is installed.",
+example = "ps | where {cpu > 5.0} | select ^pid ^name ^cpu | clip:copy")]
+struct Copy {
+    #[description("the value to copy. If not given, the input is copied.")]
+    item: Option<Value>,
+}
+
+fn copy(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Copy = Copy::parse(context.arguments, &context.printer)?;
+    let item = match cfg.item {
+        Some(item) => item,
+        None => context.input.recv()?,
+    };
+    run(false, &to_text(item)?)?;
+    context.output.send(Value::Empty())
+}
+
+#[signature(
+paste,
+can_block = true,
+output = Unknown,
+short = "Return the text on the clipboard",
+long = "The text is returned as a string, or as a table stream with one row per line if lines is true.",
+long = "",
+long = "The clipboard is accessed using wl-paste, xclip, xsel or pbpaste, whichever is installed.",
+example = "clip:paste lines=true | where {line =~ re\"ERROR.*\"}")]
+struct Paste {
+    #[default(false)]
+    #[description("split the text into lines.")]
+    lines: bool,
+}
+
+fn paste(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Paste = Paste::parse(context.arguments, &context.printer)?;
+    let text = String::from_utf8_lossy(&run(true, &[])?).to_string();
+    if cfg.lines {
+        let output = context.output.initialize(LINES_OUTPUT_TYPE.clone())?;
+        for line in text.lines() {
+            output.send(Row::new(vec![Value::string(line)]))?;
+        }
+        Ok(())
+    } else {
+        context.output.send(Value::String(text))
+    }
+}
+
+pub fn declare(root: &mut ScopeLoader) -> CrushResult<()> {
+    root.create_lazy_namespace(
+        "clip",
+        Box::new(move |env| {
+            Copy::declare(env)?;
+            Paste::declare(env)?;
+            Ok(())
+        }))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lang::table::Table;
+
+    #[test]
+    fn tables_become_tsv() {
+        let table = Value::Table(Table::new(
+            vec![ColumnType::new("name", ValueType::String), ColumnType::new("n", ValueType::Integer)],
+            vec![
+                Row::new(vec![Value::string("a\tb"), Value::Integer(1)]),
+                Row::new(vec![Value::string("c"), Value::Integer(2)]),
+            ]));
+        assert_eq!(to_text(table).unwrap(), b"name\tn\na b\t1\nc\t2\n".to_vec());
+        assert_eq!(to_text(Value::string("abc")).unwrap(), b"abc".to_vec());
+    }
+}
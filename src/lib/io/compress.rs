@@ -0,0 +1,220 @@
+use std::io::{Error, Read};
+use std::process::{Child, ChildStdout, Stdio};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+
+use crate::lang::argument::ArgumentHandler;
+use crate::lang::binary::{Adapter, BinaryReader};
+use crate::lang::command::OutputType::Known;
+use crate::lang::errors::CrushResult;
+use crate::lang::execution_context::ExecutionContext;
+use crate::lang::files::Files;
+use crate::lang::scope::ScopeLoader;
+use crate::lang::stream::{ValueReceiver, ValueSender};
+use crate::lang::value::{Value, ValueType};
+use crate::util::gzip::{GzipDecoder, GzipEncoder};
+use crate::util::thread::build;
+use signature::signature;
+
+/**
+A reader that pipes its source through an external program and reads the output of that
+program. Used for formats that have no built in implementation.
+*/
+struct ProcessReader {
+    program: &'static str,
+    child: Child,
+    stdout: ChildStdout,
+    feeder: Option<JoinHandle<()>>,
+    stderr: Option<JoinHandle<String>>,
+}
+
+impl ProcessReader {
+    fn spawn(program: &'static str, args: &[&str], mut source: Box<dyn BinaryReader + Send + Sync>) -> std::io::Result<ProcessReader> {
+        let mut child = std::process::Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| Error::new(e.kind(), format!("Failed to run {}: {}", program, e)))?;
+        let mut stdin = child.stdin.take().unwrap();
+        let stdout = child.stdout.take().unwrap();
+        let mut stderr = child.stderr.take().unwrap();
+        // A write error means that the program exited early, that error is reported when reading
+        let feeder = build(program).spawn(move || {
+            let _ = std::io::copy(&mut source, &mut stdin);
+        })?;
+        // Read stderr as it is written, so that a program writing a lot of it doesn't block
+        let stderr = build(program).spawn(move || {
+            let mut message = String::new();
+            let _ = stderr.read_to_string(&mut message);
+            message
+        })?;
+        Ok(ProcessReader { program, child, stdout, feeder: Some(feeder), stderr: Some(stderr) })
+    }
+
+    fn adapter(program: &'static str, args: &'static [&'static str]) -> Adapter {
+        Arc::new(move |source| match ProcessReader::spawn(program, args, source) {
+            Ok(reader) => Box::new(reader),
+            Err(e) => Box::new(FailedReader { error: Some(e) }),
+        })
+    }
+}
+
+impl Read for ProcessReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let count = self.stdout.read(buf)?;
+        if count == 0 && !buf.is_empty() {
+            if let Some(feeder) = self.feeder.take() {
+                let _ = feeder.join();
+                let message = self.stderr.take()
+                    .and_then(|stderr| stderr.join().ok())
+                    .unwrap_or_default();
+                let status = self.child.wait()?;
+                if !status.success() {
+                    return Err(Error::other(format!("{} failed: {}", self.program, message.trim())));
+                }
+            }
+        }
+        Ok(count)
+    }
+}
+
+impl Drop for ProcessReader {
+    fn drop(&mut self) {
+        if self.feeder.is_some() {
+            let _ = self.child.kill();
+            let _ = self.child.wait();
+        }
+    }
+}
+
+/**
+A reader that fails with the error that prevented the real reader from being created.
+*/
+struct FailedReader {
+    error: Option<Error>,
+}
+
+impl Read for FailedReader {
+    fn read(&mut self, _buf: &mut [u8]) -> std::io::Result<usize> {
+        match self.error.take() {
+            Some(e) => Err(e),
+            None => Ok(0),
+        }
+    }
+}
+
+fn gzip_adapter(decompress: bool) -> Adapter {
+    if decompress {
+        Arc::new(|source| Box::new(GzipDecoder::new(source)))
+    } else {
+        Arc::new(|source| Box::new(GzipEncoder::new(source)))
+    }
+}
+
+fn run(input: ValueReceiver, output: ValueSender, files: Files, name: &str, adapter: Adapter) -> CrushResult<()> {
+    let source = files.reader(input)?;
+    output.send(Value::BinaryStream(<dyn BinaryReader>::adapt(source, name, adapter)))
+}
+
+#[signature(
+gzip,
+can_block = true,
+output = Known(ValueType::BinaryStream),
+short = "Compress the specified files (or input) using gzip",
+long = "The output is a binary stream, that is compressed as it is being read, so arbitrarily",
+long = "large data can be compressed without creating temporary files.",
+example = "bin:from ./access.log | gzip | bin:to ./access.log.gz")]
+struct Gzip {
+    #[unnamed()]
+    #[description("the files to compress (compress the input if no file is specified).")]
+    files: Files,
+    #[default(false)]
+    #[description("decompress instead of compressing, same as gunzip.")]
+    decompress: bool,
+}
+
+fn gzip(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Gzip = Gzip::parse(context.arguments, &context.printer)?;
+    run(context.input, context.output, cfg.files, if cfg.decompress { "gunzip" } else { "gzip" }, gzip_adapter(cfg.decompress))
+}
+
+#[signature(
+gunzip,
+can_block = true,
+output = Known(ValueType::BinaryStream),
+short = "Decompress the specified gzip files (or input)",
+long = "The output is a binary stream, that is decompressed as it is being read. Several gzip",
+long = "files are decompressed as if they were one.",
+example = "gunzip ./access.log.gz | lines:from | where {line =~ ^(error)}")]
+struct Gunzip {
+    #[unnamed()]
+    #[description("the files to decompress (decompress the input if no file is specified).")]
+    files: Files,
+}
+
+fn gunzip(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Gunzip = Gunzip::parse(context.arguments, &context.printer)?;
+    run(context.input, context.output, cfg.files, "gunzip", gzip_adapter(true))
+}
+
+#[signature(
+zstd,
+can_block = true,
+output = Known(ValueType::BinaryStream),
+short = "Compress or decompress the specified files (or input) using zstd",
+long = "The data is piped through the zstd program, which must be installed.",
+example = "bin:from ./dump.sql.zst | zstd --decompress | lines:from")]
+struct Zstd {
+    #[unnamed()]
+    #[description("the files to process (process the input if no file is specified).")]
+    files: Files,
+    #[default(false)]
+    #[description("decompress instead of compressing.")]
+    decompress: bool,
+}
+
+fn zstd(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Zstd = Zstd::parse(context.arguments, &context.printer)?;
+    let adapter = if cfg.decompress {
+        ProcessReader::adapter("zstd", &["-d", "-c", "-q"])
+    } else {
+        ProcessReader::adapter("zstd", &["-c", "-q"])
+    };
+    run(context.input, context.output, cfg.files, "zstd", adapter)
+}
+
+#[signature(
+bzip2,
+can_block = true,
+output = Known(ValueType::BinaryStream),
+short = "Compress or decompress the specified files (or input) using bzip2",
+long = "The data is piped through the bzip2 program, which must be installed.",
+example = "bzip2 --decompress ./words.bz2 | lines:from")]
+struct Bzip2 {
+    #[unnamed()]
+    #[description("the files to process (process the input if no file is specified).")]
+    files: Files,
+    #[default(false)]
+    #[description("decompress instead of compressing.")]
+    decompress: bool,
+}
+
+fn bzip2(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Bzip2 = Bzip2::parse(context.arguments, &context.printer)?;
+    let adapter = if cfg.decompress {
+        ProcessReader::adapter("bzip2", &["-d", "-c", "-q"])
+    } else {
+        ProcessReader::adapter("bzip2", &["-c", "-q"])
+    };
+    run(context.input, context.output, cfg.files, "bzip2", adapter)
+}
+
+pub fn declare(root: &mut ScopeLoader) -> CrushResult<()> {
+    Gzip::declare(root)?;
+    Gunzip::declare(root)?;
+    Zstd::declare(root)?;
+    Bzip2::declare(root)?;
+    Ok(())
+}
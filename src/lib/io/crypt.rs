@@ -0,0 +1,93 @@
+use std::sync::Arc;
+
+use crate::lang::argument::ArgumentHandler;
+use crate::lang::binary::{Adapter, BinaryReader};
+use crate::lang::command::OutputType::Known;
+use crate::lang::errors::{CrushResult, argument_error, to_crush_error};
+use crate::lang::execution_context::ExecutionContext;
+use crate::lang::files::Files;
+use crate::lang::scope::ScopeLoader;
+use crate::lang::stream::{ValueReceiver, ValueSender};
+use crate::lang::value::{Value, ValueType};
+use crate::util::crypt::{Decryptor, Encryptor, Secret};
+use signature::signature;
+
+fn secret(password: Option<String>, key: Option<Value>) -> CrushResult<Secret> {
+    match (password, key) {
+        (Some(password), None) => Ok(Secret::Password(password.into_bytes())),
+        (None, Some(Value::String(s))) => Ok(Secret::Key(s.into_bytes())),
+        (None, Some(Value::Binary(b))) => Ok(Secret::Key(b)),
+        (None, Some(Value::File(f))) => Ok(Secret::Key(to_crush_error(std::fs::read(f))?)),
+        (None, Some(v)) => argument_error(format!("Expected the key to be a string, binary or file, got a {}", v.value_type().to_string()).as_str()),
+        _ => argument_error("Expected exactly one of password and key"),
+    }
+}
+
+fn run(input: ValueReceiver, output: ValueSender, files: Files, name: &str, adapter: Adapter) -> CrushResult<()> {
+    let source = files.reader(input)?;
+    output.send(Value::BinaryStream(<dyn BinaryReader>::adapt(source, name, adapter)))
+}
+
+#[signature(
+encrypt,
+can_block = true,
+output = Known(ValueType::BinaryStream),
+short = "Encrypt the specified files (or input) with a password or a key",
+long = "The output is a binary stream that is encrypted as it is being read, using AES-256-GCM in",
+long = "chunks, so arbitrarily large data can be encrypted. With a password, the encryption key is",
+long = "derived from it using scrypt, which deliberately takes a moment. A key can be any string,",
+long = "binary or file, but should be long and random, since it is used without key stretching.",
+long = "",
+long = "Use crypt:decrypt with the same password or key to get the data back.",
+example = "bin:from ./backup.tar | crypt:encrypt password=\"correct horse battery staple\" | bin:to ./backup.tar.enc")]
+struct Encrypt {
+    #[unnamed()]
+    #[description("the files to encrypt (encrypt the input if no file is specified).")]
+    files: Files,
+    #[description("the password to encrypt with.")]
+    password: Option<String>,
+    #[description("the key to encrypt with, as a string, binary or file.")]
+    key: Option<Value>,
+}
+
+fn encrypt(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Encrypt = Encrypt::parse(context.arguments, &context.printer)?;
+    let secret = secret(cfg.password, cfg.key)?;
+    run(context.input, context.output, cfg.files, "encrypt", Arc::new(move |source| Box::new(Encryptor::new(source, secret.clone()))))
+}
+
+#[signature(
+decrypt,
+can_block = true,
+output = Known(ValueType::BinaryStream),
+short = "Decrypt files (or input) encrypted with crypt:encrypt",
+long = "The output is a binary stream that is decrypted as it is being read. Every chunk of the data",
+long = "is authenticated before it is output, and reading fails if the password or key is wrong or",
+long = "if the data has been modified or truncated.",
+example = "crypt:decrypt ./secrets.json.enc key=./backup.key | json:from")]
+struct Decrypt {
+    #[unnamed()]
+    #[description("the files to decrypt (decrypt the input if no file is specified).")]
+    files: Files,
+    #[description("the password the data was encrypted with.")]
+    password: Option<String>,
+    #[description("the key the data was encrypted with, as a string, binary or file.")]
+    key: Option<Value>,
+}
+
+fn decrypt(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Decrypt = Decrypt::parse(context.arguments, &context.printer)?;
+    let secret = secret(cfg.password, cfg.key)?;
+    run(context.input, context.output, cfg.files, "decrypt", Arc::new(move |source| Box::new(Decryptor::new(source, secret.clone()))))
+}
+
+pub fn declare(root: &mut ScopeLoader) -> CrushResult<()> {
+    root.create_lazy_namespace(
+        "crypt",
+        Box::new(move |env| {
+            Encrypt::declare(env)?;
+            Decrypt::declare(env)?;
+            Ok(())
+        }))?;
+    Ok(())
+}
@@ -0,0 +1,109 @@
+use std::net::{IpAddr, SocketAddr, ToSocketAddrs};
+
+use chrono::Duration;
+use lazy_static::lazy_static;
+
+use crate::lang::argument::ArgumentHandler;
+use crate::lang::command::OutputType::Known;
+use crate::lang::errors::{CrushResult, argument_error, mandate, to_crush_error};
+use crate::lang::execution_context::ExecutionContext;
+use crate::lang::scope::ScopeLoader;
+use crate::lang::table::{ColumnType, Row};
+use crate::lang::value::{Value, ValueType};
+use crate::util::dns;
+use signature::signature;
+
+lazy_static! {
+    static ref OUTPUT_TYPE: Vec<ColumnType> = vec![
+        ColumnType::new("name", ValueType::String),
+        ColumnType::new("ttl", ValueType::Duration),
+        ColumnType::new("type", ValueType::String),
+        ColumnType::new("value", ValueType::String),
+    ];
+}
+
+fn server_address(server: &str) -> CrushResult<SocketAddr> {
+    if let Ok(ip) = server.parse::<IpAddr>() {
+        return Ok(SocketAddr::new(ip, 53));
+    }
+    let address = if server.contains(':') { server.to_string() } else { format!("{}:53", server) };
+    mandate(
+        to_crush_error(address.to_socket_addrs())?.next(),
+        format!("Could not resolve {}", server).as_str())
+}
+
+#[signature(
+lookup,
+can_block = true,
+output = Known(ValueType::TableStream(OUTPUT_TYPE.clone())),
+short = "Look up DNS records",
+long = "Outputs one row for each record in the answers to the queries, including the CNAME records",
+long = "that lead to the requested records. Names are given without the trailing dot.",
+long = "",
+long = "If the type is not given, A records are looked up, except for IP addresses, where the PTR",
+long = "record of the address is looked up instead. Values that contain several fields, like those of",
+long = "MX, SRV and SOA records, are in the same format as in zone files. Records of types that are",
+long = "not understood have their data as hexadecimal.",
+long = "",
+long = "The default server is the first nameserver in /etc/resolv.conf. Names that can not be",
+long = "looked up are reported as errors, and the remaining names are still looked up.",
+example = "dns:lookup \"example.com\" type=\"MX\" server=\"1.1.1.1\" | sort ^value")]
+struct Lookup {
+    #[unnamed()]
+    #[description("the names or IP addresses to look up.")]
+    names: Vec<String>,
+    #[description("the record type, e.g. A, AAAA, MX, TXT or TYPE65.")]
+    r#type: Option<String>,
+    #[description("the address of the DNS server to ask, optionally with a port.")]
+    server: Option<String>,
+    #[description("how long to wait for an answer. The default is five seconds.")]
+    timeout: Option<Duration>,
+}
+
+fn lookup(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Lookup = Lookup::parse(context.arguments, &context.printer)?;
+    if cfg.names.is_empty() {
+        return argument_error("Expected at least one name to look up");
+    }
+    let server = match &cfg.server {
+        Some(server) => server_address(server)?,
+        None => dns::system_server(),
+    };
+    let timeout = match cfg.timeout {
+        None => std::time::Duration::from_secs(5),
+        Some(timeout) => to_crush_error(timeout.to_std())?,
+    };
+    let record_type = cfg.r#type.as_deref().map(dns::record_type).transpose()?;
+
+    let output = context.output.initialize(OUTPUT_TYPE.clone())?;
+    for name in cfg.names {
+        let (name, record_type) = match (name.parse::<IpAddr>(), record_type) {
+            (Ok(ip), None) => (dns::reverse_name(&ip), 12),
+            (_, record_type) => (name, record_type.unwrap_or(1)),
+        };
+        match dns::query(&name, record_type, server, timeout) {
+            Ok(records) => {
+                for record in records {
+                    output.send(Row::new(vec![
+                        Value::String(record.name),
+                        Value::Duration(Duration::seconds(i64::from(record.ttl))),
+                        Value::String(dns::type_name(record.record_type)),
+                        Value::String(record.value),
+                    ]))?;
+                }
+            }
+            Err(e) => context.printer.error(format!("{}: {}", name, e.message).as_str()),
+        }
+    }
+    Ok(())
+}
+
+pub fn declare(root: &mut ScopeLoader) -> CrushResult<()> {
+    root.create_lazy_namespace(
+        "dns",
+        Box::new(move |env| {
+            Lookup::declare(env)?;
+            Ok(())
+        }))?;
+    Ok(())
+}
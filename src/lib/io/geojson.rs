@@ -0,0 +1,134 @@
+use std::io::BufReader;
+
+use lazy_static::lazy_static;
+
+use super::json::from_json;
+use crate::lang::argument::ArgumentHandler;
+use crate::lang::command::OutputType::Known;
+use crate::lang::dict::Dict;
+use crate::lang::errors::{CrushResult, data_error, to_crush_error};
+use crate::lang::execution_context::ExecutionContext;
+use crate::lang::files::Files;
+use crate::lang::list::List;
+use crate::lang::r#struct::Struct;
+use crate::lang::scope::ScopeLoader;
+use crate::lang::{table::ColumnType, table::Row, value::Value, value::ValueType};
+use signature::signature;
+
+lazy_static! {
+    static ref OUTPUT_TYPE: Vec<ColumnType> = vec![
+        ColumnType::new("geometry", ValueType::Struct),
+        ColumnType::new("properties", ValueType::Dict(Box::from(ValueType::String), Box::from(ValueType::Any))),
+    ];
+}
+
+/** Positions become lists of floats, and lines, rings and polygons become lists of those. */
+fn coordinates(json: &serde_json::Value) -> CrushResult<Value> {
+    match json {
+        serde_json::Value::Number(n) => match n.as_f64() {
+            Some(f) => Ok(Value::Float(f)),
+            None => data_error("Invalid coordinate"),
+        },
+        serde_json::Value::Array(elements) => {
+            let values = elements.iter().map(coordinates).collect::<CrushResult<Vec<_>>>()?;
+            let element_type = values.first().map(|v| v.value_type()).unwrap_or(ValueType::Float);
+            Ok(Value::List(List::new(element_type, values)))
+        }
+        _ => data_error("Expected coordinates to be numbers or lists"),
+    }
+}
+
+fn geometry(json: &serde_json::Value) -> CrushResult<Value> {
+    let object = match json {
+        serde_json::Value::Null => return Ok(Value::Empty()),
+        serde_json::Value::Object(o) => o,
+        _ => return data_error("Expected a geometry to be an object"),
+    };
+    let geometry_type = match object.get("type") {
+        Some(serde_json::Value::String(t)) => t.clone(),
+        _ => return data_error("Expected a geometry to have a type"),
+    };
+    let member = match (geometry_type.as_str(), object.get("geometries"), object.get("coordinates")) {
+        ("GeometryCollection", Some(serde_json::Value::Array(geometries)), _) => (
+            "geometries",
+            Value::List(List::new(
+                ValueType::Struct,
+                geometries.iter().map(geometry).collect::<CrushResult<Vec<_>>>()?)),
+        ),
+        (_, _, Some(c)) => ("coordinates", coordinates(c)?),
+        _ => return data_error(format!("Expected a {} geometry to have coordinates", geometry_type).as_str()),
+    };
+    Ok(Value::Struct(Struct::new(
+        vec![
+            ("type".to_string(), Value::String(geometry_type)),
+            (member.0.to_string(), member.1),
+        ],
+        None)))
+}
+
+fn properties(json: Option<&serde_json::Value>) -> CrushResult<Value> {
+    let dict = Dict::new(ValueType::String, ValueType::Any);
+    if let Some(serde_json::Value::Object(object)) = json {
+        for (key, value) in object {
+            dict.insert(Value::string(key), from_json(value)?)?;
+        }
+    }
+    Ok(Value::Dict(dict))
+}
+
+fn feature(json: &serde_json::Value) -> CrushResult<Row> {
+    match json {
+        serde_json::Value::Object(object) => match object.get("type") {
+            Some(serde_json::Value::String(t)) if t == "Feature" => Ok(Row::new(vec![
+                geometry(object.get("geometry").unwrap_or(&serde_json::Value::Null))?,
+                properties(object.get("properties"))?,
+            ])),
+            // A bare geometry is treated as a feature without properties
+            _ => Ok(Row::new(vec![geometry(json)?, properties(None)?])),
+        },
+        _ => data_error("Expected a GeoJSON object"),
+    }
+}
+
+#[signature(
+from,
+can_block = true,
+output = Known(ValueType::TableStream(OUTPUT_TYPE.clone())),
+short = "Read the features of a GeoJSON file",
+long = "Every feature is output as a row with its geometry and properties. The geometry is a struct",
+long = "with a type member and either a coordinates member, containing nested lists of floats, or",
+long = "for geometry collections a geometries member. The properties are a dict.",
+long = "",
+long = "The input can be a feature collection, a single feature or a single geometry.",
+example = "geojson:from ./countries.geojson | where {geo:contains geometry (data lat=59.33 lon=18.07)}")]
+struct From {
+    #[unnamed()]
+    #[description("the file to read.")]
+    files: Files,
+}
+
+fn from(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: From = From::parse(context.arguments, &context.printer)?;
+    let output = context.output.initialize(OUTPUT_TYPE.clone())?;
+    let reader = BufReader::new(cfg.files.reader(context.input)?);
+    let json: serde_json::Value = to_crush_error(serde_json::from_reader(reader))?;
+    match (json.get("type"), json.get("features")) {
+        (Some(serde_json::Value::String(t)), Some(serde_json::Value::Array(features))) if t == "FeatureCollection" => {
+            for f in features {
+                output.send(feature(f)?)?;
+            }
+            Ok(())
+        }
+        _ => output.send(feature(&json)?),
+    }
+}
+
+pub fn declare(root: &mut ScopeLoader) -> CrushResult<()> {
+    root.create_lazy_namespace(
+        "geojson",
+        Box::new(move |env| {
+            From::declare(env)?;
+            Ok(())
+        }))?;
+    Ok(())
+}
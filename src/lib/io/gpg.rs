@@ -0,0 +1,252 @@
+use std::fs::DirBuilder;
+use std::os::unix::fs::DirBuilderExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use chrono::{Local, NaiveDateTime, TimeZone, Utc};
+
+use crate::lang::argument::ArgumentHandler;
+use crate::lang::command::OutputType::Known;
+use crate::lang::errors::{CrushResult, error, to_crush_error};
+use crate::lang::execution_context::ExecutionContext;
+use crate::lang::r#struct::Struct;
+use crate::lang::scope::ScopeLoader;
+use crate::lang::value::{Value, ValueType};
+use signature::signature;
+
+static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/**
+A temporary gpg home directory with only the keys of a keyring in it, so that verifying
+doesn't depend on, or change, the keys of the user. Removed when dropped.
+*/
+struct Home {
+    path: PathBuf,
+}
+
+impl Home {
+    fn new(keyring: &Path) -> CrushResult<Home> {
+        let path = std::env::temp_dir().join(format!(
+            "crush-gpg-{}-{}", std::process::id(), COUNTER.fetch_add(1, Ordering::Relaxed)));
+        to_crush_error(DirBuilder::new().mode(0o700).create(&path))?;
+        let home = Home { path };
+        let output = to_crush_error(Command::new("gpg")
+            .arg("--homedir").arg(&home.path)
+            .args(["--batch", "--no-tty", "--import"])
+            .arg(keyring)
+            .stdin(Stdio::null())
+            .output())?;
+        if !output.status.success() {
+            return error(format!(
+                "Failed to import the keyring: {}",
+                String::from_utf8_lossy(&output.stderr).trim()).as_str());
+        }
+        Ok(home)
+    }
+}
+
+impl Drop for Home {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.path);
+    }
+}
+
+fn time(field: Option<&str>) -> Value {
+    let field = match field {
+        Some(field) => field,
+        None => return Value::Empty(),
+    };
+    if let Ok(seconds) = field.parse::<i64>() {
+        return Local.timestamp_opt(seconds, 0).single().map(Value::Time).unwrap_or(Value::Empty());
+    }
+    match NaiveDateTime::parse_from_str(field, "%Y%m%dT%H%M%S") {
+        Ok(t) => Value::Time(Utc.from_utc_datetime(&t).with_timezone(&Local)),
+        Err(_) => Value::Empty(),
+    }
+}
+
+struct Verification {
+    status: Option<&'static str>,
+    valid: bool,
+    signer: Value,
+    key: Value,
+    timestamp: Value,
+    trust: Value,
+}
+
+/** Interprets the machine readable status lines gpg writes with --status-fd. */
+fn parse_status(status: &str) -> Verification {
+    let mut res = Verification {
+        status: None,
+        valid: false,
+        signer: Value::Empty(),
+        key: Value::Empty(),
+        timestamp: Value::Empty(),
+        trust: Value::Empty(),
+    };
+    for line in status.lines() {
+        let line = match line.strip_prefix("[GNUPG:] ") {
+            Some(line) => line,
+            None => continue,
+        };
+        let (keyword, rest) = line.split_once(' ').unwrap_or((line, ""));
+        let fields = rest.split(' ').collect::<Vec<_>>();
+        let signature_status = match keyword {
+            "GOODSIG" => Some("good"),
+            "BADSIG" => Some("bad"),
+            "EXPSIG" => Some("expired_signature"),
+            "EXPKEYSIG" => Some("expired_key"),
+            "REVKEYSIG" => Some("revoked_key"),
+            _ => None,
+        };
+        if let Some(signature_status) = signature_status {
+            // Only the first signature is reported
+            if res.status.is_none() {
+                res.status = Some(signature_status);
+                res.signer = match rest.split_once(' ') {
+                    Some((_, user)) => Value::string(user),
+                    None => Value::Empty(),
+                };
+            }
+            continue;
+        }
+        match keyword {
+            "VALIDSIG" if res.status == Some("good") && !res.valid => {
+                res.valid = true;
+                res.key = fields.last().map(|k| Value::string(k)).unwrap_or(Value::Empty());
+                res.timestamp = time(fields.get(2).copied());
+            }
+            "ERRSIG" if res.status.is_none() => {
+                res.status = Some(if fields.get(5) == Some(&"9") { "missing_key" } else { "error" });
+                res.key = match fields.get(6) {
+                    Some(fingerprint) if *fingerprint != "-" => Value::string(fingerprint),
+                    _ => fields.first().map(|k| Value::string(k)).unwrap_or(Value::Empty()),
+                };
+                res.timestamp = time(fields.get(4).copied());
+            }
+            _ => {
+                if let Some(trust) = keyword.strip_prefix("TRUST_") {
+                    if let Value::Empty() = res.trust {
+                        res.trust = Value::String(trust.to_lowercase());
+                    }
+                }
+            }
+        }
+    }
+    res
+}
+
+#[signature(
+verify,
+can_block = true,
+output = Known(ValueType::Struct),
+short = "Verify a GPG signature",
+long = "Runs gpg, which must be installed, and returns a struct with the following members:",
+long = "",
+long = "    * valid, true if the signature is good",
+long = "    * status, one of good, bad, expired_signature, expired_key, revoked_key, missing_key and error",
+long = "    * signer, the user id of the key that made the signature, if it is known",
+long = "    * key, the fingerprint of the key that made the signature",
+long = "    * timestamp, when the signature was made",
+long = "    * trust, how much gpg trusts the key, e.g. ultimate, full or undefined",
+long = "",
+long = "A bad signature is not an error, but a missing or malformed signature is. If a keyring is",
+long = "given, only the keys in it are used, otherwise the keys of the user are. The keyring can be",
+long = "an exported public key, armored or not.",
+example = "(gpg:verify ./crush.tar.gz ./crush.tar.gz.asc keyring=./release-keys.asc):valid")]
+struct Verify {
+    #[description("the signed file.")]
+    file: PathBuf,
+    #[description("the detached signature. If not given, the file must contain the signature.")]
+    signature: Option<PathBuf>,
+    #[description("a file with the keys to trust.")]
+    keyring: Option<PathBuf>,
+}
+
+fn verify(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Verify = Verify::parse(context.arguments, &context.printer)?;
+    let home = match &cfg.keyring {
+        Some(keyring) => Some(Home::new(keyring)?),
+        None => None,
+    };
+    let mut cmd = Command::new("gpg");
+    if let Some(environment) = context.env.process_environment() {
+        environment.apply(&mut cmd);
+    }
+    if let Some(home) = &home {
+        cmd.arg("--homedir").arg(&home.path);
+    }
+    cmd.args(["--batch", "--no-tty", "--status-fd", "1", "--verify"]);
+    if let Some(signature) = &cfg.signature {
+        cmd.arg(signature);
+    }
+    let output = to_crush_error(cmd.arg(&cfg.file).stdin(Stdio::null()).output())?;
+
+    let res = parse_status(&String::from_utf8_lossy(&output.stdout));
+    let status = match res.status {
+        Some(status) => status,
+        None => return error(format!(
+            "Failed to verify the signature: {}",
+            String::from_utf8_lossy(&output.stderr).trim()).as_str()),
+    };
+    context.output.send(Value::Struct(Struct::new(
+        vec![
+            ("valid".to_string(), Value::Bool(res.valid)),
+            ("status".to_string(), Value::string(status)),
+            ("signer".to_string(), res.signer),
+            ("key".to_string(), res.key),
+            ("timestamp".to_string(), res.timestamp),
+            ("trust".to_string(), res.trust),
+        ],
+        None)))
+}
+
+pub fn declare(root: &mut ScopeLoader) -> CrushResult<()> {
+    root.create_lazy_namespace(
+        "gpg",
+        Box::new(move |env| {
+            Verify::declare(env)?;
+            Ok(())
+        }))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_good_signature() {
+        let res = parse_status("[GNUPG:] NEWSIG
+[GNUPG:] SIG_ID gK60MdR9GARIBcs3k58yNguaR5c 2020-06-01 1590969600
+[GNUPG:] GOODSIG 08EFBE3953015BF9 Crush Test <test@crush.invalid>
+[GNUPG:] VALIDSIG 1C04 2020-06-01 1590969600 0 4 0 22 8 00 1C04DC06
+[GNUPG:] TRUST_UNDEFINED 0 pgp
+");
+        assert_eq!(res.status, Some("good"));
+        assert!(res.valid);
+        assert!(res.signer == Value::string("Crush Test <test@crush.invalid>"));
+        assert!(res.key == Value::string("1C04DC06"));
+        assert!(res.timestamp == Value::Time(Local.timestamp(1590969600, 0)));
+        assert!(res.trust == Value::string("undefined"));
+    }
+
+    #[test]
+    fn parse_missing_key() {
+        let res = parse_status("[GNUPG:] ERRSIG 08EFBE3953015BF9 22 8 00 20200601T000000 9 -
+[GNUPG:] NO_PUBKEY 08EFBE3953015BF9
+");
+        assert_eq!(res.status, Some("missing_key"));
+        assert!(!res.valid);
+        assert!(res.key == Value::string("08EFBE3953015BF9"));
+        assert!(res.timestamp == Value::Time(Local.timestamp(1590969600, 0)));
+    }
+
+    #[test]
+    fn invalid_times_are_empty() {
+        assert!(matches!(time(Some("99999999999999999")), Value::Empty()));
+        assert!(matches!(time(Some("soon")), Value::Empty()));
+        assert!(matches!(time(None), Value::Empty()));
+    }
+}
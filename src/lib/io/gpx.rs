@@ -0,0 +1,138 @@
+use std::io::{BufRead, BufReader};
+
+use chrono::{DateTime, Local};
+use lazy_static::lazy_static;
+
+use crate::lang::argument::ArgumentHandler;
+use crate::lang::command::OutputType::Known;
+use crate::lang::errors::{CrushResult, data_error};
+use crate::lang::execution_context::ExecutionContext;
+use crate::lang::files::Files;
+use crate::lang::scope::ScopeLoader;
+use crate::lang::{table::ColumnType, table::Row, value::Value, value::ValueType};
+use crate::lib::geo::haversine;
+use crate::util::xml::{Event, Reader, local_name};
+use signature::signature;
+
+lazy_static! {
+    // Time, elevation and speed are optional in GPX files, and empty when missing
+    static ref OUTPUT_TYPE: Vec<ColumnType> = vec![
+        ColumnType::new("time", ValueType::Any),
+        ColumnType::new("lat", ValueType::Float),
+        ColumnType::new("lon", ValueType::Float),
+        ColumnType::new("elevation", ValueType::Any),
+        ColumnType::new("speed", ValueType::Any),
+    ];
+}
+
+#[derive(Default)]
+struct Point {
+    time: Option<DateTime<Local>>,
+    lat: f64,
+    lon: f64,
+    elevation: Option<f64>,
+    speed: Option<f64>,
+}
+
+fn coordinate(attributes: &[(String, String)], name: &str) -> CrushResult<f64> {
+    match attributes.iter().find(|(n, _)| local_name(n) == name).map(|(_, v)| v.trim().parse::<f64>()) {
+        Some(Ok(value)) => Ok(value),
+        _ => data_error(format!("Expected a point to have a numeric {} attribute", name).as_str()),
+    }
+}
+
+fn points(reader: impl BufRead) -> CrushResult<Vec<Point>> {
+    let mut res = Vec::new();
+    let mut current: Option<Point> = None;
+    let mut element = String::new();
+    // The previous point in the same segment or route, used to calculate the speed
+    let mut previous: Option<usize> = None;
+    let mut reader = Reader::new(reader);
+    while let Some(event) = reader.next()? {
+        match event {
+            // GPX files use namespace prefixes inconsistently, so they are ignored
+            Event::Start(name, attributes) => match local_name(&name) {
+                "trkpt" | "rtept" => current = Some(Point {
+                    lat: coordinate(&attributes, "lat")?,
+                    lon: coordinate(&attributes, "lon")?,
+                    ..Point::default()
+                }),
+                "trk" | "trkseg" | "rte" => previous = None,
+                name => element = name.to_string(),
+            },
+            Event::Text(text) => if let Some(point) = current.as_mut() {
+                let text = text.trim();
+                match element.as_str() {
+                    "ele" => point.elevation = text.parse().ok(),
+                    "speed" => point.speed = text.parse().ok(),
+                    "time" => point.time = DateTime::parse_from_rfc3339(text).ok().map(|t| t.with_timezone(&Local)),
+                    _ => {}
+                }
+            },
+            Event::End(name) => {
+                if local_name(&name) == "trkpt" || local_name(&name) == "rtept" {
+                    if let Some(mut point) = current.take() {
+                        let prev: Option<&Point> = previous.and_then(|idx| res.get(idx));
+                        if let (None, Some(prev), Some(time)) = (point.speed, prev, point.time) {
+                            if let Some(prev_time) = prev.time {
+                                let seconds = (time - prev_time).num_milliseconds() as f64 / 1000.0;
+                                if seconds > 0.0 {
+                                    point.speed = Some(haversine((prev.lat, prev.lon), (point.lat, point.lon)) / seconds);
+                                }
+                            }
+                        }
+                        previous = Some(res.len());
+                        res.push(point);
+                    }
+                }
+                element.clear();
+            }
+        }
+    }
+    Ok(res)
+}
+
+fn optional(value: Option<f64>) -> Value {
+    value.map(Value::Float).unwrap_or(Value::Empty())
+}
+
+#[signature(
+from,
+can_block = true,
+output = Known(ValueType::TableStream(OUTPUT_TYPE.clone())),
+short = "Read the points of the tracks and routes in a GPX file",
+long = "Every track point and route point is output as a row with its time, latitude, longitude,",
+long = "elevation in meters and speed in meters per second. Points without a recorded speed get",
+long = "one calculated from the distance to and time since the previous point. Values missing from",
+long = "the file are empty.",
+example = "gpx:from ./run.gpx | select ^time kmh={speed * 3.6}")]
+struct From {
+    #[unnamed()]
+    #[description("the file to read.")]
+    files: Files,
+}
+
+fn from(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: From = From::parse(context.arguments, &context.printer)?;
+    let output = context.output.initialize(OUTPUT_TYPE.clone())?;
+    for point in points(BufReader::new(cfg.files.reader(context.input)?))? {
+        output.send(Row::new(vec![
+            point.time.map(Value::Time).unwrap_or(Value::Empty()),
+            Value::Float(point.lat),
+            Value::Float(point.lon),
+            optional(point.elevation),
+            optional(point.speed),
+        ]))?;
+    }
+    Ok(())
+}
+
+pub fn declare(root: &mut ScopeLoader) -> CrushResult<()> {
+    root.create_lazy_namespace(
+        "gpx",
+        Box::new(move |env| {
+            From::declare(env)?;
+            Ok(())
+        }))?;
+    Ok(())
+}
@@ -0,0 +1,479 @@
+use std::collections::HashSet;
+use std::io::Read;
+
+use chrono::{Datelike, Duration, Local, NaiveDate, NaiveDateTime, TimeZone, Utc, Weekday};
+use lazy_static::lazy_static;
+
+use crate::lang::argument::ArgumentHandler;
+use crate::lang::command::OutputType::Known;
+use crate::lang::errors::{CrushResult, argument_error, data_error, to_crush_error};
+use crate::lang::execution_context::ExecutionContext;
+use crate::lang::files::Files;
+use crate::lang::list::List;
+use crate::lang::scope::ScopeLoader;
+use crate::lang::value::Time;
+use crate::lang::{table::ColumnType, table::Row, value::Value, value::ValueType};
+use signature::signature;
+
+lazy_static! {
+    static ref OUTPUT_TYPE: Vec<ColumnType> = vec![
+        ColumnType::new("start", ValueType::Time),
+        ColumnType::new("end", ValueType::Time),
+        ColumnType::new("summary", ValueType::String),
+        ColumnType::new("location", ValueType::String),
+        ColumnType::new("attendees", ValueType::List(Box::from(ValueType::String))),
+    ];
+}
+
+/** The most periods of a recurrence rule that are looked at, so broken rules can't loop forever. */
+const MAX_PERIODS: i64 = 100_000;
+
+/**
+A time as written in the calendar. Times in UTC are marked as such, all other times are
+treated as local times, since there is no time zone database to look other zones up in.
+*/
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+struct CalendarTime {
+    time: NaiveDateTime,
+    utc: bool,
+}
+
+impl CalendarTime {
+    fn parse(value: &str) -> CrushResult<CalendarTime> {
+        let value = value.trim();
+        let (value, utc) = match value.strip_suffix('Z') {
+            Some(v) => (v, true),
+            None => (value, false),
+        };
+        let time = if value.len() == 8 {
+            NaiveDate::parse_from_str(value, "%Y%m%d").map(|d| d.and_hms(0, 0, 0))
+        } else {
+            NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S")
+        };
+        match time {
+            Ok(time) => Ok(CalendarTime { time, utc }),
+            Err(_) => data_error(format!("Invalid calendar time {}", value).as_str()),
+        }
+    }
+
+    fn with_time(&self, time: NaiveDateTime) -> CalendarTime {
+        CalendarTime { time, utc: self.utc }
+    }
+
+    fn to_local(self) -> Time {
+        if self.utc {
+            Utc.from_utc_datetime(&self.time).with_timezone(&Local)
+        } else {
+            // Times skipped by a daylight saving change are moved an hour forward
+            Local.from_local_datetime(&self.time).earliest()
+                .unwrap_or_else(|| Local.from_local_datetime(&(self.time + Duration::hours(1))).unwrap())
+        }
+    }
+}
+
+/** A content line, like DTSTART;TZID=Europe/Stockholm:20200501T100000. */
+struct Property {
+    name: String,
+    parameters: Vec<(String, String)>,
+    value: String,
+}
+
+impl Property {
+    fn parse(line: &str) -> Option<Property> {
+        // Parameter values may be quoted and contain colons
+        let mut in_quotes = false;
+        let colon = line.char_indices().find(|(_, c)| {
+            if *c == '"' {
+                in_quotes = !in_quotes;
+            }
+            *c == ':' && !in_quotes
+        })?.0;
+        let mut parts = line[..colon].split(';');
+        let name = parts.next()?.to_uppercase();
+        let parameters = parts
+            .filter_map(|p| p.split_once('='))
+            .map(|(k, v)| (k.to_uppercase(), v.trim_matches('"').to_string()))
+            .collect();
+        Some(Property { name, parameters, value: line[colon + 1..].to_string() })
+    }
+
+    fn parameter(&self, name: &str) -> Option<&str> {
+        self.parameters.iter().find(|(k, _)| k == name).map(|(_, v)| v.as_str())
+    }
+}
+
+fn unescape(text: &str) -> String {
+    let mut res = String::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            match chars.next() {
+                Some('n') | Some('N') => res.push('\n'),
+                Some(other) => res.push(other),
+                None => {}
+            }
+        } else {
+            res.push(c);
+        }
+    }
+    res
+}
+
+/** Durations like P1W, P1D and PT1H30M. */
+fn parse_duration(value: &str) -> CrushResult<Duration> {
+    let (negative, value) = match value.trim().strip_prefix('-') {
+        Some(v) => (true, v),
+        None => (false, value.trim().trim_start_matches('+')),
+    };
+    let value = match value.strip_prefix('P') {
+        Some(v) => v,
+        None => return data_error(format!("Invalid duration {}", value).as_str()),
+    };
+    let mut res = Duration::zero();
+    let mut number = String::new();
+    for c in value.chars() {
+        match c {
+            '0'..='9' => number.push(c),
+            'T' => {}
+            unit => {
+                let n = match number.parse::<i64>() {
+                    Ok(n) => n,
+                    Err(_) => return data_error(format!("Invalid duration {}", value).as_str()),
+                };
+                res = res + match unit {
+                    'W' => Duration::weeks(n),
+                    'D' => Duration::days(n),
+                    'H' => Duration::hours(n),
+                    'M' => Duration::minutes(n),
+                    'S' => Duration::seconds(n),
+                    _ => return data_error(format!("Invalid duration {}", value).as_str()),
+                };
+                number.clear();
+            }
+        }
+    }
+    Ok(if negative { -res } else { res })
+}
+
+fn weekday(name: &str) -> CrushResult<Weekday> {
+    match name {
+        "MO" => Ok(Weekday::Mon),
+        "TU" => Ok(Weekday::Tue),
+        "WE" => Ok(Weekday::Wed),
+        "TH" => Ok(Weekday::Thu),
+        "FR" => Ok(Weekday::Fri),
+        "SA" => Ok(Weekday::Sat),
+        "SU" => Ok(Weekday::Sun),
+        _ => data_error(format!("Invalid weekday {}", name).as_str()),
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Frequency {
+    Daily,
+    Weekly,
+    Monthly,
+    Yearly,
+}
+
+struct Rule {
+    frequency: Frequency,
+    interval: i64,
+    count: Option<usize>,
+    until: Option<CalendarTime>,
+    /** Weekdays, with an optional ordinal for monthly rules, like the -1 in -1FR. */
+    by_day: Vec<(Option<i64>, Weekday)>,
+    by_month_day: Vec<i64>,
+}
+
+impl Rule {
+    fn parse(value: &str) -> CrushResult<Rule> {
+        let mut rule = Rule {
+            frequency: Frequency::Daily,
+            interval: 1,
+            count: None,
+            until: None,
+            by_day: Vec::new(),
+            by_month_day: Vec::new(),
+        };
+        let mut frequency = None;
+        for part in value.split(';') {
+            let (key, value) = match part.split_once('=') {
+                Some(p) => p,
+                None => continue,
+            };
+            let number = |v: &str| match v.parse::<i64>() {
+                Ok(n) => Ok(n),
+                Err(_) => data_error(format!("Invalid recurrence rule {}", part).as_str()),
+            };
+            match key.to_uppercase().as_str() {
+                "FREQ" => frequency = Some(match value.to_uppercase().as_str() {
+                    "DAILY" => Frequency::Daily,
+                    "WEEKLY" => Frequency::Weekly,
+                    "MONTHLY" => Frequency::Monthly,
+                    "YEARLY" => Frequency::Yearly,
+                    _ => return data_error(format!("Unsupported recurrence frequency {}", value).as_str()),
+                }),
+                "INTERVAL" => rule.interval = number(value)?.max(1),
+                "COUNT" => rule.count = Some(number(value)?.max(0) as usize),
+                "UNTIL" => rule.until = Some(CalendarTime::parse(value)?),
+                "BYDAY" => for day in value.split(',') {
+                    let split = day.len().saturating_sub(2);
+                    let ordinal = if split > 0 { Some(number(&day[..split])?) } else { None };
+                    rule.by_day.push((ordinal, weekday(&day[split..].to_uppercase())?));
+                },
+                "BYMONTHDAY" => for day in value.split(',') {
+                    rule.by_month_day.push(number(day)?);
+                },
+                _ => {}
+            }
+        }
+        rule.frequency = match frequency {
+            Some(f) => f,
+            None => return data_error("Expected the recurrence rule to have a frequency"),
+        };
+        Ok(rule)
+    }
+
+    /** The start dates of all occurrences in the period that is the specified number of periods after the first one. */
+    fn period(&self, first: NaiveDate, idx: i64) -> Vec<NaiveDate> {
+        let mut res = match self.frequency {
+            Frequency::Daily => vec![first + Duration::days(idx * self.interval)],
+            Frequency::Weekly => {
+                let monday = first - Duration::days(i64::from(first.weekday().num_days_from_monday()));
+                let week = monday + Duration::weeks(idx * self.interval);
+                if self.by_day.is_empty() {
+                    vec![week + Duration::days(i64::from(first.weekday().num_days_from_monday()))]
+                } else {
+                    self.by_day.iter()
+                        .map(|(_, day)| week + Duration::days(i64::from(day.num_days_from_monday())))
+                        .collect()
+                }
+            }
+            Frequency::Monthly => {
+                let months = i64::from(first.year()) * 12 + i64::from(first.month0()) + idx * self.interval;
+                let (year, month) = ((months / 12) as i32, (months % 12) as u32 + 1);
+                if !self.by_day.is_empty() {
+                    self.by_day.iter().flat_map(|(ordinal, day)| weekdays_in_month(year, month, *day, *ordinal)).collect()
+                } else if !self.by_month_day.is_empty() {
+                    self.by_month_day.iter().filter_map(|day| month_day(year, month, *day)).collect()
+                } else {
+                    month_day(year, month, i64::from(first.day())).into_iter().collect()
+                }
+            }
+            Frequency::Yearly => {
+                NaiveDate::from_ymd_opt(first.year() + (idx * self.interval) as i32, first.month(), first.day())
+                    .into_iter().collect()
+            }
+        };
+        res.sort();
+        res
+    }
+}
+
+fn days_in_month(year: i32, month: u32) -> i64 {
+    let next = if month == 12 { NaiveDate::from_ymd(year + 1, 1, 1) } else { NaiveDate::from_ymd(year, month + 1, 1) };
+    (next - NaiveDate::from_ymd(year, month, 1)).num_days()
+}
+
+/** A day of a month, where negative days count from the end of the month. */
+fn month_day(year: i32, month: u32, day: i64) -> Option<NaiveDate> {
+    let day = if day < 0 { days_in_month(year, month) + day + 1 } else { day };
+    if day < 1 {
+        return None;
+    }
+    NaiveDate::from_ymd_opt(year, month, day as u32)
+}
+
+/** All days in a month that are the specified weekday, or only the nth one if there is an ordinal. */
+fn weekdays_in_month(year: i32, month: u32, weekday: Weekday, ordinal: Option<i64>) -> Vec<NaiveDate> {
+    let days = (1..=days_in_month(year, month))
+        .filter_map(|day| month_day(year, month, day))
+        .filter(|date| date.weekday() == weekday)
+        .collect::<Vec<_>>();
+    match ordinal {
+        None => days,
+        Some(n) if n > 0 => days.get(n as usize - 1).copied().into_iter().collect(),
+        Some(n) => days.len().checked_sub((-n) as usize).and_then(|idx| days.get(idx)).copied().into_iter().collect(),
+    }
+}
+
+#[derive(Default)]
+struct Event {
+    uid: String,
+    start: Option<CalendarTime>,
+    end: Option<CalendarTime>,
+    duration: Option<Duration>,
+    summary: String,
+    location: String,
+    attendees: Vec<String>,
+    rule: Option<Rule>,
+    exceptions: HashSet<CalendarTime>,
+    recurrence_id: Option<CalendarTime>,
+}
+
+impl Event {
+    fn length(&self) -> Duration {
+        let start = match self.start {
+            Some(start) => start,
+            None => return Duration::zero(),
+        };
+        match (self.end, self.duration) {
+            (Some(end), _) => end.time - start.time,
+            (None, Some(duration)) => duration,
+            // Events without an end that start at midnight are all day events
+            (None, None) if start.time.time() == chrono::NaiveTime::from_hms(0, 0, 0) => Duration::days(1),
+            (None, None) => Duration::zero(),
+        }
+    }
+
+    /** The start of every occurrence of the event that overlaps with the range. */
+    fn occurrences(&self, from: Option<Time>, to: Time) -> Vec<CalendarTime> {
+        let start = match self.start {
+            Some(start) => start,
+            None => return vec![],
+        };
+        let length = self.length();
+        let overlaps = |occurrence: &CalendarTime| {
+            let local = occurrence.to_local();
+            local < to && from.map(|from| local + length > from).unwrap_or(true)
+        };
+        let rule = match &self.rule {
+            Some(rule) => rule,
+            None => return Some(start).filter(overlaps).into_iter().collect(),
+        };
+
+        let mut res = Vec::new();
+        let mut count = 0;
+        for idx in 0..MAX_PERIODS {
+            for date in rule.period(start.time.date(), idx) {
+                let occurrence = start.with_time(date.and_time(start.time.time()));
+                if occurrence.time < start.time {
+                    continue;
+                }
+                if rule.count.map(|c| count >= c).unwrap_or(false)
+                    || rule.until.map(|u| occurrence.time > u.time).unwrap_or(false)
+                    || occurrence.to_local() >= to {
+                    return res;
+                }
+                count += 1;
+                if !self.exceptions.contains(&occurrence) && overlaps(&occurrence) {
+                    res.push(occurrence);
+                }
+            }
+        }
+        res
+    }
+}
+
+fn events(data: &str) -> CrushResult<Vec<Event>> {
+    // Long lines are folded by starting the continuation lines with white space
+    let unfolded = data.replace("\r\n", "\n").replace("\n ", "").replace("\n\t", "");
+    let mut res = Vec::new();
+    let mut current: Option<Event> = None;
+    for line in unfolded.lines() {
+        let property = match Property::parse(line) {
+            Some(p) => p,
+            None => continue,
+        };
+        match (property.name.as_str(), property.value.to_uppercase().as_str()) {
+            ("BEGIN", "VEVENT") => current = Some(Event::default()),
+            ("END", "VEVENT") => if let Some(event) = current.take() {
+                res.push(event);
+            },
+            _ => if let Some(event) = current.as_mut() {
+                let value = property.value.as_str();
+                match property.name.as_str() {
+                    "UID" => event.uid = value.to_string(),
+                    "DTSTART" => event.start = Some(CalendarTime::parse(value)?),
+                    "DTEND" => event.end = Some(CalendarTime::parse(value)?),
+                    "DURATION" => event.duration = Some(parse_duration(value)?),
+                    "SUMMARY" => event.summary = unescape(value),
+                    "LOCATION" => event.location = unescape(value),
+                    "ATTENDEE" => event.attendees.push(match property.parameter("CN") {
+                        Some(name) => name.to_string(),
+                        None => value.trim_start_matches("mailto:").trim_start_matches("MAILTO:").to_string(),
+                    }),
+                    "RRULE" => event.rule = Some(Rule::parse(value)?),
+                    "EXDATE" => for exception in value.split(',') {
+                        event.exceptions.insert(CalendarTime::parse(exception)?);
+                    },
+                    "RECURRENCE-ID" => event.recurrence_id = Some(CalendarTime::parse(value)?),
+                    _ => {}
+                }
+            },
+        }
+    }
+    Ok(res)
+}
+
+#[signature(
+from,
+can_block = true,
+output = Known(ValueType::TableStream(OUTPUT_TYPE.clone())),
+short = "Read the events of an iCalendar file",
+long = "Every occurrence of every event is output as a row with its start and end time, summary,",
+long = "location and the names or addresses of its attendees. Recurring events are expanded into",
+long = "one row per occurrence, and only occurrences that overlap with the range given by from and",
+long = "to are output. If to is not specified, recurring events without an end are expanded up to",
+long = "a year from now.",
+long = "",
+long = "Times with a time zone other than UTC are treated as local times.",
+example = "ics:from ./work.ics from=(time:now) to=(time:now) + (duration:new days=7) | select length={end - start} | sum ^length")]
+struct From {
+    #[unnamed()]
+    #[description("the file to read.")]
+    files: Files,
+    #[description("only output occurrences that end after this time.")]
+    from: Option<Time>,
+    #[description("only output occurrences that start before this time.")]
+    to: Option<Time>,
+}
+
+fn from(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: From = From::parse(context.arguments, &context.printer)?;
+    let to = cfg.to.unwrap_or_else(|| Local::now() + Duration::days(365));
+    if cfg.from.map(|from| from > to).unwrap_or(false) {
+        return argument_error("Expected from to be before to");
+    }
+    let output = context.output.initialize(OUTPUT_TYPE.clone())?;
+    let mut data = String::new();
+    to_crush_error(cfg.files.reader(context.input)?.read_to_string(&mut data))?;
+    let events = events(&data)?;
+
+    // Occurrences of recurring events that have been changed are replaced by separate events
+    let overridden = events.iter()
+        .filter_map(|e| e.recurrence_id.map(|id| (e.uid.clone(), id)))
+        .collect::<HashSet<_>>();
+    let mut rows = Vec::new();
+    for event in &events {
+        for occurrence in event.occurrences(cfg.from, to) {
+            if event.recurrence_id.is_none() && overridden.contains(&(event.uid.clone(), occurrence)) {
+                continue;
+            }
+            let start = occurrence.to_local();
+            rows.push((start, Row::new(vec![
+                Value::Time(start),
+                Value::Time(start + event.length()),
+                Value::string(&event.summary),
+                Value::string(&event.location),
+                Value::List(List::new(ValueType::String, event.attendees.iter().map(|a| Value::string(a)).collect())),
+            ])));
+        }
+    }
+    rows.sort_by_key(|(start, _)| *start);
+    for (_, row) in rows {
+        output.send(row)?;
+    }
+    Ok(())
+}
+
+pub fn declare(root: &mut ScopeLoader) -> CrushResult<()> {
+    root.create_lazy_namespace(
+        "ics",
+        Box::new(move |env| {
+            From::declare(env)?;
+            Ok(())
+        }))?;
+    Ok(())
+}
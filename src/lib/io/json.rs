@@ -21,7 +21,7 @@ use crate::lang::files::Files;
 use signature::signature;
 use crate::lang::argument::ArgumentHandler;
 
-fn from_json(json_value: &serde_json::Value) -> CrushResult<Value> {
+pub fn from_json(json_value: &serde_json::Value) -> CrushResult<Value> {
     match json_value {
         serde_json::Value::Null => Ok(Value::Empty()),
         serde_json::Value::Bool(b) => Ok(Value::Bool(*b)),
@@ -31,7 +31,7 @@ fn from_json(json_value: &serde_json::Value) -> CrushResult<Value> {
             } else if f.is_i64() {
                 Ok(Value::Integer(f.as_i64().expect("") as i128))
             } else {
-                Ok(Value::Float(f.as_f64().ok_or(CrushError { kind: InvalidData, message: "Not a valid number".to_string() })?))
+                Ok(Value::Float(f.as_f64().ok_or(CrushError::new(InvalidData, "Not a valid number"))?))
             }
         }
         serde_json::Value::String(s) => Ok(Value::string(s.as_str())),
@@ -86,7 +86,7 @@ fn from_json(json_value: &serde_json::Value) -> CrushResult<Value> {
     }
 }
 
-fn to_json(value: Value) -> CrushResult<serde_json::Value> {
+pub(super) fn to_json(value: Value) -> CrushResult<serde_json::Value> {
     match value.materialize() {
         Value::File(s) =>
             Ok(serde_json::Value::from(mandate(s.to_str(), "Invalid filename")?)),
@@ -0,0 +1,185 @@
+use chrono::{Local, TimeZone};
+use openssl::bn::BigNum;
+use openssl::ecdsa::EcdsaSig;
+use openssl::hash::MessageDigest;
+use openssl::memcmp;
+use openssl::pkey::{PKey, Public};
+use openssl::rsa::Padding;
+use openssl::sign::{Signer, Verifier};
+
+use super::json::from_json;
+use crate::lang::argument::ArgumentHandler;
+use crate::lang::command::OutputType::Known;
+use crate::lang::errors::{CrushResult, argument_error, data_error, to_crush_error};
+use crate::lang::execution_context::ExecutionContext;
+use crate::lang::r#struct::Struct;
+use crate::lang::scope::ScopeLoader;
+use crate::lang::value::{Value, ValueType};
+use signature::signature;
+
+/** The registered claims that hold a number of seconds since the epoch. */
+const TIME_CLAIMS: [&str; 3] = ["exp", "nbf", "iat"];
+
+fn base64url_decode(text: &str) -> CrushResult<Vec<u8>> {
+    let mut standard = text.trim_end_matches('=').replace('-', "+").replace('_', "/");
+    while !standard.len().is_multiple_of(4) {
+        standard.push('=');
+    }
+    match openssl::base64::decode_block(&standard) {
+        Ok(data) => Ok(data),
+        Err(_) => data_error("Invalid base64 in token"),
+    }
+}
+
+fn decode_part(text: &str, name: &str) -> CrushResult<serde_json::Map<String, serde_json::Value>> {
+    match serde_json::from_slice(&base64url_decode(text)?) {
+        Ok(serde_json::Value::Object(object)) => Ok(object),
+        _ => data_error(format!("Expected the {} of the token to be a JSON object", name).as_str()),
+    }
+}
+
+fn to_struct(object: serde_json::Map<String, serde_json::Value>) -> CrushResult<Value> {
+    let mut members = Vec::new();
+    for (name, value) in object.iter() {
+        let time = match (TIME_CLAIMS.contains(&name.as_str()), value.as_i64()) {
+            (true, Some(seconds)) => Local.timestamp_opt(seconds, 0).single(),
+            _ => None,
+        };
+        let value = match time {
+            Some(time) => Value::Time(time),
+            None => from_json(value)?,
+        };
+        members.push((name.clone(), value));
+    }
+    Ok(Value::Struct(Struct::new(members, None)))
+}
+
+fn digest(algorithm: &str) -> CrushResult<MessageDigest> {
+    match algorithm.get(2..).unwrap_or("") {
+        "256" => Ok(MessageDigest::sha256()),
+        "384" => Ok(MessageDigest::sha384()),
+        "512" => Ok(MessageDigest::sha512()),
+        _ => argument_error(format!("Unsupported algorithm {}", algorithm).as_str()),
+    }
+}
+
+fn public_key(key: &[u8]) -> CrushResult<PKey<Public>> {
+    match PKey::public_key_from_pem(key) {
+        Ok(key) => Ok(key),
+        Err(_) => argument_error("Expected the key to be a public key in PEM format"),
+    }
+}
+
+/** JWTs store ECDSA signatures as the two numbers r and s concatenated, not in DER. */
+fn ecdsa_der(signature: &[u8]) -> CrushResult<Vec<u8>> {
+    if signature.is_empty() || !signature.len().is_multiple_of(2) {
+        return data_error("Invalid ECDSA signature length");
+    }
+    let (r, s) = signature.split_at(signature.len() / 2);
+    let sig = to_crush_error(EcdsaSig::from_private_components(
+        to_crush_error(BigNum::from_slice(r))?,
+        to_crush_error(BigNum::from_slice(s))?))?;
+    to_crush_error(sig.to_der())
+}
+
+fn verify(algorithm: &str, key: &[u8], message: &[u8], signature: &[u8]) -> CrushResult<bool> {
+    match algorithm.get(..2).unwrap_or("") {
+        "HS" => {
+            let key = to_crush_error(PKey::hmac(key))?;
+            let mut signer = to_crush_error(Signer::new(digest(algorithm)?, &key))?;
+            to_crush_error(signer.update(message))?;
+            let expected = to_crush_error(signer.sign_to_vec())?;
+            Ok(expected.len() == signature.len() && memcmp::eq(&expected, signature))
+        }
+        "RS" | "PS" => {
+            let key = public_key(key)?;
+            let mut verifier = to_crush_error(Verifier::new(digest(algorithm)?, &key))?;
+            if algorithm.starts_with("PS") {
+                to_crush_error(verifier.set_rsa_padding(Padding::PKCS1_PSS))?;
+            }
+            to_crush_error(verifier.update(message))?;
+            Ok(verifier.verify(signature).unwrap_or(false))
+        }
+        "ES" => {
+            let key = public_key(key)?;
+            let mut verifier = to_crush_error(Verifier::new(digest(algorithm)?, &key))?;
+            to_crush_error(verifier.update(message))?;
+            Ok(verifier.verify(&ecdsa_der(signature)?).unwrap_or(false))
+        }
+        "Ed" if algorithm == "EdDSA" => {
+            let key = public_key(key)?;
+            let mut verifier = to_crush_error(Verifier::new_without_digest(&key))?;
+            Ok(verifier.verify_oneshot(signature, message).unwrap_or(false))
+        }
+        _ => argument_error(format!("Unsupported algorithm {}", algorithm).as_str()),
+    }
+}
+
+#[signature(
+decode,
+can_block = false,
+output = Known(ValueType::Struct),
+short = "Decode a JSON Web Token",
+long = "Returns a struct with the header and the payload of the token, with the exp, nbf and iat",
+long = "claims converted to times, unless they are out of range. If a key is given, the signature is",
+long = "verified and the valid member is true or false, otherwise it is empty.",
+long = "",
+long = "For the HS algorithms, the key is the shared secret. For the RS, PS, ES and EdDSA algorithms,",
+long = "it is a public key in PEM format, given as a string or a file. Tokens using the none",
+long = "algorithm are never valid.",
+example = "(jwt:decode token):payload:exp < (time:now)")]
+struct Decode {
+    #[description("the token to decode.")]
+    token: String,
+    #[description("the key to verify the signature with, as a string, binary or file.")]
+    key: Option<Value>,
+}
+
+fn decode(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Decode = Decode::parse(context.arguments, &context.printer)?;
+    let token = cfg.token.trim();
+    let token = token.strip_prefix("Bearer ").unwrap_or(token);
+    let parts = token.split('.').collect::<Vec<_>>();
+    if parts.len() != 3 {
+        return data_error("Expected a token with three parts separated by dots");
+    }
+    let header = decode_part(parts[0], "header")?;
+    let payload = decode_part(parts[1], "payload")?;
+    let algorithm = header.get("alg").and_then(|a| a.as_str()).unwrap_or("none").to_string();
+
+    let valid = match cfg.key {
+        None => Value::Empty(),
+        Some(key) => {
+            let key = match key {
+                Value::String(s) => s.into_bytes(),
+                Value::Binary(b) => b,
+                Value::File(f) => to_crush_error(std::fs::read(f))?,
+                v => return argument_error(format!("Expected the key to be a string, binary or file, got a {}", v.value_type().to_string()).as_str()),
+            };
+            if algorithm == "none" {
+                Value::Bool(false)
+            } else {
+                let message = format!("{}.{}", parts[0], parts[1]);
+                Value::Bool(verify(&algorithm, &key, message.as_bytes(), &base64url_decode(parts[2])?)?)
+            }
+        }
+    };
+
+    context.output.send(Value::Struct(Struct::new(
+        vec![
+            ("header".to_string(), to_struct(header)?),
+            ("payload".to_string(), to_struct(payload)?),
+            ("valid".to_string(), valid),
+        ],
+        None)))
+}
+
+pub fn declare(root: &mut ScopeLoader) -> CrushResult<()> {
+    root.create_lazy_namespace(
+        "jwt",
+        Box::new(move |env| {
+            Decode::declare(env)?;
+            Ok(())
+        }))?;
+    Ok(())
+}
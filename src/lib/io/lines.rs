@@ -1,4 +1,4 @@
-use std::io::{BufReader, BufRead};
+use std::io::BufRead;
 use crate::lang::{
     execution_context::ExecutionContext,
     table::Row,
@@ -15,33 +15,55 @@ use crate::lang::scope::ScopeLoader;
 #[signature(
 from,
 can_block = true,
-short = "Read specified files (or input) as a table with one line of text per row")]
+short = "Read specified files (or input) as a table with one line of text per row",
+long = "The input can be binary data or a string. Lines are separated by newlines by default. In",
+long = "that case a carriage return before the newline is removed as well, so files with Windows",
+long = "line endings are read correctly.",
+example = "ps | select ^command | lines:to | lines:from separator=\"\\n\"")]
 struct From {
     #[unnamed()]
     #[description("the files to read from (read from input if no file is specified).")]
     files: Files,
+    #[default("\n")]
+    #[description("the string that separates lines.")]
+    separator: String,
+}
+
+fn line(mut buf: Vec<u8>, newline: bool) -> CrushResult<Value> {
+    if newline {
+        while buf.last() == Some(&b'\r') {
+            buf.pop();
+        }
+        let leading = buf.iter().take_while(|b| **b == b'\r').count();
+        buf.drain(..leading);
+    }
+    Ok(Value::String(to_crush_error(String::from_utf8(buf))?))
 }
 
 pub fn from(context: ExecutionContext) -> CrushResult<()> {
     let output = context.output.initialize(vec![ColumnType::new("line", ValueType::String)])?;
     let cfg: From = From::parse(context.arguments, &context.printer)?;
-    let mut reader = BufReader::new(cfg.files.reader(context.input)?);
-    let mut line = String::new();
+    let separator = cfg.separator.as_bytes();
+    let last = match separator.last() {
+        Some(last) => *last,
+        None => return argument_error("The separator can not be empty"),
+    };
+    let newline = cfg.separator == "\n";
+    let mut reader = cfg.files.reader(context.input)?.buffered();
+    let mut buf = Vec::new();
 
     loop {
-        to_crush_error(reader.read_line(&mut line))?;
-        if line.is_empty() {
+        // Read up to the last byte of the separator, the whole separator may not have been read yet
+        if to_crush_error(reader.read_until(last, &mut buf))? == 0 {
+            if !buf.is_empty() {
+                context.printer.handle_error(output.send(Row::new(vec![line(buf, newline)?])));
+            }
             break;
         }
-        let mut s = if line.ends_with('\n') { &line[0..line.len() - 1] } else { &line[..] };
-        while s.starts_with('\r') {
-            s = &s[1..];
-        }
-        while s.ends_with('\r') {
-            s = &s[0..line.len()-1];
+        if buf.ends_with(separator) {
+            buf.truncate(buf.len() - separator.len());
+            context.printer.handle_error(output.send(Row::new(vec![line(std::mem::take(&mut buf), newline)?])));
         }
-        context.printer.handle_error(output.send(Row::new(vec![Value::string(s)])));
-        line.clear();
     }
     Ok(())
 }
@@ -7,16 +7,33 @@ use crate::lang::pretty_printer::PrettyPrinter;
 use crate::lang::argument::ArgumentHandler;
 use crate::lang::command::OutputType::{Known};
 use signature::signature;
+use crate::lang::binary::BinaryReader;
 
+mod api;
+mod archive;
 mod bin;
+mod clip;
+mod compress;
+mod crypt;
 mod csv;
+mod dns;
+mod geojson;
+mod gpg;
+mod gpx;
 mod http;
-mod json;
+mod ics;
+pub mod json;
+mod jwt;
 mod lines;
 mod pup;
 mod split;
+mod style;
+mod tmpfile;
+mod tls;
 mod toml;
 mod words;
+mod ws;
+mod xml;
 
 pub fn val(mut context: ExecutionContext) -> CrushResult<()> {
     context.arguments.check_len(1)?;
@@ -74,18 +91,34 @@ pub fn declare(root: &Scope) -> CrushResult<()> {
     let e = root.create_lazy_namespace(
         "io",
         Box::new(move |env| {
+            api::declare(env)?;
+            archive::declare(env)?;
             bin::declare(env)?;
+            clip::declare(env)?;
+            compress::declare(env)?;
+            crypt::declare(env)?;
             csv::declare(env)?;
+            dns::declare(env)?;
+            geojson::declare(env)?;
+            gpg::declare(env)?;
+            gpx::declare(env)?;
+            ics::declare(env)?;
             pup::declare(env)?;
+            tls::declare(env)?;
             toml::declare(env)?;
             json::declare(env)?;
+            jwt::declare(env)?;
             lines::declare(env)?;
             split::declare(env)?;
             words::declare(env)?;
+            ws::declare(env)?;
+            xml::declare(env)?;
 
             http::Http::declare(env)?;
             Echo::declare(env)?;
             Member::declare(env)?;
+            tmpfile::Tmpfile::declare(env)?;
+            style::Style::declare(env)?;
             env.declare_command(
                 "val", val, false,
                 "val value:any",
@@ -96,6 +129,9 @@ pub fn declare(root: &Scope) -> CrushResult<()> {
             env.declare_command(
                 "dir", dir, false,
                 "dir value:any", "List members of value", None, Known(ValueType::Empty))?;
+            if !termion::is_tty(&std::io::stdin()) {
+                env.declare("stdin", Value::BinaryStream(BinaryReader::stdin()))?;
+            }
             Ok(())
         }))?;
     root.r#use(&e);
@@ -1,4 +1,4 @@
-use std::io::{BufReader, BufRead};
+use std::io::BufRead;
 use crate::lang::{
     execution_context::ExecutionContext,
     table::Row,
@@ -6,7 +6,7 @@ use crate::lang::{
     value::ValueType,
     value::Value,
 };
-use crate::lang::errors::{CrushResult, to_crush_error};
+use crate::lang::errors::{CrushResult, argument_error, to_crush_error};
 use crate::lang::files::Files;
 use signature::signature;
 use crate::lang::argument::ArgumentHandler;
@@ -44,7 +44,7 @@ pub fn from(context: ExecutionContext) -> CrushResult<()> {
     let output = context.output.initialize(vec![ColumnType::new("token", ValueType::String)])?;
     let cfg: From = From::parse(context.arguments, &context.printer)?;
 
-    let mut reader = BufReader::new(cfg.files.reader(context.input)?);
+    let mut reader = cfg.files.reader(context.input)?.buffered();
 
     let mut buf = Vec::<u8>::new();
     let mut token = String::new();
@@ -69,11 +69,79 @@ pub fn from(context: ExecutionContext) -> CrushResult<()> {
     Ok(())
 }
 
+#[signature(
+columns,
+can_block = true,
+short = "Read specified files (or input) as a table, splitting each line into columns",
+long = "Every line becomes a row, with one string column per field. The number of columns is the",
+long = "number of fields on the first line. Missing fields on later lines are empty, and any extra",
+long = "fields are left unsplit in the last column.",
+example = "val \"ann,37\\nbob,42\\n\" | split:columns separator=\",\"")]
+struct Columns {
+    #[unnamed()]
+    #[description("the files to read from (read from input if no file is specified).")]
+    files: Files,
+    #[default(",")]
+    #[description("the string that separates fields.")]
+    separator: String,
+    #[description("characters to trim from start and end of each field.")]
+    trim: Option<String>,
+    #[default(false)]
+    #[description("use the fields of the first line as column names instead of column1, column2 and so on.")]
+    header: bool,
+}
+
+fn fields(line: &str, separator: &str, trim: &Option<String>, count: Option<usize>) -> Vec<String> {
+    let parts: Vec<&str> = match count {
+        Some(count) => line.splitn(count, separator).collect(),
+        None => line.split(separator).collect(),
+    };
+    parts.into_iter()
+        .map(|field| match trim {
+            Some(t) => field.trim_matches(|ch| t.contains(ch)).to_string(),
+            None => field.to_string(),
+        })
+        .collect()
+}
+
+pub fn columns(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Columns = Columns::parse(context.arguments, &context.printer)?;
+    if cfg.separator.is_empty() {
+        return argument_error("The separator can not be empty");
+    }
+    let reader = cfg.files.reader(context.input)?.buffered();
+    let mut lines = reader.lines();
+
+    let first = match lines.next() {
+        Some(line) => to_crush_error(line)?,
+        None => return Ok(()),
+    };
+    let first = fields(first.trim_end_matches('\r'), &cfg.separator, &cfg.trim, None);
+    let names = if cfg.header {
+        first.clone()
+    } else {
+        (1..=first.len()).map(|idx| format!("column{}", idx)).collect()
+    };
+    let output = context.output.initialize(
+        names.iter().map(|name| ColumnType::new(name, ValueType::String)).collect())?;
+    if !cfg.header {
+        output.send(Row::new(first.into_iter().map(Value::String).collect()))?;
+    }
+    for line in lines {
+        let line = to_crush_error(line)?;
+        let mut row = fields(line.trim_end_matches('\r'), &cfg.separator, &cfg.trim, Some(names.len()));
+        row.resize(names.len(), String::new());
+        output.send(Row::new(row.into_iter().map(Value::String).collect()))?;
+    }
+    Ok(())
+}
+
 pub fn declare(root: &mut ScopeLoader) -> CrushResult<()> {
     root.create_lazy_namespace(
         "split",
         Box::new(move |env| {
             From::declare(env)?;
+            Columns::declare(env)?;
             Ok(())
         }))?;
     Ok(())
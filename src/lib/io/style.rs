@@ -0,0 +1,57 @@
+use crate::lang::execution_context::ExecutionContext;
+use crate::lang::errors::CrushResult;
+use crate::lang::value::{Value, ValueType};
+use crate::lang::command::OutputType::Known;
+use crate::lang::style::{Color, Styled, Style as TextStyle};
+use signature::signature;
+use crate::lang::argument::ArgumentHandler;
+
+#[signature(
+style,
+can_block = false,
+output = Known(ValueType::Styled),
+short = "Apply colors and other attributes to text",
+long = "The attributes are kept as part of the value and are only turned into escape codes when",
+long = "the value is printed to a terminal, so styled text can be stored, compared and written to",
+long = "files like any other text. Styling an already styled value adds to its attributes.",
+long = "",
+long = "Colors are one of black, red, green, yellow, blue, magenta, cyan and white, the same",
+long = "names prefixed with bright_, or an rgb color written as #rrggbb.",
+example = "style \"error\" foreground=\"red\" bold=true")]
+pub struct Style {
+    #[description("the text to style. Values that are not text are converted to text.")]
+    text: Value,
+    #[description("the color of the text.")]
+    foreground: Option<String>,
+    #[description("the color behind the text.")]
+    background: Option<String>,
+    #[default(false)]
+    #[description("render the text in bold.")]
+    bold: bool,
+    #[default(false)]
+    #[description("render the text with decreased intensity.")]
+    dim: bool,
+    #[default(false)]
+    #[description("render the text in italics.")]
+    italic: bool,
+    #[default(false)]
+    #[description("underline the text.")]
+    underline: bool,
+}
+
+fn style(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Style = Style::parse(context.arguments, &context.printer)?;
+    let attributes = TextStyle {
+        foreground: cfg.foreground.as_deref().map(Color::parse).transpose()?,
+        background: cfg.background.as_deref().map(Color::parse).transpose()?,
+        bold: cfg.bold,
+        dim: cfg.dim,
+        italic: cfg.italic,
+        underline: cfg.underline,
+    };
+    let styled = match cfg.text {
+        Value::Styled(s) => Styled::new(&s.text, s.style.merge(&attributes)),
+        v => Styled::new(&v.to_string(), attributes),
+    };
+    context.output.send(Value::Styled(styled))
+}
@@ -0,0 +1,254 @@
+use std::convert::TryFrom;
+use std::net::{IpAddr, TcpStream, ToSocketAddrs};
+
+use chrono::{Duration, Local, TimeZone};
+use lazy_static::lazy_static;
+use openssl::asn1::{Asn1Time, Asn1TimeRef};
+use openssl::hash::MessageDigest;
+use openssl::ssl::{SslConnector, SslMethod, SslVerifyMode};
+use openssl::x509::{X509, X509NameRef, X509Ref};
+
+use crate::lang::argument::ArgumentHandler;
+use crate::lang::command::OutputType::Known;
+use crate::lang::errors::{CrushResult, argument_error, data_error, error, mandate, to_crush_error};
+use crate::lang::execution_context::ExecutionContext;
+use crate::lang::files::Files;
+use crate::lang::list::List;
+use crate::lang::scope::ScopeLoader;
+use crate::lang::table::{ColumnType, Row};
+use crate::lang::value::{Value, ValueType};
+use signature::signature;
+
+lazy_static! {
+    static ref CERT_OUTPUT_TYPE: Vec<ColumnType> = output_type(ColumnType::new("host", ValueType::String));
+    static ref CERT_FILE_OUTPUT_TYPE: Vec<ColumnType> = output_type(ColumnType::new("file", ValueType::File));
+}
+
+fn output_type(source: ColumnType) -> Vec<ColumnType> {
+    vec![
+        source,
+        ColumnType::new("depth", ValueType::Integer),
+        ColumnType::new("subject", ValueType::String),
+        ColumnType::new("issuer", ValueType::String),
+        ColumnType::new("sans", ValueType::List(Box::new(ValueType::String))),
+        ColumnType::new("not_before", ValueType::Time),
+        ColumnType::new("not_after", ValueType::Time),
+        ColumnType::new("serial", ValueType::String),
+        ColumnType::new("sha256", ValueType::String),
+    ]
+}
+
+fn hex(data: &[u8]) -> String {
+    data.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/** Formats a name the way most tools do, e.g. "O=Example, CN=example.com". */
+fn format_name(name: &X509NameRef) -> String {
+    name.entries()
+        .map(|entry| {
+            let key = entry.object().nid().short_name().unwrap_or("?");
+            let value = entry.data().as_utf8()
+                .map(|s| s.to_string())
+                .unwrap_or_else(|_| String::from_utf8_lossy(entry.data().as_slice()).to_string());
+            format!("{}={}", key, value)
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn sans(cert: &X509Ref) -> Value {
+    let mut res = Vec::new();
+    if let Some(names) = cert.subject_alt_names() {
+        for name in names.iter() {
+            if let Some(dns) = name.dnsname() {
+                res.push(Value::string(dns));
+            } else if let Some(email) = name.email() {
+                res.push(Value::string(email));
+            } else if let Some(uri) = name.uri() {
+                res.push(Value::string(uri));
+            } else if let Some(ip) = name.ipaddress() {
+                let ip = match ip.len() {
+                    4 => <[u8; 4]>::try_from(ip).map(|b| IpAddr::from(b).to_string()),
+                    16 => <[u8; 16]>::try_from(ip).map(|b| IpAddr::from(b).to_string()),
+                    _ => Ok(hex(ip)),
+                };
+                res.push(Value::String(ip.unwrap_or_default()));
+            }
+        }
+    }
+    Value::List(List::new(ValueType::String, res))
+}
+
+fn time(time: &Asn1TimeRef) -> CrushResult<Value> {
+    let epoch = to_crush_error(Asn1Time::from_unix(0))?;
+    let diff = to_crush_error(epoch.diff(time))?;
+    Ok(Local.timestamp_opt(i64::from(diff.days) * 86400 + i64::from(diff.secs), 0)
+        .single()
+        .map(Value::Time)
+        .unwrap_or(Value::Empty()))
+}
+
+fn rows(source: Value, chain: &[&X509Ref]) -> CrushResult<Vec<Row>> {
+    let mut res = Vec::new();
+    for (depth, cert) in chain.iter().enumerate() {
+        let serial = to_crush_error(cert.serial_number().to_bn())?;
+        res.push(Row::new(vec![
+            source.clone(),
+            Value::Integer(depth as i128),
+            Value::String(format_name(cert.subject_name())),
+            Value::String(format_name(cert.issuer_name())),
+            sans(cert),
+            time(cert.not_before())?,
+            time(cert.not_after())?,
+            Value::String(to_crush_error(serial.to_hex_str())?.to_lowercase()),
+            Value::String(hex(&to_crush_error(cert.digest(MessageDigest::sha256()))?)),
+        ]));
+    }
+    Ok(res)
+}
+
+/** Splits host:port into the host name used for SNI and the address to connect to. */
+fn split_host(host: &str) -> CrushResult<(String, String)> {
+    if let Some(rest) = host.strip_prefix('[') {
+        let (name, port) = mandate(rest.split_once(']'), "Missing ] in IPv6 address")?;
+        return match port.strip_prefix(':') {
+            Some(port) => Ok((name.to_string(), format!("[{}]:{}", name, port))),
+            None if port.is_empty() => Ok((name.to_string(), format!("[{}]:443", name))),
+            None => argument_error(format!("Invalid host {}", host).as_str()),
+        };
+    }
+    match host.rsplit_once(':') {
+        Some((name, _)) => Ok((name.to_string(), host.to_string())),
+        None => Ok((host.to_string(), format!("{}:443", host))),
+    }
+}
+
+fn fetch_chain(host: &str, timeout: std::time::Duration) -> CrushResult<Vec<X509>> {
+    let (name, address) = split_host(host)?;
+    let address = mandate(
+        to_crush_error(address.to_socket_addrs())?.next(),
+        format!("Could not resolve {}", host).as_str())?;
+    let tcp = match TcpStream::connect_timeout(&address, timeout) {
+        Ok(tcp) => tcp,
+        Err(e) => return error(format!("Could not connect to {}: {}", host, e).as_str()),
+    };
+    to_crush_error(tcp.set_read_timeout(Some(timeout)))?;
+    to_crush_error(tcp.set_write_timeout(Some(timeout)))?;
+
+    let mut builder = to_crush_error(SslConnector::builder(SslMethod::tls()))?;
+    builder.set_verify(SslVerifyMode::NONE);
+    let stream = match builder.build().connect(&name, tcp) {
+        Ok(stream) => stream,
+        Err(e) => return error(format!("TLS handshake with {} failed: {}", host, e).as_str()),
+    };
+    let chain = mandate(stream.ssl().peer_cert_chain(), "The server did not send any certificates")?;
+    Ok(chain.iter().map(|c| c.to_owned()).collect())
+}
+
+#[signature(
+cert,
+can_block = true,
+output = Known(ValueType::TableStream(CERT_OUTPUT_TYPE.clone())),
+short = "Fetch the certificate chain of TLS servers",
+long = "Connects to each host, given as host or host:port with port 443 as the default, and outputs",
+long = "one row per certificate in the chain the server sends. The depth of the server certificate",
+long = "is 0, and its issuers follow. The chain is returned as is, without being verified, so that",
+long = "expired and self-signed certificates can be inspected too.",
+long = "",
+long = "Hosts that can not be reached are reported as errors, and the remaining hosts are still",
+long = "checked.",
+example = "tls:cert \"example.com\" \"mail.example.com:465\" | where {depth == 0 and not_after < (time:now) + (duration:new days=30)}")]
+struct Cert {
+    #[unnamed()]
+    #[description("the hosts to connect to.")]
+    hosts: Vec<String>,
+    #[description("how long to wait for each host. The default is ten seconds.")]
+    timeout: Option<Duration>,
+}
+
+fn cert(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Cert = Cert::parse(context.arguments, &context.printer)?;
+    let timeout = match cfg.timeout {
+        None => std::time::Duration::from_secs(10),
+        Some(timeout) => to_crush_error(timeout.to_std())?,
+    };
+    let output = context.output.initialize(CERT_OUTPUT_TYPE.clone())?;
+    for host in cfg.hosts {
+        let rows = fetch_chain(&host, timeout)
+            .and_then(|chain| rows(Value::String(host.clone()), &chain.iter().map(|c| c.as_ref()).collect::<Vec<_>>()));
+        match rows {
+            Ok(rows) => {
+                for row in rows {
+                    output.send(row)?;
+                }
+            }
+            Err(e) => context.printer.crush_error(e),
+        }
+    }
+    Ok(())
+}
+
+#[signature(
+cert_file,
+can_block = true,
+output = Known(ValueType::TableStream(CERT_FILE_OUTPUT_TYPE.clone())),
+short = "Read the certificates in PEM or DER files",
+long = "Outputs one row per certificate, in the order they appear in the file. A PEM file may",
+long = "contain a whole chain, in which case the depth is the position of each certificate.",
+example = "tls:cert_file ./certs/*.pem | where {not_after < (time:now)} | select ^file ^subject")]
+struct CertFile {
+    #[unnamed()]
+    #[description("the files to read.")]
+    files: Files,
+}
+
+fn cert_file(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: CertFile = CertFile::parse(context.arguments, &context.printer)?;
+    let output = context.output.initialize(CERT_FILE_OUTPUT_TYPE.clone())?;
+    for file in cfg.files.into_vec() {
+        let data = to_crush_error(std::fs::read(&file))?;
+        let chain = match X509::stack_from_pem(&data) {
+            Ok(chain) if !chain.is_empty() => chain,
+            _ => match X509::from_der(&data) {
+                Ok(cert) => vec![cert],
+                Err(_) => return data_error(format!("{} does not contain a certificate", file.to_string_lossy()).as_str()),
+            },
+        };
+        for row in rows(Value::File(file), &chain.iter().map(|c| c.as_ref()).collect::<Vec<_>>())? {
+            output.send(row)?;
+        }
+    }
+    Ok(())
+}
+
+pub fn declare(root: &mut ScopeLoader) -> CrushResult<()> {
+    root.create_lazy_namespace(
+        "tls",
+        Box::new(move |env| {
+            Cert::declare(env)?;
+            CertFile::declare(env)?;
+            Ok(())
+        }))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_host_works() {
+        assert_eq!(split_host("example.com").unwrap(), ("example.com".to_string(), "example.com:443".to_string()));
+        assert_eq!(split_host("example.com:8443").unwrap(), ("example.com".to_string(), "example.com:8443".to_string()));
+        assert_eq!(split_host("[::1]").unwrap(), ("::1".to_string(), "[::1]:443".to_string()));
+        assert_eq!(split_host("[::1]:8443").unwrap(), ("::1".to_string(), "[::1]:8443".to_string()));
+        assert!(split_host("[::1").is_err());
+    }
+
+    #[test]
+    fn times() {
+        let time = |s: &str| super::time(&Asn1Time::from_str(s).unwrap()).unwrap();
+        assert!(time("20200913122640Z") == Value::Time(Local.timestamp_opt(1600000000, 0).unwrap()));
+        assert!(matches!(time("99991231235959Z"), Value::Time(_)));
+    }
+}
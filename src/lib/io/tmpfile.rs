@@ -0,0 +1,77 @@
+use crate::lang::execution_context::ExecutionContext;
+use crate::lang::errors::{CrushResult, to_crush_error, argument_error};
+use crate::lang::value::{Value, ValueType};
+use crate::lang::command::OutputType::Known;
+use signature::signature;
+use crate::lang::argument::ArgumentHandler;
+use std::fs::{File, OpenOptions};
+use std::io::{Write, ErrorKind};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+#[signature(
+tmpfile,
+can_block = true,
+output = Known(ValueType::File),
+short = "Write the input to a temporary file and return the path of the file",
+long = "Binary streams and strings are written as is. Each row of a table stream is written on its",
+long = "own line, with the cells separated by tabs.",
+long = "",
+long = "This is useful for passing the output of a pipeline to external commands that expect a",
+long = "file name. The file is removed when the job that created it finishes.",
+example = "diff (ls | tmpfile) (ls /tmp | tmpfile)")]
+pub struct Tmpfile {
+    #[default("")]
+    #[description("a suffix for the file name, for commands that care about the file extension.")]
+    suffix: String,
+}
+
+fn create(suffix: &str) -> CrushResult<(PathBuf, File)> {
+    loop {
+        let path = std::env::temp_dir().join(format!(
+            "crush-{}-{}{}",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed),
+            suffix));
+        match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(file) => return Ok((path, file)),
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => continue,
+            Err(e) => return to_crush_error(Err(e)),
+        }
+    }
+}
+
+fn write(value: Value, file: &mut File) -> CrushResult<()> {
+    match value {
+        Value::BinaryStream(mut input) => {
+            to_crush_error(std::io::copy(input.as_mut(), file))?;
+        }
+        Value::Binary(b) => to_crush_error(file.write_all(&b))?,
+        Value::String(s) => to_crush_error(file.write_all(s.as_bytes()))?,
+        value => match value.stream() {
+            Some(mut input) => {
+                while let Ok(row) = input.read() {
+                    let mut line = row.cells()
+                        .iter()
+                        .map(|c| c.to_string())
+                        .collect::<Vec<_>>()
+                        .join("\t");
+                    line.push('\n');
+                    to_crush_error(file.write_all(line.as_bytes()))?;
+                }
+            }
+            None => return argument_error("Expected a binary stream, a string or a table stream"),
+        }
+    }
+    Ok(())
+}
+
+pub fn tmpfile(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Tmpfile = Tmpfile::parse(context.arguments, &context.printer)?;
+    let (path, mut file) = create(&cfg.suffix)?;
+    context.env.add_temporary_file(path.clone());
+    write(context.input.recv()?, &mut file)?;
+    context.output.send(Value::File(path))
+}
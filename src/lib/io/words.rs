@@ -1,4 +1,4 @@
-use std::io::{BufReader, BufRead};
+use std::io::BufRead;
 use crate::lang::{
     execution_context::ExecutionContext,
     table::Row,
@@ -36,7 +36,7 @@ pub fn from(context: ExecutionContext) -> CrushResult<()> {
     let output = context.output.initialize(vec![ColumnType::new("word", ValueType::String)])?;
     let cfg: From = From::parse(context.arguments, &context.printer)?;
 
-    let mut reader = BufReader::new(cfg.files.reader(context.input)?);
+    let mut reader = cfg.files.reader(context.input)?.buffered();
 
     let mut buf = Vec::<u8>::new();
     let mut token = String::new();
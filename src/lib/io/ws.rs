@@ -0,0 +1,237 @@
+use std::collections::HashMap;
+use std::io::Read;
+use std::sync::{Arc, Mutex};
+
+use chrono::{Duration, Local};
+use lazy_static::lazy_static;
+
+use crate::lang::argument::ArgumentHandler;
+use crate::lang::command::OutputType::Known;
+use crate::lang::errors::{CrushResult, argument_error, error, mandate, to_crush_error};
+use crate::lang::execution_context::ExecutionContext;
+use crate::lang::r#struct::Struct;
+use crate::lang::scope::ScopeLoader;
+use crate::lang::stream::{OutputStream, streams};
+use crate::lang::table::{ColumnType, Row};
+use crate::lang::value::{Value, ValueType};
+use crate::util::thread::build;
+use crate::util::websocket::{Message, Receiver, WebSocket};
+use signature::signature;
+
+use super::json::to_json;
+
+lazy_static! {
+    static ref MESSAGE_OUTPUT_TYPE: Vec<ColumnType> = vec![
+        ColumnType::new("type", ValueType::String),
+        ColumnType::new("data", ValueType::Any),
+        ColumnType::new("time", ValueType::Time),
+    ];
+    /** The open connections, by the id of their handle. */
+    static ref SOCKETS: Mutex<HashMap<i128, Arc<WebSocket>>> = Mutex::new(HashMap::new());
+    static ref NEXT_ID: Mutex<i128> = Mutex::new(1);
+}
+
+fn message_row(message: Message) -> Row {
+    let (kind, data) = match message {
+        Message::Text(text) => ("text", Value::String(text)),
+        Message::Binary(data) => ("binary", Value::Binary(data)),
+    };
+    Row::new(vec![Value::string(kind), data, Value::Time(Local::now())])
+}
+
+/** Passes on messages until the connection closes or nobody reads the messages any more. */
+fn receive(socket: &WebSocket, mut receiver: Receiver, output: OutputStream) -> CrushResult<()> {
+    while let Some(message) = socket.receive(&mut receiver)? {
+        if output.send(message_row(message)).is_err() {
+            let _ = socket.close();
+            break;
+        }
+    }
+    Ok(())
+}
+
+#[signature(
+connect,
+can_block = true,
+output = Known(ValueType::Struct),
+short = "Connect to a WebSocket server",
+long = "Return a struct with the following fields:",
+long = "* url:string, the url connected to",
+long = "* id:integer, the id of the connection, for use with ws:send and ws:close",
+long = "* protocol:string, the subprotocol picked by the server, if any",
+long = "* messages:table_stream, the messages received, with the columns type, data and time",
+long = "",
+long = "Text messages have the type \"text\" and their data is a string. Binary messages have the",
+long = "type \"binary\" and their data is binary. The messages stream ends when the connection is",
+long = "closed, and the connection is closed when nothing reads the messages stream any more.",
+example = "feed := (ws:connect \"wss://feed.example.com/trades\"); feed:messages | where {type == \"text\"}")]
+struct Connect {
+    #[description("the ws:// or wss:// url to connect to.")]
+    url: String,
+    #[description("extra HTTP headers for the handshake, must be on the form \"key:value\".")]
+    header: Vec<String>,
+    #[description("subprotocols to ask the server for.")]
+    protocol: Vec<String>,
+    #[description("how long to wait for the connection to be established. The default is ten seconds.")]
+    timeout: Option<Duration>,
+}
+
+fn connect(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Connect = Connect::parse(context.arguments, &context.printer)?;
+    let mut headers = Vec::new();
+    for h in &cfg.header {
+        match h.split_once(':') {
+            Some((name, value)) => headers.push((name.trim().to_string(), value.trim().to_string())),
+            None => return argument_error("Bad header format"),
+        }
+    }
+    if !cfg.protocol.is_empty() {
+        headers.push(("Sec-WebSocket-Protocol".to_string(), cfg.protocol.join(", ")));
+    }
+    let timeout = to_crush_error(cfg.timeout.unwrap_or_else(|| Duration::seconds(10)).to_std())?;
+    let (socket, receiver, protocol) = WebSocket::connect(&cfg.url, &headers, timeout)?;
+    let socket = Arc::new(socket);
+
+    let id = {
+        let mut next = NEXT_ID.lock().unwrap();
+        *next += 1;
+        *next - 1
+    };
+    SOCKETS.lock().unwrap().insert(id, socket.clone());
+
+    let (output, input) = streams(MESSAGE_OUTPUT_TYPE.clone());
+    let printer = context.printer.clone();
+    to_crush_error(build("ws-receive").spawn(move || {
+        printer.handle_error(receive(&socket, receiver, output));
+        SOCKETS.lock().unwrap().remove(&id);
+    }))?;
+
+    context.output.send(Value::Struct(Struct::new(
+        vec![
+            ("url".to_string(), Value::String(cfg.url)),
+            ("id".to_string(), Value::Integer(id)),
+            ("protocol".to_string(), protocol.map(Value::String).unwrap_or(Value::Empty())),
+            ("messages".to_string(), Value::TableStream(input)),
+        ],
+        None,
+    )))
+}
+
+/** Finds the connection of a handle returned by ws:connect, or of its id. */
+fn socket(handle: Option<Value>) -> CrushResult<Arc<WebSocket>> {
+    let id = match handle {
+        Some(Value::Integer(id)) => id,
+        Some(Value::Struct(s)) => match s.get("id") {
+            Some(Value::Integer(id)) => id,
+            _ => return argument_error("Expected a connection returned by ws:connect"),
+        },
+        Some(v) => return argument_error(format!(
+            "Expected a connection returned by ws:connect, got a value of type {}",
+            v.value_type().to_string()).as_str()),
+        None => return argument_error("Expected a connection"),
+    };
+    mandate(
+        SOCKETS.lock().unwrap().get(&id).cloned(),
+        format!("No open WebSocket connection with id {}", id).as_str())
+}
+
+/** Strings are sent as text, binary data as binary, and anything else as JSON text. */
+fn message(value: Value) -> CrushResult<Message> {
+    match value {
+        Value::String(s) => Ok(Message::Text(s)),
+        Value::Binary(b) => Ok(Message::Binary(b)),
+        Value::BinaryStream(mut s) => {
+            let mut data = Vec::new();
+            to_crush_error(s.read_to_end(&mut data))?;
+            Ok(Message::Binary(data))
+        }
+        Value::TableStream(_) => argument_error("Can't send a table stream as a message, use materialize to turn it into a table"),
+        v => Ok(Message::Text(to_crush_error(serde_json::to_string(&to_json(v)?))?)),
+    }
+}
+
+#[signature(
+send,
+can_block = true,
+output = Known(ValueType::Empty),
+short = "Send messages on a WebSocket connection",
+long = "Strings are sent as text messages, and binary data and binary streams as binary messages.",
+long = "Any other value is sent as a text message with the value in JSON format. If no values are",
+long = "given, the rows of the input are sent instead, using the value of their only column, or",
+long = "the whole row as a JSON object if there are several columns.",
+example = "ws:send feed (data action=\"subscribe\" channel=\"trades\")")]
+struct Send {
+    #[description("the connection, as returned by ws:connect.")]
+    socket: Option<Value>,
+    #[unnamed()]
+    #[description("the values to send.")]
+    values: Vec<Value>,
+}
+
+fn send(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Send = Send::parse(context.arguments, &context.printer)?;
+    let socket = socket(cfg.socket)?;
+    if cfg.values.is_empty() {
+        let mut input = mandate(context.input.recv()?.stream(), "Expected a stream")?;
+        let types = input.types().to_vec();
+        while let Ok(row) = input.read() {
+            let value = if types.len() == 1 {
+                row.into_vec().remove(0)
+            } else {
+                Value::Struct(row.into_struct(&types))
+            };
+            socket.send(&message(value)?)?;
+        }
+    } else {
+        for value in cfg.values {
+            socket.send(&message(value)?)?;
+        }
+    }
+    context.output.send(Value::Empty())
+}
+
+#[signature(
+close,
+can_block = false,
+output = Known(ValueType::Empty),
+short = "Close a WebSocket connection",
+long = "The messages stream of the connection ends once the server has acknowledged the close.",
+example = "ws:close feed")]
+struct Close {
+    #[description("the connection, as returned by ws:connect.")]
+    socket: Option<Value>,
+}
+
+fn close(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Close = Close::parse(context.arguments, &context.printer)?;
+    match socket(cfg.socket)?.close() {
+        Ok(()) => context.output.send(Value::Empty()),
+        Err(_) => error("The connection is already closed"),
+    }
+}
+
+pub fn declare(root: &mut ScopeLoader) -> CrushResult<()> {
+    root.create_lazy_namespace(
+        "ws",
+        Box::new(move |env| {
+            Connect::declare(env)?;
+            Send::declare(env)?;
+            Close::declare(env)?;
+            Ok(())
+        }))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn messages() {
+        assert_eq!(message(Value::string("hi")).unwrap(), Message::Text("hi".to_string()));
+        assert_eq!(message(Value::Binary(vec![1, 2])).unwrap(), Message::Binary(vec![1, 2]));
+        assert_eq!(message(Value::Integer(7)).unwrap(), Message::Text("7".to_string()));
+        assert!(socket(Some(Value::Integer(-1))).is_err());
+        assert!(socket(Some(Value::string("x"))).is_err());
+    }
+}
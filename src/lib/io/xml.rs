@@ -0,0 +1,151 @@
+use std::io::BufReader;
+
+use lazy_static::lazy_static;
+
+use crate::lang::argument::ArgumentHandler;
+use crate::lang::command::OutputType::Known;
+use crate::lang::errors::{CrushResult, data_error};
+use crate::lang::execution_context::ExecutionContext;
+use crate::lang::files::Files;
+use crate::lang::list::List;
+use crate::lang::r#struct::Struct;
+use crate::lang::scope::ScopeLoader;
+use crate::lang::{table::ColumnType, table::Row, value::Value, value::ValueType};
+use crate::util::xml::{Event, Reader, local_name};
+use signature::signature;
+
+lazy_static! {
+    static ref OUTPUT_TYPE: Vec<ColumnType> = vec![
+        ColumnType::new("record", ValueType::Struct),
+    ];
+}
+
+/** An element inside of a record that has been started but not yet ended. */
+struct Element {
+    name: String,
+    attributes: Vec<(String, String)>,
+    children: Vec<(String, Value)>,
+    text: String,
+}
+
+impl Element {
+    fn new(name: &str, attributes: Vec<(String, String)>) -> Element {
+        Element {
+            name: name.to_string(),
+            attributes,
+            children: Vec::new(),
+            text: String::new(),
+        }
+    }
+
+    fn is_simple(&self) -> bool {
+        self.attributes.is_empty() && self.children.is_empty()
+    }
+
+    fn into_struct(self) -> Struct {
+        let mut members: Vec<(String, Value)> = self.attributes.into_iter()
+            .map(|(name, value)| (local_name(&name).to_string(), Value::String(value)))
+            .collect();
+        // Repeated children are collected into a list, placed where the first one was
+        let mut names: Vec<String> = Vec::new();
+        let mut values: Vec<Vec<Value>> = Vec::new();
+        for (name, value) in self.children {
+            match names.iter().position(|n| *n == name) {
+                Some(idx) => values[idx].push(value),
+                None => {
+                    names.push(name);
+                    values.push(vec![value]);
+                }
+            }
+        }
+        for (name, mut values) in names.into_iter().zip(values) {
+            let value = if values.len() == 1 {
+                values.remove(0)
+            } else {
+                let element_type = values[0].value_type();
+                let element_type = if values.iter().all(|v| v.value_type() == element_type) { element_type } else { ValueType::Any };
+                Value::List(List::new(element_type, values))
+            };
+            members.push((name, value));
+        }
+        let text = self.text.trim();
+        if !text.is_empty() {
+            members.push(("text".to_string(), Value::string(text)));
+        }
+        Struct::new(members, None)
+    }
+
+    fn into_value(self) -> Value {
+        if self.is_simple() {
+            Value::string(self.text.trim())
+        } else {
+            Value::Struct(self.into_struct())
+        }
+    }
+}
+
+#[signature(
+records,
+can_block = true,
+output = Known(ValueType::TableStream(OUTPUT_TYPE.clone())),
+short = "Stream the matching elements of an XML document as structs",
+long = "The document is read incrementally, and only the element currently being converted is kept",
+long = "in memory, so arbitrarily large documents can be processed. Every element with the specified",
+long = "tag is output as a row with a single column named record, containing a struct. Elements with",
+long = "the tag that are nested inside of another matching element are part of the outer record.",
+long = "",
+long = "The members of the struct are the attributes and child elements of the element. Child elements",
+long = "with only text are converted to strings, other child elements are converted to structs in the",
+long = "same way. Repeated child elements become a list, and the text of an element that also has",
+long = "attributes or children is put in a member named text. Namespace prefixes are removed from",
+long = "member names, and no attempt is made to convert values from strings to other types.",
+example = "xml:records ./pages.xml tag=\"page\" | where {record:ns == \"0\"} | select title={record:title}")]
+struct Records {
+    #[unnamed()]
+    #[description("the file to read.")]
+    files: Files,
+    #[description("the name of the elements to output, with or without a namespace prefix.")]
+    tag: String,
+}
+
+fn records(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Records = Records::parse(context.arguments, &context.printer)?;
+    let output = context.output.initialize(OUTPUT_TYPE.clone())?;
+    let mut reader = Reader::new(BufReader::new(cfg.files.reader(context.input)?));
+    let mut stack: Vec<Element> = Vec::new();
+    while let Some(event) = reader.next()? {
+        match event {
+            Event::Start(name, attributes) => {
+                if !stack.is_empty() || name == cfg.tag || local_name(&name) == cfg.tag {
+                    stack.push(Element::new(&name, attributes));
+                }
+            }
+            Event::Text(text) => if let Some(element) = stack.last_mut() {
+                element.text.push_str(&text);
+            },
+            Event::End(name) => if let Some(element) = stack.pop() {
+                if element.name != name {
+                    return data_error(format!("Expected the end of element {}, found {}", element.name, name).as_str());
+                }
+                match stack.last_mut() {
+                    Some(parent) => parent.children.push((local_name(&name).to_string(), element.into_value())),
+                    None => output.send(Row::new(vec![Value::Struct(element.into_struct())]))?,
+                }
+            },
+        }
+    }
+    if let Some(element) = stack.last() {
+        return data_error(format!("Unexpected end of XML document inside of element {}", element.name).as_str());
+    }
+    Ok(())
+}
+
+pub fn declare(root: &mut ScopeLoader) -> CrushResult<()> {
+    root.create_lazy_namespace(
+        "xml",
+        Box::new(move |env| {
+            Records::declare(env)?;
+            Ok(())
+        }))?;
+    Ok(())
+}
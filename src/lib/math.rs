@@ -4,19 +4,32 @@ use crate::lang::{value::Value};
 use crate::lang::scope::Scope;
 use crate::lang::execution_context::ArgumentVector;
 use crate::lang::errors::argument_error;
-use crate::lang::command::OutputType::Known;
+use crate::lang::command::OutputType::Unknown;
 use crate::lang::value::ValueType;
+use crate::lib::broadcast::apply;
+
+const BROADCAST: &str = r#"    The first argument can also be a list, in which case the function is applied
+    to each element, or a field, in which case the function is applied to that
+    column of each row of the input.
+
+    Example:
+
+    ls | math:sqrt ^size"#;
+
+fn number(value: &Value) -> CrushResult<f64> {
+    match value {
+        Value::Float(f) => Ok(*f),
+        Value::Integer(i) => Ok(*i as f64),
+        v => argument_error(format!("Expected a number, got a {}", v.value_type().to_string()).as_str()),
+    }
+}
 
 macro_rules! math_fun {
     ($name:ident, $op:expr) => {
 fn $name(mut context: ExecutionContext) -> CrushResult<()> {
     context.arguments.check_len(1)?;
-    let x = match context.arguments.value(0)? {
-        Value::Float(f) => f,
-        Value::Integer(i) => i as f64,
-        v => return argument_error(format!("Expected a number, got a {}", v.value_type().to_string()).as_str()),
-    };
-    context.output.send(Value::Float($op(x)))
+    let subject = context.arguments.value(0)?;
+    apply(context.input, context.output, subject, Some(ValueType::Float), |x| Ok(Value::Float($op(number(x)?))))
 }
     }
 }
@@ -25,17 +38,9 @@ macro_rules! math_fun2 {
     ($name:ident, $op:expr) => {
 fn $name(mut context: ExecutionContext) -> CrushResult<()> {
     context.arguments.check_len(2)?;
-    let x = match context.arguments.value(0)? {
-        Value::Float(f) => f,
-        Value::Integer(i) => i as f64,
-        v => return argument_error(format!("Expected a number, got a {}", v.value_type().to_string()).as_str()),
-    };
-    let y = match context.arguments.value(1)? {
-        Value::Float(f) => f,
-        Value::Integer(i) => i as f64,
-        v => return argument_error(format!("Expected a number, got a {}", v.value_type().to_string()).as_str()),
-    };
-    context.output.send(Value::Float($op(x, y)))
+    let y = number(&context.arguments.value(1)?)?;
+    let subject = context.arguments.value(0)?;
+    apply(context.input, context.output, subject, Some(ValueType::Float), |x| Ok(Value::Float($op(number(x)?, y))))
 }
     }
 }
@@ -49,9 +54,24 @@ math_fun!(acos, |x:f64| x.acos());
 math_fun!(atan, |x:f64| x.atan());
 math_fun!(ceil, |x:f64| x.ceil());
 math_fun!(floor, |x:f64| x.floor());
+math_fun!(round, |x:f64| x.round());
 math_fun!(ln, |x:f64| x.ln());
+math_fun!(exp, |x:f64| x.exp());
 math_fun2!(pow, |x:f64, y:f64| x.powf(y));
 math_fun2!(log, |x:f64, y:f64| x.log(y));
+math_fun2!(atan2, |x:f64, y:f64| x.atan2(y));
+
+fn abs(mut context: ExecutionContext) -> CrushResult<()> {
+    context.arguments.check_len(1)?;
+    let subject = context.arguments.value(0)?;
+    apply(context.input, context.output, subject, None, |x| match x {
+        Value::Integer(i) => match i.checked_abs() {
+            Some(i) => Ok(Value::Integer(i)),
+            None => argument_error("Integer overflow"),
+        },
+        x => Ok(Value::Float(number(x)?.abs())),
+    })
+}
 
 pub fn declare(root: &Scope) -> CrushResult<()> {
     root.create_lazy_namespace(
@@ -61,62 +81,82 @@ pub fn declare(root: &Scope) -> CrushResult<()> {
                 "sin", sin, false,
                 "math:sin angle:float",
                 "The sine of the specified angle",
-                None, Known(ValueType::Float))?;
+                Some(BROADCAST), Unknown)?;
             env.declare_command(
                 "cos", cos, false,
                 "math:cos angle:float",
                 "The cosine of the specified angle",
-                None, Known(ValueType::Float))?;
+                Some(BROADCAST), Unknown)?;
             env.declare_command(
                 "tan", tan, false,
                 "math:tan angle:float",
                 "The tangent of the specified angle",
-                None, Known(ValueType::Float))?;
+                Some(BROADCAST), Unknown)?;
             env.declare_command(
                 "sqrt", sqrt, false,
-                "math:sqrt angle:float",
-                "The square root of the specified angle",
-                None, Known(ValueType::Float))?;
+                "math:sqrt number:float",
+                "The square root of the specified number",
+                Some(BROADCAST), Unknown)?;
             env.declare_command(
                 "asin", asin, false,
                 "math:asin arc:float",
                 "The inverse sine of the specified arc",
-                None, Known(ValueType::Float))?;
+                Some(BROADCAST), Unknown)?;
             env.declare_command(
                 "acos", acos, false,
                 "math:acos arc:float",
                 "The inverse cosine of the specified arc",
-                None, Known(ValueType::Float))?;
+                Some(BROADCAST), Unknown)?;
             env.declare_command(
                 "atan", atan, false,
                 "math:atan arc:float",
                 "The inverse tangent of the specified arc",
-                None, Known(ValueType::Float))?;
+                Some(BROADCAST), Unknown)?;
             env.declare_command(
                 "pow", pow, false,
                 "math:pow number:float n:float",
                 "Raise the number to n",
-                None, Known(ValueType::Float))?;
+                Some(BROADCAST), Unknown)?;
             env.declare_command(
                 "log", log, false,
                 "math:log number:float base:float",
                 "The logarithm of number in the specified base",
-                None, Known(ValueType::Float))?;
+                Some(BROADCAST), Unknown)?;
             env.declare_command(
                 "ln", ln, false,
                 "math:ln number:float",
                 "The natural logarithm of number",
-                None, Known(ValueType::Float))?;
+                Some(BROADCAST), Unknown)?;
             env.declare_command(
                 "floor", floor, false,
                 "math:floor number:float",
                 "The largest integer smaller than number",
-                None, Known(ValueType::Float))?;
+                Some(BROADCAST), Unknown)?;
             env.declare_command(
                 "ceil", ceil, false,
                 "math:ceil number:float",
                 "The smallest integer larger than number",
-                None, Known(ValueType::Float))?;
+                Some(BROADCAST), Unknown)?;
+            env.declare_command(
+                "round", round, false,
+                "math:round number:float",
+                "The integer closest to number, rounding half way cases away from zero",
+                Some(BROADCAST), Unknown)?;
+            env.declare_command(
+                "exp", exp, false,
+                "math:exp number:float",
+                "e raised to number",
+                Some(BROADCAST), Unknown)?;
+            env.declare_command(
+                "atan2", atan2, false,
+                "math:atan2 y:float x:float",
+                "The angle of the point (x, y), between -pi and pi",
+                Some(BROADCAST), Unknown)?;
+            env.declare_command(
+                "abs", abs, false,
+                "math:abs number:(integer|float)",
+                "The absolute value of number, of the same type as number",
+                Some(BROADCAST), Unknown)?;
             env.declare("pi", Value::Float(std::f64::consts::PI))?;
             env.declare("tau", Value::Float(std::f64::consts::PI * 2.0))?;
             env.declare("e", Value::Float(std::f64::consts::E))?;
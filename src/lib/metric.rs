@@ -0,0 +1,308 @@
+use std::sync::Mutex;
+use std::time::Instant;
+
+use chrono::{Duration, Local};
+use lazy_static::lazy_static;
+
+use crate::lang::argument::ArgumentHandler;
+use crate::lang::command::Command;
+use crate::lang::command::OutputType::Known;
+use crate::lang::errors::{CrushResult, argument_error};
+use crate::lang::execution_context::ExecutionContext;
+use crate::lang::scope::Scope;
+use crate::lang::table::{ColumnType, Row};
+use crate::lang::value::{Time, Value, ValueType};
+use crate::util::prometheus;
+use ordered_map::OrderedMap;
+use signature::signature;
+
+lazy_static! {
+    static ref LIST_OUTPUT_TYPE: Vec<ColumnType> = vec![
+        ColumnType::new("name", ValueType::String),
+        ColumnType::new("type", ValueType::String),
+        ColumnType::new("value", ValueType::Any),
+        ColumnType::new("count", ValueType::Integer),
+        ColumnType::new("updated", ValueType::Time),
+    ];
+    /** The metrics of this session, in the order they were first updated. */
+    static ref METRICS: Mutex<OrderedMap<String, Metric>> = Mutex::new(OrderedMap::new());
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Kind {
+    Counter,
+    Gauge,
+    Timer,
+}
+
+impl Kind {
+    fn name(self) -> &'static str {
+        match self {
+            Kind::Counter => "counter",
+            Kind::Gauge => "gauge",
+            Kind::Timer => "timer",
+        }
+    }
+}
+
+#[derive(Clone)]
+enum Number {
+    Integer(i128),
+    Float(f64),
+}
+
+impl Number {
+    fn from_value(value: Value) -> CrushResult<Number> {
+        match value {
+            Value::Integer(i) => Ok(Number::Integer(i)),
+            Value::Float(f) => Ok(Number::Float(f)),
+            v => argument_error(format!("Expected a number, got a value of type {}", v.value_type().to_string()).as_str()),
+        }
+    }
+
+    fn add(&self, other: &Number) -> Number {
+        match (self, other) {
+            (Number::Integer(a), Number::Integer(b)) => Number::Integer(a.saturating_add(*b)),
+            (a, b) => Number::Float(a.as_f64() + b.as_f64()),
+        }
+    }
+
+    fn as_f64(&self) -> f64 {
+        match self {
+            Number::Integer(i) => *i as f64,
+            Number::Float(f) => *f,
+        }
+    }
+
+    fn is_negative(&self) -> bool {
+        self.as_f64() < 0.0
+    }
+}
+
+#[derive(Clone)]
+struct Metric {
+    kind: Kind,
+    value: Number,
+    /** The total time of all runs, for timers. */
+    total: Duration,
+    /** The number of times the metric was updated. */
+    count: i128,
+    updated: Time,
+}
+
+/** Applies an update to a metric, creating it if needed, and checks it is of the expected kind. */
+fn update(name: &str, kind: Kind, apply: impl FnOnce(&mut Metric)) -> CrushResult<()> {
+    let mut metrics = METRICS.lock().unwrap();
+    let mut metric = metrics.get(name).cloned().unwrap_or(Metric {
+        kind,
+        value: Number::Integer(0),
+        total: Duration::zero(),
+        count: 0,
+        updated: Local::now(),
+    });
+    if metric.kind != kind {
+        return argument_error(format!("The metric {} is a {}, not a {}", name, metric.kind.name(), kind.name()).as_str());
+    }
+    apply(&mut metric);
+    metric.count += 1;
+    metric.updated = Local::now();
+    metrics.insert(name.to_string(), metric);
+    Ok(())
+}
+
+#[signature(
+incr,
+can_block = false,
+short = "Increase a counter metric of this session",
+long = "The counter is created with the value zero the first time it is increased. Counters can only",
+long = "go up, use metric:set for values that can also go down.",
+example = "for (files --recurse .) {metric:incr \"files_seen\"}")]
+struct Incr {
+    #[description("the name of the counter.")]
+    name: String,
+    #[description("the amount to increase the counter by, an integer or a float. The default is 1.")]
+    by: Option<Value>,
+}
+
+fn incr(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Incr = Incr::parse(context.arguments, &context.printer)?;
+    let by = Number::from_value(cfg.by.unwrap_or(Value::Integer(1)))?;
+    if by.is_negative() {
+        return argument_error("Counters can not be decreased");
+    }
+    update(&cfg.name, Kind::Counter, |metric| metric.value = metric.value.add(&by))?;
+    context.output.send(Value::Empty())
+}
+
+#[signature(
+set,
+can_block = false,
+short = "Set a gauge metric of this session to a value",
+long = "The gauge is created the first time it is set.",
+example = "metric:set \"queue_length\" (ls ./queue | count)")]
+struct Set {
+    #[description("the name of the gauge.")]
+    name: String,
+    #[description("the new value, an integer or a float.")]
+    to: Option<Value>,
+}
+
+fn set(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Set = Set::parse(context.arguments, &context.printer)?;
+    let value = match cfg.to {
+        Some(value) => Number::from_value(value)?,
+        None => return argument_error("Expected a value"),
+    };
+    update(&cfg.name, Kind::Gauge, |metric| metric.value = value)?;
+    context.output.send(Value::Empty())
+}
+
+#[signature(
+timer,
+can_block = true,
+short = "Run a command and add the time it took to a timer metric of this session",
+long = "The output of the command is passed on unchanged. A timer keeps the number of runs and the",
+long = "total time of all runs, so the average is the value divided by the count. The run is recorded",
+long = "even if the command fails.",
+example = "metric:timer \"backup\" {tar:to ./backup.tar ./data}")]
+struct Timer {
+    #[description("the name of the timer.")]
+    name: String,
+    #[description("the command to time.")]
+    command: Command,
+}
+
+fn timer(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Timer = Timer::parse(context.arguments.clone(), &context.printer)?;
+    let start = Instant::now();
+    let result = cfg.command.invoke(context.with_args(vec![], None));
+    let elapsed = Duration::from_std(start.elapsed()).unwrap_or_else(|_| Duration::max_value());
+    // A timer that has run for longer than a duration can hold stays at the largest duration
+    update(&cfg.name, Kind::Timer, |metric| {
+        metric.total = metric.total.checked_add(&elapsed).unwrap_or_else(Duration::max_value)
+    })?;
+    result
+}
+
+#[signature(
+list,
+can_block = false,
+output = Known(ValueType::TableStream(LIST_OUTPUT_TYPE.clone())),
+short = "Return a table stream of the metrics of this session",
+long = "Each row contains the name of a metric, whether it is a counter, gauge or timer, its value,",
+long = "how many times it was updated and when it was last updated. The value of a timer is the total",
+long = "time of all its runs.",
+example = "metric:list | where {type == \"counter\"}")]
+struct List {}
+
+fn list(context: ExecutionContext) -> CrushResult<()> {
+    List::parse(context.arguments, &context.printer)?;
+    let metrics = METRICS.lock().unwrap().clone();
+    let output = context.output.initialize(LIST_OUTPUT_TYPE.clone())?;
+    for (name, metric) in metrics.iter() {
+        output.send(Row::new(vec![
+            Value::string(name),
+            Value::string(metric.kind.name()),
+            match (metric.kind, &metric.value) {
+                (Kind::Timer, _) => Value::Duration(metric.total),
+                (_, Number::Integer(i)) => Value::Integer(*i),
+                (_, Number::Float(f)) => Value::Float(*f),
+            },
+            Value::Integer(metric.count),
+            Value::Time(metric.updated),
+        ]))?;
+    }
+    Ok(())
+}
+
+fn to_prometheus(metrics: &OrderedMap<String, Metric>) -> String {
+    let mut res = String::new();
+    for (name, metric) in metrics.iter() {
+        let name = prometheus::name(name);
+        match metric.kind {
+            Kind::Timer => {
+                let seconds = metric.total.num_microseconds()
+                    .map(|us| us as f64 / 1_000_000.0)
+                    .unwrap_or(metric.total.num_seconds() as f64);
+                res.push_str(&format!("# TYPE {} summary\n", name));
+                res.push_str(&format!("{}_sum {}\n", name, seconds));
+                res.push_str(&format!("{}_count {}\n", name, metric.count));
+            }
+            kind => {
+                let value = match metric.value {
+                    Number::Integer(i) => i.to_string(),
+                    Number::Float(f) => prometheus::format_value(f),
+                };
+                res.push_str(&format!("# TYPE {} {}\n", name, kind.name()));
+                res.push_str(&format!("{} {}\n", name, value));
+            }
+        }
+    }
+    res
+}
+
+#[signature(
+export,
+can_block = false,
+output = Known(ValueType::String),
+short = "Return the metrics of this session in the Prometheus text format",
+long = "Counters and gauges are exported as such, timers as summaries of their total time in seconds",
+long = "and number of runs. Characters that are not allowed in Prometheus metric names are replaced",
+long = "with underscores.",
+example = "metric:export | bin:to ./metrics.prom")]
+struct Export {}
+
+fn export(context: ExecutionContext) -> CrushResult<()> {
+    Export::parse(context.arguments, &context.printer)?;
+    let text = to_prometheus(&METRICS.lock().unwrap());
+    context.output.send(Value::String(text))
+}
+
+pub fn declare(root: &Scope) -> CrushResult<()> {
+    root.create_lazy_namespace(
+        "metric",
+        Box::new(move |env| {
+            Incr::declare(env)?;
+            Set::declare(env)?;
+            Timer::declare(env)?;
+            List::declare(env)?;
+            Export::declare(env)?;
+            Ok(())
+        }))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format() {
+        let mut metrics = OrderedMap::new();
+        let metric = Metric {
+            kind: Kind::Counter,
+            value: Number::Integer(3),
+            total: Duration::zero(),
+            count: 3,
+            updated: Local::now(),
+        };
+        metrics.insert("files".to_string(), metric.clone());
+        metrics.insert("load".to_string(), Metric { kind: Kind::Gauge, value: Number::Float(0.5), ..metric.clone() });
+        metrics.insert("backup".to_string(), Metric { kind: Kind::Timer, total: Duration::milliseconds(1500), count: 2, ..metric });
+        assert_eq!(
+            to_prometheus(&metrics),
+            "# TYPE files counter\nfiles 3\n# TYPE load gauge\nload 0.5\n# TYPE backup summary\nbackup_sum 1.5\nbackup_count 2\n");
+    }
+
+    #[test]
+    fn overflow() {
+        match Number::Integer(i128::MAX).add(&Number::Integer(1)) {
+            Number::Integer(i) => assert_eq!(i, i128::MAX),
+            Number::Float(_) => panic!("Expected an integer"),
+        }
+        match Number::Integer(i128::MIN).add(&Number::Integer(-1)) {
+            Number::Integer(i) => assert_eq!(i, i128::MIN),
+            Number::Float(_) => panic!("Expected an integer"),
+        }
+    }
+}
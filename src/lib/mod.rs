@@ -7,27 +7,38 @@ pub mod io;
 pub mod binary_op;
 
 
+mod auth;
 mod comp;
 mod cond;
 mod stream;
 pub mod types;
 mod control;
 mod constants;
+mod env;
 mod math;
+mod text;
+mod broadcast;
+mod geo;
 mod user;
 mod remote;
+mod docker;
 mod random;
+mod metric;
 mod host;
+mod record;
+mod hash;
+pub mod args;
+mod render;
+mod sys;
+mod ext;
 
 use crate::{lang::scope::Scope, lang::errors::CrushResult};
-use crate::lang::execute;
-use crate::lang::stream::ValueSender;
+use crate::lang::module;
 use crate::lang::printer::Printer;
-use std::path::Path;
 use std::fs::read_dir;
 use crate::lang::errors::to_crush_error;
 
-fn declare_external(root: &Scope, printer: &Printer, output: &ValueSender) -> CrushResult<()> {
+fn declare_external(root: &Scope, printer: &Printer) -> CrushResult<()> {
     match read_dir("src/crushlib/") {
         Err(_) => Ok(()),
         Ok(dirs) => {
@@ -40,7 +51,7 @@ fn declare_external(root: &Scope, printer: &Printer, output: &ValueSender) -> Cr
                             }
                             Some(name_with_extension) => {
                                 let name = name_with_extension.trim_end_matches(".crush");
-                                let s = load_external_namespace(name, &entry.path(), root, printer, output)?;
+                                let s = module::declare(root, name, &entry.path(), printer)?;
                                 if name == "lls" {
                                     root.r#use(&s);
                                 }
@@ -55,22 +66,8 @@ fn declare_external(root: &Scope, printer: &Printer, output: &ValueSender) -> Cr
     }
 }
 
-fn load_external_namespace(name: &str, file: &Path, root: &Scope, printer: &Printer, output: &ValueSender) -> CrushResult<Scope> {
-    let local_printer = printer.clone();
-    let local_output = output.clone();
-    let local_file = file.to_path_buf();
-    root.create_lazy_namespace(name, Box::new(move |env| {
-        let tmp_env: Scope = env.create_temporary_namespace();
-        execute::file(tmp_env.clone(), &local_file, &local_printer, &local_output)?;
-        let data = tmp_env.export()?;
-        for (k, v) in data.mapping {
-            env.declare(&k, v)?;
-        }
-        Ok(())
-    }))
-}
-
-pub fn declare(root: &Scope, printer: &Printer, output: &ValueSender) -> CrushResult<()> {
+pub fn declare(root: &Scope, printer: &Printer) -> CrushResult<()> {
+    auth::declare(root)?;
     comp::declare(root)?;
     cond::declare(root)?;
     traversal::declare(root)?;
@@ -81,12 +78,23 @@ pub fn declare(root: &Scope, printer: &Printer, output: &ValueSender) -> CrushRe
     io::declare(root)?;
     control::declare(root)?;
     constants::declare(root)?;
+    env::declare(root)?;
     math::declare(root)?;
+    text::declare(root)?;
+    geo::declare(root)?;
     user::declare(root)?;
     remote::declare(root)?;
+    docker::declare(root)?;
     random::declare(root)?;
+    metric::declare(root)?;
     host::declare(root)?;
-    declare_external(root, printer, output)?;
+    record::declare(root)?;
+    hash::declare(root)?;
+    args::declare(root)?;
+    render::declare(root)?;
+    sys::declare(root)?;
+    ext::declare(root)?;
+    declare_external(root, printer)?;
     root.readonly();
     Ok(())
 }
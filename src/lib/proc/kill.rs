@@ -0,0 +1,136 @@
+use std::convert::TryFrom;
+use std::str::FromStr;
+
+use lazy_static::lazy_static;
+use nix::sys::signal::{self, Signal};
+use nix::unistd::Pid;
+
+use super::ps::Process;
+use crate::lang::argument::ArgumentHandler;
+use crate::lang::command::OutputType::Known;
+use crate::lang::errors::{CrushResult, argument_error};
+use crate::lang::execution_context::ExecutionContext;
+use crate::lang::stream::ValueReceiver;
+use crate::lang::{table::ColumnType, table::Row, value::Value, value::ValueType};
+use signature::signature;
+
+lazy_static! {
+    static ref OUTPUT_TYPE: Vec<ColumnType> = vec![
+        ColumnType::new("pid", ValueType::Integer),
+        ColumnType::new("signal", ValueType::String),
+        ColumnType::new("success", ValueType::Bool),
+        ColumnType::new("error", ValueType::String),
+    ];
+}
+
+/** Signals can be given by name, with or without the SIG prefix, or by number. */
+fn parse_signal(name: &str) -> CrushResult<Signal> {
+    let name = name.trim().to_uppercase();
+    let res = match name.parse::<i32>() {
+        Ok(number) => Signal::try_from(number).ok(),
+        Err(_) if name.starts_with("SIG") => Signal::from_str(&name).ok(),
+        Err(_) => Signal::from_str(&format!("SIG{}", name)).ok(),
+    };
+    match res {
+        Some(signal) => Ok(signal),
+        None => argument_error(format!("Unknown signal {}", name).as_str()),
+    }
+}
+
+/** The pids in the pid column of the input. */
+fn input_pids(input: ValueReceiver) -> CrushResult<Vec<i128>> {
+    let mut stream = match input.recv()?.stream() {
+        Some(stream) => stream,
+        None => return argument_error("Expected either process ids or a table stream with a pid column"),
+    };
+    let idx = match stream.types().iter().position(|c| c.name == "pid") {
+        Some(idx) => idx,
+        None => return argument_error("Expected the input to have a pid column"),
+    };
+    let mut res = Vec::new();
+    while let Ok(row) = stream.read() {
+        match &row.cells()[idx] {
+            Value::Integer(pid) => res.push(*pid),
+            v => return argument_error(format!("Expected pids to be integers, got a {}", v.value_type().to_string()).as_str()),
+        }
+    }
+    Ok(res)
+}
+
+/** The pids of all processes with a matching name, except for this shell. */
+fn matching_pids(patterns: &[Value]) -> CrushResult<Vec<i128>> {
+    let own = i128::from(std::process::id());
+    Ok(Process::all()?.into_iter()
+        .filter(|p| p.pid != own)
+        .filter(|p| patterns.iter().any(|pattern| match pattern {
+            Value::Glob(g) => g.matches(&p.name),
+            Value::Regex(_, re) => re.is_match(&p.name),
+            _ => false,
+        }))
+        .map(|p| p.pid)
+        .collect())
+}
+
+#[signature(
+kill,
+can_block = true,
+short = "Send a signal to a set of processes",
+long = "Processes are specified using their pid, or using a glob or regular expression that is matched",
+long = "against the names of all running processes. If no processes are specified, the pid column of",
+long = "the input is used.",
+long = "",
+long = "The signal is given by name, with or without the SIG prefix, or by number. The set of existing",
+long = "signals is platform dependent, but common signals include SIGHUP, SIGINT, SIGQUIT, SIGILL,",
+long = "SIGTRAP, SIGABRT, SIGBUS, SIGFPE, SIGKILL, SIGUSR1, SIGSEGV, SIGUSR2, SIGPIPE, SIGALRM,",
+long = "SIGTERM, SIGCHLD, SIGCONT and SIGWINCH.",
+long = "",
+long = "Every signalled process is output as a row with its pid, the signal, whether it was sent and",
+long = "if not, why.",
+example = "ps | where {user == \"nobody\" and cpu > 90.0} | kill signal=\"KILL\"",
+output = Known(ValueType::TableStream(OUTPUT_TYPE.clone())))]
+pub struct Kill {
+    #[unnamed()]
+    #[description("pids, or globs or regular expressions matching the names of processes to signal.")]
+    processes: Vec<Value>,
+    #[default("SIGTERM")]
+    #[description("the name or number of the signal to send.")]
+    signal: String,
+}
+
+fn kill(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Kill = Kill::parse(context.arguments, &context.printer)?;
+    let signal = parse_signal(&cfg.signal)?;
+
+    let pids = if cfg.processes.is_empty() {
+        input_pids(context.input)?
+    } else {
+        let mut pids = Vec::new();
+        let mut patterns = Vec::new();
+        for process in cfg.processes {
+            match process {
+                Value::Integer(pid) => pids.push(pid),
+                Value::Glob(_) | Value::Regex(_, _) => patterns.push(process),
+                v => return argument_error(format!("Expected a pid, glob or regular expression, got a {}", v.value_type().to_string()).as_str()),
+            }
+        }
+        if !patterns.is_empty() {
+            pids.append(&mut matching_pids(&patterns)?);
+        }
+        pids
+    };
+
+    let output = context.output.initialize(OUTPUT_TYPE.clone())?;
+    for pid in pids {
+        let res = match i32::try_from(pid) {
+            Ok(raw) if raw > 0 => signal::kill(Pid::from_raw(raw), signal).map_err(|e| e.to_string()),
+            _ => Err("Invalid pid".to_string()),
+        };
+        output.send(Row::new(vec![
+            Value::Integer(pid),
+            Value::string(signal.as_str()),
+            Value::Bool(res.is_ok()),
+            Value::String(res.err().unwrap_or_default()),
+        ]))?;
+    }
+    Ok(())
+}
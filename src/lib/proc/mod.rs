@@ -0,0 +1,21 @@
+use crate::lang::errors::CrushResult;
+use crate::lang::argument::ArgumentHandler;
+use crate::lang::scope::Scope;
+
+mod kill;
+mod ps;
+
+pub fn declare(root: &Scope) -> CrushResult<()> {
+    let e = root.create_lazy_namespace(
+        "proc",
+        Box::new(move |env| {
+            ps::Ps::declare(env)?;
+            kill::Kill::declare(env)?;
+            crate::lib::user::Users::declare(env)?;
+            crate::lib::user::Groups::declare(env)?;
+            crate::lib::user::Id::declare(env)?;
+            Ok(())
+        }))?;
+    root.r#use(&e);
+    Ok(())
+}
@@ -0,0 +1,228 @@
+use std::collections::HashMap;
+use std::fs;
+use std::os::unix::fs::MetadataExt;
+use std::time::Instant;
+
+use chrono::Duration;
+use lazy_static::lazy_static;
+use nix::unistd::{sysconf, SysconfVar};
+
+use crate::lang::argument::ArgumentHandler;
+use crate::lang::command::OutputType::Known;
+use crate::lang::errors::{CrushResult, argument_error, to_crush_error};
+use crate::lang::execution_context::ExecutionContext;
+use crate::lang::stream::OutputStream;
+use crate::lang::{table::ColumnType, table::Row, value::Value, value::ValueType};
+use crate::util::user_map::{create_user_map, UserMap};
+use signature::signature;
+use users::{uid_t, User};
+
+lazy_static! {
+    static ref OUTPUT_TYPE: Vec<ColumnType> = vec![
+        ColumnType::new("pid", ValueType::Integer),
+        ColumnType::new("ppid", ValueType::Integer),
+        ColumnType::new("user", ValueType::String),
+        ColumnType::new("cpu", ValueType::Float),
+        ColumnType::new("time", ValueType::Duration),
+        ColumnType::new("rss", ValueType::Integer),
+        ColumnType::new("vsize", ValueType::Integer),
+        ColumnType::new("state", ValueType::String),
+        ColumnType::new("command", ValueType::String),
+    ];
+}
+
+fn state_name(state: char) -> &'static str {
+    match state {
+        'R' => "Running",
+        'S' => "Sleeping",
+        'D' => "Waiting",
+        'T' => "Stopped",
+        't' => "Traced",
+        'W' => "Paging",
+        'X' | 'x' => "Dead",
+        'Z' => "Zombie",
+        'I' => "Idle",
+        'P' => "Parked",
+        _ => "Unknown",
+    }
+}
+
+/** The information about a single process found in /proc. */
+pub(super) struct Process {
+    pub(super) pid: i128,
+    ppid: i128,
+    uid: uid_t,
+    state: char,
+    /** Time spent running in user and kernel mode, in clock ticks. */
+    ticks: u64,
+    /** The time the process started after boot, in clock ticks. */
+    start: u64,
+    vsize: u64,
+    /** Resident memory, in pages. */
+    rss: u64,
+    /** The name of the executable, which unlike the command line can't be changed by the process. */
+    pub(super) name: String,
+    command: String,
+}
+
+impl Process {
+    fn read(pid: i128) -> Option<Process> {
+        let dir = format!("/proc/{}", pid);
+        let stat = fs::read_to_string(format!("{}/stat", dir)).ok()?;
+        // The name of the command is in parentheses and may itself contain spaces and parentheses
+        let name_start = stat.find('(')?;
+        let name_end = stat.rfind(')')?;
+        let name = &stat[name_start + 1..name_end];
+        let fields = stat[name_end + 1..].split_whitespace().collect::<Vec<_>>();
+        let field = |idx: usize| fields.get(idx).and_then(|f| f.parse::<u64>().ok());
+
+        let command = match fs::read(format!("{}/cmdline", dir)) {
+            Ok(cmdline) if !cmdline.is_empty() => cmdline
+                .split(|b| *b == 0)
+                .filter(|arg| !arg.is_empty())
+                .map(|arg| String::from_utf8_lossy(arg).to_string())
+                .collect::<Vec<_>>()
+                .join(" "),
+            // Kernel threads have no command line
+            _ => format!("[{}]", name),
+        };
+
+        Some(Process {
+            pid,
+            ppid: i128::from(field(1)?),
+            uid: fs::metadata(&dir).ok()?.uid(),
+            state: fields.first()?.chars().next()?,
+            ticks: field(11)? + field(12)?,
+            start: field(19)?,
+            vsize: field(20)?,
+            rss: field(21)?,
+            name: name.to_string(),
+            command,
+        })
+    }
+
+    pub(super) fn all() -> CrushResult<Vec<Process>> {
+        let mut res = Vec::new();
+        for entry in to_crush_error(fs::read_dir("/proc"))?.flatten() {
+            if let Some(pid) = entry.file_name().to_str().and_then(|name| name.parse::<i128>().ok()) {
+                // Processes that exit while being read are skipped
+                if let Some(process) = Process::read(pid) {
+                    res.push(process);
+                }
+            }
+        }
+        res.sort_by_key(|p| p.pid);
+        Ok(res)
+    }
+}
+
+struct System {
+    ticks_per_second: f64,
+    page_size: u64,
+    users: HashMap<uid_t, User>,
+}
+
+impl System {
+    fn new() -> CrushResult<System> {
+        let conf = |var: SysconfVar, default: i64| sysconf(var).ok().flatten().unwrap_or(default);
+        Ok(System {
+            ticks_per_second: conf(SysconfVar::CLK_TCK, 100) as f64,
+            page_size: conf(SysconfVar::PAGE_SIZE, 4096) as u64,
+            users: create_user_map(),
+        })
+    }
+
+    /** The number of seconds since the system was booted. */
+    fn uptime(&self) -> CrushResult<f64> {
+        let uptime = to_crush_error(fs::read_to_string("/proc/uptime"))?;
+        match uptime.split_whitespace().next().and_then(|s| s.parse::<f64>().ok()) {
+            Some(seconds) => Ok(seconds),
+            None => argument_error("Could not read the system uptime"),
+        }
+    }
+
+    fn send(&self, output: &OutputStream, process: Process, cpu: f64) -> CrushResult<()> {
+        output.send(Row::new(vec![
+            Value::Integer(process.pid),
+            Value::Integer(process.ppid),
+            self.users.get_name(process.uid),
+            Value::Float((cpu * 10.0).round() / 10.0),
+            Value::Duration(Duration::milliseconds((process.ticks as f64 * 1000.0 / self.ticks_per_second) as i64)),
+            Value::Integer(i128::from(process.rss * self.page_size)),
+            Value::Integer(i128::from(process.vsize)),
+            Value::string(state_name(process.state)),
+            Value::String(process.command),
+        ]))
+    }
+}
+
+#[signature(
+ps,
+can_block = true,
+short = "Return a table stream containing information on all running processes on the system",
+long = "Each row contains the process id, the id of the parent process, the owner, the cpu usage in",
+long = "percent, the total cpu time used, the resident and virtual memory size in bytes, the state and",
+long = "the command line of a process.",
+long = "",
+long = "Without an interval, the cpu usage is the average over the lifetime of the process, like the",
+long = "ps command of most systems. With an interval, ps keeps running and outputs all processes",
+long = "again every interval, with the cpu usage measured over the last interval.",
+long = "",
+long = "The state is one of Running, Sleeping, Waiting, Stopped, Traced, Paging, Dead, Zombie, Idle",
+long = "and Parked.",
+example = "ps interval=(duration:new seconds=2) | where {cpu > 50.0}",
+output = Known(ValueType::TableStream(OUTPUT_TYPE.clone())))]
+pub struct Ps {
+    #[description("keep running, and output the processes every interval.")]
+    interval: Option<Duration>,
+    #[description("stop after outputting the processes this many times.")]
+    rounds: Option<i128>,
+}
+
+fn ps(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Ps = Ps::parse(context.arguments, &context.printer)?;
+    let output = context.output.initialize(OUTPUT_TYPE.clone())?;
+    let system = System::new()?;
+
+    let interval = match cfg.interval {
+        None => {
+            let uptime = system.uptime()?;
+            for process in Process::all()? {
+                let elapsed = uptime - process.start as f64 / system.ticks_per_second;
+                let cpu = if elapsed > 0.0 {
+                    process.ticks as f64 / system.ticks_per_second / elapsed * 100.0
+                } else {
+                    0.0
+                };
+                system.send(&output, process, cpu)?;
+            }
+            return Ok(());
+        }
+        Some(interval) if interval <= Duration::zero() => return argument_error("Expected the interval to be positive"),
+        Some(interval) => to_crush_error(interval.to_std())?,
+    };
+
+    let mut previous: HashMap<(i128, u64), u64> = Process::all()?.iter()
+        .map(|p| ((p.pid, p.start), p.ticks))
+        .collect();
+    let mut last = Instant::now();
+    let mut round = 0;
+    while cfg.rounds.map(|rounds| round < rounds).unwrap_or(true) {
+        std::thread::sleep(interval);
+        let processes = Process::all()?;
+        let now = Instant::now();
+        let elapsed = now.duration_since(last).as_secs_f64();
+        let mut current = HashMap::new();
+        for process in processes {
+            // Pids can be reused, so processes are told apart by their start time as well
+            let key = (process.pid, process.start);
+            let used = process.ticks - previous.get(&key).copied().unwrap_or(0).min(process.ticks);
+            current.insert(key, process.ticks);
+            system.send(&output, process, used as f64 / system.ticks_per_second / elapsed * 100.0)?;
+        }
+        previous = current;
+        last = now;
+        round += 1;
+    }
+    Ok(())
+}
@@ -1,10 +1,30 @@
+use std::sync::Mutex;
+
+use lazy_static::lazy_static;
+use rand::{Rng, RngCore, SeedableRng};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+
 use crate::lang::execution_context::ExecutionContext;
-use crate::lang::errors::CrushResult;
+use crate::lang::errors::{CrushResult, argument_error, mandate};
 use crate::lang::{value::Value};
 use crate::lang::scope::Scope;
 use signature::signature;
 use crate::lang::argument::ArgumentHandler;
 
+lazy_static! {
+    /**
+    All random values come from this generator, so that seeding it with random:seed makes
+    scripts produce the same values every time they run.
+    */
+    static ref RNG: Mutex<StdRng> = Mutex::new(StdRng::from_entropy());
+}
+
+fn with_rng<T>(f: impl FnOnce(&mut StdRng) -> T) -> T {
+    let mut rng = RNG.lock().unwrap();
+    f(&mut rng)
+}
+
 #[signature(
     float,
     can_block = false,
@@ -17,25 +37,114 @@ struct Float {
 
 fn float(context: ExecutionContext) -> CrushResult<()> {
     let cfg: Float = Float::parse(context.arguments, &context.printer)?;
-    context.output.send(Value::Float(rand::random::<f64>()*cfg.to))?;
+    context.output.send(Value::Float(with_rng(|rng| rng.gen::<f64>()) * cfg.to))?;
     Ok(())
 }
 
 #[signature(
 integer,
 can_block = false,
-short = "generate a random integer between 0 and 1 (or some other specified number)")]
+short = "generate a random integer between 0 and 1 (or some other specified range)",
+long = "With one bound, the integer is between 0 and that bound. With two bounds, the integer is",
+long = "between the first one (inclusive) and the second one (exclusive).",
+example = "random:integer 1 7")]
 struct Integer {
-    #[default(2)]
+    #[description("lower bound (inclusive), or the upper bound if it is the only bound.")]
+    from: Option<i128>,
     #[description("upper bound (exclusive).")]
-    to: i128,
+    to: Option<i128>,
 }
 
 fn integer(context: ExecutionContext) -> CrushResult<()> {
     let cfg: Integer = Integer::parse(context.arguments, &context.printer)?;
-    let n = rand::random::<f64>()*(cfg.to as f64);
-    context.output.send(Value::Integer(n as i128))?;
-    Ok(())
+    let (from, to) = match (cfg.from, cfg.to) {
+        (None, None) => (0, 2),
+        (Some(to), None) | (None, Some(to)) => (0, to),
+        (Some(from), Some(to)) => (from, to),
+    };
+    if from >= to {
+        return argument_error(format!("Empty range {} to {}", from, to).as_str());
+    }
+    context.output.send(Value::Integer(with_rng(|rng| rng.gen_range(from, to))))
+}
+
+/** Formats 16 random bytes as a version 4 UUID, as described in RFC 4122. */
+fn uuid_v4(mut bytes: [u8; 16]) -> String {
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    format!("{}-{}-{}-{}-{}", &hex[0..8], &hex[8..12], &hex[12..16], &hex[16..20], &hex[20..32])
+}
+
+#[signature(
+uuid,
+can_block = false,
+short = "generate a random (version 4) UUID",
+example = "random:uuid")]
+struct Uuid {}
+
+fn uuid(context: ExecutionContext) -> CrushResult<()> {
+    Uuid::parse(context.arguments, &context.printer)?;
+    context.output.send(Value::String(uuid_v4(with_rng(|rng| rng.gen()))))
+}
+
+#[signature(
+bytes,
+can_block = false,
+short = "generate random binary data",
+example = "random:bytes 32 | bin:to ./key.bin")]
+struct Bytes {
+    #[description("the number of bytes to generate.")]
+    count: i128,
+}
+
+fn bytes(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Bytes = Bytes::parse(context.arguments, &context.printer)?;
+    if cfg.count < 0 || cfg.count > 1 << 30 {
+        return argument_error("The number of bytes must be between 0 and 2^30");
+    }
+    let mut data = vec![0u8; cfg.count as usize];
+    with_rng(|rng| rng.fill_bytes(&mut data));
+    context.output.send(Value::Binary(data))
+}
+
+#[signature(
+choice,
+can_block = false,
+short = "pick a random element of a list",
+example = "random:choice (list:of \"heads\" \"tails\")")]
+struct Choice {
+    #[description("the list to pick an element of.")]
+    list: Option<Value>,
+}
+
+fn choice(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Choice = Choice::parse(context.arguments, &context.printer)?;
+    match cfg.list {
+        Some(Value::List(l)) => {
+            let elements = l.dump();
+            context.output.send(mandate(
+                with_rng(|rng| elements.choose(rng).cloned()),
+                "Can't pick an element of an empty list")?)
+        }
+        _ => argument_error("Expected a list"),
+    }
+}
+
+#[signature(
+seed,
+can_block = false,
+short = "seed the random number generator, so that the random values that follow are reproducible",
+example = "random:seed 42")]
+struct Seed {
+    #[description("the seed.")]
+    seed: i128,
+}
+
+fn seed(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Seed = Seed::parse(context.arguments, &context.printer)?;
+    with_rng(|rng| *rng = StdRng::seed_from_u64(cfg.seed as u64));
+    context.output.send(Value::Empty())
 }
 
 pub fn declare(root: &Scope) -> CrushResult<()> {
@@ -44,7 +153,22 @@ pub fn declare(root: &Scope) -> CrushResult<()> {
         Box::new(move |env| {
             Float::declare(env)?;
             Integer::declare(env)?;
+            Uuid::declare(env)?;
+            Bytes::declare(env)?;
+            Choice::declare(env)?;
+            Seed::declare(env)?;
             Ok(())
         }))?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn uuids() {
+        assert_eq!(uuid_v4([0xff; 16]), "ffffffff-ffff-4fff-bfff-ffffffffffff");
+        assert_eq!(uuid_v4([0; 16]), "00000000-0000-4000-8000-000000000000");
+    }
+}
@@ -0,0 +1,125 @@
+use crate::lang::execution_context::ExecutionContext;
+use crate::lang::errors::{CrushResult, to_crush_error, argument_error};
+use crate::lang::scope::Scope;
+use crate::lang::recording::{Recorder, Recording, EventKind};
+use signature::signature;
+use crate::lang::argument::ArgumentHandler;
+use serde_json::json;
+use std::fs::File;
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::Duration;
+
+#[signature(
+start,
+can_block = false,
+short = "Start recording the commands and output of the interactive session into a file",
+long = "Every command entered and every line of output and error is recorded together with the",
+long = "time it was printed. Use record:replay to play the recording back, or record:asciinema to",
+long = "convert it for use with asciinema.",
+example = "record:start session.jsonl")]
+struct Start {
+    #[description("the file to record to.")]
+    file: PathBuf,
+}
+
+fn start(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Start = Start::parse(context.arguments, &context.printer)?;
+    let recorder = Recorder::create(&cfg.file, context.printer.width(), context.printer.height())?;
+    context.printer.start_recording(recorder)?;
+    context.output.empty()
+}
+
+#[signature(
+stop,
+can_block = false,
+short = "Stop recording the session")]
+struct Stop {}
+
+fn stop(context: ExecutionContext) -> CrushResult<()> {
+    Stop::parse(context.arguments, &context.printer)?;
+    context.printer.stop_recording()?;
+    context.output.empty()
+}
+
+#[signature(
+replay,
+can_block = true,
+short = "Play back a recorded session with its original timing",
+example = "record:replay session.jsonl speed=2.0")]
+struct Replay {
+    #[description("the recording to play back.")]
+    file: PathBuf,
+    #[default(1.0)]
+    #[description("how many times faster than the original session to play back.")]
+    speed: f64,
+}
+
+fn replay(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Replay = Replay::parse(context.arguments, &context.printer)?;
+    if cfg.speed <= 0.0 {
+        return argument_error("The speed must be positive");
+    }
+    let recording = Recording::load(&cfg.file)?;
+    let mut time = 0.0;
+    for event in recording.events {
+        if event.time > time {
+            std::thread::sleep(Duration::from_secs_f64((event.time - time) / cfg.speed));
+            time = event.time;
+        }
+        match event.kind {
+            EventKind::Command => context.printer.line(&format!("crush> {}", event.text)),
+            EventKind::Output => context.printer.line(&event.text),
+            EventKind::Error => context.printer.error(&event.text),
+        }
+    }
+    context.output.empty()
+}
+
+#[signature(
+asciinema,
+can_block = true,
+short = "Convert a recorded session to the asciicast v2 format used by asciinema",
+example = "record:asciinema session.jsonl session.cast")]
+struct Asciinema {
+    #[description("the recording to convert.")]
+    file: PathBuf,
+    #[description("the file to write the asciicast to.")]
+    destination: PathBuf,
+}
+
+fn asciinema(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Asciinema = Asciinema::parse(context.arguments, &context.printer)?;
+    let recording = Recording::load(&cfg.file)?;
+    let mut out = to_crush_error(File::create(&cfg.destination))?;
+    let header = json!({
+        "version": 2,
+        "width": if recording.width == 0 { 80 } else { recording.width },
+        "height": if recording.height == 0 { 24 } else { recording.height },
+        "timestamp": recording.timestamp,
+    });
+    to_crush_error(writeln!(out, "{}", header))?;
+    for event in recording.events {
+        let text = match event.kind {
+            EventKind::Command => format!("crush> {}", event.text),
+            EventKind::Output => event.text,
+            EventKind::Error => format!("Error: {}", event.text),
+        };
+        let line = json!([event.time, "o", format!("{}\r\n", text.replace('\n', "\r\n"))]);
+        to_crush_error(writeln!(out, "{}", line))?;
+    }
+    context.output.empty()
+}
+
+pub fn declare(root: &Scope) -> CrushResult<()> {
+    root.create_lazy_namespace(
+        "record",
+        Box::new(move |env| {
+            Start::declare(env)?;
+            Stop::declare(env)?;
+            Replay::declare(env)?;
+            Asciinema::declare(env)?;
+            Ok(())
+        }))?;
+    Ok(())
+}
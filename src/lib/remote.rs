@@ -1,11 +1,11 @@
-use crate::lang::errors::{CrushResult, to_crush_error, CrushError, mandate, error};
+use crate::lang::errors::{CrushResult, to_crush_error, CrushError, mandate, error, argument_error};
 use crate::lang::value::Value;
 use crate::lang::scope::Scope;
 use crate::lang::execution_context::{ExecutionContext};
 use signature::signature;
 use crate::lang::command::Command;
 use crate::lang::argument::ArgumentHandler;
-use std::net::TcpStream;
+use std::net::{TcpListener, TcpStream};
 use std::io::{Read, Write};
 use std::cmp::min;
 use ssh2::Session;
@@ -14,8 +14,13 @@ use std::thread;
 use crossbeam::unbounded;
 use crate::lang::table::{ColumnType, Row};
 use crate::lang::value::ValueType;
+use crate::lang::command::OutputType::{Known, Unknown};
+use crate::lang::r#struct::Struct;
+use crate::lang::wire;
 use users::get_current_username;
 use std::thread::JoinHandle;
+use std::sync::Mutex;
+use lazy_static::lazy_static;
 
 fn parse(mut host: String, default_username: &Option<String>) -> CrushResult<(String, String)> {
     let username;
@@ -39,19 +44,23 @@ fn parse(mut host: String, default_username: &Option<String>) -> CrushResult<(St
     Ok((host, username))
 }
 
-fn run_remote(cmd: &Vec<u8>, env: &Scope, host: String, default_username: &Option<String>, password: &Option<String>) -> CrushResult<Value> {
-    let (host, username) = parse(host, &default_username)?;
-
-    let tcp = to_crush_error(TcpStream::connect(&host))?;
+fn session(host: &str, username: &str, password: &Option<String>) -> CrushResult<Session> {
+    let tcp = to_crush_error(TcpStream::connect(host))?;
     let mut sess = to_crush_error(Session::new())?;
 
     sess.set_tcp_stream(tcp);
     to_crush_error(sess.handshake())?;
     if let Some(pass) = password {
-        to_crush_error(sess.userauth_password(&username, pass))?
+        to_crush_error(sess.userauth_password(username, pass))?
     } else {
-        to_crush_error(sess.userauth_agent(&username))?;
+        to_crush_error(sess.userauth_agent(username))?;
     }
+    Ok(sess)
+}
+
+fn run_remote(cmd: &Vec<u8>, env: &Scope, host: String, default_username: &Option<String>, password: &Option<String>) -> CrushResult<Value> {
+    let (host, username) = parse(host, &default_username)?;
+    let sess = session(&host, &username, password)?;
 
     let mut channel = to_crush_error(sess.channel_session())?;
     to_crush_error(channel.exec("crush --pup"))?;
@@ -86,7 +95,7 @@ fn exec(context: ExecutionContext) -> CrushResult<()> {
     let mut in_buf = Vec::new();
     serialize(&Value::Command(cfg.command), &mut in_buf)?;
     context.output.send(
-        run_remote(&in_buf, &context.env, cfg.host, &cfg.username, &cfg.username)?)
+        run_remote(&in_buf, &context.env, cfg.host, &cfg.username, &cfg.password)?)
 }
 
 #[signature(
@@ -167,12 +176,187 @@ fn pexec(context: ExecutionContext) -> CrushResult<()> {
     Ok(())
 }
 
+lazy_static! {
+    /**
+    The password or token of every connection. Connections only hold an index into this, so
+    that printing one does not show them.
+    */
+    static ref SECRETS: Mutex<Vec<Option<String>>> = Mutex::new(Vec::new());
+}
+
+fn store_secret(secret: Option<String>) -> CrushResult<Value> {
+    let mut secrets = SECRETS.lock().unwrap();
+    secrets.push(secret);
+    Ok(Value::Integer(secrets.len() as i128 - 1))
+}
+
+fn secret(connection: &Struct) -> CrushResult<Option<String>> {
+    match connection.get("secret") {
+        Some(Value::Integer(idx)) => match SECRETS.lock().unwrap().get(idx as usize) {
+            Some(secret) => Ok(secret.clone()),
+            None => argument_error("Expected a connection created by remote:connect"),
+        },
+        _ => argument_error("Expected a connection created by remote:connect"),
+    }
+}
+
+fn member(connection: &Struct, name: &str) -> Option<String> {
+    match connection.get(name) {
+        Some(Value::String(s)) => Some(s),
+        _ => None,
+    }
+}
+
+#[signature(
+connect,
+can_block = true,
+output = Known(ValueType::Struct),
+short = "Connect to a remote crush instance",
+long = "The target is either ssh://[user@]host[:port], where crush is started on the host over ssh,",
+long = "or tcp://host:port, where an instance is running remote:serve. A target without a scheme uses",
+long = "ssh. The connection is checked, and a struct describing it is returned, to be passed to",
+long = "remote:run. The password or token is not part of the struct, so it can be printed safely.",
+example = "server := (remote:connect \"tcp://build.example.com:7001\" token=$token)")]
+struct Connect {
+    #[description("where to connect to.")]
+    target: String,
+    #[description("username on the remote machine, for ssh.")]
+    username: Option<String>,
+    #[description("password on the remote machine, for ssh. If no password is provided, agent authentication will be used.")]
+    password: Option<String>,
+    #[description("the token the remote instance was started with, for tcp.")]
+    token: Option<String>,
+}
+
+fn connect(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Connect = Connect::parse(context.arguments, &context.printer)?;
+    let (transport, host, username, secret) = match cfg.target.split_once("://") {
+        Some(("tcp", host)) => {
+            if cfg.token.is_none() {
+                return argument_error("A token is needed for tcp connections");
+            }
+            wire::ping(&mut to_crush_error(TcpStream::connect(host))?, cfg.token.as_deref())?;
+            ("tcp", host.to_string(), Value::Empty(), cfg.token)
+        }
+        Some(("ssh", host)) | Some(("", host)) => {
+            let (host, username) = parse(host.to_string(), &cfg.username)?;
+            session(&host, &username, &cfg.password)?;
+            ("ssh", host, Value::String(username), cfg.password)
+        }
+        None => {
+            let (host, username) = parse(cfg.target.clone(), &cfg.username)?;
+            session(&host, &username, &cfg.password)?;
+            ("ssh", host, Value::String(username), cfg.password)
+        }
+        Some((scheme, _)) => return argument_error(format!("Unknown transport {}", scheme).as_str()),
+    };
+    context.output.send(Value::Struct(Struct::new(
+        vec![
+            ("transport".to_string(), Value::string(transport)),
+            ("host".to_string(), Value::String(host)),
+            ("username".to_string(), username),
+            ("secret".to_string(), store_secret(secret)?),
+        ],
+        None)))
+}
+
+#[signature(
+run,
+can_block = true,
+output = Unknown,
+short = "Run a command in a remote crush instance and stream back its output",
+long = "The command is sent to the instance described by the connection, and its output is returned",
+long = "as it is produced. Table streams keep their column types and the types of their cells, so the",
+long = "output can be processed locally as if the command had run locally.",
+example = "remote:run server {ps | where {cpu > 10.0}} | sort ^cpu")]
+struct Run {
+    #[description("a connection created by remote:connect.")]
+    connection: Value,
+    #[description("the command to run.")]
+    command: Command,
+}
+
+fn run(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Run = Run::parse(context.arguments, &context.printer)?;
+    let connection = match cfg.connection {
+        Value::Struct(s) => s,
+        _ => return argument_error("Expected a connection created by remote:connect"),
+    };
+    let host = mandate(member(&connection, "host"), "Expected a connection created by remote:connect")?;
+    match member(&connection, "transport").as_deref() {
+        Some("tcp") => {
+            let mut stream = to_crush_error(TcpStream::connect(&host))?;
+            wire::send_command(&mut stream, cfg.command, secret(&connection)?.as_deref())?;
+            wire::receive_output(&mut stream, &context.env, context.output)
+        }
+        Some("ssh") => {
+            let username = mandate(member(&connection, "username"), "Missing username")?;
+            let sess = session(&host, &username, &secret(&connection)?)?;
+            let mut channel = to_crush_error(sess.channel_session())?;
+            to_crush_error(channel.exec("crush --remote"))?;
+            wire::send_command(&mut channel, cfg.command, None)?;
+            wire::receive_output(&mut channel, &context.env, context.output)?;
+            to_crush_error(channel.wait_close())
+        }
+        _ => argument_error("Expected a connection created by remote:connect"),
+    }
+}
+
+#[signature(
+serve,
+can_block = true,
+output = Known(ValueType::Empty),
+short = "Let other crush instances run commands in this one over tcp",
+long = "Listens on the address, and runs the commands that clients connecting with remote:connect",
+long = "and remote:run send, as long as they give the same token. This command does not return.",
+long = "",
+long = "The connection is not encrypted, so only listen on trusted networks, or on localhost behind",
+long = "a tunnel. Anyone with the token can run any command as the user running this instance.",
+example = "remote:serve \"127.0.0.1:7001\" token=(bin:from ./token | lines:from | head 1):line")]
+struct Serve {
+    #[description("the address to listen on, e.g. 127.0.0.1:7001.")]
+    address: String,
+    #[description("the token clients must give.")]
+    token: String,
+}
+
+fn serve(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Serve = Serve::parse(context.arguments, &context.printer)?;
+    if cfg.token.is_empty() {
+        return argument_error("The token must not be empty");
+    }
+    let listener = to_crush_error(TcpListener::bind(&cfg.address))?;
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                context.printer.handle_error::<()>(to_crush_error(Err(e)));
+                continue;
+            }
+        };
+        let env = context.env.clone();
+        let printer = context.printer.clone();
+        let token = cfg.token.clone();
+        to_crush_error(thread::Builder::new().name("remote:serve".to_string()).spawn(move || {
+            let mut source = match stream.try_clone() {
+                Ok(source) => source,
+                Err(_) => return,
+            };
+            printer.handle_error(wire::serve(&mut source, Box::new(stream), env, &printer, Some(&token)));
+        }))?;
+    }
+    Ok(())
+}
+
 pub fn declare(root: &Scope) -> CrushResult<()> {
     let e = root.create_lazy_namespace(
         "remote",
         Box::new(move |env| {
             Exec::declare(env)?;
             Pexec::declare(env)?;
+            Connect::declare(env)?;
+            Run::declare(env)?;
+            Serve::declare(env)?;
             Ok(())
         }))?;
     root.r#use(&e);
@@ -0,0 +1,213 @@
+use std::io::Write;
+
+use crate::lang::argument::ArgumentHandler;
+use crate::lang::command::OutputType::Unknown;
+use crate::lang::errors::{CrushResult, argument_error, to_crush_error};
+use crate::lang::execution_context::ExecutionContext;
+use crate::lang::files::Files;
+use crate::lang::scope::Scope;
+use crate::lang::stream::{ValueReceiver, ValueSender};
+use crate::lang::style::Style;
+use crate::lang::value::{Alignment, Value};
+use signature::signature;
+
+pub mod plot;
+mod png;
+mod svg;
+
+/** The size of one character cell, in pixels. Both renderers lay text out on this grid. */
+const CHAR_WIDTH: usize = crate::util::font::WIDTH;
+const LINE_HEIGHT: usize = crate::util::font::HEIGHT;
+/** Vertical space between the text of a row and the edges of the row. */
+const ROW_PADDING: usize = 3;
+const ROW_HEIGHT: usize = LINE_HEIGHT + 2 * ROW_PADDING;
+const MARGIN: usize = 10;
+/** The number of blank characters between two columns. */
+const COLUMN_GAP: usize = 2;
+
+type Rgb = (u8, u8, u8);
+
+/**
+The colors a table is drawn with. Cells with styled text use their own colors on top of these.
+*/
+struct Theme {
+    background: Rgb,
+    foreground: Rgb,
+    header_background: Rgb,
+    stripe: Rgb,
+    rule: Rgb,
+}
+
+impl Theme {
+    fn get(name: &str) -> Theme {
+        match name {
+            "dark" => Theme {
+                background: (13, 17, 23),
+                foreground: (201, 209, 217),
+                header_background: (22, 27, 34),
+                stripe: (17, 22, 29),
+                rule: (48, 54, 61),
+            },
+            _ => Theme {
+                background: (255, 255, 255),
+                foreground: (36, 41, 46),
+                header_background: (234, 238, 242),
+                stripe: (246, 248, 250),
+                rule: (208, 215, 222),
+            },
+        }
+    }
+}
+
+struct Cell {
+    text: String,
+    style: Style,
+    alignment: Alignment,
+}
+
+impl Cell {
+    fn new(value: &Value) -> Cell {
+        // Each cell is drawn on a single line
+        let text = value.to_string().replace(['\n', '\r', '\t'], " ");
+        Cell {
+            text,
+            style: match value {
+                Value::Styled(s) => s.style,
+                _ => Style::default(),
+            },
+            alignment: value.alignment(),
+        }
+    }
+
+    fn plain(text: &str) -> Cell {
+        Cell { text: text.to_string(), style: Style::default(), alignment: Alignment::Left }
+    }
+}
+
+/**
+A table with the position of every column worked out, in pixels.
+*/
+struct Layout {
+    header: Vec<String>,
+    rows: Vec<Vec<Cell>>,
+    /** The left edge and the width of each column. */
+    columns: Vec<(usize, usize)>,
+    width: usize,
+    height: usize,
+}
+
+impl Layout {
+    fn new(input: ValueReceiver) -> CrushResult<Layout> {
+        let mut stream = match input.recv()?.stream() {
+            Some(stream) => stream,
+            None => return argument_error("Expected a table to render"),
+        };
+        let header = stream.types().iter().map(|c| c.name.clone()).collect::<Vec<_>>();
+        if header.is_empty() {
+            return argument_error("Can not render a table without columns");
+        }
+        let mut rows = Vec::new();
+        while let Ok(row) = stream.read() {
+            rows.push(row.cells().iter().map(Cell::new).collect::<Vec<_>>());
+        }
+
+        let mut columns = Vec::new();
+        let mut left = MARGIN;
+        for (idx, name) in header.iter().enumerate() {
+            let chars = rows.iter()
+                .map(|r| r[idx].text.chars().count())
+                .chain(std::iter::once(name.chars().count()))
+                .max()
+                .unwrap();
+            columns.push((left, chars * CHAR_WIDTH));
+            left += (chars + COLUMN_GAP) * CHAR_WIDTH;
+        }
+        let width = left - COLUMN_GAP * CHAR_WIDTH + MARGIN;
+        let height = 2 * MARGIN + (rows.len() + 1) * ROW_HEIGHT;
+        Ok(Layout { header, rows, columns, width, height })
+    }
+
+    /** The top edge of the specified row, where row 0 is the header. */
+    fn row_top(&self, row: usize) -> usize {
+        MARGIN + row * ROW_HEIGHT
+    }
+
+    /** The left edge of the text of a cell, taking the alignment into account. */
+    fn text_left(&self, column: usize, cell: &Cell) -> usize {
+        let (left, width) = self.columns[column];
+        match cell.alignment {
+            Alignment::Right => left + width - cell.text.chars().count() * CHAR_WIDTH,
+            Alignment::Left => left,
+        }
+    }
+}
+
+fn write(file: Files, output: ValueSender, data: &[u8]) -> CrushResult<()> {
+    let mut writer = file.writer(output)?;
+    to_crush_error(writer.write_all(data))
+}
+
+#[signature(
+svg,
+can_block = true,
+output = Unknown,
+short = "Draw the input table as an SVG image",
+long = "The columns are laid out the same way as when the table is printed, and numbers, times and",
+long = "durations are right aligned. Cells containing styled text keep their colors and attributes.",
+long = "If no file is specified, the image is written to the output as a binary stream.",
+example = "ps | sort ^cpu | tail 10 | render:svg ./top.svg")]
+struct Svg {
+    #[unnamed()]
+    #[description("the file to write the image to.")]
+    file: Files,
+    #[default("light")]
+    #[values("light", "dark")]
+    #[description("the color theme.")]
+    theme: String,
+}
+
+fn svg(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Svg = Svg::parse(context.arguments, &context.printer)?;
+    let layout = Layout::new(context.input)?;
+    let data = svg::render(&layout, &Theme::get(&cfg.theme));
+    write(cfg.file, context.output, data.as_bytes())
+}
+
+#[signature(
+png,
+can_block = true,
+output = Unknown,
+short = "Draw the input table as a PNG image",
+long = "The columns are laid out the same way as when the table is printed, and numbers, times and",
+long = "durations are right aligned. Cells containing styled text keep their colors and attributes.",
+long = "Text is drawn using a built in font that covers ASCII, other characters are drawn as",
+long = "question marks. If no file is specified, the image is written to the output as a binary",
+long = "stream.",
+example = "ps | sort ^cpu | tail 10 | render:png ./top.png theme=\"dark\"")]
+struct Png {
+    #[unnamed()]
+    #[description("the file to write the image to.")]
+    file: Files,
+    #[default("light")]
+    #[values("light", "dark")]
+    #[description("the color theme.")]
+    theme: String,
+}
+
+fn png(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Png = Png::parse(context.arguments, &context.printer)?;
+    let layout = Layout::new(context.input)?;
+    let data = to_crush_error(png::render(&layout, &Theme::get(&cfg.theme)))?;
+    write(cfg.file, context.output, &data)
+}
+
+pub fn declare(root: &Scope) -> CrushResult<()> {
+    root.create_lazy_namespace(
+        "render",
+        Box::new(move |env| {
+            Svg::declare(env)?;
+            Png::declare(env)?;
+            Ok(())
+        }))?;
+    Ok(())
+}
@@ -0,0 +1,556 @@
+use std::cmp::Ordering;
+
+use chrono::{Duration, Local, TimeZone};
+
+use super::png::Canvas;
+use super::{svg, Theme, Rgb, write, CHAR_WIDTH, LINE_HEIGHT};
+use crate::lang::argument::ArgumentHandler;
+use crate::lang::command::OutputType::Unknown;
+use crate::lang::errors::{CrushResult, argument_error, error, to_crush_error};
+use crate::lang::execution_context::ExecutionContext;
+use crate::lang::files::Files;
+use crate::lang::style::{Color, Style};
+use crate::lang::table::{ColumnType, ColumnVec};
+use crate::lang::value::{Field, Value, ValueType};
+use crate::util::time::duration_format;
+use signature::signature;
+
+/** The size of images when no size is specified, in pixels. */
+const IMAGE_WIDTH: usize = 640;
+const IMAGE_HEIGHT: usize = 400;
+/** The number of lines in terminal charts when no height is specified. */
+const TERMINAL_HEIGHT: usize = 12;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Kind {
+    Line,
+    Scatter,
+    Bar,
+}
+
+/** How the numbers on an axis are shown. Times and durations are plotted as seconds. */
+#[derive(Clone, Copy, PartialEq)]
+enum Scale {
+    Number,
+    Time,
+    Duration,
+}
+
+impl Scale {
+    fn of(value_type: &ValueType) -> Option<Scale> {
+        match value_type {
+            ValueType::Integer | ValueType::Float => Some(Scale::Number),
+            ValueType::Time => Some(Scale::Time),
+            ValueType::Duration => Some(Scale::Duration),
+            _ => None,
+        }
+    }
+
+    fn number(&self, value: &Value) -> CrushResult<f64> {
+        match value {
+            Value::Integer(i) => Ok(*i as f64),
+            Value::Float(f) => Ok(*f),
+            Value::Time(t) => Ok(t.timestamp_nanos() as f64 / 1e9),
+            Value::Duration(d) => Ok(d.num_milliseconds() as f64 / 1e3),
+            v => error(format!("Can not plot a value of type {}", v.value_type().to_string()).as_str()),
+        }
+    }
+
+    fn label(&self, value: f64) -> String {
+        match self {
+            Scale::Number => {
+                if value.fract() == 0.0 && value.abs() < 1e15 {
+                    format!("{}", value as i64)
+                } else {
+                    let res = format!("{:.3}", value);
+                    res.trim_end_matches('0').trim_end_matches('.').to_string()
+                }
+            }
+            Scale::Time => Local.timestamp_nanos((value * 1e9) as i64).format("%Y-%m-%d %H:%M:%S").to_string(),
+            Scale::Duration => duration_format(&Duration::milliseconds((value * 1e3).round() as i64)),
+        }
+    }
+}
+
+struct Axis {
+    scale: Scale,
+    min: f64,
+    max: f64,
+}
+
+impl Axis {
+    fn new(scale: Scale, values: impl Iterator<Item=f64>, include_zero: bool) -> Axis {
+        let (mut min, mut max) = values.fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), v| (min.min(v), max.max(v)));
+        if include_zero {
+            min = min.min(0.0);
+            max = max.max(0.0);
+        }
+        if !min.is_finite() {
+            min = 0.0;
+            max = 1.0;
+        } else if min == max {
+            min -= 1.0;
+            max += 1.0;
+        }
+        Axis { scale, min, max }
+    }
+
+    /** The position of the value along the axis, from 0 at the minimum to 1 at the maximum. */
+    fn position(&self, value: f64) -> f64 {
+        (value - self.min) / (self.max - self.min)
+    }
+}
+
+/**
+The data to plot. For bar charts the x values are labels and the bars are evenly spaced,
+for other charts the x values must be numeric.
+*/
+struct Chart {
+    kind: Kind,
+    x_name: String,
+    y_name: String,
+    labels: Vec<String>,
+    points: Vec<(f64, f64)>,
+    x: Axis,
+    y: Axis,
+}
+
+fn numeric_column(types: &[ColumnType], field: &Option<Field>, skip: Option<usize>, name: &str) -> CrushResult<Option<usize>> {
+    match field {
+        Some(f) => Ok(Some(types.find(f)?)),
+        None => match types.iter().enumerate().position(|(idx, c)| Some(idx) != skip && Scale::of(&c.cell_type).is_some()) {
+            Some(idx) => Ok(Some(idx)),
+            None if name == "x" => Ok(None),
+            None => argument_error("The input has no numeric column to plot, specify one using y"),
+        },
+    }
+}
+
+impl Chart {
+    fn new(kind: Kind, value: Value, x: &Option<Field>, y: &Option<Field>) -> CrushResult<Chart> {
+        let mut stream = match value.stream() {
+            Some(stream) => stream,
+            None => return argument_error("Expected a table stream to plot"),
+        };
+        let types = stream.types().to_vec();
+        let x_idx = match x {
+            Some(_) => numeric_column(&types, x, None, "x")?,
+            None => None,
+        };
+        let y_idx = numeric_column(&types, y, x_idx, "y")?.unwrap();
+
+        let mut rows = Vec::new();
+        while let Ok(row) = stream.read() {
+            rows.push(row.into_vec());
+        }
+        let y_scale = match rows.first() {
+            Some(row) => match Scale::of(&row[y_idx].value_type()) {
+                Some(scale) => scale,
+                None => return argument_error(format!("Expected column {} to contain numbers, times or durations", types[y_idx].name).as_str()),
+            },
+            None => Scale::Number,
+        };
+        let x_scale = match (kind, x_idx, rows.first()) {
+            (Kind::Bar, _, _) | (_, None, _) | (_, _, None) => Scale::Number,
+            (_, Some(idx), Some(row)) => match Scale::of(&row[idx].value_type()) {
+                Some(scale) => scale,
+                None => return argument_error(format!("Expected column {} to contain numbers, times or durations, use kind=\"bar\" to plot other values", types[idx].name).as_str()),
+            },
+        };
+
+        let mut labels = Vec::new();
+        let mut points = Vec::new();
+        for (idx, row) in rows.iter().enumerate() {
+            let x = match (kind, x_idx) {
+                (Kind::Bar, Some(x_idx)) => {
+                    labels.push(row[x_idx].to_string());
+                    idx as f64
+                }
+                (_, Some(x_idx)) => x_scale.number(&row[x_idx])?,
+                (_, None) => {
+                    labels.push(idx.to_string());
+                    idx as f64
+                }
+            };
+            points.push((x, y_scale.number(&row[y_idx])?));
+        }
+        if kind == Kind::Line {
+            points.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(Ordering::Equal));
+        }
+
+        let x_axis = if kind == Kind::Bar {
+            // Leave room for half a bar on each side
+            Axis { scale: Scale::Number, min: -0.5, max: points.len() as f64 - 0.5 }
+        } else {
+            Axis::new(x_scale, points.iter().map(|p| p.0), false)
+        };
+        let y_axis = Axis::new(y_scale, points.iter().map(|p| p.1), kind == Kind::Bar);
+        Ok(Chart {
+            kind,
+            x_name: x_idx.map(|idx| types[idx].name.clone()).unwrap_or_else(|| "row".to_string()),
+            y_name: types[y_idx].name.clone(),
+            labels,
+            points,
+            x: x_axis,
+            y: y_axis,
+        })
+    }
+
+    fn x_labels(&self) -> (String, String) {
+        if self.kind == Kind::Bar || self.labels.len() == self.points.len() && !self.labels.is_empty() {
+            (self.labels.first().cloned().unwrap_or_default(), self.labels.last().cloned().unwrap_or_default())
+        } else {
+            (self.x.scale.label(self.x.min), self.x.scale.label(self.x.max))
+        }
+    }
+
+    fn y_labels(&self) -> (String, String) {
+        (self.y.scale.label(self.y.min), self.y.scale.label(self.y.max))
+    }
+}
+
+/**
+A grid of braille characters, each of which has two columns and four rows of dots that can
+be turned on individually.
+*/
+struct Braille {
+    width: usize,
+    height: usize,
+    cells: Vec<u8>,
+}
+
+impl Braille {
+    fn new(width: usize, height: usize) -> Braille {
+        Braille { width, height, cells: vec![0; width * height] }
+    }
+
+    fn set(&mut self, x: i64, y: i64) {
+        const BITS: [[u8; 4]; 2] = [[0x01, 0x02, 0x04, 0x40], [0x08, 0x10, 0x20, 0x80]];
+        if x >= 0 && y >= 0 && (x as usize) < self.width * 2 && (y as usize) < self.height * 4 {
+            let (x, y) = (x as usize, y as usize);
+            self.cells[y / 4 * self.width + x / 2] |= BITS[x % 2][y % 4];
+        }
+    }
+
+    fn line(&mut self, from: (i64, i64), to: (i64, i64)) {
+        let steps = (to.0 - from.0).abs().max((to.1 - from.1).abs()).max(1);
+        for step in 0..=steps {
+            self.set(
+                from.0 + (to.0 - from.0) * step / steps,
+                from.1 + (to.1 - from.1) * step / steps);
+        }
+    }
+
+    fn row(&self, row: usize) -> String {
+        self.cells[row * self.width..(row + 1) * self.width].iter()
+            .map(|bits| std::char::from_u32(0x2800 + u32::from(*bits)).unwrap())
+            .collect()
+    }
+}
+
+fn terminal(chart: &Chart, width: usize, height: usize) -> Vec<String> {
+    let (y_min, y_max) = chart.y_labels();
+    let label_width = y_min.chars().count().max(y_max.chars().count());
+    let plot_width = width.saturating_sub(label_width + 2).max(10);
+    let plot_height = height.max(2);
+
+    let rows: Vec<String> = match chart.kind {
+        Kind::Bar => {
+            const BLOCKS: [char; 9] = [' ', '▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+            let mut grid = vec![vec![' '; plot_width]; plot_height];
+            let slot = (plot_width / chart.points.len().max(1)).max(1);
+            let base = (chart.y.position(0.0) * (plot_height * 8) as f64).round() as usize;
+            for (idx, (_, y)) in chart.points.iter().enumerate() {
+                let top = (chart.y.position(*y) * (plot_height * 8) as f64).round() as usize;
+                let (low, high) = if top >= base { (base, top) } else { (top, base) };
+                let bar_width = if slot >= 3 { slot - 1 } else { slot };
+                for column in idx * slot..(idx * slot + bar_width).min(plot_width) {
+                    for (row, line) in grid.iter_mut().enumerate() {
+                        let bottom = (plot_height - 1 - row) * 8;
+                        let filled = high.min(bottom + 8).saturating_sub(low.max(bottom));
+                        if filled > 0 {
+                            line[column] = if low > bottom { BLOCKS[8] } else { BLOCKS[filled] };
+                        }
+                    }
+                }
+            }
+            grid.into_iter().map(|line| line.into_iter().collect()).collect()
+        }
+        Kind::Line | Kind::Scatter => {
+            let mut braille = Braille::new(plot_width, plot_height);
+            let dot = |p: &(f64, f64)| (
+                (chart.x.position(p.0) * (plot_width * 2 - 1) as f64).round() as i64,
+                ((1.0 - chart.y.position(p.1)) * (plot_height * 4 - 1) as f64).round() as i64);
+            let dots = chart.points.iter().map(dot).collect::<Vec<_>>();
+            for (idx, d) in dots.iter().enumerate() {
+                match (chart.kind, idx) {
+                    (Kind::Line, idx) if idx > 0 => braille.line(dots[idx - 1], *d),
+                    _ => braille.set(d.0, d.1),
+                }
+            }
+            (0..plot_height).map(|row| braille.row(row)).collect()
+        }
+    };
+
+    let mut res = vec![format!("{:>width$}", chart.y_name, width = label_width)];
+    for (idx, row) in rows.into_iter().enumerate() {
+        let (label, tick) = match idx {
+            0 => (y_max.as_str(), '┤'),
+            i if i == plot_height - 1 => (y_min.as_str(), '┤'),
+            _ => ("", '│'),
+        };
+        res.push(format!("{:>width$} {}{}", label, tick, row, width = label_width));
+    }
+    res.push(format!("{:width$} └{}", "", "─".repeat(plot_width), width = label_width));
+    let (x_min, x_max) = chart.x_labels();
+    let gap = plot_width.saturating_sub(x_min.chars().count() + x_max.chars().count()).max(1);
+    res.push(format!("{:width$}  {}{}{}", "", x_min, " ".repeat(gap), x_max, width = label_width));
+    res.push(format!("{:>width$}", chart.x_name, width = label_width + 2 + plot_width));
+    res
+}
+
+/**
+The area of an image that data is drawn in, with room for the axis labels around it.
+*/
+struct Frame {
+    left: usize,
+    top: usize,
+    width: usize,
+    height: usize,
+}
+
+impl Frame {
+    fn new(chart: &Chart, width: usize, height: usize) -> Frame {
+        let (y_min, y_max) = chart.y_labels();
+        let label_width = y_min.chars().count().max(y_max.chars().count()).max(chart.y_name.chars().count());
+        let left = (label_width + 2) * CHAR_WIDTH;
+        let top = LINE_HEIGHT * 2;
+        Frame {
+            left,
+            top,
+            width: width.saturating_sub(left + 2 * CHAR_WIDTH).max(1),
+            height: height.saturating_sub(top + 3 * LINE_HEIGHT).max(1),
+        }
+    }
+
+    fn point(&self, chart: &Chart, point: &(f64, f64)) -> (f64, f64) {
+        (self.left as f64 + chart.x.position(point.0) * self.width as f64,
+         self.top as f64 + (1.0 - chart.y.position(point.1)) * self.height as f64)
+    }
+
+    /** The left edge and width of the bar with the specified index. */
+    fn bar(&self, chart: &Chart, idx: usize) -> (f64, f64) {
+        let slot = self.width as f64 / chart.points.len().max(1) as f64;
+        (self.left as f64 + slot * (idx as f64 + 0.1), slot * 0.8)
+    }
+
+    fn baseline(&self, chart: &Chart) -> f64 {
+        self.top as f64 + (1.0 - chart.y.position(0.0)) * self.height as f64
+    }
+}
+
+fn series_color() -> Rgb {
+    Color::Blue.rgb()
+}
+
+fn render_svg(chart: &Chart, theme: &Theme, width: usize, height: usize) -> String {
+    let frame = Frame::new(chart, width, height);
+    let color = svg::color(series_color());
+    let text = |x: usize, y: usize, anchor: &str, label: &str| format!(
+        r#"<text x="{}" y="{}" text-anchor="{}" fill="{}">{}</text>"#,
+        x, y, anchor, svg::color(theme.foreground), svg::escape(label));
+    let mut res = vec![
+        format!(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{}" height="{}" viewBox="0 0 {} {}">"#,
+            width, height, width, height),
+        r#"<g font-family="DejaVu Sans Mono, Menlo, Consolas, monospace" font-size="13px">"#.to_string(),
+        format!(r#"<rect x="0" y="0" width="{}" height="{}" fill="{}"/>"#, width, height, svg::color(theme.background)),
+    ];
+
+    match chart.kind {
+        Kind::Line => {
+            let points = chart.points.iter()
+                .map(|p| frame.point(chart, p))
+                .map(|(x, y)| format!("{:.1},{:.1}", x, y))
+                .collect::<Vec<_>>();
+            res.push(format!(
+                r#"<polyline points="{}" fill="none" stroke="{}" stroke-width="2" stroke-linejoin="round"/>"#,
+                points.join(" "), color));
+        }
+        Kind::Scatter => {
+            for p in &chart.points {
+                let (x, y) = frame.point(chart, p);
+                res.push(format!(r#"<circle cx="{:.1}" cy="{:.1}" r="3" fill="{}"/>"#, x, y, color));
+            }
+        }
+        Kind::Bar => {
+            let base = frame.baseline(chart);
+            for (idx, p) in chart.points.iter().enumerate() {
+                let (left, bar_width) = frame.bar(chart, idx);
+                let (_, y) = frame.point(chart, p);
+                res.push(format!(
+                    r#"<rect x="{:.1}" y="{:.1}" width="{:.1}" height="{:.1}" fill="{}"/>"#,
+                    left, y.min(base), bar_width, (y - base).abs(), color));
+            }
+        }
+    }
+
+    let rule = svg::color(theme.rule);
+    let bottom = frame.top + frame.height;
+    res.push(format!(r#"<path d="M{} {}V{}H{}" fill="none" stroke="{}"/>"#, frame.left, frame.top, bottom, frame.left + frame.width, rule));
+    let (y_min, y_max) = chart.y_labels();
+    let (x_min, x_max) = chart.x_labels();
+    let label_x = frame.left - CHAR_WIDTH;
+    res.push(text(label_x, LINE_HEIGHT, "end", &chart.y_name));
+    res.push(text(label_x, frame.top + 4, "end", &y_max));
+    res.push(text(label_x, bottom + 4, "end", &y_min));
+    res.push(text(frame.left, bottom + LINE_HEIGHT + 4, "start", &x_min));
+    res.push(text(frame.left + frame.width, bottom + LINE_HEIGHT + 4, "end", &x_max));
+    res.push(text(frame.left + frame.width / 2, bottom + 2 * LINE_HEIGHT + 4, "middle", &chart.x_name));
+    res.push("</g>".to_string());
+    res.push("</svg>".to_string());
+    res.push(String::new());
+    res.join("\n")
+}
+
+fn render_png(chart: &Chart, theme: &Theme, width: usize, height: usize) -> std::io::Result<Vec<u8>> {
+    let frame = Frame::new(chart, width, height);
+    let mut canvas = Canvas::new(width, height, theme.background);
+    let color = series_color();
+    let pixel = |p: (f64, f64)| (p.0.round() as i64, p.1.round() as i64);
+
+    match chart.kind {
+        Kind::Line => {
+            let points = chart.points.iter().map(|p| pixel(frame.point(chart, p))).collect::<Vec<_>>();
+            for pair in points.windows(2) {
+                // Two pixels wide
+                canvas.line(pair[0], pair[1], color);
+                canvas.line((pair[0].0, pair[0].1 + 1), (pair[1].0, pair[1].1 + 1), color);
+            }
+        }
+        Kind::Scatter => {
+            for p in &chart.points {
+                let (x, y) = pixel(frame.point(chart, p));
+                canvas.fill((x - 2).max(0) as usize, (y - 2).max(0) as usize, 5, 5, color);
+            }
+        }
+        Kind::Bar => {
+            let base = frame.baseline(chart);
+            for (idx, p) in chart.points.iter().enumerate() {
+                let (left, bar_width) = frame.bar(chart, idx);
+                let (_, y) = frame.point(chart, p);
+                canvas.fill(
+                    left.round() as usize, y.min(base).round() as usize,
+                    bar_width.round().max(1.0) as usize, (y - base).abs().round() as usize,
+                    color);
+            }
+        }
+    }
+
+    let bottom = frame.top + frame.height;
+    canvas.fill(frame.left, frame.top, 1, frame.height + 1, theme.rule);
+    canvas.fill(frame.left, bottom, frame.width + 1, 1, theme.rule);
+    let (y_min, y_max) = chart.y_labels();
+    let (x_min, x_max) = chart.x_labels();
+    let plain = Style::default();
+    let right_aligned = |text: &str, right: usize| right.saturating_sub(text.chars().count() * CHAR_WIDTH);
+    let label_x = frame.left - CHAR_WIDTH;
+    canvas.string(right_aligned(&chart.y_name, label_x), 0, &chart.y_name, theme.foreground, &plain);
+    canvas.string(right_aligned(&y_max, label_x), frame.top.saturating_sub(LINE_HEIGHT / 2), &y_max, theme.foreground, &plain);
+    canvas.string(right_aligned(&y_min, label_x), bottom.saturating_sub(LINE_HEIGHT / 2), &y_min, theme.foreground, &plain);
+    canvas.string(frame.left, bottom + 4, &x_min, theme.foreground, &plain);
+    canvas.string(right_aligned(&x_max, frame.left + frame.width), bottom + 4, &x_max, theme.foreground, &plain);
+    let center = frame.left + frame.width / 2;
+    canvas.string(center.saturating_sub(chart.x_name.chars().count() * CHAR_WIDTH / 2), bottom + 4 + LINE_HEIGHT, &chart.x_name, theme.foreground, &plain);
+    canvas.encode()
+}
+
+#[signature(
+plot,
+can_block = true,
+output = Unknown,
+short = "Draw a chart of the input",
+long = "Plots one column of the input against another. By default, the chart is drawn in the",
+long = "terminal using braille characters for lines and points and block characters for bars.",
+long = "",
+long = "The chart can also be drawn as an SVG or PNG image, which is written to the specified file,",
+long = "or output as a binary stream if there is no file. If a file is specified, the format is",
+long = "guessed from its extension.",
+long = "",
+long = "The x column of line and scatter charts, and the y column of all charts, must contain",
+long = "numbers, times or durations. Bar charts use the values of the x column as labels.",
+example = "seq 20 | select ^value square={value * value} | plot x=^value y=^square")]
+pub struct Plot {
+    #[unnamed()]
+    #[description("the file to write the image to.")]
+    file: Files,
+    #[description("the column to plot along the horizontal axis. The row number if not specified.")]
+    x: Option<Field>,
+    #[description("the column to plot along the vertical axis. The first numeric column if not specified.")]
+    y: Option<Field>,
+    #[default("line")]
+    #[values("line", "scatter", "bar")]
+    #[description("the kind of chart.")]
+    kind: String,
+    #[values("terminal", "svg", "png")]
+    #[description("how to draw the chart.")]
+    format: Option<String>,
+    #[description("the width of the chart, in characters for the terminal and pixels for images.")]
+    width: Option<i128>,
+    #[description("the height of the chart, in lines for the terminal and pixels for images.")]
+    height: Option<i128>,
+    #[default("light")]
+    #[values("light", "dark")]
+    #[description("the color theme of images.")]
+    theme: String,
+}
+
+fn size(value: Option<i128>, default: usize, name: &str) -> CrushResult<usize> {
+    match value {
+        None => Ok(default),
+        Some(v) if v > 0 && v <= 100_000 => Ok(v as usize),
+        Some(_) => argument_error(format!("Expected {} to be a positive number", name).as_str()),
+    }
+}
+
+fn plot(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Plot = Plot::parse(context.arguments, &context.printer)?;
+    let kind = match cfg.kind.as_str() {
+        "scatter" => Kind::Scatter,
+        "bar" => Kind::Bar,
+        _ => Kind::Line,
+    };
+    let chart = Chart::new(kind, context.input.recv()?, &cfg.x, &cfg.y)?;
+    let format = match (cfg.format, cfg.file.had_entries()) {
+        (Some(format), _) => format,
+        (None, false) => "terminal".to_string(),
+        (None, true) => {
+            match cfg.file.as_slice()[0].extension().and_then(|e| e.to_str()) {
+                Some("svg") => "svg".to_string(),
+                Some("png") => "png".to_string(),
+                _ => return argument_error("Can not tell the image format from the file name, specify it using format"),
+            }
+        }
+    };
+    let theme = Theme::get(&cfg.theme);
+    match format.as_str() {
+        "svg" => {
+            let data = render_svg(&chart, &theme, size(cfg.width, IMAGE_WIDTH, "width")?, size(cfg.height, IMAGE_HEIGHT, "height")?);
+            write(cfg.file, context.output, data.as_bytes())
+        }
+        "png" => {
+            let data = to_crush_error(render_png(&chart, &theme, size(cfg.width, IMAGE_WIDTH, "width")?, size(cfg.height, IMAGE_HEIGHT, "height")?))?;
+            write(cfg.file, context.output, &data)
+        }
+        _ => {
+            if cfg.file.had_entries() {
+                return argument_error("Terminal charts can not be written to a file");
+            }
+            let lines = terminal(&chart, size(cfg.width, context.printer.width(), "width")?, size(cfg.height, TERMINAL_HEIGHT, "height")?);
+            for line in lines {
+                context.printer.line(&line);
+            }
+            context.output.send(Value::Empty())
+        }
+    }
+}
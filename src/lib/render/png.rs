@@ -0,0 +1,138 @@
+use super::{Layout, Theme, Rgb, Cell, CHAR_WIDTH, ROW_HEIGHT, ROW_PADDING, MARGIN};
+use crate::lang::style::Style;
+use crate::util::font;
+
+pub(super) struct Canvas {
+    width: usize,
+    height: usize,
+    pixels: Vec<u8>,
+}
+
+fn mix(from: Rgb, to: Rgb, amount: f64) -> Rgb {
+    let channel = |a: u8, b: u8| (f64::from(a) + (f64::from(b) - f64::from(a)) * amount).round() as u8;
+    (channel(from.0, to.0), channel(from.1, to.1), channel(from.2, to.2))
+}
+
+impl Canvas {
+    pub(super) fn new(width: usize, height: usize, background: Rgb) -> Canvas {
+        let mut pixels = Vec::with_capacity(width * height * 3);
+        for _ in 0..width * height {
+            pixels.extend_from_slice(&[background.0, background.1, background.2]);
+        }
+        Canvas { width, height, pixels }
+    }
+
+    fn get(&self, x: usize, y: usize) -> Rgb {
+        let idx = (y * self.width + x) * 3;
+        (self.pixels[idx], self.pixels[idx + 1], self.pixels[idx + 2])
+    }
+
+    /** Paint a pixel, with an opacity between 0 and 1. Pixels outside of the canvas are ignored. */
+    fn blend(&mut self, x: usize, y: usize, color: Rgb, opacity: f64) {
+        if x < self.width && y < self.height {
+            let (r, g, b) = mix(self.get(x, y), color, opacity);
+            let idx = (y * self.width + x) * 3;
+            self.pixels[idx..idx + 3].copy_from_slice(&[r, g, b]);
+        }
+    }
+
+    pub(super) fn fill(&mut self, x: usize, y: usize, width: usize, height: usize, color: Rgb) {
+        for yy in y..y + height {
+            for xx in x..x + width {
+                self.blend(xx, yy, color, 1.0);
+            }
+        }
+    }
+
+    /** Draw a straight line, one pixel wide, between two points. */
+    pub(super) fn line(&mut self, from: (i64, i64), to: (i64, i64), color: Rgb) {
+        let (mut x, mut y) = from;
+        let dx = (to.0 - x).abs();
+        let dy = -(to.1 - y).abs();
+        let step_x = if x < to.0 { 1 } else { -1 };
+        let step_y = if y < to.1 { 1 } else { -1 };
+        let mut error = dx + dy;
+        loop {
+            if x >= 0 && y >= 0 {
+                self.blend(x as usize, y as usize, color, 1.0);
+            }
+            if (x, y) == to {
+                break;
+            }
+            let doubled = 2 * error;
+            if doubled >= dy {
+                error += dy;
+                x += step_x;
+            }
+            if doubled <= dx {
+                error += dx;
+                y += step_y;
+            }
+        }
+    }
+
+    /**
+    Draw a line of text with its top left corner at the specified position. The colors of the
+    style are ignored, all other attributes are used.
+    */
+    pub(super) fn string(&mut self, left: usize, top: usize, text: &str, color: Rgb, style: &Style) {
+        let opacity = if style.dim { 0.6 } else { 1.0 };
+        for (idx, c) in text.chars().enumerate() {
+            let x = left + idx * CHAR_WIDTH;
+            for y in 0..font::HEIGHT {
+                // Italic text is drawn by leaning the glyph to the right above the baseline
+                let lean = if style.italic { font::BASELINE.saturating_sub(y) / 4 } else { 0 };
+                for xx in 0..font::WIDTH {
+                    let coverage = font::coverage(c, xx, y);
+                    if coverage > 0 {
+                        let alpha = opacity * f64::from(coverage) / 3.0;
+                        self.blend(x + xx + lean, top + y, color, alpha);
+                        // Bold text is drawn twice, one pixel apart
+                        if style.bold {
+                            self.blend(x + xx + lean + 1, top + y, color, alpha);
+                        }
+                    }
+                }
+            }
+            if style.underline {
+                for xx in 0..CHAR_WIDTH {
+                    self.blend(x + xx, top + font::BASELINE + 2, color, opacity);
+                }
+            }
+        }
+    }
+
+    fn text(&mut self, layout: &Layout, theme: &Theme, column: usize, row: usize, cell: &Cell, bold: bool) {
+        let left = layout.text_left(column, cell);
+        let top = layout.row_top(row);
+        if let Some(background) = cell.style.background {
+            self.fill(left, top, cell.text.chars().count() * CHAR_WIDTH, ROW_HEIGHT, background.rgb());
+        }
+        let color = cell.style.foreground.map(|c| c.rgb()).unwrap_or(theme.foreground);
+        let style = Style { bold: bold || cell.style.bold, ..cell.style };
+        self.string(left, top + ROW_PADDING, &cell.text, color, &style);
+    }
+
+    pub(super) fn encode(&self) -> std::io::Result<Vec<u8>> {
+        crate::util::png::encode(self.width, self.height, &self.pixels)
+    }
+}
+
+pub fn render(layout: &Layout, theme: &Theme) -> std::io::Result<Vec<u8>> {
+    let mut canvas = Canvas::new(layout.width, layout.height, theme.background);
+    canvas.fill(0, layout.row_top(0), layout.width, ROW_HEIGHT, theme.header_background);
+    for row in (1..layout.rows.len()).step_by(2) {
+        canvas.fill(0, layout.row_top(row + 1), layout.width, ROW_HEIGHT, theme.stripe);
+    }
+    canvas.fill(MARGIN, layout.row_top(1) - 1, layout.width - 2 * MARGIN, 1, theme.rule);
+
+    for (column, name) in layout.header.iter().enumerate() {
+        canvas.text(layout, theme, column, 0, &Cell::plain(name), true);
+    }
+    for (idx, row) in layout.rows.iter().enumerate() {
+        for (column, cell) in row.iter().enumerate() {
+            canvas.text(layout, theme, column, idx + 1, cell, false);
+        }
+    }
+    canvas.encode()
+}
@@ -0,0 +1,76 @@
+use super::{Layout, Theme, Rgb, Cell, CHAR_WIDTH, ROW_HEIGHT, ROW_PADDING, MARGIN};
+use crate::util::font::BASELINE;
+
+pub(super) fn color(rgb: Rgb) -> String {
+    format!("#{:02x}{:02x}{:02x}", rgb.0, rgb.1, rgb.2)
+}
+
+pub(super) fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn rect(res: &mut Vec<String>, x: usize, y: usize, width: usize, height: usize, fill: Rgb) {
+    res.push(format!(
+        r#"<rect x="{}" y="{}" width="{}" height="{}" fill="{}"/>"#,
+        x, y, width, height, color(fill)));
+}
+
+fn text(res: &mut Vec<String>, layout: &Layout, theme: &Theme, column: usize, row: usize, cell: &Cell, bold: bool) {
+    let x = layout.text_left(column, cell);
+    let top = layout.row_top(row);
+    if let Some(background) = cell.style.background {
+        rect(res, x, top, cell.text.chars().count() * CHAR_WIDTH, ROW_HEIGHT, background.rgb());
+    }
+    let mut attributes = format!(
+        r#"x="{}" y="{}" fill="{}""#,
+        x, top + ROW_PADDING + BASELINE,
+        color(cell.style.foreground.map(|c| c.rgb()).unwrap_or(theme.foreground)));
+    if bold || cell.style.bold {
+        attributes.push_str(r#" font-weight="bold""#);
+    }
+    if cell.style.italic {
+        attributes.push_str(r#" font-style="italic""#);
+    }
+    if cell.style.underline {
+        attributes.push_str(r#" text-decoration="underline""#);
+    }
+    if cell.style.dim {
+        attributes.push_str(r#" opacity="0.6""#);
+    }
+    res.push(format!(
+        r#"<text {} textLength="{}">{}</text>"#,
+        attributes, cell.text.chars().count() * CHAR_WIDTH, escape(&cell.text)));
+}
+
+pub fn render(layout: &Layout, theme: &Theme) -> String {
+    let mut res = vec![
+        format!(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{}" height="{}" viewBox="0 0 {} {}">"#,
+            layout.width, layout.height, layout.width, layout.height),
+        r#"<g font-family="DejaVu Sans Mono, Menlo, Consolas, monospace" font-size="13px" xml:space="preserve">"#.to_string(),
+    ];
+    rect(&mut res, 0, 0, layout.width, layout.height, theme.background);
+    rect(&mut res, 0, layout.row_top(0), layout.width, ROW_HEIGHT, theme.header_background);
+    for row in (1..layout.rows.len()).step_by(2) {
+        rect(&mut res, 0, layout.row_top(row + 1), layout.width, ROW_HEIGHT, theme.stripe);
+    }
+    rect(&mut res, MARGIN, layout.row_top(1) - 1, layout.width - 2 * MARGIN, 1, theme.rule);
+
+    for (column, name) in layout.header.iter().enumerate() {
+        text(&mut res, layout, theme, column, 0, &Cell::plain(name), true);
+    }
+    for (idx, row) in layout.rows.iter().enumerate() {
+        for (column, cell) in row.iter().enumerate() {
+            if !cell.text.is_empty() {
+                text(&mut res, layout, theme, column, idx + 1, cell, false);
+            }
+        }
+    }
+    res.push("</g>".to_string());
+    res.push("</svg>".to_string());
+    res.push(String::new());
+    res.join("\n")
+}
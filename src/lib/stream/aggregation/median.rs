@@ -0,0 +1,140 @@
+use crate::lang::errors::{CrushResult, error};
+use crate::lang::value::{Value, ValueType};
+use super::{Accumulator, to_f64, from_f64};
+use float_ord::FloatOrd;
+
+/**
+    Exact median. All values are kept in memory until the end of the stream.
+*/
+pub struct Median {
+    value_type: ValueType,
+    values: Vec<f64>,
+}
+
+impl Median {
+    pub fn new(value_type: ValueType) -> Median {
+        Median { value_type, values: Vec::new() }
+    }
+}
+
+impl Accumulator for Median {
+    fn add(&mut self, value: &Value) -> CrushResult<()> {
+        self.values.push(to_f64(value)?);
+        Ok(())
+    }
+
+    fn result(&self) -> CrushResult<Value> {
+        if self.values.is_empty() {
+            return error("Can't calculate median of an empty stream");
+        }
+        let mut values = self.values.clone();
+        values.sort_by_key(|v| FloatOrd(*v));
+        let mid = values.len() / 2;
+        let res = if values.len() & 1 == 0 {
+            (values[mid - 1] + values[mid]) / 2.0
+        } else {
+            values[mid]
+        };
+        Ok(from_f64(&self.value_type, res))
+    }
+}
+
+/**
+    Approximate median using the P² algorithm by Jain and Chlamtac, which uses
+    a constant amount of memory regardless of the length of the stream.
+*/
+pub struct ApproximateMedian {
+    value_type: ValueType,
+    heights: Vec<f64>,
+    positions: [f64; 5],
+    desired: [f64; 5],
+}
+
+const INCREMENTS: [f64; 5] = [0.0, 0.25, 0.5, 0.75, 1.0];
+
+impl ApproximateMedian {
+    pub fn new(value_type: ValueType) -> ApproximateMedian {
+        ApproximateMedian {
+            value_type,
+            heights: Vec::with_capacity(5),
+            positions: [1.0, 2.0, 3.0, 4.0, 5.0],
+            desired: [1.0, 2.0, 3.0, 4.0, 5.0],
+        }
+    }
+
+    fn parabolic(&self, i: usize, d: f64) -> f64 {
+        let q = &self.heights;
+        let n = &self.positions;
+        q[i] + d / (n[i + 1] - n[i - 1]) * (
+            (n[i] - n[i - 1] + d) * (q[i + 1] - q[i]) / (n[i + 1] - n[i]) +
+                (n[i + 1] - n[i] - d) * (q[i] - q[i - 1]) / (n[i] - n[i - 1]))
+    }
+
+    fn linear(&self, i: usize, d: f64) -> f64 {
+        let j = (i as f64 + d) as usize;
+        self.heights[i] + d * (self.heights[j] - self.heights[i]) / (self.positions[j] - self.positions[i])
+    }
+}
+
+impl Accumulator for ApproximateMedian {
+    fn add(&mut self, value: &Value) -> CrushResult<()> {
+        let x = to_f64(value)?;
+        if x.is_nan() {
+            return error("Can't estimate the median of a column containing NaN");
+        }
+        if self.heights.len() < 5 {
+            self.heights.push(x);
+            self.heights.sort_by_key(|v| FloatOrd(*v));
+            return Ok(());
+        }
+
+        let k = if x < self.heights[0] {
+            self.heights[0] = x;
+            0
+        } else if x >= self.heights[4] {
+            self.heights[4] = x;
+            3
+        } else {
+            (1..5).find(|i| x < self.heights[*i]).unwrap() - 1
+        };
+
+        for position in self.positions[k + 1..].iter_mut() {
+            *position += 1.0;
+        }
+        for (desired, increment) in self.desired.iter_mut().zip(INCREMENTS.iter()) {
+            *desired += increment;
+        }
+
+        for i in 1..4 {
+            let d = self.desired[i] - self.positions[i];
+            if (d >= 1.0 && self.positions[i + 1] - self.positions[i] > 1.0) ||
+                (d <= -1.0 && self.positions[i - 1] - self.positions[i] < -1.0) {
+                let d = d.signum();
+                let candidate = self.parabolic(i, d);
+                self.heights[i] = if self.heights[i - 1] < candidate && candidate < self.heights[i + 1] {
+                    candidate
+                } else {
+                    self.linear(i, d)
+                };
+                self.positions[i] += d;
+            }
+        }
+        Ok(())
+    }
+
+    fn result(&self) -> CrushResult<Value> {
+        match self.heights.len() {
+            0 => error("Can't calculate median of an empty stream"),
+            5 => Ok(from_f64(&self.value_type, self.heights[2])),
+            len => {
+                let mid = len / 2;
+                let res = if len & 1 == 0 {
+                    (self.heights[mid - 1] + self.heights[mid]) / 2.0
+                } else {
+                    self.heights[mid]
+                };
+                Ok(from_f64(&self.value_type, res))
+            }
+        }
+    }
+}
@@ -0,0 +1,354 @@
+use crate::lang::execution_context::ExecutionContext;
+use crate::lang::errors::{CrushResult, error, argument_error};
+use crate::lang::{
+    value::ValueType,
+    value::Value,
+};
+use crate::lang::{table::ColumnType, table::Row, argument::Argument};
+use crate::lang::stream::Stream;
+use crate::lang::table::ColumnVec;
+use chrono::Duration;
+use float_ord::FloatOrd;
+
+mod median;
+
+/**
+    A single pass computation over the values of one column.
+*/
+trait Accumulator {
+    fn add(&mut self, value: &Value) -> CrushResult<()>;
+    fn result(&self) -> CrushResult<Value>;
+}
+
+type AccumulatorFactory = fn(&ValueType, &str) -> CrushResult<Box<dyn Accumulator>>;
+
+fn parse(input_type: &[ColumnType], arguments: &[Argument]) -> CrushResult<Vec<usize>> {
+    if arguments.is_empty() {
+        return if input_type.len() == 1 {
+            Ok(vec![0])
+        } else {
+            error("Specify which column to operate on")
+        };
+    }
+    arguments.iter()
+        .map(|arg| match (&arg.argument_type, &arg.value) {
+            (None, Value::Field(f)) => match f.len() {
+                1 => Ok(input_type.find_str(f[0].as_ref())?),
+                _ => error("Path contains too many elements"),
+            },
+            (None, _) => error("Unexpected cell type, expected field"),
+            (Some(name), _) => argument_error(format!("Unknown argument {}", name).as_str()),
+        })
+        .collect()
+}
+
+/**
+    An accumulator for a column of any type, e.g. the output of pmap, that
+    is created for the type of the first value in the column.
+*/
+struct Deferred {
+    name: String,
+    factory: AccumulatorFactory,
+    accumulator: Option<Box<dyn Accumulator>>,
+}
+
+impl Accumulator for Deferred {
+    fn add(&mut self, value: &Value) -> CrushResult<()> {
+        if self.accumulator.is_none() {
+            self.accumulator = Some((self.factory)(&value.value_type(), &self.name)?);
+        }
+        match &mut self.accumulator {
+            Some(accumulator) => accumulator.add(value),
+            None => error("Invalid cell value"),
+        }
+    }
+
+    fn result(&self) -> CrushResult<Value> {
+        match &self.accumulator {
+            Some(accumulator) => accumulator.result(),
+            None => (self.factory)(&ValueType::Any, &self.name)?.result(),
+        }
+    }
+}
+
+fn accumulator(t: &ValueType, name: &str, factory: AccumulatorFactory) -> CrushResult<Box<dyn Accumulator>> {
+    match t {
+        ValueType::Any => Ok(Box::from(Deferred { name: name.to_string(), factory, accumulator: None })),
+        t => factory(t, name),
+    }
+}
+
+/**
+    Feed every row of the input to one accumulator per requested column. If
+    a single column was requested, the result is sent as a plain value,
+    otherwise a table with a single row is sent, containing one column per
+    requested column.
+*/
+fn aggregate(context: ExecutionContext, name: &str, factory: AccumulatorFactory) -> CrushResult<()> {
+    match context.input.recv()?.stream() {
+        Some(mut input) => {
+            let columns = parse(input.types(), &context.arguments)?;
+            let mut accumulators = columns.iter()
+                .map(|idx| accumulator(&input.types()[*idx].cell_type, name, factory))
+                .collect::<CrushResult<Vec<_>>>()?;
+            run(&mut input, &columns, &mut accumulators)?;
+            let mut results = accumulators.iter()
+                .map(|a| a.result())
+                .collect::<CrushResult<Vec<_>>>()?;
+            if results.len() == 1 {
+                context.output.send(results.remove(0))
+            } else {
+                let output_type = columns.iter()
+                    .zip(results.iter())
+                    .map(|(idx, value)| ColumnType::new(&input.types()[*idx].name, value.value_type()))
+                    .collect();
+                let output = context.output.initialize(output_type)?;
+                output.send(Row::new(results))
+            }
+        }
+        None => error("Expected a stream"),
+    }
+}
+
+fn run(input: &mut Stream, columns: &[usize], accumulators: &mut [Box<dyn Accumulator>]) -> CrushResult<()> {
+    while let Ok(row) = input.read() {
+        for (idx, accumulator) in columns.iter().zip(accumulators.iter_mut()) {
+            accumulator.add(&row.cells()[*idx])?;
+        }
+    }
+    Ok(())
+}
+
+fn unsupported(name: &str, t: &ValueType) -> CrushResult<Box<dyn Accumulator>> {
+    argument_error(format!("Can't calculate {} of elements of type {}", name, t.to_string()).as_str())
+}
+
+fn to_f64(value: &Value) -> CrushResult<f64> {
+    match value {
+        Value::Integer(i) => Ok(*i as f64),
+        Value::Float(f) => Ok(*f),
+        Value::Duration(d) => Ok(d.num_nanoseconds().map(|n| n as f64)
+            .unwrap_or_else(|| d.num_milliseconds() as f64 * 1_000_000.0)),
+        _ => error("Invalid cell value"),
+    }
+}
+
+fn from_f64(value_type: &ValueType, value: f64) -> Value {
+    match value_type {
+        ValueType::Integer => Value::Integer(value.round() as i128),
+        ValueType::Duration => Value::Duration(Duration::nanoseconds(value.round() as i64)),
+        _ => Value::Float(value),
+    }
+}
+
+macro_rules! sum_accumulator {
+    ($name:ident, $var_type:ty, $value_type:ident, $count_type:ty) => {
+struct $name {
+    sum: $var_type,
+    count: i128,
+    average: bool,
+}
+
+impl Accumulator for $name {
+    fn add(&mut self, value: &Value) -> CrushResult<()> {
+        match value {
+            Value::$value_type(i) => {
+                /* The Duration of chrono 0.4.9 does not implement AddAssign. */
+                #[allow(clippy::assign_op_pattern)]
+                {
+                    self.sum = self.sum + *i;
+                }
+                self.count += 1;
+                Ok(())
+            }
+            _ => error("Invalid cell value"),
+        }
+    }
+
+    fn result(&self) -> CrushResult<Value> {
+        if !self.average {
+            Ok(Value::$value_type(self.sum))
+        } else if self.count == 0 {
+            error("Can't calculate average of an empty stream")
+        } else {
+            Ok(Value::$value_type(self.sum / (self.count as $count_type)))
+        }
+    }
+}
+    }
+}
+
+sum_accumulator!(IntegerSum, i128, Integer, i128);
+sum_accumulator!(FloatSum, f64, Float, f64);
+sum_accumulator!(DurationSum, Duration, Duration, i32);
+
+fn sum_accumulator(t: &ValueType, name: &str) -> CrushResult<Box<dyn Accumulator>> {
+    sum_or_average_accumulator(t, name, false)
+}
+
+fn avg_accumulator(t: &ValueType, name: &str) -> CrushResult<Box<dyn Accumulator>> {
+    sum_or_average_accumulator(t, name, true)
+}
+
+fn sum_or_average_accumulator(t: &ValueType, name: &str, average: bool) -> CrushResult<Box<dyn Accumulator>> {
+    match t {
+        ValueType::Integer => Ok(Box::from(IntegerSum { sum: 0, count: 0, average })),
+        ValueType::Float => Ok(Box::from(FloatSum { sum: 0.0, count: 0, average })),
+        ValueType::Duration => Ok(Box::from(DurationSum { sum: Duration::seconds(0), count: 0, average })),
+        t => unsupported(name, t),
+    }
+}
+
+pub fn sum(context: ExecutionContext) -> CrushResult<()> {
+    aggregate(context, "sum", sum_accumulator)
+}
+
+pub fn avg(context: ExecutionContext) -> CrushResult<()> {
+    aggregate(context, "average", avg_accumulator)
+}
+
+macro_rules! pick_accumulator {
+    ($name:ident, $var_type:ty, $value_type:ident, $op:expr) => {
+struct $name {
+    value: Option<$var_type>,
+}
+
+impl Accumulator for $name {
+    fn add(&mut self, value: &Value) -> CrushResult<()> {
+        match value {
+            Value::$value_type(i) => {
+                self.value = Some(match self.value {
+                    None => *i,
+                    Some(v) => $op(v, *i),
+                });
+                Ok(())
+            }
+            _ => error("Invalid cell value"),
+        }
+    }
+
+    fn result(&self) -> CrushResult<Value> {
+        match self.value {
+            Some(v) => Ok(Value::$value_type(v)),
+            None => error("Can't pick a value from an empty stream"),
+        }
+    }
+}
+    }
+}
+
+pick_accumulator!(IntegerMin, i128, Integer, std::cmp::min);
+pick_accumulator!(FloatMin, f64, Float, |a, b| std::cmp::min(FloatOrd(a),FloatOrd(b)).0);
+pick_accumulator!(DurationMin, Duration, Duration, std::cmp::min);
+pick_accumulator!(TimeMin, chrono::DateTime<chrono::Local>, Time, std::cmp::min);
+
+pick_accumulator!(IntegerMax, i128, Integer, std::cmp::max);
+pick_accumulator!(FloatMax, f64, Float, |a, b| std::cmp::max(FloatOrd(a),FloatOrd(b)).0);
+pick_accumulator!(DurationMax, Duration, Duration, std::cmp::max);
+pick_accumulator!(TimeMax, chrono::DateTime<chrono::Local>, Time, std::cmp::max);
+
+fn min_accumulator(t: &ValueType, name: &str) -> CrushResult<Box<dyn Accumulator>> {
+    match t {
+        ValueType::Integer => Ok(Box::from(IntegerMin { value: None })),
+        ValueType::Float => Ok(Box::from(FloatMin { value: None })),
+        ValueType::Duration => Ok(Box::from(DurationMin { value: None })),
+        ValueType::Time => Ok(Box::from(TimeMin { value: None })),
+        t => unsupported(name, t),
+    }
+}
+
+fn max_accumulator(t: &ValueType, name: &str) -> CrushResult<Box<dyn Accumulator>> {
+    match t {
+        ValueType::Integer => Ok(Box::from(IntegerMax { value: None })),
+        ValueType::Float => Ok(Box::from(FloatMax { value: None })),
+        ValueType::Duration => Ok(Box::from(DurationMax { value: None })),
+        ValueType::Time => Ok(Box::from(TimeMax { value: None })),
+        t => unsupported(name, t),
+    }
+}
+
+pub fn min(context: ExecutionContext) -> CrushResult<()> {
+    aggregate(context, "min", min_accumulator)
+}
+
+pub fn max(context: ExecutionContext) -> CrushResult<()> {
+    aggregate(context, "max", max_accumulator)
+}
+
+/**
+    Standard deviation using Welford's online algorithm.
+*/
+struct StdDev {
+    value_type: ValueType,
+    count: f64,
+    mean: f64,
+    m2: f64,
+}
+
+impl Accumulator for StdDev {
+    fn add(&mut self, value: &Value) -> CrushResult<()> {
+        let x = to_f64(value)?;
+        self.count += 1.0;
+        let delta = x - self.mean;
+        self.mean += delta / self.count;
+        self.m2 += delta * (x - self.mean);
+        Ok(())
+    }
+
+    fn result(&self) -> CrushResult<Value> {
+        if self.count == 0.0 {
+            return error("Can't calculate standard deviation of an empty stream");
+        }
+        let res = (self.m2 / self.count).sqrt();
+        Ok(match self.value_type {
+            ValueType::Duration => from_f64(&self.value_type, res),
+            _ => Value::Float(res),
+        })
+    }
+}
+
+fn stddev_accumulator(t: &ValueType, name: &str) -> CrushResult<Box<dyn Accumulator>> {
+    match t {
+        ValueType::Integer | ValueType::Float | ValueType::Duration =>
+            Ok(Box::from(StdDev { value_type: t.clone(), count: 0.0, mean: 0.0, m2: 0.0 })),
+        t => unsupported(name, t),
+    }
+}
+
+pub fn stddev(context: ExecutionContext) -> CrushResult<()> {
+    aggregate(context, "standard deviation", stddev_accumulator)
+}
+
+fn median_accumulator(t: &ValueType, name: &str) -> CrushResult<Box<dyn Accumulator>> {
+    match t {
+        ValueType::Integer | ValueType::Float | ValueType::Duration =>
+            Ok(Box::from(median::Median::new(t.clone()))),
+        t => unsupported(name, t),
+    }
+}
+
+fn approximate_median_accumulator(t: &ValueType, name: &str) -> CrushResult<Box<dyn Accumulator>> {
+    match t {
+        ValueType::Integer | ValueType::Float | ValueType::Duration =>
+            Ok(Box::from(median::ApproximateMedian::new(t.clone()))),
+        t => unsupported(name, t),
+    }
+}
+
+pub fn median(mut context: ExecutionContext) -> CrushResult<()> {
+    let mut approximate = false;
+    let mut arguments = Vec::new();
+    for arg in context.arguments.drain(..) {
+        match (arg.argument_type.as_deref(), &arg.value) {
+            (Some("approximate"), Value::Bool(b)) => approximate = *b,
+            (Some("approximate"), _) => return argument_error("Expected approximate to be a boolean"),
+            _ => arguments.push(arg),
+        }
+    }
+    context.arguments = arguments;
+    if approximate {
+        aggregate(context, "median", approximate_median_accumulator)
+    } else {
+        aggregate(context, "median", median_accumulator)
+    }
+}
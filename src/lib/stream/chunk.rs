@@ -0,0 +1,97 @@
+use crate::lang::execution_context::ExecutionContext;
+use crate::lang::errors::{CrushResult, error, argument_error};
+use crate::lang::{value::ValueType, table::Row, value::Value};
+use crate::lang::stream::{CrushStream, OutputStream};
+use crate::lang::table::{ColumnType, ColumnVec, Table};
+use crate::lang::value::Field;
+use signature::signature;
+use crate::lang::argument::ArgumentHandler;
+
+#[signature(
+chunk,
+can_block = true,
+short = "Split the input into chunks, each a table of consecutive rows",
+long = "Every row of the output contains a table with up to size rows of the input in the chunk column.",
+long = "If by is given, consecutive rows with the same value in that column go into the same chunk,",
+long = "and the value is added as the first column of the output.",
+long = "",
+long = "    ps | sort ^user | chunk by=^user",
+example = "for (seq 10000 | chunk size=1000) {echo (chunk | count)}")]
+pub struct Chunk {
+    #[description("the maximum number of rows in a chunk.")]
+    size: Option<i128>,
+    #[description("start a new chunk whenever the value of this column changes.")]
+    by: Option<Field>,
+}
+
+struct Chunker {
+    types: Vec<ColumnType>,
+    key: Option<Value>,
+    rows: Vec<Row>,
+    output: OutputStream,
+}
+
+impl Chunker {
+    fn flush(&mut self) -> CrushResult<()> {
+        if self.rows.is_empty() {
+            return Ok(());
+        }
+        let table = Value::Table(Table::new(self.types.clone(), std::mem::take(&mut self.rows)));
+        self.output.send(Row::new(match &self.key {
+            Some(key) => vec![key.clone(), table],
+            None => vec![table],
+        }))
+    }
+}
+
+pub fn run(size: Option<usize>, by: Option<usize>, input: &mut dyn CrushStream, output: OutputStream) -> CrushResult<()> {
+    let mut chunker = Chunker {
+        types: input.types().to_vec(),
+        key: None,
+        rows: Vec::new(),
+        output,
+    };
+    while let Ok(row) = input.read() {
+        if let Some(idx) = by {
+            let key = &row.cells()[idx];
+            if chunker.key.as_ref() != Some(key) {
+                chunker.flush()?;
+                chunker.key = Some(key.clone());
+            }
+        }
+        chunker.rows.push(row);
+        if Some(chunker.rows.len()) == size {
+            chunker.flush()?;
+        }
+    }
+    chunker.flush()
+}
+
+pub fn chunk(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Chunk = Chunk::parse(context.arguments, &context.printer)?;
+    let size = match cfg.size {
+        Some(size) if size <= 0 => return argument_error("The chunk size must be positive"),
+        Some(size) => Some(size as usize),
+        None => None,
+    };
+    if size.is_none() && cfg.by.is_none() {
+        return argument_error("Expected either a chunk size or a column to chunk by");
+    }
+    match context.input.recv()?.stream() {
+        Some(mut input) => {
+            let by = match &cfg.by {
+                Some(field) => Some(input.types().find(field)?),
+                None => None,
+            };
+            let mut output_type = Vec::new();
+            if let Some(idx) = by {
+                output_type.push(input.types()[idx].clone());
+            }
+            output_type.push(ColumnType::new("chunk", ValueType::Table(input.types().to_vec())));
+            ColumnType::deduplicate(&mut output_type);
+            let output = context.output.initialize(output_type)?;
+            run(size, by, input.as_mut(), output)
+        }
+        None => error("Expected a stream"),
+    }
+}
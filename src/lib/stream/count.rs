@@ -4,8 +4,11 @@ use crate::lang::value::Value;
 use crate::lang::stream::Stream;
 
 fn count_rows(mut s: Stream) -> Value {
+    if let Some(count) = s.row_count() {
+        return Value::Integer(count as i128);
+    }
     let mut res: i128 = 0;
-    while let Ok(_) = s.read() {
+    while s.read().is_ok() {
         res += 1;
     }
     Value::Integer(res)
@@ -0,0 +1,99 @@
+use chrono::Duration;
+
+use crate::lang::argument::ArgumentHandler;
+use crate::lang::errors::{CrushResult, argument_error, error};
+use crate::lang::execution_context::ExecutionContext;
+use crate::lang::stream::{CrushStream, OutputStream, RecvTimeoutError};
+use crate::lang::table::{ColumnVec, Row};
+use crate::lang::value::{Field, Value};
+use signature::signature;
+
+#[signature(
+debounce,
+can_block = true,
+short = "Pass on a row of the input only once no new rows have arrived for a while",
+long = "Every row starts a wait of the given duration. If another row arrives before the wait is",
+long = "over, the earlier row is dropped and the wait starts over, so a burst of rows results in just",
+long = "its last row. The last row of the input is always passed on.",
+long = "",
+long = "This is useful for streams of events, like those from watch, where one change often causes",
+long = "many events.",
+example = "for (watch ./src | debounce 2s) {make}")]
+pub struct Debounce {
+    #[description("how long the input must be quiet before the last row is passed on.")]
+    duration: Duration,
+}
+
+pub fn run_debounce(duration: Duration, input: &mut dyn CrushStream, output: OutputStream) -> CrushResult<()> {
+    let mut pending: Option<Row> = None;
+    loop {
+        match pending.take() {
+            None => match input.read() {
+                Ok(row) => pending = Some(row),
+                Err(_) => return Ok(()),
+            },
+            Some(row) => match input.read_timeout(duration) {
+                Ok(newer) => pending = Some(newer),
+                Err(RecvTimeoutError::Timeout) => output.send(row)?,
+                Err(RecvTimeoutError::Disconnected) => return output.send(row),
+            },
+        }
+    }
+}
+
+pub fn debounce(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Debounce = Debounce::parse(context.arguments, &context.printer)?;
+    if cfg.duration < Duration::zero() {
+        return argument_error("The duration must not be negative");
+    }
+    match context.input.recv()?.stream() {
+        Some(mut input) => {
+            let output = context.output.initialize(input.types().to_vec())?;
+            run_debounce(cfg.duration, input.as_mut(), output)
+        }
+        None => error("Expected a stream"),
+    }
+}
+
+#[signature(
+changes,
+can_block = true,
+short = "Pass on only the rows of the input that differ from the row before them",
+long = "If a column is given, only that column is compared, otherwise the whole row is. The first row",
+long = "is always passed on. Unlike uniq, a value that comes back after another one is passed on",
+long = "again, and no memory is needed for the values already seen, so it works on endless streams.",
+example = "watch ./src | select ^file | changes")]
+pub struct Changes {
+    #[description("the column to compare.")]
+    field: Option<Field>,
+}
+
+pub fn run_changes(idx: Option<usize>, input: &mut dyn CrushStream, output: OutputStream) -> CrushResult<()> {
+    let mut previous: Option<Vec<Value>> = None;
+    while let Ok(row) = input.read() {
+        let key = match idx {
+            Some(idx) => vec![row.cells()[idx].clone()],
+            None => row.cells().clone(),
+        };
+        if previous.as_ref() != Some(&key) {
+            previous = Some(key);
+            output.send(row)?;
+        }
+    }
+    Ok(())
+}
+
+pub fn changes(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Changes = Changes::parse(context.arguments, &context.printer)?;
+    match context.input.recv()?.stream() {
+        Some(mut input) => {
+            let idx = match &cfg.field {
+                Some(field) => Some(input.types().find(field)?),
+                None => None,
+            };
+            let output = context.output.initialize(input.types().to_vec())?;
+            run_changes(idx, input.as_mut(), output)
+        }
+        None => error("Expected a stream"),
+    }
+}
@@ -3,13 +3,29 @@ use crate::lang::errors::{CrushResult, error};
 use crate::lang::{value::ValueType, table::Row, value::Value};
 use crate::lang::stream::{CrushStream, ValueSender};
 use crate::lang::table::ColumnType;
+use signature::signature;
+use crate::lang::argument::ArgumentHandler;
 
-pub fn run(input: &mut dyn CrushStream, sender: ValueSender) -> CrushResult<()> {
-    let mut output_type = vec![ColumnType::new("idx", ValueType::Integer)];
+#[signature(
+enumerate,
+can_block = true,
+short = "Prepend a column containing the row number to each row of the input",
+example = "ps | enumerate start=1")]
+pub struct Enumerate {
+    #[default(0)]
+    #[description("the index of the first row.")]
+    start: i128,
+    #[default("idx")]
+    #[description("the name of the index column.")]
+    name: String,
+}
+
+pub fn run(cfg: Enumerate, input: &mut dyn CrushStream, sender: ValueSender) -> CrushResult<()> {
+    let mut output_type = vec![ColumnType::new(&cfg.name, ValueType::Integer)];
     output_type.extend(input.types().to_vec());
     let output = sender.initialize(output_type)?;
 
-    let mut line: i128 = 0;
+    let mut line: i128 = cfg.start;
     while let Ok(row) = input.read() {
         let mut out = vec![Value::Integer(line)];
         out.extend(row.into_vec());
@@ -19,9 +35,10 @@ pub fn run(input: &mut dyn CrushStream, sender: ValueSender) -> CrushResult<()>
     Ok(())
 }
 
-pub fn perform(context: ExecutionContext) -> CrushResult<()> {
+pub fn enumerate(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Enumerate = Enumerate::parse(context.arguments, &context.printer)?;
     match context.input.recv()?.stream() {
-        Some(mut r) => run(r.as_mut(), context.output),
+        Some(mut r) => run(cfg, r.as_mut(), context.output),
         None => error("Expected a stream"),
     }
 }
@@ -0,0 +1,130 @@
+use std::hash::Hash;
+use std::time::Instant;
+
+use chrono::Duration;
+
+use crate::lang::argument::{Argument, ArgumentHandler};
+use crate::lang::errors::{CrushResult, argument_error, error, to_crush_error};
+use crate::lang::execution_context::ExecutionContext;
+use crate::lang::stream::{CrushStream, OutputStream, RecvTimeoutError};
+use crate::lang::table::{ColumnType, ColumnVec, Row};
+use crate::lang::value::{Field, Value, ValueType};
+use crate::util::expiry::Expiry;
+use signature::signature;
+
+#[signature(
+expire,
+can_block = true,
+short = "Track the keys of the input, and output an event when one has not been seen for a while",
+long = "Every row of the input is passed on with an extra event column, which is added if the key of",
+long = "the row was not being tracked, and refreshed if it was. When no row with a key has arrived",
+long = "within the ttl, the last row with that key is output again with the event expired, and the",
+long = "key is forgotten. When the input ends, all keys that are still tracked expire at once.",
+long = "",
+long = "Only keys seen within the ttl are remembered, so this works on endless streams, like uniq and",
+long = "group do when given a ttl. Keeping just the added rows gives the distinct keys of a sliding",
+long = "window, and keeping just the expired rows gives the last row of every burst.",
+example = "watch ./src | select ^file | expire ttl=10s | where {event == \"expired\"}")]
+pub struct Expire {
+    #[description("the column to use as the key. The default is the whole row.")]
+    key: Option<Field>,
+    #[description("how long a key is remembered after its last row.")]
+    ttl: Duration,
+}
+
+fn event(mut row: Row, event: &str) -> Row {
+    row.push(Value::string(event));
+    row
+}
+
+/** What happened while waiting for the next row of a stream whose keys expire. */
+pub enum Next {
+    Row(Row),
+    Deadline,
+    End,
+}
+
+/**
+Read the next row of the input, but wait no longer than until the next key expires, so that
+expired keys are handled even when the input pauses.
+*/
+pub fn next<K: Hash + Eq + Clone, V>(keys: &mut Expiry<K, V>, input: &mut dyn CrushStream) -> CrushResult<Next> {
+    match keys.next_deadline() {
+        None => Ok(input.read().map(Next::Row).unwrap_or(Next::End)),
+        Some(deadline) => {
+            let wait = deadline.saturating_duration_since(Instant::now());
+            match input.read_timeout(to_crush_error(Duration::from_std(wait))?) {
+                Ok(row) => Ok(Next::Row(row)),
+                Err(RecvTimeoutError::Timeout) => Ok(Next::Deadline),
+                Err(RecvTimeoutError::Disconnected) => Ok(Next::End),
+            }
+        }
+    }
+}
+
+/**
+Remove the ttl argument of a stream command that can forget keys, like uniq and group, and
+return it.
+*/
+pub fn ttl(arguments: &mut Vec<Argument>) -> CrushResult<Option<std::time::Duration>> {
+    let idx = match arguments.iter().position(|a| a.argument_type.as_deref() == Some("ttl")) {
+        Some(idx) => idx,
+        None => return Ok(None),
+    };
+    match arguments.remove(idx).value {
+        Value::Duration(ttl) if ttl > Duration::zero() => Ok(Some(to_crush_error(ttl.to_std())?)),
+        Value::Duration(_) => argument_error("The ttl must be positive"),
+        value => argument_error(format!("Expected the ttl to be a duration, got a {}", value.value_type().to_string()).as_str()),
+    }
+}
+
+pub fn run(idx: Option<usize>, ttl: std::time::Duration, input: &mut dyn CrushStream, output: OutputStream) -> CrushResult<()> {
+    let mut keys: Expiry<Row, Row> = Expiry::new(ttl);
+    loop {
+        let row = match next(&mut keys, input)? {
+            Next::Row(row) => row,
+            Next::Deadline => {
+                for (_, row) in keys.expire(Instant::now()) {
+                    output.send(event(row, "expired"))?;
+                }
+                continue;
+            }
+            Next::End => break,
+        };
+        // Rows are only read until a deadline has passed, so expire those keys first
+        for (_, row) in keys.expire(Instant::now()) {
+            output.send(event(row, "expired"))?;
+        }
+        let key = match idx {
+            Some(idx) => Row::new(vec![row.cells()[idx].clone()]),
+            None => row.clone(),
+        };
+        let added = keys.touch(key, row.clone(), Instant::now());
+        output.send(event(row, if added { "added" } else { "refreshed" }))?;
+    }
+    for (_, row) in keys.drain() {
+        output.send(event(row, "expired"))?;
+    }
+    Ok(())
+}
+
+pub fn expire(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Expire = Expire::parse(context.arguments, &context.printer)?;
+    if cfg.ttl <= Duration::zero() {
+        return argument_error("The ttl must be positive");
+    }
+    let ttl = to_crush_error(cfg.ttl.to_std())?;
+    match context.input.recv()?.stream() {
+        Some(mut input) => {
+            let idx = match &cfg.key {
+                Some(key) => Some(input.types().find(key)?),
+                None => None,
+            };
+            let mut types = input.types().to_vec();
+            types.push(ColumnType::new("event", ValueType::String));
+            let output = context.output.initialize(types)?;
+            run(idx, ttl, input.as_mut(), output)
+        }
+        None => error("Expected a stream"),
+    }
+}
@@ -0,0 +1,65 @@
+use crate::lang::execution_context::ExecutionContext;
+use crate::lang::errors::{CrushResult, error};
+use crate::lang::value::Value;
+use crate::lang::stream::ValueSender;
+use signature::signature;
+use crate::lang::argument::ArgumentHandler;
+
+#[signature(
+first,
+can_block = true,
+short = "Return the first row of the input as a struct",
+long = "Reading stops after the first row, so the rest of the input is never produced.",
+example = "ps | sort ^cpu | reverse | first")]
+pub struct First {
+    #[default(false)]
+    #[description("return empty instead of failing if the input has no rows.")]
+    allow_empty: bool,
+}
+
+#[signature(
+last,
+can_block = true,
+short = "Return the last row of the input as a struct",
+example = "ps | sort ^cpu | last")]
+pub struct Last {
+    #[default(false)]
+    #[description("return empty instead of failing if the input has no rows.")]
+    allow_empty: bool,
+}
+
+fn empty(output: ValueSender, allow_empty: bool) -> CrushResult<()> {
+    if allow_empty {
+        output.send(Value::Empty())
+    } else {
+        error("The input has no rows")
+    }
+}
+
+pub fn first(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: First = First::parse(context.arguments, &context.printer)?;
+    match context.input.recv()?.stream() {
+        Some(mut input) => match input.read() {
+            Ok(row) => context.output.send(Value::Struct(row.into_struct(input.types()))),
+            Err(_) => empty(context.output, cfg.allow_empty),
+        },
+        None => error("Expected a stream"),
+    }
+}
+
+pub fn last(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Last = Last::parse(context.arguments, &context.printer)?;
+    match context.input.recv()?.stream() {
+        Some(mut input) => {
+            let mut last = None;
+            while let Ok(row) = input.read() {
+                last = Some(row);
+            }
+            match last {
+                Some(row) => context.output.send(Value::Struct(row.into_struct(input.types()))),
+                None => empty(context.output, cfg.allow_empty),
+            }
+        }
+        None => error("Expected a stream"),
+    }
+}
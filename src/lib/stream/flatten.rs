@@ -0,0 +1,113 @@
+use crate::lang::execution_context::ExecutionContext;
+use crate::lang::errors::{CrushResult, error, argument_error};
+use crate::lang::{value::ValueType, table::Row, value::Value};
+use crate::lang::stream::{CrushStream, OutputStream};
+use crate::lang::table::{ColumnType, ColumnVec};
+use crate::lang::value::Field;
+use signature::signature;
+use crate::lang::argument::ArgumentHandler;
+
+#[signature(
+flatten,
+can_block = true,
+short = "Emit one row for every element of a column containing lists, tables or table streams",
+long = "The nested column is replaced by the element itself for lists, and by the columns of the",
+long = "nested rows for tables and table streams. The other columns of the row are repeated for",
+long = "every element. Nested columns with the same name as an outer column get a numeric suffix.",
+example = "ls | group ^user | flatten ^group")]
+pub struct Flatten {
+    #[description("the column to flatten. Not required if there is only one column containing nested values.")]
+    field: Option<Field>,
+}
+
+fn is_nested(t: &ValueType) -> bool {
+    matches!(t, ValueType::List(_) | ValueType::Table(_) | ValueType::TableStream(_))
+}
+
+fn send_nested(prefix: &[Value], cells: Vec<Value>, suffix: &[Value], output: &OutputStream) -> CrushResult<()> {
+    let mut out = prefix.to_vec();
+    out.extend(cells);
+    out.extend_from_slice(suffix);
+    output.send(Row::new(out))
+}
+
+fn flatten_row(idx: usize, row: Row, output: &OutputStream) -> CrushResult<()> {
+    let mut cells = row.into_vec();
+    let suffix = cells.split_off(idx + 1);
+    let nested = cells.pop().unwrap();
+    match nested {
+        Value::List(l) => {
+            for element in l.dump() {
+                send_nested(&cells, vec![element], &suffix, output)?;
+            }
+        }
+        Value::Table(t) => {
+            for r in t.rows() {
+                send_nested(&cells, r.clone().into_vec(), &suffix, output)?;
+            }
+        }
+        Value::TableStream(mut s) => {
+            while let Ok(r) = s.read() {
+                send_nested(&cells, r.into_vec(), &suffix, output)?;
+            }
+        }
+        v => return error(format!("Expected a list, a table or a table stream, got a value of type {}", v.value_type().to_string()).as_str()),
+    }
+    Ok(())
+}
+
+pub fn run(idx: usize, input: &mut dyn CrushStream, output: OutputStream) -> CrushResult<()> {
+    if let ValueType::TableStream(_) = input.types()[idx].cell_type {
+        // Nested streams, like the ones created by group, are usually not complete until the
+        // outer stream is, so read all outer rows before reading any nested stream.
+        let mut rows = Vec::new();
+        while let Ok(row) = input.read() {
+            rows.push(row);
+        }
+        for row in rows {
+            flatten_row(idx, row, &output)?;
+        }
+    } else {
+        while let Ok(row) = input.read() {
+            flatten_row(idx, row, &output)?;
+        }
+    }
+    Ok(())
+}
+
+pub fn flatten(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Flatten = Flatten::parse(context.arguments, &context.printer)?;
+    match context.input.recv()?.stream() {
+        Some(mut input) => {
+            let idx = match cfg.field {
+                Some(field) => input.types().find(&field)?,
+                None => {
+                    let nested = input.types().iter()
+                        .enumerate()
+                        .filter(|(_, t)| is_nested(&t.cell_type))
+                        .map(|(idx, _)| idx)
+                        .collect::<Vec<usize>>();
+                    if nested.len() != 1 {
+                        return argument_error("Missing column to flatten");
+                    }
+                    nested[0]
+                }
+            };
+            let nested_type = match &input.types()[idx].cell_type {
+                ValueType::List(element_type) =>
+                    vec![ColumnType::new(&input.types()[idx].name, element_type.as_ref().clone())],
+                ValueType::Table(types) | ValueType::TableStream(types) => types.clone(),
+                t => return argument_error(
+                    format!("Expected column {} to contain lists, tables or table streams, got {}",
+                            input.types()[idx].name, t.to_string()).as_str()),
+            };
+            let mut output_type = input.types()[..idx].to_vec();
+            output_type.extend(nested_type);
+            output_type.extend_from_slice(&input.types()[idx + 1..]);
+            ColumnType::deduplicate(&mut output_type);
+            let output = context.output.initialize(output_type)?;
+            run(idx, input.as_mut(), output)
+        }
+        None => error("Expected a stream"),
+    }
+}
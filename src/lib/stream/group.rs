@@ -14,13 +14,22 @@ use crate::lang::{table::ColumnType};
 use crate::lang::errors::{CrushResult, error};
 use crate::lang::stream::CrushStream;
 use crate::lang::table::ColumnVec;
+use crate::util::expiry::Expiry;
+use crate::lang::value::Collation;
+use super::expire::{self, Next};
+use super::sort;
+use std::time::{Duration, Instant};
 
 pub struct Config {
     name: String,
     column: usize,
+    ttl: Option<Duration>,
+    collation: Collation,
 }
 
-pub fn parse(input_type: &[ColumnType], arguments: Vec<Argument>) -> CrushResult<Config> {
+pub fn parse(input_type: &[ColumnType], mut arguments: Vec<Argument>) -> CrushResult<Config> {
+    let ttl = expire::ttl(&mut arguments)?;
+    let collation = sort::collation(&mut arguments)?;
     arguments.check_len(1)?;
     let arg = &arguments[0];
     let name = arg.argument_type.clone().unwrap_or_else(|| "group".to_string());
@@ -29,11 +38,15 @@ pub fn parse(input_type: &[ColumnType], arguments: Vec<Argument>) -> CrushResult
             Ok(Config {
                 column: input_type.find_str(cell_name)?,
                 name,
+                ttl,
+                collation,
             }),
         Value::Field(cell_name) =>
             Ok(Config {
                 column: input_type.find(cell_name)?,
                 name,
+                ttl,
+                collation,
             }),
         _ => argument_error("Bad comparison key"),
     }
@@ -48,12 +61,12 @@ pub fn run(
     let mut groups: HashMap<Value, OutputStream> = HashMap::new();
 
     while let Ok(row) = input.read() {
-        let key = row.cells()[config.column].clone();
+        let key = config.collation.key(&row.cells()[config.column]);
         let val = groups.get(&key);
         match val {
             None => {
                 let (output_stream, input_stream) = unlimited_streams(input_type.to_vec());
-                let out_row = Row::new(vec![key.clone(), Value::TableStream(input_stream)]);
+                let out_row = Row::new(vec![row.cells()[config.column].clone(), Value::TableStream(input_stream)]);
                 output.send(out_row)?;
                 let _ = output_stream.send(row);
                 groups.insert(key, output_stream);
@@ -66,6 +79,45 @@ pub fn run(
     Ok(())
 }
 
+/**
+Like run, but a group is closed once no row with its key has arrived within the ttl, and a later
+row with that key starts a new group.
+*/
+pub fn run_expiring(
+    config: Config,
+    ttl: Duration,
+    input_type: &[ColumnType],
+    input: &mut dyn CrushStream,
+    output: OutputStream,
+) -> CrushResult<()> {
+    let mut groups: Expiry<Value, OutputStream> = Expiry::new(ttl);
+    loop {
+        let row = match expire::next(&mut groups, input)? {
+            Next::Row(row) => row,
+            Next::Deadline => {
+                // Dropping the output streams ends the expired groups
+                groups.expire(Instant::now());
+                continue;
+            }
+            Next::End => break,
+        };
+        let now = Instant::now();
+        groups.expire(now);
+        let key = config.collation.key(&row.cells()[config.column]);
+        let output_stream = match groups.remove(&key) {
+            Some(output_stream) => output_stream,
+            None => {
+                let (output_stream, input_stream) = unlimited_streams(input_type.to_vec());
+                output.send(Row::new(vec![row.cells()[config.column].clone(), Value::TableStream(input_stream)]))?;
+                output_stream
+            }
+        };
+        let _ = output_stream.send(row);
+        groups.touch(key, output_stream, now);
+    }
+    Ok(())
+}
+
 pub fn perform(context: ExecutionContext) -> CrushResult<()> {
     match context.input.recv()?.stream() {
         Some(mut input) => {
@@ -77,7 +129,11 @@ pub fn perform(context: ExecutionContext) -> CrushResult<()> {
                     ValueType::TableStream(input.types().to_vec()))
             ];
             let output = context.output.initialize(output_type)?;
-            run(config, &input.types().to_vec(), input.as_mut(), output)
+            let input_type = input.types().to_vec();
+            match config.ttl {
+                None => run(config, &input_type, input.as_mut(), output),
+                Some(ttl) => run_expiring(config, ttl, &input_type, input.as_mut(), output),
+            }
         }
         None => error("Expected a stream"),
     }
@@ -4,7 +4,7 @@ use std::collections::HashMap;
 use crate::lang::stream::CrushStream;
 use crate::lang::errors::CrushError;
 use crate::lang::table::Row;
-use crate::lang::table::ColumnType;
+use crate::lang::table::{ColumnType, ColumnMapping};
 use crate::lang::value::ValueType;
 use crate::lang::value::Value;
 use crate::lang::stream::OutputStream;
@@ -20,6 +20,8 @@ pub struct Config {
     right_table_idx: usize,
     left_column_idx: usize,
     right_column_idx: usize,
+    /** The type both join columns are widened to, e.g. float when joining integers with floats. */
+    key_type: ValueType,
 }
 
 pub fn get_sub_type(cell_type: &ValueType) -> Result<&[ColumnType], CrushError> {
@@ -54,16 +56,11 @@ fn parse(input_type: &[ColumnType], arguments: Vec<Argument>) -> Result<Config,
 
     match (&arguments[0].value, &arguments[1].value) {
         (Value::Field(l), Value::Field(r)) => {
-            let config = match (l.len(), r.len()) {
+            let (left_table_idx, right_table_idx, left_column_idx, right_column_idx) = match (l.len(), r.len()) {
                 (1, 1) => {
                     let (left_table_idx, right_table_idx, left_types, right_types) = guess_tables(&input_type)?;
 
-                    Config {
-                        left_table_idx,
-                        right_table_idx,
-                        left_column_idx: left_types.find(&l)?,
-                        right_column_idx: right_types.find(&r)?,
-                    }
+                    (left_table_idx, right_table_idx, left_types.find(&l)?, right_types.find(&r)?)
                 }
                 (2, 2) => {
                     let (left_table_idx, left_column_idx) =
@@ -76,25 +73,23 @@ fn parse(input_type: &[ColumnType], arguments: Vec<Argument>) -> Result<Config,
                         return argument_error("Left and right table can't be the same");
                     }
 
-                    Config {
-                        left_table_idx,
-                        right_table_idx,
-                        left_column_idx,
-                        right_column_idx,
-                    }
+                    (left_table_idx, right_table_idx, left_column_idx, right_column_idx)
                 }
                 _ => return argument_error("Expected both fields on the form %table.column or %column"),
             };
 
-            let r_type = &get_sub_type(&input_type[config.right_table_idx].cell_type)?[config.right_column_idx].cell_type;
-            let l_type = &get_sub_type(&input_type[config.left_table_idx].cell_type)?[config.left_column_idx].cell_type;
-            if r_type != l_type {
-                return argument_error("Cannot join two columns of different types");
-            }
-            if !r_type.is_hashable() {
+            let r_type = &get_sub_type(&input_type[right_table_idx].cell_type)?[right_column_idx].cell_type;
+            let l_type = &get_sub_type(&input_type[left_table_idx].cell_type)?[left_column_idx].cell_type;
+            let key_type = match ColumnType::widen(l_type, r_type) {
+                Some(key_type) => key_type,
+                None => return argument_error(format!(
+                    "Cannot join two columns of different types, left column is of type {}, right column is of type {}",
+                    l_type.to_string(), r_type.to_string()).as_str()),
+            };
+            if !key_type.is_hashable() {
                 argument_error("Cannot join on this column type. (It is either mutable or not comparable)")
             } else {
-                Ok(config)
+                Ok(Config { left_table_idx, right_table_idx, left_column_idx, right_column_idx, key_type })
             }
         }
         _ => argument_error("Expected arguments like %table1.col == %table2.col"),
@@ -110,13 +105,24 @@ fn combine(mut l: Row, r: Row, cfg: &Config) -> Row {
     l
 }
 
+/** The columns of a table, with the join column widened to the type of the key. */
+fn widened(types: &[ColumnType], column_idx: usize, key_type: &ValueType) -> Vec<ColumnType> {
+    let mut res = types.to_vec();
+    res[column_idx].cell_type = key_type.clone();
+    res
+}
+
 fn do_join(cfg: &Config, l: &mut dyn CrushStream, r: &mut dyn CrushStream, output: &OutputStream, printer: &Printer) -> CrushResult<()> {
+    let l_mapping = ColumnMapping::new(l.types(), &widened(l.types(), cfg.left_column_idx, &cfg.key_type))?;
+    let r_mapping = ColumnMapping::new(r.types(), &widened(r.types(), cfg.right_column_idx, &cfg.key_type))?;
     let mut l_data: HashMap<Value, Row> = HashMap::new();
     while let Ok(row) = l.read() {
+        let row = l_mapping.map(row)?;
         l_data.insert(row.cells()[cfg.left_column_idx].clone(), row);
     }
 
     while let Ok(r_row) = r.read() {
+        let r_row = r_mapping.map(r_row)?;
         l_data.remove(&r_row.cells()[cfg.right_column_idx])
             .map(|l_row| {
                 printer.handle_error(output.send(combine(l_row, r_row, cfg)));
@@ -149,12 +155,13 @@ fn get_output_type(input_type: &[ColumnType], cfg: &Config) -> Result<Vec<Column
 
     match (tables[cfg.left_table_idx], tables[cfg.right_table_idx]) {
         (Some(v1), Some(v2)) => {
-            let mut res = v1.clone();
+            let mut res = widened(v1, cfg.left_column_idx, &cfg.key_type);
             for (idx, c) in v2.iter().enumerate() {
                 if idx != cfg.right_column_idx {
                     res.push(c.clone());
                 }
             }
+            ColumnType::deduplicate(&mut res);
             Ok(res)
         }
         _ => argument_error("Impossible error?"),
@@ -0,0 +1,123 @@
+use std::cmp::Ordering;
+
+use crate::lang::argument::ArgumentHandler;
+use crate::lang::command::OutputType::Unknown;
+use crate::lang::errors::{CrushResult, argument_error, data_error, error};
+use crate::lang::execution_context::ExecutionContext;
+use crate::lang::table::{ColumnVec, Row};
+use crate::lang::value::{Collation, Field, Value};
+use signature::signature;
+
+#[signature(
+lookup,
+can_block = false,
+output = Unknown,
+short = "Find the row of a table with a key, using a binary search",
+long = "The table must be sorted on the key column, using the same collation as the lookup. The row is",
+long = "returned as a struct, or the empty value if no row has the key. If several rows have the key,",
+long = "the first one is returned. Finding a row takes time proportional to the logarithm of the size of",
+long = "the table, so this is fast even for large reference tables that are looked up often.",
+long = "",
+long = "If the table is not sorted, the result is unspecified. Use verify to check that it is, which",
+long = "takes time proportional to the size of the table.",
+example = "lookup (csv:from hosts.csv name=string owner=string | sort ^name | materialize) ^name \"db1\"")]
+pub struct Lookup {
+    #[description("the table to search, sorted on the key column.")]
+    table: Option<Value>,
+    #[description("the key column.")]
+    key: Field,
+    #[description("the key to search for.")]
+    search: Option<Value>,
+    #[default("binary")]
+    #[description("the collation the table is sorted with, as used by sort.")]
+    collate: String,
+    #[default(false)]
+    #[description("check that the table is sorted on the key column.")]
+    verify: bool,
+}
+
+fn compare(collation: Collation, a: &Value, b: &Value) -> CrushResult<Ordering> {
+    match collation.compare(a, b) {
+        Some(ordering) => Ok(ordering),
+        None => argument_error(format!(
+            "Can't compare a key of type {} with a key of type {}",
+            a.value_type().to_string(), b.value_type().to_string()).as_str()),
+    }
+}
+
+/** Returns the first row with the key in the key column, given rows sorted on that column. */
+pub fn search<'a>(rows: &'a [Row], idx: usize, collation: Collation, key: &Value) -> CrushResult<Option<&'a Row>> {
+    let mut low = 0;
+    let mut high = rows.len();
+    while low < high {
+        let mid = low + (high - low) / 2;
+        match compare(collation, &rows[mid].cells()[idx], key)? {
+            Ordering::Less => low = mid + 1,
+            _ => high = mid,
+        }
+    }
+    match rows.get(low) {
+        Some(row) if compare(collation, &row.cells()[idx], key)? == Ordering::Equal => Ok(Some(row)),
+        _ => Ok(None),
+    }
+}
+
+fn verify(rows: &[Row], idx: usize, collation: Collation) -> CrushResult<()> {
+    for (pos, pair) in rows.windows(2).enumerate() {
+        if compare(collation, &pair[0].cells()[idx], &pair[1].cells()[idx])? == Ordering::Greater {
+            return data_error(format!("The table is not sorted on the key column at row {}", pos + 1).as_str());
+        }
+    }
+    Ok(())
+}
+
+pub fn lookup(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Lookup = Lookup::parse(context.arguments, &context.printer)?;
+    let collation = Collation::parse(&cfg.collate)?;
+    let table = match cfg.table {
+        Some(Value::Table(table)) => table,
+        Some(v) => return argument_error(format!(
+            "Expected a table, got a value of type {}. Use materialize to turn a stream into a table.",
+            v.value_type().to_string()).as_str()),
+        None => return error("Expected a table"),
+    };
+    let key = match cfg.search {
+        Some(key) => key,
+        None => return argument_error("Expected a key to search for"),
+    };
+    let idx = table.types().find(&cfg.key)?;
+    if cfg.verify {
+        verify(table.rows(), idx, collation)?;
+    }
+    match search(table.rows(), idx, collation, &key)? {
+        Some(row) => context.output.send(Value::Struct(row.clone().into_struct(table.types()))),
+        None => context.output.send(Value::Empty()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn binary_search() {
+        let rows: Vec<Row> = [1, 3, 3, 5, 8]
+            .iter()
+            .enumerate()
+            .map(|(pos, k)| Row::new(vec![Value::Integer(*k), Value::Integer(pos as i128)]))
+            .collect();
+        let find = |k: i128| search(&rows, 0, Collation::Binary, &Value::Integer(k)).unwrap()
+            .map(|row| row.cells()[1].clone());
+        assert!(find(1) == Some(Value::Integer(0)));
+        assert!(find(3) == Some(Value::Integer(1)));
+        assert!(find(8) == Some(Value::Integer(4)));
+        assert!(find(0).is_none());
+        assert!(find(4).is_none());
+        assert!(find(9).is_none());
+        assert!(search(&rows, 0, Collation::Binary, &Value::string("3")).is_err());
+        assert!(verify(&rows, 0, Collation::Binary).is_ok());
+        assert!(verify(&rows, 1, Collation::Binary).is_ok());
+        let reversed: Vec<Row> = rows.into_iter().rev().collect();
+        assert!(verify(&reversed, 0, Collation::Binary).is_err());
+    }
+}
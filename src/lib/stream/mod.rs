@@ -8,6 +8,7 @@ mod head;
 mod tail;
 mod r#where;
 mod sort;
+mod lookup;
 mod reverse;
 
 mod select;
@@ -17,10 +18,22 @@ mod uniq;
 mod group;
 mod join;
 mod zip;
+mod union;
+mod sample;
+mod rename;
+mod pmap;
+mod first_last;
+mod flatten;
+mod chunk;
+mod top;
+mod throttle;
+mod debounce;
+mod expire;
+mod replay;
 //mod aggr;
 
 mod count;
-mod sum_avg;
+mod aggregation;
 mod seq;
 
 pub fn declare(root: &Scope) -> CrushResult<()> {
@@ -34,24 +47,45 @@ pub fn declare(root: &Scope) -> CrushResult<()> {
                 "tail", tail::perform, true,
                 "tail [lines:integer]", "Return the last lines of the io. Defaults to 10.", None, Passthrough)?;
             r#where::Where::declare(env)?;
+            replay::Replay::declare(env)?;
             sort::Sort::declare(env)?;
+            lookup::Lookup::declare(env)?;
             env.declare_command(
                 "reverse", reverse::reverse, true,
                 "reverse", "Reverses the order of the rows in the io", None,
                 Passthrough)?;
             env.declare_command(
                 "group", group::perform, true,
-                "group group=field|string", "Group io by the specified column", None,
+                "group group=field|string [ttl=duration] [collate=string]", "Group io by the specified column",
+                Some(r#"    Values that are equal under the collation, one of binary, natural, locale and
+    case_insensitive as used by sort, go in the same group. The default is binary.
+
+    With a ttl, a group ends once no row with its key has arrived for that long, and a
+    later row with the same key starts a new group. This makes group usable on endless streams.
+
+    Example:
+
+    watch ./src | group ^file ttl=1s"#),
                 Unknown)?;
             env.declare_command(
                 "join", join::perform, true,
-                "join left:field right:field", "Join two streams together on the specified keys", None,
+                "join left:field right:field", "Join two streams together on the specified keys",
+                Some(r#"    An integer column can be joined with a float column, the keys are then compared
+    as floats."#),
                 Unknown)?;
             env.declare_command(
                 "uniq", uniq::uniq, true,
-                "uniq column:field",
+                "uniq column:field [ttl=duration] [collate=string]",
                 "Only output the first row if multiple rows has the same value for the specified column",
-                example!("ps | uniq ^user"),
+                Some(r#"    Values that are equal under the collation, one of binary, natural, locale and
+    case_insensitive as used by sort, count as the same. The default is binary.
+
+    With a ttl, a value is forgotten once no row with it has arrived for that long, so
+    that only the keys of recent rows are remembered. This makes uniq usable on endless streams.
+
+    Example:
+
+    ps | uniq ^user"#),
                 Passthrough)?;
             //env.declare_str("aggr", Value::Command(CrushCommand::command_undocumented(aggr::perform)))?;
             env.declare_command(
@@ -59,35 +93,74 @@ pub fn declare(root: &Scope) -> CrushResult<()> {
                 "count",
                 "Count the number of rows in the io", example!("ps | count"), Known(ValueType::Integer))?;
             env.declare_command(
-                "sum", sum_avg::sum, true,
-                "sum column:field",
+                "sum", aggregation::sum, true,
+                "sum [column:field...]",
                 "Calculate the sum for the specific column across all rows",
                 example!("ps | sum ^cpu"), Unknown)?;
             env.declare_command(
-                "min", sum_avg::min, true,
-                "min [column:field]",
+                "min", aggregation::min, true,
+                "min [column:field...]",
                 "Find the minimum value of the specific column across all rows",
                 example!("ps | min ^cpu"), Unknown)?;
             env.declare_command(
-                "max", sum_avg::max, true,
-                "max [column:field]",
+                "max", aggregation::max, true,
+                "max [column:field...]",
                 "Find the maximum value of the specific column across all rows",
                 example!("ps | max ^cpu"), Unknown)?;
             env.declare_command(
-                "avg", sum_avg::avg, true,
-                "avg column:field",
+                "avg", aggregation::avg, true,
+                "avg [column:field...]",
                 "Calculate the average of the specific column across all rows",
                 example!("ps | avg ^cpu"), Unknown)?;
+            env.declare_command(
+                "median", aggregation::median, true,
+                "median [column:field...] [approximate:bool]",
+                "Calculate the median of the specific column across all rows",
+                Some(r#"    If approximate is true, the median is estimated in constant memory using
+    the P² algorithm instead of keeping all values in memory.
+
+    Example:
+
+    ps | median ^cpu"#), Unknown)?;
+            env.declare_command(
+                "stddev", aggregation::stddev, true,
+                "stddev [column:field...]",
+                "Calculate the standard deviation of the specific column across all rows",
+                example!("ps | stddev ^cpu"), Unknown)?;
             env.declare_command(
                 "select", select::select, true,
-                "select copy_fields:field... [%] new_field=definition:command",
+                "select copy_fields:field... [%] new_field=definition:(command|field)",
                 "Pass on some old fields and calculate new ones for each line of io",
-                example!(r#"ls | select ^user path={"{}/{}":format (pwd) file}"#), Unknown)?;
-            env.declare_command(
-                "enumerate", enumerate::perform, true,
-                "enumerate", "Prepend a column containing the row number to each row of the io", None, Unknown)?;
+                Some(r#"    Columns are output in the order they are given. A named field argument
+    copies the column with that field name under a new name. If % is given,
+    all columns of the input are kept, and named arguments that use the name
+    of an existing column replace it.
+
+    Examples:
+
+    ls | select ^user path={"{}/{}":format (pwd) file}
+    ps | select ^pid owner=^user ^command"#), Unknown)?;
+            enumerate::Enumerate::declare(env)?;
             zip::Zip::declare(env)?;
+            union::Union::declare(env)?;
+            sample::Sample::declare(env)?;
+            sample::Shuffle::declare(env)?;
+            rename::Rename::declare(env)?;
+            rename::Header::declare(env)?;
+            pmap::Pmap::declare(env)?;
+            first_last::First::declare(env)?;
+            first_last::Last::declare(env)?;
             seq::Seq::declare(env)?;
+            seq::Range::declare(env)?;
+            flatten::Flatten::declare(env)?;
+            chunk::Chunk::declare(env)?;
+            top::Top::declare(env)?;
+            throttle::Throttle::declare(env)?;
+            throttle::Delay::declare(env)?;
+            debounce::Debounce::declare(env)?;
+            debounce::Changes::declare(env)?;
+            expire::Expire::declare(env)?;
+            crate::lib::render::plot::Plot::declare(env)?;
             Ok(())
         }))?;
     root.r#use(&e);
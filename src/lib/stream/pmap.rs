@@ -0,0 +1,142 @@
+use crate::lang::execution_context::ExecutionContext;
+use crate::lang::errors::{CrushResult, error, argument_error, to_crush_error};
+use crate::lang::stream::{empty_channel, channels, black_hole, OutputStream};
+use crate::lang::table::{ColumnType, Row};
+use crate::lang::argument::Argument;
+use crate::lang::command::Command;
+use crate::lang::value::{Value, ValueType};
+use crate::lang::printer::Printer;
+use crate::util::thread::build;
+use crossbeam::{bounded, unbounded, Receiver};
+use std::collections::BTreeMap;
+use signature::signature;
+use crate::lang::argument::ArgumentHandler;
+
+#[signature(
+pmap,
+can_block = true,
+short = "Apply a closure to each row of the input using multiple threads",
+long = "The columns of the row are exported to the environment of the closure using the column names.\n    The output is a stream with a single column named value of type any, containing the result of the\n    closure.\n    If ordered is false, results are emitted as soon as they are done, which may be in any order.",
+example = "seq 10 | pmap parallelism=4 {value * value}")]
+pub struct Pmap {
+    #[description("the closure to apply to each row.")]
+    closure: Command,
+    #[default(4usize)]
+    #[description("the number of worker threads to use.")]
+    parallelism: usize,
+    #[default(true)]
+    #[description("emit results in the same order as the input.")]
+    ordered: bool,
+}
+
+fn evaluate(closure: &Command, row: Row, input_type: &[ColumnType], base_context: &ExecutionContext) -> CrushResult<Value> {
+    let arguments = row.into_vec()
+        .drain(..)
+        .zip(input_type.iter())
+        .map(|(c, t)| Argument::named(t.name.as_ref(), c))
+        .collect();
+    let (sender, receiver) = channels();
+    closure.invoke(base_context.clone().with_args(arguments, None).with_sender(sender))?;
+    receiver.recv()
+}
+
+/**
+    Receive results from the workers and pass them on. Rows where the closure
+    failed are reported as None, so that ordered output does not wait for
+    them forever.
+*/
+fn collect(results: Receiver<(usize, Option<Value>)>, ordered: bool, output: OutputStream, printer: &Printer) -> CrushResult<()> {
+    let mut pending = BTreeMap::new();
+    let mut next = 0usize;
+    while let Ok((idx, value)) = results.recv() {
+        let ready = if ordered {
+            pending.insert(idx, value);
+            let mut ready = Vec::new();
+            while let Some(value) = pending.remove(&next) {
+                ready.push(value);
+                next += 1;
+            }
+            ready
+        } else {
+            vec![value]
+        };
+        for value in ready.into_iter().flatten() {
+            printer.handle_error(output.send(Row::new(vec![value])));
+        }
+    }
+    Ok(())
+}
+
+pub fn pmap(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Pmap = Pmap::parse(context.arguments, &context.printer)?;
+    if cfg.parallelism == 0 {
+        return argument_error("Parallelism must be at least 1");
+    }
+    match context.input.recv()?.stream() {
+        Some(mut input) => {
+            let input_type = input.types().to_vec();
+            let base_context = ExecutionContext {
+                input: empty_channel(),
+                output: black_hole(),
+                arguments: vec![],
+                env: context.env.clone(),
+                this: None,
+                printer: context.printer.clone(),
+            };
+
+            let (job_sender, job_receiver) = bounded::<(usize, Row)>(cfg.parallelism * 2);
+            let (result_sender, result_receiver) = unbounded::<(usize, Option<Value>)>();
+            let mut workers = Vec::new();
+            for _ in 0..cfg.parallelism {
+                let jobs = job_receiver.clone();
+                let results = result_sender.clone();
+                let closure = cfg.closure.clone();
+                let input_type = input_type.clone();
+                let base_context = base_context.clone();
+                workers.push(to_crush_error(build("pmap:worker").spawn(move || {
+                    while let Ok((idx, row)) = jobs.recv() {
+                        let value = match evaluate(&closure, row, &input_type, &base_context) {
+                            Ok(value) => Some(value),
+                            Err(e) => {
+                                base_context.printer.crush_error(e);
+                                None
+                            }
+                        };
+                        if results.send((idx, value)).is_err() {
+                            break;
+                        }
+                    }
+                }))?);
+            }
+            drop(result_sender);
+
+            let ordered = cfg.ordered;
+            /* The closure may return values of any type, and there may be no results at all. */
+            let sender = context.output.initialize(vec![ColumnType::new("value", ValueType::Any)])?;
+            let printer = context.printer.clone();
+            let collector = to_crush_error(build("pmap:collector").spawn(move || {
+                printer.handle_error(collect(result_receiver, ordered, sender, &printer));
+            }))?;
+
+            let mut idx = 0usize;
+            while let Ok(row) = input.read() {
+                if job_sender.send((idx, row)).is_err() {
+                    break;
+                }
+                idx += 1;
+            }
+            drop(job_sender);
+
+            for worker in workers {
+                if worker.join().is_err() {
+                    return error("pmap worker thread panicked");
+                }
+            }
+            if collector.join().is_err() {
+                return error("pmap collector thread panicked");
+            }
+            Ok(())
+        }
+        None => error("Expected a stream"),
+    }
+}
@@ -0,0 +1,72 @@
+use crate::lang::execution_context::ExecutionContext;
+use crate::lang::errors::{CrushResult, error, argument_error};
+use crate::lang::table::{ColumnType, ColumnVec};
+use crate::lang::stream::{Stream, ValueSender};
+use crate::lang::ordered_string_map::OrderedStringMap;
+use signature::signature;
+use crate::lang::argument::ArgumentHandler;
+
+#[signature(
+rename,
+can_block = true,
+short = "Rename some of the columns of the input",
+long = "Each named argument maps the current name of a column to its new name.",
+example = "ps | rename pid=\"process_id\"")]
+pub struct Rename {
+    #[named()]
+    #[description("current and new name of each column to rename.")]
+    columns: OrderedStringMap<String>,
+}
+
+#[signature(
+header,
+can_block = true,
+short = "Replace the names of all columns of the input",
+example = "csv:from data.csv a=string b=integer | header \"name\" \"age\"")]
+pub struct Header {
+    #[unnamed()]
+    #[description("the new column names, one per column.")]
+    names: Vec<String>,
+}
+
+fn run(mut input: Stream, output_type: Vec<ColumnType>, sender: ValueSender) -> CrushResult<()> {
+    let output = sender.initialize(output_type)?;
+    while let Ok(row) = input.read() {
+        output.send(row)?;
+    }
+    Ok(())
+}
+
+pub fn rename(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Rename = Rename::parse(context.arguments, &context.printer)?;
+    match context.input.recv()?.stream() {
+        Some(input) => {
+            let mut output_type = input.types().to_vec();
+            for (old_name, new_name) in cfg.columns.iter() {
+                let idx = input.types().find_str(old_name)?;
+                output_type[idx].name = new_name.clone();
+            }
+            run(input, output_type, context.output)
+        }
+        None => error("Expected a stream"),
+    }
+}
+
+pub fn header(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Header = Header::parse(context.arguments, &context.printer)?;
+    match context.input.recv()?.stream() {
+        Some(input) => {
+            if cfg.names.len() != input.types().len() {
+                return argument_error(format!(
+                    "Expected {} column names, got {}",
+                    input.types().len(), cfg.names.len()).as_str());
+            }
+            let output_type = input.types().iter()
+                .zip(cfg.names.iter())
+                .map(|(t, name)| ColumnType::new(name, t.cell_type.clone()))
+                .collect();
+            run(input, output_type, context.output)
+        }
+        None => error("Expected a stream"),
+    }
+}
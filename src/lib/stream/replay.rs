@@ -0,0 +1,28 @@
+use crate::lang::execution_context::ExecutionContext;
+use crate::lang::errors::{CrushResult, argument_error};
+use crate::lang::value::Value;
+use crate::lang::command::OutputType::Passthrough;
+use signature::signature;
+use crate::lang::argument::ArgumentHandler;
+
+#[signature(
+replay,
+can_block = false,
+output = Passthrough,
+short = "Pass on the input as a stream that can be read any number of times",
+long = "Every use of the output reads all of its rows from the start, so one stream can be fed into",
+long = "several commands, even at the same time, without running the commands producing it again.",
+long = "Rows are read as they are needed and kept for later readers, in memory for the first ten",
+long = "thousand rows and in a temporary file after that.",
+example = "files := (find ./src | replay); data size=(files | sum ^size) count=(files | count)")]
+pub struct Replay {}
+
+pub fn replay(context: ExecutionContext) -> CrushResult<()> {
+    Replay::parse(context.arguments, &context.printer)?;
+    match context.input.recv()? {
+        Value::TableStream(s) => context.output.send(Value::TableStream(s.replayable()?)),
+        Value::Table(t) => context.output.send(Value::Table(t)),
+        v => argument_error(
+            format!("Expected a table stream, got a value of type {}", v.value_type().to_string()).as_str()),
+    }
+}
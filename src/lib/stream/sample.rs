@@ -0,0 +1,97 @@
+use crate::lang::execution_context::ExecutionContext;
+use crate::lang::errors::{CrushResult, error, argument_error};
+use crate::lang::table::Row;
+use signature::signature;
+use crate::lang::argument::ArgumentHandler;
+use crate::lang::command::OutputType::Passthrough;
+use rand::Rng;
+use rand::seq::SliceRandom;
+
+#[signature(
+sample,
+can_block = true,
+output = Passthrough,
+short = "Return a random subset of the rows of the input",
+long = "If a count is given, exactly that many rows are picked (or all rows if the input is shorter),\n    using reservoir sampling so that the input may be arbitrarily large. If a fraction is given,\n    each row is passed on with that probability.",
+example = "ps | sample 5")]
+pub struct Sample {
+    #[description("the number of rows to pick.")]
+    count: Option<i128>,
+    #[description("the probability of passing on each row.")]
+    fraction: Option<f64>,
+}
+
+pub fn sample(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Sample = Sample::parse(context.arguments, &context.printer)?;
+    match context.input.recv()?.stream() {
+        Some(mut input) => {
+            let output = context.output.initialize(input.types().to_vec())?;
+            let mut rng = rand::thread_rng();
+            match (cfg.count, cfg.fraction) {
+                (Some(count), None) => {
+                    if count < 0 {
+                        return argument_error("Count must be non-negative");
+                    }
+                    let count = count as usize;
+                    let mut reservoir: Vec<Row> = Vec::with_capacity(count.min(1024));
+                    let mut seen = 0usize;
+                    while let Ok(row) = input.read() {
+                        seen += 1;
+                        if reservoir.len() < count {
+                            reservoir.push(row);
+                        } else {
+                            let idx = rng.gen_range(0, seen);
+                            if idx < count {
+                                reservoir[idx] = row;
+                            }
+                        }
+                    }
+                    for row in reservoir {
+                        output.send(row)?;
+                    }
+                    Ok(())
+                }
+                (None, Some(fraction)) => {
+                    if !(0.0..=1.0).contains(&fraction) {
+                        return argument_error("Fraction must be between 0 and 1");
+                    }
+                    while let Ok(row) = input.read() {
+                        if rng.gen::<f64>() < fraction {
+                            output.send(row)?;
+                        }
+                    }
+                    Ok(())
+                }
+                _ => argument_error("Expected exactly one of count and fraction"),
+            }
+        }
+        None => error("Expected a stream"),
+    }
+}
+
+#[signature(
+shuffle,
+can_block = true,
+output = Passthrough,
+short = "Return the rows of the input in random order",
+example = "seq 10 | shuffle")]
+pub struct Shuffle {}
+
+pub fn shuffle(context: ExecutionContext) -> CrushResult<()> {
+    Shuffle::parse(context.arguments, &context.printer)?;
+    match context.input.recv()?.stream() {
+        Some(mut input) => {
+            let output = context.output.initialize(input.types().to_vec())?;
+            let mut rows: Vec<Row> = Vec::new();
+            while let Ok(row) = input.read() {
+                rows.push(row);
+            }
+            rows.shuffle(&mut rand::thread_rng());
+            for row in rows {
+                output.send(row)?;
+            }
+            Ok(())
+        }
+        None => error("Expected a stream"),
+    }
+}
@@ -165,6 +165,13 @@ pub fn select(mut context: ExecutionContext) -> CrushResult<()> {
                             _ => columns.push((Location::Append(name.to_string()), Source::Closure(closure))),
                         }
                     }
+                    (Some(name), Value::Field(field)) => {
+                        let source = Source::Argument(input_type.find(&field)?);
+                        match (copy, input_type.find_str(name)) {
+                            (true, Ok(idx)) => columns.push((Location::Replace(idx), source)),
+                            _ => columns.push((Location::Append(name.to_string()), source)),
+                        }
+                    }
                     (None, Value::Field(name)) => {
                         if name.len() != 1 {
                             return argument_error("Invalid field");
@@ -1,5 +1,5 @@
 use crate::lang::execution_context::{ExecutionContext};
-use crate::lang::errors::{CrushResult};
+use crate::lang::errors::{CrushResult, argument_error};
 use crate::{
     lang::{
         table::Row,
@@ -10,6 +10,8 @@ use crate::{
 use crate::lang::table::ColumnType;
 use signature::signature;
 use crate::lang::argument::ArgumentHandler;
+use crate::lang::value::Time;
+use chrono::Duration;
 
 #[signature(seq, short="Return a stream of sequential numbers")]
 #[derive(Debug)]
@@ -24,6 +26,9 @@ pub struct Seq {
 
 pub fn seq(context: ExecutionContext) -> CrushResult<()> {
     let mut cfg: Seq = Seq::parse(context.arguments, &context.printer)?;
+    if cfg.step == 0 {
+        return argument_error("Step must be non-zero");
+    }
     let output = context.output.initialize(vec![
         ColumnType::new("value", ValueType::Integer)])?;
 
@@ -47,3 +52,34 @@ pub fn seq(context: ExecutionContext) -> CrushResult<()> {
     }
     Ok(())
 }
+
+#[signature(
+range,
+short = "Return a stream of points in time, starting at from and separated by step",
+long = "The stream stops before reaching to. A negative step counts backwards in time.",
+example = "range from=(time:now) to=(time:now) + 7d step=1d")]
+pub struct Range {
+    #[description("the first point in time.")]
+    from: Time,
+    #[description("the point in time to stop at. It is not included in the output.")]
+    to: Time,
+    #[description("the time between two points.")]
+    step: Duration,
+}
+
+pub fn range(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Range = Range::parse(context.arguments, &context.printer)?;
+    let forward = cfg.step > Duration::zero();
+    if cfg.step == Duration::zero() {
+        return argument_error("Step must be non-zero");
+    }
+    let output = context.output.initialize(vec![
+        ColumnType::new("value", ValueType::Time)])?;
+
+    let mut time = cfg.from;
+    while (forward && time < cfg.to) || (!forward && time > cfg.to) {
+        output.send(Row::new(vec![Value::Time(time)]))?;
+        time = time + cfg.step;
+    }
+    Ok(())
+}
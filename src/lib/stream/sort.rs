@@ -8,8 +8,8 @@ use crate::lang::errors::{CrushResult, error};
 use crate::lang::stream::CrushStream;
 use crate::lang::table::ColumnVec;
 use signature::signature;
-use crate::lang::argument::ArgumentHandler;
-use crate::lang::value::Field;
+use crate::lang::argument::{Argument, ArgumentHandler};
+use crate::lang::value::{Collation, Field, Value};
 use crate::lang::command::OutputType::Passthrough;
 
 #[signature(
@@ -17,21 +17,43 @@ use crate::lang::command::OutputType::Passthrough;
     can_block=true,
     short="Sort io based on column",
     long="ps | sort ^cpu",
+    long="",
+    long="The collation decides how text is ordered. binary orders by character, natural orders runs of",
+    long="digits by their numeric value so that file2 comes before file10, locale orders by the rules of",
+    long="the locale of the user and case_insensitive orders by character ignoring case.",
+    example="files | sort ^file collate=natural",
     output=Passthrough)]
 pub struct Sort {
     #[description("the column to sort on. Not required if there is only one column.")]
     field: Option<Field>,
+    #[default("binary")]
+    #[description("how to order text, one of binary, natural, locale and case_insensitive.")]
+    collate: String,
 }
 
-pub fn run(idx: usize, input: &mut dyn CrushStream, output: OutputStream) -> CrushResult<()> {
+/**
+Remove the collate argument of a stream command that compares values, like uniq and group, and
+return the collation it names. The default is binary.
+*/
+pub fn collation(arguments: &mut Vec<Argument>) -> CrushResult<Collation> {
+    match arguments.iter().position(|a| a.argument_type.as_deref() == Some("collate")) {
+        None => Ok(Collation::Binary),
+        Some(idx) => match arguments.remove(idx).value {
+            Value::String(name) => Collation::parse(&name),
+            value => argument_error(format!("Expected the collation to be a string, got a {}", value.value_type().to_string()).as_str()),
+        },
+    }
+}
+
+pub fn run(idx: usize, collation: Collation, input: &mut dyn CrushStream, output: OutputStream) -> CrushResult<()> {
     let mut res: Vec<Row> = Vec::new();
     while let Ok(row) = input.read() {
         res.push(row);
     }
 
     res.sort_by(|a, b|
-        a.cells()[idx]
-            .partial_cmp(&b.cells()[idx])
+        collation
+            .compare(&a.cells()[idx], &b.cells()[idx])
             .expect("OH NO!"));
 
     for row in res {
@@ -46,13 +68,14 @@ pub fn sort(context: ExecutionContext) -> CrushResult<()> {
         Some(mut input) => {
             let output = context.output.initialize(input.types().to_vec())?;
             let cfg: Sort = Sort::parse(context.arguments, &context.printer)?;
+            let collation = Collation::parse(&cfg.collate)?;
             let idx = match cfg.field {
                 None => if input.types().len() == 1 {0} else {return argument_error("Missing comparison key"); },
                 Some(field) => input.types().find(&field)?,
             };
 
             if input.types()[idx].cell_type.is_comparable() {
-                run(idx, input.as_mut(), output)
+                run(idx, collation, input.as_mut(), output)
             } else {
                 argument_error("Bad comparison key")
             }
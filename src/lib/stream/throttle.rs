@@ -0,0 +1,88 @@
+use std::collections::VecDeque;
+use std::thread;
+use std::time::Instant;
+
+use chrono::Duration;
+
+use crate::lang::argument::ArgumentHandler;
+use crate::lang::errors::{CrushResult, argument_error, error, to_crush_error};
+use crate::lang::execution_context::ExecutionContext;
+use crate::lang::stream::{CrushStream, OutputStream};
+use signature::signature;
+
+#[signature(
+throttle,
+can_block = true,
+short = "Limit how many rows of the input are passed on per period of time",
+long = "Rows are passed on unchanged, but no more than the given number of rows within any period",
+long = "of the given length. Rows that would exceed the limit are held back until they are allowed,",
+long = "so the rows that come after them are too. Use this before commands that call rate limited",
+long = "services, like sending notifications.",
+example = "for (lines:from ./urls.txt | throttle rows=5 per=1s) {http line}")]
+pub struct Throttle {
+    #[default(1)]
+    #[description("the number of rows allowed per period.")]
+    rows: i128,
+    #[description("the length of the period.")]
+    per: Duration,
+}
+
+pub fn run_throttle(rows: usize, per: std::time::Duration, input: &mut dyn CrushStream, output: OutputStream) -> CrushResult<()> {
+    // When the most recent rows were passed on, oldest first
+    let mut sent: VecDeque<Instant> = VecDeque::with_capacity(rows);
+    while let Ok(row) = input.read() {
+        if sent.len() == rows {
+            if let Some(oldest) = sent.pop_front() {
+                if let Some(wait) = (oldest + per).checked_duration_since(Instant::now()) {
+                    thread::sleep(wait);
+                }
+            }
+        }
+        sent.push_back(Instant::now());
+        output.send(row)?;
+    }
+    Ok(())
+}
+
+pub fn throttle(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Throttle = Throttle::parse(context.arguments, &context.printer)?;
+    if cfg.rows <= 0 {
+        return argument_error("The number of rows must be positive");
+    }
+    let per = to_crush_error(cfg.per.to_std())?;
+    match context.input.recv()?.stream() {
+        Some(mut input) => {
+            let output = context.output.initialize(input.types().to_vec())?;
+            run_throttle(cfg.rows as usize, per, input.as_mut(), output)
+        }
+        None => error("Expected a stream"),
+    }
+}
+
+#[signature(
+delay,
+can_block = true,
+short = "Wait before passing on each row of the input",
+long = "Rows are passed on unchanged, each one after waiting for the given duration. Unlike throttle,",
+long = "this also spaces out rows that arrive slowly.",
+example = "for (seq 10 | delay 200ms) {echo value}")]
+pub struct Delay {
+    #[description("how long to wait before each row.")]
+    duration: Duration,
+}
+
+pub fn delay(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Delay = Delay::parse(context.arguments, &context.printer)?;
+    let duration = to_crush_error(cfg.duration.to_std())?;
+    match context.input.recv()?.stream() {
+        Some(mut input) => {
+            let output = context.output.initialize(input.types().to_vec())?;
+            while let Ok(row) = input.read() {
+                thread::sleep(duration);
+                output.send(row)?;
+            }
+            Ok(())
+        }
+        None => error("Expected a stream"),
+    }
+}
@@ -0,0 +1,100 @@
+use crate::lang::execution_context::ExecutionContext;
+use crate::lang::errors::{CrushResult, error, argument_error};
+use crate::lang::table::Row;
+use crate::lang::stream::{CrushStream, OutputStream};
+use crate::lang::table::ColumnVec;
+use crate::lang::value::{Field, Value};
+use crate::lang::command::OutputType::Passthrough;
+use signature::signature;
+use crate::lang::argument::ArgumentHandler;
+use std::cmp::{Ordering, Reverse};
+use std::collections::BinaryHeap;
+
+#[signature(
+top,
+can_block = true,
+output = Passthrough,
+short = "Pass on the n rows with the largest values in a column, largest first",
+long = "Only n rows are kept in memory at any time, which makes this much cheaper than sorting the",
+long = "whole input. Rows with equal values are passed on in the order they were read.",
+example = "find . | top 10 by=^size")]
+pub struct Top {
+    #[default(10)]
+    #[description("the number of rows to pass on.")]
+    n: i128,
+    #[description("the column to compare. Not required if there is only one column.")]
+    by: Option<Field>,
+    #[default(false)]
+    #[description("pass on the rows with the smallest values instead, smallest first.")]
+    smallest: bool,
+}
+
+struct Entry {
+    key: Value,
+    smallest: bool,
+    index: usize,
+    row: Row,
+}
+
+impl Ord for Entry {
+    /** Entries that should be passed on first compare as greater. */
+    fn cmp(&self, other: &Self) -> Ordering {
+        let ordering = self.key.partial_cmp(&other.key).unwrap_or(Ordering::Equal);
+        let ordering = if self.smallest { ordering.reverse() } else { ordering };
+        ordering.then_with(|| other.index.cmp(&self.index))
+    }
+}
+
+impl PartialOrd for Entry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Entry {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Entry {}
+
+pub fn run(cfg: &Top, idx: usize, input: &mut dyn CrushStream, output: OutputStream) -> CrushResult<()> {
+    let n = cfg.n as usize;
+    let mut heap = BinaryHeap::with_capacity(n.min(1024) + 1);
+    let mut index = 0;
+    while let Ok(row) = input.read() {
+        heap.push(Reverse(Entry {
+            key: row.cells()[idx].clone(),
+            smallest: cfg.smallest,
+            index,
+            row,
+        }));
+        if heap.len() > n {
+            heap.pop();
+        }
+        index += 1;
+    }
+    for entry in heap.into_sorted_vec() {
+        output.send(entry.0.row)?;
+    }
+    Ok(())
+}
+
+pub fn top(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Top = Top::parse(context.arguments, &context.printer)?;
+    if cfg.n < 0 {
+        return argument_error("The number of rows can't be negative");
+    }
+    match context.input.recv()?.stream() {
+        Some(mut input) => {
+            let idx = match &cfg.by {
+                Some(field) => input.types().find(field)?,
+                None => if input.types().len() == 1 { 0 } else { return argument_error("Missing comparison key"); },
+            };
+            let output = context.output.initialize(input.types().to_vec())?;
+            run(&cfg, idx, input.as_mut(), output)
+        }
+        None => error("Expected a stream"),
+    }
+}
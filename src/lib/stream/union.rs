@@ -0,0 +1,41 @@
+use crate::lang::execution_context::ExecutionContext;
+use crate::lang::errors::{CrushResult, argument_error, mandate};
+use crate::lang::stream::Stream;
+use crate::lang::table::{ColumnType, ColumnMapping};
+use signature::signature;
+use crate::lang::argument::ArgumentHandler;
+use crate::lang::value::Value;
+
+#[signature(
+union,
+can_block = true,
+short = "Concatenate multiple streams of data into one",
+long = "Columns are matched by name, so the streams may list their columns in different orders.\n    Integer columns are widened into floats if another stream has a float column of the same name.\n    The column order of the first stream is used for the output.",
+example = "union (lines example_data/age.csv) (lines example_data/home.csv)")]
+pub struct Union {
+    #[unnamed()]
+    #[description("the streams to concatenate.")]
+    streams: Vec<Value>,
+}
+
+pub fn union(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Union = Union::parse(context.arguments, &context.printer)?;
+    if cfg.streams.is_empty() {
+        return argument_error("Expected at least one stream");
+    }
+    let mut streams = cfg.streams.into_iter()
+        .map(|v| mandate(v.stream(), "Expected a type that can be streamed"))
+        .collect::<CrushResult<Vec<Stream>>>()?;
+    let types = streams.iter().map(|s| s.types()).collect::<Vec<&[ColumnType]>>();
+    let output_type = ColumnType::merge(&types)?;
+    let mappings = streams.iter()
+        .map(|s| ColumnMapping::new(s.types(), &output_type))
+        .collect::<CrushResult<Vec<_>>>()?;
+    let output = context.output.initialize(output_type)?;
+    for (stream, mapping) in streams.iter_mut().zip(mappings.iter()) {
+        while let Ok(row) = stream.read() {
+            output.send(mapping.map(row)?)?;
+        }
+    }
+    Ok(())
+}
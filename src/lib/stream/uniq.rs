@@ -2,42 +2,60 @@ use crate::lang::execution_context::{ExecutionContext, ArgumentVector};
 use std::collections::HashSet;
 use crate::lang::argument::Argument;
 use crate::lang::table::Row;
-use crate::lang::{value::Value, table::ColumnType};
+use crate::lang::table::ColumnType;
 use crate::lang::errors::{CrushResult, error};
 use crate::lang::stream::{CrushStream, OutputStream};
 use crate::lang::table::ColumnVec;
 use crate::lang::printer::Printer;
+use crate::util::expiry::Expiry;
+use crate::lang::value::Collation;
+use super::{expire, sort};
+use std::time::{Duration, Instant};
 
-fn parse(input_type: &[ColumnType], mut arguments: Vec<Argument>) -> CrushResult<Option<usize>> {
+struct Config {
+    idx: Option<usize>,
+    ttl: Option<Duration>,
+    collation: Collation,
+}
+
+fn parse(input_type: &[ColumnType], mut arguments: Vec<Argument>) -> CrushResult<Config> {
+    let ttl = expire::ttl(&mut arguments)?;
+    let collation = sort::collation(&mut arguments)?;
     arguments.check_len_range(0, 1)?;
-    if let Some(f) = arguments.optional_field(0)? {
-        Ok(Some(input_type.find(&f)?))
-    } else {
-        Ok(None)
-    }
+    let idx = match arguments.optional_field(0)? {
+        Some(f) => Some(input_type.find(&f)?),
+        None => None,
+    };
+    Ok(Config { idx, ttl, collation })
 }
 
 fn run(
-    idx: Option<usize>,
+    config: Config,
     input: &mut dyn CrushStream,
     output: OutputStream,
     printer: &Printer,
 ) -> CrushResult<()> {
-    match idx {
+    let collation = config.collation;
+    let key = |row: &Row| match config.idx {
+        None => Row::new(row.cells().iter().map(|cell| collation.key(cell)).collect()),
+        Some(idx) => Row::new(vec![collation.key(&row.cells()[idx])]),
+    };
+    match config.ttl {
         None => {
             let mut seen: HashSet<Row> = HashSet::new();
             while let Ok(row) = input.read() {
-                if !seen.contains(&row) {
-                    seen.insert(row.clone());
+                if seen.insert(key(&row)) {
                     printer.handle_error(output.send(row));
                 }
             }
         }
-        Some(idx) => {
-            let mut seen: HashSet<Value> = HashSet::new();
+        Some(ttl) => {
+            // Keys not seen within the ttl are forgotten, so that endless streams can be used
+            let mut seen: Expiry<Row, ()> = Expiry::new(ttl);
             while let Ok(row) = input.read() {
-                if !seen.contains(&row.cells()[idx]) {
-                    seen.insert(row.cells()[idx].clone());
+                let now = Instant::now();
+                seen.expire(now);
+                if seen.touch(key(&row), (), now) {
                     printer.handle_error(output.send(row));
                 }
             }
@@ -49,9 +67,9 @@ fn run(
 pub fn uniq(context: ExecutionContext) -> CrushResult<()> {
     match context.input.recv()?.stream() {
         Some(mut input) => {
-            let idx = parse(input.types(), context.arguments)?;
+            let config = parse(input.types(), context.arguments)?;
             let output = context.output.initialize(input.types().to_vec())?;
-            run(idx, input.as_mut(), output, &context.printer)
+            run(config, input.as_mut(), output, &context.printer)
         }
         _ => error("Expected io to be a stream"),
     }
@@ -1,31 +1,160 @@
 use crate::{
     lang::{
-        value::Value,
+        value::{Value, ValueType},
         table::Row,
     },
 };
 use crate::lang::execution_context::ExecutionContext;
-use crate::lang::errors::{error, CrushResult};
+use crate::lang::errors::{error, argument_error, CrushResult};
 use crate::lang::stream::{empty_channel, channels, black_hole};
 use crate::lang::{table::ColumnType, argument::Argument};
 use crate::lang::command::Command;
 use signature::signature;
 use crate::lang::argument::ArgumentHandler;
 use crate::lang::command::OutputType::Passthrough;
+use crate::lang::table::ColumnVec;
+use std::cmp::Ordering;
 
 #[signature(
 r#where,
 can_block = true,
 output = Passthrough,
 short = "Filter out rows from io based on condition",
-long = "The columns of the row are exported to the environment using the column names.",
-example = "ps | where {status != \"Sleeping\"}")]
+long = "The condition is either a closure or a field, an operator and a value. When using a closure,",
+long = "the columns of the row are exported to the environment using the column names.",
+long = "",
+long = "In the compact form, the value is converted to the type of the column before comparing,",
+long = "except that integer columns are compared with floating point values as floats.",
+long = "The operators ==, !=, <, <=, > and >= compare values the same way as the corresponding",
+long = "expressions do, while =~ and !~ match the column against a glob or a regex.",
+long = "",
+long = "    ps | where ^state \"!=\" \"Sleeping\"",
+example = "ps | where {state != \"Sleeping\"}")]
 pub struct Where {
-    #[description("the condition to filter on.")]
-    condition: Command,
+    #[description("the condition to filter on, or the field to compare in the compact form.")]
+    condition: Value,
+    #[description("the comparison operator of the compact form.")]
+    operator: Option<String>,
+    #[description("the value to compare the field with in the compact form.")]
+    operand: Option<Value>,
+}
+
+#[derive(Clone, Copy)]
+enum Operator {
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+    Match,
+    NotMatch,
+}
+
+impl Operator {
+    fn parse(s: &str) -> CrushResult<Operator> {
+        Ok(match s {
+            "==" => Operator::Equal,
+            "!=" => Operator::NotEqual,
+            "<" => Operator::Less,
+            "<=" => Operator::LessOrEqual,
+            ">" => Operator::Greater,
+            ">=" => Operator::GreaterOrEqual,
+            "=~" => Operator::Match,
+            "!~" => Operator::NotMatch,
+            _ => return argument_error(format!("Unknown operator {}", s).as_str()),
+        })
+    }
+}
+
+enum Condition {
+    Closure(Command),
+    Comparison {
+        column: usize,
+        operator: Operator,
+        value: Value,
+    },
+}
+
+impl Condition {
+    fn new(cfg: Where, input_type: &[ColumnType]) -> CrushResult<Condition> {
+        match (cfg.condition, cfg.operator, cfg.operand) {
+            (Value::Command(closure), None, None) => Ok(Condition::Closure(closure)),
+            (Value::Field(field), Some(operator), Some(value)) => {
+                let column = input_type.find(&field)?;
+                let operator = Operator::parse(&operator)?;
+                let value = match operator {
+                    Operator::Match | Operator::NotMatch => match value {
+                        Value::Glob(_) | Value::Regex(_, _) => value,
+                        _ => return argument_error(
+                            format!(
+                                "Expected a glob or a regex to match against, got a value of type {}",
+                                value.value_type().to_string()).as_str()),
+                    },
+                    _ if input_type[column].cell_type == ValueType::Integer && value.value_type() == ValueType::Float => value,
+                    _ => {
+                        let cell_type = input_type[column].cell_type.clone();
+                        let value_type = value.value_type();
+                        match value.convert(cell_type.clone()) {
+                            Ok(v) => v,
+                            Err(_) => return argument_error(
+                                format!(
+                                    "Can't compare column {} of type {} with a value of type {}",
+                                    input_type[column].name,
+                                    cell_type.to_string(),
+                                    value_type.to_string()).as_str()),
+                        }
+                    }
+                };
+                Ok(Condition::Comparison { column, operator, value })
+            }
+            _ => argument_error("Expected either a closure or a field, an operator and a value"),
+        }
+    }
+
+    fn evaluate(
+        &self,
+        row: &Row,
+        input_type: &[ColumnType],
+        base_context: &ExecutionContext) -> CrushResult<bool> {
+        match self {
+            Condition::Closure(closure) => evaluate_closure(closure.as_ref().clone(), row, input_type, base_context),
+            Condition::Comparison { column, operator, value } =>
+                compare(&row.cells()[*column], *operator, value),
+        }
+    }
+}
+
+fn compare(cell: &Value, operator: Operator, value: &Value) -> CrushResult<bool> {
+    if let (Value::Integer(i), Value::Float(_)) = (cell, value) {
+        return compare(&Value::Float(*i as f64), operator, value);
+    }
+    let ordering = || match cell.partial_cmp(value) {
+        Some(ordering) => Ok(ordering),
+        None => argument_error(
+            format!(
+                "Values of type {} and {} can't be compared with each other",
+                cell.value_type().to_string(),
+                value.value_type().to_string()).as_str()),
+    };
+    let matches = || match value {
+        Value::Glob(g) => Ok(g.matches(&cell.to_string())),
+        Value::Regex(_, re) => Ok(re.is_match(&cell.to_string())),
+        _ => error("Expected a glob or a regex"),
+    };
+    Ok(match operator {
+        Operator::Equal => cell == value,
+        Operator::NotEqual => cell != value,
+        Operator::Less => ordering()? == Ordering::Less,
+        Operator::LessOrEqual => ordering()? != Ordering::Greater,
+        Operator::Greater => ordering()? == Ordering::Greater,
+        Operator::GreaterOrEqual => ordering()? != Ordering::Less,
+        Operator::Match => matches()?,
+        Operator::NotMatch => !matches()?,
+    })
 }
 
-fn evaluate(
+fn evaluate_closure(
     condition: Command,
     row: &Row,
     input_type: &[ColumnType],
@@ -51,6 +180,7 @@ pub fn r#where(context: ExecutionContext) -> CrushResult<()> {
 
     match context.input.recv()?.stream() {
         Some(mut input) => {
+            let condition = Condition::new(cfg, input.types())?;
             let base_context = ExecutionContext {
                 input: empty_channel(),
                 output: black_hole(),
@@ -61,9 +191,9 @@ pub fn r#where(context: ExecutionContext) -> CrushResult<()> {
             };
             let output = context.output.initialize(input.types().to_vec())?;
             while let Ok(row) = input.read() {
-                match evaluate(cfg.condition.clone(), &row, input.types(), &base_context) {
+                match condition.evaluate(&row, input.types(), &base_context) {
                     Ok(val) => if val && output.send(row).is_err() { break; },
-                    Err(e) => base_context.printer.crush_error(e),
+                    Err(e) => base_context.printer.handle_error::<()>(Err(e)),
                 }
             }
             Ok(())
@@ -1,28 +1,64 @@
 use crate::lang::execution_context::ExecutionContext;
 use crate::lang::errors::CrushResult;
-use crate::lang::stream::{ValueSender, Stream};
+use crate::lang::stream::Stream;
+use crate::lang::table::{ColumnType, ColumnMapping};
 use signature::signature;
 use crate::lang::argument::ArgumentHandler;
 
 #[signature(
 zip,
 can_block = true,
-short = "Combine two streams of data into one")]
+short = "Combine two streams of data into one",
+long = "If both streams have columns with the same name, the columns of the second stream are renamed\n    by adding a numeric suffix, unless a prefix is specified. Columns with the same name are widened\n    to a common type, so that e.g. an integer column can be compared with a float column of the other\n    stream.")]
 pub struct Zip {
     #[description("the first stream.")]
     first: Stream,
     #[description("the second stream.")]
     second: Stream,
+    #[description("prefix to add to the column names of the first stream.")]
+    first_prefix: Option<String>,
+    #[description("prefix to add to the column names of the second stream.")]
+    second_prefix: Option<String>,
+}
+
+fn prefixed(types: &[ColumnType], prefix: &Option<String>) -> Vec<ColumnType> {
+    types.iter()
+        .map(|t| match prefix {
+            Some(p) => ColumnType::new(&format!("{}{}", p, t.name), t.cell_type.clone()),
+            None => t.clone(),
+        })
+        .collect()
+}
+
+/**
+The columns of a stream, where columns that share their name with a column of the other stream
+are widened to a type both can be converted to.
+*/
+fn widened(types: &[ColumnType], other: &[ColumnType]) -> Vec<ColumnType> {
+    types.iter()
+        .map(|t| match other.iter()
+            .find(|o| o.name == t.name)
+            .and_then(|o| ColumnType::widen(&t.cell_type, &o.cell_type)) {
+            Some(cell_type) => ColumnType::new(&t.name, cell_type),
+            None => t.clone(),
+        })
+        .collect()
 }
 
 pub fn zip(context: ExecutionContext) -> CrushResult<()> {
     let mut cfg: Zip = Zip::parse(context.arguments, &context.printer)?;
+    let first_types = widened(cfg.first.types(), cfg.second.types());
+    let second_types = widened(cfg.second.types(), cfg.first.types());
+    let first_mapping = ColumnMapping::new(cfg.first.types(), &first_types)?;
+    let second_mapping = ColumnMapping::new(cfg.second.types(), &second_types)?;
     let mut output_type = Vec::new();
-    output_type.append(&mut cfg.first.types().to_vec());
-    output_type.append(&mut cfg.second.types().to_vec());
+    output_type.append(&mut prefixed(&first_types, &cfg.first_prefix));
+    output_type.append(&mut prefixed(&second_types, &cfg.second_prefix));
+    ColumnType::deduplicate(&mut output_type);
     let output = context.output.initialize(output_type)?;
-    while let (Ok(mut row1), Ok(row2)) = (cfg.first.read(), cfg.second.read()) {
-        row1.append(&mut row2.into_vec());
+    while let (Ok(row1), Ok(row2)) = (cfg.first.read(), cfg.second.read()) {
+        let mut row1 = first_mapping.map(row1)?;
+        row1.append(&mut second_mapping.map(row2)?.into_vec());
         output.send(row1)?;
     }
     Ok(())
@@ -0,0 +1,11 @@
+use crate::lang::errors::CrushResult;
+use crate::lang::scope::Scope;
+
+mod net;
+mod systemd;
+
+pub fn declare(root: &Scope) -> CrushResult<()> {
+    net::declare(root)?;
+    systemd::declare(root)?;
+    Ok(())
+}
@@ -0,0 +1,710 @@
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fs;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::Instant;
+
+use chrono::Duration;
+use crossbeam::unbounded;
+use lazy_static::lazy_static;
+use nix::ifaddrs::getifaddrs;
+use nix::net::if_::InterfaceFlags;
+use nix::sys::socket::SockAddr;
+
+use crate::lang::argument::ArgumentHandler;
+use crate::lang::command::OutputType::{Known, Unknown};
+use crate::lang::errors::{CrushResult, argument_error, error, mandate, to_crush_error};
+use crate::lang::execution_context::ExecutionContext;
+use crate::lang::list::List;
+use crate::lang::r#struct::Struct;
+use crate::lang::scope::Scope;
+use crate::lang::stream::{CrushStream, ValueSender};
+use crate::lang::table::ColumnVec;
+use crate::lang::{table::ColumnType, table::Row, value::Field, value::Value, value::ValueType};
+use crate::util::icmp::Pinger;
+use crate::util::whois;
+use signature::signature;
+
+lazy_static! {
+    static ref IFACES_OUTPUT_TYPE: Vec<ColumnType> = vec![
+        ColumnType::new("name", ValueType::String),
+        ColumnType::new("addresses", ValueType::List(Box::new(ValueType::String))),
+        ColumnType::new("mac", ValueType::Any),
+        ColumnType::new("mtu", ValueType::Any),
+        ColumnType::new("flags", ValueType::List(Box::new(ValueType::String))),
+    ];
+    static ref CONNECTIONS_OUTPUT_TYPE: Vec<ColumnType> = vec![
+        ColumnType::new("protocol", ValueType::String),
+        ColumnType::new("local", ValueType::String),
+        ColumnType::new("remote", ValueType::String),
+        ColumnType::new("state", ValueType::String),
+        ColumnType::new("pid", ValueType::Any),
+    ];
+    static ref LISTEN_PORTS_OUTPUT_TYPE: Vec<ColumnType> = vec![
+        ColumnType::new("protocol", ValueType::String),
+        ColumnType::new("address", ValueType::String),
+        ColumnType::new("port", ValueType::Integer),
+        ColumnType::new("pid", ValueType::Any),
+        ColumnType::new("name", ValueType::Any),
+    ];
+    static ref PING_OUTPUT_TYPE: Vec<ColumnType> = vec![
+        ColumnType::new("seq", ValueType::Integer),
+        ColumnType::new("rtt", ValueType::Any),
+        ColumnType::new("ttl", ValueType::Any),
+        ColumnType::new("success", ValueType::Bool),
+    ];
+    static ref SCAN_OUTPUT_TYPE: Vec<ColumnType> = vec![
+        ColumnType::new("port", ValueType::Integer),
+        ColumnType::new("service", ValueType::Any),
+        ColumnType::new("rtt", ValueType::Duration),
+    ];
+    static ref ORIGIN_OUTPUT_TYPE: Vec<ColumnType> = vec![
+        ColumnType::new("country", ValueType::Any),
+        ColumnType::new("asn", ValueType::Any),
+        ColumnType::new("org", ValueType::Any),
+    ];
+}
+
+const FLAG_NAMES: [(InterfaceFlags, &str); 8] = [
+    (InterfaceFlags::IFF_UP, "up"),
+    (InterfaceFlags::IFF_BROADCAST, "broadcast"),
+    (InterfaceFlags::IFF_LOOPBACK, "loopback"),
+    (InterfaceFlags::IFF_POINTOPOINT, "point_to_point"),
+    (InterfaceFlags::IFF_RUNNING, "running"),
+    (InterfaceFlags::IFF_NOARP, "noarp"),
+    (InterfaceFlags::IFF_PROMISC, "promisc"),
+    (InterfaceFlags::IFF_MULTICAST, "multicast"),
+];
+
+fn optional(value: Option<Value>) -> Value {
+    value.unwrap_or(Value::Empty())
+}
+
+fn ip_address(address: &Option<SockAddr>) -> Option<IpAddr> {
+    match address {
+        Some(SockAddr::Inet(inet)) => Some(inet.ip().to_std()),
+        _ => None,
+    }
+}
+
+/** The number of leading one bits in a netmask. */
+fn prefix_length(netmask: IpAddr) -> u32 {
+    match netmask {
+        IpAddr::V4(mask) => u32::from(mask).leading_ones(),
+        IpAddr::V6(mask) => u128::from(mask).leading_ones(),
+    }
+}
+
+struct Interface {
+    addresses: Vec<Value>,
+    mac: Option<String>,
+    flags: InterfaceFlags,
+}
+
+#[signature(
+ifaces,
+can_block = true,
+output = Known(ValueType::TableStream(IFACES_OUTPUT_TYPE.clone())),
+short = "Return a table stream of the network interfaces of the system",
+long = "Each row contains the name of an interface, its addresses with their prefix length, its MAC",
+long = "address, MTU and flags. The flags are some of up, broadcast, loopback, point_to_point,",
+long = "running, noarp, promisc and multicast.",
+example = "net:ifaces | select ^name ^addresses")]
+pub struct Ifaces {}
+
+fn ifaces(context: ExecutionContext) -> CrushResult<()> {
+    let output = context.output.initialize(IFACES_OUTPUT_TYPE.clone())?;
+    let mut names = Vec::new();
+    let mut interfaces: HashMap<String, Interface> = HashMap::new();
+    for address in to_crush_error(getifaddrs())? {
+        let interface = interfaces.entry(address.interface_name.clone()).or_insert_with(|| {
+            names.push(address.interface_name.clone());
+            Interface { addresses: Vec::new(), mac: None, flags: address.flags }
+        });
+        match (&address.address, ip_address(&address.address)) {
+            (_, Some(ip)) => interface.addresses.push(Value::String(match ip_address(&address.netmask) {
+                Some(netmask) => format!("{}/{}", ip, prefix_length(netmask)),
+                None => ip.to_string(),
+            })),
+            (Some(SockAddr::Link(link)), None) => interface.mac = Some(link.to_string()),
+            _ => {}
+        }
+    }
+
+    for name in names {
+        if let Some(interface) = interfaces.remove(&name) {
+            let mtu = fs::read_to_string(format!("/sys/class/net/{}/mtu", name)).ok()
+                .and_then(|mtu| mtu.trim().parse::<i128>().ok());
+            let flags = FLAG_NAMES.iter()
+                .filter(|(flag, _)| interface.flags.contains(*flag))
+                .map(|(_, name)| Value::string(name))
+                .collect();
+            output.send(Row::new(vec![
+                Value::String(name),
+                Value::List(List::new(ValueType::String, interface.addresses)),
+                optional(interface.mac.map(Value::String)),
+                optional(mtu.map(Value::Integer)),
+                Value::List(List::new(ValueType::String, flags)),
+            ]))?;
+        }
+    }
+    Ok(())
+}
+
+fn tcp_state(code: u8) -> &'static str {
+    match code {
+        0x01 => "established",
+        0x02 => "syn_sent",
+        0x03 => "syn_received",
+        0x04 => "fin_wait1",
+        0x05 => "fin_wait2",
+        0x06 => "time_wait",
+        0x07 => "close",
+        0x08 => "close_wait",
+        0x09 => "last_ack",
+        0x0A => "listen",
+        0x0B => "closing",
+        _ => "unknown",
+    }
+}
+
+/**
+Parse an address from /proc/net, which is written as hexadecimal 32 bit words in host byte order,
+followed by a colon and the port.
+*/
+fn parse_address(text: &str) -> Option<SocketAddr> {
+    let (address, port) = text.split_once(':')?;
+    let port = u16::from_str_radix(port, 16).ok()?;
+    let mut octets = Vec::new();
+    for idx in (0..address.len()).step_by(8) {
+        let word = u32::from_str_radix(address.get(idx..idx + 8)?, 16).ok()?;
+        octets.extend_from_slice(&word.to_ne_bytes());
+    }
+    let ip = match octets.len() {
+        4 => IpAddr::V4(Ipv4Addr::new(octets[0], octets[1], octets[2], octets[3])),
+        16 => {
+            let mut v6 = [0u8; 16];
+            v6.copy_from_slice(&octets);
+            IpAddr::V6(Ipv6Addr::from(v6))
+        }
+        _ => return None,
+    };
+    Some(SocketAddr::new(ip, port))
+}
+
+struct Socket {
+    protocol: &'static str,
+    local: SocketAddr,
+    remote: SocketAddr,
+    state: &'static str,
+    inode: u64,
+}
+
+fn sockets() -> Vec<Socket> {
+    let mut res = Vec::new();
+    for protocol in &["tcp", "tcp6", "udp", "udp6"] {
+        let data = match fs::read_to_string(format!("/proc/net/{}", protocol)) {
+            Ok(data) => data,
+            // IPv6 may be disabled
+            Err(_) => continue,
+        };
+        for line in data.lines().skip(1) {
+            let fields = line.split_whitespace().collect::<Vec<_>>();
+            if fields.len() < 10 {
+                continue;
+            }
+            let state = u8::from_str_radix(fields[3], 16).unwrap_or(0);
+            if let (Some(local), Some(remote), Ok(inode)) = (parse_address(fields[1]), parse_address(fields[2]), fields[9].parse::<u64>()) {
+                res.push(Socket {
+                    protocol,
+                    local,
+                    remote,
+                    // UDP sockets have no state, but the kernel reports unconnected ones as closed
+                    state: if protocol.starts_with("udp") {
+                        if remote.port() == 0 { "unconnected" } else { "connected" }
+                    } else {
+                        tcp_state(state)
+                    },
+                    inode,
+                });
+            }
+        }
+    }
+    res
+}
+
+/** Map socket inodes to the pid and name of the process owning them, for all visible processes. */
+fn socket_owners() -> HashMap<u64, (i128, String)> {
+    let mut res = HashMap::new();
+    let entries = match fs::read_dir("/proc") {
+        Ok(entries) => entries,
+        Err(_) => return res,
+    };
+    for entry in entries.flatten() {
+        let pid = match entry.file_name().to_str().and_then(|n| n.parse::<i128>().ok()) {
+            Some(pid) => pid,
+            None => continue,
+        };
+        // The file descriptors of processes owned by other users can't be read without privileges
+        let fds = match fs::read_dir(entry.path().join("fd")) {
+            Ok(fds) => fds,
+            Err(_) => continue,
+        };
+        let name = fs::read_to_string(entry.path().join("comm")).unwrap_or_default().trim().to_string();
+        for fd in fds.flatten() {
+            if let Ok(target) = fs::read_link(fd.path()) {
+                let target = target.to_string_lossy();
+                if let Some(inode) = target.strip_prefix("socket:[").and_then(|t| t.strip_suffix(']')) {
+                    if let Ok(inode) = inode.parse::<u64>() {
+                        res.entry(inode).or_insert_with(|| (pid, name.clone()));
+                    }
+                }
+            }
+        }
+    }
+    res
+}
+
+#[signature(
+connections,
+can_block = true,
+output = Known(ValueType::TableStream(CONNECTIONS_OUTPUT_TYPE.clone())),
+short = "Return a table stream of the TCP and UDP sockets of the system",
+long = "Each row contains the protocol, the local and remote address, the state and the pid of the",
+long = "process owning the socket. The pid is empty if the owner could not be found, which is",
+long = "usually because the process belongs to another user.",
+example = "net:connections | where {state == \"established\"} | group ^remote")]
+pub struct Connections {}
+
+fn connections(context: ExecutionContext) -> CrushResult<()> {
+    let output = context.output.initialize(CONNECTIONS_OUTPUT_TYPE.clone())?;
+    let owners = socket_owners();
+    for socket in sockets() {
+        output.send(Row::new(vec![
+            Value::string(socket.protocol),
+            Value::String(socket.local.to_string()),
+            Value::String(socket.remote.to_string()),
+            Value::string(socket.state),
+            optional(owners.get(&socket.inode).map(|(pid, _)| Value::Integer(*pid))),
+        ]))?;
+    }
+    Ok(())
+}
+
+#[signature(
+listen_ports,
+can_block = true,
+output = Known(ValueType::TableStream(LISTEN_PORTS_OUTPUT_TYPE.clone())),
+short = "Return a table stream of the ports that the system is listening on",
+long = "Each row contains the protocol, the address and port that a listening TCP socket or an",
+long = "unconnected UDP socket is bound to, and the pid and name of the process owning it. The",
+long = "pid and name are empty if the owner could not be found.",
+example = "net:listen_ports | where {port < 1024}")]
+pub struct ListenPorts {}
+
+fn listen_ports(context: ExecutionContext) -> CrushResult<()> {
+    let output = context.output.initialize(LISTEN_PORTS_OUTPUT_TYPE.clone())?;
+    let owners = socket_owners();
+    let mut sockets = sockets().into_iter()
+        .filter(|s| s.state == "listen" || s.state == "unconnected")
+        .collect::<Vec<_>>();
+    sockets.sort_by_key(|s| (s.local.port(), s.protocol));
+    for socket in sockets {
+        let owner = owners.get(&socket.inode);
+        output.send(Row::new(vec![
+            Value::string(socket.protocol),
+            Value::String(socket.local.ip().to_string()),
+            Value::Integer(i128::from(socket.local.port())),
+            optional(owner.map(|(pid, _)| Value::Integer(*pid))),
+            optional(owner.map(|(_, name)| Value::string(name))),
+        ]))?;
+    }
+    Ok(())
+}
+
+fn timeout(timeout: Option<Duration>) -> CrushResult<std::time::Duration> {
+    match timeout {
+        None => Ok(std::time::Duration::from_secs(10)),
+        Some(timeout) => to_crush_error(timeout.to_std()),
+    }
+}
+
+fn strings(values: Vec<String>) -> Value {
+    Value::List(List::new(ValueType::String, values.into_iter().map(Value::String).collect()))
+}
+
+#[signature(
+whois,
+can_block = true,
+output = Known(ValueType::Struct),
+short = "Look up the registration of a domain",
+long = "Returns a struct with the registrar, the created, updated and expires times, the nameservers",
+long = "and the status codes of the domain, and the WHOIS server that answered. Members that the",
+long = "server did not report are empty.",
+long = "",
+long = "The lookup starts at whois.iana.org, and follows referrals to the registry of the top level",
+long = "domain and to the registrar of the domain. What the registry reports takes precedence.",
+example = "(net:whois \"example.com\"):expires - (time:now)")]
+pub struct Whois {
+    #[description("the domain to look up.")]
+    domain: String,
+    #[description("the WHOIS server to start at.")]
+    server: Option<String>,
+    #[description("how long to wait for each server. The default is ten seconds.")]
+    timeout: Option<Duration>,
+}
+
+fn whois(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Whois = Whois::parse(context.arguments, &context.printer)?;
+    let domain = cfg.domain.trim_end_matches('.').to_lowercase();
+    let res = whois::domain(
+        &domain,
+        cfg.server.as_deref().unwrap_or(whois::IANA),
+        timeout(cfg.timeout)?)?;
+    context.output.send(Value::Struct(Struct::new(
+        vec![
+            ("domain".to_string(), Value::String(domain)),
+            ("registrar".to_string(), optional(res.registrar.map(Value::String))),
+            ("created".to_string(), optional(res.created.map(Value::Time))),
+            ("updated".to_string(), optional(res.updated.map(Value::Time))),
+            ("expires".to_string(), optional(res.expires.map(Value::Time))),
+            ("nameservers".to_string(), strings(res.nameservers)),
+            ("status".to_string(), strings(res.status)),
+            ("server".to_string(), Value::String(res.server)),
+        ],
+        None)))
+}
+
+/** Rows are looked up this many at a time, so that a long stream doesn't have to be read first. */
+const GEOIP_BATCH_SIZE: usize = 1000;
+
+/** Looks up the addresses that aren't in the cache yet. Anything that isn't an IP address is ignored. */
+fn geoip_lookup(addresses: &[String], cache: &mut HashMap<String, Vec<Value>>, server: &str, timeout: std::time::Duration) -> CrushResult<()> {
+    let mut missing = addresses.iter()
+        .filter(|a| !cache.contains_key(*a) && a.parse::<IpAddr>().is_ok())
+        .cloned()
+        .collect::<Vec<_>>();
+    missing.sort();
+    missing.dedup();
+    if missing.is_empty() {
+        return Ok(());
+    }
+    for origin in whois::origins(&missing, server, timeout)? {
+        cache.insert(origin.address, vec![
+            optional(origin.country.map(Value::String)),
+            optional(origin.asn.map(Value::Integer)),
+            optional(origin.org.map(Value::String)),
+        ]);
+    }
+    Ok(())
+}
+
+/** The address in a cell, which may also be a socket address like the ones net:connections outputs. */
+fn cell_address(cell: &Value) -> String {
+    let text = match cell {
+        Value::String(s) => s.clone(),
+        v => v.to_string(),
+    };
+    match text.parse::<SocketAddr>() {
+        Ok(address) => address.ip().to_string(),
+        Err(_) => text,
+    }
+}
+
+fn origin(address: &str, cache: &HashMap<String, Vec<Value>>) -> Vec<Value> {
+    cache.get(address).cloned().unwrap_or_else(|| vec![Value::Empty(), Value::Empty(), Value::Empty()])
+}
+
+#[signature(
+geoip,
+can_block = true,
+output = Unknown,
+short = "Look up the country, autonomous system and owner of IP addresses",
+long = "If addresses are given, returns a table stream with the address, the country code, the",
+long = "number of the autonomous system that announces the address, and the organization it belongs",
+long = "to. If a column is given instead, the input is a table stream, and the country, asn and org",
+long = "columns are added to every row, based on the address in that column. Cells that aren't IP",
+long = "addresses, or addresses that aren't announced, like private ones, get empty columns.",
+long = "",
+long = "The addresses are looked up using the IP to ASN service of Team Cymru, which is queried in",
+long = "batches, and each distinct address is only looked up once.",
+example = "net:connections | net:geoip column=^remote | group ^org")]
+pub struct Geoip {
+    #[unnamed()]
+    #[description("the addresses to look up.")]
+    addresses: Vec<String>,
+    #[description("the column of the input with the addresses to look up.")]
+    column: Option<Field>,
+    #[description("the WHOIS server of the service.")]
+    server: Option<String>,
+    #[description("how long to wait for the server. The default is ten seconds.")]
+    timeout: Option<Duration>,
+}
+
+fn geoip_column(input: &mut dyn CrushStream, column: Field, cfg: &Geoip, sender: ValueSender) -> CrushResult<()> {
+    let idx = input.types().find(&column)?;
+    let server = cfg.server.as_deref().unwrap_or(whois::CYMRU);
+    let timeout = timeout(cfg.timeout)?;
+    let mut output_type = input.types().to_vec();
+    output_type.extend(ORIGIN_OUTPUT_TYPE.iter().cloned());
+    let output = sender.initialize(output_type)?;
+    let mut cache = HashMap::new();
+    loop {
+        let mut rows = Vec::new();
+        while rows.len() < GEOIP_BATCH_SIZE {
+            match input.read() {
+                Ok(row) => rows.push(row),
+                Err(_) => break,
+            }
+        }
+        if rows.is_empty() {
+            return Ok(());
+        }
+        let addresses = rows.iter().map(|r| cell_address(&r.cells()[idx])).collect::<Vec<_>>();
+        geoip_lookup(&addresses, &mut cache, server, timeout)?;
+        for (row, address) in rows.into_iter().zip(addresses) {
+            let mut cells = row.into_vec();
+            cells.extend(origin(&address, &cache));
+            output.send(Row::new(cells))?;
+        }
+    }
+}
+
+fn geoip(context: ExecutionContext) -> CrushResult<()> {
+    let mut cfg: Geoip = Geoip::parse(context.arguments, &context.printer)?;
+    if let Some(column) = cfg.column.take() {
+        if !cfg.addresses.is_empty() {
+            return argument_error("Expected either addresses or a column, not both");
+        }
+        return match context.input.recv()?.stream() {
+            Some(mut input) => geoip_column(input.as_mut(), column, &cfg, context.output),
+            None => error("Expected a stream"),
+        };
+    }
+    if cfg.addresses.is_empty() {
+        return argument_error("Expected at least one address or a column");
+    }
+    let mut cache = HashMap::new();
+    let mut output_type = vec![ColumnType::new("address", ValueType::String)];
+    output_type.extend(ORIGIN_OUTPUT_TYPE.iter().cloned());
+    let output = context.output.initialize(output_type)?;
+    for chunk in cfg.addresses.chunks(GEOIP_BATCH_SIZE) {
+        geoip_lookup(chunk, &mut cache, cfg.server.as_deref().unwrap_or(whois::CYMRU), timeout(cfg.timeout)?)?;
+        for address in chunk {
+            let mut cells = vec![Value::String(address.clone())];
+            cells.extend(origin(address, &cache));
+            output.send(Row::new(cells))?;
+        }
+    }
+    Ok(())
+}
+
+fn resolve(host: &str) -> CrushResult<IpAddr> {
+    if let Ok(ip) = host.parse::<IpAddr>() {
+        return Ok(ip);
+    }
+    let address = mandate(
+        to_crush_error((host, 0).to_socket_addrs())?.next(),
+        format!("Could not resolve {}", host).as_str())?;
+    Ok(address.ip())
+}
+
+fn duration_or(duration: Option<Duration>, default: Duration) -> CrushResult<std::time::Duration> {
+    to_crush_error(duration.unwrap_or(default).to_std())
+}
+
+#[signature(
+ping,
+can_block = true,
+output = Known(ValueType::TableStream(PING_OUTPUT_TYPE.clone())),
+short = "Send ICMP echo requests to a host",
+long = "Outputs one row per request, with the sequence number, the round trip time, the TTL of the",
+long = "reply and whether there was a reply before the timeout. The round trip time and TTL are",
+long = "empty for requests without a reply.",
+long = "",
+long = "Sending ICMP requires either CAP_NET_RAW or that the group of the user is allowed to use",
+long = "unprivileged ICMP sockets by net.ipv4.ping_group_range.",
+example = "net:ping \"example.com\" count=20 | where {success} | avg ^rtt")]
+pub struct Ping {
+    #[description("the host to ping.")]
+    host: String,
+    #[default(4)]
+    #[description("the number of requests to send.")]
+    count: i128,
+    #[description("the time between requests. The default is one second.")]
+    interval: Option<Duration>,
+    #[description("how long to wait for each reply. The default is one second.")]
+    timeout: Option<Duration>,
+}
+
+fn ping(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Ping = Ping::parse(context.arguments, &context.printer)?;
+    if cfg.count < 0 || cfg.count > i128::from(u16::MAX) + 1 {
+        return argument_error("Expected count to be between 0 and 65536");
+    }
+    let interval = duration_or(cfg.interval, Duration::seconds(1))?;
+    let timeout = duration_or(cfg.timeout, Duration::seconds(1))?;
+    let pinger = Pinger::new(resolve(&cfg.host)?)?;
+    let output = context.output.initialize(PING_OUTPUT_TYPE.clone())?;
+    for seq in 0..cfg.count {
+        let start = Instant::now();
+        let row = match pinger.probe(seq as u16, timeout)? {
+            Some(reply) => vec![
+                Value::Integer(seq),
+                Value::Duration(to_crush_error(Duration::from_std(reply.rtt))?),
+                optional(reply.ttl.map(|ttl| Value::Integer(i128::from(ttl)))),
+                Value::Bool(true),
+            ],
+            None => vec![Value::Integer(seq), Value::Empty(), Value::Empty(), Value::Bool(false)],
+        };
+        output.send(Row::new(row))?;
+        if seq + 1 < cfg.count {
+            if let Some(remaining) = interval.checked_sub(start.elapsed()) {
+                thread::sleep(remaining);
+            }
+        }
+    }
+    Ok(())
+}
+
+/** Parses port lists like 22,80,8000..8100. */
+fn parse_ports(spec: &str) -> CrushResult<Vec<u16>> {
+    let mut res = Vec::new();
+    for part in spec.split(',').map(|p| p.trim()).filter(|p| !p.is_empty()) {
+        let (from, to) = part.split_once("..").or_else(|| part.split_once('-')).unwrap_or((part, part));
+        match (from.trim().parse::<u16>(), to.trim().parse::<u16>()) {
+            (Ok(from), Ok(to)) if from <= to => res.extend(from..=to),
+            _ => return argument_error(format!("Invalid port range {}", part).as_str()),
+        }
+    }
+    Ok(res)
+}
+
+fn ports(value: Option<Value>) -> CrushResult<Vec<u16>> {
+    match value {
+        None => Ok((1..=1024).collect()),
+        Some(Value::String(spec)) => parse_ports(&spec),
+        Some(Value::Integer(port)) => Ok(vec![to_crush_error(u16::try_from(port))?]),
+        Some(Value::List(ports)) => ports.dump().into_iter()
+            .map(|port| match port {
+                Value::Integer(port) => to_crush_error(u16::try_from(port)),
+                v => argument_error(format!("Expected a port number, got a {}", v.value_type().to_string()).as_str()),
+            })
+            .collect(),
+        Some(v) => argument_error(format!("Expected ports to be a string, integer or list, got a {}", v.value_type().to_string()).as_str()),
+    }
+}
+
+/** The names of TCP ports from /etc/services. */
+fn services() -> HashMap<u16, String> {
+    fs::read_to_string("/etc/services").unwrap_or_default().lines()
+        .filter_map(|line| {
+            let mut fields = line.split('#').next()?.split_whitespace();
+            let name = fields.next()?;
+            let port = fields.next()?.strip_suffix("/tcp")?.parse::<u16>().ok()?;
+            Some((port, name.to_string()))
+        })
+        .collect()
+}
+
+#[signature(
+scan,
+can_block = true,
+output = Known(ValueType::TableStream(SCAN_OUTPUT_TYPE.clone())),
+short = "Find the open TCP ports of a host",
+long = "Tries to connect to each port, and outputs a row with the port, the name of its service from",
+long = "/etc/services, and how long it took to connect, for each port that accepted the connection.",
+long = "Ports are tried in parallel, so rows are output in the order the ports are found.",
+long = "",
+long = "Ports are given as a list of port numbers, or as a string of comma separated ports and",
+long = "ranges, like \"22,80,8000..8100\". Only scan hosts you are allowed to.",
+example = "net:scan \"localhost\" ports=\"1..65535\" | sort ^port")]
+pub struct Scan {
+    #[description("the host to scan.")]
+    host: String,
+    #[description("the ports to try. The default is 1..1024.")]
+    ports: Option<Value>,
+    #[default(64)]
+    #[description("the number of ports to try at the same time.")]
+    parallel: i128,
+    #[description("how long to wait for each connection. The default is one second.")]
+    timeout: Option<Duration>,
+}
+
+fn scan(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Scan = Scan::parse(context.arguments, &context.printer)?;
+    if cfg.parallel < 1 {
+        return argument_error("Expected parallel to be positive");
+    }
+    let ip = resolve(&cfg.host)?;
+    let ports = Arc::new(ports(cfg.ports)?);
+    let timeout = duration_or(cfg.timeout, Duration::seconds(1))?;
+    let next = Arc::new(AtomicUsize::new(0));
+    let (sender, receiver) = unbounded::<(u16, std::time::Duration)>();
+    for _ in 0..(cfg.parallel as usize).min(ports.len()) {
+        let ports = ports.clone();
+        let next = next.clone();
+        let sender = sender.clone();
+        to_crush_error(thread::Builder::new().name("net:scan".to_string()).spawn(move || {
+            while let Some(port) = ports.get(next.fetch_add(1, Ordering::Relaxed)) {
+                let start = Instant::now();
+                if TcpStream::connect_timeout(&SocketAddr::new(ip, *port), timeout).is_ok()
+                    && sender.send((*port, start.elapsed())).is_err() {
+                    return;
+                }
+            }
+        }))?;
+    }
+    drop(sender);
+
+    let services = services();
+    let output = context.output.initialize(SCAN_OUTPUT_TYPE.clone())?;
+    while let Ok((port, rtt)) = receiver.recv() {
+        output.send(Row::new(vec![
+            Value::Integer(i128::from(port)),
+            optional(services.get(&port).map(|name| Value::string(name))),
+            Value::Duration(to_crush_error(Duration::from_std(rtt))?),
+        ]))?;
+    }
+    Ok(())
+}
+
+pub fn declare(root: &Scope) -> CrushResult<()> {
+    root.create_lazy_namespace(
+        "net",
+        Box::new(move |env| {
+            Ifaces::declare(env)?;
+            Connections::declare(env)?;
+            ListenPorts::declare(env)?;
+            Whois::declare(env)?;
+            Geoip::declare(env)?;
+            Ping::declare(env)?;
+            Scan::declare(env)?;
+            Ok(())
+        }))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_address() {
+        assert_eq!(parse_address("0100007F:0035"), Some("127.0.0.1:53".parse().unwrap()));
+        assert_eq!(parse_address("00000000000000000000000001000000:1F90"), Some("[::1]:8080".parse().unwrap()));
+        assert_eq!(parse_address("B80D0120000000000000000001000000:0050"), Some("[2001:db8::1]:80".parse().unwrap()));
+        assert_eq!(parse_address("nonsense"), None);
+    }
+
+    #[test]
+    fn test_parse_ports() {
+        assert_eq!(parse_ports("22, 80,8000..8002").unwrap(), vec![22, 80, 8000, 8001, 8002]);
+        assert_eq!(parse_ports("1-3").unwrap(), vec![1, 2, 3]);
+        assert!(parse_ports("3..1").is_err());
+        assert!(parse_ports("http").is_err());
+    }
+}
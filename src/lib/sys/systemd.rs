@@ -0,0 +1,278 @@
+use std::io::BufRead;
+use std::path::PathBuf;
+use std::process::Command;
+
+use chrono::{Local, TimeZone};
+use lazy_static::lazy_static;
+
+use crate::lang::argument::ArgumentHandler;
+use crate::lang::command::OutputType::Known;
+use crate::lang::errors::{CrushResult, argument_error, to_crush_error};
+use crate::lang::execution_context::ExecutionContext;
+use crate::lang::scope::Scope;
+use crate::lang::table::{ColumnType, Row};
+use crate::lang::value::{Value, ValueType};
+use crate::util::external;
+use signature::signature;
+
+lazy_static! {
+    static ref JOURNAL_OUTPUT_TYPE: Vec<ColumnType> = vec![
+        ColumnType::new("time", ValueType::Time),
+        ColumnType::new("unit", ValueType::Any),
+        ColumnType::new("priority", ValueType::Any),
+        ColumnType::new("identifier", ValueType::Any),
+        ColumnType::new("pid", ValueType::Any),
+        ColumnType::new("message", ValueType::String),
+    ];
+    static ref UNITS_OUTPUT_TYPE: Vec<ColumnType> = vec![
+        ColumnType::new("unit", ValueType::String),
+        ColumnType::new("load", ValueType::String),
+        ColumnType::new("active", ValueType::String),
+        ColumnType::new("sub", ValueType::String),
+        ColumnType::new("description", ValueType::String),
+    ];
+}
+
+const PRIORITIES: [&str; 8] = ["emerg", "alert", "crit", "err", "warning", "notice", "info", "debug"];
+
+/** Journal fields are strings, or arrays of bytes if they are not valid UTF-8. */
+fn field(entry: &serde_json::Value, name: &str) -> Option<String> {
+    match &entry[name] {
+        serde_json::Value::String(s) => Some(s.clone()),
+        serde_json::Value::Array(bytes) => Some(String::from_utf8_lossy(
+            &bytes.iter().map(|b| b.as_u64().unwrap_or(b'?' as u64) as u8).collect::<Vec<_>>()).to_string()),
+        _ => None,
+    }
+}
+
+fn integer(entry: &serde_json::Value, name: &str) -> Value {
+    field(entry, name)
+        .and_then(|s| s.parse::<i128>().ok())
+        .map(Value::Integer)
+        .unwrap_or(Value::Empty())
+}
+
+fn parse_entry(entry: &serde_json::Value) -> Row {
+    let micros = field(entry, "__REALTIME_TIMESTAMP").and_then(|s| s.parse::<i64>().ok()).unwrap_or(0);
+    // Messages logged by units are attributed to the unit, those about units to the unit they are about
+    let unit = field(entry, "_SYSTEMD_UNIT")
+        .or_else(|| field(entry, "UNIT"))
+        .or_else(|| field(entry, "_SYSTEMD_USER_UNIT"))
+        .map(Value::String)
+        .unwrap_or(Value::Empty());
+    Row::new(vec![
+        Local.timestamp_opt(micros.div_euclid(1_000_000), (micros.rem_euclid(1_000_000) * 1000) as u32)
+            .single()
+            .map(Value::Time)
+            .unwrap_or(Value::Empty()),
+        unit,
+        integer(entry, "PRIORITY"),
+        field(entry, "SYSLOG_IDENTIFIER").map(Value::String).unwrap_or(Value::Empty()),
+        integer(entry, "_PID"),
+        Value::String(field(entry, "MESSAGE").unwrap_or_default()),
+    ])
+}
+
+#[signature(
+journal,
+can_block = true,
+output = Known(ValueType::TableStream(JOURNAL_OUTPUT_TYPE.clone())),
+short = "Return a table stream of the entries of the systemd journal, oldest first",
+long = "Each row contains the time of an entry, the unit it belongs to, its priority from 0 (emerg) to",
+long = "7 (debug), the identifier and process id of the program that logged it, and its message.",
+long = "",
+long = "With follow, the stream does not end, but new entries are output as they are logged.",
+long = "Reading the entries of other users and of the system may require being in the",
+long = "systemd-journal group.",
+example = "systemd:journal unit=\"nginx.service\" since=1h | where {priority < 4}")]
+pub struct Journal {
+    #[description("only return entries of this unit, like sshd.service.")]
+    unit: Option<String>,
+    #[description("only return entries of this priority or more important, either a number or a name like err.")]
+    priority: Option<Value>,
+    #[description("only return entries logged after this time, or within this duration before now.")]
+    since: Option<Value>,
+    #[default(false)]
+    #[description("only return entries of the current boot.")]
+    boot: bool,
+    #[description("only return this many of the latest entries.")]
+    lines: Option<i128>,
+    #[default(false)]
+    #[description("keep running and output new entries as they are logged.")]
+    follow: bool,
+    #[description("read the journal files in this directory instead of the system journal.")]
+    directory: Option<PathBuf>,
+}
+
+fn priority(value: Value) -> CrushResult<String> {
+    match value {
+        Value::Integer(i) if (0..8).contains(&i) => Ok(i.to_string()),
+        Value::String(s) if PRIORITIES.contains(&s.as_str()) => Ok(s),
+        _ => argument_error(format!("Expected the priority to be 0 to 7 or one of {}", PRIORITIES.join(", ")).as_str()),
+    }
+}
+
+fn journal(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Journal = Journal::parse(context.arguments, &context.printer)?;
+    let mut command = Command::new("journalctl");
+    if let Some(environment) = context.env.process_environment() {
+        environment.apply(&mut command);
+    }
+    command.args(["--output=json", "--no-pager", "--quiet"]);
+    if let Some(unit) = &cfg.unit {
+        command.arg(format!("--unit={}", unit));
+    }
+    if let Some(value) = cfg.priority {
+        command.arg(format!("--priority={}", priority(value)?));
+    }
+    match cfg.since {
+        None => {}
+        Some(Value::Time(t)) => { command.arg(format!("--since={}", t.format("%Y-%m-%d %H:%M:%S"))); }
+        Some(Value::Duration(d)) => { command.arg(format!("--since={}", (Local::now() - d).format("%Y-%m-%d %H:%M:%S"))); }
+        Some(v) => return argument_error(
+            format!("Expected since to be a time or a duration, got a value of type {}", v.value_type().to_string()).as_str()),
+    }
+    if cfg.boot {
+        command.arg("--boot");
+    }
+    if let Some(lines) = cfg.lines {
+        if lines < 0 {
+            return argument_error("The number of lines must not be negative");
+        }
+        command.arg(format!("--lines={}", lines));
+    }
+    if cfg.follow {
+        command.arg("--follow");
+    }
+    if let Some(directory) = &cfg.directory {
+        command.arg("--directory").arg(directory);
+    }
+    let output = context.output.initialize(JOURNAL_OUTPUT_TYPE.clone())?;
+    let (child, stdout) = external::spawn("journalctl", command)?;
+    let result = (|| {
+        for line in stdout.lines() {
+            let line = to_crush_error(line)?;
+            if let Ok(entry) = serde_json::from_str::<serde_json::Value>(&line) {
+                output.send(parse_entry(&entry))?;
+            }
+        }
+        Ok(())
+    })();
+    external::finish("journalctl", child, result)
+}
+
+/** Parses a line of systemctl list-units --plain --no-legend. */
+fn parse_unit(line: &str) -> Option<Row> {
+    let mut rest = line.trim_start_matches(|c: char| c == '●' || c == '*' || c.is_whitespace());
+    let mut cells = Vec::new();
+    for _ in 0..4 {
+        let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        if end == 0 {
+            return None;
+        }
+        cells.push(Value::string(&rest[..end]));
+        rest = rest[end..].trim_start();
+    }
+    cells.push(Value::string(rest.trim_end()));
+    Some(Row::new(cells))
+}
+
+#[signature(
+units,
+can_block = true,
+output = Known(ValueType::TableStream(UNITS_OUTPUT_TYPE.clone())),
+short = "Return a table stream of the units of systemd",
+long = "Each row contains the name of a unit, whether it was loaded, whether it is active, its more",
+long = "detailed state, like running or exited, and its description.",
+example = "systemd:units type=\"service\" | where {active == \"failed\"}")]
+pub struct Units {
+    #[description("only list units of this type, like service, timer or socket.")]
+    r#type: Option<String>,
+    #[description("only list units in this state, like running or failed.")]
+    state: Option<String>,
+    #[default(false)]
+    #[description("also list units that are not loaded or not active.")]
+    all: bool,
+    #[default(false)]
+    #[description("list the units of the user's service manager instead of the system's.")]
+    user: bool,
+}
+
+fn units(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Units = Units::parse(context.arguments, &context.printer)?;
+    let mut command = Command::new("systemctl");
+    if let Some(environment) = context.env.process_environment() {
+        environment.apply(&mut command);
+    }
+    command.args(["list-units", "--plain", "--no-legend", "--no-pager"]);
+    if let Some(t) = &cfg.r#type {
+        command.arg(format!("--type={}", t));
+    }
+    if let Some(state) = &cfg.state {
+        command.arg(format!("--state={}", state));
+    }
+    if cfg.all {
+        command.arg("--all");
+    }
+    if cfg.user {
+        command.arg("--user");
+    }
+    let output = context.output.initialize(UNITS_OUTPUT_TYPE.clone())?;
+    let (child, stdout) = external::spawn("systemctl", command)?;
+    let result = (|| {
+        for line in stdout.lines() {
+            if let Some(row) = parse_unit(&to_crush_error(line)?) {
+                output.send(row)?;
+            }
+        }
+        Ok(())
+    })();
+    external::finish("systemctl", child, result)
+}
+
+pub fn declare(root: &Scope) -> CrushResult<()> {
+    root.create_lazy_namespace(
+        "systemd",
+        Box::new(move |env| {
+            Journal::declare(env)?;
+            Units::declare(env)?;
+            Ok(())
+        }))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn entry() {
+        let row = parse_entry(&json!({
+            "__REALTIME_TIMESTAMP": "1600000000250000",
+            "_SYSTEMD_UNIT": "sshd.service",
+            "PRIORITY": "6",
+            "SYSLOG_IDENTIFIER": "sshd",
+            "_PID": "812",
+            "MESSAGE": [65, 255, 66],
+        }));
+        let cells = row.cells();
+        assert!(cells[0] == Value::Time(Local.timestamp(1600000000, 250_000_000)));
+        assert!(cells[1] == Value::string("sshd.service"));
+        assert!(cells[2] == Value::Integer(6));
+        assert!(cells[4] == Value::Integer(812));
+        assert!(cells[5] == Value::string("A\u{fffd}B"));
+        let row = parse_entry(&json!({"__REALTIME_TIMESTAMP": i64::MAX.to_string()}));
+        assert!(matches!(row.cells()[0], Value::Empty()));
+    }
+
+    #[test]
+    fn unit() {
+        let row = parse_unit("● nginx.service  loaded failed failed A high performance web server").unwrap();
+        let cells = row.cells();
+        assert!(cells[0] == Value::string("nginx.service"));
+        assert!(cells[2] == Value::string("failed"));
+        assert!(cells[4] == Value::string("A high performance web server"));
+        assert!(parse_unit("").is_none());
+    }
+}
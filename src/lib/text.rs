@@ -0,0 +1,348 @@
+use crate::lang::argument::ArgumentHandler;
+use crate::lang::command::OutputType::{Known, Unknown};
+use crate::lang::errors::{CrushResult, argument_error, mandate};
+use crate::lang::execution_context::ExecutionContext;
+use crate::lang::list::List;
+use crate::lang::scope::Scope;
+use crate::lang::value::{Value, ValueType};
+use crate::lib::broadcast::apply;
+use crate::lib::types::string::format::do_format;
+use signature::signature;
+
+fn string(value: &Value) -> CrushResult<&str> {
+    match value {
+        Value::String(s) => Ok(s),
+        v => argument_error(format!("Expected a string, got a {}", v.value_type().to_string()).as_str()),
+    }
+}
+
+fn subject(text: Option<Value>) -> CrushResult<Value> {
+    mandate(text, "Expected a string, a list of strings or a field")
+}
+
+/**
+Converts an index counted in characters, where negative indices count from the end, into a
+byte offset, clamped to the string.
+*/
+fn offset(s: &str, idx: i128) -> usize {
+    let len = s.chars().count() as i128;
+    let idx = if idx < 0 { (len + idx).max(0) } else { idx.min(len) } as usize;
+    s.char_indices().nth(idx).map(|(pos, _)| pos).unwrap_or_else(|| s.len())
+}
+
+#[signature(
+upper,
+can_block = false,
+output = Unknown,
+short = "Convert a string to upper case",
+long = "The string can also be a list of strings, in which case each element is converted, or a",
+long = "field, in which case that column of each row of the input is converted.",
+example = "ls | str:upper ^file")]
+struct Upper {
+    #[description("the string to convert.")]
+    text: Option<Value>,
+}
+
+fn upper(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Upper = Upper::parse(context.arguments, &context.printer)?;
+    apply(context.input, context.output, subject(cfg.text)?, Some(ValueType::String), |s| Ok(Value::String(string(s)?.to_uppercase())))
+}
+
+#[signature(
+lower,
+can_block = false,
+output = Unknown,
+short = "Convert a string to lower case",
+long = "The string can also be a list of strings, in which case each element is converted, or a",
+long = "field, in which case that column of each row of the input is converted.",
+example = "ls | str:lower ^file")]
+struct Lower {
+    #[description("the string to convert.")]
+    text: Option<Value>,
+}
+
+fn lower(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Lower = Lower::parse(context.arguments, &context.printer)?;
+    apply(context.input, context.output, subject(cfg.text)?, Some(ValueType::String), |s| Ok(Value::String(string(s)?.to_lowercase())))
+}
+
+#[signature(
+trim,
+can_block = false,
+output = Unknown,
+short = "Remove whitespace from both ends of a string",
+long = "The string can also be a list of strings, in which case each element is trimmed, or a",
+long = "field, in which case that column of each row of the input is trimmed.",
+example = "lines:from names.txt | str:trim ^line")]
+struct Trim {
+    #[description("the string to trim.")]
+    text: Option<Value>,
+}
+
+fn trim(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Trim = Trim::parse(context.arguments, &context.printer)?;
+    apply(context.input, context.output, subject(cfg.text)?, Some(ValueType::String), |s| Ok(Value::string(string(s)?.trim())))
+}
+
+#[signature(
+pad,
+can_block = false,
+output = Unknown,
+short = "Pad a string to a width",
+long = "Strings that are already at least as wide are left as they are. The string can also be a list",
+long = "of strings, in which case each element is padded, or a field, in which case that column of",
+long = "each row of the input is padded.",
+example = "ps | str:pad ^name 20 align=right")]
+struct Pad {
+    #[description("the string to pad.")]
+    text: Option<Value>,
+    #[description("the width to pad to, in characters.")]
+    width: i128,
+    #[default(" ")]
+    #[description("the character to pad with.")]
+    padding: String,
+    #[values("left", "right", "center")]
+    #[default("left")]
+    #[description("where to put the string within the padding.")]
+    align: String,
+}
+
+fn pad(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Pad = Pad::parse(context.arguments, &context.printer)?;
+    let mut padding = cfg.padding.chars();
+    let padding = match (padding.next(), padding.next()) {
+        (Some(c), None) => c.to_string(),
+        _ => return argument_error("Padding string must be exactly one character long"),
+    };
+    if cfg.width > 1 << 30 {
+        return argument_error("The width must be at most 2^30 characters");
+    }
+    let width = cfg.width.max(0) as usize;
+    let align = cfg.align;
+    apply(context.input, context.output, subject(cfg.text)?, Some(ValueType::String), |s| {
+        let s = string(s)?;
+        let missing = width.saturating_sub(s.chars().count());
+        let before = match align.as_str() {
+            "right" => missing,
+            "center" => missing / 2,
+            _ => 0,
+        };
+        Ok(Value::String(format!("{}{}{}", padding.repeat(before), s, padding.repeat(missing - before))))
+    })
+}
+
+#[signature(
+replace,
+can_block = false,
+output = Unknown,
+short = "Replace every occurrence of a string or a regular expression in a string",
+long = "With a regular expression, the replacement can refer to groups of the match, e.g. $1 or $name.",
+long = "The string can also be a list of strings, in which case each element is changed, or a field,",
+long = "in which case that column of each row of the input is changed.",
+example = "ls | str:replace ^file re\"\\.jpeg$\" \".jpg\"")]
+struct Replace {
+    #[description("the string to replace in.")]
+    text: Option<Value>,
+    #[description("the string or regular expression to replace.")]
+    pattern: Option<Value>,
+    #[description("the replacement.")]
+    replacement: String,
+}
+
+fn replace(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Replace = Replace::parse(context.arguments, &context.printer)?;
+    let replacement = cfg.replacement;
+    let subject = subject(cfg.text)?;
+    match cfg.pattern {
+        Some(Value::String(pattern)) =>
+            apply(context.input, context.output, subject, Some(ValueType::String), |s| Ok(Value::String(string(s)?.replace(&pattern, &replacement)))),
+        Some(Value::Regex(_, re)) =>
+            apply(context.input, context.output, subject, Some(ValueType::String), |s| Ok(Value::String(re.replace_all(string(s)?, replacement.as_str()).to_string()))),
+        _ => argument_error("Expected the pattern to be a string or a regular expression"),
+    }
+}
+
+#[signature(
+substring,
+can_block = false,
+output = Unknown,
+short = "The characters of a string between two positions",
+long = "Positions count characters from the start of the string, or from the end of it if they are",
+long = "negative. The string can also be a list of strings, or a field, in which case that column of",
+long = "each row of the input is used.",
+example = "str:substring \"crush shell\" 0 5")]
+struct Substring {
+    #[description("the string to take a part of.")]
+    text: Option<Value>,
+    #[description("the position of the first character.")]
+    from: i128,
+    #[description("the position after the last character. The default is the end of the string.")]
+    to: Option<i128>,
+}
+
+fn substring(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Substring = Substring::parse(context.arguments, &context.printer)?;
+    let (from, to) = (cfg.from, cfg.to);
+    apply(context.input, context.output, subject(cfg.text)?, Some(ValueType::String), |s| {
+        let s = string(s)?;
+        let start = offset(s, from);
+        let end = to.map(|to| offset(s, to)).unwrap_or_else(|| s.len()).max(start);
+        Ok(Value::string(&s[start..end]))
+    })
+}
+
+#[signature(
+starts_with,
+can_block = false,
+output = Unknown,
+short = "True if a string starts with a prefix",
+long = "The string can also be a list of strings, or a field, in which case that column of each row",
+long = "of the input is replaced with the result.",
+example = "ls | str:starts_with ^file \"./.\"")]
+struct StartsWith {
+    #[description("the string to check.")]
+    text: Option<Value>,
+    #[description("the prefix.")]
+    prefix: String,
+}
+
+fn starts_with(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: StartsWith = StartsWith::parse(context.arguments, &context.printer)?;
+    let prefix = cfg.prefix;
+    apply(context.input, context.output, subject(cfg.text)?, Some(ValueType::Bool), |s| Ok(Value::Bool(string(s)?.starts_with(&prefix))))
+}
+
+#[signature(
+ends_with,
+can_block = false,
+output = Unknown,
+short = "True if a string ends with a suffix",
+long = "The string can also be a list of strings, or a field, in which case that column of each row",
+long = "of the input is replaced with the result.",
+example = "ls | str:ends_with ^file \".rs\"")]
+struct EndsWith {
+    #[description("the string to check.")]
+    text: Option<Value>,
+    #[description("the suffix.")]
+    suffix: String,
+}
+
+fn ends_with(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: EndsWith = EndsWith::parse(context.arguments, &context.printer)?;
+    let suffix = cfg.suffix;
+    apply(context.input, context.output, subject(cfg.text)?, Some(ValueType::Bool), |s| Ok(Value::Bool(string(s)?.ends_with(&suffix))))
+}
+
+#[signature(
+split,
+can_block = false,
+output = Unknown,
+short = "Split a string into a list of strings",
+long = "Without a separator, the string is split on whitespace, and empty parts are left out. The",
+long = "string can also be a list of strings, or a field, in which case that column of each row of",
+long = "the input is split.",
+example = "str:split \"a,b;c\" re\"[,;]\"")]
+struct Split {
+    #[description("the string to split.")]
+    text: Option<Value>,
+    #[description("the string or regular expression that separates the parts.")]
+    separator: Option<Value>,
+}
+
+fn split(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Split = Split::parse(context.arguments, &context.printer)?;
+    let list = |parts: Vec<&str>| Ok(Value::List(List::new(ValueType::String, parts.into_iter().map(Value::string).collect())));
+    let result_type = Some(ValueType::List(Box::from(ValueType::String)));
+    let subject = subject(cfg.text)?;
+    match cfg.separator {
+        None => apply(context.input, context.output, subject, result_type, |s| list(string(s)?.split_whitespace().collect())),
+        Some(Value::String(separator)) => apply(context.input, context.output, subject, result_type, |s| list(string(s)?.split(separator.as_str()).collect())),
+        Some(Value::Regex(_, re)) => apply(context.input, context.output, subject, result_type, |s| list(re.split(string(s)?).collect())),
+        _ => argument_error("Expected the separator to be a string or a regular expression"),
+    }
+}
+
+fn join_list(list: &Value, separator: &str) -> CrushResult<Value> {
+    match list {
+        Value::List(l) => Ok(Value::String(
+            l.dump().iter().map(|v| v.to_string()).collect::<Vec<_>>().join(separator))),
+        v => argument_error(format!("Expected a list, got a {}", v.value_type().to_string()).as_str()),
+    }
+}
+
+#[signature(
+join,
+can_block = false,
+output = Unknown,
+short = "Join the elements of a list into a string",
+long = "The list can also be a field, in which case that column of each row of the input, which must",
+long = "contain lists, is joined.",
+example = "str:join (list:of \"a\" \"b\" \"c\") \", \"")]
+struct Join {
+    #[description("the list to join.")]
+    list: Option<Value>,
+    #[default("")]
+    #[description("the string to put between the elements.")]
+    separator: String,
+}
+
+fn join(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Join = Join::parse(context.arguments, &context.printer)?;
+    let separator = cfg.separator;
+    match mandate(cfg.list, "Expected a list or a field")? {
+        Value::Field(f) => apply(context.input, context.output, Value::Field(f), Some(ValueType::String), |l| join_list(l, &separator)),
+        l => context.output.send(join_list(&l, &separator)?),
+    }
+}
+
+fn format(mut context: ExecutionContext) -> CrushResult<()> {
+    if context.arguments.is_empty() {
+        return argument_error("Expected a format string");
+    }
+    let pattern = context.arguments.remove(0).value;
+    context.output.send(Value::String(do_format(string(&pattern)?, context.arguments)?))
+}
+
+pub fn declare(root: &Scope) -> CrushResult<()> {
+    root.create_lazy_namespace(
+        "str",
+        Box::new(move |env| {
+            Upper::declare(env)?;
+            Lower::declare(env)?;
+            Trim::declare(env)?;
+            Pad::declare(env)?;
+            Replace::declare(env)?;
+            Substring::declare(env)?;
+            StartsWith::declare(env)?;
+            EndsWith::declare(env)?;
+            Split::declare(env)?;
+            Join::declare(env)?;
+            env.declare_command(
+                "format", format, false,
+                "str:format pattern:string [parameters:any]...",
+                "Format arguments into a string",
+                Some(r#"    Each {} in the pattern is replaced with the next parameter, {N} with parameter
+    number N and {name} with the named parameter name. Use {{ and }} for literal braces.
+
+    Example:
+
+    str:format "{} is {age} years old" "Ada" age=36"#),
+                Known(ValueType::String))?;
+            Ok(())
+        }))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn offsets() {
+        assert_eq!(offset("héllo", 0), 0);
+        assert_eq!(offset("héllo", 2), 3);
+        assert_eq!(offset("héllo", -1), 5);
+        assert_eq!(offset("héllo", 10), 6);
+        assert_eq!(offset("héllo", -10), 0);
+    }
+}
@@ -0,0 +1,235 @@
+use std::collections::HashSet;
+use std::fs;
+use std::fs::Metadata;
+use std::os::unix::fs::MetadataExt;
+use std::path::PathBuf;
+use std::sync::{Arc, Condvar, Mutex};
+
+use lazy_static::lazy_static;
+
+use crate::lang::argument::ArgumentHandler;
+use crate::lang::command::OutputType::Known;
+use crate::lang::errors::{CrushResult, argument_error, to_crush_error};
+use crate::lang::execution_context::ExecutionContext;
+use crate::lang::files::Files;
+use crate::lang::{value::Value, value::ValueType, table::ColumnType, table::Row};
+use crate::util::thread::build;
+use signature::signature;
+
+lazy_static! {
+    static ref OUTPUT_TYPE: Vec<ColumnType> = vec![
+        ColumnType::new("size", ValueType::Integer),
+        ColumnType::new("files", ValueType::Integer),
+        ColumnType::new("directory", ValueType::File),
+    ];
+}
+
+/**
+The most threads used to scan directories. Scanning is mostly waiting for the file system, so
+more threads than cores helps, but only up to a point.
+*/
+const MAX_THREADS: usize = 16;
+
+struct Options {
+    apparent: bool,
+    count_links: bool,
+    follow_links: bool,
+}
+
+/**
+A directory (or a single file given on the command line) along with the total size of and
+number of files directly inside it. Totals for whole trees are added up once scanning is done.
+*/
+struct Entry {
+    path: PathBuf,
+    parent: Option<usize>,
+    depth: usize,
+    size: u64,
+    files: u64,
+}
+
+struct State {
+    entries: Vec<Entry>,
+    /** Entries that are waiting to be scanned. */
+    queue: Vec<usize>,
+    /** The number of entries currently being scanned. */
+    active: usize,
+}
+
+struct Walker {
+    options: Options,
+    state: Mutex<State>,
+    changed: Condvar,
+    /** Files and directories that have already been counted, by device and inode. */
+    seen: Mutex<HashSet<(u64, u64)>>,
+}
+
+impl Walker {
+    fn metadata(&self, path: &PathBuf) -> std::io::Result<Metadata> {
+        if self.options.follow_links { fs::metadata(path) } else { fs::symlink_metadata(path) }
+    }
+
+    fn size(&self, meta: &Metadata) -> u64 {
+        if self.options.apparent { meta.len() } else { meta.blocks() * 512 }
+    }
+
+    /**
+    Returns true if the file should be counted. Files with several hard links are only counted
+    the first time they are found, and so are directories reached through symbolic links,
+    which might otherwise be counted forever.
+    */
+    fn first_visit(&self, meta: &Metadata) -> bool {
+        let check = if meta.is_dir() { self.options.follow_links } else { !self.options.count_links && meta.nlink() > 1 };
+        !check || self.seen.lock().unwrap().insert((meta.dev(), meta.ino()))
+    }
+
+    /** Wait for an entry to scan, or return None if everything has been scanned. */
+    fn next(&self) -> Option<(usize, PathBuf)> {
+        let mut state = self.state.lock().unwrap();
+        loop {
+            if let Some(idx) = state.queue.pop() {
+                state.active += 1;
+                return Some((idx, state.entries[idx].path.clone()));
+            }
+            if state.active == 0 {
+                return None;
+            }
+            state = self.changed.wait(state).unwrap();
+        }
+    }
+
+    fn scan(&self, idx: usize, path: &PathBuf) {
+        let mut size = 0;
+        let mut files = 0;
+        let mut directories = Vec::new();
+        // Directories that can't be read are skipped, like find does
+        if let Ok(dir) = fs::read_dir(path) {
+            for entry in dir.flatten() {
+                let entry_path = entry.path();
+                let meta = match self.metadata(&entry_path) {
+                    Ok(meta) => meta,
+                    Err(_) => continue,
+                };
+                if !self.first_visit(&meta) {
+                    continue;
+                }
+                if meta.is_dir() {
+                    directories.push((entry_path, self.size(&meta)));
+                } else {
+                    size += self.size(&meta);
+                    files += 1;
+                }
+            }
+        }
+
+        let mut state = self.state.lock().unwrap();
+        let depth = state.entries[idx].depth + 1;
+        state.entries[idx].size += size;
+        state.entries[idx].files += files;
+        for (path, size) in directories {
+            let child = state.entries.len();
+            state.entries.push(Entry { path, parent: Some(idx), depth, size, files: 0 });
+            state.queue.push(child);
+        }
+        state.active -= 1;
+        self.changed.notify_all();
+    }
+
+    fn run(self: Arc<Self>) {
+        while let Some((idx, path)) = self.next() {
+            self.scan(idx, &path);
+        }
+    }
+}
+
+#[signature(
+du,
+can_block = true,
+short = "Summarize the disk usage of directories",
+long = "Outputs the total size of and the number of files in every directory in the specified trees.",
+long = "The directories are scanned in parallel. Files with several hard links are only counted",
+long = "once, unless count_links is set.",
+example = "du . max_depth=1 | sort ^size",
+output = Known(ValueType::TableStream(OUTPUT_TYPE.clone())))]
+pub struct Du {
+    #[unnamed()]
+    #[description("directories to summarize.")]
+    directory: Files,
+    #[description("do not list directories deeper than this. Their sizes are still counted.")]
+    max_depth: Option<i128>,
+    #[description("count the number of bytes in files instead of the disk space they use.")]
+    #[default(false)]
+    apparent: bool,
+    #[description("count files with several hard links every time they are found.")]
+    #[default(false)]
+    count_links: bool,
+    #[description("follow symbolic links, and count the files they point to.")]
+    #[default(false)]
+    follow_links: bool,
+}
+
+fn du(context: ExecutionContext) -> CrushResult<()> {
+    let output = context.output.initialize(OUTPUT_TYPE.clone())?;
+    let config: Du = Du::parse(context.arguments, &context.printer)?;
+    let max_depth = match config.max_depth {
+        Some(d) if d < 0 => return argument_error("Expected max_depth to be non-negative"),
+        d => d.map(|d| d as usize),
+    };
+    let roots = if config.directory.had_entries() {
+        config.directory.into_vec()
+    } else {
+        vec![PathBuf::from(".")]
+    };
+
+    let walker = Arc::new(Walker {
+        options: Options {
+            apparent: config.apparent,
+            count_links: config.count_links,
+            follow_links: config.follow_links,
+        },
+        state: Mutex::new(State { entries: Vec::new(), queue: Vec::new(), active: 0 }),
+        changed: Condvar::new(),
+        seen: Mutex::new(HashSet::new()),
+    });
+    for path in roots {
+        let meta = to_crush_error(walker.metadata(&path))?;
+        walker.first_visit(&meta);
+        let mut state = walker.state.lock().unwrap();
+        let idx = state.entries.len();
+        let is_dir = meta.is_dir();
+        state.entries.push(Entry { path, parent: None, depth: 0, size: walker.size(&meta), files: if is_dir { 0 } else { 1 } });
+        if is_dir {
+            state.queue.push(idx);
+        }
+    }
+
+    let threads = std::thread::available_parallelism().map(|n| n.get() * 2).unwrap_or(4).min(MAX_THREADS);
+    let mut handles = Vec::new();
+    for _ in 0..threads {
+        let walker = walker.clone();
+        handles.push(to_crush_error(build("du").spawn(move || walker.run()))?);
+    }
+    for handle in handles {
+        let _ = handle.join();
+    }
+
+    let mut entries = std::mem::take(&mut walker.state.lock().unwrap().entries);
+    // Children are always added after their parent, so walking backwards adds up whole trees
+    for idx in (0..entries.len()).rev() {
+        if let Some(parent) = entries[idx].parent {
+            entries[parent].size += entries[idx].size;
+            entries[parent].files += entries[idx].files;
+        }
+    }
+    entries.sort_by(|a, b| a.path.cmp(&b.path));
+    for entry in entries {
+        if max_depth.map(|d| entry.depth <= d).unwrap_or(true) {
+            output.send(Row::new(vec![
+                Value::Integer(i128::from(entry.size)),
+                Value::Integer(i128::from(entry.files)),
+                Value::File(entry.path),
+            ]))?;
+        }
+    }
+    Ok(())
+}
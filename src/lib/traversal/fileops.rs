@@ -0,0 +1,310 @@
+use std::cell::RefCell;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+use lazy_static::lazy_static;
+
+use crate::lang::execution_context::ExecutionContext;
+use crate::lang::errors::{CrushResult, argument_error};
+use crate::lang::{value::Value, value::ValueType, table::ColumnType, table::Row};
+use crate::lang::stream::{OutputStream, ValueSender};
+use crate::lang::command::OutputType::Known;
+use crate::lang::files::Files;
+use signature::signature;
+use crate::lang::argument::ArgumentHandler;
+
+lazy_static! {
+    static ref OUTPUT_TYPE: Vec<ColumnType> = vec![
+        ColumnType::new("file", ValueType::File),
+        ColumnType::new("bytes", ValueType::Integer),
+        ColumnType::new("status", ValueType::String),
+        ColumnType::new("error", ValueType::String),
+    ];
+}
+
+/**
+Outputs one row for every file that was copied, moved or removed, or that could not be.
+Progress without an output stream discards the rows of successful files and keeps the
+failures, so that they can be reported later.
+*/
+struct Progress {
+    output: Option<OutputStream>,
+    failures: RefCell<Vec<(PathBuf, std::io::Error)>>,
+}
+
+impl Progress {
+    fn new(output: &ValueSender) -> CrushResult<Progress> {
+        Ok(Progress { output: Some(output.initialize(OUTPUT_TYPE.clone())?), failures: RefCell::new(vec![]) })
+    }
+
+    fn collect() -> Progress {
+        Progress { output: None, failures: RefCell::new(vec![]) }
+    }
+
+    fn report(&self, file: &Path, bytes: u64, result: std::io::Result<&str>) -> CrushResult<()> {
+        let (status, error) = match result {
+            Ok(status) => (status.to_string(), String::new()),
+            Err(e) if self.output.is_none() => {
+                self.failures.borrow_mut().push((file.to_path_buf(), e));
+                return Ok(());
+            }
+            Err(e) => ("failed".to_string(), e.to_string()),
+        };
+        match &self.output {
+            Some(output) => output.send(Row::new(vec![
+                Value::File(file.to_path_buf()),
+                Value::Integer(i128::from(bytes)),
+                Value::string(&status),
+                Value::string(&error),
+            ])),
+            None => Ok(()),
+        }
+    }
+}
+
+fn exists(path: &Path) -> bool {
+    fs::symlink_metadata(path).is_ok()
+}
+
+fn already_exists(destination: &Path) -> std::io::Error {
+    std::io::Error::new(
+        ErrorKind::AlreadyExists,
+        format!("{} already exists", destination.to_string_lossy()))
+}
+
+/**
+Split the arguments of cp and mv into the sources and the destination of every source.
+If the last argument is an existing directory, every source is put inside of it.
+*/
+fn destinations(files: Files) -> CrushResult<Vec<(PathBuf, PathBuf)>> {
+    let mut files = files.into_vec();
+    if files.len() < 2 {
+        return argument_error("Expected at least one source and a destination");
+    }
+    let destination = files.pop().unwrap();
+    if destination.is_dir() {
+        files.into_iter()
+            .map(|source| match source.file_name() {
+                Some(name) => {
+                    let target = destination.join(name);
+                    Ok((source, target))
+                }
+                None => argument_error(format!("Invalid source {}", source.to_string_lossy()).as_str()),
+            })
+            .collect()
+    } else if files.len() == 1 {
+        Ok(vec![(files.remove(0), destination)])
+    } else {
+        argument_error("Expected the destination to be a directory when there is more than one source")
+    }
+}
+
+/** Returns true if the destination is inside of the source directory. */
+fn is_inside(destination: &Path, source: &Path) -> bool {
+    let parent = match destination.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    match (fs::canonicalize(parent), fs::canonicalize(source)) {
+        (Ok(parent), Ok(source)) => parent.starts_with(source),
+        _ => false,
+    }
+}
+
+fn copy(source: &Path, destination: &Path, recursive: bool, force: bool, progress: &Progress) -> CrushResult<()> {
+    let meta = match fs::symlink_metadata(source) {
+        Ok(meta) => meta,
+        Err(e) => return progress.report(source, 0, Err(e)),
+    };
+    if meta.is_dir() {
+        if !recursive {
+            return progress.report(source, 0, Ok("skipped"));
+        }
+        if !destination.is_dir() {
+            if let Err(e) = fs::create_dir(destination) {
+                return progress.report(source, 0, Err(e));
+            }
+        }
+        let entries = match fs::read_dir(source) {
+            Ok(entries) => entries,
+            Err(e) => return progress.report(source, 0, Err(e)),
+        };
+        for entry in entries {
+            match entry {
+                Ok(entry) => copy(&entry.path(), &destination.join(entry.file_name()), recursive, force, progress)?,
+                Err(e) => progress.report(source, 0, Err(e))?,
+            }
+        }
+        Ok(())
+    } else if exists(destination) && !force {
+        progress.report(source, 0, Err(already_exists(destination)))
+    } else if meta.file_type().is_symlink() {
+        let result = fs::read_link(source)
+            .and_then(|target| {
+                if exists(destination) {
+                    fs::remove_file(destination)?;
+                }
+                std::os::unix::fs::symlink(target, destination)
+            })
+            .map(|_| "copied");
+        progress.report(source, 0, result)
+    } else {
+        match fs::copy(source, destination) {
+            Ok(bytes) => progress.report(source, bytes, Ok("copied")),
+            Err(e) => progress.report(source, 0, Err(e)),
+        }
+    }
+}
+
+fn remove(file: &Path, recursive: bool, force: bool, progress: &Progress) -> CrushResult<()> {
+    let meta = match fs::symlink_metadata(file) {
+        Ok(meta) => meta,
+        Err(e) if force && e.kind() == ErrorKind::NotFound => return Ok(()),
+        Err(e) => return progress.report(file, 0, Err(e)),
+    };
+    if meta.is_dir() {
+        if !recursive {
+            return progress.report(file, 0, Ok("skipped"));
+        }
+        match fs::read_dir(file) {
+            Ok(entries) => {
+                for entry in entries {
+                    match entry {
+                        Ok(entry) => remove(&entry.path(), recursive, force, progress)?,
+                        Err(e) => progress.report(file, 0, Err(e))?,
+                    }
+                }
+            }
+            Err(e) => return progress.report(file, 0, Err(e)),
+        }
+        progress.report(file, 0, fs::remove_dir(file).map(|_| "removed"))
+    } else {
+        progress.report(file, meta.len(), fs::remove_file(file).map(|_| "removed"))
+    }
+}
+
+#[signature(
+cp,
+can_block = true,
+short = "Copy files and directories",
+long = "If the last file is an existing directory, all other files are copied into it. Otherwise,",
+long = "exactly one file must be given, which is copied to the last file.",
+long = "",
+long = "A row is output for every file copied, containing the file, the number of bytes copied, the",
+long = "status, which is one of copied, skipped and failed, and the error message of failed files.",
+long = "Failures don't stop the remaining files from being copied. A directory can't be copied into",
+long = "itself.",
+example = "cp %.rs ./backup | where {status == \"failed\"}",
+output = Known(ValueType::TableStream(OUTPUT_TYPE.clone())))]
+pub struct Cp {
+    #[unnamed()]
+    #[description("the files to copy, followed by the destination.")]
+    files: Files,
+    #[default(false)]
+    #[description("copy the contents of directories. Directories are skipped otherwise.")]
+    recursive: bool,
+    #[default(false)]
+    #[description("overwrite existing files.")]
+    force: bool,
+}
+
+fn cp(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Cp = Cp::parse(context.arguments, &context.printer)?;
+    let pairs = destinations(cfg.files)?;
+    let progress = Progress::new(&context.output)?;
+    for (source, destination) in pairs {
+        if source.is_dir() && is_inside(&destination, &source) {
+            progress.report(&source, 0, Err(std::io::Error::new(
+                ErrorKind::InvalidInput,
+                format!("Can't copy {} into itself", source.to_string_lossy()))))?;
+            continue;
+        }
+        copy(&source, &destination, cfg.recursive, cfg.force, &progress)?;
+    }
+    Ok(())
+}
+
+#[signature(
+mv,
+can_block = true,
+short = "Move files and directories",
+long = "If the last file is an existing directory, all other files are moved into it. Otherwise,",
+long = "exactly one file must be given, which is renamed to the last file. Files that can't be",
+long = "renamed, because they are on a different file system, are copied and then removed. If",
+long = "any part of such a file can't be copied, the failures are reported and the file is kept.",
+long = "",
+long = "A row is output for every file moved, with the same columns as the output of cp.",
+example = "mv ./a.txt ./b.txt ./archive",
+output = Known(ValueType::TableStream(OUTPUT_TYPE.clone())))]
+pub struct Mv {
+    #[unnamed()]
+    #[description("the files to move, followed by the destination.")]
+    files: Files,
+    #[default(false)]
+    #[description("overwrite existing files.")]
+    force: bool,
+}
+
+fn mv(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Mv = Mv::parse(context.arguments, &context.printer)?;
+    let pairs = destinations(cfg.files)?;
+    let progress = Progress::new(&context.output)?;
+    for (source, destination) in pairs {
+        if exists(&destination) && !cfg.force {
+            progress.report(&source, 0, Err(already_exists(&destination)))?;
+            continue;
+        }
+        let bytes = fs::symlink_metadata(&source).map(|m| m.len()).unwrap_or(0);
+        match fs::rename(&source, &destination) {
+            Ok(_) => progress.report(&source, bytes, Ok("moved"))?,
+            // EXDEV, the source and destination are on different file systems
+            Err(e) if e.raw_os_error() == Some(nix::libc::EXDEV) => {
+                let copied = Progress::collect();
+                copy(&source, &destination, true, cfg.force, &copied)?;
+                let failures = copied.failures.into_inner();
+                if failures.is_empty() {
+                    let removed = if source.is_dir() { fs::remove_dir_all(&source) } else { fs::remove_file(&source) };
+                    progress.report(&source, bytes, removed.map(|_| "moved"))?;
+                } else {
+                    // Keep the source, so that nothing is lost when only part of it could be copied
+                    for (file, e) in failures {
+                        progress.report(&file, 0, Err(e))?;
+                    }
+                }
+            }
+            Err(e) => progress.report(&source, 0, Err(e))?,
+        }
+    }
+    Ok(())
+}
+
+#[signature(
+rm,
+can_block = true,
+short = "Remove files and directories",
+long = "A row is output for every file removed, containing the file, its size, the status, which is",
+long = "one of removed, skipped and failed, and the error message of failed files. Failures don't",
+long = "stop the remaining files from being removed.",
+example = "rm ./build recursive=true",
+output = Known(ValueType::TableStream(OUTPUT_TYPE.clone())))]
+pub struct Rm {
+    #[unnamed()]
+    #[description("the files to remove.")]
+    files: Files,
+    #[default(false)]
+    #[description("remove directories and their contents. Directories are skipped otherwise.")]
+    recursive: bool,
+    #[default(false)]
+    #[description("silently ignore files that don't exist.")]
+    force: bool,
+}
+
+fn rm(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Rm = Rm::parse(context.arguments, &context.printer)?;
+    let progress = Progress::new(&context.output)?;
+    for file in cfg.files.into_vec() {
+        remove(&file, cfg.recursive, cfg.force, &progress)?;
+    }
+    Ok(())
+}
@@ -1,8 +1,8 @@
-use std::collections::{HashMap, VecDeque};
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::fs;
 use std::fs::Metadata;
 use std::os::unix::fs::MetadataExt;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use chrono::{DateTime, Local};
 use users::uid_t;
@@ -13,12 +13,14 @@ use lazy_static::lazy_static;
 use crate::lang::execution_context::ExecutionContext;
 use crate::util::user_map::{create_user_map, UserMap};
 use crate::lang::{value::Value, value::ValueType, table::ColumnType, table::Row};
-use crate::lang::errors::{error, CrushResult, to_crush_error};
+use crate::lang::errors::{error, argument_error, send_error, CrushError, CrushResult, Kind, to_crush_error};
 use crate::lang::stream::OutputStream;
 use signature::signature;
 use crate::lang::argument::ArgumentHandler;
 use crate::lang::files::Files;
 use crate::lang::command::OutputType::Known;
+use crate::lang::value::Time;
+use crate::lang::printer::Printer;
 
 lazy_static! {
     static ref OUTPUT_TYPE: Vec<ColumnType> = vec![
@@ -26,78 +28,161 @@ lazy_static! {
         ColumnType::new("size", ValueType::Integer),
         ColumnType::new("modified", ValueType::Time),
         ColumnType::new("type", ValueType::String),
+        ColumnType::new("permissions", ValueType::String),
         ColumnType::new("file", ValueType::File),
     ];
 }
 
-fn insert_entity(
-    meta: &Metadata,
-    file: PathBuf,
-    users: &HashMap<uid_t, User>,
-    output: &mut OutputStream) -> CrushResult<()> {
-    let modified_system = to_crush_error(meta.modified())?;
-    let modified_datetime: DateTime<Local> = DateTime::from(modified_system);
-    let f = if file.starts_with("./") {
-        let b = file.to_str().map(|s| PathBuf::from(&s[2..]));
-        b.unwrap_or(file)
-    } else {
-        file
-    };
-    let file_type = meta.file_type();
-    let type_str = if file_type.is_dir() {
-        "directory"
-    } else if file_type.is_symlink() {
-        "symlink"
-    } else {
-        "file"
-    };
+fn permissions(mode: u32) -> String {
+    let mut res = String::with_capacity(9);
+    for (bit, c) in [
+        (0o400, 'r'), (0o200, 'w'), (0o100, 'x'),
+        (0o040, 'r'), (0o020, 'w'), (0o010, 'x'),
+        (0o004, 'r'), (0o002, 'w'), (0o001, 'x')].iter() {
+        res.push(if mode & bit != 0 { *c } else { '-' });
+    }
+    res
+}
 
-    output.send(Row::new(vec![
-        users.get_name(meta.uid()),
-        Value::Integer(i128::from(meta.len())),
-        Value::Time(modified_datetime),
-        Value::string(type_str),
-        Value::File(f)]))?;
-    Ok(())
+/**
+The filters a file must pass in order to be listed. Files that don't pass are still
+descended into.
+*/
+struct Filter {
+    min_depth: usize,
+    name: Option<Value>,
+    min_size: Option<u64>,
+    max_size: Option<u64>,
+    modified_after: Option<Time>,
+    modified_before: Option<Time>,
 }
 
-fn run_for_single_directory_or_file(
-    path: PathBuf,
-    users: &HashMap<uid_t, User>,
-    recursive: bool,
-    q: &mut VecDeque<PathBuf>,
-    output: &mut OutputStream) -> CrushResult<()> {
-    if path.is_dir() {
-        let dirs = fs::read_dir(path);
-        for maybe_entry in to_crush_error(dirs)? {
-            let entry = to_crush_error(maybe_entry)?;
-            insert_entity(
-                &to_crush_error(entry.metadata())?,
-                entry.path(),
-                &users,
-                output)?;
-            if recursive && entry.path().is_dir() && (!(entry.file_name().eq(".") || entry.file_name().eq(".."))) {
-                q.push_back(entry.path());
+impl Filter {
+    fn matches(&self, depth: usize, file: &Path, meta: &Metadata, modified: &Time) -> bool {
+        if depth < self.min_depth {
+            return false;
+        }
+        if let Some(pattern) = &self.name {
+            let name = file.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+            let matches = match pattern {
+                Value::Glob(g) => g.matches(&name),
+                Value::Regex(_, re) => re.is_match(&name),
+                _ => false,
+            };
+            if !matches {
+                return false;
             }
         }
-    } else {
-        match path.file_name() {
-            Some(_) => {
-                insert_entity(
-                    &to_crush_error(path.metadata())?,
-                    path,
-                    &users,
-                    output)?;
+        self.min_size.map(|s| meta.len() >= s).unwrap_or(true) &&
+            self.max_size.map(|s| meta.len() <= s).unwrap_or(true) &&
+            self.modified_after.map(|t| *modified >= t).unwrap_or(true) &&
+            self.modified_before.map(|t| *modified <= t).unwrap_or(true)
+    }
+}
+
+struct Walker<'a> {
+    filter: Filter,
+    max_depth: Option<usize>,
+    follow_links: bool,
+    users: HashMap<uid_t, User>,
+    visited: HashSet<(u64, u64)>,
+    output: &'a mut OutputStream,
+    printer: &'a Printer,
+}
+
+impl Walker<'_> {
+    fn metadata(&self, file: &PathBuf) -> CrushResult<Metadata> {
+        to_crush_error(if self.follow_links { fs::metadata(file) } else { fs::symlink_metadata(file) })
+    }
+
+    fn insert_entity(&mut self, depth: usize, meta: &Metadata, file: PathBuf) -> CrushResult<()> {
+        let modified_system = to_crush_error(meta.modified())?;
+        let modified_datetime: DateTime<Local> = DateTime::from(modified_system);
+        if !self.filter.matches(depth, &file, meta, &modified_datetime) {
+            return Ok(());
+        }
+        let f = if file.starts_with("./") {
+            let b = file.to_str().map(|s| PathBuf::from(&s[2..]));
+            b.unwrap_or(file)
+        } else {
+            file
+        };
+        let file_type = meta.file_type();
+        let type_str = if file_type.is_dir() {
+            "directory"
+        } else if file_type.is_symlink() {
+            "symlink"
+        } else {
+            "file"
+        };
+
+        let row = Row::new(vec![
+            self.users.get_name(meta.uid()),
+            Value::Integer(i128::from(meta.len())),
+            Value::Time(modified_datetime),
+            Value::string(type_str),
+            Value::string(&permissions(meta.mode())),
+            Value::File(f)]);
+        // Sending only fails when nothing is reading the output any more.
+        match self.output.send(row) {
+            Ok(()) => Ok(()),
+            Err(_) => send_error(),
+        }
+    }
+
+    /** Returns true if the directory with the specified metadata has not been visited before. */
+    fn first_visit(&mut self, meta: &Metadata) -> bool {
+        !self.follow_links || self.visited.insert((meta.dev(), meta.ino()))
+    }
+
+    fn run_for_single_directory_or_file(
+        &mut self,
+        path: PathBuf,
+        depth: usize,
+        q: &mut VecDeque<(PathBuf, usize)>) -> CrushResult<()> {
+        let meta = self.metadata(&path)?;
+        if meta.is_dir() {
+            if self.max_depth.map(|d| depth >= d).unwrap_or(false) || !self.first_visit(&meta) {
+                return Ok(());
+            }
+            let dirs = fs::read_dir(path);
+            for maybe_entry in to_crush_error(dirs)? {
+                let entry = to_crush_error(maybe_entry)?;
+                let entry_meta = match self.metadata(&entry.path()) {
+                    Ok(meta) => meta,
+                    Err(e) => {
+                        // E.g. a dangling symlink when following links; skip just this entry.
+                        self.printer.error(format!("{}: {}", entry.path().to_string_lossy(), e.message).as_str());
+                        continue;
+                    }
+                };
+                self.insert_entity(depth + 1, &entry_meta, entry.path())?;
+                if entry_meta.is_dir() && self.max_depth.map(|d| depth + 1 < d).unwrap_or(true) {
+                    q.push_back((entry.path(), depth + 1));
+                }
             }
-            None => {
-                return error("Invalid file name");
+        } else {
+            match path.file_name() {
+                Some(_) => {
+                    self.insert_entity(depth, &meta, path)?;
+                }
+                None => {
+                    return error("Invalid file name");
+                }
             }
         }
+        Ok(())
     }
-    Ok(())
 }
 
-#[signature(find, short="Recursively list files", output=Known(ValueType::TableStream(OUTPUT_TYPE.clone())))]
+#[signature(
+find,
+short = "Recursively list files",
+long = "All filters except max_depth only decide which files are listed, so directories that don't",
+long = "match are still searched. The depth of a file is the number of directories between it and",
+long = "the directory it was found in, so the files directly inside a listed directory have depth 1.",
+example = "find . name=%.rs min_size=10000 modified_after=(time:now) - (duration:new days=7)",
+output = Known(ValueType::TableStream(OUTPUT_TYPE.clone())))]
 pub struct Find {
     #[unnamed()]
     #[description("directories and files to list")]
@@ -105,26 +190,79 @@ pub struct Find {
     #[description("recurse into subdirectories")]
     #[default(true)]
     recursive: bool,
+    #[description("do not list files deeper than this.")]
+    max_depth: Option<i128>,
+    #[description("do not list files shallower than this.")]
+    #[default(0)]
+    min_depth: i128,
+    #[description("only list files whose name matches this glob or regex.")]
+    name: Option<Value>,
+    #[description("only list files of at least this many bytes.")]
+    min_size: Option<i128>,
+    #[description("only list files of at most this many bytes.")]
+    max_size: Option<i128>,
+    #[description("only list files modified at or after this time.")]
+    modified_after: Option<Time>,
+    #[description("only list files modified at or before this time.")]
+    modified_before: Option<Time>,
+    #[description("follow symbolic links, and list the files they point to.")]
+    #[default(false)]
+    follow_links: bool,
+}
+
+fn non_negative(value: Option<i128>, name: &str) -> CrushResult<Option<u64>> {
+    match value {
+        Some(v) if v < 0 => argument_error(format!("Expected {} to be non-negative", name).as_str()),
+        Some(v) => Ok(Some(v as u64)),
+        None => Ok(None),
+    }
 }
 
 fn find(context: ExecutionContext) -> CrushResult<()> {
     let mut output = context.output.initialize(OUTPUT_TYPE.clone())?;
     let config: Find = Find::parse(context.arguments, &context.printer)?;
 
+    match &config.name {
+        None | Some(Value::Glob(_)) | Some(Value::Regex(_, _)) => {}
+        Some(v) => return argument_error(
+            format!("Expected name to be a glob or a regex, got a value of type {}",
+                    v.value_type().to_string()).as_str()),
+    }
+
+    let max_depth = if config.recursive {
+        non_negative(config.max_depth, "max_depth")?.map(|d| d as usize)
+    } else {
+        Some(1)
+    };
+    let mut walker = Walker {
+        filter: Filter {
+            min_depth: non_negative(Some(config.min_depth), "min_depth")?.unwrap() as usize,
+            name: config.name,
+            min_size: non_negative(config.min_size, "min_size")?,
+            max_size: non_negative(config.max_size, "max_size")?,
+            modified_after: config.modified_after,
+            modified_before: config.modified_before,
+        },
+        max_depth,
+        follow_links: config.follow_links,
+        users: create_user_map(),
+        visited: HashSet::new(),
+        output: &mut output,
+        printer: &context.printer,
+    };
+
     let mut dir = if config.directory.had_entries() {
         config.directory.into_vec()
     } else {
         vec![PathBuf::from(".")]
     };
-    let users = create_user_map();
     let mut q = VecDeque::new();
-    q.extend(dir.drain(..));
-    loop {
-        if q.is_empty() {
-            break;
+    q.extend(dir.drain(..).map(|d| (d, 0)));
+    while let Some((dir, depth)) = q.pop_front() {
+        match walker.run_for_single_directory_or_file(dir, depth, &mut q) {
+            Err(CrushError { kind: Kind::SendError, .. }) => break,
+            res => context.printer.handle_error(res),
         }
-        let dir = q.pop_front().unwrap();
-        let _ = run_for_single_directory_or_file(dir, &users, config.recursive, &mut q, &mut output);
     }
     Ok(())
 }
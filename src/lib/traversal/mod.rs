@@ -5,13 +5,18 @@ use crate::util::file::{home, cwd};
 use std::path::PathBuf;
 use crate::lang::execution_context::ExecutionContext;
 use crate::lang::execution_context::ArgumentVector;
-use crate::lang::help::Help;
+use crate::lang::help::{self, Help};
 use crate::lang::printer::Printer;
 use crate::lang::argument::ArgumentHandler;
 use crate::lang::value::ValueType;
 use crate::lang::command::OutputType::Known;
+use ordered_map::OrderedMap;
 
+mod du;
 mod find;
+mod stat;
+mod watch;
+mod fileops;
 
 pub fn cd(context: ExecutionContext) -> CrushResult<()> {
     let dir = match context.arguments.len() {
@@ -36,11 +41,51 @@ pub fn pwd(context: ExecutionContext) -> CrushResult<()> {
 }
 
 fn halp(o: &dyn Help, printer: &Printer) {
-    printer.line(
-        match o.long_help() {
-            None => format!("{}\n\n    {}", o.signature(), o.short_help()),
-            Some(long_help) => format!("{}\n\n    {}\n\n{}", o.signature(), o.short_help(), long_help),
-        }.as_str());
+    printer.line(&help::render(o));
+}
+
+/**
+Shorten a line to at most 80 characters by dropping the names at the end of it. Lengths are
+counted in characters, since names need not be ASCII.
+*/
+fn truncate(line: String) -> String {
+    if line.chars().count() <= 80 {
+        return line;
+    }
+    let prefix: String = line.chars().take(77).collect();
+    let end = prefix.rfind(", ").unwrap_or(prefix.len());
+    format!("{}, ...", &prefix[..end])
+}
+
+/**
+The namespaces that are visible from a scope, each followed by the names in it. Namespaces
+that have not been loaded yet are listed without their members, since loading a namespace
+may run code, e.g. that of a module.
+*/
+fn namespaces(env: &Scope) -> CrushResult<String> {
+    let mut map = OrderedMap::new();
+    env.dump(&mut map)?;
+    let mut names: Vec<_> = map.iter()
+        .filter(|(_, value_type)| **value_type == ValueType::Scope)
+        .map(|(name, _)| name.clone())
+        .collect();
+    names.sort();
+    let width = names.iter().map(|name| name.chars().count()).max().unwrap_or(0);
+    let mut lines = Vec::new();
+    for name in names {
+        if let Some(Value::Scope(scope)) = env.get(&name)? {
+            let line = match scope.loaded_names() {
+                Some(mut members) => {
+                    members.sort();
+                    let padding = " ".repeat(width - name.chars().count());
+                    format!("    {}{}  {}", name, padding, members.join(", "))
+                }
+                None => format!("    {}", name),
+            };
+            lines.push(truncate(line));
+        }
+    }
+    Ok(lines.join("\n"))
 }
 
 pub fn help(mut context: ExecutionContext) -> CrushResult<()> {
@@ -57,9 +102,14 @@ Call the help command with the name of any value, including a command or a
 type in order to get help about it. For example, you might want to run the
 commands "help help", "help string", "help if" or "help where".
 
+Passing --help to a command also shows help about it, e.g. "sort --help".
+
 To get a list of everything in your namespace, write "var:env". To list the
 members of a value, write "dir <value>".
+
+These are the namespaces, run "help <namespace>" to see what is in one:
 "#);
+            context.printer.line(&namespaces(&context.env)?);
             context.output.send(Value::Empty())
         }
         1 => {
@@ -81,6 +131,12 @@ pub fn declare(root: &Scope) -> CrushResult<()> {
         "traversal",
         Box::new(move |env| {
             find::Find::declare(env)?;
+            du::Du::declare(env)?;
+            stat::Stat::declare(env)?;
+            watch::Watch::declare(env)?;
+            fileops::Cp::declare(env)?;
+            fileops::Mv::declare(env)?;
+            fileops::Rm::declare(env)?;
             env.declare_command(
                 "cd", cd, true,
                 "cd directory:(file,string,glob)",
@@ -100,5 +156,40 @@ pub fn declare(root: &Scope) -> CrushResult<()> {
             Ok(())
         }))?;
     root.r#use(&e);
+    root.create_lazy_namespace(
+        "files",
+        Box::new(move |env| {
+            stat::Exists::declare(env)?;
+            stat::Type::declare(env)?;
+            Ok(())
+        }))?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    #[test]
+    fn long_lines_are_truncated_on_character_boundaries() {
+        let line = format!("    ns  {}", vec!["ĺĺĺĺĺĺĺĺ"; 20].join(", "));
+        let truncated = truncate(line);
+        assert!(truncated.chars().count() <= 80);
+        assert!(truncated.ends_with("ĺĺĺĺĺĺĺĺ, ..."));
+    }
+
+    #[test]
+    fn namespaces_are_listed_without_loading_them() {
+        let root = Scope::create_root();
+        let loaded = Arc::new(AtomicBool::new(false));
+        let flag = loaded.clone();
+        root.create_lazy_namespace("module", Box::new(move |env| {
+            flag.store(true, Ordering::Relaxed);
+            env.declare("member", Value::Integer(1))
+        })).unwrap();
+        assert_eq!(namespaces(&root).unwrap(), "    module");
+        assert!(!loaded.load(Ordering::Relaxed));
+    }
+}
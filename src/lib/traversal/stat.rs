@@ -0,0 +1,125 @@
+use std::fs;
+use std::fs::Metadata;
+use std::os::unix::fs::{FileTypeExt, MetadataExt};
+use std::path::{Path, PathBuf};
+
+use chrono::{Local, TimeZone};
+
+use crate::lang::execution_context::ExecutionContext;
+use crate::lang::errors::{CrushResult, to_crush_error};
+use crate::lang::r#struct::Struct;
+use crate::lang::value::{Value, ValueType};
+use crate::lang::command::OutputType::Known;
+use signature::signature;
+use crate::lang::argument::ArgumentHandler;
+
+fn metadata(file: &Path, follow_links: bool) -> CrushResult<Metadata> {
+    to_crush_error(if follow_links { fs::metadata(file) } else { fs::symlink_metadata(file) })
+}
+
+fn file_type(meta: &Metadata) -> &'static str {
+    let file_type = meta.file_type();
+    if file_type.is_dir() {
+        "directory"
+    } else if file_type.is_symlink() {
+        "symlink"
+    } else if file_type.is_fifo() {
+        "fifo"
+    } else if file_type.is_socket() {
+        "socket"
+    } else if file_type.is_block_device() {
+        "block_device"
+    } else if file_type.is_char_device() {
+        "char_device"
+    } else {
+        "file"
+    }
+}
+
+fn time(seconds: i64, nanoseconds: i64) -> Value {
+    Value::Time(Local.timestamp(seconds, nanoseconds as u32))
+}
+
+#[signature(
+stat,
+can_block = false,
+short = "Return a struct with the metadata of a file",
+long = "The struct has the following fields:",
+long = "",
+long = "    * file, the file itself",
+long = "    * type, one of file, directory, symlink, fifo, socket, block_device and char_device",
+long = "    * size, the size of the file in bytes",
+long = "    * mode, the permission bits of the file",
+long = "    * uid and gid, the ids of the owning user and group",
+long = "    * inode, device and nlink, the inode number, the device the file is on and the number of",
+long = "      hard links to the file",
+long = "    * accessed, modified and changed, the times the file was last read, written and had its",
+long = "      metadata changed",
+example = "(stat ./Cargo.toml):size",
+output = Known(ValueType::Struct))]
+pub struct Stat {
+    #[description("the file to return the metadata of.")]
+    file: PathBuf,
+    #[default(true)]
+    #[description("return the metadata of the file symbolic links point to instead of the links themselves.")]
+    follow_links: bool,
+}
+
+fn stat(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Stat = Stat::parse(context.arguments, &context.printer)?;
+    let meta = metadata(&cfg.file, cfg.follow_links)?;
+    context.output.send(Value::Struct(Struct::new(
+        vec![
+            ("file".to_string(), Value::File(cfg.file)),
+            ("type".to_string(), Value::string(file_type(&meta))),
+            ("size".to_string(), Value::Integer(i128::from(meta.len()))),
+            ("mode".to_string(), Value::Integer(i128::from(meta.mode()))),
+            ("uid".to_string(), Value::Integer(i128::from(meta.uid()))),
+            ("gid".to_string(), Value::Integer(i128::from(meta.gid()))),
+            ("inode".to_string(), Value::Integer(i128::from(meta.ino()))),
+            ("device".to_string(), Value::Integer(i128::from(meta.dev()))),
+            ("nlink".to_string(), Value::Integer(i128::from(meta.nlink()))),
+            ("accessed".to_string(), time(meta.atime(), meta.atime_nsec())),
+            ("modified".to_string(), time(meta.mtime(), meta.mtime_nsec())),
+            ("changed".to_string(), time(meta.ctime(), meta.ctime_nsec())),
+        ],
+        None)))
+}
+
+#[signature(
+exists,
+can_block = false,
+short = "Return true if the file exists",
+long = "Symbolic links are followed, so a link pointing to a missing file does not exist.",
+example = "if (files:exists ./Cargo.toml) {echo \"found it\"}",
+output = Known(ValueType::Bool))]
+pub struct Exists {
+    #[description("the file to check.")]
+    file: PathBuf,
+}
+
+fn exists(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Exists = Exists::parse(context.arguments, &context.printer)?;
+    context.output.send(Value::Bool(cfg.file.exists()))
+}
+
+#[signature(
+r#type,
+can_block = false,
+short = "Return the type of a file",
+long = "The type is one of file, directory, symlink, fifo, socket, block_device and char_device.",
+example = "if (files:type ./src) == \"directory\" {echo \"a directory\"}",
+output = Known(ValueType::String))]
+pub struct Type {
+    #[description("the file to return the type of.")]
+    file: PathBuf,
+    #[default(false)]
+    #[description("return the type of the file symbolic links point to instead of symlink.")]
+    follow_links: bool,
+}
+
+fn r#type(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Type = Type::parse(context.arguments, &context.printer)?;
+    let meta = metadata(&cfg.file, cfg.follow_links)?;
+    context.output.send(Value::string(file_type(&meta)))
+}
@@ -0,0 +1,199 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use chrono::Local;
+use lazy_static::lazy_static;
+use nix::sys::inotify::{AddWatchFlags, InitFlags, Inotify, InotifyEvent, WatchDescriptor};
+
+use crate::lang::execution_context::ExecutionContext;
+use crate::lang::errors::{CrushResult, argument_error, to_crush_error};
+use crate::lang::{value::Value, value::ValueType, table::ColumnType, table::Row};
+use crate::lang::stream::OutputStream;
+use crate::lang::command::OutputType::Known;
+use crate::lang::files::Files;
+use signature::signature;
+use crate::lang::argument::ArgumentHandler;
+
+lazy_static! {
+    static ref OUTPUT_TYPE: Vec<ColumnType> = vec![
+        ColumnType::new("time", ValueType::Time),
+        ColumnType::new("file", ValueType::File),
+        ColumnType::new("event", ValueType::String),
+    ];
+}
+
+#[signature(
+watch,
+can_block = true,
+short = "Stream changes to files and directories as they happen",
+long = "Every change is output as a row containing the time of the change, the changed file and",
+long = "one of the following kinds of events:",
+long = "",
+long = "    * created, the file was created",
+long = "    * modified, the file was closed after being written to",
+long = "    * attributes, the permissions, owner or timestamps of the file changed",
+long = "    * deleted, the file was deleted",
+long = "    * moved_from and moved_to, the file was renamed from or to this name",
+long = "",
+long = "The output is unbounded, so watch keeps running until the commands reading its output stop,",
+long = "until count events have been output, or until every watched file has been deleted.",
+long = "Watching relies on inotify, and is only supported on Linux.",
+example = "watch ./src | where {event == \"modified\"}",
+output = Known(ValueType::TableStream(OUTPUT_TYPE.clone())))]
+pub struct Watch {
+    #[unnamed()]
+    #[description("the files and directories to watch.")]
+    files: Files,
+    #[default(true)]
+    #[description("also watch all subdirectories of watched directories, including ones created later.")]
+    recursive: bool,
+    #[description("stop after this many events.")]
+    count: Option<i128>,
+}
+
+fn flags() -> AddWatchFlags {
+    AddWatchFlags::IN_CREATE | AddWatchFlags::IN_CLOSE_WRITE | AddWatchFlags::IN_ATTRIB |
+        AddWatchFlags::IN_DELETE | AddWatchFlags::IN_DELETE_SELF |
+        AddWatchFlags::IN_MOVED_FROM | AddWatchFlags::IN_MOVED_TO | AddWatchFlags::IN_MOVE_SELF
+}
+
+fn event_name(mask: AddWatchFlags) -> Option<&'static str> {
+    if mask.contains(AddWatchFlags::IN_CREATE) {
+        Some("created")
+    } else if mask.contains(AddWatchFlags::IN_CLOSE_WRITE) {
+        Some("modified")
+    } else if mask.contains(AddWatchFlags::IN_ATTRIB) {
+        Some("attributes")
+    } else if mask.intersects(AddWatchFlags::IN_DELETE | AddWatchFlags::IN_DELETE_SELF) {
+        Some("deleted")
+    } else if mask.intersects(AddWatchFlags::IN_MOVED_FROM | AddWatchFlags::IN_MOVE_SELF) {
+        Some("moved_from")
+    } else if mask.contains(AddWatchFlags::IN_MOVED_TO) {
+        Some("moved_to")
+    } else {
+        None
+    }
+}
+
+struct Watcher {
+    inotify: Inotify,
+    recursive: bool,
+    paths: HashMap<WatchDescriptor, PathBuf>,
+}
+
+impl Watcher {
+    fn add(&mut self, path: &Path) -> CrushResult<()> {
+        let wd = to_crush_error(self.inotify.add_watch(path, flags()))?;
+        self.paths.insert(wd, path.to_path_buf());
+        if self.recursive && path.is_dir() {
+            for entry in to_crush_error(fs::read_dir(path))? {
+                let entry = to_crush_error(entry)?;
+                if to_crush_error(entry.file_type())?.is_dir() {
+                    self.add(&entry.path())?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /** Returns the row to output for an event, if any. */
+    fn handle(&mut self, event: InotifyEvent) -> CrushResult<Option<Row>> {
+        if event.mask.contains(AddWatchFlags::IN_IGNORED) {
+            self.paths.remove(&event.wd);
+            return Ok(None);
+        }
+        let path = match (self.paths.get(&event.wd), &event.name) {
+            (Some(dir), Some(name)) => dir.join(name),
+            (Some(file), None) => file.clone(),
+            (None, _) => return Ok(None),
+        };
+        let name = match event_name(event.mask) {
+            Some(name) => name,
+            None => return Ok(None),
+        };
+        if self.recursive && name == "created" && event.mask.contains(AddWatchFlags::IN_ISDIR) {
+            // The directory may already be gone again, which is not an error
+            let _ = self.add(&path);
+        }
+        Ok(Some(Row::new(vec![
+            Value::Time(Local::now()),
+            Value::File(path),
+            Value::string(name),
+        ])))
+    }
+}
+
+fn run(mut watcher: Watcher, count: Option<usize>, output: OutputStream) -> CrushResult<()> {
+    let mut sent = 0;
+    while !watcher.paths.is_empty() && count.map(|c| sent < c).unwrap_or(true) {
+        for event in to_crush_error(watcher.inotify.read_events())? {
+            if let Some(row) = watcher.handle(event)? {
+                output.send(row)?;
+                sent += 1;
+                if count.map(|c| sent >= c).unwrap_or(false) {
+                    break;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn watch(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Watch = Watch::parse(context.arguments, &context.printer)?;
+    let count = match cfg.count {
+        Some(c) if c < 0 => return argument_error("Expected count to be non-negative"),
+        c => c.map(|c| c as usize),
+    };
+    let files = if cfg.files.had_entries() {
+        cfg.files.into_vec()
+    } else {
+        vec![PathBuf::from(".")]
+    };
+    let mut watcher = Watcher {
+        inotify: to_crush_error(Inotify::init(InitFlags::IN_CLOEXEC))?,
+        recursive: cfg.recursive,
+        paths: HashMap::new(),
+    };
+    for file in &files {
+        watcher.add(file)?;
+    }
+    let output = context.output.initialize(OUTPUT_TYPE.clone())?;
+    run(watcher, count, output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lang::stream::streams;
+
+    #[test]
+    fn events_are_output_while_watching() {
+        let dir = std::env::temp_dir().join(format!("crush-watch-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let mut watcher = Watcher {
+            inotify: Inotify::init(InitFlags::IN_CLOEXEC).unwrap(),
+            recursive: false,
+            paths: HashMap::new(),
+        };
+        watcher.add(&dir).unwrap();
+        let (output, input) = streams(OUTPUT_TYPE.clone());
+        std::thread::spawn(move || run(watcher, None, output));
+        for i in 0..10 {
+            fs::write(dir.join(format!("file{}", i)), "").unwrap();
+        }
+        // The watcher keeps running, so the last event must arrive without a later one.
+        let last = Value::File(dir.join("file9"));
+        let found = loop {
+            match input.recv_timeout(chrono::Duration::seconds(2)) {
+                Ok(row) => if row.cells()[1] == last && row.cells()[2] == Value::string("modified") {
+                    break true;
+                },
+                Err(_) => break false,
+            }
+        };
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(found);
+    }
+}
@@ -0,0 +1,38 @@
+use crate::lang::errors::CrushResult;
+use crate::lang::{value::Value, execution_context::ExecutionContext};
+use crate::lang::execution_context::This;
+use ordered_map::OrderedMap;
+use lazy_static::lazy_static;
+use crate::lang::command::{Command, CrushCommand};
+use crate::lang::command::TypeMap;
+use crate::lang::command::OutputType::Known;
+use crate::lang::value::ValueType;
+
+fn full(name: &'static str) -> Vec<&'static str> {
+    vec!["global", "types", "command", name]
+}
+
+lazy_static! {
+    pub static ref METHODS: OrderedMap<String, Command> = {
+        let mut res: OrderedMap<String, Command> = OrderedMap::new();
+        res.declare(full("bind"),
+            bind, false,
+            "command:bind @arguments @@named_arguments",
+            "Returns a new command with the specified arguments already supplied",
+            Some(r#"    The arguments given to bind are passed to the command in front of any
+    arguments given when the new command is invoked. This makes it possible to
+    create reusable pipeline fragments.
+
+    Example:
+
+    big := (where:bind {size > 1048576})
+    ls | big"#),
+            Known(ValueType::Command));
+        res
+    };
+}
+
+fn bind(context: ExecutionContext) -> CrushResult<()> {
+    let command = context.this.command()?;
+    context.output.send(Value::Command(<dyn CrushCommand>::partial(command, context.arguments)))
+}
@@ -1,4 +1,4 @@
-use crate::lang::errors::{CrushResult, argument_error};
+use crate::lang::errors::{CrushResult, argument_error, mandate};
 use crate::lang::{value::Value, execution_context::ExecutionContext};
 use crate::lang::execution_context::{ArgumentVector, This};
 use ordered_map::OrderedMap;
@@ -39,10 +39,10 @@ lazy_static! {
             Known(ValueType::Duration));
         res.declare(full("__div__"),
             div, false,
-            "duration / divisor:integer",
-            "Divide this duration by the specified divisor",
+            "duration / divisor:(integer|duration)",
+            "Divide this duration by the specified divisor, or by another duration to get their ratio",
             None,
-            Known(ValueType::Duration));
+            Unknown);
         New::declare_method(&mut res, &path);
 /*
         res.declare(full("new"),
@@ -66,10 +66,10 @@ lazy_static! {
     };
 }
 
-binary_op!(add, duration, Duration, Duration, |a, b| a+b, Time, Time, |a, b| b+a);
-binary_op!(sub, duration, Duration, Duration, |a, b| a-b);
-binary_op!(mul, duration, Integer, Duration, |a, b| a*(b as i32));
-binary_op!(div, duration, Integer, Duration, |a, b| a/(b as i32));
+binary_op!(add, Add);
+binary_op!(sub, Sub);
+binary_op!(mul, Mul);
+binary_op!(div, Div);
 
 fn to_duration(a: i64, t: &str) -> CrushResult<chrono::Duration> {
     match t {
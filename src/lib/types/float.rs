@@ -1,4 +1,4 @@
-use crate::lang::errors::{CrushResult, argument_error};
+use crate::lang::errors::{CrushResult, mandate};
 use crate::lang::{value::Value, execution_context::ExecutionContext};
 use crate::lang::execution_context::{ArgumentVector, This};
 use ordered_map::OrderedMap;
@@ -59,10 +59,10 @@ lazy_static! {
     };
 }
 
-binary_op!(add, float, Integer, Float, |a, b| a+(b as f64), Float, Float, |a, b| a+b);
-binary_op!(sub, float, Integer, Float, |a, b| a-(b as f64), Float, Float, |a, b| a-b);
-binary_op!(mul, float, Integer, Float, |a, b| a*(b as f64), Float, Float, |a, b| a*b);
-binary_op!(div, float, Integer, Float, |a, b| a/(b as f64), Float, Float, |a, b| a/b);
+binary_op!(add, Add);
+binary_op!(sub, Sub);
+binary_op!(mul, Mul);
+binary_op!(div, Div);
 
 fn neg(context: ExecutionContext) -> CrushResult<()> {
     context.arguments.check_len(0)?;
@@ -1,11 +1,11 @@
-use crate::lang::errors::{CrushResult, argument_error};
+use crate::lang::errors::{CrushResult, mandate};
 use crate::lang::{value::Value, execution_context::ExecutionContext};
 use crate::lang::execution_context::{ArgumentVector, This};
 use ordered_map::OrderedMap;
 use lazy_static::lazy_static;
 use crate::lang::command::Command;
 use crate::lang::command::TypeMap;
-use crate::lang::command::OutputType::Known;
+use crate::lang::command::OutputType::{Known, Unknown};
 use crate::lang::value::ValueType;
 
 fn full(name: &'static str) -> Vec<&'static str> {
@@ -20,23 +20,23 @@ lazy_static! {
             "integer + term:(integer|float)",
             "Add this number by the specified term",
             None,
-            Known(ValueType::Integer));
+            Unknown);
         res.declare(full("__sub__"),
             sub, false,
             "integer - term:(integer|float)",
             "Subtract the specified term from this number",
             None,
-            Known(ValueType::Integer));
+            Unknown);
         res.declare(full("__mul__"),
             mul, false,
-            "integer * factor:(integer|float)", "Multiply this number with the specified factor",
+            "integer * factor:(integer|float|duration)", "Multiply this number with the specified factor",
             None,
-            Known(ValueType::Integer));
+            Unknown);
         res.declare(
             full("__div__"), div, false,
             "integer / factor:(integer|float)", "Divide this number by the specified factor",
             None,
-            Known(ValueType::Integer));
+            Unknown);
         res.declare(
             full("mod"), r#mod, false,
             "integer:mod factor:integer", "Least positive residue after integer division",
@@ -56,12 +56,12 @@ lazy_static! {
     };
 }
 
-binary_op!(add, integer, Integer, Integer, |a, b| a+b, Float, Float, |a, b| a as f64+b);
-binary_op!(sub, integer, Integer, Integer, |a, b| a-b, Float, Float, |a, b| a as f64-b);
-binary_op!(mul, integer, Integer, Integer, |a, b| a*b, Float, Float, |a, b| a as f64*b);
-binary_op!(div, integer, Integer, Integer, |a, b| a/b, Float, Float, |a, b| a as f64/b);
-binary_op!(rem, integer, Integer, Integer, |a, b| a % b);
-binary_op!(r#mod, integer, Integer, Integer, |a, b| (a % b + b) % b);
+binary_op!(add, Add);
+binary_op!(sub, Sub);
+binary_op!(mul, Mul);
+binary_op!(div, Div);
+binary_op!(rem, Rem);
+binary_op!(r#mod, Mod);
 
 fn neg(context: ExecutionContext) -> CrushResult<()> {
     context.arguments.check_len(0)?;
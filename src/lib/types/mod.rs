@@ -1,7 +1,7 @@
 use crate::lang::scope::Scope;
 use crate::lang::errors::{CrushResult, argument_error, mandate};
 use crate::lang::{value::Value, r#struct::Struct};
-use crate::lang::command::CrushCommand;
+use crate::lang::command::{ArgumentDescription, CrushCommand};
 use crate::lang::execution_context::{ExecutionContext, This};
 use crate::lang::argument::{column_names, Argument};
 use crate::lang::execution_context::ArgumentVector;
@@ -24,6 +24,8 @@ pub mod duration;
 pub mod time;
 pub mod binary;
 pub mod scope;
+pub mod command;
+pub mod styled;
 
 fn materialize(context: ExecutionContext) -> CrushResult<()> {
     context.output.send(context.input.recv()?.materialize())
@@ -114,24 +116,31 @@ pub fn declare(root: &Scope) -> CrushResult<()> {
                         class_set, false,
                         vec!["global".to_string(), "types".to_string(), "root".to_string(), "__setattr__".to_string()],
                         "root:__setitem__ name:string value:any",
+                        &[],
                         "Modify the specified field to hold the specified value",
                         None, Known(ValueType::Empty)))),
                     ("__getitem__".to_string(), Value::Command(CrushCommand::command(
                         class_get, false,
                         vec!["global".to_string(), "types".to_string(), "root".to_string(), "__getitem__".to_string()],
                         "root:__getitem__ name:string",
+                        &[],
                         "Return the value of the specified field",
                         None, Unknown))),
                     ("__setitem__".to_string(), Value::Command(CrushCommand::command(
                         class_get, false,
                         vec!["global".to_string(), "types".to_string(), "root".to_string(), "__setitem__".to_string()],
                         "root:__setitem__ name:string value:any",
+                        &[],
                         "Modify the specified field to hold the specified value",
                         None, Unknown))),
                     ("new".to_string(), Value::Command(CrushCommand::command(
                         new, true,
                         vec!["global".to_string(), "types".to_string(), "root".to_string(), "new".to_string()],
                         "root:new @unnamed @@named",
+                        &[
+                            ArgumentDescription { name: "unnamed", value_type: "any", named: false },
+                            ArgumentDescription { name: "named", value_type: "any", named: true },
+                        ],
                         "Create a new instance of the specified type",
                         None, Known(ValueType::Struct)))),
                 ], None);
@@ -223,6 +232,7 @@ pub fn declare(root: &Scope) -> CrushResult<()> {
             env.declare("table", Value::Type(ValueType::Table(vec![])))?;
             env.declare("table_stream", Value::Type(ValueType::TableStream(vec![])))?;
             env.declare("struct", Value::Type(ValueType::Struct))?;
+            env.declare("styled", Value::Type(ValueType::Styled))?;
             Ok(())
         }))?;
     root.r#use(&e);
@@ -27,7 +27,7 @@ fn argument_by_name<'a>(name: &str, param: &'a [Argument]) -> Option<&'a Argumen
     None
 }
 
-fn do_format(format: &str, param: Vec<Argument>) -> CrushResult<String> {
+pub fn do_format(format: &str, param: Vec<Argument>) -> CrushResult<String> {
     let mut implicit_idx = 0;
     let mut res = String::new();
     let mut state = Normal;
@@ -1,4 +1,4 @@
-use crate::lang::errors::{CrushResult, argument_error};
+use crate::lang::errors::{CrushResult, argument_error, mandate};
 use crate::lang::{execution_context::ExecutionContext, value::ValueType, list::List};
 use crate::lang::value::Value;
 use crate::lang::execution_context::{This, ArgumentVector};
@@ -14,12 +14,24 @@ fn full(name: &'static str) -> Vec<&'static str> {
     vec!["global", "types", "string", name]
 }
 
-mod format;
+pub mod format;
 
 lazy_static! {
     pub static ref METHODS: OrderedMap<String, Command> = {
         let mut res: OrderedMap<String, Command> = OrderedMap::new();
         let path = vec!["global", "types", "string"];
+        res.declare(
+            full("__add__"), add, false,
+            "string + suffix:string",
+            "Returns this string followed by the suffix",
+            None,
+            Known(ValueType::String));
+        res.declare(
+            full("__mul__"), mul, false,
+            "string * times:integer",
+            "Returns this string repeated times times",
+            None,
+            Known(ValueType::String));
         res.declare(
             full("upper"),
             upper, false,
@@ -118,6 +130,9 @@ lazy_static! {
     };
 }
 
+binary_op!(add, Add);
+binary_op!(mul, Mul);
+
 fn upper(context: ExecutionContext) -> CrushResult<()> {
     context.arguments.check_len(0)?;
     context.output.send(Value::String(
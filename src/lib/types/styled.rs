@@ -0,0 +1,30 @@
+use crate::lang::errors::CrushResult;
+use crate::lang::{value::Value, execution_context::ExecutionContext};
+use crate::lang::execution_context::This;
+use ordered_map::OrderedMap;
+use lazy_static::lazy_static;
+use crate::lang::command::Command;
+use crate::lang::command::TypeMap;
+use crate::lang::command::OutputType::Known;
+use crate::lang::value::ValueType;
+
+fn full(name: &'static str) -> Vec<&'static str> {
+    vec!["global", "types", "styled", name]
+}
+
+lazy_static! {
+    pub static ref METHODS: OrderedMap<String, Command> = {
+        let mut res: OrderedMap<String, Command> = OrderedMap::new();
+        res.declare(full("text"),
+            text, false,
+            "styled:text",
+            "The text without any attributes",
+            None, Known(ValueType::String));
+        res
+    };
+}
+
+fn text(context: ExecutionContext) -> CrushResult<()> {
+    let val = context.this.styled()?;
+    context.output.send(Value::String(val.text))
+}
@@ -1,6 +1,6 @@
-use crate::lang::errors::{CrushResult, argument_error, to_crush_error};
+use crate::lang::errors::{CrushResult, to_crush_error, mandate};
 use crate::lang::{value::Value, execution_context::ExecutionContext};
-use crate::lang::execution_context::{ArgumentVector, This, ValueExecutionContext};
+use crate::lang::execution_context::{ArgumentVector, ValueExecutionContext};
 use ordered_map::OrderedMap;
 use lazy_static::lazy_static;
 use chrono::{Local, Datelike, Timelike, DateTime};
@@ -11,7 +11,7 @@ use crate::lang::command::TypeMap;
 use signature::signature;
 use crate::lang::argument::ArgumentHandler;
 use crate::lang::value::ValueType;
-use crate::lang::command::OutputType::Known;
+use crate::lang::command::OutputType::{Known, Unknown};
 
 fn full(name: &'static str) -> Vec<&'static str> {
     vec!["global", "types", "time", name]
@@ -29,8 +29,9 @@ lazy_static! {
             Known(ValueType::Time));
         res.declare(
             full("__sub__"), sub, false,
-            "time - delta:duration", "Remove the specified delta from this time", None,
-            Known(ValueType::Time));
+            "time - (delta:duration | time:time)",
+            "Remove the specified delta from this time, or the duration since the specified time", None,
+            Unknown);
         res.declare(
             full("now"), now, false,"time:now", "The current point in time", None,
             Known(ValueType::Time));
@@ -39,8 +40,8 @@ lazy_static! {
     };
 }
 
-binary_op!(add, time, Duration, Time, |a, b| a+b);
-binary_op!(sub, time, Duration, Time, |a, b| a-b, Time, Duration, |a, b| a-b);
+binary_op!(add, Add);
+binary_op!(sub, Sub);
 
 fn now(context: ExecutionContext) -> CrushResult<()> {
     context.output.send(Value::Time(Local::now()))
@@ -1,10 +1,31 @@
 use crate::util::file::home;
-use users::{get_current_username, get_current_groupname, get_current_uid, get_current_gid};
+use crate::util::user_map::{all_group_ids, create_user_map};
+use users::{get_current_username, get_current_groupname, get_current_uid, get_current_gid, get_effective_uid, get_effective_gid, get_group_by_gid, get_user_by_uid, group_access_list, Group};
+use users::os::unix::{GroupExt, UserExt};
 use crate::lang::scope::Scope;
-use crate::lang::errors::{CrushResult, mandate};
+use crate::lang::errors::{CrushResult, mandate, to_crush_error};
 use crate::lang::execution_context::{ExecutionContext, ArgumentVector};
 use crate::lang::value::{Value, ValueType};
 use crate::lang::command::OutputType::Known;
+use crate::lang::{list::List, r#struct::Struct, table::ColumnType, table::Row};
+use lazy_static::lazy_static;
+use signature::signature;
+use std::ffi::OsStr;
+
+lazy_static! {
+    static ref USERS_OUTPUT_TYPE: Vec<ColumnType> = vec![
+        ColumnType::new("name", ValueType::String),
+        ColumnType::new("uid", ValueType::Integer),
+        ColumnType::new("gid", ValueType::Integer),
+        ColumnType::new("home", ValueType::File),
+        ColumnType::new("shell", ValueType::File),
+    ];
+    static ref GROUPS_OUTPUT_TYPE: Vec<ColumnType> = vec![
+        ColumnType::new("name", ValueType::String),
+        ColumnType::new("gid", ValueType::Integer),
+        ColumnType::new("members", ValueType::List(Box::new(ValueType::String))),
+    ];
+}
 
 fn home_fun(context: ExecutionContext) -> CrushResult<()> {
     context.arguments.check_len(0)?;
@@ -41,6 +62,99 @@ fn gid(context: ExecutionContext) -> CrushResult<()> {
     context.output.send(Value::Integer(get_current_gid() as i128))
 }
 
+fn os_string(s: &OsStr) -> Value {
+    Value::string(&s.to_string_lossy())
+}
+
+fn group_name(gid: u32) -> Value {
+    get_group_by_gid(gid).map(|g| os_string(g.name())).unwrap_or(Value::Empty())
+}
+
+#[signature(
+users,
+can_block = true,
+output = Known(ValueType::TableStream(USERS_OUTPUT_TYPE.clone())),
+short = "Return a table stream of all users in the user database",
+long = "Each row contains the name, user id, primary group id, home directory and login shell of a",
+long = "user. The rows are sorted by user id.",
+example = "users | where {shell == /bin/bash} | select ^name ^home")]
+pub struct Users {}
+
+fn users(context: ExecutionContext) -> CrushResult<()> {
+    let output = context.output.initialize(USERS_OUTPUT_TYPE.clone())?;
+    let mut users = create_user_map().into_values().collect::<Vec<_>>();
+    users.sort_by_key(|u| u.uid());
+    for user in users {
+        output.send(Row::new(vec![
+            os_string(user.name()),
+            Value::Integer(i128::from(user.uid())),
+            Value::Integer(i128::from(user.primary_group_id())),
+            Value::File(user.home_dir().to_path_buf()),
+            Value::File(user.shell().to_path_buf()),
+        ]))?;
+    }
+    Ok(())
+}
+
+fn group_members(group: &Group) -> Value {
+    Value::List(List::new(ValueType::String, group.members().iter().map(|m| os_string(m)).collect()))
+}
+
+#[signature(
+groups,
+can_block = true,
+output = Known(ValueType::TableStream(GROUPS_OUTPUT_TYPE.clone())),
+short = "Return a table stream of all groups in the group database",
+long = "Each row contains the name and group id of a group, and the names of the users that have",
+long = "it as a supplementary group. Users that have a group as their primary group are usually not",
+long = "listed as members. The rows are sorted by group id.",
+example = "groups | where {gid >= 1000} | select ^name ^members")]
+pub struct Groups {}
+
+fn groups(context: ExecutionContext) -> CrushResult<()> {
+    let output = context.output.initialize(GROUPS_OUTPUT_TYPE.clone())?;
+    for gid in all_group_ids() {
+        if let Some(group) = get_group_by_gid(gid) {
+            output.send(Row::new(vec![
+                os_string(group.name()),
+                Value::Integer(i128::from(group.gid())),
+                group_members(&group),
+            ]))?;
+        }
+    }
+    Ok(())
+}
+
+#[signature(
+id,
+can_block = false,
+output = Known(ValueType::Struct),
+short = "Return the credentials of the shell",
+long = "The struct contains the real and effective user and group ids, the names of the real user",
+long = "and group, and the names of all supplementary groups.",
+example = "find . | where {user != (id):user}")]
+pub struct Id {}
+
+fn id(context: ExecutionContext) -> CrushResult<()> {
+    let uid = get_current_uid();
+    let gid = get_current_gid();
+    let groups = to_crush_error(group_access_list())?
+        .iter()
+        .map(|g| os_string(g.name()))
+        .collect();
+    context.output.send(Value::Struct(Struct::new(
+        vec![
+            ("uid".to_string(), Value::Integer(i128::from(uid))),
+            ("gid".to_string(), Value::Integer(i128::from(gid))),
+            ("euid".to_string(), Value::Integer(i128::from(get_effective_uid()))),
+            ("egid".to_string(), Value::Integer(i128::from(get_effective_gid()))),
+            ("user".to_string(), get_user_by_uid(uid).map(|u| os_string(u.name())).unwrap_or(Value::Empty())),
+            ("group".to_string(), group_name(gid)),
+            ("groups".to_string(), Value::List(List::new(ValueType::String, groups))),
+        ],
+        None)))
+}
+
 pub fn declare(root: &Scope) -> CrushResult<()> {
     root.create_lazy_namespace(
         "user",
@@ -5,17 +5,25 @@ use crate::lang::execution_context::ExecutionContext;
 use crate::lang::table::{ColumnType, Row};
 use ordered_map::OrderedMap;
 use crate::lang::command::OutputType::{Known, Unknown};
+use crate::lang::materialization::{MaterializationPolicy, Policy};
+use crate::lang::r#struct::Struct;
+use signature::signature;
+use crate::lang::argument::ArgumentHandler;
+use crate::lang::module;
+use std::path::PathBuf;
 
 pub fn r#let(context: ExecutionContext) -> CrushResult<()> {
+    let policy = context.env.materialization_policy();
     for arg in context.arguments {
-        context.env.declare(mandate(arg.argument_type, "Missing variable name")?.as_ref(), arg.value)?;
+        context.env.declare(mandate(arg.argument_type, "Missing variable name")?.as_ref(), policy.apply(arg.value)?)?;
     }
     context.output.send(Value::Empty())
 }
 
 pub fn set(context: ExecutionContext) -> CrushResult<()> {
+    let policy = context.env.materialization_policy();
     for arg in context.arguments {
-        context.env.set(mandate(arg.argument_type, "Missing variable name")?.as_ref(), arg.value)?;
+        context.env.set(mandate(arg.argument_type, "Missing variable name")?.as_ref(), policy.apply(arg.value)?)?;
     }
     context.output.send(Value::Empty())
 }
@@ -39,12 +47,44 @@ pub fn r#use(context: ExecutionContext) -> CrushResult<()> {
     for arg in context.arguments.iter() {
         match (arg.argument_type.is_none(), &arg.value) {
             (true, Value::Scope(e)) => context.env.r#use(e),
-            _ => return argument_error("Expected all arguments to be scopes"),
+            (true, Value::File(f)) => {
+                let name = mandate(module::name(f), "Invalid module name")?;
+                context.env.r#use(&module::declare(&context.env, &name, f, &context.printer)?)
+            }
+            _ => return argument_error("Expected all arguments to be scopes or modules"),
         }
     }
     context.output.send(Value::Empty())
 }
 
+#[signature(
+r#mod,
+can_block = false,
+output = Known(ValueType::Empty),
+short = "Declare a namespace with the contents of a .crush file or a directory of them",
+long = "The namespace of a file contains the variables declared by the file. The namespace of a",
+long = "directory contains the namespaces of the .crush files and subdirectories in it, along with",
+long = "the variables declared by the file mod.crush in it, if there is one. Files are only loaded",
+long = "once something in their namespace is first used, and a module that uses itself while",
+long = "being loaded, directly or through other modules, is an error.",
+example = "var:mod ./scripts/deploy.crush; deploy:run target=\"staging\"")]
+struct Mod {
+    #[description("the .crush file or directory.")]
+    path: PathBuf,
+    #[description("the name of the namespace. The default is the name of the file without the .crush suffix.")]
+    name: Option<String>,
+}
+
+fn r#mod(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Mod = Mod::parse(context.arguments, &context.printer)?;
+    let name = match cfg.name {
+        Some(name) => name,
+        None => mandate(module::name(&cfg.path), "Invalid module name")?,
+    };
+    module::declare(&context.env, &name, &cfg.path, &context.printer)?;
+    context.output.send(Value::Empty())
+}
+
 pub fn env(context: ExecutionContext) -> CrushResult<()> {
     let output = context.output.initialize(vec![
         ColumnType::new("name", ValueType::String),
@@ -67,6 +107,49 @@ pub fn env(context: ExecutionContext) -> CrushResult<()> {
     Ok(())
 }
 
+#[signature(
+materialization,
+can_block = false,
+short = "Set what happens to streams when they are assigned to variables",
+long = "There is one policy for table streams and one for binary streams, and each can be one of:",
+long = "",
+long = "    * lazy, the stream is assigned as is and can only be read once. This is the default.",
+long = "    * materialize, the stream is read right away, and the variable is set to a table or a",
+long = "      binary value.",
+long = "    * replay, the variable can be used any number of times, and every use reads the stream",
+long = "      from the beginning. Table streams are only read as far as they are used, binary streams",
+long = "      are read right away.",
+long = "",
+long = "The policies apply to the scope this command is called from and all scopes it calls. If no",
+long = "policy is specified, a struct with the policies currently in effect is output.",
+example = "var:materialization table_stream=\"replay\"")]
+struct Materialization {
+    #[values("lazy", "materialize", "replay")]
+    #[description("the policy for table streams.")]
+    table_stream: Option<String>,
+    #[values("lazy", "materialize", "replay")]
+    #[description("the policy for binary streams.")]
+    binary_stream: Option<String>,
+}
+
+fn materialization(context: ExecutionContext) -> CrushResult<()> {
+    let cfg: Materialization = Materialization::parse(context.arguments, &context.printer)?;
+    if cfg.table_stream.is_none() && cfg.binary_stream.is_none() {
+        let policy = context.env.materialization_policy();
+        return context.output.send(Value::Struct(Struct::new(
+            vec![
+                ("table_stream".to_string(), Value::string(policy.table_stream.unwrap_or(Policy::Lazy).name())),
+                ("binary_stream".to_string(), Value::string(policy.binary_stream.unwrap_or(Policy::Lazy).name())),
+            ],
+            None)));
+    }
+    context.env.set_materialization_policy(MaterializationPolicy {
+        table_stream: cfg.table_stream.map(|p| Policy::from_name(&p)).transpose()?,
+        binary_stream: cfg.binary_stream.map(|p| Policy::from_name(&p)).transpose()?,
+    });
+    context.output.send(Value::Empty())
+}
+
 pub fn declare(root: &Scope) -> CrushResult<()> {
     root.create_lazy_namespace(
         "var",
@@ -88,12 +171,16 @@ pub fn declare(root: &Scope) -> CrushResult<()> {
                 Some(r#"    The columns of the table are the name, and the type of the value."#), Unknown)?;
             ns.declare_command(
                 "use", r#use, false,
-                "use scope:scope",
+                "use scope:(scope|file)",
                 "Puts the specified scope into the list of scopes to search in by default during scope lookups",
-                Some(r#"    Example:
+                Some(r#"    A .crush file or a directory is first declared as a namespace, like var:mod does.
+
+    Example:
 
     use math
     sqrt 1.0"#), Known(ValueType::Empty))?;
+            Mod::declare(ns)?;
+            Materialization::declare(ns)?;
             Ok(())
         }))?;
     Ok(())
@@ -1,48 +1,130 @@
-#[macro_use]
-extern crate lalrpop_util;
-
-mod lang;
-mod lib;
-mod util;
+use crush::{lang, lib, util};
 
 use rustyline;
 
+use rustyline::completion::Completer;
 use rustyline::error::ReadlineError;
-use rustyline::Editor;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::{Context, Editor, Helper};
 use lib::declare;
+use lib::args::doc::{self, DocFormat};
 use crate::lang::errors::{CrushResult, to_crush_error};
-use crate::lang::{printer, execute};
+use crate::lang::{printer, execute, golden, history, completion, highlight, parser};
 use crate::lang::pretty_printer::create_pretty_printer;
-use crate::util::file::home;
-use std::path::{PathBuf, Path};
+use crate::util::file::config_dir;
+use std::path::PathBuf;
+use std::borrow::Cow;
+use chrono::Local;
 use crate::lang::scope::Scope;
 use crate::lang::printer::Printer;
-use crate::lang::stream::ValueSender;
+use crate::lang::stream::{ValueSender, black_hole};
+use crate::lang::module;
 use std::io::Read;
+use crate::lang::list::List;
+use crate::lang::value::{Value, ValueType};
+
+/** The prompt for the lines after the first of a command that spans several lines. */
+const CONTINUATION_PROMPT: &str = "     > ";
+
+/**
+Connects the line editor to the scope, so that it can complete the names in it, and to the
+lexer, so that it can highlight the line.
+*/
+struct CrushHelper {
+    scope: Scope,
+    /** The lines already entered of a command that spans several lines. */
+    previous: String,
+}
+
+impl Completer for CrushHelper {
+    type Candidate = String;
+
+    fn complete(&self, line: &str, pos: usize, _ctx: &Context<'_>) -> rustyline::Result<(usize, Vec<String>)> {
+        let text = format!("{}{}", self.previous, line);
+        let (start, candidates) = completion::complete(&text, self.previous.len() + pos, &self.scope);
+        Ok((start - self.previous.len(), candidates))
+    }
+}
+
+impl Hinter for CrushHelper {}
 
-fn crush_history_file() -> String {
-    home()
-        .unwrap_or_else(|_| PathBuf::from("."))
-        .join(Path::new(".crush_history"))
-        .to_str()
-        .unwrap_or(".crush_history")
-        .to_string()
+impl Highlighter for CrushHelper {
+    fn highlight<'l>(&self, line: &'l str, pos: usize) -> Cow<'l, str> {
+        Cow::Owned(highlight::highlight(&self.previous, line, pos))
+    }
+
+    /** Any character can change how the rest of the line is highlighted, so always redraw it. */
+    fn highlight_char(&self, _line: &str, _pos: usize) -> bool {
+        true
+    }
+}
+
+impl Helper for CrushHelper {}
+
+/**
+Read a command from the line editor. As long as what has been entered is incomplete, e.g. a
+closure that has not been closed or a pipeline that ends with a pipe, more lines are read using
+the continuation prompt, and they all become one command.
+*/
+fn read_command(rl: &mut Editor<CrushHelper>, prompt: &str) -> rustyline::Result<String> {
+    let mut cmd = rl.readline(prompt);
+    while let Ok(text) = &cmd {
+        if !parser::is_incomplete(text) {
+            break;
+        }
+        let mut text = text.clone();
+        if !text.ends_with('\n') {
+            text.push('\n');
+        }
+        rl.helper_mut().unwrap().previous = text.clone();
+        cmd = rl.readline(CONTINUATION_PROMPT).map(|line| text + &line);
+    }
+    rl.helper_mut().unwrap().previous.clear();
+    cmd
 }
 
 fn run_interactive(global_env: Scope, printer: &Printer, pretty_printer: &ValueSender) -> CrushResult<()> {
     printer.line("Welcome to Crush");
     printer.line(r#"Type "help" for... help."#);
 
-    let mut rl = Editor::<()>::new();
-    let _ = rl.load_history(&crush_history_file());
+    if let Ok(config) = config_dir().map(|d| d.join("config.crush")) {
+        if config.exists() {
+            printer.handle_error(module::source(&global_env, &config, printer, &black_hole()));
+        }
+    }
+
+    let history_file = history::file()?;
+    if let Ok(legacy) = history::legacy_file() {
+        printer.handle_error(history::import(&history_file, &legacy));
+    }
+    let mut rl = Editor::<CrushHelper>::new();
+    rl.set_helper(Some(CrushHelper { scope: global_env.clone(), previous: String::new() }));
+    match history::load_limited(&history_file, history::MAX_ENTRIES) {
+        Ok(entries) => for entry in entries {
+            rl.add_history_entry(entry.command);
+        },
+        Err(e) => printer.crush_error(e),
+    }
     loop {
-        let readline = rl.readline("crush> ");
+        let readline = read_command(&mut rl, &execute::prompt(&global_env, printer));
 
         match readline {
             Ok(cmd) => {
                 if !cmd.is_empty() {
                     rl.add_history_entry(cmd.as_str());
-                    execute::string(global_env.clone(), &cmd.as_str(), &printer, pretty_printer);
+                    printer.command(&cmd);
+                    let time = Local::now();
+                    let status = execute::interactive(global_env.clone(), &cmd, printer, pretty_printer);
+                    let entry = history::Entry {
+                        time,
+                        duration: Local::now() - time,
+                        success: status.is_success(),
+                        command: cmd.trim_end().to_string(),
+                    };
+                    if history::append(&history_file, &entry).is_err() {
+                        printer.line("Error: Failed to save history.");
+                    }
                 }
             }
             Err(ReadlineError::Interrupted) => {
@@ -57,54 +139,103 @@ fn run_interactive(global_env: Scope, printer: &Printer, pretty_printer: &ValueS
                 break;
             }
         }
-        match rl.save_history(&crush_history_file()) {
-            Ok(_) => {}
-            Err(_) => {
-                printer.line("Error: Failed to save history.");
-            }
-        }
     }
     Ok(())
 }
 
+/**
+Make the name of the script and the arguments passed to it available to the script.
+*/
+fn declare_arguments(scope: &Scope, args: &[String]) -> CrushResult<()> {
+    scope.declare(
+        "argv",
+        Value::List(List::new(
+            ValueType::String,
+            args.iter().map(|a| Value::string(a)).collect())))
+}
+
 fn run() -> CrushResult<()> {
     let global_env = lang::scope::Scope::create_root();
     let (printer, print_handle) = printer::init();
     let pretty_printer = create_pretty_printer(printer.clone());
-    declare(&global_env, &printer, &pretty_printer)?;
+    declare(&global_env, &printer)?;
     let my_scope = global_env.create_child(&global_env, false);
 
     let args = std::env::args().collect::<Vec<String>>();
+    let mut code = 0;
     match args.len() {
         1 => run_interactive(
             my_scope,
             &printer,
             &pretty_printer)?,
-        2 =>
-            if args[1] == "--pup" {
-                let mut buff = Vec::new();
-                to_crush_error(std::io::stdin().read_to_end(&mut buff))?;
-                execute::pup(my_scope, &buff, &printer)?;
-            } else {
-                execute::file(
-                    my_scope,
-                    PathBuf::from(&args[1]).as_path(),
-                    &printer,
-                    &pretty_printer)?
+        2 if args[1] == "--pup" => {
+            let mut buff = Vec::new();
+            to_crush_error(std::io::stdin().read_to_end(&mut buff))?;
+            execute::pup(my_scope, &buff, &printer)?;
+        }
+        2 if args[1] == "--remote" =>
+            lang::wire::serve(&mut std::io::stdin(), Box::new(std::io::stdout()), my_scope, &printer, None)?,
+        3 if args[1] == "-c" =>
+            code = execute::string(my_scope, &args[2], &printer, &pretty_printer).code(),
+        _ if args.len() >= 2 && args[1] == "test" => {
+            let update = args.len() >= 3 && args[2] == "--update";
+            let paths = &args[if update { 3 } else { 2 }..];
+            match golden::test(paths, update, &printer) {
+                Ok(true) => {}
+                Ok(false) => code = 1,
+                Err(e) => {
+                    printer.crush_error(e);
+                    code = 1;
+                }
+            }
+        }
+        4 if args[1] == "doc" =>
+            match DocFormat::parse(&args[3]) {
+                Ok(format) => {
+                    doc::set_format(format);
+                    declare_arguments(&my_scope, &args[2..3])?;
+                    match execute::args_parse(
+                        my_scope,
+                        PathBuf::from(&args[2]).as_path(),
+                        &printer,
+                        &pretty_printer) {
+                        Ok(Some(status)) => code = status.code(),
+                        Ok(None) => printer.error(format!("{} does not declare its arguments using args:parse", args[2]).as_str()),
+                        Err(e) => {
+                            printer.crush_error(e);
+                            code = 1;
+                        }
+                    }
+                }
+                Err(e) => printer.crush_error(e),
             },
-        _ => {}
+        _ => {
+            declare_arguments(&my_scope, &args[1..])?;
+            code = execute::file(
+                my_scope,
+                PathBuf::from(&args[1]).as_path(),
+                &printer,
+                &pretty_printer)?.code();
+        }
     }
     drop(pretty_printer);
     drop(printer);
     global_env.clear();
     drop(global_env);
     let _ = print_handle.join();
+    if code != 0 {
+        std::process::exit(code);
+    }
     Ok(())
 }
 
 fn main() {
+    lang::value::init_locale();
     match run() {
         Ok(_) => (),
-        Err(e) => println!("Error during initialization: {}", e.message),
+        Err(e) => {
+            println!("Error during initialization: {}", e.message);
+            std::process::exit(1);
+        }
     }
 }
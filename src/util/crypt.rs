@@ -0,0 +1,317 @@
+/**
+Streaming authenticated encryption, used by crypt:encrypt and crypt:decrypt.
+
+The format starts with a header of the magic bytes CRUSHENC, a version byte, a mode byte, the
+scrypt parameters log2(N), r and p as one byte each, and a 16 byte random salt. The key is
+derived from a password with scrypt, or from a key with HMAC-SHA256 keyed by the salt, the
+mode byte says which. The data follows in chunks of 64 KiB, each encrypted separately with
+AES-256-GCM and followed by its tag. Like in the STREAM construction used by age, the nonce
+of a chunk is its index followed by a byte that is one for the last chunk only, so chunks can
+not be reordered, and truncating the data at a chunk boundary is detected.
+*/
+use std::io::{Error, ErrorKind, Read};
+
+use openssl::hash::MessageDigest;
+use openssl::pkey::PKey;
+use openssl::sign::Signer;
+use openssl::symm::{Cipher, Crypter, Mode};
+
+const MAGIC: &[u8; 8] = b"CRUSHENC";
+const VERSION: u8 = 1;
+const MODE_PASSWORD: u8 = 1;
+const MODE_KEY: u8 = 2;
+const SALT_SIZE: usize = 16;
+const HEADER_SIZE: usize = MAGIC.len() + 5 + SALT_SIZE;
+const CHUNK_SIZE: usize = 64 * 1024;
+const TAG_SIZE: usize = 16;
+const LOG_N: u8 = 15;
+const R: u8 = 8;
+const P: u8 = 1;
+/**
+Refuse to decrypt data that would need more than a gigabyte of memory to derive the key. The
+time it takes grows with the memory, so this also bounds how long it takes.
+*/
+const MAX_MEMORY: u64 = 1 << 30;
+const MAX_LOG_N: u8 = 20;
+
+#[derive(Clone)]
+pub enum Secret {
+    Password(Vec<u8>),
+    Key(Vec<u8>),
+}
+
+fn invalid(message: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, message)
+}
+
+fn other(e: openssl::error::ErrorStack) -> Error {
+    Error::other(e)
+}
+
+fn derive_key(secret: &Secret, header: &[u8; HEADER_SIZE]) -> std::io::Result<[u8; 32]> {
+    let salt = &header[HEADER_SIZE - SALT_SIZE..];
+    let mut key = [0u8; 32];
+    match (secret, header[9]) {
+        (Secret::Password(password), MODE_PASSWORD) => {
+            let (log_n, r, p) = (header[10], u64::from(header[11]), u64::from(header[12]));
+            if log_n > MAX_LOG_N || 128 * (1u64 << log_n) * r * p > MAX_MEMORY {
+                return Err(invalid("The key derivation parameters of the data are too expensive"));
+            }
+            let n = 1u64 << log_n;
+            openssl::pkcs5::scrypt(password, salt, n, r, p, 129 * n * r * p + 1024 * 1024, &mut key).map_err(other)?;
+        }
+        (Secret::Key(secret), MODE_KEY) => {
+            let hmac = PKey::hmac(salt).map_err(other)?;
+            let mut signer = Signer::new(MessageDigest::sha256(), &hmac).map_err(other)?;
+            signer.update(secret).map_err(other)?;
+            key.copy_from_slice(&signer.sign_to_vec().map_err(other)?);
+        }
+        (Secret::Key(_), MODE_PASSWORD) => return Err(invalid("The data was encrypted with a password, not a key")),
+        (Secret::Password(_), MODE_KEY) => return Err(invalid("The data was encrypted with a key, not a password")),
+        _ => return Err(invalid("Unknown encryption mode")),
+    }
+    Ok(key)
+}
+
+fn nonce(counter: u64, last: bool) -> [u8; 12] {
+    let mut res = [0u8; 12];
+    res[3..11].copy_from_slice(&counter.to_be_bytes());
+    res[11] = if last { 1 } else { 0 };
+    res
+}
+
+/** Like read_exact, but returns how much was read if the end of the data comes first. */
+fn read_full(source: &mut impl Read, size: usize) -> std::io::Result<Vec<u8>> {
+    let mut res = Vec::with_capacity(size);
+    source.take(size as u64).read_to_end(&mut res)?;
+    Ok(res)
+}
+
+/** The encrypted chunks are not decrypted or encrypted until they are read. */
+struct Chunks<R: Read> {
+    source: R,
+    key: [u8; 32],
+    counter: u64,
+    /** The next chunk of input, read ahead to know whether the current one is the last. */
+    next: Option<Vec<u8>>,
+}
+
+impl<R: Read> Chunks<R> {
+    fn next_chunk(&mut self, size: usize) -> std::io::Result<Option<(Vec<u8>, u64, bool)>> {
+        let current = match self.next.take() {
+            Some(current) => current,
+            None => return Ok(None),
+        };
+        let next = read_full(&mut self.source, size)?;
+        let last = next.is_empty();
+        if !last {
+            self.next = Some(next);
+        }
+        let counter = self.counter;
+        self.counter += 1;
+        Ok(Some((current, counter, last)))
+    }
+}
+
+fn serve(output: &[u8], offset: &mut usize, buf: &mut [u8]) -> usize {
+    let count = std::cmp::min(buf.len(), output.len() - *offset);
+    buf[..count].copy_from_slice(&output[*offset..*offset + count]);
+    *offset += count;
+    count
+}
+
+pub struct Encryptor<R: Read> {
+    source: Option<R>,
+    secret: Secret,
+    chunks: Option<Chunks<R>>,
+    output: Vec<u8>,
+    offset: usize,
+}
+
+impl<R: Read> Encryptor<R> {
+    pub fn new(source: R, secret: Secret) -> Encryptor<R> {
+        Encryptor { source: Some(source), secret, chunks: None, output: Vec::new(), offset: 0 }
+    }
+
+    fn start(&mut self, mut source: R) -> std::io::Result<()> {
+        let mut header = [0u8; HEADER_SIZE];
+        header[..MAGIC.len()].copy_from_slice(MAGIC);
+        header[8] = VERSION;
+        match self.secret {
+            Secret::Password(_) => {
+                header[9] = MODE_PASSWORD;
+                header[10] = LOG_N;
+                header[11] = R;
+                header[12] = P;
+            }
+            Secret::Key(_) => header[9] = MODE_KEY,
+        }
+        openssl::rand::rand_bytes(&mut header[HEADER_SIZE - SALT_SIZE..]).map_err(other)?;
+        let key = derive_key(&self.secret, &header)?;
+        let first = read_full(&mut source, CHUNK_SIZE)?;
+        self.chunks = Some(Chunks { source, key, counter: 0, next: Some(first) });
+        self.output = header.to_vec();
+        Ok(())
+    }
+
+    fn fill(&mut self) -> std::io::Result<()> {
+        let chunks = match self.chunks.as_mut() {
+            Some(chunks) => chunks,
+            None => return Ok(()),
+        };
+        if let Some((plain, counter, last)) = chunks.next_chunk(CHUNK_SIZE)? {
+            let mut crypter = Crypter::new(Cipher::aes_256_gcm(), Mode::Encrypt, &chunks.key, Some(&nonce(counter, last))).map_err(other)?;
+            let mut output = vec![0u8; plain.len() + Cipher::aes_256_gcm().block_size()];
+            let mut count = crypter.update(&plain, &mut output).map_err(other)?;
+            count += crypter.finalize(&mut output[count..]).map_err(other)?;
+            output.truncate(count);
+            let mut tag = [0u8; TAG_SIZE];
+            crypter.get_tag(&mut tag).map_err(other)?;
+            output.extend_from_slice(&tag);
+            self.output = output;
+            self.offset = 0;
+        }
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for Encryptor<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if let Some(source) = self.source.take() {
+            self.start(source)?;
+        }
+        if self.offset == self.output.len() {
+            self.fill()?;
+        }
+        Ok(serve(&self.output, &mut self.offset, buf))
+    }
+}
+
+pub struct Decryptor<R: Read> {
+    source: Option<R>,
+    secret: Secret,
+    chunks: Option<Chunks<R>>,
+    output: Vec<u8>,
+    offset: usize,
+}
+
+impl<R: Read> Decryptor<R> {
+    pub fn new(source: R, secret: Secret) -> Decryptor<R> {
+        Decryptor { source: Some(source), secret, chunks: None, output: Vec::new(), offset: 0 }
+    }
+
+    fn start(&mut self, mut source: R) -> std::io::Result<()> {
+        let mut header = [0u8; HEADER_SIZE];
+        source.read_exact(&mut header)
+            .map_err(|_| invalid("The data is not encrypted with crypt:encrypt"))?;
+        if &header[..MAGIC.len()] != MAGIC {
+            return Err(invalid("The data is not encrypted with crypt:encrypt"));
+        }
+        if header[8] != VERSION {
+            return Err(invalid("Unsupported version of the encryption format"));
+        }
+        let key = derive_key(&self.secret, &header)?;
+        let first = read_full(&mut source, CHUNK_SIZE + TAG_SIZE)?;
+        self.chunks = Some(Chunks { source, key, counter: 0, next: Some(first) });
+        Ok(())
+    }
+
+    fn fill(&mut self) -> std::io::Result<()> {
+        let chunks = match self.chunks.as_mut() {
+            Some(chunks) => chunks,
+            None => return Ok(()),
+        };
+        if let Some((encrypted, counter, last)) = chunks.next_chunk(CHUNK_SIZE + TAG_SIZE)? {
+            if encrypted.len() < TAG_SIZE {
+                return Err(invalid("The encrypted data is truncated"));
+            }
+            let (encrypted, tag) = encrypted.split_at(encrypted.len() - TAG_SIZE);
+            let mut crypter = Crypter::new(Cipher::aes_256_gcm(), Mode::Decrypt, &chunks.key, Some(&nonce(counter, last))).map_err(other)?;
+            crypter.set_tag(tag).map_err(other)?;
+            let mut output = vec![0u8; encrypted.len() + Cipher::aes_256_gcm().block_size()];
+            let mut count = crypter.update(encrypted, &mut output).map_err(other)?;
+            count += crypter.finalize(&mut output[count..])
+                .map_err(|_| invalid("Decryption failed, the password or key is wrong or the data is corrupted"))?;
+            output.truncate(count);
+            self.output = output;
+            self.offset = 0;
+        }
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for Decryptor<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if let Some(source) = self.source.take() {
+            self.start(source)?;
+        }
+        // Decrypting a chunk can produce no output, so keep going until there is some
+        while self.offset == self.output.len() && self.chunks.as_ref().map(|c| c.next.is_some()).unwrap_or(false) {
+            self.fill()?;
+        }
+        Ok(serve(&self.output, &mut self.offset, buf))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encrypt(data: &[u8], secret: Secret) -> Vec<u8> {
+        let mut res = Vec::new();
+        Encryptor::new(data, secret).read_to_end(&mut res).unwrap();
+        res
+    }
+
+    fn decrypt(data: &[u8], secret: Secret) -> std::io::Result<Vec<u8>> {
+        let mut res = Vec::new();
+        Decryptor::new(data, secret).read_to_end(&mut res)?;
+        Ok(res)
+    }
+
+    #[test]
+    fn round_trip() {
+        let key = Secret::Key(b"0123456789".to_vec());
+        for size in &[0, 1, CHUNK_SIZE - 1, CHUNK_SIZE, CHUNK_SIZE + 1, 3 * CHUNK_SIZE] {
+            let data = (0..*size).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+            let encrypted = encrypt(&data, key.clone());
+            assert_eq!(decrypt(&encrypted, key.clone()).unwrap(), data);
+        }
+    }
+
+    #[test]
+    fn password() {
+        let encrypted = encrypt(b"hello", Secret::Password(b"hunter2".to_vec()));
+        assert_eq!(decrypt(&encrypted, Secret::Password(b"hunter2".to_vec())).unwrap(), b"hello");
+        assert!(decrypt(&encrypted, Secret::Password(b"hunter3".to_vec())).is_err());
+        assert!(decrypt(&encrypted, Secret::Key(b"hunter2".to_vec())).is_err());
+    }
+
+    #[test]
+    fn tampering_is_detected() {
+        let key = Secret::Key(b"k".to_vec());
+        let data = vec![7u8; 2 * CHUNK_SIZE + 10];
+        let encrypted = encrypt(&data, key.clone());
+
+        let mut flipped = encrypted.clone();
+        flipped[HEADER_SIZE + 5] ^= 1;
+        assert!(decrypt(&flipped, key.clone()).is_err());
+
+        // Cut after the first chunk, which then looks like the last one
+        let truncated = &encrypted[..HEADER_SIZE + CHUNK_SIZE + TAG_SIZE];
+        assert!(decrypt(truncated, key.clone()).is_err());
+
+        assert!(decrypt(b"not encrypted at all, but long enough", key).is_err());
+    }
+
+    #[test]
+    fn expensive_parameters_are_refused() {
+        let password = Secret::Password(b"hunter2".to_vec());
+        let encrypted = encrypt(b"hello", password.clone());
+        for (log_n, r, p) in &[(21, 1, 1), (20, 255, 1), (15, 255, 255), (15, 8, 128)] {
+            let mut changed = encrypted.clone();
+            changed[10..13].copy_from_slice(&[*log_n, *r, *p]);
+            assert!(decrypt(&changed, password.clone()).err().unwrap().to_string().contains("too expensive"));
+        }
+    }
+}
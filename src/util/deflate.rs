@@ -0,0 +1,496 @@
+use std::io::{Error, ErrorKind, Read, Write};
+use std::cmp::min;
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115,
+    131, 163, 195, 227, 258];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0];
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577];
+const DISTANCE_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13];
+/** The order in which the code lengths of the code length code are stored. */
+const CODE_LENGTH_ORDER: [usize; 19] = [16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15];
+
+/** The maximum distance of a back reference. */
+const WINDOW_SIZE: usize = 32 * 1024;
+/** The amount of data decompressed at a time. */
+const OUTPUT_CHUNK_SIZE: usize = 16 * 1024;
+/** The amount of data compressed into a single block. */
+const INPUT_CHUNK_SIZE: usize = 64 * 1024;
+
+fn invalid(message: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, message)
+}
+
+/**
+A canonical Huffman code, stored as the number of codes of every length and the symbols
+ordered by their code.
+*/
+struct Huffman {
+    count: [u16; 16],
+    symbol: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> std::io::Result<Huffman> {
+        let mut count = [0u16; 16];
+        for length in lengths {
+            count[*length as usize] += 1;
+        }
+        let mut left: i32 = 1;
+        for c in count.iter().skip(1) {
+            left = (left << 1) - i32::from(*c);
+            if left < 0 {
+                return Err(invalid("Invalid deflate stream, over-subscribed Huffman code"));
+            }
+        }
+        let mut offset = [0u16; 16];
+        for length in 1..15 {
+            offset[length + 1] = offset[length] + count[length];
+        }
+        let mut symbol = vec![0u16; lengths.len()];
+        for (sym, length) in lengths.iter().enumerate() {
+            if *length != 0 {
+                symbol[offset[*length as usize] as usize] = sym as u16;
+                offset[*length as usize] += 1;
+            }
+        }
+        Ok(Huffman { count, symbol })
+    }
+
+    fn fixed() -> (Huffman, Huffman) {
+        let mut lengths = [0u8; 288];
+        for (sym, length) in lengths.iter_mut().enumerate() {
+            *length = match sym {
+                0..=143 => 8,
+                144..=255 => 9,
+                256..=279 => 7,
+                _ => 8,
+            };
+        }
+        (Huffman::new(&lengths).unwrap(), Huffman::new(&[5u8; 30]).unwrap())
+    }
+}
+
+struct BitReader<R: Read> {
+    inner: R,
+    buffer: u32,
+    count: u32,
+}
+
+impl<R: Read> BitReader<R> {
+    fn bits(&mut self, n: u32) -> std::io::Result<u32> {
+        while self.count < n {
+            let mut byte = [0u8];
+            self.inner.read_exact(&mut byte)?;
+            self.buffer |= u32::from(byte[0]) << self.count;
+            self.count += 8;
+        }
+        let res = self.buffer & ((1u32 << n) - 1);
+        self.buffer >>= n;
+        self.count -= n;
+        Ok(res)
+    }
+
+    /** Skip to the start of the next byte. */
+    fn align(&mut self) {
+        self.buffer = 0;
+        self.count = 0;
+    }
+
+    fn decode(&mut self, huffman: &Huffman) -> std::io::Result<u16> {
+        let mut code: i32 = 0;
+        let mut first: i32 = 0;
+        let mut index: i32 = 0;
+        for count in huffman.count.iter().skip(1) {
+            code |= self.bits(1)? as i32;
+            let count = i32::from(*count);
+            if code - count < first {
+                return Ok(huffman.symbol[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err(invalid("Invalid deflate stream, unknown Huffman code"))
+    }
+}
+
+enum State {
+    Header,
+    Stored(usize),
+    Codes(Huffman, Huffman),
+    Done,
+}
+
+/**
+A reader that decompresses a deflate stream (RFC 1951) read from another reader. The underlying
+reader is read one byte at a time, and never past the end of the deflate stream, so it should be
+buffered, and whatever follows the deflate stream can be read from it afterwards.
+*/
+pub struct Inflater<R: Read> {
+    input: BitReader<R>,
+    state: State,
+    last: bool,
+    /** All output that has not been read yet, preceded by (at least) the last window of output. */
+    window: Vec<u8>,
+    position: usize,
+}
+
+impl<R: Read> Inflater<R> {
+    pub fn new(inner: R) -> Inflater<R> {
+        Inflater {
+            input: BitReader { inner, buffer: 0, count: 0 },
+            state: State::Header,
+            last: false,
+            window: Vec::new(),
+            position: 0,
+        }
+    }
+
+    /** The underlying reader, positioned right after the end of the deflate stream. */
+    pub fn into_inner(self) -> R {
+        self.input.inner
+    }
+
+    fn header(&mut self) -> std::io::Result<State> {
+        if self.last {
+            return Ok(State::Done);
+        }
+        self.last = self.input.bits(1)? == 1;
+        match self.input.bits(2)? {
+            0 => {
+                self.input.align();
+                let length = self.input.bits(16)?;
+                let complement = self.input.bits(16)?;
+                if length != !complement & 0xffff {
+                    return Err(invalid("Invalid deflate stream, corrupt stored block length"));
+                }
+                Ok(State::Stored(length as usize))
+            }
+            1 => {
+                let (literals, distances) = Huffman::fixed();
+                Ok(State::Codes(literals, distances))
+            }
+            2 => self.dynamic(),
+            _ => Err(invalid("Invalid deflate stream, unknown block type")),
+        }
+    }
+
+    fn dynamic(&mut self) -> std::io::Result<State> {
+        let literal_count = self.input.bits(5)? as usize + 257;
+        let distance_count = self.input.bits(5)? as usize + 1;
+        let code_count = self.input.bits(4)? as usize + 4;
+        let mut code_lengths = [0u8; 19];
+        for idx in CODE_LENGTH_ORDER.iter().take(code_count) {
+            code_lengths[*idx] = self.input.bits(3)? as u8;
+        }
+        let code = Huffman::new(&code_lengths)?;
+        let total = literal_count + distance_count;
+        let mut lengths = vec![0u8; total];
+        let mut idx = 0;
+        while idx < total {
+            let sym = self.input.decode(&code)?;
+            if sym < 16 {
+                lengths[idx] = sym as u8;
+                idx += 1;
+            } else {
+                let (length, repeat) = match sym {
+                    16 if idx == 0 => return Err(invalid("Invalid deflate stream, repeat without a length")),
+                    16 => (lengths[idx - 1], 3 + self.input.bits(2)?),
+                    17 => (0, 3 + self.input.bits(3)?),
+                    _ => (0, 11 + self.input.bits(7)?),
+                };
+                let repeat = repeat as usize;
+                if idx + repeat > total {
+                    return Err(invalid("Invalid deflate stream, too many code lengths"));
+                }
+                for l in &mut lengths[idx..idx + repeat] {
+                    *l = length;
+                }
+                idx += repeat;
+            }
+        }
+        if lengths[256] == 0 {
+            return Err(invalid("Invalid deflate stream, missing end of block code"));
+        }
+        Ok(State::Codes(
+            Huffman::new(&lengths[..literal_count])?,
+            Huffman::new(&lengths[literal_count..])?))
+    }
+
+    /** Decompress more data into the window, or move on to the next state. */
+    fn step(&mut self) -> std::io::Result<()> {
+        let next = match &self.state {
+            State::Header => Some(self.header()?),
+            State::Done => None,
+            State::Stored(0) => Some(State::Header),
+            State::Stored(remaining) => {
+                let count = min(*remaining, OUTPUT_CHUNK_SIZE);
+                let start = self.window.len();
+                self.window.resize(start + count, 0);
+                self.input.inner.read_exact(&mut self.window[start..])?;
+                Some(State::Stored(*remaining - count))
+            }
+            State::Codes(literals, distances) => {
+                let goal = self.window.len() + OUTPUT_CHUNK_SIZE;
+                let mut end_of_block = false;
+                while self.window.len() < goal {
+                    let sym = self.input.decode(literals)? as usize;
+                    if sym < 256 {
+                        self.window.push(sym as u8);
+                    } else if sym == 256 {
+                        end_of_block = true;
+                        break;
+                    } else {
+                        let sym = sym - 257;
+                        if sym >= LENGTH_BASE.len() {
+                            return Err(invalid("Invalid deflate stream, unknown length code"));
+                        }
+                        let length = LENGTH_BASE[sym] as usize
+                            + self.input.bits(u32::from(LENGTH_EXTRA[sym]))? as usize;
+                        let sym = self.input.decode(distances)? as usize;
+                        if sym >= DISTANCE_BASE.len() {
+                            return Err(invalid("Invalid deflate stream, unknown distance code"));
+                        }
+                        let distance = DISTANCE_BASE[sym] as usize
+                            + self.input.bits(u32::from(DISTANCE_EXTRA[sym]))? as usize;
+                        if distance > self.window.len() {
+                            return Err(invalid("Invalid deflate stream, distance too far back"));
+                        }
+                        let start = self.window.len() - distance;
+                        for i in 0..length {
+                            let b = self.window[start + i];
+                            self.window.push(b);
+                        }
+                    }
+                }
+                if end_of_block { Some(State::Header) } else { None }
+            }
+        };
+        if let Some(state) = next {
+            self.state = state;
+        }
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for Inflater<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        loop {
+            if self.position < self.window.len() {
+                let count = min(buf.len(), self.window.len() - self.position);
+                buf[..count].copy_from_slice(&self.window[self.position..self.position + count]);
+                self.position += count;
+                return Ok(count);
+            }
+            if let State::Done = self.state {
+                return Ok(0);
+            }
+            if self.window.len() > 2 * WINDOW_SIZE {
+                let drop = self.window.len() - WINDOW_SIZE;
+                self.window.drain(..drop);
+                self.position -= drop;
+            }
+            self.step()?;
+        }
+    }
+}
+
+/**
+A writer that compresses everything written to it into a deflate stream (RFC 1951) written to
+another writer. The stream is not complete until finish has been called.
+
+Only the fixed Huffman codes are used. That compresses somewhat worse than the dynamic codes
+most other implementations use, but any decompressor can read the output.
+*/
+pub struct Deflater<W: Write> {
+    output: W,
+    pending: Vec<u8>,
+    buffer: u64,
+    count: u32,
+    bytes: Vec<u8>,
+}
+
+fn hash(data: &[u8]) -> usize {
+    ((usize::from(data[0]) << 10) ^ (usize::from(data[1]) << 5) ^ usize::from(data[2])) & 0x7fff
+}
+
+fn insert(data: &[u8], pos: usize, head: &mut [usize], previous: &mut [usize]) {
+    if pos + 2 < data.len() {
+        let h = hash(&data[pos..]);
+        previous[pos] = head[h];
+        head[h] = pos;
+    }
+}
+
+impl<W: Write> Deflater<W> {
+    pub fn new(output: W) -> Deflater<W> {
+        Deflater {
+            output,
+            pending: Vec::new(),
+            buffer: 0,
+            count: 0,
+            bytes: Vec::new(),
+        }
+    }
+
+    /** The underlying writer. Compressed data is written to it one block at a time. */
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.output
+    }
+
+    /** Write the last block and return the underlying writer. */
+    pub fn finish(mut self) -> std::io::Result<W> {
+        let data = std::mem::take(&mut self.pending);
+        self.compress(&data, true)?;
+        if self.count > 0 {
+            self.bytes.push(self.buffer as u8);
+        }
+        self.output.write_all(&self.bytes)?;
+        self.output.flush()?;
+        Ok(self.output)
+    }
+
+    fn put_bits(&mut self, value: u32, n: u32) {
+        self.buffer |= u64::from(value) << self.count;
+        self.count += n;
+        while self.count >= 8 {
+            self.bytes.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    /** Huffman codes are stored starting with the most significant bit. */
+    fn put_code(&mut self, code: u32, length: u32) {
+        let reversed = (0..length).fold(0, |acc, i| (acc << 1) | ((code >> i) & 1));
+        self.put_bits(reversed, length);
+    }
+
+    fn put_symbol(&mut self, sym: u32) {
+        match sym {
+            0..=143 => self.put_code(0x30 + sym, 8),
+            144..=255 => self.put_code(0x190 + sym - 144, 9),
+            256..=279 => self.put_code(sym - 256, 7),
+            _ => self.put_code(0xc0 + sym - 280, 8),
+        }
+    }
+
+    fn put_match(&mut self, length: usize, distance: usize) {
+        let idx = LENGTH_BASE.iter().rposition(|b| *b as usize <= length).unwrap();
+        self.put_symbol(257 + idx as u32);
+        self.put_bits((length - LENGTH_BASE[idx] as usize) as u32, u32::from(LENGTH_EXTRA[idx]));
+        let idx = DISTANCE_BASE.iter().rposition(|b| *b as usize <= distance).unwrap();
+        self.put_code(idx as u32, 5);
+        self.put_bits((distance - DISTANCE_BASE[idx] as usize) as u32, u32::from(DISTANCE_EXTRA[idx]));
+    }
+
+    /** Compress the data into a single block, finding repeated strings using hash chains. */
+    fn compress(&mut self, data: &[u8], last: bool) -> std::io::Result<()> {
+        self.put_bits(if last { 1 } else { 0 }, 1);
+        self.put_bits(1, 2);
+        let mut head = vec![usize::MAX; 0x8000];
+        let mut previous = vec![usize::MAX; data.len()];
+        let mut i = 0;
+        while i < data.len() {
+            let mut best_length = 0;
+            let mut best_distance = 0;
+            if i + 2 < data.len() {
+                let max = min(258, data.len() - i);
+                let mut candidate = head[hash(&data[i..])];
+                let mut chain = 0;
+                while candidate != usize::MAX && i - candidate <= WINDOW_SIZE && chain < 64 {
+                    let length = (0..max).find(|l| data[candidate + l] != data[i + l]).unwrap_or(max);
+                    if length > best_length {
+                        best_length = length;
+                        best_distance = i - candidate;
+                        if length == max {
+                            break;
+                        }
+                    }
+                    candidate = previous[candidate];
+                    chain += 1;
+                }
+            }
+            if best_length >= 3 {
+                self.put_match(best_length, best_distance);
+                for pos in i..i + best_length {
+                    insert(data, pos, &mut head, &mut previous);
+                }
+                i += best_length;
+            } else {
+                self.put_symbol(u32::from(data[i]));
+                insert(data, i, &mut head, &mut previous);
+                i += 1;
+            }
+        }
+        self.put_symbol(256);
+        let bytes = std::mem::take(&mut self.bytes);
+        self.output.write_all(&bytes)
+    }
+}
+
+impl<W: Write> Write for Deflater<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.pending.extend_from_slice(buf);
+        while self.pending.len() >= INPUT_CHUNK_SIZE {
+            let chunk: Vec<u8> = self.pending.drain(..INPUT_CHUNK_SIZE).collect();
+            self.compress(&chunk, false)?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.output.flush()
+    }
+}
+
+pub fn deflate(data: &[u8]) -> std::io::Result<Vec<u8>> {
+    let mut deflater = Deflater::new(Vec::new());
+    deflater.write_all(data)?;
+    deflater.finish()
+}
+
+pub fn inflate(data: &[u8]) -> std::io::Result<Vec<u8>> {
+    let mut res = Vec::new();
+    Inflater::new(data).read_to_end(&mut res)?;
+    Ok(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_inflate_dynamic() {
+        let compressed = [
+            0xb5, 0xcb, 0xd9, 0x11, 0x40, 0x30, 0x14, 0x46, 0xe1, 0x56, 0x7e, 0x0d, 0x18, 0xfb, 0xd2, 0x85,
+            0x07, 0x0d, 0x58, 0x42, 0x62, 0xbb, 0x84, 0x58, 0x52, 0xbd, 0x5b, 0x83, 0x19, 0xcf, 0xe7, 0x3b,
+            0xa5, 0x14, 0xd8, 0x8c, 0x6a, 0x46, 0xd4, 0x9a, 0xae, 0x05, 0x1d, 0xdd, 0x18, 0xcc, 0xbc, 0xee,
+            0xa0, 0x53, 0x68, 0x1c, 0x9c, 0xa7, 0xca, 0x3e, 0x68, 0xa9, 0x77, 0x51, 0xfe, 0x86, 0x8b, 0x8a,
+            0xdd, 0xfc, 0xa0, 0x66, 0x74, 0xa9, 0x43, 0xa2, 0x53, 0xa7, 0xe0, 0x64, 0xc5, 0x82, 0x49, 0x6d,
+            0x86, 0x34, 0xbf, 0xfd, 0xee, 0xc0, 0xf3, 0x83, 0x30, 0x8a, 0x93, 0x34, 0xcb, 0x3f, 0x3d, 0x2f];
+        let expected = "The quick brown fox jumps over the lazy dog. ".repeat(3)
+            + &"Pack my box with five dozen liquor jugs! 0123456789 ".repeat(2);
+        assert_eq!(inflate(&compressed).unwrap(), expected.as_bytes());
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut data = Vec::new();
+        for i in 0..200_000u64 {
+            data.push((i * i % 251) as u8);
+            if i % 7 == 0 {
+                data.extend_from_slice(b"repeated text ");
+            }
+        }
+        let compressed = deflate(&data).unwrap();
+        assert!(compressed.len() < data.len());
+        assert_eq!(inflate(&compressed).unwrap(), data);
+        assert_eq!(inflate(&deflate(b"").unwrap()).unwrap(), b"");
+    }
+}
@@ -0,0 +1,325 @@
+/**
+A minimal DNS stub resolver. Queries are sent over UDP to a single server, and retried over
+TCP if the answer was truncated. Only the answer section of the response is used, and there
+is no caching, so this is meant for looking things up, not for resolving names in bulk.
+*/
+use std::io::{Read, Write};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpStream, UdpSocket};
+use std::time::Duration;
+
+use crate::lang::errors::{CrushResult, argument_error, data_error, error, to_crush_error};
+
+const TYPES: [(&str, u16); 12] = [
+    ("A", 1),
+    ("NS", 2),
+    ("CNAME", 5),
+    ("SOA", 6),
+    ("PTR", 12),
+    ("MX", 15),
+    ("TXT", 16),
+    ("AAAA", 28),
+    ("SRV", 33),
+    ("DS", 43),
+    ("DNSKEY", 48),
+    ("CAA", 257),
+];
+
+#[derive(Debug, PartialEq)]
+pub struct Record {
+    pub name: String,
+    pub ttl: u32,
+    pub record_type: u16,
+    pub value: String,
+}
+
+pub fn record_type(name: &str) -> CrushResult<u16> {
+    let upper = name.to_uppercase();
+    if let Some((_, code)) = TYPES.iter().find(|(n, _)| *n == upper) {
+        return Ok(*code);
+    }
+    match upper.strip_prefix("TYPE").map(|n| n.parse::<u16>()) {
+        Some(Ok(code)) => Ok(code),
+        _ => argument_error(format!("Unknown record type {}", name).as_str()),
+    }
+}
+
+pub fn type_name(code: u16) -> String {
+    match TYPES.iter().find(|(_, c)| *c == code) {
+        Some((name, _)) => name.to_string(),
+        None => format!("TYPE{}", code),
+    }
+}
+
+/** The name to look up the PTR record of an address under. */
+pub fn reverse_name(ip: &IpAddr) -> String {
+    match ip {
+        IpAddr::V4(ip) => {
+            let o = ip.octets();
+            format!("{}.{}.{}.{}.in-addr.arpa", o[3], o[2], o[1], o[0])
+        }
+        IpAddr::V6(ip) => {
+            let mut res = String::new();
+            for b in ip.octets().iter().rev() {
+                res.push_str(&format!("{:x}.{:x}.", b & 0xf, b >> 4));
+            }
+            res + "ip6.arpa"
+        }
+    }
+}
+
+/** The first nameserver in /etc/resolv.conf. */
+pub fn system_server() -> SocketAddr {
+    std::fs::read_to_string("/etc/resolv.conf").ok()
+        .and_then(|conf| conf.lines()
+            .filter_map(|line| line.trim().strip_prefix("nameserver"))
+            .filter_map(|address| address.trim().split('%').next()?.parse::<IpAddr>().ok())
+            .next())
+        .map(|ip| SocketAddr::new(ip, 53))
+        .unwrap_or_else(|| SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 53))
+}
+
+pub fn encode_query(id: u16, name: &str, record_type: u16) -> CrushResult<Vec<u8>> {
+    let mut res = Vec::new();
+    res.extend_from_slice(&id.to_be_bytes());
+    // Recursion desired, one question
+    res.extend_from_slice(&[0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0]);
+    for label in name.trim_end_matches('.').split('.').filter(|l| !l.is_empty()) {
+        if label.len() > 63 {
+            return argument_error(format!("Invalid name {}", name).as_str());
+        }
+        res.push(label.len() as u8);
+        res.extend_from_slice(label.as_bytes());
+    }
+    res.push(0);
+    res.extend_from_slice(&record_type.to_be_bytes());
+    res.extend_from_slice(&1u16.to_be_bytes());
+    Ok(res)
+}
+
+struct Message<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Message<'a> {
+    fn bytes(&self, pos: usize, len: usize) -> CrushResult<&'a [u8]> {
+        match self.data.get(pos..pos + len) {
+            Some(b) => Ok(b),
+            None => data_error("Truncated DNS message"),
+        }
+    }
+
+    fn u8(&self, pos: usize) -> CrushResult<u8> {
+        Ok(self.bytes(pos, 1)?[0])
+    }
+
+    fn u16(&self, pos: usize) -> CrushResult<u16> {
+        let b = self.bytes(pos, 2)?;
+        Ok(u16::from_be_bytes([b[0], b[1]]))
+    }
+
+    fn u32(&self, pos: usize) -> CrushResult<u32> {
+        let b = self.bytes(pos, 4)?;
+        Ok(u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    /** Reads a possibly compressed name, and returns it and the position after it. */
+    fn name(&self, mut pos: usize) -> CrushResult<(String, usize)> {
+        let mut labels = Vec::new();
+        let mut end = None;
+        let mut jumps = 0;
+        loop {
+            let len = self.u8(pos)?;
+            if len & 0xc0 == 0xc0 {
+                jumps += 1;
+                if jumps > 64 {
+                    return data_error("Invalid name compression in DNS message");
+                }
+                end.get_or_insert(pos + 2);
+                pos = (self.u16(pos)? & 0x3fff) as usize;
+            } else if len == 0 {
+                let name = labels.join(".");
+                return Ok((name, end.unwrap_or(pos + 1)));
+            } else {
+                labels.push(String::from_utf8_lossy(self.bytes(pos + 1, len as usize)?).to_string());
+                pos += 1 + len as usize;
+            }
+        }
+    }
+
+    fn character_strings(&self, mut pos: usize, end: usize) -> CrushResult<Vec<String>> {
+        let mut res = Vec::new();
+        while pos < end {
+            let len = self.u8(pos)? as usize;
+            res.push(String::from_utf8_lossy(self.bytes(pos + 1, len)?).to_string());
+            pos += 1 + len;
+        }
+        Ok(res)
+    }
+
+    fn value(&self, record_type: u16, pos: usize, len: usize) -> CrushResult<String> {
+        let end = pos + len;
+        Ok(match type_name(record_type).as_str() {
+            "A" if len == 4 => {
+                let b = self.bytes(pos, 4)?;
+                Ipv4Addr::new(b[0], b[1], b[2], b[3]).to_string()
+            }
+            "AAAA" if len == 16 => {
+                let mut b = [0u8; 16];
+                b.copy_from_slice(self.bytes(pos, 16)?);
+                Ipv6Addr::from(b).to_string()
+            }
+            "NS" | "CNAME" | "PTR" => self.name(pos)?.0,
+            "MX" => format!("{} {}", self.u16(pos)?, self.name(pos + 2)?.0),
+            "TXT" => self.character_strings(pos, end)?.join(""),
+            "SOA" => {
+                let (mname, pos) = self.name(pos)?;
+                let (rname, pos) = self.name(pos)?;
+                let numbers = (0..5)
+                    .map(|i| self.u32(pos + 4 * i).map(|n| n.to_string()))
+                    .collect::<CrushResult<Vec<_>>>()?;
+                format!("{} {} {}", mname, rname, numbers.join(" "))
+            }
+            "SRV" => format!(
+                "{} {} {} {}",
+                self.u16(pos)?, self.u16(pos + 2)?, self.u16(pos + 4)?, self.name(pos + 6)?.0),
+            "CAA" => {
+                let tag_len = self.u8(pos + 1)? as usize;
+                let tag = String::from_utf8_lossy(self.bytes(pos + 2, tag_len)?);
+                let value = String::from_utf8_lossy(self.bytes(pos + 2 + tag_len, len.saturating_sub(2 + tag_len))?);
+                format!("{} {} \"{}\"", self.u8(pos)?, tag, value)
+            }
+            _ => self.bytes(pos, len)?.iter().map(|b| format!("{:02x}", b)).collect(),
+        })
+    }
+}
+
+/** Returns the records in the answer section, or true if the response was truncated. */
+pub fn decode_response(id: u16, data: &[u8]) -> CrushResult<(Vec<Record>, bool)> {
+    let message = Message { data };
+    if message.u16(0)? != id {
+        return data_error("DNS response does not match the query");
+    }
+    let flags = message.u16(2)?;
+    if flags & 0x0200 != 0 {
+        return Ok((Vec::new(), true));
+    }
+    match flags & 0x000f {
+        0 => {}
+        3 => return error("No such domain"),
+        2 => return error("The DNS server failed to answer the query"),
+        5 => return error("The DNS server refused the query"),
+        code => return error(format!("DNS query failed with response code {}", code).as_str()),
+    }
+    let questions = message.u16(4)?;
+    let answers = message.u16(6)?;
+    let mut pos = 12;
+    for _ in 0..questions {
+        pos = message.name(pos)?.1 + 4;
+    }
+    let mut res = Vec::new();
+    for _ in 0..answers {
+        let (name, next) = message.name(pos)?;
+        let record_type = message.u16(next)?;
+        let ttl = message.u32(next + 4)?;
+        let len = message.u16(next + 8)? as usize;
+        pos = next + 10;
+        res.push(Record {
+            name,
+            ttl,
+            record_type,
+            value: message.value(record_type, pos, len)?,
+        });
+        pos += len;
+    }
+    Ok((res, false))
+}
+
+fn query_tcp(query: &[u8], server: SocketAddr, timeout: Duration) -> CrushResult<Vec<u8>> {
+    let mut stream = to_crush_error(TcpStream::connect_timeout(&server, timeout))?;
+    to_crush_error(stream.set_read_timeout(Some(timeout)))?;
+    to_crush_error(stream.write_all(&(query.len() as u16).to_be_bytes()))?;
+    to_crush_error(stream.write_all(query))?;
+    let mut len = [0u8; 2];
+    to_crush_error(stream.read_exact(&mut len))?;
+    let mut res = vec![0u8; u16::from_be_bytes(len) as usize];
+    to_crush_error(stream.read_exact(&mut res))?;
+    Ok(res)
+}
+
+pub fn query(name: &str, record_type: u16, server: SocketAddr, timeout: Duration) -> CrushResult<Vec<Record>> {
+    let id = rand::random::<u16>();
+    let query = encode_query(id, name, record_type)?;
+    let local = if server.is_ipv4() { "0.0.0.0:0" } else { "[::]:0" };
+    let socket = to_crush_error(UdpSocket::bind(local))?;
+    to_crush_error(socket.set_read_timeout(Some(timeout)))?;
+    to_crush_error(socket.send_to(&query, server))?;
+    let mut buffer = [0u8; 4096];
+    loop {
+        let (len, from) = match socket.recv_from(&mut buffer) {
+            Ok(res) => res,
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock || e.kind() == std::io::ErrorKind::TimedOut =>
+                return error(format!("No response from DNS server {}", server).as_str()),
+            Err(e) => return to_crush_error(Err(e)),
+        };
+        // Ignore stray packets from elsewhere, e.g. late answers to earlier queries
+        if from != server || len < 2 || buffer[0..2] != id.to_be_bytes() {
+            continue;
+        }
+        let (records, truncated) = decode_response(id, &buffer[..len])?;
+        return if truncated {
+            Ok(decode_response(id, &query_tcp(&query, server, timeout)?)?.0)
+        } else {
+            Ok(records)
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encode_query_works() {
+        assert_eq!(
+            encode_query(0x1234, "a.bc.", 1).unwrap(),
+            vec![0x12, 0x34, 1, 0, 0, 1, 0, 0, 0, 0, 0, 0, 1, b'a', 2, b'b', b'c', 0, 0, 1, 0, 1]);
+    }
+
+    #[test]
+    fn decode_response_works() {
+        let mut data = encode_query(7, "example.com", 15).unwrap();
+        // Set the response flag and the answer count
+        data[2] = 0x81;
+        data[3] = 0x80;
+        data[7] = 2;
+        // MX 10 mail.example.com, with the name compressed to point at the question
+        data.extend_from_slice(&[0xc0, 12, 0, 15, 0, 1, 0, 0, 0x0e, 0x10, 0, 9, 0, 10, 4]);
+        data.extend_from_slice(b"mail");
+        data.extend_from_slice(&[0xc0, 12]);
+        // TXT split into two character strings
+        data.extend_from_slice(&[0xc0, 12, 0, 16, 0, 1, 0, 0, 0, 60, 0, 6, 2, b'a', b'b', 2, b'c', b'd']);
+        let (records, truncated) = decode_response(7, &data).unwrap();
+        assert!(!truncated);
+        assert_eq!(records, vec![
+            Record { name: "example.com".to_string(), ttl: 3600, record_type: 15, value: "10 mail.example.com".to_string() },
+            Record { name: "example.com".to_string(), ttl: 60, record_type: 16, value: "abcd".to_string() },
+        ]);
+    }
+
+    #[test]
+    fn decode_response_rejects_loops() {
+        let mut data = encode_query(7, "example.com", 1).unwrap();
+        data[7] = 1;
+        // An answer whose name points at itself
+        data.extend_from_slice(&[0xc0, 29]);
+        assert!(decode_response(7, &data).is_err());
+    }
+
+    #[test]
+    fn reverse_name_works() {
+        assert_eq!(reverse_name(&"192.0.2.1".parse().unwrap()), "1.2.0.192.in-addr.arpa");
+        assert_eq!(
+            reverse_name(&"2001:db8::1".parse().unwrap()),
+            "1.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.0.8.b.d.0.1.0.0.2.ip6.arpa");
+    }
+}
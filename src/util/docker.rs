@@ -0,0 +1,273 @@
+/**
+A minimal client for the Docker Engine API, which is HTTP served on a unix socket. Requests
+are made with a fresh connection each, using HTTP/1.1 with Connection: close, so a response
+ends when the body does, whether it is sent with a length, chunked or until the end.
+*/
+use std::collections::VecDeque;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::os::unix::net::UnixStream;
+use std::path::PathBuf;
+
+use crate::lang::errors::{CrushResult, argument_error, data_error, error, to_crush_error};
+
+const DEFAULT_SOCKET: &str = "/var/run/docker.sock";
+
+/** The socket of the daemon, from DOCKER_HOST if it is a unix socket. */
+pub fn socket_path() -> CrushResult<PathBuf> {
+    match std::env::var("DOCKER_HOST") {
+        Ok(host) if !host.is_empty() => match host.strip_prefix("unix://") {
+            Some(path) => Ok(PathBuf::from(path)),
+            None => argument_error(format!("Only unix sockets are supported, but DOCKER_HOST is {}", host).as_str()),
+        },
+        _ => Ok(PathBuf::from(DEFAULT_SOCKET)),
+    }
+}
+
+/** Reads a chunked transfer encoded body. */
+struct Chunked<R: BufRead> {
+    source: R,
+    remaining: usize,
+    done: bool,
+}
+
+impl<R: BufRead> Read for Chunked<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        if self.done || buf.is_empty() {
+            return Ok(0);
+        }
+        if self.remaining == 0 {
+            let mut line = String::new();
+            // The data of the previous chunk ends with a line break
+            while line.trim().is_empty() {
+                line.clear();
+                if self.source.read_line(&mut line)? == 0 {
+                    self.done = true;
+                    return Ok(0);
+                }
+            }
+            let size = line.trim().split(';').next().unwrap_or("");
+            self.remaining = usize::from_str_radix(size, 16)
+                .map_err(|_| std::io::Error::new(std::io::ErrorKind::InvalidData, "Invalid chunk size"))?;
+            if self.remaining == 0 {
+                self.done = true;
+                return Ok(0);
+            }
+        }
+        let len = buf.len().min(self.remaining);
+        let count = self.source.read(&mut buf[..len])?;
+        if count == 0 {
+            return Err(std::io::Error::new(std::io::ErrorKind::UnexpectedEof, "Truncated chunk"));
+        }
+        self.remaining -= count;
+        Ok(count)
+    }
+}
+
+pub struct Response {
+    pub status: u16,
+    pub body: Box<dyn Read + Send>,
+}
+
+impl Response {
+    /** Parses the status line and headers, leaving the body to be read. */
+    fn parse(mut source: impl BufRead + Send + 'static) -> CrushResult<Response> {
+        let mut line = String::new();
+        to_crush_error(source.read_line(&mut line))?;
+        let status = match line.split_whitespace().nth(1).map(|s| s.parse::<u16>()) {
+            Some(Ok(status)) => status,
+            _ => return data_error("Invalid response from the Docker daemon"),
+        };
+        let mut length = None;
+        let mut chunked = false;
+        loop {
+            line.clear();
+            if to_crush_error(source.read_line(&mut line))? == 0 || line.trim().is_empty() {
+                break;
+            }
+            if let Some((name, value)) = line.split_once(':') {
+                match name.trim().to_lowercase().as_str() {
+                    "content-length" => length = value.trim().parse::<u64>().ok(),
+                    "transfer-encoding" => chunked = value.trim().eq_ignore_ascii_case("chunked"),
+                    _ => {}
+                }
+            }
+        }
+        let body: Box<dyn Read + Send> = match (chunked, length) {
+            (true, _) => Box::new(Chunked { source, remaining: 0, done: false }),
+            (false, Some(length)) => Box::new(source.take(length)),
+            (false, None) => Box::new(source),
+        };
+        Ok(Response { status, body })
+    }
+
+    /** Fails with the message of the daemon unless the request succeeded. */
+    pub fn check(mut self) -> CrushResult<Response> {
+        if self.status < 300 {
+            return Ok(self);
+        }
+        let mut text = String::new();
+        let _ = self.body.read_to_string(&mut text);
+        let message = serde_json::from_str::<serde_json::Value>(&text).ok()
+            .and_then(|json| json["message"].as_str().map(|m| m.to_string()))
+            .unwrap_or_else(|| text.trim().to_string());
+        error(format!("Docker request failed with status {}: {}", self.status, message).as_str())
+    }
+
+    pub fn json(self) -> CrushResult<serde_json::Value> {
+        to_crush_error(serde_json::from_reader(self.check()?.body))
+    }
+}
+
+pub fn request(method: &str, path: &str, body: Option<&serde_json::Value>) -> CrushResult<Response> {
+    let socket = socket_path()?;
+    let mut stream = match UnixStream::connect(&socket) {
+        Ok(stream) => stream,
+        Err(e) => return error(format!("Could not connect to the Docker daemon at {}: {}", socket.display(), e).as_str()),
+    };
+    let body = body.map(|b| b.to_string()).unwrap_or_default();
+    let mut head = format!("{} {} HTTP/1.1\r\nHost: docker\r\nConnection: close\r\n", method, path);
+    if method == "POST" {
+        head.push_str(&format!("Content-Type: application/json\r\nContent-Length: {}\r\n", body.len()));
+    }
+    head.push_str("\r\n");
+    to_crush_error(stream.write_all(head.as_bytes()))?;
+    to_crush_error(stream.write_all(body.as_bytes()))?;
+    Response::parse(BufReader::new(stream))
+}
+
+/** Escapes a value for use in the query string of a request. */
+pub fn escape(value: &str) -> String {
+    let mut res = String::new();
+    for b in value.bytes() {
+        if b.is_ascii_alphanumeric() || b"-_.~".contains(&b) {
+            res.push(b as char);
+        } else {
+            res.push_str(&format!("%{:02X}", b));
+        }
+    }
+    res
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Output {
+    Stdout,
+    Stderr,
+}
+
+impl Output {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Output::Stdout => "stdout",
+            Output::Stderr => "stderr",
+        }
+    }
+}
+
+/**
+Splits the output of a container into lines. Unless the container has a terminal, stdout and
+stderr are multiplexed into frames, each with an eight byte header holding the stream it
+belongs to and its length. Lines can span frames, so each stream keeps its own partial line.
+*/
+pub struct Lines {
+    source: Box<dyn Read + Send>,
+    tty: bool,
+    partial: [Vec<u8>; 2],
+    lines: VecDeque<(Output, String)>,
+    done: bool,
+}
+
+impl Lines {
+    pub fn new(source: Box<dyn Read + Send>, tty: bool) -> Lines {
+        Lines { source, tty, partial: [Vec::new(), Vec::new()], lines: VecDeque::new(), done: false }
+    }
+
+    fn add(&mut self, output: Output, data: &[u8]) {
+        let partial = &mut self.partial[output as usize];
+        partial.extend_from_slice(data);
+        while let Some(idx) = partial.iter().position(|b| *b == b'\n') {
+            let line = partial.drain(..=idx).collect::<Vec<_>>();
+            let line = String::from_utf8_lossy(&line[..idx]);
+            self.lines.push_back((output, line.trim_end_matches('\r').to_string()));
+        }
+    }
+
+    /** Reads more data, and returns false at the end of the output. */
+    fn fill(&mut self) -> CrushResult<bool> {
+        if self.tty {
+            let mut buf = [0u8; 8192];
+            let count = to_crush_error(self.source.read(&mut buf))?;
+            self.add(Output::Stdout, &buf[..count]);
+            return Ok(count > 0);
+        }
+        let mut header = [0u8; 8];
+        match self.source.read_exact(&mut header) {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(false),
+            Err(e) => return to_crush_error(Err(e)),
+        }
+        let mut data = vec![0u8; u32::from_be_bytes([header[4], header[5], header[6], header[7]]) as usize];
+        to_crush_error(self.source.read_exact(&mut data))?;
+        self.add(if header[0] == 2 { Output::Stderr } else { Output::Stdout }, &data);
+        Ok(true)
+    }
+
+    pub fn next(&mut self) -> CrushResult<Option<(Output, String)>> {
+        while self.lines.is_empty() && !self.done {
+            if !self.fill()? {
+                self.done = true;
+                // Output that doesn't end with a line break still counts as a line
+                for output in &[Output::Stdout, Output::Stderr] {
+                    if !self.partial[*output as usize].is_empty() {
+                        self.add(*output, b"\n");
+                    }
+                }
+            }
+        }
+        Ok(self.lines.pop_front())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn chunked_response() {
+        let response = Response::parse(Cursor::new(
+            b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhello\r\n7\r\n world!\r\n0\r\n\r\n".to_vec())).unwrap();
+        assert_eq!(response.status, 200);
+        let mut body = String::new();
+        response.check().unwrap().body.read_to_string(&mut body).unwrap();
+        assert_eq!(body, "hello world!");
+    }
+
+    #[test]
+    fn error_response() {
+        let response = Response::parse(Cursor::new(
+            b"HTTP/1.1 404 Not Found\r\nContent-Length: 36\r\n\r\n{\"message\":\"No such container: foo\"}".to_vec())).unwrap();
+        assert_eq!(
+            response.check().err().unwrap().message,
+            "Docker request failed with status 404: No such container: foo");
+    }
+
+    #[test]
+    fn multiplexed_lines() {
+        let mut data = vec![1, 0, 0, 0, 0, 0, 0, 4];
+        data.extend_from_slice(b"a\nbc");
+        data.extend_from_slice(&[2, 0, 0, 0, 0, 0, 0, 4]);
+        data.extend_from_slice(b"err\n");
+        data.extend_from_slice(&[1, 0, 0, 0, 0, 0, 0, 2]);
+        data.extend_from_slice(b"d\n");
+        let mut lines = Lines::new(Box::new(Cursor::new(data)), false);
+        assert_eq!(lines.next().unwrap(), Some((Output::Stdout, "a".to_string())));
+        assert_eq!(lines.next().unwrap(), Some((Output::Stderr, "err".to_string())));
+        assert_eq!(lines.next().unwrap(), Some((Output::Stdout, "bcd".to_string())));
+        assert_eq!(lines.next().unwrap(), None);
+    }
+
+    #[test]
+    fn escape_works() {
+        assert_eq!(escape("a b/c"), "a%20b%2Fc");
+    }
+}
@@ -0,0 +1,111 @@
+/**
+A set of keys that are forgotten when they have not been seen for a while, for stream commands
+that need to remember what they have seen without running out of memory on endless streams.
+Every key has a value, usually the last row seen with the key, which is handed back when the key
+expires.
+*/
+use std::collections::{HashMap, VecDeque};
+use std::hash::Hash;
+use std::time::{Duration, Instant};
+
+pub struct Expiry<K, V> {
+    ttl: Duration,
+    entries: HashMap<K, (Instant, V)>,
+    /**
+    The deadlines of all keys, soonest first. Since every key gets the same ttl, deadlines are
+    added in order. A key that was seen again has more than one deadline here, but only the one
+    in entries counts.
+    */
+    deadlines: VecDeque<(Instant, K)>,
+}
+
+impl<K: Hash + Eq + Clone, V> Expiry<K, V> {
+    pub fn new(ttl: Duration) -> Expiry<K, V> {
+        Expiry {
+            ttl,
+            entries: HashMap::new(),
+            deadlines: VecDeque::new(),
+        }
+    }
+
+    /** Records that the key was seen, and returns true if it was not already known. */
+    pub fn touch(&mut self, key: K, value: V, now: Instant) -> bool {
+        let deadline = now + self.ttl;
+        self.deadlines.push_back((deadline, key.clone()));
+        self.entries.insert(key, (deadline, value)).is_none()
+    }
+
+    /** Forgets a key without it expiring, and returns its value. */
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        self.entries.remove(key).map(|(_, value)| value)
+    }
+
+    fn is_current(&self, deadline: Instant, key: &K) -> bool {
+        self.entries.get(key).map(|(d, _)| *d == deadline).unwrap_or(false)
+    }
+
+    /** When the next key expires, if there are any. */
+    pub fn next_deadline(&mut self) -> Option<Instant> {
+        while let Some((deadline, key)) = self.deadlines.front() {
+            if self.is_current(*deadline, key) {
+                return Some(*deadline);
+            }
+            self.deadlines.pop_front();
+        }
+        None
+    }
+
+    /** Removes and returns the keys that have expired by now, in the order they expired. */
+    pub fn expire(&mut self, now: Instant) -> Vec<(K, V)> {
+        let mut res = Vec::new();
+        while let Some(deadline) = self.next_deadline() {
+            if deadline > now {
+                break;
+            }
+            if let Some((_, key)) = self.deadlines.pop_front() {
+                if let Some((_, value)) = self.entries.remove(&key) {
+                    res.push((key, value));
+                }
+            }
+        }
+        res
+    }
+
+    /** Removes and returns all keys, in the order they would have expired. */
+    pub fn drain(&mut self) -> Vec<(K, V)> {
+        let mut res = Vec::new();
+        while let Some((deadline, key)) = self.deadlines.pop_front() {
+            if self.is_current(deadline, &key) {
+                if let Some((_, value)) = self.entries.remove(&key) {
+                    res.push((key, value));
+                }
+            }
+        }
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expiry() {
+        let start = Instant::now();
+        let at = |ms: u64| start + Duration::from_millis(ms);
+        let mut keys = Expiry::new(Duration::from_millis(100));
+        assert!(keys.touch("a", 1, at(0)));
+        assert!(keys.touch("b", 2, at(50)));
+        assert!(!keys.touch("a", 3, at(60)));
+        assert_eq!(keys.next_deadline(), Some(at(150)));
+        assert!(keys.expire(at(149)).is_empty());
+        assert_eq!(keys.expire(at(160)), vec![("b", 2), ("a", 3)]);
+        assert_eq!(keys.next_deadline(), None);
+        assert!(keys.touch("a", 4, at(200)));
+        assert!(keys.touch("c", 5, at(210)));
+        assert_eq!(keys.remove(&"a"), Some(4));
+        assert_eq!(keys.next_deadline(), Some(at(310)));
+        assert_eq!(keys.drain(), vec![("c", 5)]);
+        assert_eq!(keys.next_deadline(), None);
+    }
+}
@@ -0,0 +1,47 @@
+/**
+Helpers for commands that are implemented by running an external program and reading its
+output, like journalctl and systemctl.
+*/
+use std::io::{BufReader, ErrorKind, Read};
+use std::process::{Child, ChildStdout, Command, Stdio};
+
+use crate::lang::errors::{CrushResult, error, to_crush_error};
+
+/** Starts a program with its output piped. */
+pub fn spawn(program: &str, mut command: Command) -> CrushResult<(Child, BufReader<ChildStdout>)> {
+    let mut child = match command
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn() {
+        Ok(child) => child,
+        Err(e) if e.kind() == ErrorKind::NotFound => return error(format!("{} is not installed", program).as_str()),
+        Err(e) => return to_crush_error(Err(e)),
+    };
+    match child.stdout.take() {
+        Some(stdout) => Ok((child, BufReader::new(stdout))),
+        None => error(format!("Failed to read the output of {}", program).as_str()),
+    }
+}
+
+/**
+Waits for a program to exit after its output has been read, or kills it if reading failed, e.g.
+because the commands reading our output stopped. If the program failed, the error is what it
+wrote to stderr.
+*/
+pub fn finish(program: &str, mut child: Child, result: CrushResult<()>) -> CrushResult<()> {
+    if result.is_err() {
+        let _ = child.kill();
+        let _ = child.wait();
+        return result;
+    }
+    let status = to_crush_error(child.wait())?;
+    if status.success() {
+        return Ok(());
+    }
+    let mut message = String::new();
+    if let Some(mut stderr) = child.stderr.take() {
+        let _ = stderr.read_to_string(&mut message);
+    }
+    error(format!("{} failed: {}", program, message.trim().trim_start_matches("fatal: ")).as_str())
+}
@@ -14,3 +14,8 @@ pub fn home() -> CrushResult<PathBuf> {
         None => error("Could not find users home directory"),
     }
 }
+
+/** The directory of the configuration file, the history and other per user files of crush. */
+pub fn config_dir() -> CrushResult<PathBuf> {
+    Ok(home()?.join(".config").join("crush"))
+}
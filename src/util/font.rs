@@ -0,0 +1,135 @@
+pub const WIDTH: usize = 8;
+pub const HEIGHT: usize = 16;
+/** The distance from the top of a glyph to the baseline, in pixels. */
+pub const BASELINE: usize = 12;
+
+const FIRST: u32 = 0x20;
+
+/**
+A bitmap font for drawing text into images, covering printable ASCII. The glyphs were rendered
+from DejaVu Sans Mono at 13 pixels per em. Each pixel has a coverage from 0 (transparent) to
+3 (solid), stored as two bits per pixel with the leftmost pixel in the high bits of each row.
+*/
+const GLYPHS: [[u16; HEIGHT]; 95] = [
+    [0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x0000], // ' '
+    [0x0000, 0x0000, 0x0000, 0x0280, 0x0280, 0x0280, 0x0280, 0x0280, 0x0280, 0x0140, 0x0000, 0x0280, 0x0140, 0x0000, 0x0000, 0x0000], // '!'
+    [0x0000, 0x0000, 0x0000, 0x0960, 0x0960, 0x0960, 0x0410, 0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x0000], // '"'
+    [0x0000, 0x0000, 0x0000, 0x0248, 0x0318, 0x1769, 0x6fba, 0x0930, 0x0c60, 0xaee9, 0x1890, 0x24c0, 0x1040, 0x0000, 0x0000, 0x0000], // '#'
+    [0x0000, 0x0000, 0x0040, 0x0180, 0x0ae4, 0x2994, 0x2980, 0x1e80, 0x06f4, 0x019c, 0x018c, 0x2aa8, 0x0690, 0x0180, 0x0040, 0x0000], // '$'
+    [0x0000, 0x0000, 0x0000, 0x1900, 0x6700, 0x9240, 0x6b05, 0x19a4, 0x1a64, 0x1099, 0x0186, 0x00d9, 0x0024, 0x0000, 0x0000, 0x0000], // '%'
+    [0x0000, 0x0000, 0x0000, 0x0be0, 0x1800, 0x1c00, 0x0d00, 0x2b41, 0x6186, 0xa0a6, 0x603c, 0x297d, 0x0685, 0x0000, 0x0000, 0x0000], // '&'
+    [0x0000, 0x0000, 0x0000, 0x0280, 0x0280, 0x0280, 0x0140, 0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x0000], // '
+    [0x0000, 0x0000, 0x0000, 0x0090, 0x0180, 0x0240, 0x0340, 0x0700, 0x0700, 0x0300, 0x0340, 0x0280, 0x01c0, 0x0090, 0x0010, 0x0000], // '('
+    [0x0000, 0x0000, 0x0000, 0x0600, 0x0240, 0x0180, 0x01c0, 0x00d0, 0x00d0, 0x00d0, 0x01c0, 0x0280, 0x0340, 0x0600, 0x0400, 0x0000], // ')'
+    [0x0000, 0x0000, 0x0000, 0x0180, 0x2598, 0x07d0, 0x0aa0, 0x1184, 0x0140, 0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x0000], // '*'
+    [0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x0280, 0x0280, 0x0280, 0x7ffd, 0x0280, 0x0280, 0x0140, 0x0000, 0x0000, 0x0000, 0x0000], // '+'
+    [0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x0180, 0x02c0, 0x0380, 0x0300, 0x0100, 0x0000], // ','
+    [0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x0690, 0x0690, 0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x0000], // '-'
+    [0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x0140, 0x0280, 0x0140, 0x0000, 0x0000, 0x0000], // '.'
+    [0x0000, 0x0000, 0x0000, 0x0028, 0x0034, 0x0060, 0x00d0, 0x01c0, 0x0340, 0x0700, 0x0a00, 0x1c00, 0x2800, 0x2000, 0x0000, 0x0000], // '/'
+    [0x0000, 0x0000, 0x0000, 0x0be0, 0x2c38, 0x241c, 0x341c, 0x369c, 0x355c, 0x341c, 0x2828, 0x1eb4, 0x0690, 0x0000, 0x0000, 0x0000], // '0'
+    [0x0000, 0x0000, 0x0000, 0x0bc0, 0x09c0, 0x01c0, 0x01c0, 0x01c0, 0x01c0, 0x01c0, 0x01c0, 0x0ae8, 0x0aa8, 0x0000, 0x0000, 0x0000], // '1'
+    [0x0000, 0x0000, 0x0100, 0x2fe0, 0x1038, 0x0028, 0x0028, 0x0070, 0x01d0, 0x0740, 0x1d00, 0x3aa4, 0x2aa4, 0x0000, 0x0000, 0x0000], // '2'
+    [0x0000, 0x0000, 0x0100, 0x2fe0, 0x1038, 0x0028, 0x0074, 0x0be0, 0x0028, 0x001c, 0x002c, 0x2ab8, 0x1a90, 0x0000, 0x0000, 0x0000], // '3'
+    [0x0000, 0x0000, 0x0000, 0x00b0, 0x01b0, 0x0370, 0x0970, 0x1870, 0x3470, 0x7ab9, 0x1574, 0x0070, 0x0020, 0x0000, 0x0000, 0x0000], // '4'
+    [0x0000, 0x0000, 0x0000, 0x2ff4, 0x2800, 0x2800, 0x2a90, 0x15b4, 0x0028, 0x002c, 0x0028, 0x2ab4, 0x1a90, 0x0000, 0x0000, 0x0000], // '5'
+    [0x0000, 0x0000, 0x0040, 0x0bf4, 0x1d04, 0x2400, 0x36a0, 0x3d68, 0x381c, 0x341c, 0x281c, 0x1d68, 0x0690, 0x0000, 0x0000, 0x0000], // '6'
+    [0x0000, 0x0000, 0x0000, 0x3ffc, 0x0028, 0x0034, 0x0070, 0x0090, 0x01c0, 0x0280, 0x0340, 0x0700, 0x0500, 0x0000, 0x0000, 0x0000], // '7'
+    [0x0000, 0x0000, 0x0000, 0x1ff4, 0x2828, 0x2828, 0x2828, 0x0be0, 0x2828, 0x341c, 0x341c, 0x2d68, 0x0690, 0x0000, 0x0000, 0x0000], // '8'
+    [0x0000, 0x0000, 0x0000, 0x1fe0, 0x2828, 0x3418, 0x341c, 0x382c, 0x1eac, 0x015c, 0x0028, 0x1ab0, 0x1a80, 0x0000, 0x0000, 0x0000], // '9'
+    [0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x0140, 0x0280, 0x0280, 0x0000, 0x0000, 0x0140, 0x0280, 0x0140, 0x0000, 0x0000, 0x0000], // ':'
+    [0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x0140, 0x0280, 0x0280, 0x0000, 0x0000, 0x0180, 0x02c0, 0x0380, 0x0300, 0x0100, 0x0000], // ';'
+    [0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x0005, 0x01b8, 0x1f40, 0x7800, 0x1b90, 0x007d, 0x0004, 0x0000, 0x0000, 0x0000, 0x0000], // '<'
+    [0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x2aa8, 0x6aa9, 0x0000, 0x6aa9, 0x1554, 0x0000, 0x0000, 0x0000, 0x0000, 0x0000], // '='
+    [0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x5000, 0x2e40, 0x01f4, 0x002d, 0x06e4, 0x7e00, 0x1000, 0x0000, 0x0000, 0x0000, 0x0000], // '>'
+    [0x0000, 0x0000, 0x0000, 0x0ff0, 0x0428, 0x0028, 0x0074, 0x01d0, 0x0280, 0x0280, 0x0000, 0x0280, 0x0140, 0x0000, 0x0000, 0x0000], // '?'
+    [0x0000, 0x0000, 0x0000, 0x0150, 0x0aa8, 0x2409, 0x60aa, 0x935a, 0x9606, 0x9606, 0x935a, 0x61a5, 0x2400, 0x0aa4, 0x01a4, 0x0000], // '@'
+    [0x0000, 0x0000, 0x0000, 0x03c0, 0x07d0, 0x0aa0, 0x0970, 0x0c30, 0x1824, 0x2ff8, 0x341c, 0x700d, 0x5005, 0x0000, 0x0000, 0x0000], // 'A'
+    [0x0000, 0x0000, 0x0000, 0x2fe4, 0x2828, 0x281c, 0x2828, 0x2ff4, 0x282c, 0x280d, 0x280d, 0x2ab8, 0x1a90, 0x0000, 0x0000, 0x0000], // 'B'
+    [0x0000, 0x0000, 0x0040, 0x07f8, 0x1d04, 0x2800, 0x3400, 0x3400, 0x3400, 0x3800, 0x2c00, 0x0a5c, 0x01a4, 0x0000, 0x0000, 0x0000], // 'C'
+    [0x0000, 0x0000, 0x0000, 0x3f90, 0x3474, 0x3428, 0x341c, 0x341c, 0x341c, 0x341c, 0x3428, 0x3ae0, 0x2a40, 0x0000, 0x0000, 0x0000], // 'D'
+    [0x0000, 0x0000, 0x0000, 0x2ffc, 0x2800, 0x2800, 0x2800, 0x2ff8, 0x2800, 0x2800, 0x2800, 0x2ea8, 0x1aa8, 0x0000, 0x0000, 0x0000], // 'E'
+    [0x0000, 0x0000, 0x0000, 0x2ffd, 0x2800, 0x2800, 0x2800, 0x2ff8, 0x2800, 0x2800, 0x2800, 0x2800, 0x1400, 0x0000, 0x0000, 0x0000], // 'F'
+    [0x0000, 0x0000, 0x0040, 0x0bf8, 0x2d04, 0x3400, 0x7400, 0x7014, 0x706c, 0x340c, 0x280c, 0x1e6c, 0x02a0, 0x0000, 0x0000, 0x0000], // 'G'
+    [0x0000, 0x0000, 0x0000, 0x341c, 0x341c, 0x341c, 0x341c, 0x3ffc, 0x341c, 0x341c, 0x341c, 0x341c, 0x2008, 0x0000, 0x0000, 0x0000], // 'H'
+    [0x0000, 0x0000, 0x0000, 0x2ff8, 0x0280, 0x0280, 0x0280, 0x0280, 0x0280, 0x0280, 0x0280, 0x1be4, 0x1aa4, 0x0000, 0x0000, 0x0000], // 'I'
+    [0x0000, 0x0000, 0x0000, 0x0bf4, 0x0034, 0x0034, 0x0034, 0x0034, 0x0034, 0x0034, 0x0070, 0x79a0, 0x1a80, 0x0000, 0x0000, 0x0000], // 'J'
+    [0x0000, 0x0000, 0x0000, 0x341d, 0x3474, 0x35d0, 0x3740, 0x3f80, 0x39d0, 0x34a0, 0x3438, 0x341d, 0x2005, 0x0000, 0x0000, 0x0000], // 'K'
+    [0x0000, 0x0000, 0x0000, 0x2800, 0x2800, 0x2800, 0x2800, 0x2800, 0x2800, 0x2800, 0x2800, 0x2ea8, 0x1aa8, 0x0000, 0x0000, 0x0000], // 'L'
+    [0x0000, 0x0000, 0x0000, 0x782d, 0x6c39, 0x6969, 0x6699, 0x63c9, 0x6289, 0x6009, 0x6009, 0x6009, 0x1004, 0x0000, 0x0000, 0x0000], // 'M'
+    [0x0000, 0x0000, 0x0000, 0x381c, 0x3d1c, 0x3a1c, 0x371c, 0x365c, 0x35dc, 0x34ac, 0x347c, 0x343c, 0x2018, 0x0000, 0x0000, 0x0000], // 'N'
+    [0x0000, 0x0000, 0x0000, 0x0be0, 0x2828, 0x341c, 0x341c, 0x741d, 0x741d, 0x341c, 0x282c, 0x1d74, 0x0690, 0x0000, 0x0000, 0x0000], // 'O'
+    [0x0000, 0x0000, 0x0000, 0x2fe4, 0x282c, 0x280d, 0x280d, 0x296c, 0x2ea0, 0x2800, 0x2800, 0x2800, 0x1400, 0x0000, 0x0000, 0x0000], // 'P'
+    [0x0000, 0x0000, 0x0000, 0x0be0, 0x2828, 0x341c, 0x341c, 0x741d, 0x741d, 0x341c, 0x282c, 0x1d74, 0x06e0, 0x0024, 0x0000, 0x0000], // 'Q'
+    [0x0000, 0x0000, 0x0000, 0x3fe0, 0x3478, 0x3428, 0x3428, 0x3ab4, 0x3aa0, 0x3434, 0x341c, 0x340d, 0x2005, 0x0000, 0x0000, 0x0000], // 'R'
+    [0x0000, 0x0000, 0x0000, 0x1bf4, 0x2804, 0x3400, 0x3800, 0x1fe0, 0x0178, 0x001c, 0x001c, 0x3978, 0x1a90, 0x0000, 0x0000, 0x0000], // 'S'
+    [0x0000, 0x0000, 0x0000, 0xbffe, 0x0280, 0x0280, 0x0280, 0x0280, 0x0280, 0x0280, 0x0280, 0x0280, 0x0140, 0x0000, 0x0000, 0x0000], // 'T'
+    [0x0000, 0x0000, 0x0000, 0x341c, 0x341c, 0x341c, 0x341c, 0x341c, 0x341c, 0x341c, 0x341c, 0x2d78, 0x0690, 0x0000, 0x0000, 0x0000], // 'U'
+    [0x0000, 0x0000, 0x0000, 0x700d, 0x340c, 0x241c, 0x2828, 0x1c34, 0x0d70, 0x0aa0, 0x0690, 0x03c0, 0x0140, 0x0000, 0x0000, 0x0000], // 'V'
+    [0x0000, 0x0000, 0x0000, 0x9006, 0xa00a, 0xa14a, 0x62c9, 0x7389, 0x369c, 0x396c, 0x2d78, 0x2c38, 0x1414, 0x0000, 0x0000, 0x0000], // 'W'
+    [0x0000, 0x0000, 0x0000, 0x340d, 0x1c28, 0x0a70, 0x0790, 0x02c0, 0x07d0, 0x0d70, 0x2828, 0x740d, 0x5005, 0x0000, 0x0000, 0x0000], // 'X'
+    [0x0000, 0x0000, 0x0000, 0x700d, 0x2828, 0x1c34, 0x0aa0, 0x03c0, 0x0280, 0x0280, 0x0280, 0x0280, 0x0140, 0x0000, 0x0000, 0x0000], // 'Y'
+    [0x0000, 0x0000, 0x0000, 0x2ffe, 0x001c, 0x0034, 0x00a0, 0x01c0, 0x0340, 0x0a00, 0x1c00, 0x3aa9, 0x2aa9, 0x0000, 0x0000, 0x0000], // 'Z'
+    [0x0000, 0x0000, 0x0290, 0x0390, 0x0340, 0x0340, 0x0340, 0x0340, 0x0340, 0x0340, 0x0340, 0x0340, 0x0340, 0x03a0, 0x0150, 0x0000], // '['
+    [0x0000, 0x0000, 0x0000, 0x3000, 0x2800, 0x0c00, 0x0a00, 0x0300, 0x0280, 0x01c0, 0x00a0, 0x0070, 0x0034, 0x0018, 0x0000, 0x0000], // \
+    [0x0000, 0x0000, 0x0680, 0x06c0, 0x01c0, 0x01c0, 0x01c0, 0x01c0, 0x01c0, 0x01c0, 0x01c0, 0x01c0, 0x01c0, 0x0ac0, 0x0540, 0x0000], // ']'
+    [0x0000, 0x0000, 0x0000, 0x03c0, 0x0aa0, 0x2828, 0x1004, 0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x0000], // '^'
+    [0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0xaaaa], // '_'
+    [0x0000, 0x0000, 0x0900, 0x0240, 0x0140, 0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x0000], // '`'
+    [0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x1a90, 0x1568, 0x0018, 0x1bf8, 0x3818, 0x3428, 0x29a8, 0x0a44, 0x0000, 0x0000, 0x0000], // 'a'
+    [0x0000, 0x0000, 0x1400, 0x2800, 0x2800, 0x2aa0, 0x2d68, 0x281c, 0x280d, 0x280c, 0x281c, 0x2e68, 0x1690, 0x0000, 0x0000, 0x0000], // 'b'
+    [0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x02a4, 0x0e58, 0x1800, 0x2800, 0x2800, 0x1c00, 0x0a58, 0x01a4, 0x0000, 0x0000, 0x0000], // 'c'
+    [0x0000, 0x0000, 0x0014, 0x0028, 0x0028, 0x0aa8, 0x2978, 0x3428, 0x3028, 0x3428, 0x3428, 0x2db8, 0x0694, 0x0000, 0x0000, 0x0000], // 'd'
+    [0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x06a0, 0x1d68, 0x340c, 0x3aad, 0x3554, 0x3400, 0x1d5c, 0x06a4, 0x0000, 0x0000, 0x0000], // 'e'
+    [0x0000, 0x0000, 0x0054, 0x02e4, 0x0280, 0x1ba4, 0x1694, 0x0280, 0x0280, 0x0280, 0x0280, 0x0280, 0x0140, 0x0000, 0x0000, 0x0000], // 'f'
+    [0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x0a94, 0x2978, 0x3428, 0x3028, 0x3428, 0x3428, 0x1eb8, 0x0668, 0x0024, 0x1ea0, 0x0540], // 'g'
+    [0x0000, 0x0000, 0x1400, 0x2800, 0x2800, 0x2aa0, 0x2d78, 0x2828, 0x2828, 0x2828, 0x2828, 0x2828, 0x1414, 0x0000, 0x0000, 0x0000], // 'h'
+    [0x0000, 0x0000, 0x0140, 0x0280, 0x0000, 0x0a40, 0x0680, 0x0280, 0x0280, 0x0280, 0x0280, 0x1ae8, 0x1aa8, 0x0000, 0x0000, 0x0000], // 'i'
+    [0x0000, 0x0000, 0x0080, 0x00c0, 0x0000, 0x0a80, 0x05c0, 0x00c0, 0x00c0, 0x00c0, 0x00c0, 0x00c0, 0x00c0, 0x01c0, 0x2b80, 0x1400], // 'j'
+    [0x0000, 0x0000, 0x1400, 0x2800, 0x2800, 0x2814, 0x2874, 0x29d0, 0x2f80, 0x2ca0, 0x2874, 0x281c, 0x1405, 0x0000, 0x0000, 0x0000], // 'k'
+    [0x0000, 0x0000, 0x2a00, 0x2b40, 0x0340, 0x0340, 0x0340, 0x0340, 0x0340, 0x0340, 0x0340, 0x02a4, 0x0064, 0x0000, 0x0000, 0x0000], // 'l'
+    [0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x1a68, 0x76dd, 0x7289, 0x6289, 0x6289, 0x6289, 0x6289, 0x1144, 0x0000, 0x0000, 0x0000], // 'm'
+    [0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x16a0, 0x2d78, 0x2828, 0x2828, 0x2828, 0x2828, 0x2828, 0x1414, 0x0000, 0x0000, 0x0000], // 'n'
+    [0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x0690, 0x2d78, 0x341c, 0x341c, 0x341c, 0x341c, 0x1d74, 0x0690, 0x0000, 0x0000, 0x0000], // 'o'
+    [0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x16a0, 0x2d68, 0x281c, 0x280c, 0x280c, 0x281c, 0x2e68, 0x2a90, 0x2800, 0x2800, 0x1000], // 'p'
+    [0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x0a94, 0x2d78, 0x3428, 0x3428, 0x3428, 0x3428, 0x1d78, 0x06a8, 0x0028, 0x0028, 0x0004], // 'q'
+    [0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x0569, 0x0a95, 0x0b00, 0x0a00, 0x0a00, 0x0a00, 0x0a00, 0x0500, 0x0000, 0x0000, 0x0000], // 'r'
+    [0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x06a0, 0x1d54, 0x2800, 0x1e90, 0x01b4, 0x0028, 0x1974, 0x0a90, 0x0000, 0x0000, 0x0000], // 's'
+    [0x0000, 0x0000, 0x0000, 0x0600, 0x0700, 0x2ba4, 0x1754, 0x0700, 0x0700, 0x0700, 0x0700, 0x03a4, 0x0064, 0x0000, 0x0000, 0x0000], // 't'
+    [0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x1414, 0x2828, 0x2828, 0x2828, 0x2828, 0x2828, 0x1db8, 0x0654, 0x0000, 0x0000, 0x0000], // 'u'
+    [0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x2008, 0x341c, 0x2828, 0x0c34, 0x0970, 0x0690, 0x03c0, 0x0140, 0x0000, 0x0000, 0x0000], // 'v'
+    [0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x4001, 0xa00a, 0x6149, 0x728d, 0x369c, 0x2968, 0x2d38, 0x0820, 0x0000, 0x0000, 0x0000], // 'w'
+    [0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x1414, 0x1c34, 0x0aa0, 0x03c0, 0x07d0, 0x0d70, 0x2828, 0x2008, 0x0000, 0x0000, 0x0000], // 'x'
+    [0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x2008, 0x241c, 0x1828, 0x0d34, 0x0a60, 0x0790, 0x03c0, 0x0280, 0x0340, 0x2e00, 0x1400], // 'y'
+    [0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x1aa4, 0x0568, 0x00a0, 0x01c0, 0x0740, 0x0900, 0x2ea4, 0x1aa4, 0x0000, 0x0000, 0x0000], // 'z'
+    [0x0000, 0x0000, 0x0054, 0x01e4, 0x0280, 0x0280, 0x0280, 0x0280, 0x1f00, 0x0340, 0x0280, 0x0280, 0x0280, 0x01d0, 0x0064, 0x0000], // '{'
+    [0x0000, 0x0000, 0x0140, 0x0280, 0x0280, 0x0280, 0x0280, 0x0280, 0x0280, 0x0280, 0x0280, 0x0280, 0x0280, 0x0280, 0x0280, 0x0140], // '|'
+    [0x0000, 0x0000, 0x1500, 0x1b40, 0x0280, 0x0280, 0x0280, 0x0280, 0x00f4, 0x01c0, 0x0280, 0x0280, 0x0280, 0x0740, 0x1900, 0x0000], // '}'
+    [0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x1900, 0x7aed, 0x0050, 0x0000, 0x0000, 0x0000, 0x0000, 0x0000, 0x0000], // '~'
+];
+
+/**
+The coverage of the pixel at the specified position in the glyph for the specified character.
+Characters that are not printable ASCII are drawn as a question mark.
+*/
+pub fn coverage(c: char, x: usize, y: usize) -> u8 {
+    let idx = match u32::from(c) {
+        n if (FIRST..FIRST + GLYPHS.len() as u32).contains(&n) => (n - FIRST) as usize,
+        _ => ('?' as u32 - FIRST) as usize,
+    };
+    ((GLYPHS[idx][y] >> (2 * (WIDTH - 1 - x))) & 3) as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_coverage() {
+        assert!((0..HEIGHT).all(|y| (0..WIDTH).all(|x| coverage(' ', x, y) == 0)));
+        assert!((0..HEIGHT).any(|y| (0..WIDTH).any(|x| coverage('|', x, y) > 0)));
+        assert_eq!(
+            (0..HEIGHT).map(|y| (0..WIDTH).map(|x| coverage('\u{263a}', x, y)).collect::<Vec<_>>()).collect::<Vec<_>>(),
+            (0..HEIGHT).map(|y| (0..WIDTH).map(|x| coverage('?', x, y)).collect::<Vec<_>>()).collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,223 @@
+use std::cmp::min;
+use std::io::{BufRead, BufReader, Error, ErrorKind, Read, Write};
+
+use crate::util::crc32::Crc32;
+use crate::util::deflate::{Deflater, Inflater};
+
+const MAGIC: [u8; 2] = [0x1f, 0x8b];
+const DEFLATE: u8 = 8;
+const FLAG_HEADER_CRC: u8 = 0x02;
+const FLAG_EXTRA: u8 = 0x04;
+const FLAG_NAME: u8 = 0x08;
+const FLAG_COMMENT: u8 = 0x10;
+const OS_UNIX: u8 = 3;
+const CHUNK_SIZE: usize = 64 * 1024;
+
+fn invalid(message: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, message)
+}
+
+fn skip_string<R: BufRead>(input: &mut R) -> std::io::Result<()> {
+    let mut ignored = Vec::new();
+    input.read_until(0, &mut ignored)?;
+    Ok(())
+}
+
+fn read_header<R: BufRead>(input: &mut R) -> std::io::Result<()> {
+    let mut header = [0u8; 10];
+    input.read_exact(&mut header)
+        .map_err(|_| invalid("Invalid gzip header, the input is not gzip compressed data"))?;
+    if header[0..2] != MAGIC {
+        return Err(invalid("Invalid gzip header, the input is not gzip compressed data"));
+    }
+    if header[2] != DEFLATE {
+        return Err(invalid("Unsupported gzip compression method"));
+    }
+    let flags = header[3];
+    if flags & FLAG_EXTRA != 0 {
+        let mut length = [0u8; 2];
+        input.read_exact(&mut length)?;
+        let mut extra = vec![0u8; usize::from(u16::from_le_bytes(length))];
+        input.read_exact(&mut extra)?;
+    }
+    if flags & FLAG_NAME != 0 {
+        skip_string(input)?;
+    }
+    if flags & FLAG_COMMENT != 0 {
+        skip_string(input)?;
+    }
+    if flags & FLAG_HEADER_CRC != 0 {
+        let mut crc = [0u8; 2];
+        input.read_exact(&mut crc)?;
+    }
+    Ok(())
+}
+
+enum DecoderState<R: Read> {
+    Header(BufReader<R>, bool),
+    Body(Inflater<BufReader<R>>),
+    Done,
+}
+
+/**
+A reader that decompresses gzip data (RFC 1952) read from another reader. Several gzip streams
+following each other, like the result of concatenating gzip files, are decompressed as one.
+*/
+pub struct GzipDecoder<R: Read> {
+    state: DecoderState<R>,
+    crc: Crc32,
+    size: u32,
+}
+
+impl<R: Read> GzipDecoder<R> {
+    pub fn new(inner: R) -> GzipDecoder<R> {
+        GzipDecoder {
+            state: DecoderState::Header(BufReader::new(inner), true),
+            crc: Crc32::new(),
+            size: 0,
+        }
+    }
+
+    fn trailer(&mut self, input: &mut BufReader<R>) -> std::io::Result<()> {
+        let mut trailer = [0u8; 8];
+        input.read_exact(&mut trailer)
+            .map_err(|_| invalid("Truncated gzip data"))?;
+        if u32::from_le_bytes([trailer[0], trailer[1], trailer[2], trailer[3]]) != self.crc.finish() {
+            return Err(invalid("Invalid gzip data, checksum mismatch"));
+        }
+        if u32::from_le_bytes([trailer[4], trailer[5], trailer[6], trailer[7]]) != self.size {
+            return Err(invalid("Invalid gzip data, size mismatch"));
+        }
+        self.crc = Crc32::new();
+        self.size = 0;
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for GzipDecoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        loop {
+            match std::mem::replace(&mut self.state, DecoderState::Done) {
+                DecoderState::Header(mut input, first) => {
+                    if !first && input.fill_buf()?.is_empty() {
+                        return Ok(0);
+                    }
+                    read_header(&mut input)?;
+                    self.state = DecoderState::Body(Inflater::new(input));
+                }
+                DecoderState::Body(mut inflater) => {
+                    let count = inflater.read(buf)?;
+                    if count > 0 || buf.is_empty() {
+                        self.crc.update(&buf[..count]);
+                        self.size = self.size.wrapping_add(count as u32);
+                        self.state = DecoderState::Body(inflater);
+                        return Ok(count);
+                    }
+                    let mut input = inflater.into_inner();
+                    self.trailer(&mut input)?;
+                    self.state = DecoderState::Header(input, false);
+                }
+                DecoderState::Done => return Ok(0),
+            }
+        }
+    }
+}
+
+/**
+A reader that compresses data read from another reader into gzip format. The input is read and
+compressed one chunk at a time as the output is being read.
+*/
+pub struct GzipEncoder<R: Read> {
+    input: R,
+    deflater: Option<Deflater<Vec<u8>>>,
+    crc: Crc32,
+    size: u32,
+    output: Vec<u8>,
+    position: usize,
+}
+
+impl<R: Read> GzipEncoder<R> {
+    pub fn new(input: R) -> GzipEncoder<R> {
+        GzipEncoder {
+            input,
+            deflater: Some(Deflater::new(Vec::new())),
+            crc: Crc32::new(),
+            size: 0,
+            output: vec![MAGIC[0], MAGIC[1], DEFLATE, 0, 0, 0, 0, 0, 0, OS_UNIX],
+            position: 0,
+        }
+    }
+
+    /** Compress more input, returning false if all input has already been compressed. */
+    fn fill(&mut self) -> std::io::Result<bool> {
+        let mut deflater = match self.deflater.take() {
+            Some(deflater) => deflater,
+            None => return Ok(false),
+        };
+        self.output.clear();
+        self.position = 0;
+        let mut chunk = vec![0u8; CHUNK_SIZE];
+        let count = self.input.read(&mut chunk)?;
+        if count == 0 {
+            self.output = deflater.finish()?;
+            self.output.extend_from_slice(&self.crc.finish().to_le_bytes());
+            self.output.extend_from_slice(&self.size.to_le_bytes());
+        } else {
+            self.crc.update(&chunk[..count]);
+            self.size = self.size.wrapping_add(count as u32);
+            deflater.write_all(&chunk[..count])?;
+            self.output.append(deflater.get_mut());
+            self.deflater = Some(deflater);
+        }
+        Ok(true)
+    }
+}
+
+impl<R: Read> Read for GzipEncoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        while self.position == self.output.len() {
+            if !self.fill()? {
+                return Ok(0);
+            }
+        }
+        let count = min(buf.len(), self.output.len() - self.position);
+        buf[..count].copy_from_slice(&self.output[self.position..self.position + count]);
+        self.position += count;
+        Ok(count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HELLO: [u8; 32] = [
+        0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x03, 0xcb, 0x48, 0xcd, 0xc9, 0xc9, 0x57,
+        0x28, 0xcf, 0x2f, 0xca, 0x49, 0xe1, 0x02, 0x00, 0x2d, 0x3b, 0x08, 0xaf, 0x0c, 0x00, 0x00, 0x00,
+    ];
+
+    fn decode(data: &[u8]) -> std::io::Result<Vec<u8>> {
+        let mut res = Vec::new();
+        GzipDecoder::new(data).read_to_end(&mut res)?;
+        Ok(res)
+    }
+
+    #[test]
+    fn test_decode() {
+        assert_eq!(decode(&HELLO).unwrap(), b"hello world\n".to_vec());
+        let concatenated = [&HELLO[..], &HELLO[..]].concat();
+        assert_eq!(decode(&concatenated).unwrap(), b"hello world\nhello world\n".to_vec());
+        let mut corrupt = HELLO;
+        corrupt[24] ^= 1;
+        assert!(decode(&corrupt).is_err());
+        assert!(decode(b"hello world\n").is_err());
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let data: Vec<u8> = (0..200_000u64).map(|i| (i * i % 251) as u8).collect();
+        let mut compressed = Vec::new();
+        GzipEncoder::new(&data[..]).read_to_end(&mut compressed).unwrap();
+        assert_eq!(decode(&compressed).unwrap(), data);
+    }
+}
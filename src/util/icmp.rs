@@ -0,0 +1,217 @@
+/**
+Sends ICMP echo requests and waits for the replies.
+
+Unprivileged ICMP sockets are used where the system allows it (see net.ipv4.ping_group_range),
+in which case the kernel picks the identifier and only passes on replies to our own requests.
+Otherwise a raw socket is used, which requires CAP_NET_RAW, sees all ICMP traffic, and for
+IPv4 also gets the IP header of the reply. Either way the TTL of the reply is read from the
+ancillary data the kernel attaches when IP_RECVTTL or IPV6_RECVHOPLIMIT is set.
+*/
+use std::mem::{size_of, zeroed};
+use std::net::{IpAddr, SocketAddr};
+use std::os::unix::io::RawFd;
+use std::time::{Duration, Instant};
+
+use crate::lang::errors::{CrushResult, error};
+
+const ECHO_REQUEST_V4: u8 = 8;
+const ECHO_REPLY_V4: u8 = 0;
+const ECHO_REQUEST_V6: u8 = 128;
+const ECHO_REPLY_V6: u8 = 129;
+
+// The Linux values, which the version of libc in use doesn't have yet
+const IP_RECVTTL: libc::c_int = 12;
+const IPV6_RECVHOPLIMIT: libc::c_int = 51;
+const IPV6_HOPLIMIT: libc::c_int = 52;
+
+/** The same amount of data as the ping command sends. */
+const PAYLOAD_SIZE: u8 = 56;
+
+fn os_error<T>(what: &str) -> CrushResult<T> {
+    error(format!("{}: {}", what, std::io::Error::last_os_error()).as_str())
+}
+
+/** The internet checksum, the ones' complement of the ones' complement sum of all 16 bit words. */
+pub fn checksum(data: &[u8]) -> u16 {
+    let mut sum: u32 = 0;
+    for chunk in data.chunks(2) {
+        let word = if chunk.len() == 2 { u16::from_be_bytes([chunk[0], chunk[1]]) } else { u16::from(chunk[0]) << 8 };
+        sum += u32::from(word);
+    }
+    while sum >> 16 != 0 {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+pub fn echo_request(v6: bool, id: u16, seq: u16) -> Vec<u8> {
+    let mut res = vec![if v6 { ECHO_REQUEST_V6 } else { ECHO_REQUEST_V4 }, 0, 0, 0];
+    res.extend_from_slice(&id.to_be_bytes());
+    res.extend_from_slice(&seq.to_be_bytes());
+    res.extend(0..PAYLOAD_SIZE);
+    // The kernel fills in the checksum of ICMPv6, since it covers the IPv6 pseudo header
+    if !v6 {
+        let sum = checksum(&res);
+        res[2..4].copy_from_slice(&sum.to_be_bytes());
+    }
+    res
+}
+
+/** The identifier and sequence number of an echo reply, or None for any other packet. */
+pub fn parse_reply(v6: bool, packet: &[u8]) -> Option<(u16, u16)> {
+    let expected = if v6 { ECHO_REPLY_V6 } else { ECHO_REPLY_V4 };
+    if packet.len() < 8 || packet[0] != expected {
+        return None;
+    }
+    Some((u16::from_be_bytes([packet[4], packet[5]]), u16::from_be_bytes([packet[6], packet[7]])))
+}
+
+pub struct Reply {
+    pub rtt: Duration,
+    pub ttl: Option<u8>,
+}
+
+pub struct Pinger {
+    fd: RawFd,
+    address: IpAddr,
+    raw: bool,
+    id: u16,
+}
+
+impl Pinger {
+    pub fn new(address: IpAddr) -> CrushResult<Pinger> {
+        let (domain, protocol) = match address {
+            IpAddr::V4(_) => (libc::AF_INET, libc::IPPROTO_ICMP),
+            IpAddr::V6(_) => (libc::AF_INET6, libc::IPPROTO_ICMPV6),
+        };
+        let mut raw = false;
+        let mut fd = unsafe { libc::socket(domain, libc::SOCK_DGRAM | libc::SOCK_CLOEXEC, protocol) };
+        if fd < 0 {
+            raw = true;
+            fd = unsafe { libc::socket(domain, libc::SOCK_RAW | libc::SOCK_CLOEXEC, protocol) };
+        }
+        if fd < 0 {
+            return os_error("Could not create an ICMP socket, which requires CAP_NET_RAW or a group in net.ipv4.ping_group_range");
+        }
+        let pinger = Pinger { fd, address, raw, id: rand::random() };
+        let (level, option) = match address {
+            IpAddr::V4(_) => (libc::IPPROTO_IP, IP_RECVTTL),
+            IpAddr::V6(_) => (libc::IPPROTO_IPV6, IPV6_RECVHOPLIMIT),
+        };
+        let on: libc::c_int = 1;
+        let res = unsafe {
+            libc::setsockopt(fd, level, option, &on as *const _ as *const libc::c_void, size_of::<libc::c_int>() as libc::socklen_t)
+        };
+        if res < 0 {
+            return os_error("Could not set up the ICMP socket");
+        }
+        Ok(pinger)
+    }
+
+    fn send(&self, seq: u16) -> CrushResult<()> {
+        let packet = echo_request(self.address.is_ipv6(), self.id, seq);
+        let address = nix::sys::socket::SockAddr::new_inet(nix::sys::socket::InetAddr::from_std(&SocketAddr::new(self.address, 0)));
+        let res = unsafe {
+            let (address, len) = address.as_ffi_pair();
+            libc::sendto(self.fd, packet.as_ptr() as *const libc::c_void, packet.len(), 0, address, len)
+        };
+        if res < 0 {
+            return os_error(format!("Could not send to {}", self.address).as_str());
+        }
+        Ok(())
+    }
+
+    /** Receives one packet, and returns it along with the TTL from the ancillary data. */
+    fn receive(&self, deadline: Instant) -> CrushResult<Option<(Vec<u8>, Option<u8>)>> {
+        let now = Instant::now();
+        if now >= deadline {
+            return Ok(None);
+        }
+        let mut poll = libc::pollfd { fd: self.fd, events: libc::POLLIN, revents: 0 };
+        let millis = (deadline - now).as_millis().max(1) as libc::c_int;
+        match unsafe { libc::poll(&mut poll, 1, millis) } {
+            0 => return Ok(None),
+            n if n < 0 => return os_error("Could not wait for a reply"),
+            _ => {}
+        }
+        let mut buf = [0u8; 2048];
+        // u64 to get the alignment that cmsghdr needs
+        let mut control = [0u64; 16];
+        let mut iov = libc::iovec { iov_base: buf.as_mut_ptr() as *mut libc::c_void, iov_len: buf.len() };
+        let mut msg: libc::msghdr = unsafe { zeroed() };
+        msg.msg_iov = &mut iov;
+        msg.msg_iovlen = 1;
+        msg.msg_control = control.as_mut_ptr() as *mut libc::c_void;
+        msg.msg_controllen = size_of::<[u64; 16]>() as _;
+        let len = unsafe { libc::recvmsg(self.fd, &mut msg, 0) };
+        if len < 0 {
+            return os_error("Could not receive a reply");
+        }
+        let mut ttl = None;
+        unsafe {
+            let mut cmsg = libc::CMSG_FIRSTHDR(&msg);
+            while !cmsg.is_null() {
+                let header = &*cmsg;
+                if (header.cmsg_level == libc::IPPROTO_IP && header.cmsg_type == libc::IP_TTL)
+                    || (header.cmsg_level == libc::IPPROTO_IPV6 && header.cmsg_type == IPV6_HOPLIMIT) {
+                    ttl = Some(*(libc::CMSG_DATA(cmsg) as *const libc::c_int) as u8);
+                }
+                cmsg = libc::CMSG_NXTHDR(&msg, cmsg);
+            }
+        }
+        let mut packet = &buf[..len as usize];
+        // Raw IPv4 sockets get the IP header too
+        if self.raw && self.address.is_ipv4() && !packet.is_empty() {
+            let header_len = usize::from(packet[0] & 0x0f) * 4;
+            if packet.len() < header_len {
+                return Ok(Some((vec![], ttl)));
+            }
+            packet = &packet[header_len..];
+        }
+        Ok(Some((packet.to_vec(), ttl)))
+    }
+
+    /** Sends an echo request, and returns the reply, or None if there was none before the timeout. */
+    pub fn probe(&self, seq: u16, timeout: Duration) -> CrushResult<Option<Reply>> {
+        let start = Instant::now();
+        let deadline = start + timeout;
+        self.send(seq)?;
+        while let Some((packet, ttl)) = self.receive(deadline)? {
+            match parse_reply(self.address.is_ipv6(), &packet) {
+                // Unprivileged sockets get their identifier from the kernel, which already filters on it
+                Some((id, reply_seq)) if reply_seq == seq && (!self.raw || id == self.id) =>
+                    return Ok(Some(Reply { rtt: start.elapsed(), ttl })),
+                _ => continue,
+            }
+        }
+        Ok(None)
+    }
+}
+
+impl Drop for Pinger {
+    fn drop(&mut self) {
+        unsafe { libc::close(self.fd) };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checksum_works() {
+        // The example from RFC 1071
+        assert_eq!(checksum(&[0x00, 0x01, 0xf2, 0x03, 0xf4, 0xf5, 0xf6, 0xf7]), !0xddf2);
+        let request = echo_request(false, 0x1234, 7);
+        assert_eq!(checksum(&request), 0);
+    }
+
+    #[test]
+    fn replies_are_recognized() {
+        let mut reply = echo_request(false, 0x1234, 7);
+        assert_eq!(parse_reply(false, &reply), None);
+        reply[0] = ECHO_REPLY_V4;
+        assert_eq!(parse_reply(false, &reply), Some((0x1234, 7)));
+        assert_eq!(parse_reply(true, &reply), None);
+    }
+}
@@ -0,0 +1,745 @@
+/**
+A client for the Kafka wire protocol, with just what is needed to consume and produce records:
+finding the leaders of the partitions of a topic, looking up and committing the offsets of a
+consumer group, fetching records and producing them.
+
+Every request uses the oldest version of its API that current brokers still accept, which keeps
+the encoding simple. Records are read from and written as version 2 record batches. Older
+message sets are read too. Of the compression codecs, only gzip is supported.
+*/
+use std::collections::HashMap;
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::time::Duration;
+
+use crate::lang::errors::{CrushResult, data_error, error, to_crush_error};
+use crate::util::crc32::crc32c;
+use crate::util::gzip::GzipDecoder;
+
+const PRODUCE: i16 = 0;
+const FETCH: i16 = 1;
+const LIST_OFFSETS: i16 = 2;
+const METADATA: i16 = 3;
+const OFFSET_COMMIT: i16 = 8;
+const OFFSET_FETCH: i16 = 9;
+const FIND_COORDINATOR: i16 = 10;
+
+pub const NONE: i16 = 0;
+pub const OFFSET_OUT_OF_RANGE: i16 = 1;
+pub const UNKNOWN_TOPIC_OR_PARTITION: i16 = 3;
+pub const LEADER_NOT_AVAILABLE: i16 = 5;
+pub const NOT_LEADER_OR_FOLLOWER: i16 = 6;
+
+/** The special timestamps of a ListOffsets request. */
+pub const LATEST: i64 = -1;
+pub const EARLIEST: i64 = -2;
+
+pub fn error_name(code: i16) -> String {
+    match code {
+        1 => "offset out of range",
+        2 => "corrupt message",
+        3 => "unknown topic or partition",
+        5 => "leader not available",
+        6 => "not leader or follower",
+        7 => "request timed out",
+        10 => "message too large",
+        14 => "coordinator load in progress",
+        15 => "coordinator not available",
+        16 => "not coordinator",
+        17 => "invalid topic",
+        25 => "unknown member id",
+        29 => "topic authorization failed",
+        30 => "group authorization failed",
+        31 => "cluster authorization failed",
+        _ => return format!("error code {}", code),
+    }.to_string()
+}
+
+fn check(code: i16, what: &str) -> CrushResult<()> {
+    if code == NONE {
+        Ok(())
+    } else {
+        error(format!("Kafka {} failed: {}", what, error_name(code)).as_str())
+    }
+}
+
+#[derive(Default)]
+struct Encoder {
+    buf: Vec<u8>,
+}
+
+impl Encoder {
+    fn i8(&mut self, v: i8) -> &mut Self {
+        self.buf.push(v as u8);
+        self
+    }
+
+    fn i16(&mut self, v: i16) -> &mut Self {
+        self.buf.extend_from_slice(&v.to_be_bytes());
+        self
+    }
+
+    fn i32(&mut self, v: i32) -> &mut Self {
+        self.buf.extend_from_slice(&v.to_be_bytes());
+        self
+    }
+
+    fn i64(&mut self, v: i64) -> &mut Self {
+        self.buf.extend_from_slice(&v.to_be_bytes());
+        self
+    }
+
+    fn string(&mut self, v: &str) -> &mut Self {
+        self.i16(v.len() as i16);
+        self.buf.extend_from_slice(v.as_bytes());
+        self
+    }
+
+    fn null_string(&mut self) -> &mut Self {
+        self.i16(-1)
+    }
+
+    fn bytes(&mut self, v: &[u8]) -> &mut Self {
+        self.i32(v.len() as i32);
+        self.buf.extend_from_slice(v);
+        self
+    }
+
+    /** A zigzag encoded variable length integer, as used inside record batches. */
+    fn varint(&mut self, v: i64) -> &mut Self {
+        let mut n = ((v << 1) ^ (v >> 63)) as u64;
+        while n >= 0x80 {
+            self.buf.push((n as u8) | 0x80);
+            n >>= 7;
+        }
+        self.buf.push(n as u8);
+        self
+    }
+
+    fn varbytes(&mut self, v: Option<&[u8]>) -> &mut Self {
+        match v {
+            Some(v) => {
+                self.varint(v.len() as i64);
+                self.buf.extend_from_slice(v);
+            }
+            None => {
+                self.varint(-1);
+            }
+        }
+        self
+    }
+}
+
+struct Decoder<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Decoder<'a> {
+    fn new(data: &'a [u8]) -> Decoder<'a> {
+        Decoder { data, pos: 0 }
+    }
+
+    fn remaining(&self) -> usize {
+        self.data.len() - self.pos
+    }
+
+    fn take(&mut self, len: usize) -> CrushResult<&'a [u8]> {
+        if self.remaining() < len {
+            return data_error("Truncated Kafka message");
+        }
+        let res = &self.data[self.pos..self.pos + len];
+        self.pos += len;
+        Ok(res)
+    }
+
+    fn i8(&mut self) -> CrushResult<i8> {
+        Ok(self.take(1)?[0] as i8)
+    }
+
+    fn i16(&mut self) -> CrushResult<i16> {
+        let b = self.take(2)?;
+        Ok(i16::from_be_bytes([b[0], b[1]]))
+    }
+
+    fn i32(&mut self) -> CrushResult<i32> {
+        let b = self.take(4)?;
+        Ok(i32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+    }
+
+    fn i64(&mut self) -> CrushResult<i64> {
+        let mut b = [0u8; 8];
+        b.copy_from_slice(self.take(8)?);
+        Ok(i64::from_be_bytes(b))
+    }
+
+    fn string(&mut self) -> CrushResult<String> {
+        Ok(self.null_string()?.unwrap_or_default())
+    }
+
+    fn null_string(&mut self) -> CrushResult<Option<String>> {
+        let len = self.i16()?;
+        if len < 0 {
+            return Ok(None);
+        }
+        Ok(Some(String::from_utf8_lossy(self.take(len as usize)?).to_string()))
+    }
+
+    fn bytes(&mut self) -> CrushResult<Option<&'a [u8]>> {
+        let len = self.i32()?;
+        if len < 0 {
+            return Ok(None);
+        }
+        Ok(Some(self.take(len as usize)?))
+    }
+
+    fn array<T>(&mut self, mut item: impl FnMut(&mut Decoder<'a>) -> CrushResult<T>) -> CrushResult<Vec<T>> {
+        let len = self.i32()?;
+        let mut res = Vec::new();
+        for _ in 0..len.max(0) {
+            res.push(item(self)?);
+        }
+        Ok(res)
+    }
+
+    fn varint(&mut self) -> CrushResult<i64> {
+        let mut n = 0u64;
+        for shift in (0..64).step_by(7) {
+            let b = self.take(1)?[0];
+            n |= u64::from(b & 0x7f) << shift;
+            if b & 0x80 == 0 {
+                return Ok((n >> 1) as i64 ^ -((n & 1) as i64));
+            }
+        }
+        data_error("Invalid varint in Kafka message")
+    }
+
+    fn varbytes(&mut self) -> CrushResult<Option<Vec<u8>>> {
+        let len = self.varint()?;
+        if len < 0 {
+            return Ok(None);
+        }
+        Ok(Some(self.take(len as usize)?.to_vec()))
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Record {
+    pub offset: i64,
+    /** Milliseconds since the epoch. */
+    pub timestamp: i64,
+    pub key: Option<Vec<u8>>,
+    pub value: Option<Vec<u8>>,
+}
+
+/** The key and value of a record to produce. */
+pub type KeyValue = (Option<Vec<u8>>, Option<Vec<u8>>);
+
+/** Encodes records as a version 2 record batch. */
+pub fn encode_batch(records: &[KeyValue], timestamp: i64) -> Vec<u8> {
+    let mut body = Encoder::default();
+    body.i16(0) // attributes: no compression, create time
+        .i32(records.len() as i32 - 1)
+        .i64(timestamp)
+        .i64(timestamp)
+        .i64(-1) // producer id
+        .i16(-1) // producer epoch
+        .i32(-1) // base sequence
+        .i32(records.len() as i32);
+    for (idx, (key, value)) in records.iter().enumerate() {
+        let mut record = Encoder::default();
+        record.i8(0)
+            .varint(0)
+            .varint(idx as i64)
+            .varbytes(key.as_deref())
+            .varbytes(value.as_deref())
+            .varint(0);
+        body.varint(record.buf.len() as i64);
+        body.buf.extend_from_slice(&record.buf);
+    }
+    let mut batch = Encoder::default();
+    batch.i64(0)
+        .i32(4 + 1 + 4 + body.buf.len() as i32)
+        .i32(-1) // partition leader epoch
+        .i8(2)
+        .i32(crc32c(&body.buf) as i32);
+    batch.buf.extend_from_slice(&body.buf);
+    batch.buf
+}
+
+fn decode_records(data: &[u8], base_offset: i64, base_timestamp: i64, log_append_time: Option<i64>, count: i32, res: &mut Vec<Record>) -> CrushResult<()> {
+    let mut d = Decoder::new(data);
+    for _ in 0..count {
+        let len = d.varint()?;
+        let mut record = Decoder::new(d.take(len.max(0) as usize)?);
+        record.i8()?;
+        let timestamp_delta = record.varint()?;
+        let offset_delta = record.varint()?;
+        let key = record.varbytes()?;
+        let value = record.varbytes()?;
+        res.push(Record {
+            offset: base_offset + offset_delta,
+            timestamp: log_append_time.unwrap_or(base_timestamp + timestamp_delta),
+            key,
+            value,
+        });
+    }
+    Ok(())
+}
+
+/**
+Decodes the record batches and message sets of a partition in a fetch response. Returns the
+records and the offset after the last complete batch, if there was one. A fetch response can end
+with an incomplete batch, which is left for the next fetch.
+*/
+pub fn decode_batches(data: &[u8]) -> CrushResult<(Vec<Record>, Option<i64>)> {
+    let mut d = Decoder::new(data);
+    let mut res = Vec::new();
+    let mut next = None;
+    while d.remaining() >= 17 {
+        let base_offset = d.i64()?;
+        let len = d.i32()?;
+        if len < 5 || d.remaining() < len as usize {
+            break;
+        }
+        let mut batch = Decoder::new(d.take(len as usize)?);
+        let magic = batch.data[4];
+        if magic < 2 {
+            // A legacy message, which holds a single record, with the crc before the magic byte
+            batch.i32()?;
+            batch.i8()?;
+            let attributes = batch.i8()?;
+            let timestamp = if magic == 1 { batch.i64()? } else { -1 };
+            if attributes & 7 != 0 {
+                return data_error("Compressed legacy Kafka messages are not supported");
+            }
+            let key = batch.bytes()?.map(|k| k.to_vec());
+            let value = batch.bytes()?.map(|v| v.to_vec());
+            res.push(Record { offset: base_offset, timestamp, key, value });
+            next = Some(base_offset + 1);
+            continue;
+        }
+        batch.i32()?; // partition leader epoch
+        batch.i8()?;
+        let crc = batch.i32()? as u32;
+        if crc32c(&batch.data[batch.pos..]) != crc {
+            return data_error("Invalid checksum in Kafka record batch");
+        }
+        let attributes = batch.i16()?;
+        let last_offset_delta = batch.i32()?;
+        let base_timestamp = batch.i64()?;
+        let max_timestamp = batch.i64()?;
+        batch.i64()?; // producer id
+        batch.i16()?; // producer epoch
+        batch.i32()?; // base sequence
+        let count = batch.i32()?;
+        next = Some(base_offset + last_offset_delta as i64 + 1);
+        // Control batches mark the ends of transactions and hold no records
+        if attributes & 0x20 != 0 {
+            continue;
+        }
+        let log_append_time = if attributes & 0x08 != 0 { Some(max_timestamp) } else { None };
+        let records = &batch.data[batch.pos..];
+        match attributes & 7 {
+            0 => decode_records(records, base_offset, base_timestamp, log_append_time, count, &mut res)?,
+            1 => {
+                let mut plain = Vec::new();
+                to_crush_error(GzipDecoder::new(records).read_to_end(&mut plain))?;
+                decode_records(&plain, base_offset, base_timestamp, log_append_time, count, &mut res)?;
+            }
+            2 => return data_error("Snappy compressed Kafka records are not supported"),
+            3 => return data_error("LZ4 compressed Kafka records are not supported"),
+            _ => return data_error("Zstandard compressed Kafka records are not supported"),
+        }
+    }
+    Ok((res, next))
+}
+
+/** The hash the Java client uses to pick the partition of a record with a key. */
+pub fn murmur2(data: &[u8]) -> i32 {
+    const M: u32 = 0x5bd1_e995;
+    let mut h: u32 = 0x9747_b28c ^ data.len() as u32;
+    let chunks = data.chunks_exact(4);
+    let tail = chunks.remainder();
+    for chunk in chunks {
+        let mut k = u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+        k = k.wrapping_mul(M);
+        k ^= k >> 24;
+        k = k.wrapping_mul(M);
+        h = h.wrapping_mul(M);
+        h ^= k;
+    }
+    if tail.len() >= 3 {
+        h ^= u32::from(tail[2]) << 16;
+    }
+    if tail.len() >= 2 {
+        h ^= u32::from(tail[1]) << 8;
+    }
+    if !tail.is_empty() {
+        h ^= u32::from(tail[0]);
+        h = h.wrapping_mul(M);
+    }
+    h ^= h >> 13;
+    h = h.wrapping_mul(M);
+    h ^= h >> 15;
+    h as i32
+}
+
+pub fn partition_for_key(key: &[u8], partitions: usize) -> i32 {
+    ((murmur2(key) & 0x7fff_ffff) as usize % partitions) as i32
+}
+
+struct Connection {
+    stream: TcpStream,
+    correlation_id: i32,
+}
+
+impl Connection {
+    fn connect(address: &str) -> CrushResult<Connection> {
+        let stream = match TcpStream::connect(address) {
+            Ok(stream) => stream,
+            Err(e) => return error(format!("Failed to connect to Kafka broker {}: {}", address, e).as_str()),
+        };
+        to_crush_error(stream.set_read_timeout(Some(Duration::from_secs(60))))?;
+        Ok(Connection { stream, correlation_id: 0 })
+    }
+
+    fn request(&mut self, api_key: i16, version: i16, body: &[u8]) -> CrushResult<Vec<u8>> {
+        self.correlation_id += 1;
+        let mut header = Encoder::default();
+        header.i16(api_key).i16(version).i32(self.correlation_id).string("crush");
+        let mut message = Encoder::default();
+        message.i32((header.buf.len() + body.len()) as i32);
+        message.buf.extend_from_slice(&header.buf);
+        message.buf.extend_from_slice(body);
+        to_crush_error(self.stream.write_all(&message.buf))?;
+        let mut size = [0u8; 4];
+        to_crush_error(self.stream.read_exact(&mut size))?;
+        let mut response = vec![0u8; i32::from_be_bytes(size).max(4) as usize];
+        to_crush_error(self.stream.read_exact(&mut response))?;
+        if response[0..4] != self.correlation_id.to_be_bytes() {
+            return data_error("Kafka response does not match the request");
+        }
+        response.drain(0..4);
+        Ok(response)
+    }
+}
+
+pub struct Partition {
+    pub id: i32,
+    pub leader: i32,
+}
+
+pub struct FetchedPartition {
+    pub partition: i32,
+    pub error: i16,
+    pub records: Vec<Record>,
+    /** The offset to fetch next, if any complete batches were fetched. */
+    pub next_offset: Option<i64>,
+}
+
+pub struct Client {
+    bootstrap: Vec<String>,
+    /** The addresses of the brokers, by node id. */
+    brokers: HashMap<i32, String>,
+    connections: HashMap<String, Connection>,
+}
+
+impl Client {
+    /** Creates a client for the cluster with the given comma separated bootstrap brokers. */
+    pub fn new(brokers: &str) -> Client {
+        Client {
+            bootstrap: brokers.split(',').map(|b| b.trim().to_string()).filter(|b| !b.is_empty()).collect(),
+            brokers: HashMap::new(),
+            connections: HashMap::new(),
+        }
+    }
+
+    fn request(&mut self, address: &str, api_key: i16, version: i16, body: &Encoder) -> CrushResult<Vec<u8>> {
+        if !self.connections.contains_key(address) {
+            self.connections.insert(address.to_string(), Connection::connect(address)?);
+        }
+        let res = self.connections.get_mut(address).map(|c| c.request(api_key, version, &body.buf));
+        match res {
+            Some(Ok(res)) => Ok(res),
+            Some(Err(e)) => {
+                // The connection is in an unknown state, so the next request makes a new one
+                self.connections.remove(address);
+                Err(e)
+            }
+            None => error("Lost the connection to the Kafka broker"),
+        }
+    }
+
+    fn broker_request(&mut self, node: i32, api_key: i16, version: i16, body: &Encoder) -> CrushResult<Vec<u8>> {
+        let address = match self.brokers.get(&node) {
+            Some(address) => address.clone(),
+            None => return error(format!("Unknown Kafka broker {}", node).as_str()),
+        };
+        self.request(&address, api_key, version, body)
+    }
+
+    /** Looks up the partitions of a topic and their leaders, and learns the addresses of the brokers. */
+    pub fn partitions(&mut self, topic: &str) -> CrushResult<Vec<Partition>> {
+        let mut body = Encoder::default();
+        body.i32(1).string(topic);
+        let mut last_error = None;
+        let mut candidates: Vec<String> = self.brokers.values().cloned().collect();
+        candidates.extend(self.bootstrap.iter().cloned());
+        for address in candidates {
+            match self.request(&address, METADATA, 1, &body) {
+                Ok(response) => return self.parse_metadata(&response, topic),
+                Err(e) => last_error = Some(e),
+            }
+        }
+        match last_error {
+            Some(e) => Err(e),
+            None => error("No Kafka brokers given"),
+        }
+    }
+
+    fn parse_metadata(&mut self, response: &[u8], topic: &str) -> CrushResult<Vec<Partition>> {
+        let mut d = Decoder::new(response);
+        for (id, host, port) in d.array(|d| {
+            let id = d.i32()?;
+            let host = d.string()?;
+            let port = d.i32()?;
+            d.null_string()?;
+            Ok((id, host, port))
+        })? {
+            self.brokers.insert(id, format!("{}:{}", host, port));
+        }
+        d.i32()?; // controller id
+        let topics = d.array(|d| {
+            let error = d.i16()?;
+            let name = d.string()?;
+            d.i8()?;
+            let partitions = d.array(|d| {
+                d.i16()?;
+                let id = d.i32()?;
+                let leader = d.i32()?;
+                d.array(|d| d.i32())?;
+                d.array(|d| d.i32())?;
+                Ok(Partition { id, leader })
+            })?;
+            Ok((error, name, partitions))
+        })?;
+        for (code, name, mut partitions) in topics {
+            if name == topic {
+                check(code, format!("metadata request for topic {}", topic).as_str())?;
+                partitions.sort_by_key(|p| p.id);
+                return Ok(partitions);
+            }
+        }
+        error(format!("Unknown Kafka topic {}", topic).as_str())
+    }
+
+    /** Looks up the offsets of partitions at a time, or at the LATEST or EARLIEST special times. */
+    pub fn list_offsets(&mut self, leader: i32, topic: &str, partitions: &[i32], timestamp: i64) -> CrushResult<HashMap<i32, i64>> {
+        let mut body = Encoder::default();
+        body.i32(-1).i32(1).string(topic).i32(partitions.len() as i32);
+        for partition in partitions {
+            body.i32(*partition).i64(timestamp);
+        }
+        let response = self.broker_request(leader, LIST_OFFSETS, 1, &body)?;
+        let mut res = HashMap::new();
+        let mut d = Decoder::new(&response);
+        for partitions in d.array(|d| {
+            d.string()?;
+            d.array(|d| Ok((d.i32()?, d.i16()?, d.i64()?, d.i64()?)))
+        })? {
+            for (partition, code, _, offset) in partitions {
+                check(code, "offset lookup")?;
+                res.insert(partition, offset);
+            }
+        }
+        Ok(res)
+    }
+
+    /** Fetches the records of partitions led by a broker, waiting up to max_wait for any to arrive. */
+    pub fn fetch(&mut self, leader: i32, topic: &str, offsets: &[(i32, i64)], max_wait: Duration) -> CrushResult<Vec<FetchedPartition>> {
+        let mut body = Encoder::default();
+        body.i32(-1)
+            .i32(max_wait.as_millis() as i32)
+            .i32(1)
+            .i32(16 * 1024 * 1024)
+            .i8(0) // read uncommitted
+            .i32(1)
+            .string(topic)
+            .i32(offsets.len() as i32);
+        for (partition, offset) in offsets {
+            body.i32(*partition).i64(*offset).i32(1024 * 1024);
+        }
+        let response = self.broker_request(leader, FETCH, 4, &body)?;
+        let mut d = Decoder::new(&response);
+        d.i32()?; // throttle time
+        let topics = d.array(|d| {
+            d.string()?;
+            d.array(|d| {
+                let partition = d.i32()?;
+                let error = d.i16()?;
+                d.i64()?; // high watermark
+                d.i64()?; // last stable offset
+                d.array(|d| Ok((d.i64()?, d.i64()?)))?;
+                let (records, next_offset) = match d.bytes()? {
+                    Some(data) if error == NONE => decode_batches(data)?,
+                    _ => (vec![], None),
+                };
+                Ok(FetchedPartition { partition, error, records, next_offset })
+            })
+        })?;
+        Ok(topics.into_iter().flatten().collect())
+    }
+
+    /** Produces records to a partition, and returns the offset of the first one. */
+    pub fn produce(&mut self, leader: i32, topic: &str, partition: i32, batch: &[u8]) -> CrushResult<i64> {
+        let mut body = Encoder::default();
+        body.null_string()
+            .i16(-1) // wait for all in sync replicas
+            .i32(30000)
+            .i32(1)
+            .string(topic)
+            .i32(1)
+            .i32(partition)
+            .bytes(batch);
+        let response = self.broker_request(leader, PRODUCE, 3, &body)?;
+        let mut d = Decoder::new(&response);
+        let mut base_offset = -1;
+        for partitions in d.array(|d| {
+            d.string()?;
+            d.array(|d| Ok((d.i32()?, d.i16()?, d.i64()?, d.i64()?)))
+        })? {
+            for (_, code, offset, _) in partitions {
+                check(code, "produce request")?;
+                base_offset = offset;
+            }
+        }
+        Ok(base_offset)
+    }
+
+    /** Finds the broker that coordinates a consumer group, and returns its node id. */
+    pub fn coordinator(&mut self, group: &str) -> CrushResult<i32> {
+        let mut body = Encoder::default();
+        body.string(group);
+        let node = match self.brokers.keys().next() {
+            Some(node) => *node,
+            None => return error("Unknown Kafka brokers"),
+        };
+        let response = self.broker_request(node, FIND_COORDINATOR, 0, &body)?;
+        let mut d = Decoder::new(&response);
+        check(d.i16()?, "coordinator lookup")?;
+        let id = d.i32()?;
+        let host = d.string()?;
+        let port = d.i32()?;
+        self.brokers.insert(id, format!("{}:{}", host, port));
+        Ok(id)
+    }
+
+    /** The committed offsets of a consumer group, for the partitions that have one. */
+    pub fn committed(&mut self, coordinator: i32, group: &str, topic: &str, partitions: &[i32]) -> CrushResult<HashMap<i32, i64>> {
+        let mut body = Encoder::default();
+        body.string(group).i32(1).string(topic).i32(partitions.len() as i32);
+        for partition in partitions {
+            body.i32(*partition);
+        }
+        let response = self.broker_request(coordinator, OFFSET_FETCH, 1, &body)?;
+        let mut d = Decoder::new(&response);
+        let mut res = HashMap::new();
+        for partitions in d.array(|d| {
+            d.string()?;
+            d.array(|d| {
+                let partition = d.i32()?;
+                let offset = d.i64()?;
+                d.null_string()?;
+                Ok((partition, offset, d.i16()?))
+            })
+        })? {
+            for (partition, offset, code) in partitions {
+                check(code, "offset fetch")?;
+                if offset >= 0 {
+                    res.insert(partition, offset);
+                }
+            }
+        }
+        Ok(res)
+    }
+
+    /**
+    Commits the offsets of a consumer group. The group is used without joining it, so the commits
+    are accepted as long as no member of the group is active.
+    */
+    pub fn commit(&mut self, coordinator: i32, group: &str, topic: &str, offsets: &[(i32, i64)]) -> CrushResult<()> {
+        let mut body = Encoder::default();
+        body.string(group)
+            .i32(-1) // generation
+            .string("") // member id
+            .i64(-1) // retention time
+            .i32(1)
+            .string(topic)
+            .i32(offsets.len() as i32);
+        for (partition, offset) in offsets {
+            body.i32(*partition).i64(*offset).null_string();
+        }
+        let response = self.broker_request(coordinator, OFFSET_COMMIT, 2, &body)?;
+        let mut d = Decoder::new(&response);
+        for partitions in d.array(|d| {
+            d.string()?;
+            d.array(|d| Ok((d.i32()?, d.i16()?)))
+        })? {
+            for (_, code) in partitions {
+                check(code, "offset commit")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn batches() {
+        let records = vec![
+            (Some(b"k".to_vec()), Some(b"hello".to_vec())),
+            (None, Some(vec![0u8; 300])),
+            (Some(b"deleted".to_vec()), None),
+        ];
+        let mut data = encode_batch(&records, 1_600_000_000_000);
+        // Rebase the batch and add the start of another one, like in a fetch response
+        data[0..8].copy_from_slice(&40i64.to_be_bytes());
+        data.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 43, 0, 0, 1, 0, 0, 0, 0, 0, 2]);
+        let (decoded, next) = decode_batches(&data).unwrap();
+        assert_eq!(next, Some(43));
+        assert_eq!(decoded.len(), 3);
+        assert_eq!(decoded[0], Record { offset: 40, timestamp: 1_600_000_000_000, key: Some(b"k".to_vec()), value: Some(b"hello".to_vec()) });
+        assert_eq!(decoded[1].value.as_ref().map(|v| v.len()), Some(300));
+        assert_eq!(decoded[2].offset, 42);
+        assert_eq!(decoded[2].value, None);
+        data[70] ^= 1;
+        assert!(decode_batches(&data).is_err());
+    }
+
+    #[test]
+    fn varints() {
+        for v in &[0i64, -1, 1, 63, -64, 64, 300, -300, i64::MAX, i64::MIN] {
+            let mut e = Encoder::default();
+            e.varint(*v);
+            assert_eq!(Decoder::new(&e.buf).varint().unwrap(), *v);
+        }
+        let mut e = Encoder::default();
+        e.varint(-1).varint(150);
+        assert_eq!(e.buf, vec![1, 0xac, 0x02]);
+    }
+
+    #[test]
+    fn partitioning() {
+        assert_eq!(murmur2(b"21"), -973932308);
+        assert_eq!(murmur2(b"foobar"), -790332482);
+        assert_eq!(murmur2(b"a-little-bit-long-string"), -985981536);
+        assert_eq!(murmur2(b"a-little-bit-longer-string"), -1486304829);
+        assert_eq!(murmur2(b"lkjh234lh9fiuh90y23oiuhsafujhadof229phr9h19h89h8"), -58897971);
+        assert_eq!(murmur2(b"abc"), 479470107);
+        assert_eq!(partition_for_key(b"foobar", 3), ((-790332482i32 & 0x7fff_ffff) % 3));
+    }
+}
@@ -0,0 +1,273 @@
+/**
+Access to the Kubernetes API, configured the same way as kubectl: from the kubeconfig file in
+KUBECONFIG or ~/.kube/config, or from the service account of the pod when running inside of
+a cluster. Bearer tokens, token files, exec credential plugins, client certificates and basic
+auth are supported.
+*/
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use openssl::pkcs12::Pkcs12;
+use openssl::pkey::PKey;
+use openssl::x509::X509;
+use reqwest::blocking::{Client, RequestBuilder};
+
+use crate::lang::errors::{CrushResult, argument_error, error, mandate, to_crush_error};
+use crate::util::yaml;
+
+const SERVICE_ACCOUNT: &str = "/var/run/secrets/kubernetes.io/serviceaccount";
+
+pub struct Config {
+    pub server: String,
+    pub namespace: String,
+    token: Option<String>,
+    basic: Option<(String, String)>,
+    ca: Option<Vec<u8>>,
+    identity: Option<(Vec<u8>, Vec<u8>)>,
+    insecure: bool,
+}
+
+fn kubeconfig_path() -> Option<PathBuf> {
+    match std::env::var("KUBECONFIG") {
+        Ok(paths) if !paths.is_empty() => paths.split(':').map(PathBuf::from).find(|p| p.exists()),
+        _ => dirs::home_dir().map(|home| home.join(".kube").join("config")).filter(|p| p.exists()),
+    }
+}
+
+/** Finds the entry with the given name in a list like clusters or users, and returns the named member of it. */
+fn named<'a>(config: &'a serde_json::Value, list: &str, name: &str, member: &str) -> CrushResult<&'a serde_json::Value> {
+    mandate(
+        config[list].as_array()
+            .and_then(|entries| entries.iter().find(|e| e["name"].as_str() == Some(name)))
+            .map(|e| &e[member]),
+        format!("No {} named {} in the kubeconfig", member, name).as_str())
+}
+
+/** Data is either inline as base64, or in a file relative to the kubeconfig. */
+fn data(entry: &serde_json::Value, name: &str, base: &Path) -> CrushResult<Option<Vec<u8>>> {
+    if let Some(inline) = entry[format!("{}-data", name)].as_str() {
+        let inline = inline.split_whitespace().collect::<String>();
+        return Ok(Some(to_crush_error(openssl::base64::decode_block(&inline))?));
+    }
+    match entry[name].as_str() {
+        Some(file) => Ok(Some(to_crush_error(std::fs::read(base.join(file)))?)),
+        None => Ok(None),
+    }
+}
+
+/** Runs an exec credential plugin, like the ones cloud providers use, and returns the token it prints. */
+fn exec_token(exec: &serde_json::Value) -> CrushResult<String> {
+    let command = mandate(exec["command"].as_str(), "Expected the exec credential plugin to have a command")?;
+    let mut cmd = Command::new(command);
+    for arg in exec["args"].as_array().unwrap_or(&vec![]) {
+        cmd.arg(arg.as_str().unwrap_or(""));
+    }
+    for env in exec["env"].as_array().unwrap_or(&vec![]) {
+        if let (Some(name), Some(value)) = (env["name"].as_str(), env["value"].as_str()) {
+            cmd.env(name, value);
+        }
+    }
+    let output = to_crush_error(cmd.stdin(Stdio::null()).stderr(Stdio::inherit()).output())?;
+    if !output.status.success() {
+        return error(format!("The credential plugin {} failed", command).as_str());
+    }
+    let credential: serde_json::Value = to_crush_error(serde_json::from_slice(&output.stdout))?;
+    match credential["status"]["token"].as_str() {
+        Some(token) => Ok(token.to_string()),
+        None => error(format!("The credential plugin {} did not return a token", command).as_str()),
+    }
+}
+
+impl Config {
+    /** Loads the given context of the kubeconfig, or the current one. */
+    pub fn load(context: Option<&str>) -> CrushResult<Config> {
+        let path = match kubeconfig_path() {
+            Some(path) => path,
+            None if context.is_none() && std::env::var_os("KUBERNETES_SERVICE_HOST").is_some() => return Config::in_cluster(),
+            None => return error("No kubeconfig found, set KUBECONFIG or create ~/.kube/config"),
+        };
+        let config = yaml::parse(&to_crush_error(std::fs::read_to_string(&path))?)?;
+        let base = path.parent().unwrap_or_else(|| Path::new("."));
+        let context_name = match context {
+            Some(context) => context.to_string(),
+            None => mandate(config["current-context"].as_str(), "The kubeconfig has no current context")?.to_string(),
+        };
+        let context = named(&config, "contexts", &context_name, "context")?;
+        let cluster = named(&config, "clusters", mandate(context["cluster"].as_str(), "The context has no cluster")?, "cluster")?;
+        let user = match context["user"].as_str() {
+            Some(user) => named(&config, "users", user, "user")?,
+            None => &serde_json::Value::Null,
+        };
+
+        let token = match (user["token"].as_str(), user["tokenFile"].as_str(), user.get("exec")) {
+            (Some(token), _, _) => Some(token.to_string()),
+            (None, Some(file), _) => Some(to_crush_error(std::fs::read_to_string(base.join(file)))?.trim().to_string()),
+            (None, None, Some(exec)) => Some(exec_token(exec)?),
+            _ => None,
+        };
+        if user.get("auth-provider").is_some() && token.is_none() {
+            return argument_error("Auth provider plugins are not supported, use an exec credential plugin instead");
+        }
+        let identity = match (data(user, "client-certificate", base)?, data(user, "client-key", base)?) {
+            (Some(certificate), Some(key)) => Some((certificate, key)),
+            _ => None,
+        };
+        let basic = match (user["username"].as_str(), user["password"].as_str()) {
+            (Some(username), Some(password)) => Some((username.to_string(), password.to_string())),
+            _ => None,
+        };
+        Ok(Config {
+            server: mandate(cluster["server"].as_str(), "The cluster has no server")?.trim_end_matches('/').to_string(),
+            namespace: context["namespace"].as_str().unwrap_or("default").to_string(),
+            token,
+            basic,
+            ca: data(cluster, "certificate-authority", base)?,
+            identity,
+            insecure: cluster["insecure-skip-tls-verify"].as_bool().unwrap_or(false),
+        })
+    }
+
+    fn in_cluster() -> CrushResult<Config> {
+        let host = to_crush_error(std::env::var("KUBERNETES_SERVICE_HOST"))?;
+        let port = std::env::var("KUBERNETES_SERVICE_PORT").unwrap_or_else(|_| "443".to_string());
+        let account = Path::new(SERVICE_ACCOUNT);
+        let host = if host.contains(':') { format!("[{}]", host) } else { host };
+        Ok(Config {
+            server: format!("https://{}:{}", host, port),
+            namespace: std::fs::read_to_string(account.join("namespace")).map(|n| n.trim().to_string()).unwrap_or_else(|_| "default".to_string()),
+            token: Some(to_crush_error(std::fs::read_to_string(account.join("token")))?.trim().to_string()),
+            basic: None,
+            ca: std::fs::read(account.join("ca.crt")).ok(),
+            identity: None,
+            insecure: false,
+        })
+    }
+
+    /** A client for the cluster. Streaming requests like following logs have no timeout. */
+    fn client(&self, streaming: bool) -> CrushResult<Client> {
+        let mut builder = Client::builder().danger_accept_invalid_certs(self.insecure);
+        if streaming {
+            builder = builder.timeout(None);
+        }
+        if let Some(ca) = &self.ca {
+            for certificate in to_crush_error(X509::stack_from_pem(ca))? {
+                let der = to_crush_error(certificate.to_der())?;
+                builder = builder.add_root_certificate(to_crush_error(reqwest::Certificate::from_der(&der))?);
+            }
+        }
+        if let Some((certificate, key)) = &self.identity {
+            // The TLS backend only takes client certificates as PKCS #12
+            let key = to_crush_error(PKey::private_key_from_pem(key))?;
+            let certificate = to_crush_error(X509::from_pem(certificate))?;
+            let pkcs12 = to_crush_error(Pkcs12::builder().name("crush").pkey(&key).cert(&certificate).build2(""))?;
+            let identity = reqwest::Identity::from_pkcs12_der(&to_crush_error(pkcs12.to_der())?, "");
+            builder = builder.identity(to_crush_error(identity)?);
+        }
+        to_crush_error(builder.build())
+    }
+
+    fn request(&self, path: &str, query: &[(&str, String)], streaming: bool) -> CrushResult<RequestBuilder> {
+        let mut request = self.client(streaming)?
+            .get(&format!("{}{}", self.server, path))
+            .query(query);
+        if let Some(token) = &self.token {
+            request = request.bearer_auth(token);
+        } else if let Some((username, password)) = &self.basic {
+            request = request.basic_auth(username, Some(password));
+        }
+        Ok(request)
+    }
+
+    fn send(&self, path: &str, query: &[(&str, String)], streaming: bool) -> CrushResult<reqwest::blocking::Response> {
+        let mut response = to_crush_error(self.request(path, query, streaming)?.send())?;
+        if response.status().is_success() {
+            return Ok(response);
+        }
+        let status = response.status();
+        let mut text = String::new();
+        let _ = response.read_to_string(&mut text);
+        // Errors are Status objects with a message
+        let message = serde_json::from_str::<serde_json::Value>(&text).ok()
+            .and_then(|status| status["message"].as_str().map(|m| m.to_string()))
+            .unwrap_or(text);
+        error(format!("Kubernetes request failed with status {}: {}", status, message.trim()).as_str())
+    }
+
+    pub fn get(&self, path: &str, query: &[(&str, String)]) -> CrushResult<serde_json::Value> {
+        to_crush_error(serde_json::from_reader(self.send(path, query, false)?))
+    }
+
+    pub fn stream(&self, path: &str, query: &[(&str, String)]) -> CrushResult<Box<dyn Read + Send>> {
+        Ok(Box::new(self.send(path, query, true)?))
+    }
+}
+
+/** A kind of resource, and where it is in the API. */
+pub struct Resource {
+    pub name: &'static str,
+    aliases: &'static [&'static str],
+    pub group_version: &'static str,
+    pub namespaced: bool,
+}
+
+const RESOURCES: [Resource; 21] = [
+    Resource { name: "pods", aliases: &["pod", "po"], group_version: "v1", namespaced: true },
+    Resource { name: "services", aliases: &["service", "svc"], group_version: "v1", namespaced: true },
+    Resource { name: "configmaps", aliases: &["configmap", "cm"], group_version: "v1", namespaced: true },
+    Resource { name: "secrets", aliases: &["secret"], group_version: "v1", namespaced: true },
+    Resource { name: "endpoints", aliases: &["endpoint", "ep"], group_version: "v1", namespaced: true },
+    Resource { name: "events", aliases: &["event", "ev"], group_version: "v1", namespaced: true },
+    Resource { name: "persistentvolumeclaims", aliases: &["persistentvolumeclaim", "pvc"], group_version: "v1", namespaced: true },
+    Resource { name: "serviceaccounts", aliases: &["serviceaccount", "sa"], group_version: "v1", namespaced: true },
+    Resource { name: "nodes", aliases: &["node", "no"], group_version: "v1", namespaced: false },
+    Resource { name: "namespaces", aliases: &["namespace", "ns"], group_version: "v1", namespaced: false },
+    Resource { name: "persistentvolumes", aliases: &["persistentvolume", "pv"], group_version: "v1", namespaced: false },
+    Resource { name: "deployments", aliases: &["deployment", "deploy"], group_version: "apps/v1", namespaced: true },
+    Resource { name: "statefulsets", aliases: &["statefulset", "sts"], group_version: "apps/v1", namespaced: true },
+    Resource { name: "daemonsets", aliases: &["daemonset", "ds"], group_version: "apps/v1", namespaced: true },
+    Resource { name: "replicasets", aliases: &["replicaset", "rs"], group_version: "apps/v1", namespaced: true },
+    Resource { name: "jobs", aliases: &["job"], group_version: "batch/v1", namespaced: true },
+    Resource { name: "cronjobs", aliases: &["cronjob", "cj"], group_version: "batch/v1", namespaced: true },
+    Resource { name: "ingresses", aliases: &["ingress", "ing"], group_version: "networking.k8s.io/v1", namespaced: true },
+    Resource { name: "networkpolicies", aliases: &["networkpolicy", "netpol"], group_version: "networking.k8s.io/v1", namespaced: true },
+    Resource { name: "storageclasses", aliases: &["storageclass", "sc"], group_version: "storage.k8s.io/v1", namespaced: false },
+    Resource { name: "horizontalpodautoscalers", aliases: &["horizontalpodautoscaler", "hpa"], group_version: "autoscaling/v2", namespaced: true },
+];
+
+/**
+The path to a collection of resources. Kinds are given by name, singular name or short name,
+or as group/version/resource for resources that aren't built in, e.g. custom resources.
+*/
+pub fn collection_path(kind: &str, namespace: Option<&str>) -> CrushResult<String> {
+    let lower = kind.to_lowercase();
+    let (group_version, resource, namespaced) =
+        match RESOURCES.iter().find(|r| r.name == lower || r.aliases.contains(&lower.as_str())) {
+            Some(r) => (r.group_version.to_string(), r.name.to_string(), r.namespaced),
+            None => match lower.rsplit_once('/') {
+                Some((group_version, resource)) if group_version.contains('/') => (group_version.to_string(), resource.to_string(), true),
+                _ => return argument_error(format!("Unknown kind {}, use group/version/resource for custom resources", kind).as_str()),
+            },
+        };
+    let prefix = if group_version.contains('/') { format!("/apis/{}", group_version) } else { format!("/api/{}", group_version) };
+    match (namespaced, namespace) {
+        (true, Some(namespace)) => Ok(format!("{}/namespaces/{}/{}", prefix, namespace, resource)),
+        _ => Ok(format!("{}/{}", prefix, resource)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paths() {
+        assert_eq!(collection_path("po", Some("kube-system")).unwrap(), "/api/v1/namespaces/kube-system/pods");
+        assert_eq!(collection_path("Deployment", None).unwrap(), "/apis/apps/v1/deployments");
+        assert_eq!(collection_path("nodes", Some("default")).unwrap(), "/api/v1/nodes");
+        assert_eq!(
+            collection_path("cert-manager.io/v1/certificates", Some("web")).unwrap(),
+            "/apis/cert-manager.io/v1/namespaces/web/certificates");
+        assert!(collection_path("widgets", None).is_err());
+    }
+}
@@ -0,0 +1,225 @@
+use std::cmp::min;
+use std::fmt::{Debug, Formatter};
+use std::fs::File;
+use std::io::{BufRead, Error, ErrorKind, Read};
+use std::os::unix::io::AsRawFd;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use nix::libc::c_void;
+use nix::sys::mman::{madvise, mmap, munmap, MapFlags, MmapAdvise, ProtFlags};
+
+use crate::lang::binary::BinaryReader;
+
+/**
+Regular files at least this large are memory mapped instead of being read through the file
+descriptor. Mapping has a fixed setup cost, so it only pays off for large files.
+*/
+pub const MMAP_THRESHOLD: u64 = 4 * 1024 * 1024;
+
+/**
+The largest slice handed out by fill_buf. Consumers that scan for separators work on one
+chunk at a time, so the pages of a multi-gigabyte file are touched in order.
+*/
+const CHUNK_SIZE: usize = 4 * 1024 * 1024;
+
+/**
+Files modified more recently than this are read normally, because they are likely still
+being written to, e.g. log files, and might be truncated while they are being read.
+*/
+const QUIET_PERIOD: Duration = Duration::from_secs(10);
+
+/**
+A read only mapping of a whole file. The mapping is removed when the last reference to it
+is dropped.
+
+If the file is truncated by someone else while it is mapped, reading the missing pages kills
+the whole shell with SIGBUS. To make that unlikely, only files that have not been modified for
+a while are mapped, and the size of the file is checked again before every chunk is read, so
+that a truncation is reported as an error instead. A file truncated between that check and
+reading the chunk still causes a SIGBUS.
+*/
+struct Mapping {
+    address: *mut c_void,
+    len: usize,
+    file: File,
+}
+
+// The mapping is read only, so sharing it between threads is safe
+unsafe impl Send for Mapping {}
+unsafe impl Sync for Mapping {}
+
+impl Mapping {
+    fn new(file: &File, len: usize) -> std::io::Result<Mapping> {
+        let file = file.try_clone()?;
+        let address = unsafe {
+            mmap(std::ptr::null_mut(), len, ProtFlags::PROT_READ, MapFlags::MAP_PRIVATE, file.as_raw_fd(), 0)
+        }.map_err(|e| Error::other(e.to_string()))?;
+        // Only a hint, reading works just as well if it is ignored
+        let _ = unsafe { madvise(address, len, MmapAdvise::MADV_SEQUENTIAL) };
+        Ok(Mapping { address, len, file })
+    }
+
+    /** Fail if the file no longer covers the mapping up to the specified offset. */
+    fn check(&self, end: usize) -> std::io::Result<()> {
+        if self.file.metadata()?.len() < end as u64 {
+            return Err(Error::new(ErrorKind::UnexpectedEof, "The file was truncated while being read"));
+        }
+        Ok(())
+    }
+
+    fn data(&self) -> &[u8] {
+        unsafe { std::slice::from_raw_parts(self.address as *const u8, self.len) }
+    }
+}
+
+impl Drop for Mapping {
+    fn drop(&mut self) {
+        let _ = unsafe { munmap(self.address, self.len) };
+    }
+}
+
+/**
+A BinaryReader for a memory mapped file. Data is copied straight out of the page cache, and
+line oriented commands can scan the mapping through BufRead without copying it at all.
+*/
+pub struct MappedReader {
+    mapping: Arc<Mapping>,
+    offset: usize,
+    /** The offset up to which the file was known to be large enough when last checked. */
+    checked: usize,
+}
+
+impl MappedReader {
+    /**
+    Map the specified file, or return None if it is not a regular file large enough to be
+    worth mapping, or if it has been modified recently, in which case it should be read normally.
+    */
+    pub fn open(file: &File) -> std::io::Result<Option<MappedReader>> {
+        let metadata = file.metadata()?;
+        if !metadata.is_file() || metadata.len() < MMAP_THRESHOLD || metadata.len() > usize::MAX as u64 {
+            return Ok(None);
+        }
+        let quiet = SystemTime::now().duration_since(metadata.modified()?)
+            .map(|age| age >= QUIET_PERIOD)
+            .unwrap_or(false);
+        if !quiet {
+            return Ok(None);
+        }
+        let mapping = Mapping::new(file, metadata.len() as usize)?;
+        Ok(Some(MappedReader { mapping: Arc::new(mapping), offset: 0, checked: 0 }))
+    }
+}
+
+impl Read for MappedReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let available = self.fill_buf()?;
+        let len = min(buf.len(), available.len());
+        buf[..len].copy_from_slice(&available[..len]);
+        self.consume(len);
+        Ok(len)
+    }
+}
+
+impl BufRead for MappedReader {
+    fn fill_buf(&mut self) -> std::io::Result<&[u8]> {
+        let data = self.mapping.data();
+        let end = min(data.len(), self.offset + CHUNK_SIZE);
+        if end > self.checked {
+            self.mapping.check(end)?;
+            self.checked = end;
+        }
+        Ok(&data[self.offset..end])
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.offset = min(self.mapping.len, self.offset + amt);
+    }
+}
+
+impl BinaryReader for MappedReader {
+    fn clone(&self) -> Box<dyn BinaryReader + Send + Sync> {
+        Box::from(MappedReader { mapping: self.mapping.clone(), offset: 0, checked: 0 })
+    }
+
+    fn buffered(self: Box<Self>) -> Box<dyn BufRead + Send + Sync> {
+        self
+    }
+}
+
+impl Debug for MappedReader {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+        f.write_str("<mapped file reader>")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Seek, SeekFrom, Write};
+
+    fn temporary_file(len: usize) -> File {
+        let path = std::env::temp_dir().join(format!("crush-mmap-test-{}-{}", std::process::id(), len));
+        let mut file = std::fs::OpenOptions::new().read(true).write(true).create(true).truncate(true).open(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let line = b"0123456789abcdefghijklmnopqrstuvwxyz\n";
+        let mut written = 0;
+        while written < len {
+            let count = min(line.len(), len - written);
+            file.write_all(&line[..count]).unwrap();
+            written += count;
+        }
+        file.seek(SeekFrom::Start(0)).unwrap();
+        file.set_modified(SystemTime::now() - 2 * QUIET_PERIOD).unwrap();
+        file
+    }
+
+    #[test]
+    fn test_small_files_are_not_mapped() {
+        assert!(MappedReader::open(&temporary_file(1000)).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_recently_modified_files_are_not_mapped() {
+        let file = temporary_file(MMAP_THRESHOLD as usize);
+        file.set_modified(SystemTime::now()).unwrap();
+        assert!(MappedReader::open(&file).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_truncated_file() {
+        let file = temporary_file(MMAP_THRESHOLD as usize + CHUNK_SIZE);
+        let mut reader = MappedReader::open(&file).unwrap().unwrap();
+        let mut buf = vec![0u8; 1024];
+        assert_eq!(reader.read(&mut buf).unwrap(), 1024);
+        file.set_len(1000).unwrap();
+        reader.consume(CHUNK_SIZE);
+        assert_eq!(reader.read(&mut buf).unwrap_err().kind(), ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn test_read() {
+        let len = MMAP_THRESHOLD as usize + CHUNK_SIZE + 17;
+        let mut file = temporary_file(len);
+        let mut expected = Vec::new();
+        file.read_to_end(&mut expected).unwrap();
+
+        let reader = MappedReader::open(&file).unwrap().unwrap();
+        let mut copy = BinaryReader::clone(&reader);
+        let mut data = Vec::new();
+        copy.read_to_end(&mut data).unwrap();
+        assert_eq!(data, expected);
+
+        let mut lines = 0;
+        let mut total = 0;
+        let mut buffered = Box::new(reader).buffered();
+        let mut line = Vec::new();
+        while buffered.read_until(b'\n', &mut line).unwrap() > 0 {
+            lines += 1;
+            total += line.len();
+            line.clear();
+        }
+        assert_eq!(total, len);
+        assert_eq!(lines, len.div_ceil(37));
+    }
+}
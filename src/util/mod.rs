@@ -6,3 +6,23 @@ pub mod glob;
 pub mod replace;
 pub mod regex;
 pub mod identity_arc;
+pub mod crc32;
+pub mod deflate;
+pub mod gzip;
+pub mod mmap;
+pub mod font;
+pub mod png;
+pub mod xml;
+pub mod dns;
+pub mod crypt;
+pub mod whois;
+pub mod docker;
+pub mod icmp;
+pub mod yaml;
+pub mod kube;
+pub mod external;
+pub mod prometheus;
+pub mod expiry;
+pub mod kafka;
+pub mod aws;
+pub mod websocket;
@@ -0,0 +1,98 @@
+use std::io::Write;
+
+use crate::util::crc32::Crc32;
+use crate::util::deflate::Deflater;
+
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+const BIT_DEPTH: u8 = 8;
+const COLOR_TYPE_RGB: u8 = 2;
+
+fn adler32(data: &[u8]) -> u32 {
+    let mut a: u32 = 1;
+    let mut b: u32 = 0;
+    for chunk in data.chunks(5552) {
+        for byte in chunk {
+            a += u32::from(*byte);
+            b += a;
+        }
+        a %= 65521;
+        b %= 65521;
+    }
+    (b << 16) | a
+}
+
+fn chunk(output: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    output.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    output.extend_from_slice(kind);
+    output.extend_from_slice(data);
+    let mut crc = Crc32::new();
+    crc.update(kind);
+    crc.update(data);
+    output.extend_from_slice(&crc.finish().to_be_bytes());
+}
+
+/**
+Encode an image as a PNG file. The pixels are given as rows of 8 bit red, green and blue
+triplets, starting from the top left corner.
+*/
+pub fn encode(width: usize, height: usize, pixels: &[u8]) -> std::io::Result<Vec<u8>> {
+    assert_eq!(pixels.len(), width * height * 3);
+    let mut raw = Vec::with_capacity((width * 3 + 1) * height);
+    for row in pixels.chunks(width * 3) {
+        // Filter type 0, the row is stored as is
+        raw.push(0);
+        raw.extend_from_slice(row);
+    }
+    // The image data is a zlib stream, i.e. a deflate stream with a header and a checksum
+    let mut deflater = Deflater::new(vec![0x78, 0x01]);
+    deflater.write_all(&raw)?;
+    let mut compressed = deflater.finish()?;
+    compressed.extend_from_slice(&adler32(&raw).to_be_bytes());
+
+    let mut header = Vec::new();
+    header.extend_from_slice(&(width as u32).to_be_bytes());
+    header.extend_from_slice(&(height as u32).to_be_bytes());
+    // Bit depth, color type, compression method, filter method and interlace method
+    header.extend_from_slice(&[BIT_DEPTH, COLOR_TYPE_RGB, 0, 0, 0]);
+
+    let mut res = SIGNATURE.to_vec();
+    chunk(&mut res, b"IHDR", &header);
+    chunk(&mut res, b"IDAT", &compressed);
+    chunk(&mut res, b"IEND", &[]);
+    Ok(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::crc32::crc32;
+    use crate::util::deflate::inflate;
+
+    #[test]
+    fn test_adler32() {
+        assert_eq!(adler32(b"Wikipedia"), 0x11e60398);
+        // Long runs of large bytes must not overflow the sums between reductions
+        let data = [0xff; 100_000];
+        let (a, b) = data.iter().fold((1u64, 0u64), |(a, b), d| ((a + *d as u64) % 65521, (b + a + *d as u64) % 65521));
+        assert_eq!(adler32(&data), ((b << 16) | a) as u32);
+    }
+
+    #[test]
+    fn test_encode() {
+        let pixels: Vec<u8> = (0..4 * 3 * 3).map(|i| (i * 7) as u8).collect();
+        let png = encode(4, 3, &pixels).unwrap();
+        assert_eq!(png[..8], SIGNATURE);
+        assert_eq!(&png[12..16], b"IHDR");
+        assert_eq!(u32::from_be_bytes([png[16], png[17], png[18], png[19]]), 4);
+        assert_eq!(u32::from_be_bytes([png[20], png[21], png[22], png[23]]), 3);
+        assert_eq!(u32::from_be_bytes([png[29], png[30], png[31], png[32]]), crc32(&png[12..29]));
+
+        let length = u32::from_be_bytes([png[33], png[34], png[35], png[36]]) as usize;
+        assert_eq!(&png[37..41], b"IDAT");
+        let zlib = &png[41..41 + length];
+        let raw = inflate(&zlib[2..zlib.len() - 4]).unwrap();
+        assert_eq!(raw.len(), 3 * (4 * 3 + 1));
+        assert_eq!(&raw[1..13], &pixels[0..12]);
+        assert_eq!(&png[png.len() - 8..png.len() - 4], b"IEND");
+    }
+}
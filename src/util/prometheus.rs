@@ -0,0 +1,175 @@
+/**
+Reading and writing the Prometheus text exposition format, where every sample is a line like
+
+    http_requests_total{method="post",code="200"} 1027 1395066363000
+
+with optional labels and an optional timestamp in milliseconds. Comment lines, including the
+HELP and TYPE lines, are ignored when reading.
+*/
+use crate::lang::errors::{CrushResult, data_error};
+
+#[derive(Debug, PartialEq)]
+pub struct Sample {
+    pub metric: String,
+    pub labels: Vec<(String, String)>,
+    pub value: f64,
+    /** Milliseconds since the epoch. */
+    pub timestamp: Option<i64>,
+}
+
+/** Replaces the characters that are not allowed in metric and label names with underscores. */
+pub fn name(name: &str) -> String {
+    let res: String = name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '_' || c == ':' { c } else { '_' })
+        .collect();
+    match res.chars().next() {
+        Some(c) if !c.is_ascii_digit() => res,
+        _ => format!("_{}", res),
+    }
+}
+
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+pub fn format_value(value: f64) -> String {
+    if value.is_nan() {
+        "NaN".to_string()
+    } else if value.is_infinite() {
+        if value > 0.0 { "+Inf" } else { "-Inf" }.to_string()
+    } else {
+        value.to_string()
+    }
+}
+
+pub fn format(sample: &Sample) -> String {
+    let mut res = name(&sample.metric);
+    if !sample.labels.is_empty() {
+        res.push('{');
+        res.push_str(&sample.labels.iter()
+            .map(|(k, v)| format!("{}=\"{}\"", name(k), escape(v)))
+            .collect::<Vec<_>>()
+            .join(","));
+        res.push('}');
+    }
+    res.push(' ');
+    res.push_str(&format_value(sample.value));
+    if let Some(timestamp) = sample.timestamp {
+        res.push_str(&format!(" {}", timestamp));
+    }
+    res
+}
+
+fn parse_value(value: &str) -> Option<f64> {
+    match value {
+        "NaN" => Some(f64::NAN),
+        "+Inf" | "Inf" => Some(f64::INFINITY),
+        "-Inf" => Some(f64::NEG_INFINITY),
+        _ => value.parse().ok(),
+    }
+}
+
+/** Parses the labels after the opening brace, and returns them with the rest of the line after the closing brace. */
+fn parse_labels(mut text: &str) -> Option<(Vec<(String, String)>, &str)> {
+    let mut labels = Vec::new();
+    loop {
+        text = text.trim_start();
+        if let Some(rest) = text.strip_prefix('}') {
+            return Some((labels, rest));
+        }
+        let eq = text.find('=')?;
+        let key = text[..eq].trim().to_string();
+        text = text[eq + 1..].trim_start().strip_prefix('"')?;
+        let mut value = String::new();
+        let mut chars = text.char_indices();
+        let end = loop {
+            match chars.next()? {
+                (idx, '"') => break idx,
+                (_, '\\') => match chars.next()?.1 {
+                    'n' => value.push('\n'),
+                    c => value.push(c),
+                },
+                (_, c) => value.push(c),
+            }
+        };
+        labels.push((key, value));
+        text = text[end + 1..].trim_start();
+        text = text.strip_prefix(',').unwrap_or(text);
+    }
+}
+
+fn parse_line(line: &str) -> Option<Sample> {
+    let end = line.find(|c: char| c == '{' || c.is_whitespace()).unwrap_or(line.len());
+    let metric = line[..end].to_string();
+    let (labels, rest) = match line[end..].strip_prefix('{') {
+        Some(rest) => parse_labels(rest)?,
+        None => (vec![], &line[end..]),
+    };
+    let mut parts = rest.split_whitespace();
+    let value = parse_value(parts.next()?)?;
+    let timestamp = match parts.next() {
+        Some(t) => Some(t.parse().ok()?),
+        None => None,
+    };
+    if metric.is_empty() || parts.next().is_some() {
+        return None;
+    }
+    Some(Sample { metric, labels, value, timestamp })
+}
+
+pub fn parse(text: &str) -> CrushResult<Vec<Sample>> {
+    let mut res = Vec::new();
+    for (idx, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        match parse_line(line) {
+            Some(sample) => res.push(sample),
+            None => return data_error(format!("Invalid metric on line {}: {}", idx + 1, line).as_str()),
+        }
+    }
+    Ok(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let text = "# HELP http_requests_total The total number of requests.\n\
+                    # TYPE http_requests_total counter\n\
+                    http_requests_total{method=\"post\",code=\"200\"} 1027 1395066363000\n\
+                    msdos_file_access_time_seconds{path=\"C:\\\\DIR\\\\FILE.TXT\",error=\"Cannot find \\\"file\\\"\\n\"} 1.458255915e9\n\
+                    up 1\n\
+                    \n\
+                    temperature{} -Inf\n";
+        let samples = parse(text).unwrap();
+        assert_eq!(samples[0], Sample {
+            metric: "http_requests_total".to_string(),
+            labels: vec![("method".to_string(), "post".to_string()), ("code".to_string(), "200".to_string())],
+            value: 1027.0,
+            timestamp: Some(1395066363000),
+        });
+        assert_eq!(samples[1].labels[0].1, "C:\\DIR\\FILE.TXT");
+        assert_eq!(samples[1].labels[1].1, "Cannot find \"file\"\n");
+        assert_eq!(samples[2], Sample { metric: "up".to_string(), labels: vec![], value: 1.0, timestamp: None });
+        assert_eq!(samples[3].value, f64::NEG_INFINITY);
+        assert_eq!(format(&samples[0]), "http_requests_total{method=\"post\",code=\"200\"} 1027 1395066363000");
+        assert_eq!(parse(&format(&samples[1])).unwrap()[0], samples[1]);
+    }
+
+    #[test]
+    fn errors() {
+        assert!(parse("up").is_err());
+        assert!(parse("up{job=\"a} 1").is_err());
+        assert!(parse("up 1 2 3").is_err());
+    }
+
+    #[test]
+    fn names() {
+        assert_eq!(name("http.requests-total"), "http_requests_total");
+        assert_eq!(name("5xx"), "_5xx");
+    }
+}
@@ -1,4 +1,6 @@
-use chrono::Duration;
+use chrono::{DateTime, Duration, Local, NaiveDate, NaiveDateTime, TimeZone};
+use crate::lang::errors::{CrushResult, argument_error};
+use std::convert::TryFrom;
 
 pub fn duration_format(d: &Duration) -> String {
     const MICROS_IN_SECOND: i128 = 1_000_000_000;
@@ -63,3 +65,113 @@ pub fn duration_format(d: &Duration) -> String {
     }
     res
 }
+
+/**
+Parse a duration like 1h30m, 1.5s or 100ms. This is the syntax of duration literals, and of
+strings passed as duration arguments. The units are ns, us, ms, s, m, h, d, w and y, where a
+year is 365 days.
+*/
+pub fn parse_duration(s: &str) -> CrushResult<Duration> {
+    const NANOS_IN_SECOND: i128 = 1_000_000_000;
+    let invalid = || argument_error(format!("Invalid duration {}", s).as_str());
+    let (negative, mut rest) = match s.trim() {
+        t if t.starts_with('-') => (true, &t[1..]),
+        t => (false, t),
+    };
+    if rest.is_empty() {
+        return invalid();
+    }
+    let mut nanos: i128 = 0;
+    while !rest.is_empty() {
+        let number_len = rest.find(|c: char| !(c.is_ascii_digit() || c == '.' || c == '_')).unwrap_or(rest.len());
+        let unit_len = rest[number_len..].find(|c: char| !c.is_ascii_alphabetic()).unwrap_or(rest.len() - number_len);
+        let number = rest[..number_len].replace('_', "");
+        let unit: i128 = match &rest[number_len..number_len + unit_len] {
+            "ns" => 1,
+            "us" => 1_000,
+            "ms" => 1_000_000,
+            "s" => NANOS_IN_SECOND,
+            "m" => NANOS_IN_SECOND * 60,
+            "h" => NANOS_IN_SECOND * 60 * 60,
+            "d" => NANOS_IN_SECOND * 60 * 60 * 24,
+            "w" => NANOS_IN_SECOND * 60 * 60 * 24 * 7,
+            "y" => NANOS_IN_SECOND * 60 * 60 * 24 * 365,
+            _ => return invalid(),
+        };
+        let amount = if number.contains('.') {
+            match number.parse::<f64>() {
+                Ok(f) => (f * unit as f64) as i128,
+                Err(_) => return invalid(),
+            }
+        } else {
+            match number.parse::<i128>().ok().and_then(|n| n.checked_mul(unit)) {
+                Some(n) => n,
+                None => return invalid(),
+            }
+        };
+        nanos = match nanos.checked_add(amount) {
+            Some(n) => n,
+            None => return invalid(),
+        };
+        rest = &rest[number_len + unit_len..];
+    }
+    if negative {
+        nanos = -nanos;
+    }
+    match i64::try_from(nanos / NANOS_IN_SECOND) {
+        Ok(seconds) if seconds.abs() <= i64::MAX / 1000 =>
+            Ok(Duration::seconds(seconds) + Duration::nanoseconds((nanos % NANOS_IN_SECOND) as i64)),
+        _ => argument_error(format!("Duration {} is out of range", s).as_str()),
+    }
+}
+
+/**
+Parse a point in time like 2020-01-01T12:00:00, the syntax of time literals. Strings passed
+as time arguments may also use a space instead of the T, leave out the time of day, or
+specify a time zone like in RFC 3339. Times without a time zone are in the local time zone.
+*/
+pub fn parse_time(s: &str) -> CrushResult<DateTime<Local>> {
+    let s = s.trim();
+    if let Ok(t) = DateTime::parse_from_rfc3339(s) {
+        return Ok(t.with_timezone(&Local));
+    }
+    let naive = ["%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%dT%H:%M", "%Y-%m-%d %H:%M:%S%.f", "%Y-%m-%d %H:%M"].iter()
+        .filter_map(|format| NaiveDateTime::parse_from_str(s, format).ok())
+        .next()
+        .or_else(|| NaiveDate::parse_from_str(s, "%Y-%m-%d").ok().map(|d| d.and_hms(0, 0, 0)));
+    match naive.and_then(|t| Local.from_local_datetime(&t).single()) {
+        Some(t) => Ok(t),
+        None => argument_error(format!("Invalid time {}", s).as_str()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Datelike, Timelike};
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("5s").unwrap(), Duration::seconds(5));
+        assert_eq!(parse_duration("1h30m").unwrap(), Duration::minutes(90));
+        assert_eq!(parse_duration("1.5s").unwrap(), Duration::milliseconds(1500));
+        assert_eq!(parse_duration("100ms").unwrap(), Duration::milliseconds(100));
+        assert_eq!(parse_duration("-2d").unwrap(), Duration::days(-2));
+        assert_eq!(parse_duration("1_000ns").unwrap(), Duration::microseconds(1));
+        assert!(parse_duration("").is_err());
+        assert!(parse_duration("5").is_err());
+        assert!(parse_duration("5x").is_err());
+        assert!(parse_duration("99999999999999y").is_err());
+    }
+
+    #[test]
+    fn test_parse_time() {
+        let t = parse_time("2020-02-03T04:05:06").unwrap();
+        assert_eq!((t.year(), t.month(), t.day(), t.hour(), t.minute(), t.second()), (2020, 2, 3, 4, 5, 6));
+        assert_eq!(parse_time("2020-02-03 04:05").unwrap().minute(), 5);
+        assert_eq!(parse_time("2020-02-03T04:05:06.5").unwrap().nanosecond(), 500_000_000);
+        assert_eq!(parse_time("2020-02-03").unwrap().hour(), 0);
+        assert_eq!(parse_time("2020-02-03T04:05:06Z").unwrap(), Local.timestamp(1_580_702_706, 0));
+        assert!(parse_time("yesterday").is_err());
+    }
+}
@@ -1,7 +1,7 @@
 use std::collections::HashMap;
 use std::sync::Mutex;
 
-use users::uid_t;
+use users::{gid_t, uid_t};
 use users::User;
 
 use lazy_static::lazy_static;
@@ -18,6 +18,26 @@ pub fn create_user_map() -> HashMap<uid_t, User> {
     users.map(|user| (user.uid(), user)).collect()
 }
 
+/** The ids of all groups, sorted and without duplicates. */
+pub fn all_group_ids() -> Vec<gid_t> {
+    let _user_lock = USER_MUTEX.lock().unwrap();
+    let mut res = Vec::new();
+    unsafe {
+        libc::setgrent();
+        loop {
+            let group = libc::getgrent();
+            if group.is_null() {
+                break;
+            }
+            res.push((*group).gr_gid);
+        }
+        libc::endgrent();
+    }
+    res.sort_unstable();
+    res.dedup();
+    res
+}
+
 pub trait UserMap {
     fn get_name(&self, uid: uid_t) -> Value;
 }
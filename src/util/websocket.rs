@@ -0,0 +1,382 @@
+/**
+A WebSocket client, as described in RFC 6455, over plain TCP for ws urls and TLS for wss urls.
+
+A connection is shared between one thread reading messages and any number of threads sending
+them. Reads time out often, so that the reading thread never holds on to the connection for
+long, and any partial frame read so far is kept until the rest of it arrives.
+*/
+use std::io::{ErrorKind, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use openssl::hash::{MessageDigest, hash};
+use openssl::ssl::{SslConnector, SslMethod, SslStream};
+
+use crate::lang::errors::{CrushResult, argument_error, data_error, error, mandate, to_crush_error};
+
+const GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+const CONTINUATION: u8 = 0;
+const TEXT: u8 = 1;
+const BINARY: u8 = 2;
+const CLOSE: u8 = 8;
+const PING: u8 = 9;
+const PONG: u8 = 10;
+
+/** Messages larger than this are refused rather than buffered. */
+const MAX_MESSAGE: usize = 64 * 1024 * 1024;
+
+#[derive(Debug, PartialEq)]
+pub enum Message {
+    Text(String),
+    Binary(Vec<u8>),
+}
+
+enum Stream {
+    Plain(TcpStream),
+    Tls(Box<SslStream<TcpStream>>),
+}
+
+impl Read for Stream {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            Stream::Plain(s) => s.read(buf),
+            Stream::Tls(s) => s.read(buf),
+        }
+    }
+}
+
+impl Write for Stream {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            Stream::Plain(s) => s.write(buf),
+            Stream::Tls(s) => s.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            Stream::Plain(s) => s.flush(),
+            Stream::Tls(s) => s.flush(),
+        }
+    }
+}
+
+struct Url {
+    tls: bool,
+    host: String,
+    port: u16,
+    path: String,
+}
+
+fn parse_url(url: &str) -> CrushResult<Url> {
+    let (tls, rest) = if let Some(rest) = url.strip_prefix("wss://") {
+        (true, rest)
+    } else if let Some(rest) = url.strip_prefix("ws://") {
+        (false, rest)
+    } else {
+        return argument_error(format!("Expected a ws:// or wss:// url, got {}", url).as_str());
+    };
+    let (authority, path) = match rest.find('/') {
+        Some(idx) => (&rest[..idx], rest[idx..].to_string()),
+        None => (rest, "/".to_string()),
+    };
+    // Ports are separated by the last colon, unless it is inside of a bracketed IPv6 address
+    let (host, port) = match authority.rfind(':') {
+        Some(idx) if !authority[idx..].contains(']') =>
+            (&authority[..idx], to_crush_error(authority[idx + 1..].parse::<u16>())?),
+        _ => (authority, if tls { 443 } else { 80 }),
+    };
+    if host.is_empty() {
+        return argument_error(format!("Missing host in url {}", url).as_str());
+    }
+    Ok(Url { tls, host: host.trim_start_matches('[').trim_end_matches(']').to_string(), port, path })
+}
+
+/** The value the server must answer a handshake with the given key with. */
+pub fn accept_key(key: &str) -> CrushResult<String> {
+    let digest = to_crush_error(hash(MessageDigest::sha1(), format!("{}{}", key, GUID).as_bytes()))?;
+    Ok(openssl::base64::encode_block(&digest))
+}
+
+/** Encodes a frame as sent by a client, which must always mask the payload. */
+fn encode_frame(opcode: u8, payload: &[u8], mask: [u8; 4]) -> Vec<u8> {
+    let mut res = vec![0x80 | opcode];
+    match payload.len() {
+        len if len < 126 => res.push(0x80 | len as u8),
+        len if len < 65536 => {
+            res.push(0x80 | 126);
+            res.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+        len => {
+            res.push(0x80 | 127);
+            res.extend_from_slice(&(len as u64).to_be_bytes());
+        }
+    }
+    res.extend_from_slice(&mask);
+    res.extend(payload.iter().enumerate().map(|(idx, b)| b ^ mask[idx % 4]));
+    res
+}
+
+struct Frame {
+    fin: bool,
+    opcode: u8,
+    payload: Vec<u8>,
+}
+
+/** Removes a complete frame from the start of the buffer, if there is one. */
+fn decode_frame(buffer: &mut Vec<u8>) -> CrushResult<Option<Frame>> {
+    if buffer.len() < 2 {
+        return Ok(None);
+    }
+    let fin = buffer[0] & 0x80 != 0;
+    let opcode = buffer[0] & 0x0f;
+    let masked = buffer[1] & 0x80 != 0;
+    let (len, mut offset) = match buffer[1] & 0x7f {
+        126 if buffer.len() >= 4 => (u16::from_be_bytes([buffer[2], buffer[3]]) as u64, 4),
+        127 if buffer.len() >= 10 => {
+            let mut len = [0u8; 8];
+            len.copy_from_slice(&buffer[2..10]);
+            (u64::from_be_bytes(len), 10)
+        }
+        126 | 127 => return Ok(None),
+        len => (len as u64, 2),
+    };
+    if len > MAX_MESSAGE as u64 {
+        return data_error("WebSocket message too large");
+    }
+    let mask = if masked {
+        if buffer.len() < offset + 4 {
+            return Ok(None);
+        }
+        offset += 4;
+        Some([buffer[offset - 4], buffer[offset - 3], buffer[offset - 2], buffer[offset - 1]])
+    } else {
+        None
+    };
+    let end = offset + len as usize;
+    if buffer.len() < end {
+        return Ok(None);
+    }
+    let mut payload: Vec<u8> = buffer.drain(..end).skip(offset).collect();
+    if let Some(mask) = mask {
+        for (idx, b) in payload.iter_mut().enumerate() {
+            *b ^= mask[idx % 4];
+        }
+    }
+    Ok(Some(Frame { fin, opcode, payload }))
+}
+
+pub struct WebSocket {
+    stream: Mutex<Stream>,
+}
+
+/** The part of a connection that only the reading thread uses. */
+pub struct Receiver {
+    buffer: Vec<u8>,
+    /** The opcode and payload of a fragmented message received so far. */
+    fragments: Option<(u8, Vec<u8>)>,
+}
+
+impl WebSocket {
+    /**
+    Connects to a url and performs the opening handshake, sending the given extra headers.
+    Returns the socket, the part used to receive messages, and the subprotocol the server picked.
+    */
+    pub fn connect(url: &str, headers: &[(String, String)], timeout: Duration) -> CrushResult<(WebSocket, Receiver, Option<String>)> {
+        let url = parse_url(url)?;
+        let address = mandate(
+            to_crush_error((url.host.as_str(), url.port).to_socket_addrs())?.next(),
+            format!("Could not resolve {}", url.host).as_str())?;
+        let tcp = match TcpStream::connect_timeout(&address, timeout) {
+            Ok(tcp) => tcp,
+            Err(e) => return error(format!("Could not connect to {}: {}", url.host, e).as_str()),
+        };
+        to_crush_error(tcp.set_read_timeout(Some(timeout)))?;
+        to_crush_error(tcp.set_nodelay(true))?;
+        let mut stream = if url.tls {
+            let connector = to_crush_error(SslConnector::builder(SslMethod::tls()))?.build();
+            match connector.connect(&url.host, tcp) {
+                Ok(stream) => Stream::Tls(Box::new(stream)),
+                Err(e) => return error(format!("TLS handshake with {} failed: {}", url.host, e).as_str()),
+            }
+        } else {
+            Stream::Plain(tcp)
+        };
+
+        let key = openssl::base64::encode_block(&rand::random::<[u8; 16]>());
+        let default_port = if url.tls { 443 } else { 80 };
+        let host = if url.port == default_port { url.host.clone() } else { format!("{}:{}", url.host, url.port) };
+        let mut request = format!(
+            "GET {} HTTP/1.1\r\nHost: {}\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Key: {}\r\nSec-WebSocket-Version: 13\r\n",
+            url.path, host, key);
+        for (name, value) in headers {
+            request.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        request.push_str("\r\n");
+        to_crush_error(stream.write_all(request.as_bytes()))?;
+
+        let mut buffer = Vec::new();
+        let header_end = loop {
+            if let Some(idx) = buffer.windows(4).position(|w| w == b"\r\n\r\n") {
+                break idx + 4;
+            }
+            if buffer.len() > 64 * 1024 {
+                return data_error("WebSocket handshake response too large");
+            }
+            let mut chunk = [0u8; 4096];
+            match to_crush_error(stream.read(&mut chunk))? {
+                0 => return error("The server closed the connection during the WebSocket handshake"),
+                len => buffer.extend_from_slice(&chunk[..len]),
+            }
+        };
+        let response = String::from_utf8_lossy(&buffer[..header_end]).to_string();
+        buffer.drain(..header_end);
+        let mut lines = response.lines();
+        let status = lines.next().unwrap_or("");
+        if status.split_whitespace().nth(1) != Some("101") {
+            return error(format!("WebSocket handshake failed: {}", status).as_str());
+        }
+        let header = |name: &str| response.lines()
+            .skip(1)
+            .filter_map(|line| line.split_once(':'))
+            .find(|(k, _)| k.trim().eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.trim().to_string());
+        if header("Sec-WebSocket-Accept") != Some(accept_key(&key)?) {
+            return error("WebSocket handshake failed: the server sent the wrong accept key");
+        }
+        let protocol = header("Sec-WebSocket-Protocol");
+
+        // From now on, reads time out quickly so that senders don't have to wait for long
+        match &stream {
+            Stream::Plain(s) => to_crush_error(s.set_read_timeout(Some(Duration::from_millis(50))))?,
+            Stream::Tls(s) => to_crush_error(s.get_ref().set_read_timeout(Some(Duration::from_millis(50))))?,
+        }
+        Ok((
+            WebSocket { stream: Mutex::new(stream) },
+            Receiver { buffer, fragments: None },
+            protocol,
+        ))
+    }
+
+    fn send_frame(&self, opcode: u8, payload: &[u8]) -> CrushResult<()> {
+        let frame = encode_frame(opcode, payload, rand::random());
+        match self.stream.lock() {
+            Ok(mut stream) => to_crush_error(stream.write_all(&frame)),
+            Err(_) => error("The WebSocket connection is broken"),
+        }
+    }
+
+    pub fn send(&self, message: &Message) -> CrushResult<()> {
+        match message {
+            Message::Text(text) => self.send_frame(TEXT, text.as_bytes()),
+            Message::Binary(data) => self.send_frame(BINARY, data),
+        }
+    }
+
+    /** Starts the closing handshake. The receiver ends once the server has answered. */
+    pub fn close(&self) -> CrushResult<()> {
+        self.send_frame(CLOSE, &1000u16.to_be_bytes())
+    }
+
+    /**
+    Waits for the next message, answering pings and closes as they arrive. Returns None once the
+    connection has been closed.
+    */
+    pub fn receive(&self, receiver: &mut Receiver) -> CrushResult<Option<Message>> {
+        loop {
+            while let Some(frame) = decode_frame(&mut receiver.buffer)? {
+                match frame.opcode {
+                    PING => self.send_frame(PONG, &frame.payload)?,
+                    PONG => {}
+                    CLOSE => {
+                        // Echo the status code, if any, to complete the closing handshake
+                        let _ = self.send_frame(CLOSE, &frame.payload[..frame.payload.len().min(2)]);
+                        return Ok(None);
+                    }
+                    TEXT | BINARY if receiver.fragments.is_none() => {
+                        if frame.fin {
+                            return Ok(Some(message(frame.opcode, frame.payload)));
+                        }
+                        receiver.fragments = Some((frame.opcode, frame.payload));
+                    }
+                    CONTINUATION if receiver.fragments.is_some() => {
+                        if let Some((_, data)) = receiver.fragments.as_mut() {
+                            if data.len() + frame.payload.len() > MAX_MESSAGE {
+                                return data_error("WebSocket message too large");
+                            }
+                            data.extend_from_slice(&frame.payload);
+                        }
+                        if frame.fin {
+                            if let Some((opcode, data)) = receiver.fragments.take() {
+                                return Ok(Some(message(opcode, data)));
+                            }
+                        }
+                    }
+                    opcode => return data_error(format!("Unexpected WebSocket frame with opcode {}", opcode).as_str()),
+                }
+            }
+            let mut chunk = [0u8; 16 * 1024];
+            let res = match self.stream.lock() {
+                Ok(mut stream) => stream.read(&mut chunk),
+                Err(_) => return error("The WebSocket connection is broken"),
+            };
+            match res {
+                Ok(0) => return Ok(None),
+                Ok(len) => receiver.buffer.extend_from_slice(&chunk[..len]),
+                Err(e) if e.kind() == ErrorKind::WouldBlock || e.kind() == ErrorKind::TimedOut || e.kind() == ErrorKind::Interrupted => {
+                    // Give senders a chance to grab the connection
+                    std::thread::yield_now();
+                }
+                Err(e) => return to_crush_error(Err(e)),
+            }
+        }
+    }
+}
+
+fn message(opcode: u8, payload: Vec<u8>) -> Message {
+    match opcode {
+        TEXT => Message::Text(String::from_utf8_lossy(&payload).to_string()),
+        _ => Message::Binary(payload),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn handshake() {
+        assert_eq!(accept_key("dGhlIHNhbXBsZSBub25jZQ==").unwrap(), "s3pPLMBiTxaQ9kYGzzhZRbK+xOo=");
+        let url = parse_url("wss://example.com:8443/feed?x=1").unwrap();
+        assert!(url.tls);
+        assert_eq!((url.host.as_str(), url.port, url.path.as_str()), ("example.com", 8443, "/feed?x=1"));
+        let url = parse_url("ws://[::1]").unwrap();
+        assert_eq!((url.host.as_str(), url.port, url.path.as_str()), ("::1", 80, "/"));
+        assert!(parse_url("http://example.com").is_err());
+    }
+
+    #[test]
+    fn frames() {
+        for len in &[0usize, 5, 125, 126, 65535, 65536] {
+            let payload: Vec<u8> = (0..*len).map(|i| i as u8).collect();
+            let mut buffer = encode_frame(BINARY, &payload, [1, 2, 3, 4]);
+            buffer.extend_from_slice(&[0x81]);
+            let frame = decode_frame(&mut buffer).unwrap().unwrap();
+            assert!(frame.fin);
+            assert_eq!(frame.opcode, BINARY);
+            assert_eq!(frame.payload, payload);
+            assert_eq!(buffer, vec![0x81]);
+            assert!(decode_frame(&mut buffer).unwrap().is_none());
+        }
+        // The unmasked example from the RFC, split in the middle
+        let mut buffer = vec![0x01, 0x03, 0x48, 0x65];
+        assert!(decode_frame(&mut buffer).unwrap().is_none());
+        buffer.extend_from_slice(&[0x6c, 0x80, 0x02, 0x6c, 0x6f]);
+        let first = decode_frame(&mut buffer).unwrap().unwrap();
+        assert_eq!((first.fin, first.opcode, first.payload), (false, TEXT, b"Hel".to_vec()));
+        let second = decode_frame(&mut buffer).unwrap().unwrap();
+        assert_eq!((second.fin, second.opcode, second.payload), (true, CONTINUATION, b"lo".to_vec()));
+    }
+}
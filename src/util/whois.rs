@@ -0,0 +1,289 @@
+/**
+A minimal client for the WHOIS protocol, where a query is sent to port 43 of a server as a
+single line, and the server replies with free form text and closes the connection.
+
+Domain lookups start at IANA, which refers to the registry of the top level domain, which in
+turn often refers to the registrar of the domain. Since every registry formats its replies a
+little differently, the replies are interpreted as key: value lines with the keys used by the
+most common registries.
+*/
+use std::io::{Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, TimeZone, Utc};
+
+use crate::lang::errors::{CrushResult, error, to_crush_error};
+
+pub const IANA: &str = "whois.iana.org";
+pub const CYMRU: &str = "whois.cymru.com";
+
+const MAX_REFERRALS: usize = 3;
+
+pub fn query(server: &str, query: &str, timeout: Duration) -> CrushResult<String> {
+    let address = if server.contains(':') { server.to_string() } else { format!("{}:43", server) };
+    let mut stream = None;
+    for address in to_crush_error(address.to_socket_addrs())? {
+        if let Ok(s) = TcpStream::connect_timeout(&address, timeout) {
+            stream = Some(s);
+            break;
+        }
+    }
+    let mut stream = match stream {
+        Some(stream) => stream,
+        None => return error(format!("Could not connect to WHOIS server {}", server).as_str()),
+    };
+    to_crush_error(stream.set_read_timeout(Some(timeout)))?;
+    to_crush_error(stream.write_all(format!("{}\r\n", query).as_bytes()))?;
+    let mut res = Vec::new();
+    to_crush_error(stream.read_to_end(&mut res))?;
+    Ok(String::from_utf8_lossy(&res).to_string())
+}
+
+/** The key: value lines of a reply, with the keys lower cased. Comments and free text are skipped. */
+fn fields(text: &str) -> Vec<(String, &str)> {
+    text.lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.starts_with('%') && !line.starts_with('#') && !line.starts_with(">>>"))
+        .filter_map(|line| line.split_once(':'))
+        .map(|(key, value)| (key.trim().to_lowercase(), value.trim()))
+        .filter(|(key, value)| !key.is_empty() && !value.is_empty())
+        .collect()
+}
+
+/** The server that a reply refers to, and whether the reply has nothing but the referral. */
+fn referral(text: &str) -> Option<(String, bool)> {
+    fields(text).into_iter()
+        .find(|(key, _)| key == "refer" || key == "whois" || key == "registrar whois server")
+        .map(|(key, server)| {
+            let server = server.trim_start_matches("https://").trim_start_matches("http://").trim_end_matches('/');
+            (server.to_lowercase(), key != "registrar whois server")
+        })
+        .filter(|(server, _)| !server.is_empty())
+}
+
+/** Parses the many date formats used by registries. */
+pub fn parse_time(text: &str) -> Option<DateTime<Local>> {
+    let text = text.trim();
+    if let Ok(t) = DateTime::parse_from_rfc3339(text) {
+        return Some(t.with_timezone(&Local));
+    }
+    for format in &["%Y-%m-%dT%H:%M:%S%.f%z", "%Y-%m-%d %H:%M:%S%.f%z", "%Y-%m-%d %H:%M:%S %z"] {
+        if let Ok(t) = DateTime::parse_from_str(text, format) {
+            return Some(t.with_timezone(&Local));
+        }
+    }
+    let text = text.trim_end_matches('Z').trim_end_matches(" UTC").trim_end_matches(" GMT");
+    for format in &["%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%d %H:%M:%S%.f", "%Y.%m.%d %H:%M:%S", "%d.%m.%Y %H:%M:%S"] {
+        if let Ok(t) = NaiveDateTime::parse_from_str(text, format) {
+            return Some(Utc.from_utc_datetime(&t).with_timezone(&Local));
+        }
+    }
+    for format in &["%Y-%m-%d", "%d-%b-%Y", "%Y.%m.%d", "%d.%m.%Y", "%Y/%m/%d", "%Y%m%d"] {
+        if let Ok(d) = NaiveDate::parse_from_str(text, format) {
+            return Some(Utc.from_utc_datetime(&d.and_hms(0, 0, 0)).with_timezone(&Local));
+        }
+    }
+    None
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct Domain {
+    pub registrar: Option<String>,
+    pub created: Option<DateTime<Local>>,
+    pub updated: Option<DateTime<Local>>,
+    pub expires: Option<DateTime<Local>>,
+    pub nameservers: Vec<String>,
+    pub status: Vec<String>,
+    /** The server that gave the most specific answer. */
+    pub server: String,
+}
+
+const REGISTRAR: [&str; 4] = ["registrar", "sponsoring registrar", "registrar name", "registrar organization"];
+const CREATED: [&str; 6] = ["creation date", "created", "created on", "registered on", "registration time", "domain registration date"];
+const UPDATED: [&str; 5] = ["updated date", "last updated", "last-update", "last modified", "changed"];
+const EXPIRES: [&str; 7] = [
+    "registry expiry date", "registrar registration expiration date", "expiration date", "expiry date",
+    "expires", "paid-till", "expiration time",
+];
+const NAMESERVERS: [&str; 4] = ["name server", "nserver", "nameserver", "nameservers"];
+const STATUS: [&str; 3] = ["domain status", "status", "state"];
+
+impl Domain {
+    /** Adds the fields of a reply that aren't known yet. */
+    fn add(&mut self, text: &str) {
+        let mut nameservers = Vec::new();
+        let mut status = Vec::new();
+        for (key, value) in fields(text) {
+            let key = key.as_str();
+            if REGISTRAR.contains(&key) && self.registrar.is_none() {
+                self.registrar = Some(value.to_string());
+            } else if CREATED.contains(&key) && self.created.is_none() {
+                self.created = parse_time(value);
+            } else if UPDATED.contains(&key) && self.updated.is_none() {
+                self.updated = parse_time(value);
+            } else if EXPIRES.contains(&key) && self.expires.is_none() {
+                self.expires = parse_time(value);
+            } else if NAMESERVERS.contains(&key) {
+                // Some registries add the addresses of the nameserver after its name
+                if let Some(name) = value.split_whitespace().next() {
+                    let name = name.trim_end_matches('.').to_lowercase();
+                    if !nameservers.contains(&name) {
+                        nameservers.push(name);
+                    }
+                }
+            } else if STATUS.contains(&key) {
+                // EPP status codes are followed by a link explaining them
+                if let Some(code) = value.split_whitespace().next() {
+                    status.push(code.to_string());
+                }
+            }
+        }
+        if self.nameservers.is_empty() {
+            self.nameservers = nameservers;
+        }
+        if self.status.is_empty() {
+            self.status = status;
+        }
+    }
+}
+
+/**
+Looks up a domain, following referrals from the given server. Registry replies take precedence
+over registrar replies, since the registry is authoritative for the dates and nameservers.
+*/
+pub fn domain(name: &str, server: &str, timeout: Duration) -> CrushResult<Domain> {
+    let mut res = Domain::default();
+    let mut server = server.to_string();
+    let mut answered = false;
+    for _ in 0..MAX_REFERRALS {
+        let text = match query(&server, name, timeout) {
+            Ok(text) => text,
+            // A registrar that is down is not fatal if the registry already answered
+            Err(_) if answered => break,
+            Err(e) => return Err(e),
+        };
+        let next = referral(&text);
+        match next {
+            Some((_, true)) => {}
+            _ => {
+                res.add(&text);
+                res.server = server.clone();
+                answered = true;
+            }
+        }
+        match next {
+            Some((next, _)) if next != server => server = next,
+            _ => break,
+        }
+    }
+    if !answered || (res.registrar.is_none() && res.created.is_none() && res.nameservers.is_empty()) {
+        return error(format!("No WHOIS information found for {}", name).as_str());
+    }
+    Ok(res)
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Origin {
+    pub address: String,
+    pub asn: Option<i128>,
+    pub prefix: Option<String>,
+    pub country: Option<String>,
+    pub org: Option<String>,
+}
+
+fn known(field: Option<&str>) -> Option<String> {
+    match field.map(|f| f.trim()) {
+        None | Some("") | Some("NA") => None,
+        Some(f) => Some(f.to_string()),
+    }
+}
+
+/** Parses a bulk reply from the IP to ASN service of Team Cymru. */
+pub fn parse_origins(text: &str) -> Vec<Origin> {
+    text.lines()
+        .filter(|line| !line.starts_with("Bulk mode") && !line.starts_with("Error"))
+        .map(|line| line.split('|').collect::<Vec<_>>())
+        .filter(|fields| fields.len() >= 7)
+        .map(|fields| Origin {
+            address: fields[1].trim().to_string(),
+            asn: fields[0].trim().parse::<i128>().ok(),
+            prefix: known(Some(fields[2])),
+            country: known(Some(fields[3])),
+            org: known(Some(fields[6])),
+        })
+        .collect()
+}
+
+/** Looks up the network, country and owner of addresses, in a single query. */
+pub fn origins(addresses: &[String], server: &str, timeout: Duration) -> CrushResult<Vec<Origin>> {
+    let mut request = "begin\nverbose".to_string();
+    for address in addresses {
+        request.push('\n');
+        request.push_str(address);
+    }
+    request.push_str("\nend");
+    Ok(parse_origins(&query(server, &request, timeout)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_registry_reply() {
+        let mut domain = Domain::default();
+        domain.add("   Domain Name: EXAMPLE.COM
+   Registrar WHOIS Server: whois.iana.org
+   Updated Date: 2024-08-14T07:01:34Z
+   Creation Date: 1995-08-14T04:00:00Z
+   Registry Expiry Date: 2025-08-13T04:00:00Z
+   Registrar: RESERVED-Internet Assigned Numbers Authority
+   Domain Status: clientDeleteProhibited https://icann.org/epp#clientDeleteProhibited
+   Name Server: A.IANA-SERVERS.NET
+   Name Server: B.IANA-SERVERS.NET
+>>> Last update of whois database: 2024-09-01T00:00:00Z <<<
+");
+        assert_eq!(domain.registrar.as_deref(), Some("RESERVED-Internet Assigned Numbers Authority"));
+        assert_eq!(domain.created, Some(Local.timestamp(808372800, 0)));
+        assert_eq!(domain.expires, Some(Local.timestamp(1755057600, 0)));
+        assert_eq!(domain.nameservers, vec!["a.iana-servers.net", "b.iana-servers.net"]);
+        assert_eq!(domain.status, vec!["clientDeleteProhibited"]);
+    }
+
+    #[test]
+    fn referrals() {
+        assert_eq!(referral("% IANA WHOIS server\nrefer:        whois.verisign-grs.com\n"), Some(("whois.verisign-grs.com".to_string(), true)));
+        assert_eq!(referral("Registrar WHOIS Server: http://whois.markmonitor.com/\n"), Some(("whois.markmonitor.com".to_string(), false)));
+        assert_eq!(referral("Registrar WHOIS Server: \n"), None);
+    }
+
+    #[test]
+    fn parse_times() {
+        let t = Local.timestamp(808372800, 0);
+        assert_eq!(parse_time("1995-08-14T04:00:00Z"), Some(t));
+        assert_eq!(parse_time("1995-08-14T04:00:00.000+00:00"), Some(t));
+        assert_eq!(parse_time("1995-08-14T06:00:00+0200"), Some(t));
+        assert_eq!(parse_time("1995-08-14 04:00:00"), Some(t));
+        assert_eq!(parse_time("14-Aug-1995"), Some(Local.timestamp(808358400, 0)));
+        assert_eq!(parse_time("tomorrow"), None);
+    }
+
+    #[test]
+    fn parse_bulk_reply() {
+        let origins = parse_origins("Bulk mode; whois.cymru.com [2024-09-01 00:00:00 +0000]
+13335   | 1.1.1.1          | 1.1.1.0/24          | AU | apnic    | 2011-08-11 | CLOUDFLARENET, US
+NA      | 10.0.0.1         | NA                  |    | other    |            | NA
+");
+        assert_eq!(origins, vec![
+            Origin {
+                address: "1.1.1.1".to_string(),
+                asn: Some(13335),
+                prefix: Some("1.1.1.0/24".to_string()),
+                country: Some("AU".to_string()),
+                org: Some("CLOUDFLARENET, US".to_string()),
+            },
+            Origin { address: "10.0.0.1".to_string(), asn: None, prefix: None, country: None, org: None },
+        ]);
+    }
+}
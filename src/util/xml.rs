@@ -0,0 +1,216 @@
+/**
+A small streaming XML reader. It reads one element or piece of text at a time from any
+BufRead, so memory use doesn't depend on the size of the document. Declarations, comments,
+processing instructions and doctypes are skipped, and DTDs are not used for anything, so
+only the predefined and numeric entities are understood.
+*/
+use std::io::BufRead;
+
+use crate::lang::errors::{CrushResult, data_error, to_crush_error};
+
+#[derive(Debug, PartialEq)]
+pub enum Event {
+    Start(String, Vec<(String, String)>),
+    End(String),
+    Text(String),
+}
+
+pub struct Reader<R: BufRead> {
+    reader: R,
+    buffer: Vec<u8>,
+    /** True if the '<' starting the next tag has already been read. */
+    in_tag: bool,
+    /** Empty elements like <a/> are reported as a start and an end event. */
+    pending_end: Option<String>,
+}
+
+pub fn unescape(text: &str) -> String {
+    let mut res = String::new();
+    let mut rest = text;
+    while let Some(idx) = rest.find('&') {
+        res.push_str(&rest[..idx]);
+        rest = &rest[idx..];
+        let end = match rest.find(';') {
+            Some(end) => end,
+            None => break,
+        };
+        let decoded = match &rest[1..end] {
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "amp" => Some('&'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            e if e.starts_with("#x") => u32::from_str_radix(&e[2..], 16).ok().and_then(std::char::from_u32),
+            e if e.starts_with('#') => e[1..].parse().ok().and_then(std::char::from_u32),
+            _ => None,
+        };
+        match decoded {
+            Some(c) => {
+                res.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                res.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    res.push_str(rest);
+    res
+}
+
+/** The name of an element or attribute without its namespace prefix. */
+pub fn local_name(name: &str) -> &str {
+    name.rsplit(':').next().unwrap_or(name)
+}
+
+fn attributes(mut text: &str) -> Vec<(String, String)> {
+    let mut res = Vec::new();
+    while let Some(eq) = text.find('=') {
+        let name = text[..eq].trim();
+        let rest = text[eq + 1..].trim_start();
+        let quote = match rest.chars().next() {
+            Some(q) if q == '"' || q == '\'' => q,
+            _ => break,
+        };
+        let end = match rest[1..].find(quote) {
+            Some(end) => end + 1,
+            None => break,
+        };
+        res.push((name.to_string(), unescape(&rest[1..end])));
+        text = &rest[end + 1..];
+    }
+    res
+}
+
+/** True if the tag read so far ends inside of a quoted attribute value. */
+fn in_quotes(tag: &[u8]) -> bool {
+    let mut quote = None;
+    for c in tag {
+        match (quote, *c) {
+            (None, b'"') | (None, b'\'') => quote = Some(*c),
+            (Some(q), c) if q == c => quote = None,
+            _ => {}
+        }
+    }
+    quote.is_some()
+}
+
+impl<R: BufRead> Reader<R> {
+    pub fn new(reader: R) -> Reader<R> {
+        Reader { reader, buffer: Vec::new(), in_tag: false, pending_end: None }
+    }
+
+    /** Keep reading until the tag in the buffer ends with the specified terminator. */
+    fn read_until(&mut self, terminator: &[u8]) -> CrushResult<()> {
+        while !self.buffer.ends_with(terminator) {
+            if to_crush_error(self.reader.read_until(b'>', &mut self.buffer))? == 0 {
+                return data_error("Unexpected end of XML document inside of a tag");
+            }
+        }
+        Ok(())
+    }
+
+    pub fn next(&mut self) -> CrushResult<Option<Event>> {
+        loop {
+            if let Some(name) = self.pending_end.take() {
+                return Ok(Some(Event::End(name)));
+            }
+            if !self.in_tag {
+                self.buffer.clear();
+                if to_crush_error(self.reader.read_until(b'<', &mut self.buffer))? == 0 {
+                    return Ok(None);
+                }
+                self.in_tag = self.buffer.last() == Some(&b'<');
+                if self.in_tag {
+                    self.buffer.pop();
+                }
+                let text = String::from_utf8_lossy(&self.buffer);
+                if !text.trim().is_empty() {
+                    return Ok(Some(Event::Text(unescape(&text))));
+                }
+                if !self.in_tag {
+                    return Ok(None);
+                }
+            }
+
+            self.in_tag = false;
+            self.buffer.clear();
+            self.read_until(b">")?;
+            if self.buffer.starts_with(b"!--") {
+                self.read_until(b"-->")?;
+                continue;
+            }
+            if self.buffer.starts_with(b"![CDATA[") {
+                self.read_until(b"]]>")?;
+                let len = self.buffer.len();
+                return Ok(Some(Event::Text(String::from_utf8_lossy(&self.buffer[8..len - 3]).to_string())));
+            }
+            if self.buffer.starts_with(b"?") {
+                self.read_until(b"?>")?;
+                continue;
+            }
+            if self.buffer.starts_with(b"!") {
+                // Doctypes may contain an internal subset in brackets, with tags of its own
+                if self.buffer.contains(&b'[') {
+                    self.read_until(b"]>")?;
+                }
+                continue;
+            }
+            // A '>' inside of an attribute value doesn't end the tag
+            while in_quotes(&self.buffer) {
+                if to_crush_error(self.reader.read_until(b'>', &mut self.buffer))? == 0 {
+                    return data_error("Unexpected end of XML document inside of a tag");
+                }
+            }
+
+            let tag = String::from_utf8_lossy(&self.buffer[..self.buffer.len() - 1]).to_string();
+            if let Some(name) = tag.strip_prefix('/') {
+                return Ok(Some(Event::End(name.trim().to_string())));
+            }
+            let (tag, empty) = match tag.strip_suffix('/') {
+                Some(tag) => (tag, true),
+                None => (tag.as_str(), false),
+            };
+            let name_end = tag.find(char::is_whitespace).unwrap_or(tag.len());
+            let name = tag[..name_end].to_string();
+            if empty {
+                self.pending_end = Some(name.clone());
+            }
+            return Ok(Some(Event::Start(name, attributes(&tag[name_end..]))));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn events(data: &str) -> Vec<Event> {
+        let mut reader = Reader::new(data.as_bytes());
+        let mut res = Vec::new();
+        while let Some(event) = reader.next().unwrap() {
+            res.push(event);
+        }
+        res
+    }
+
+    #[test]
+    fn test_events() {
+        assert_eq!(
+            events("<?xml version=\"1.0\"?><!-- a > b --><a x='1 > 2' y=\"&lt;\">t &amp; u<b/><![CDATA[<c>]]></a>"),
+            vec![
+                Event::Start("a".to_string(), vec![("x".to_string(), "1 > 2".to_string()), ("y".to_string(), "<".to_string())]),
+                Event::Text("t & u".to_string()),
+                Event::Start("b".to_string(), vec![]),
+                Event::End("b".to_string()),
+                Event::Text("<c>".to_string()),
+                Event::End("a".to_string()),
+            ]);
+    }
+
+    #[test]
+    fn test_unescape() {
+        assert_eq!(unescape("&#65;&#x42;&unknown; &"), "AB&unknown; &");
+    }
+}
@@ -0,0 +1,305 @@
+/**
+A parser for the subset of YAML that configuration files like kubeconfig are written in:
+block mappings and sequences, plain and quoted scalars, and empty or single line flow
+collections. Anchors, tags, multi line scalars and multiple documents are not supported.
+
+The result is a serde_json value, so that it can be used the same way as parsed JSON.
+*/
+use serde_json::{Map, Value};
+
+use crate::lang::errors::{CrushResult, data_error};
+
+struct Line {
+    number: usize,
+    indent: usize,
+    text: String,
+}
+
+/** Removes a trailing comment, which starts with a # after whitespace, outside of quotes. */
+fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    let mut previous = ' ';
+    for (idx, c) in line.char_indices() {
+        match (quote, c) {
+            (None, '"') | (None, '\'') => quote = Some(c),
+            (Some(q), c) if c == q => quote = None,
+            (None, '#') if previous == ' ' || previous == '\t' => return &line[..idx],
+            _ => {}
+        }
+        previous = c;
+    }
+    line
+}
+
+fn lines(text: &str) -> Vec<Line> {
+    text.lines()
+        .enumerate()
+        .filter_map(|(idx, line)| {
+            let line = strip_comment(line).trim_end();
+            let content = line.trim_start();
+            if content.is_empty() || content == "---" || content == "..." {
+                return None;
+            }
+            Some(Line { number: idx + 1, indent: line.len() - content.len(), text: content.to_string() })
+        })
+        .collect()
+}
+
+fn error<T>(line: &Line, message: &str) -> CrushResult<T> {
+    data_error(format!("Invalid YAML on line {}: {}", line.number, message).as_str())
+}
+
+fn unquote_double(text: &str) -> String {
+    let mut res = String::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            res.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => res.push('\n'),
+            Some('t') => res.push('\t'),
+            Some('r') => res.push('\r'),
+            Some('0') => res.push('\0'),
+            Some(c) => res.push(c),
+            None => {}
+        }
+    }
+    res
+}
+
+fn split_flow(text: &str) -> Vec<&str> {
+    let mut res = Vec::new();
+    let mut quote = None;
+    let mut start = 0;
+    for (idx, c) in text.char_indices() {
+        match (quote, c) {
+            (None, '"') | (None, '\'') => quote = Some(c),
+            (Some(q), c) if c == q => quote = None,
+            (None, ',') => {
+                res.push(text[start..idx].trim());
+                start = idx + 1;
+            }
+            _ => {}
+        }
+    }
+    res.push(text[start..].trim());
+    res.into_iter().filter(|s| !s.is_empty()).collect()
+}
+
+fn scalar(text: &str, line: &Line) -> CrushResult<Value> {
+    let text = text.trim();
+    if text.len() >= 2 && text.starts_with('"') && text.ends_with('"') {
+        return Ok(Value::String(unquote_double(&text[1..text.len() - 1])));
+    }
+    if text.len() >= 2 && text.starts_with('\'') && text.ends_with('\'') {
+        return Ok(Value::String(text[1..text.len() - 1].replace("''", "'")));
+    }
+    if text.starts_with('[') && text.ends_with(']') {
+        return Ok(Value::Array(
+            split_flow(&text[1..text.len() - 1]).into_iter()
+                .map(|item| scalar(item, line))
+                .collect::<CrushResult<Vec<_>>>()?));
+    }
+    if text.starts_with('{') && text.ends_with('}') {
+        let mut res = Map::new();
+        for item in split_flow(&text[1..text.len() - 1]) {
+            match split_key(item) {
+                Some((key, value)) => res.insert(key, scalar(value, line)?),
+                None => return error(line, "expected key: value in flow mapping"),
+            };
+        }
+        return Ok(Value::Object(res));
+    }
+    if text.starts_with('|') || text.starts_with('>') || text.starts_with('&') || text.starts_with('*') || text.starts_with('!') {
+        return error(line, "block scalars, anchors and tags are not supported");
+    }
+    Ok(match text {
+        "" | "~" | "null" | "Null" | "NULL" => Value::Null,
+        "true" | "True" | "TRUE" => Value::Bool(true),
+        "false" | "False" | "FALSE" => Value::Bool(false),
+        _ => match (text.parse::<i64>(), text.parse::<f64>()) {
+            (Ok(i), _) => Value::from(i),
+            (_, Ok(f)) if f.is_finite() => Value::from(f),
+            _ => Value::String(text.to_string()),
+        },
+    })
+}
+
+/** Splits a mapping entry into its key and the rest of the line, if the line is one. */
+fn split_key(text: &str) -> Option<(String, &str)> {
+    let (key, rest) = if text.starts_with('"') || text.starts_with('\'') {
+        let quote = text.chars().next()?;
+        let end = text[1..].find(quote)? + 1;
+        let rest = text[end + 1..].trim_start().strip_prefix(':')?;
+        (text[1..end].to_string(), rest)
+    } else {
+        let idx = text.match_indices(':').map(|(idx, _)| idx)
+            .find(|idx| text[idx + 1..].is_empty() || text[idx + 1..].starts_with(' '))?;
+        (text[..idx].trim().to_string(), &text[idx + 1..])
+    };
+    if !rest.is_empty() && !rest.starts_with(' ') {
+        return None;
+    }
+    Some((key, rest.trim()))
+}
+
+struct Parser {
+    lines: Vec<Line>,
+    idx: usize,
+}
+
+impl Parser {
+    fn block(&mut self, indent: usize) -> CrushResult<Value> {
+        let line = &self.lines[self.idx];
+        if line.text == "-" || line.text.starts_with("- ") {
+            self.sequence(indent)
+        } else {
+            self.mapping(indent)
+        }
+    }
+
+    /** The value of an entry whose value is on the following lines, if any. */
+    fn nested(&mut self, indent: usize, sequence_allowed: bool) -> CrushResult<Value> {
+        match self.lines.get(self.idx) {
+            Some(next) if next.indent > indent => {
+                let indent = next.indent;
+                self.block(indent)
+            }
+            // Sequences are commonly not indented relative to the key they are the value of
+            Some(next) if sequence_allowed && next.indent == indent && (next.text == "-" || next.text.starts_with("- ")) =>
+                self.sequence(indent),
+            _ => Ok(Value::Null),
+        }
+    }
+
+    fn sequence(&mut self, indent: usize) -> CrushResult<Value> {
+        let mut res = Vec::new();
+        while self.idx < self.lines.len() && self.lines[self.idx].indent == indent {
+            let line = &mut self.lines[self.idx];
+            let rest = match line.text.strip_prefix('-') {
+                Some(rest) if rest.is_empty() || rest.starts_with(' ') => rest.trim_start().to_string(),
+                _ => break,
+            };
+            if rest.is_empty() {
+                self.idx += 1;
+                res.push(self.nested(indent, false)?);
+            } else if split_key(&rest).is_some() || rest.starts_with("- ") {
+                // The item is a block that starts on the same line as the dash
+                line.indent += line.text.len() - rest.len();
+                line.text = rest;
+                let indent = line.indent;
+                res.push(self.block(indent)?);
+            } else {
+                let value = scalar(&rest, line)?;
+                self.idx += 1;
+                res.push(value);
+            }
+        }
+        Ok(Value::Array(res))
+    }
+
+    fn mapping(&mut self, indent: usize) -> CrushResult<Value> {
+        let mut res = Map::new();
+        while self.idx < self.lines.len() && self.lines[self.idx].indent == indent {
+            let line = &self.lines[self.idx];
+            if line.text.starts_with("- ") {
+                break;
+            }
+            let (key, rest) = match split_key(&line.text) {
+                Some((key, rest)) => (key, rest.to_string()),
+                None => return error(line, "expected key: value"),
+            };
+            self.idx += 1;
+            let value = if rest.is_empty() {
+                self.nested(indent, true)?
+            } else {
+                scalar(&rest, &self.lines[self.idx - 1])?
+            };
+            res.insert(key, value);
+        }
+        if let Some(line) = self.lines.get(self.idx) {
+            if line.indent > indent {
+                return error(line, "unexpected indentation");
+            }
+        }
+        Ok(Value::Object(res))
+    }
+}
+
+pub fn parse(text: &str) -> CrushResult<Value> {
+    let mut parser = Parser { lines: lines(text), idx: 0 };
+    if parser.lines.is_empty() {
+        return Ok(Value::Null);
+    }
+    let indent = parser.lines[0].indent;
+    let res = parser.block(indent)?;
+    match parser.lines.get(parser.idx) {
+        Some(line) => error(line, "unexpected indentation"),
+        None => Ok(res),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn kubeconfig() {
+        let text = r#"apiVersion: v1
+clusters:
+- cluster:
+    certificate-authority-data: LS0tLS1C   # base64
+    server: https://127.0.0.1:6443
+  name: kind
+contexts:
+- context:
+    cluster: kind
+    user: "kind-admin"
+  name: kind
+current-context: kind
+preferences: {}
+users:
+- name: kind-admin
+  user:
+    exec:
+      args: [eks, get-token, '--cluster-name', "a, b"]
+      command: aws
+      env:
+      -   name: 'AWS_PROFILE'
+          value: prod
+    insecure: false
+    port: 8080
+"#;
+        assert_eq!(parse(text).unwrap(), json!({
+            "apiVersion": "v1",
+            "clusters": [{"cluster": {"certificate-authority-data": "LS0tLS1C", "server": "https://127.0.0.1:6443"}, "name": "kind"}],
+            "contexts": [{"context": {"cluster": "kind", "user": "kind-admin"}, "name": "kind"}],
+            "current-context": "kind",
+            "preferences": {},
+            "users": [{"name": "kind-admin", "user": {
+                "exec": {
+                    "args": ["eks", "get-token", "--cluster-name", "a, b"],
+                    "command": "aws",
+                    "env": [{"name": "AWS_PROFILE", "value": "prod"}],
+                },
+                "insecure": false,
+                "port": 8080,
+            }}],
+        }));
+    }
+
+    #[test]
+    fn nested_sequences() {
+        assert_eq!(parse("- - a\n  - b\n- c\n-\n  d: 1\n").unwrap(), json!([["a", "b"], "c", {"d": 1}]));
+    }
+
+    #[test]
+    fn errors() {
+        assert!(parse("a: 1\n  b: 2\n").is_err());
+        assert!(parse("a: |\n  text\n").is_err());
+        assert!(parse("just text\n").is_err());
+    }
+}